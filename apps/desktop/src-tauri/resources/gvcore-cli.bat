@@ -2,6 +2,16 @@
 REM Mock gvcore-cli for testing the Tauri integration on Windows
 REM Simulates progress output in the format: [stage_name] percent%% - message
 
+if "%~1"=="--version" (
+    echo gvcore-cli 0.0.0-mock
+    exit /b 0
+)
+
+if "%~1"=="capabilities" (
+    echo {"version":"0.0.0-mock","flags":["--input","--output","--brush-path","--colmap-path","--tone-map","--equirect-split","--masks","--images"],"presets":["fast","balanced","quality"]}
+    exit /b 0
+)
+
 echo [frame_extraction] 0%% - Starting frame extraction...
 timeout /t 1 /nobreak >nul
 
@@ -0,0 +1,317 @@
+//! Action Registry
+//!
+//! Describes the backend's commands for the frontend's command palette. The list
+//! comes from the macro in lib.rs that also builds the invoke handler, so a new
+//! command appears here without further changes; the tables below only refine
+//! titles, categories and when an action is available. Dispatched actions run
+//! through the regular invoke handler, exactly as if the webview had invoked them.
+
+use crate::error::AppError;
+use crate::jobs;
+use crate::share::ShareState;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::OnceLock;
+use tauri::ipc::{InvokeBody, InvokeResponse, InvokeResponseBody};
+use tauri::webview::InvokeRequest;
+use tauri::{Manager, State, Webview};
+use tokio::sync::oneshot;
+
+/// Commands that are not actions in their own right
+const HIDDEN: &[&str] = &[
+    "get_available_actions",
+    "dispatch_action",
+    "subscribe_events",
+    "unsubscribe_events",
+];
+
+/// Commands that act on the production open in the frontend
+const NEEDS_PRODUCTION: &[&str] = &[
+    "move_production",
+    "preview_delete_production",
+    "delete_production",
+    "archive_production_intermediates",
+    "restore_production_intermediates",
+    "set_production_tags",
+    "set_production_notes",
+    "set_production_pinned",
+    "get_production_defaults",
+    "clear_production_defaults",
+    "save_camera_bookmark",
+    "list_camera_bookmarks",
+    "delete_camera_bookmark",
+    "start_share_server",
+    "verify_artifact",
+    "open_with_external_viewer",
+    "render_splat_preview",
+];
+
+static REGISTERED: OnceLock<Vec<Registered>> = OnceLock::new();
+
+/// A command as registered with the invoke handler
+#[derive(Debug, Clone, Copy)]
+pub struct Registered {
+    pub module: &'static str,
+    pub name: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Action {
+    /// The command name, which dispatch_action takes
+    pub id: String,
+    pub title: String,
+    pub category: String,
+    pub needs_production: bool,
+    pub enabled: bool,
+}
+
+/// What decides whether an action can run right now
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AppState {
+    pub job_running: bool,
+    pub sharing: bool,
+}
+
+/// Record the registered commands; called once while the app is built
+pub fn register(commands: Vec<Registered>) {
+    REGISTERED.set(commands).ok();
+}
+
+/// Actions the backend offers, with whether each can run right now
+#[tauri::command]
+pub async fn get_available_actions(share: State<'_, ShareState>) -> Result<Vec<Action>, AppError> {
+    let state = AppState {
+        job_running: jobs::any_active(),
+        sharing: share.status().is_some(),
+    };
+    Ok(actions(registered(), &state))
+}
+
+/// Run an action by id. The payload holds the command's arguments. Errors
+/// always arrive as `{ code, message }`, also from commands that fail with a
+/// plain string.
+#[tauri::command]
+pub async fn dispatch_action(
+    webview: Webview,
+    share: State<'_, ShareState>,
+    id: String,
+    payload: Option<Value>,
+) -> Result<Value, Value> {
+    let state = AppState {
+        job_running: jobs::any_active(),
+        sharing: share.status().is_some(),
+    };
+    let action = actions(registered(), &state)
+        .into_iter()
+        .find(|action| action.id == id)
+        .ok_or_else(|| error_value(AppError::NotFound(format!("Action {}", id))))?;
+    if !action.enabled {
+        return Err(error_value(AppError::InvalidInput(format!(
+            "{} is not available right now",
+            action.title
+        ))));
+    }
+
+    let url = webview
+        .url()
+        .map_err(|e| error_value(AppError::Io(e.to_string())))?;
+    let request = InvokeRequest {
+        cmd: id,
+        callback: tauri::ipc::CallbackFn(0),
+        error: tauri::ipc::CallbackFn(1),
+        url,
+        body: InvokeBody::Json(payload.unwrap_or_else(|| Value::Object(Default::default()))),
+        headers: Default::default(),
+        invoke_key: webview.app_handle().invoke_key().to_string(),
+    };
+    let (tx, rx) = oneshot::channel();
+    webview.clone().on_message(
+        request,
+        Box::new(move |_webview, _cmd, response, _callback, _error| {
+            tx.send(response).ok();
+        }),
+    );
+
+    match rx.await {
+        Ok(InvokeResponse::Ok(InvokeResponseBody::Json(json))) => {
+            serde_json::from_str(&json).map_err(|e| error_value(AppError::Io(e.to_string())))
+        }
+        Ok(InvokeResponse::Ok(InvokeResponseBody::Raw(bytes))) => Ok(Value::from(bytes)),
+        Ok(InvokeResponse::Err(error)) => Err(normalize_error(error.0)),
+        Err(_) => Err(error_value(AppError::Io(
+            "Action finished without a response".to_string(),
+        ))),
+    }
+}
+
+fn registered() -> &'static [Registered] {
+    REGISTERED.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+pub fn actions(registered: &[Registered], state: &AppState) -> Vec<Action> {
+    registered
+        .iter()
+        .filter(|command| !HIDDEN.contains(&command.name))
+        .map(|command| Action {
+            id: command.name.to_string(),
+            title: title(command.name),
+            category: category(command).to_string(),
+            needs_production: NEEDS_PRODUCTION.contains(&command.name),
+            enabled: enabled(command.name, state),
+        })
+        .collect()
+}
+
+fn title(name: &str) -> String {
+    match name {
+        "cancel_processing" => "Cancel Job".to_string(),
+        "resume_processing" => "Resume Job".to_string(),
+        "get_cli_path" => "Show CLI Path".to_string(),
+        "start_share_server" => "Share on LAN".to_string(),
+        "stop_share_server" => "Stop Sharing".to_string(),
+        "open_with_external_viewer" => "Open With External Viewer".to_string(),
+        "undo_last_operation" => "Undo".to_string(),
+        _ => name
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" "),
+    }
+}
+
+fn category(command: &Registered) -> &'static str {
+    match (command.module, command.name) {
+        ("commands", "process_videos" | "cancel_processing")
+        | (
+            "disk" | "frames_cache" | "cache" | "progress_indicator" | "volume_watch"
+            | "operations",
+            _,
+        ) => "Processing",
+        ("commands", name) if name.starts_with("pick_") => "Files",
+        ("written_by", _) => "Files",
+        ("commands" | "capabilities" | "presets" | "undo", _) => "General",
+        ("media" | "overlap" | "sync" | "masks" | "prefetch", _) => "Media",
+        (
+            "history" | "history_export" | "history_sweep" | "hooks" | "queue" | "training_metrics"
+            | "checkpoints" | "cancel_impact" | "notifications",
+            _,
+        ) => "Jobs",
+        (
+            "productions" | "archive" | "downsample" | "duplicates" | "recents" | "reconcile"
+            | "preferences" | "library" | "import" | "opening" | "camera_bookmarks" | "retention",
+            _,
+        ) => "Productions",
+        ("integrity" | "hashing" | "viewers" | "splat_preview" | "comparison", _) => "Artifacts",
+        ("pending_tasks" | "web_export", _) => "Artifacts",
+        ("share", _) => "Sharing",
+        (
+            "setup" | "secrets" | "network" | "datafile" | "spawn_diagnosis" | "cli_location"
+            | "performance" | "health" | "app_data" | "scheduler" | "safe_mode"
+            | "settings_profiles",
+            _,
+        ) => "Settings",
+        _ => "Other",
+    }
+}
+
+fn enabled(name: &str, state: &AppState) -> bool {
+    match name {
+        "cancel_processing" | "resume_processing" => state.job_running,
+        "switch_profile" => !state.job_running,
+        "start_share_server" => !state.sharing,
+        "stop_share_server" => state.sharing,
+        _ => true,
+    }
+}
+
+fn error_value(error: AppError) -> Value {
+    serde_json::to_value(error).unwrap_or_default()
+}
+
+/// Give every command error the `{ code, message }` shape of AppError
+fn normalize_error(error: Value) -> Value {
+    match error {
+        Value::Object(ref map) if map.contains_key("code") && map.contains_key("message") => error,
+        Value::String(message) => error_value(AppError::Io(message)),
+        other => error_value(AppError::Io(other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> Vec<Registered> {
+        [
+            ("commands", "process_videos"),
+            ("commands", "cancel_processing"),
+            ("commands", "pick_videos"),
+            ("share", "start_share_server"),
+            ("share", "stop_share_server"),
+            ("recents", "set_production_tags"),
+            ("actions", "dispatch_action"),
+            ("future", "export_turntable_video"),
+        ]
+        .into_iter()
+        .map(|(module, name)| Registered { module, name })
+        .collect()
+    }
+
+    #[test]
+    fn new_commands_appear_with_derived_details() {
+        let actions = actions(&registry(), &AppState::default());
+        assert!(actions.iter().all(|a| a.id != "dispatch_action"));
+
+        let export = actions.last().unwrap();
+        assert_eq!(export.id, "export_turntable_video");
+        assert_eq!(export.title, "Export Turntable Video");
+        assert_eq!(export.category, "Other");
+        assert!(export.enabled);
+
+        let tags = actions
+            .iter()
+            .find(|a| a.id == "set_production_tags")
+            .unwrap();
+        assert_eq!(tags.category, "Productions");
+        assert!(tags.needs_production);
+        assert_eq!(actions[2].category, "Files");
+    }
+
+    #[test]
+    fn availability_follows_app_state() {
+        let disabled = |state: AppState| -> Vec<String> {
+            actions(&registry(), &state)
+                .into_iter()
+                .filter(|a| !a.enabled)
+                .map(|a| a.title)
+                .collect()
+        };
+        assert_eq!(
+            disabled(AppState::default()),
+            ["Cancel Job", "Stop Sharing"]
+        );
+        assert_eq!(
+            disabled(AppState {
+                job_running: true,
+                sharing: true
+            }),
+            ["Share on LAN"]
+        );
+    }
+
+    #[test]
+    fn errors_are_normalized_to_code_and_message() {
+        let plain = normalize_error(Value::from("ffmpeg not found"));
+        assert_eq!(plain["code"], "io");
+        assert_eq!(plain["message"], "ffmpeg not found");
+
+        let typed = error_value(AppError::Cancelled);
+        assert_eq!(normalize_error(typed.clone()), typed);
+    }
+}
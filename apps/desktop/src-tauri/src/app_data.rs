@@ -0,0 +1,530 @@
+//! App Data Location
+//!
+//! The backend keeps its data where Tauri puts app data, unless that directory
+//! has been relocated, e.g. because a locked-down machine makes it read-only.
+//! A relocation is remembered in a small pointer file kept outside app data,
+//! in the first writable of the config, local data and cache directories that
+//! differ from it, and read back before anything else at startup.
+//!
+//! The directory is resolved once at startup and kept as managed state: the
+//! relocation if there is one, then Tauri's directory, then GAMEVIEW_DATA_DIR,
+//! then a portable `data` directory next to the executable, whichever can be
+//! written to first. When none can, the app still starts, and
+//! get_backend_health reports it so the frontend shows its recovery screen.
+
+use crate::error::AppError;
+use crate::fsutil;
+use crate::inspection;
+use crate::path_policy::PathPolicy;
+use crate::setup;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{Manager, Runtime, State};
+
+const POINTER_FILE: &str = "app-data-location.json";
+
+/// Matches the bundle identifier, which names Tauri's directories for the app
+const IDENTIFIER: &str = "ai.gameview.desktop";
+
+/// Names a directory to use when Tauri's cannot be written to
+pub const ENV_VAR: &str = "GAMEVIEW_DATA_DIR";
+
+/// Directory next to the executable used when nothing else is writable
+const PORTABLE_DIR: &str = "data";
+
+/// Name of the directory suggested when none is given
+const SUGGESTED_NAME: &str = "Game View Data";
+
+/// Files tied to the session that wrote them, left behind when relocating
+const NOT_COPIED: &[&str] = &[POINTER_FILE, crate::health::SESSION_FILE];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Pointer {
+    path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Relocated {
+    pub path: String,
+    /// Files copied over from the previous location
+    pub copied: usize,
+    /// Files that could not be copied
+    pub failed: usize,
+}
+
+/// Where a candidate app data directory came from, in the order they are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataDirSource {
+    /// Moved there with relocate_app_data
+    Relocated,
+    /// Where Tauri puts app data
+    Standard,
+    /// GAMEVIEW_DATA_DIR
+    Environment,
+    /// Next to the executable
+    Portable,
+    /// An overlay in the temp directory, in inspection mode
+    Inspection,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RejectedDir {
+    pub source: DataDirSource,
+    /// Empty when the location itself could not be determined
+    pub path: String,
+    pub reason: String,
+}
+
+/// The app data directory chosen at startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedDataDir {
+    /// None when no candidate could be written to
+    pub path: Option<PathBuf>,
+    pub source: Option<DataDirSource>,
+    /// Candidates passed over before it, in the order they were tried
+    pub rejected: Vec<RejectedDir>,
+}
+
+/// The app data directory in use
+pub fn dir<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    resolved(app)
+        .path
+        .ok_or_else(|| "No writable directory for app data was found".to_string())
+}
+
+/// The app data directory chosen at startup, and what was passed over
+pub fn resolved<R: Runtime>(app: &tauri::AppHandle<R>) -> ResolvedDataDir {
+    match app.try_state::<ResolvedDataDir>() {
+        Some(resolved) => resolved.inner().clone(),
+        // Only before setup has resolved it
+        None => resolve(app),
+    }
+}
+
+/// Choose the app data directory, creating it; setup keeps the result as state
+pub fn resolve<R: Runtime>(app: &tauri::AppHandle<R>) -> ResolvedDataDir {
+    let relocated = pointer_dirs(app)
+        .into_iter()
+        .find_map(|dir| read_pointer(&dir.join(POINTER_FILE)));
+    if inspection::active() {
+        // Nothing written there lasts, but what was saved before carries over
+        let saved = relocated.or_else(|| default_dir(app).ok());
+        return choose(vec![(
+            DataDirSource::Inspection,
+            inspection::overlay(saved.as_deref()),
+        )]);
+    }
+    let mut candidates: Vec<(DataDirSource, Result<PathBuf, String>)> = vec![];
+    if let Some(relocated) = relocated {
+        candidates.push((DataDirSource::Relocated, Ok(relocated)));
+    }
+    candidates.push((DataDirSource::Standard, default_dir(app)));
+    if let Some(dir) = std::env::var_os(ENV_VAR).filter(|v| !v.is_empty()) {
+        candidates.push((DataDirSource::Environment, Ok(PathBuf::from(dir))));
+    }
+    candidates.push((DataDirSource::Portable, portable_dir()));
+    choose(candidates)
+}
+
+/// Every directory resolve could have chosen, in the order it tries them,
+/// worked out without a running app. A launch handing its request to the
+/// running instance looks in each for what the instance keeps in app data.
+/// Inspection overlays, made per process, come last.
+pub fn possible_dirs() -> Vec<PathBuf> {
+    let mut dirs = headless_dirs(
+        HeadlessBase::of_host(),
+        std::env::var_os(ENV_VAR).filter(|v| !v.is_empty()),
+        portable_dir().ok(),
+    );
+    dirs.extend(inspection::overlays());
+    dirs
+}
+
+/// The per-user directories Tauri's path resolver starts from, app directories
+/// not yet appended
+#[derive(Debug)]
+struct HeadlessBase {
+    data: Option<PathBuf>,
+    config: Option<PathBuf>,
+    local_data: Option<PathBuf>,
+    cache: Option<PathBuf>,
+}
+
+impl HeadlessBase {
+    fn of_host() -> Self {
+        let var = |name: &str| {
+            std::env::var_os(name)
+                .map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+        };
+        let home = |relative: &str| var("HOME").map(|home| home.join(relative));
+        if cfg!(target_os = "windows") {
+            Self {
+                data: var("APPDATA"),
+                config: var("APPDATA"),
+                local_data: var("LOCALAPPDATA"),
+                cache: var("LOCALAPPDATA"),
+            }
+        } else if cfg!(target_os = "macos") {
+            Self {
+                data: home("Library/Application Support"),
+                config: home("Library/Application Support"),
+                local_data: home("Library/Application Support"),
+                cache: home("Library/Caches"),
+            }
+        } else {
+            let data = var("XDG_DATA_HOME").or_else(|| home(".local/share"));
+            Self {
+                data: data.clone(),
+                config: var("XDG_CONFIG_HOME").or_else(|| home(".config")),
+                local_data: data,
+                cache: var("XDG_CACHE_HOME").or_else(|| home(".cache")),
+            }
+        }
+    }
+}
+
+// resolve's candidates outside inspection mode, with the pointer dirs as
+// pointer_dirs would find them
+fn headless_dirs(
+    base: HeadlessBase,
+    environment: Option<std::ffi::OsString>,
+    portable: Option<PathBuf>,
+) -> Vec<PathBuf> {
+    let default = base.data.map(|dir| dir.join(IDENTIFIER));
+    let relocated = [base.config, base.local_data, base.cache]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(IDENTIFIER))
+        .filter(|dir| Some(dir) != default.as_ref())
+        .find_map(|dir| read_pointer(&dir.join(POINTER_FILE)));
+    relocated
+        .into_iter()
+        .chain(default)
+        .chain(environment.map(PathBuf::from))
+        .chain(portable)
+        .collect()
+}
+
+/// The first candidate that is absolute and can be created and written to
+fn choose(candidates: Vec<(DataDirSource, Result<PathBuf, String>)>) -> ResolvedDataDir {
+    let mut rejected = vec![];
+    for (source, candidate) in candidates {
+        let reject = |path: &Path, reason: String| RejectedDir {
+            source,
+            path: path.to_string_lossy().to_string(),
+            reason,
+        };
+        let dir = match candidate {
+            Ok(dir) => dir,
+            Err(reason) => {
+                rejected.push(reject(Path::new(""), reason));
+                continue;
+            }
+        };
+        if !dir.is_absolute() {
+            rejected.push(reject(&dir, "Not an absolute path".to_string()));
+        } else if let Err(e) = std::fs::create_dir_all(&dir) {
+            rejected.push(reject(&dir, e.to_string()));
+        } else if !setup::is_writable(&dir) {
+            rejected.push(reject(&dir, "Cannot be written to".to_string()));
+        } else {
+            for passed in &rejected {
+                eprintln!(
+                    "Not keeping app data in {} ({:?}): {}",
+                    passed.path, passed.source, passed.reason
+                );
+            }
+            return ResolvedDataDir {
+                path: Some(dir),
+                source: Some(source),
+                rejected,
+            };
+        }
+    }
+    ResolvedDataDir {
+        path: None,
+        source: None,
+        rejected,
+    }
+}
+
+fn portable_dir() -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let dir = exe
+        .parent()
+        .ok_or_else(|| format!("{} has no parent directory", exe.display()))?;
+    Ok(dir.join(PORTABLE_DIR))
+}
+
+/// Where app data could go instead: a directory of its own among the user's
+/// documents, or in the local data directory when that differs
+pub fn suggested_dir<R: Runtime>(app: &tauri::AppHandle<R>) -> Option<PathBuf> {
+    let default = default_dir(app).ok();
+    let local = app
+        .path()
+        .app_local_data_dir()
+        .ok()
+        .filter(|dir| Some(dir) != default.as_ref());
+    local.or_else(|| {
+        app.path()
+            .document_dir()
+            .ok()
+            .map(|dir| dir.join(SUGGESTED_NAME))
+    })
+}
+
+/// Move app data to `path`, which must be a folder picked through a native
+/// dialog, or to the suggested directory, and restart so every part of the
+/// backend picks it up
+#[tauri::command]
+pub async fn relocate_app_data(
+    app: tauri::AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: Option<String>,
+) -> Result<Relocated, AppError> {
+    let target = match path {
+        Some(path) => {
+            // Secrets and history go wherever this points
+            policy.check_target(&path)?;
+            PathBuf::from(path)
+        }
+        None => suggested_dir(&app).ok_or_else(|| {
+            AppError::NotFound("No alternative directory for app data was found".to_string())
+        })?,
+    };
+    // Nothing to copy when no app data directory could be used at all
+    let current = dir(&app).ok();
+    let pointer_dirs = pointer_dirs(&app);
+    let relocated =
+        tokio::task::spawn_blocking(move || relocate(current.as_deref(), &target, &pointer_dirs))
+            .await
+            .map_err(|e| e.to_string())??;
+    app.request_restart();
+    Ok(relocated)
+}
+
+fn relocate(
+    current: Option<&Path>,
+    target: &Path,
+    pointer_dirs: &[PathBuf],
+) -> Result<Relocated, AppError> {
+    std::fs::create_dir_all(target).ok();
+    if !setup::is_writable(target) {
+        return Err(AppError::InvalidInput(format!(
+            "Cannot write to {}",
+            target.display()
+        )));
+    }
+    let pointer = Pointer {
+        path: target.to_string_lossy().to_string(),
+    };
+    let saved = pointer_dirs.iter().any(|dir| {
+        std::fs::create_dir_all(dir).is_ok()
+            && fsutil::write_json_atomic(&dir.join(POINTER_FILE), &pointer).is_ok()
+    });
+    if !saved {
+        return Err(AppError::Io(
+            "Nowhere to remember the new app data location".to_string(),
+        ));
+    }
+    let mut relocated = Relocated {
+        path: pointer.path,
+        copied: 0,
+        failed: 0,
+    };
+    if let Some(current) = current.filter(|current| *current != target) {
+        copy_missing(current, target, &mut relocated);
+    }
+    Ok(relocated)
+}
+
+/// Copy what `from` holds into `to`, best effort and without replacing
+/// anything already there
+fn copy_missing(from: &Path, to: &Path, relocated: &mut Relocated) {
+    let Ok(entries) = std::fs::read_dir(from) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if NOT_COPIED.contains(&name.to_string_lossy().as_ref()) {
+            continue;
+        }
+        let target = to.join(&name);
+        if entry.path().is_dir() {
+            if std::fs::create_dir_all(&target).is_ok() {
+                copy_missing(&entry.path(), &target, relocated);
+            } else {
+                relocated.failed += 1;
+            }
+        } else if !target.exists() {
+            match std::fs::copy(entry.path(), &target) {
+                Ok(_) => relocated.copied += 1,
+                Err(e) => {
+                    eprintln!("Failed to copy {}: {}", entry.path().display(), e);
+                    relocated.failed += 1;
+                }
+            }
+        }
+    }
+}
+
+fn default_dir<R: Runtime>(app: &tauri::AppHandle<R>) -> Result<PathBuf, String> {
+    app.path().app_data_dir().map_err(|e| e.to_string())
+}
+
+/// Where the pointer file may be kept, in order of preference
+fn pointer_dirs<R: Runtime>(app: &tauri::AppHandle<R>) -> Vec<PathBuf> {
+    let default = default_dir(app).ok();
+    let resolver = app.path();
+    let mut dirs: Vec<PathBuf> = [
+        resolver.app_config_dir(),
+        resolver.app_local_data_dir(),
+        resolver.app_cache_dir(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|dir| Some(dir) != default.as_ref())
+    .collect();
+    dirs.dedup();
+    dirs
+}
+
+fn read_pointer(path: &Path) -> Option<PathBuf> {
+    let contents = std::fs::read(path).ok()?;
+    match serde_json::from_slice::<Pointer>(&contents) {
+        Ok(pointer) if Path::new(&pointer.path).is_absolute() => Some(PathBuf::from(pointer.path)),
+        Ok(_) | Err(_) => {
+            eprintln!("Ignoring unusable app data location in {}", path.display());
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn relocating_copies_app_data_and_remembers_the_new_place() {
+        let paths = TempPaths::new();
+        let current = paths.root().join("data");
+        std::fs::write(current.join("settings.json"), b"{}").unwrap();
+        std::fs::write(current.join(crate::health::SESSION_FILE), b"{}").unwrap();
+        std::fs::create_dir_all(current.join("logs")).unwrap();
+        std::fs::write(current.join("logs").join("job.log"), b"log").unwrap();
+        let target = paths.root().join("relocated");
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(target.join("settings.json"), b"newer").unwrap();
+        let config = paths.root().join("config");
+
+        let relocated = relocate(Some(&current), &target, std::slice::from_ref(&config)).unwrap();
+        assert_eq!(relocated.copied, 1);
+        assert_eq!(
+            std::fs::read(target.join("settings.json")).unwrap(),
+            b"newer"
+        );
+        assert!(target.join("logs").join("job.log").is_file());
+        assert!(!target.join(crate::health::SESSION_FILE).exists());
+        assert_eq!(
+            read_pointer(&config.join(POINTER_FILE)),
+            Some(target.clone())
+        );
+    }
+
+    #[test]
+    fn a_headless_launch_looks_where_resolve_would() {
+        let paths = TempPaths::new();
+        let base = |root: &Path| HeadlessBase {
+            data: Some(root.join("share")),
+            config: Some(root.join("config")),
+            local_data: Some(root.join("share")),
+            cache: Some(root.join("cache")),
+        };
+        let standard = paths.root().join("share").join(IDENTIFIER);
+        let env = paths.root().join("env");
+        let portable = paths.root().join("portable");
+
+        let dirs = headless_dirs(
+            base(paths.root()),
+            Some(env.clone().into_os_string()),
+            Some(portable.clone()),
+        );
+        assert_eq!(dirs, [standard.clone(), env.clone(), portable.clone()]);
+
+        // A relocation remembered in the cache directory comes first
+        let relocated = paths.root().join("relocated");
+        let pointer_dir = paths.root().join("cache").join(IDENTIFIER);
+        std::fs::create_dir_all(&pointer_dir).unwrap();
+        let pointer = Pointer {
+            path: relocated.to_string_lossy().to_string(),
+        };
+        fsutil::write_json_atomic(&pointer_dir.join(POINTER_FILE), &pointer).unwrap();
+        let dirs = headless_dirs(base(paths.root()), None, Some(portable.clone()));
+        assert_eq!(dirs, [relocated, standard, portable]);
+    }
+
+    #[test]
+    fn the_first_writable_candidate_is_chosen_in_order() {
+        let paths = TempPaths::new();
+        // Under a regular file, so it cannot be created even as root
+        let blocked = paths.root().join("file");
+        std::fs::write(&blocked, b"").unwrap();
+        let locked = paths.root().join("locked");
+        std::fs::create_dir_all(&locked).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+        }
+        let env = paths.root().join("env");
+        let portable = paths.root().join("portable").join(PORTABLE_DIR);
+
+        let resolved = choose(vec![
+            (DataDirSource::Relocated, Ok(blocked.join("data"))),
+            (
+                DataDirSource::Standard,
+                Err("no home directory".to_string()),
+            ),
+            (
+                DataDirSource::Environment,
+                Ok(PathBuf::from("relative/data")),
+            ),
+            (DataDirSource::Environment, Ok(locked.clone())),
+            (DataDirSource::Portable, Ok(portable.clone())),
+            (DataDirSource::Portable, Ok(env.clone())),
+        ]);
+        let rejected: Vec<DataDirSource> = resolved.rejected.iter().map(|r| r.source).collect();
+        // Root writes through stripped permissions
+        if setup::is_writable(&locked) {
+            assert_eq!(resolved.path, Some(locked.clone()));
+            assert_eq!(rejected.len(), 3);
+        } else {
+            assert_eq!(resolved.path, Some(portable.clone()));
+            assert_eq!(resolved.source, Some(DataDirSource::Portable));
+            assert_eq!(
+                rejected,
+                [
+                    DataDirSource::Relocated,
+                    DataDirSource::Standard,
+                    DataDirSource::Environment,
+                    DataDirSource::Environment,
+                ]
+            );
+            assert_eq!(resolved.rejected[3].reason, "Cannot be written to");
+        }
+        assert_eq!(resolved.rejected[1].reason, "no home directory");
+        assert!(!env.exists());
+
+        let nowhere = choose(vec![(DataDirSource::Standard, Ok(blocked.join("data")))]);
+        assert_eq!(nowhere.path, None);
+        assert_eq!(nowhere.rejected.len(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    }
+}
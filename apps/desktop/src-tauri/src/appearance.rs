@@ -0,0 +1,264 @@
+//! Appearance
+//!
+//! The theme used to be a free string the backend never looked at, so a typo
+//! stayed in the settings for good and each part of the frontend fell back in
+//! its own way. Appearance is now a theme of light, dark or system, an accent
+//! color and reduce_motion; save_settings refuses anything else. Settings
+//! saved as a bare theme name load as the theme it names, or as system when
+//! it names none.
+//!
+//! The backend applies the theme to the window chrome, so the title bar
+//! matches the app on Windows. Under system the windows follow the OS, and
+//! the theme they report is noted: get_effective_theme resolves system
+//! against it, and "os-theme-changed" is sent when the OS switches.
+
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::settings::{SettingsState, SettingsStore};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+
+// The theme the OS was last seen to use
+static OS_THEME: Mutex<Option<Shown>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Whatever the OS uses
+    #[default]
+    System,
+}
+
+impl Theme {
+    /// The theme a name saved by older versions stands for; system for one that names none
+    fn from_name(name: &str) -> Theme {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "light" => Theme::Light,
+            "dark" => Theme::Dark,
+            _ => Theme::System,
+        }
+    }
+}
+
+/// The theme windows show once system is resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shown {
+    Light,
+    Dark,
+}
+
+impl From<tauri::Theme> for Shown {
+    fn from(theme: tauri::Theme) -> Shown {
+        match theme {
+            tauri::Theme::Dark => Shown::Dark,
+            _ => Shown::Light,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppearanceSettings {
+    #[serde(default)]
+    pub theme: Theme,
+    /// A "#rrggbb" color for highlights; the frontend's own when unset
+    #[serde(default)]
+    pub accent_color: Option<String>,
+    /// Fewer animations and transitions
+    #[serde(default)]
+    pub reduce_motion: bool,
+}
+
+impl AppearanceSettings {
+    /// The same appearance with the accent color in lowercase
+    pub fn normalized(mut self) -> AppearanceSettings {
+        self.accent_color = self
+            .accent_color
+            .map(|color| color.trim().to_ascii_lowercase());
+        self
+    }
+}
+
+/// What get_effective_theme and "os-theme-changed" report
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EffectiveTheme {
+    /// The theme the windows show
+    pub theme: Shown,
+    /// The theme chosen in the settings
+    pub setting: Theme,
+    /// The OS's theme, once a window has reported it
+    pub os_theme: Option<Shown>,
+}
+
+/// Appearance as saved, either as itself or as the bare theme name of older versions
+pub fn from_saved<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<AppearanceSettings, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Name(String),
+        Appearance(AppearanceSettings),
+    }
+    Ok(match Saved::deserialize(deserializer)? {
+        Saved::Name(name) => AppearanceSettings {
+            theme: Theme::from_name(&name),
+            ..Default::default()
+        },
+        Saved::Appearance(appearance) => appearance,
+    })
+}
+
+/// Problems with `appearance`, keyed by their field below `field`
+pub fn check(appearance: &AppearanceSettings, field: &str) -> Vec<(String, Message)> {
+    let mut problems = vec![];
+    if let Some(color) = &appearance.accent_color {
+        let hex = color.trim().strip_prefix('#').unwrap_or("");
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            let message = Message::new("args.invalid")
+                .with("detail", format!("{} is not a color like #3b82f6", color));
+            problems.push((format!("{}.accentColor", field), message));
+        }
+    }
+    problems
+}
+
+/// The theme windows show for `setting` while the OS uses `os_theme`
+pub fn resolve(setting: Theme, os_theme: Option<Shown>) -> Shown {
+    match setting {
+        Theme::Light => Shown::Light,
+        Theme::Dark => Shown::Dark,
+        Theme::System => os_theme.unwrap_or(Shown::Light),
+    }
+}
+
+fn effective(setting: Theme) -> EffectiveTheme {
+    let os_theme = *OS_THEME.lock().unwrap();
+    EffectiveTheme {
+        theme: resolve(setting, os_theme),
+        setting,
+        os_theme,
+    }
+}
+
+// The theme in the settings; None in recovery, where no settings are loaded
+fn setting(app: &AppHandle) -> Option<Theme> {
+    let settings = app.try_state::<SettingsState>()?;
+    Some(settings.settings().appearance.theme)
+}
+
+fn apply_to(window: &WebviewWindow, setting: Theme) {
+    let forced = match setting {
+        Theme::Light => Some(tauri::Theme::Light),
+        Theme::Dark => Some(tauri::Theme::Dark),
+        Theme::System => None,
+    };
+    window.set_theme(forced).ok();
+    // A window following the OS shows the OS's theme
+    if setting == Theme::System {
+        if let Ok(theme) = window.theme() {
+            *OS_THEME.lock().unwrap() = Some(theme.into());
+        }
+    }
+}
+
+/// Note the OS's theme while the windows still follow it, then apply `appearance`
+pub fn start(app: &AppHandle, appearance: &AppearanceSettings) {
+    if let Some(theme) = app
+        .webview_windows()
+        .values()
+        .find_map(|window| window.theme().ok())
+    {
+        *OS_THEME.lock().unwrap() = Some(theme.into());
+    }
+    apply(app, appearance);
+}
+
+/// Put the theme of `appearance` on every window's chrome
+pub fn apply(app: &AppHandle, appearance: &AppearanceSettings) {
+    for window in app.webview_windows().values() {
+        apply_to(window, appearance.theme);
+    }
+}
+
+/// Put the theme of the settings on a window opened since they were applied
+pub fn apply_to_window(app: &AppHandle, label: &str) {
+    if let (Some(window), Some(setting)) = (app.get_webview_window(label), setting(app)) {
+        apply_to(&window, setting);
+    }
+}
+
+/// A window reported a theme change; under system that is the OS switching
+pub fn theme_changed(app: &AppHandle, theme: tauri::Theme) {
+    let Some(setting) = setting(app) else {
+        return;
+    };
+    // Otherwise the change is the theme the backend forced
+    if setting != Theme::System {
+        return;
+    }
+    let theme = Shown::from(theme);
+    let previous = OS_THEME.lock().unwrap().replace(theme);
+    if previous != Some(theme) {
+        app.emit("os-theme-changed", effective(setting)).ok();
+    }
+}
+
+/// The theme the windows show, with system resolved against the OS
+#[tauri::command]
+pub async fn get_effective_theme(app: AppHandle) -> Result<EffectiveTheme, AppError> {
+    Ok(effective(app.settings().appearance.theme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Saved {
+        #[serde(deserialize_with = "from_saved")]
+        theme: AppearanceSettings,
+    }
+
+    fn saved(json: &str) -> AppearanceSettings {
+        serde_json::from_str::<Saved>(json).unwrap().theme
+    }
+
+    #[test]
+    fn theme_names_of_older_versions_load_as_themes() {
+        assert_eq!(saved(r#"{"theme":"dark"}"#).theme, Theme::Dark);
+        assert_eq!(saved(r#"{"theme":" Light "}"#).theme, Theme::Light);
+        assert_eq!(saved(r#"{"theme":"drak"}"#).theme, Theme::System);
+        let current = saved(r##"{"theme":{"theme":"dark","accentColor":"#3b82f6"}}"##);
+        assert_eq!(current.theme, Theme::Dark);
+        assert_eq!(current.accent_color.as_deref(), Some("#3b82f6"));
+        assert!(serde_json::from_str::<Saved>(r#"{"theme":{"theme":"drak"}}"#).is_err());
+    }
+
+    #[test]
+    fn accent_colors_must_be_hex() {
+        let with_accent = |color: &str| AppearanceSettings {
+            accent_color: Some(color.to_string()),
+            ..Default::default()
+        };
+        assert!(check(&with_accent("#3B82F6"), "appearance").is_empty());
+        let problems = check(&with_accent("blue"), "appearance");
+        assert_eq!(problems[0].0, "appearance.accentColor");
+        assert!(!check(&with_accent("#3b82f"), "appearance").is_empty());
+        assert_eq!(
+            with_accent(" #3B82F6").normalized().accent_color.as_deref(),
+            Some("#3b82f6")
+        );
+    }
+
+    #[test]
+    fn system_follows_the_os() {
+        assert_eq!(resolve(Theme::System, Some(Shown::Dark)), Shown::Dark);
+        assert_eq!(resolve(Theme::System, None), Shown::Light);
+        assert_eq!(resolve(Theme::Light, Some(Shown::Dark)), Shown::Light);
+    }
+}
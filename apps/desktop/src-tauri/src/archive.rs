@@ -0,0 +1,566 @@
+//! Intermediate Archives
+//!
+//! COLMAP data and extracted frames are only needed to re-train, but they dwarf
+//! the artifact. Archiving packs them into one zstd-compressed tarball next to
+//! the artifact, reads it back to check every file is in it, and only then
+//! deletes the originals. The sidecar records the archive, so nothing processes
+//! into the production until restore_production_intermediates has put them back.
+
+use crate::error::AppError;
+use crate::job_log;
+use crate::jobs;
+use crate::operations::{self, OperationHandle, OperationKind};
+use crate::path_policy::PathPolicy;
+use crate::productions::{dir_size, INTERMEDIATE_ENTRIES};
+use crate::scheduler::{self, Pool};
+use crate::sidecar;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, State};
+
+/// The archive, in the production directory
+pub const ARCHIVE_NAME: &str = "intermediates.gvarchive";
+
+/// Written here first, so a failed run never leaves a half archive behind
+const PARTIAL_NAME: &str = "intermediates.gvarchive.partial";
+
+/// Extracted here first, so a failed restore never leaves half the files behind
+const RESTORE_DIR: &str = ".gvarchive-restore";
+
+/// Fast enough to keep up with the disk; frames barely compress further anyway
+const ZSTD_LEVEL: i32 = 3;
+
+/// What the sidecar records about archived intermediates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntermediatesArchive {
+    /// Top-level entries of the production that were archived
+    pub entries: Vec<String>,
+    pub original_bytes: u64,
+    pub archive_bytes: u64,
+    pub archived_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveStage {
+    Compressing,
+    Verifying,
+    Restoring,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveProgress {
+    pub path: String,
+    pub stage: ArchiveStage,
+    pub done_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Compress a production's intermediates into its archive and delete them.
+/// Progress arrives as "archive-progress" events.
+#[tauri::command]
+pub async fn archive_production_intermediates(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+) -> Result<IntermediatesArchive, AppError> {
+    policy.check_existing(&path)?;
+    archive_intermediates(app, path).await
+}
+
+/// Extract a production's archived intermediates back into place
+#[tauri::command]
+pub async fn restore_production_intermediates(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+) -> Result<(), AppError> {
+    policy.check_existing(&path)?;
+    run(app, path, restore).await
+}
+
+/// Archive a production's intermediates like archive_production_intermediates,
+/// for callers that checked the path themselves
+pub async fn archive_intermediates(
+    app: AppHandle,
+    path: String,
+) -> Result<IntermediatesArchive, AppError> {
+    run(app, path, archive).await
+}
+
+/// Put a production's archived intermediates back, without progress or cancelling
+pub fn restore_now(dir: &Path) -> Result<(), AppError> {
+    restore(dir, &AtomicBool::new(false), &mut |_, _, _| {})
+}
+
+/// Cancel a running archive or restore; the production is left as it was
+#[tauri::command]
+pub async fn cancel_archive_operation() -> Result<(), AppError> {
+    operations::cancel_kind(OperationKind::Archive);
+    Ok(())
+}
+
+/// Fail with NeedsRestore when the production's intermediates are archived, as
+/// a new run would write fresh ones next to the archive
+pub fn check_not_archived(dir: &Path) -> Result<(), AppError> {
+    let archived = sidecar::read(dir)
+        .ok()
+        .flatten()
+        .is_some_and(|s| s.archive.is_some());
+    if archived && dir.join(ARCHIVE_NAME).is_file() {
+        return Err(AppError::NeedsRestore(dir.to_string_lossy().to_string()));
+    }
+    Ok(())
+}
+
+type Operation<T> =
+    fn(&Path, &AtomicBool, &mut dyn FnMut(ArchiveStage, u64, u64)) -> Result<T, AppError>;
+
+async fn run<T: Send + 'static>(
+    app: AppHandle,
+    path: String,
+    operation: Operation<T>,
+) -> Result<T, AppError> {
+    let dir = PathBuf::from(&path);
+    if jobs::is_targeting(&dir) {
+        return Err(AppError::ProductionBusy(path));
+    }
+    let handle = OperationHandle::start(&app, OperationKind::Archive, &path);
+
+    scheduler::run_blocking(Pool::CpuHeavy, move || {
+        operation(
+            &dir,
+            handle.token(),
+            &mut |stage, done_bytes, total_bytes| {
+                handle.progress(done_bytes, Some(total_bytes));
+                let progress = ArchiveProgress {
+                    path: path.clone(),
+                    stage,
+                    done_bytes,
+                    total_bytes,
+                };
+                app.emit("archive-progress", &progress).ok();
+            },
+        )
+    })
+    .await?
+}
+
+fn archive(
+    dir: &Path,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(ArchiveStage, u64, u64),
+) -> Result<IntermediatesArchive, AppError> {
+    let mut meta = sidecar::read(dir)?
+        .ok_or_else(|| AppError::NotFound(format!("Sidecar of {}", dir.display())))?;
+    if meta.archive.is_some() {
+        return Err(AppError::InvalidInput(format!(
+            "The intermediates of {} are already archived",
+            dir.display()
+        )));
+    }
+    let entries: Vec<String> = INTERMEDIATE_ENTRIES
+        .iter()
+        .filter(|name| dir.join(name).exists())
+        .map(|name| name.to_string())
+        .collect();
+    if entries.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "{} has no intermediates to archive",
+            dir.display()
+        )));
+    }
+
+    let mut original_bytes = 0;
+    for name in &entries {
+        let path = dir.join(name);
+        original_bytes += if path.is_dir() {
+            dir_size(&path)?
+        } else {
+            std::fs::metadata(&path)?.len()
+        };
+    }
+
+    let partial = dir.join(PARTIAL_NAME);
+    let written = write_archive(dir, &entries, &partial, cancel, progress, original_bytes)
+        .and_then(|manifest| verify_archive(&partial, &manifest, cancel, progress));
+    if let Err(e) = written {
+        std::fs::remove_file(&partial).ok();
+        return Err(stopped(e, cancel));
+    }
+    let archive = dir.join(ARCHIVE_NAME);
+    std::fs::rename(&partial, &archive)?;
+
+    let record = IntermediatesArchive {
+        entries,
+        original_bytes,
+        archive_bytes: std::fs::metadata(&archive)?.len(),
+        archived_at: job_log::unix_timestamp(),
+    };
+    meta.archive = Some(record.clone());
+    if let Err(e) = sidecar::write(dir, &meta) {
+        std::fs::remove_file(&archive).ok();
+        return Err(e.into());
+    }
+
+    for name in &record.entries {
+        let path = dir.join(name);
+        let removed = if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        removed.map_err(|e| {
+            AppError::Io(format!(
+                "Archived, but could not remove {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+    }
+    Ok(record)
+}
+
+/// Write the archive, returning the size of every file in it
+fn write_archive(
+    dir: &Path,
+    entries: &[String],
+    target: &Path,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(ArchiveStage, u64, u64),
+    total_bytes: u64,
+) -> io::Result<BTreeMap<String, u64>> {
+    let mut meter = Meter::new(ArchiveStage::Compressing, total_bytes, cancel, progress);
+    let mut encoder = zstd::Encoder::new(BufWriter::new(File::create(target)?), ZSTD_LEVEL)?;
+    encoder.include_checksum(true)?;
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+
+    let mut manifest = BTreeMap::new();
+    for name in entries {
+        append_tree(
+            &mut builder,
+            dir,
+            Path::new(name),
+            &mut meter,
+            &mut manifest,
+        )?;
+    }
+    let file = builder.into_inner()?.finish()?.into_inner()?;
+    file.sync_all()?;
+    Ok(manifest)
+}
+
+fn append_tree<W: Write>(
+    builder: &mut tar::Builder<W>,
+    root: &Path,
+    relative: &Path,
+    meter: &mut Meter,
+    manifest: &mut BTreeMap<String, u64>,
+) -> io::Result<()> {
+    let path = root.join(relative);
+    let metadata = std::fs::symlink_metadata(&path)?;
+    if metadata.is_dir() {
+        builder.append_dir(relative, &path)?;
+        let mut children = std::fs::read_dir(&path)?
+            .map(|entry| entry.map(|e| e.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        children.sort();
+        for child in children {
+            append_tree(builder, root, &relative.join(child), meter, manifest)?;
+        }
+    } else if metadata.is_file() {
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata(&metadata);
+        let file = Tracked {
+            inner: File::open(&path)?,
+            meter,
+        };
+        builder.append_data(&mut header, relative, file)?;
+        manifest.insert(archive_name(relative), metadata.len());
+    } else {
+        // Symlinks are stored as links
+        builder.append_path_with_name(&path, relative)?;
+    }
+    Ok(())
+}
+
+/// Read the whole archive back, which also checks zstd's checksum, and compare
+/// its files with what was written
+fn verify_archive(
+    archive: &Path,
+    expected: &BTreeMap<String, u64>,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(ArchiveStage, u64, u64),
+) -> io::Result<()> {
+    let file = File::open(archive)?;
+    let total_bytes = file.metadata()?.len();
+    let mut meter = Meter::new(ArchiveStage::Verifying, total_bytes, cancel, progress);
+    let reader = Tracked {
+        inner: BufReader::new(file),
+        meter: &mut meter,
+    };
+    let mut tarball = tar::Archive::new(zstd::Decoder::new(reader)?);
+
+    let mut found = BTreeMap::new();
+    for entry in tarball.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            let name = archive_name(&entry.path()?);
+            let bytes = io::copy(&mut entry, &mut io::sink())?;
+            found.insert(name, bytes);
+        }
+    }
+    if &found != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "The archive does not match the intermediates it was made from",
+        ));
+    }
+    Ok(())
+}
+
+fn restore(
+    dir: &Path,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(ArchiveStage, u64, u64),
+) -> Result<(), AppError> {
+    let mut meta = sidecar::read(dir)?
+        .ok_or_else(|| AppError::NotFound(format!("Sidecar of {}", dir.display())))?;
+    let record = meta.archive.clone().ok_or_else(|| {
+        AppError::NotFound(format!("Archived intermediates of {}", dir.display()))
+    })?;
+    let archive = dir.join(ARCHIVE_NAME);
+    if !archive.is_file() {
+        return Err(AppError::NotFound(archive.to_string_lossy().to_string()));
+    }
+    if let Some(existing) = record.entries.iter().find(|name| dir.join(name).exists()) {
+        return Err(AppError::InvalidInput(format!(
+            "{} already exists; remove it before restoring",
+            dir.join(existing).display()
+        )));
+    }
+
+    let staging = dir.join(RESTORE_DIR);
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)?;
+    }
+    if let Err(e) = extract(&archive, &staging, cancel, progress) {
+        std::fs::remove_dir_all(&staging).ok();
+        return Err(stopped(e, cancel));
+    }
+    for name in &record.entries {
+        std::fs::rename(staging.join(name), dir.join(name))?;
+    }
+    std::fs::remove_dir_all(&staging)?;
+
+    meta.archive = None;
+    sidecar::write(dir, &meta)?;
+    std::fs::remove_file(&archive)?;
+    Ok(())
+}
+
+fn extract(
+    archive: &Path,
+    target: &Path,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(ArchiveStage, u64, u64),
+) -> io::Result<()> {
+    let file = File::open(archive)?;
+    let total_bytes = file.metadata()?.len();
+    let mut meter = Meter::new(ArchiveStage::Restoring, total_bytes, cancel, progress);
+    let reader = Tracked {
+        inner: BufReader::new(file),
+        meter: &mut meter,
+    };
+    std::fs::create_dir_all(target)?;
+    // unpack refuses entries that would land outside the target
+    tar::Archive::new(zstd::Decoder::new(reader)?).unpack(target)
+}
+
+/// Forward-slash path of an entry, the same on every platform
+fn archive_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// I/O errors raised because the user cancelled
+fn stopped(error: io::Error, cancel: &AtomicBool) -> AppError {
+    if cancel.load(Ordering::SeqCst) {
+        AppError::Cancelled
+    } else {
+        error.into()
+    }
+}
+
+/// Bytes processed in one stage, reported at most once per percent
+struct Meter<'a> {
+    stage: ArchiveStage,
+    done: u64,
+    total: u64,
+    reported_percent: Option<u64>,
+    cancel: &'a AtomicBool,
+    progress: &'a mut dyn FnMut(ArchiveStage, u64, u64),
+}
+
+impl<'a> Meter<'a> {
+    fn new(
+        stage: ArchiveStage,
+        total: u64,
+        cancel: &'a AtomicBool,
+        progress: &'a mut dyn FnMut(ArchiveStage, u64, u64),
+    ) -> Self {
+        Self {
+            stage,
+            done: 0,
+            total,
+            reported_percent: None,
+            cancel,
+            progress,
+        }
+    }
+
+    fn advance(&mut self, bytes: usize) -> io::Result<()> {
+        if self.cancel.load(Ordering::SeqCst) {
+            return Err(io::Error::other("Cancelled"));
+        }
+        self.done += bytes as u64;
+        let percent = self.done * 100 / self.total.max(1);
+        if self.reported_percent != Some(percent) {
+            self.reported_percent = Some(percent);
+            (self.progress)(self.stage, self.done, self.total);
+        }
+        Ok(())
+    }
+}
+
+/// A reader that feeds a meter, failing once the meter is cancelled
+struct Tracked<'m, 'a, R> {
+    inner: R,
+    meter: &'m mut Meter<'a>,
+}
+
+impl<R: Read> Read for Tracked<'_, '_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.meter.advance(read)?;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use crate::sidecar::{ProductionSource, Sidecar};
+
+    fn production(paths: &TempPaths) -> PathBuf {
+        let dir = paths.root().join("harbour");
+        std::fs::create_dir_all(dir.join("frames")).unwrap();
+        std::fs::create_dir_all(dir.join("colmap").join("sparse").join("0")).unwrap();
+        for i in 0..20 {
+            let frame = dir.join("frames").join(format!("{:04}.png", i));
+            std::fs::write(frame, vec![i as u8; 4096]).unwrap();
+        }
+        let cameras = dir
+            .join("colmap")
+            .join("sparse")
+            .join("0")
+            .join("cameras.bin");
+        std::fs::write(cameras, b"pinhole").unwrap();
+        std::fs::write(dir.join("database.db"), vec![1u8; 10_000]).unwrap();
+        std::fs::write(dir.join("output.ply"), b"ply").unwrap();
+        let sidecar = Sidecar {
+            job_id: "job-1".to_string(),
+            production_dir: dir.to_string_lossy().to_string(),
+            artifact_path: dir.join("output.ply").to_string_lossy().to_string(),
+            preset: "balanced".to_string(),
+            videos: vec![],
+            created_at: 0,
+            artifact_sha256: None,
+            capture_type: Default::default(),
+            archive: None,
+            preview: false,
+            input_sha256: vec![],
+            command: None,
+            training: None,
+            derivatives: vec![],
+            source: ProductionSource::Job,
+            imported_from: None,
+            resolution_normalization: None,
+            camera_groups: vec![],
+            artifacts: vec![],
+        };
+        sidecar::write(&dir, &sidecar).unwrap();
+        dir
+    }
+
+    #[test]
+    fn archives_and_restores_intermediates() {
+        let paths = TempPaths::new();
+        let dir = production(&paths);
+        let cancel = AtomicBool::new(false);
+
+        let mut stages = vec![];
+        let record = archive(&dir, &cancel, &mut |stage, _, _| stages.push(stage)).unwrap();
+        assert_eq!(record.entries, ["frames", "colmap", "database.db"]);
+        assert_eq!(record.original_bytes, 20 * 4096 + 7 + 10_000);
+        assert!(record.archive_bytes < record.original_bytes);
+        assert!(stages.contains(&ArchiveStage::Compressing));
+        assert_eq!(stages.last(), Some(&ArchiveStage::Verifying));
+
+        assert!(!dir.join("frames").exists() && !dir.join("database.db").exists());
+        assert!(dir.join("output.ply").exists());
+        let meta = sidecar::read(&dir).unwrap().unwrap();
+        assert_eq!(meta.archive.as_ref(), Some(&record));
+        assert_eq!(
+            check_not_archived(&dir).unwrap_err().code(),
+            "needs_restore"
+        );
+
+        restore(&dir, &cancel, &mut |_, _, _| {}).unwrap();
+        let frame = std::fs::read(dir.join("frames").join("0007.png")).unwrap();
+        assert_eq!(frame, vec![7u8; 4096]);
+        let cameras = dir
+            .join("colmap")
+            .join("sparse")
+            .join("0")
+            .join("cameras.bin");
+        assert_eq!(std::fs::read(cameras).unwrap(), b"pinhole");
+        assert!(!dir.join(ARCHIVE_NAME).exists() && !dir.join(RESTORE_DIR).exists());
+        assert!(sidecar::read(&dir).unwrap().unwrap().archive.is_none());
+        check_not_archived(&dir).unwrap();
+    }
+
+    #[test]
+    fn cancelled_archive_keeps_the_originals() {
+        let paths = TempPaths::new();
+        let dir = production(&paths);
+
+        let err = archive(&dir, &AtomicBool::new(true), &mut |_, _, _| {}).unwrap_err();
+        assert_eq!(err.code(), "cancelled");
+        assert_eq!(std::fs::read_dir(dir.join("frames")).unwrap().count(), 20);
+        assert!(!dir.join(PARTIAL_NAME).exists() && !dir.join(ARCHIVE_NAME).exists());
+        assert!(sidecar::read(&dir).unwrap().unwrap().archive.is_none());
+    }
+
+    #[test]
+    fn restore_does_not_overwrite_new_intermediates() {
+        let paths = TempPaths::new();
+        let dir = production(&paths);
+        let cancel = AtomicBool::new(false);
+        archive(&dir, &cancel, &mut |_, _, _| {}).unwrap();
+
+        std::fs::create_dir(dir.join("frames")).unwrap();
+        let err = restore(&dir, &cancel, &mut |_, _, _| {}).unwrap_err();
+        assert_eq!(err.code(), "invalid_input");
+        assert!(dir.join(ARCHIVE_NAME).exists());
+    }
+}
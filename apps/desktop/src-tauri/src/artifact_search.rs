@@ -0,0 +1,246 @@
+//! Artifact Search
+//!
+//! Some CLI versions write the artifact into export/ or a timestamped folder
+//! of the output instead of as output.ply at its top. When the CLI does not
+//! name it with a result line, the job's output is searched for it, up to
+//! MAX_DEPTH folders down and leaving out checkpoints and working directories.
+//! Only PLY files holding every splat their header announces count, and of
+//! those the newest is the artifact. Two equally new ones are both reported
+//! for the user to choose between rather than guessed at. SPZ files in the
+//! folder of the artifact are kept as its other artifacts.
+
+use crate::artifacts::ArtifactKind;
+use crate::{checkpoints, conversion, workdir};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How many folders below the output a search goes
+pub const MAX_DEPTH: usize = 3;
+
+/// Folders of the output that never hold the artifact
+pub const SKIPPED: [&str; 2] = [workdir::DIR_NAME, checkpoints::DIR_NAME];
+
+/// A PLY file the search came across
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// Relative to the searched folder
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    /// Why it is not the artifact, once that is known
+    pub rejected: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Found {
+    Artifact(PathBuf),
+    /// Equally new valid candidates, in path order
+    Ambiguous(Vec<PathBuf>),
+    Nothing,
+}
+
+/// Every candidate, with why each loser lost, and what was found
+#[derive(Debug)]
+pub struct Search {
+    pub candidates: Vec<Candidate>,
+    pub found: Found,
+    /// Every SPZ file come across, relative to the searched folder, in path order
+    pub spz: Vec<PathBuf>,
+}
+
+impl Search {
+    /// The SPZ files in the same folder as `artifact`
+    pub fn beside(&self, artifact: &Path) -> Vec<PathBuf> {
+        self.spz
+            .iter()
+            .filter(|spz| spz.parent() == artifact.parent())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Search `root` for the artifact
+pub fn search(root: &Path) -> Search {
+    let mut candidates = vec![];
+    let mut spz = vec![];
+    collect(root, Path::new(""), 0, &mut candidates, &mut spz);
+    candidates.sort_by(|a, b| a.path.cmp(&b.path));
+    spz.sort();
+    let found = choose(&mut candidates);
+    Search {
+        candidates,
+        found,
+        spz,
+    }
+}
+
+fn collect(
+    root: &Path,
+    relative: &Path,
+    depth: usize,
+    candidates: &mut Vec<Candidate>,
+    spz: &mut Vec<PathBuf>,
+) {
+    let Ok(entries) = std::fs::read_dir(root.join(relative)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = relative.join(entry.file_name());
+        // Symlinks are not followed out of the output
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let skipped = SKIPPED.iter().any(|name| entry.file_name() == *name);
+            if !skipped && depth < MAX_DEPTH {
+                collect(root, &path, depth + 1, candidates, spz);
+            }
+        } else if file_type.is_file() && ArtifactKind::of(&path) == ArtifactKind::Spz {
+            spz.push(path);
+        } else if file_type.is_file() && is_ply(&path) {
+            let full = root.join(&path);
+            let modified = entry.metadata().and_then(|m| m.modified()).ok();
+            let rejected = match conversion::complete_splat_count(&full) {
+                Ok(_) if modified.is_none() => Some("its modification time is unknown".to_string()),
+                Ok(_) => None,
+                Err(e) => Some(e),
+            };
+            candidates.push(Candidate {
+                path,
+                modified,
+                rejected,
+            });
+        }
+    }
+}
+
+fn is_ply(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ply"))
+}
+
+/// The newest of the candidates not yet rejected; the older ones are marked
+/// as rejected for it
+fn choose(candidates: &mut [Candidate]) -> Found {
+    let newest = candidates
+        .iter()
+        .filter(|c| c.rejected.is_none())
+        .filter_map(|c| c.modified)
+        .max();
+    let Some(newest) = newest else {
+        return Found::Nothing;
+    };
+    let mut tied: Vec<PathBuf> = candidates
+        .iter()
+        .filter(|c| c.rejected.is_none() && c.modified == Some(newest))
+        .map(|c| c.path.clone())
+        .collect();
+    let winner = tied[0].display().to_string();
+    for candidate in candidates.iter_mut() {
+        if candidate.rejected.is_none() && candidate.modified != Some(newest) {
+            candidate.rejected = Some(format!("older than {}", winner));
+        }
+    }
+    if tied.len() == 1 {
+        Found::Artifact(tied.remove(0))
+    } else {
+        Found::Ambiguous(tied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::TempPaths;
+    use std::time::Duration;
+
+    fn candidate(path: &str, age_secs: u64) -> Candidate {
+        Candidate {
+            path: PathBuf::from(path),
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000 - age_secs)),
+            rejected: None,
+        }
+    }
+
+    #[test]
+    fn finds_valid_plys_within_reach_of_the_output() {
+        let paths = TempPaths::new();
+        let root = paths.root();
+        let write = |relative: &str, content: &[u8]| {
+            let path = root.join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        let ply = sample_ply(&[[0.0; 14]; 2]);
+        write("export/20260301-1200/splat.ply", &ply);
+        write("a/b/c/d/too-deep.ply", &ply);
+        write("checkpoints/step-7000.ply", &ply);
+        write(".gv/job-1/output/output.ply", &ply);
+        write("export/truncated.ply", &ply[..ply.len() - 1]);
+        write("export/preview.jpg", b"jpg");
+        write("export/20260301-1200/splat.spz", b"spz");
+        write("export/old.spz", b"spz");
+
+        let result = search(root);
+
+        assert_eq!(
+            result.found,
+            Found::Artifact(PathBuf::from("export/20260301-1200/splat.ply"))
+        );
+        let considered: Vec<(&Path, Option<&str>)> = result
+            .candidates
+            .iter()
+            .map(|c| (c.path.as_path(), c.rejected.as_deref()))
+            .collect();
+        assert_eq!(
+            considered,
+            [
+                (Path::new("export/20260301-1200/splat.ply"), None),
+                (
+                    Path::new("export/truncated.ply"),
+                    Some("PLY file is truncated")
+                ),
+            ]
+        );
+        assert_eq!(
+            result.beside(Path::new("export/20260301-1200/splat.ply")),
+            [PathBuf::from("export/20260301-1200/splat.spz")]
+        );
+        assert_eq!(search(&root.join("missing")).found, Found::Nothing);
+    }
+
+    #[test]
+    fn the_newest_wins_and_a_tie_is_reported() {
+        let mut candidates = vec![
+            candidate("export/splat.ply", 60),
+            candidate("output.ply", 10),
+            Candidate {
+                rejected: Some("PLY file is truncated".to_string()),
+                ..candidate("partial.ply", 0)
+            },
+        ];
+        assert_eq!(
+            choose(&mut candidates),
+            Found::Artifact(PathBuf::from("output.ply"))
+        );
+        assert_eq!(
+            candidates[0].rejected.as_deref(),
+            Some("older than output.ply")
+        );
+        assert_eq!(candidates[1].rejected, None);
+
+        let mut tied = vec![
+            candidate("a/splat.ply", 10),
+            candidate("b/splat.ply", 10),
+            candidate("output.ply", 30),
+        ];
+        assert_eq!(
+            choose(&mut tied),
+            Found::Ambiguous(vec![
+                PathBuf::from("a/splat.ply"),
+                PathBuf::from("b/splat.ply")
+            ])
+        );
+        assert!(tied[2].rejected.is_some());
+    }
+}
@@ -0,0 +1,171 @@
+//! Job Artifacts
+//!
+//! Newer CLI versions can write more than one artifact in a run, such as a
+//! full-quality PLY next to a web-optimized SPZ, each named by a result line
+//! that may give its kind. A job keeps every artifact with its size and
+//! checksum, in its history record and its sidecar.
+//!
+//! One of them is the primary artifact: the one the viewer opens, the output
+//! name template renames and that stands for the job wherever a single path is
+//! expected. By precedence it is the first PLY named, or without one the first
+//! SPZ, or without either the first artifact named.
+
+use crate::fsutil;
+use crate::hashing::{self, Reuse};
+use crate::job_log::JobLog;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+/// Kinds in order of precedence for the primary artifact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactKind {
+    /// Full-quality splats, which the viewer opens
+    Ply,
+    /// Compressed splats for the web
+    Spz,
+    /// A kind newer than this version
+    #[serde(other)]
+    Other,
+}
+
+impl ArtifactKind {
+    /// The kind a file's extension implies
+    pub fn of(path: &Path) -> ArtifactKind {
+        let ext = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "ply" => ArtifactKind::Ply,
+            "spz" => ArtifactKind::Spz,
+            _ => ArtifactKind::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub kind: ArtifactKind,
+    pub path: String,
+    /// Size when the job finished
+    pub bytes: u64,
+    /// Hex SHA-256 when the job finished; absent when it could not be read
+    pub sha256: Option<String>,
+}
+
+impl Artifact {
+    /// An artifact not yet measured, of the kind the CLI named or else its extension implies
+    pub fn named(path: String, kind: Option<ArtifactKind>) -> Artifact {
+        Artifact {
+            kind: kind.unwrap_or_else(|| ArtifactKind::of(Path::new(&path))),
+            path,
+            bytes: 0,
+            sha256: None,
+        }
+    }
+}
+
+/// What process_videos made
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobOutput {
+    /// The primary artifact, or in per-clip mode the directory of the
+    /// productions; all that frontends from before artifacts read
+    pub artifact_path: String,
+    /// Primary first; empty in per-clip mode, where each production's sidecar lists its own
+    pub artifacts: Vec<Artifact>,
+}
+
+/// Position of the primary among `kinds`
+pub fn primary_index(kinds: impl IntoIterator<Item = ArtifactKind>) -> Option<usize> {
+    kinds
+        .into_iter()
+        .enumerate()
+        .min_by_key(|(i, kind)| (*kind, *i))
+        .map(|(i, _)| i)
+}
+
+/// The primary of `artifacts`
+pub fn primary(artifacts: &[Artifact]) -> Option<&Artifact> {
+    artifacts.get(primary_index(artifacts.iter().map(|a| a.kind))?)
+}
+
+/// Move the primary to the front, keeping the others in order
+pub fn primary_first(artifacts: &mut [Artifact]) {
+    if let Some(i) = primary_index(artifacts.iter().map(|a| a.kind)) {
+        artifacts[..=i].rotate_right(1);
+    }
+}
+
+/// Record each artifact's size and checksum
+pub async fn measure(artifacts: &mut [Artifact], cancel: &AtomicBool, log: &mut JobLog) {
+    for artifact in artifacts {
+        let path = Path::new(&artifact.path);
+        artifact.bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        match hashing::sha256(path, Reuse::Cached, cancel, &mut |_, _| {}).await {
+            Ok(sha256) => {
+                log.line(&format!("Artifact {} SHA-256: {}", artifact.path, sha256));
+                artifact.sha256 = Some(sha256);
+            }
+            Err(e) => log.line(&format!(
+                "Failed to checksum artifact {}: {}",
+                artifact.path, e
+            )),
+        }
+    }
+}
+
+/// Point artifacts under `from` to where they are under `to`
+pub fn rebase(artifacts: &mut [Artifact], from: &Path, to: &Path) {
+    for artifact in artifacts {
+        if let Some(path) = fsutil::rebase(&artifact.path, from, to) {
+            artifact.path = path;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artifact(path: &str) -> Artifact {
+        Artifact::named(path.to_string(), None)
+    }
+
+    #[test]
+    fn a_ply_comes_before_an_spz_and_the_rest() {
+        let mut artifacts = vec![
+            artifact("/p/web.spz"),
+            artifact("/p/cameras.json"),
+            artifact("/p/splat.ply"),
+            artifact("/p/splat-2.ply"),
+        ];
+        assert_eq!(primary(&artifacts).unwrap().path, "/p/splat.ply");
+
+        primary_first(&mut artifacts);
+        let paths: Vec<&str> = artifacts.iter().map(|a| a.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/p/splat.ply",
+                "/p/web.spz",
+                "/p/cameras.json",
+                "/p/splat-2.ply"
+            ]
+        );
+
+        let web_only = [artifact("/p/cameras.json"), artifact("/p/web.spz")];
+        assert_eq!(primary(&web_only).unwrap().kind, ArtifactKind::Spz);
+        assert_eq!(primary(&[]), None);
+    }
+
+    #[test]
+    fn the_named_kind_wins_over_the_extension() {
+        let named = Artifact::named("/p/export.bin".to_string(), Some(ArtifactKind::Spz));
+        assert_eq!(named.kind, ArtifactKind::Spz);
+        assert_eq!(artifact("/p/SPLAT.PLY").kind, ArtifactKind::Ply);
+        let unknown: ArtifactKind = serde_json::from_str("\"ksplat\"").unwrap();
+        assert_eq!(unknown, ArtifactKind::Other);
+    }
+}
@@ -0,0 +1,173 @@
+//! Automatic Retries
+//!
+//! Some failures go away on their own, such as the GPU running out of memory
+//! right after another app closed, or a network share hiccuping while frames
+//! are written, and users only ever pressed retry for those. A job whose CLI
+//! exits with one of the retryable_codes is now run again by itself, up to
+//! max_retries times, after a backoff that doubles with each attempt. A
+//! retried job keeps its place in the queue and its job id, and starts again
+//! where its frames cache lets it, reported with job-auto-retrying. Its one
+//! history record lists every attempt.
+//!
+//! Only the CLI's exit codes are ever retried. Failures found before the CLI
+//! runs, such as invalid input, missing masks or a CLI that cannot be
+//! started, would fail the same way again. A cancelled job is never retried.
+
+use crate::job_log::unix_timestamp;
+use crate::messages::Failure;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// The failure parameter holding the exit code of a CLI that failed
+pub const EXIT_CODE_PARAM: &str = "exit_code";
+
+/// Most retries the settings may allow
+pub const MAX_RETRIES: u32 = 10;
+
+/// How often cancellation is checked during a backoff
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetrySettings {
+    /// Runs after the first; 0 turns retrying off
+    pub max_retries: u32,
+    /// Wait before the first retry; each later one waits twice as long as the one before
+    pub backoff_secs: u64,
+    /// Exit codes of the CLI that mean a transient failure; none until listed
+    pub retryable_codes: Vec<i32>,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        RetrySettings {
+            max_retries: 2,
+            backoff_secs: 30,
+            retryable_codes: vec![],
+        }
+    }
+}
+
+impl RetrySettings {
+    /// The exit code of `failure` when it is retryable and `attempt`, counted
+    /// from 1, may be followed by another
+    pub fn retryable(&self, failure: &Failure, attempt: u32) -> Option<i32> {
+        if attempt > self.max_retries {
+            return None;
+        }
+        let code = exit_code(failure)?;
+        self.retryable_codes.contains(&code).then_some(code)
+    }
+
+    /// How long to wait before retrying after `attempt`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        Duration::from_secs(self.backoff_secs.saturating_mul(factor))
+    }
+}
+
+/// The exit code a failed CLI run recorded in its failure
+pub fn exit_code(failure: &Failure) -> Option<i32> {
+    failure.message.params.get(EXIT_CODE_PARAM)?.parse().ok()
+}
+
+/// One run of a retried job's CLI, in its history record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attempt {
+    /// Counted from 1
+    pub attempt: u32,
+    pub started_at: u64,
+    pub finished_at: u64,
+    /// Why it failed; None for the attempt that succeeded
+    pub error: Option<String>,
+    pub exit_code: Option<i32>,
+    /// Where it started, when not at the beginning
+    pub resumed_from_stage: Option<String>,
+}
+
+impl Attempt {
+    /// An attempt started now
+    pub fn start(attempt: u32, resumed_from_stage: Option<String>) -> Attempt {
+        Attempt {
+            attempt,
+            started_at: unix_timestamp(),
+            finished_at: 0,
+            error: None,
+            exit_code: None,
+            resumed_from_stage,
+        }
+    }
+
+    pub fn finish<T>(&mut self, result: &Result<T, Failure>) {
+        self.finished_at = unix_timestamp();
+        if let Err(failure) = result {
+            self.error = Some(failure.to_string());
+            self.exit_code = exit_code(failure);
+        }
+    }
+}
+
+/// Sent as job-auto-retrying when a failed job is about to run again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AutoRetrying {
+    pub job_id: String,
+    /// The attempt about to start, counted from 1
+    pub attempt: u32,
+    pub max_attempts: u32,
+    /// The exit code that made the last attempt retryable
+    pub exit_code: i32,
+    pub backoff_secs: u64,
+    /// Where the attempt starts, when not at the beginning
+    pub resumes_from_stage: Option<String>,
+}
+
+/// Wait out a backoff; false when the job was cancelled meanwhile
+pub async fn wait(backoff: Duration, cancel: &AtomicBool) -> bool {
+    let until = tokio::time::Instant::now() + backoff;
+    loop {
+        if cancel.load(Ordering::SeqCst) {
+            return false;
+        }
+        let now = tokio::time::Instant::now();
+        if now >= until {
+            return true;
+        }
+        tokio::time::sleep((until - now).min(CANCEL_POLL_INTERVAL)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Message;
+
+    fn exited(code: i32) -> Failure {
+        Message::new("job.cli_exit_status")
+            .with("status", format!("exit status: {}", code))
+            .with(EXIT_CODE_PARAM, code)
+            .into()
+    }
+
+    #[test]
+    fn only_listed_exit_codes_are_retried_and_only_so_often() {
+        let settings = RetrySettings {
+            max_retries: 2,
+            backoff_secs: 10,
+            retryable_codes: vec![75],
+        };
+        assert_eq!(settings.retryable(&exited(75), 1), Some(75));
+        assert_eq!(settings.retryable(&exited(75), 2), Some(75));
+        assert_eq!(settings.retryable(&exited(75), 3), None);
+        assert_eq!(settings.retryable(&exited(1), 1), None);
+        // Failures from before the CLI ran carry no exit code
+        let invalid: Failure = Message::new("job.invalid_masks").into();
+        assert_eq!(settings.retryable(&invalid, 1), None);
+
+        assert_eq!(settings.backoff(1), Duration::from_secs(10));
+        assert_eq!(settings.backoff(3), Duration::from_secs(40));
+        assert_eq!(settings.backoff(200), Duration::from_secs(u64::MAX));
+    }
+}
@@ -0,0 +1,710 @@
+//! Cache
+//!
+//! One store for everything the app can compute again: clip thumbnails and
+//! metadata under app_data/cache, and the frames the backend extracts under the
+//! scratch directory. An entry is a file or directory at
+//! <root>/<category>/<name>, listed in an index with its size and when it was
+//! last used. The index is split over SHARDS files, each behind its own lock,
+//! so commands reading different entries do not wait on one another. Once an
+//! insert takes a cache past its limit, the least recently used entries no job
+//! has leased are deleted, whatever their category.
+//!
+//! An index is written before the entry it adds is moved into place, so a
+//! crash leaves at most an indexed entry without its file, which is dropped
+//! when the cache is next opened. An unreadable index is rebuilt from what is
+//! on disk. Last-use times are kept in memory and written with the next change
+//! to their shard.
+
+use crate::error::AppError;
+use crate::frames_cache;
+use crate::fsutil;
+use crate::platform::PathProvider;
+use crate::productions;
+use crate::settings::{AppSettings, SettingsStore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::AppHandle;
+
+/// Directory under app_data that holds the app's cache
+const DIR_NAME: &str = "cache";
+
+/// Default of the cacheMaxBytes setting
+pub const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Smallest cacheMaxBytes setting accepted
+pub const MIN_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Index files per cache
+const SHARDS: usize = 16;
+
+/// Names of entries still being written start with this
+const STAGING_PREFIX: &str = ".partial-";
+
+// The cacheMaxBytes setting, kept current by configure
+static MAX_BYTES: AtomicU64 = AtomicU64::new(DEFAULT_MAX_BYTES);
+
+// Caches opened so far, by root; each root is loaded once per run
+static OPEN: Mutex<BTreeMap<PathBuf, Arc<Cache>>> = Mutex::new(BTreeMap::new());
+
+// Last-use times handed out, so that no two are equal
+static CLOCK: AtomicU64 = AtomicU64::new(0);
+
+// Staging paths handed out, so that concurrent writers of one entry never collide
+static STAGED: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Thumbnails,
+    Metadata,
+    Frames,
+}
+
+impl Category {
+    const ALL: [Category; 3] = [Category::Thumbnails, Category::Metadata, Category::Frames];
+
+    fn dir_name(self) -> &'static str {
+        match self {
+            Category::Thumbnails => "thumbnails",
+            Category::Metadata => "metadata",
+            Category::Frames => "frames",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Entry {
+    category: Category,
+    name: String,
+    bytes: u64,
+    /// Milliseconds since the epoch
+    last_access: u64,
+    /// Leases held by running jobs; eviction skips the entry while any are
+    #[serde(skip)]
+    leases: usize,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Index {
+    entries: Vec<Entry>,
+}
+
+#[derive(Default)]
+struct Shard {
+    entries: BTreeMap<(Category, String), Entry>,
+    /// Holds last-use times not yet written
+    dirty: bool,
+}
+
+/// What get_cache_stats reports per category
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryStats {
+    pub category: Category,
+    pub dir: String,
+    pub entries: usize,
+    pub bytes: u64,
+    /// Limit of the cache the category is kept in, shared with its other categories
+    pub max_bytes: u64,
+}
+
+pub struct Cache {
+    root: PathBuf,
+    max_bytes: AtomicU64,
+    total: AtomicU64,
+    shards: Vec<Mutex<Shard>>,
+    // Held by an eviction pass, so two inserts do not both evict for the same overshoot
+    evicting: Mutex<()>,
+}
+
+/// An entry claimed by a job; eviction skips it until this is dropped
+pub struct Lease {
+    cache: Arc<Cache>,
+    category: Category,
+    name: String,
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut shard = self.cache.shard(self.category, &self.name);
+        if let Some(entry) = shard.entries.get_mut(&(self.category, self.name.clone())) {
+            entry.leases = entry.leases.saturating_sub(1);
+        }
+    }
+}
+
+/// Keep app_data/cache within the cacheMaxBytes setting, at startup and whenever the settings change
+pub fn configure(settings: &AppSettings) {
+    MAX_BYTES.store(settings.cache_max_bytes, Ordering::Relaxed);
+}
+
+/// The cache in app_data, for thumbnails and clip metadata
+pub fn app_cache(paths: &impl PathProvider) -> Result<Arc<Cache>, AppError> {
+    Ok(open(
+        &paths.app_data_dir()?.join(DIR_NAME),
+        MAX_BYTES.load(Ordering::Relaxed),
+    ))
+}
+
+/// The cache at `root`, held to `max_bytes`
+pub fn open(root: &Path, max_bytes: u64) -> Arc<Cache> {
+    let mut open = OPEN.lock().unwrap();
+    let cache = open
+        .entry(root.to_path_buf())
+        .or_insert_with(|| Arc::new(Cache::load(root)))
+        .clone();
+    cache.max_bytes.store(max_bytes, Ordering::Relaxed);
+    cache
+}
+
+impl Cache {
+    fn load(root: &Path) -> Cache {
+        remove_staged(root);
+        let mut shards: Vec<Shard> = (0..SHARDS).map(|_| Shard::default()).collect();
+        let mut unreadable = vec![];
+        for (i, shard) in shards.iter_mut().enumerate() {
+            let Some(index) = read_index(&index_path(root, i)) else {
+                unreadable.push(i);
+                continue;
+            };
+            for entry in index.entries {
+                // Indexed by an insert that crashed before moving the entry in
+                if !entry_path(root, entry.category, &entry.name).exists() {
+                    shard.dirty = true;
+                    continue;
+                }
+                shard
+                    .entries
+                    .insert((entry.category, entry.name.clone()), entry);
+            }
+        }
+        if !unreadable.is_empty() {
+            for entry in scan(root) {
+                let i = shard_of(entry.category, &entry.name);
+                if unreadable.contains(&i) {
+                    shards[i]
+                        .entries
+                        .insert((entry.category, entry.name.clone()), entry);
+                }
+            }
+            for &i in &unreadable {
+                shards[i].dirty = true;
+            }
+        }
+
+        let cache = Cache {
+            root: root.to_path_buf(),
+            max_bytes: AtomicU64::new(u64::MAX),
+            total: AtomicU64::new(
+                shards
+                    .iter()
+                    .flat_map(|s| s.entries.values())
+                    .map(|e| e.bytes)
+                    .sum(),
+            ),
+            shards: shards.into_iter().map(Mutex::new).collect(),
+            evicting: Mutex::new(()),
+        };
+        for i in 0..SHARDS {
+            let mut shard = cache.shards[i].lock().unwrap();
+            if shard.dirty {
+                cache.save_shard(i, &mut shard).ok();
+            }
+        }
+        cache
+    }
+
+    /// Where an entry is kept, whether or not it is cached
+    pub fn path(&self, category: Category, name: &str) -> PathBuf {
+        entry_path(&self.root, category, name)
+    }
+
+    /// The path of a cached entry, marking it used
+    pub fn get(&self, category: Category, name: &str) -> Option<PathBuf> {
+        self.touch(category, name, false)
+    }
+
+    /// The path of a cached entry, claimed for the caller
+    pub fn lease(self: &Arc<Self>, category: Category, name: &str) -> Option<(PathBuf, Lease)> {
+        let path = self.touch(category, name, true)?;
+        Some((path, self.lease_of(category, name)))
+    }
+
+    /// The content of a cached file
+    pub fn read(&self, category: Category, name: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.get(category, name)?).ok()
+    }
+
+    /// Cache `content` as a file
+    pub fn write(&self, category: Category, name: &str, content: &[u8]) -> Result<PathBuf, String> {
+        let staging = self.staging(category, name)?;
+        std::fs::write(&staging, content).map_err(|e| e.to_string())?;
+        self.insert(category, name, &staging)
+    }
+
+    /// A fresh path to write an entry to before insert. It ends in the entry's
+    /// name, so tools that go by the extension write the right format.
+    pub fn staging(&self, category: Category, name: &str) -> Result<PathBuf, String> {
+        let dir = self.root.join(category.dir_name());
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let n = STAGED.fetch_add(1, Ordering::Relaxed);
+        Ok(dir.join(format!("{}{}-{}", STAGING_PREFIX, n, name)))
+    }
+
+    /// Move what was written at `staging` into the cache, replacing any earlier
+    /// version of the entry, and evict to make room
+    pub fn insert(
+        &self,
+        category: Category,
+        name: &str,
+        staging: &Path,
+    ) -> Result<PathBuf, String> {
+        let path = self.put(category, name, staging, false)?;
+        self.evict();
+        Ok(path)
+    }
+
+    /// As insert, with the entry claimed for the caller
+    pub fn insert_leased(
+        self: &Arc<Self>,
+        category: Category,
+        name: &str,
+        staging: &Path,
+    ) -> Result<(PathBuf, Lease), String> {
+        let path = self.put(category, name, staging, true)?;
+        let lease = self.lease_of(category, name);
+        self.evict();
+        Ok((path, lease))
+    }
+
+    /// Delete every entry of `category` no job has leased, returning the bytes freed
+    pub fn clear(&self, category: Category) -> u64 {
+        self.shrink(0, Some(category))
+    }
+
+    pub fn stats(&self, category: Category) -> CategoryStats {
+        let (mut entries, mut bytes) = (0, 0);
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap();
+            for entry in shard.entries.values().filter(|e| e.category == category) {
+                entries += 1;
+                bytes += entry.bytes;
+            }
+        }
+        CategoryStats {
+            category,
+            dir: self
+                .root
+                .join(category.dir_name())
+                .to_string_lossy()
+                .to_string(),
+            entries,
+            bytes,
+            max_bytes: self.max_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn shard(&self, category: Category, name: &str) -> MutexGuard<'_, Shard> {
+        self.shards[shard_of(category, name)].lock().unwrap()
+    }
+
+    fn lease_of(self: &Arc<Self>, category: Category, name: &str) -> Lease {
+        Lease {
+            cache: self.clone(),
+            category,
+            name: name.to_string(),
+        }
+    }
+
+    fn touch(&self, category: Category, name: &str, lease: bool) -> Option<PathBuf> {
+        let path = self.path(category, name);
+        let key = (category, name.to_string());
+        let mut shard = self.shard(category, name);
+        if !shard.entries.contains_key(&key) {
+            return None;
+        }
+        shard.dirty = true;
+        if !path.exists() {
+            // Deleted behind the cache's back
+            if let Some(entry) = shard.entries.remove(&key) {
+                self.total.fetch_sub(entry.bytes, Ordering::Relaxed);
+            }
+            return None;
+        }
+        let entry = shard.entries.get_mut(&key)?;
+        entry.last_access = now();
+        if lease {
+            entry.leases += 1;
+        }
+        Some(path)
+    }
+
+    fn put(
+        &self,
+        category: Category,
+        name: &str,
+        staging: &Path,
+        lease: bool,
+    ) -> Result<PathBuf, String> {
+        let bytes = size(staging).map_err(|e| e.to_string())?;
+        let path = self.path(category, name);
+        let key = (category, name.to_string());
+        let i = shard_of(category, name);
+        let mut shard = self.shards[i].lock().unwrap();
+        let earlier = shard.entries.get(&key).cloned();
+        let entry = Entry {
+            category,
+            name: name.to_string(),
+            bytes,
+            last_access: now(),
+            leases: earlier.as_ref().map_or(0, |e| e.leases) + usize::from(lease),
+        };
+        shard.entries.insert(key.clone(), entry);
+
+        let moved = self
+            .save_shard(i, &mut shard)
+            .and_then(|()| replace(staging, &path).map_err(|e| e.to_string()));
+        if let Err(e) = moved {
+            match earlier {
+                Some(earlier) => shard.entries.insert(key, earlier),
+                None => shard.entries.remove(&key),
+            };
+            self.save_shard(i, &mut shard).ok();
+            remove(staging).ok();
+            return Err(e);
+        }
+        self.total.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(earlier) = earlier {
+            self.total.fetch_sub(earlier.bytes, Ordering::Relaxed);
+        }
+        Ok(path)
+    }
+
+    fn evict(&self) {
+        self.shrink(self.max_bytes.load(Ordering::Relaxed), None);
+    }
+
+    /// Delete the least recently used entries no job has leased, of `only` or
+    /// of any category, until the cache fits in `max_bytes`
+    fn shrink(&self, max_bytes: u64, only: Option<Category>) -> u64 {
+        let _evicting = self.evicting.lock().unwrap();
+        if self.total.load(Ordering::Relaxed) <= max_bytes {
+            return 0;
+        }
+        let mut candidates = vec![];
+        for (i, shard) in self.shards.iter().enumerate() {
+            let shard = shard.lock().unwrap();
+            candidates.extend(
+                shard
+                    .entries
+                    .values()
+                    .filter(|e| e.leases == 0 && only.map_or(true, |c| c == e.category))
+                    .map(|e| (e.last_access, i, e.category, e.name.clone())),
+            );
+        }
+        candidates.sort();
+
+        let mut freed = 0;
+        let mut changed = [false; SHARDS];
+        for (last_access, i, category, name) in candidates {
+            if self.total.load(Ordering::Relaxed) <= max_bytes {
+                break;
+            }
+            let mut shard = self.shards[i].lock().unwrap();
+            let key = (category, name);
+            // Used or leased since the candidates were gathered
+            let unchanged = shard
+                .entries
+                .get(&key)
+                .is_some_and(|e| e.last_access == last_access && e.leases == 0);
+            // One that cannot be deleted, such as a file open elsewhere on Windows, stays listed
+            if !unchanged || remove(&self.path(category, &key.1)).is_err() {
+                continue;
+            }
+            if let Some(entry) = shard.entries.remove(&key) {
+                self.total.fetch_sub(entry.bytes, Ordering::Relaxed);
+                freed += entry.bytes;
+                changed[i] = true;
+            }
+        }
+        for (i, _) in changed.iter().enumerate().filter(|(_, changed)| **changed) {
+            let mut shard = self.shards[i].lock().unwrap();
+            self.save_shard(i, &mut shard).ok();
+        }
+        freed
+    }
+
+    fn save_shard(&self, i: usize, shard: &mut Shard) -> Result<(), String> {
+        std::fs::create_dir_all(&self.root).map_err(|e| e.to_string())?;
+        let index = Index {
+            entries: shard.entries.values().cloned().collect(),
+        };
+        fsutil::write_json_atomic(&index_path(&self.root, i), &index)?;
+        shard.dirty = false;
+        Ok(())
+    }
+}
+
+fn entry_path(root: &Path, category: Category, name: &str) -> PathBuf {
+    root.join(category.dir_name()).join(name)
+}
+
+fn index_path(root: &Path, shard: usize) -> PathBuf {
+    root.join(format!("index-{:02}.json", shard))
+}
+
+fn shard_of(category: Category, name: &str) -> usize {
+    let digest = Sha256::digest(format!("{}/{}", category.dir_name(), name).as_bytes());
+    digest[0] as usize % SHARDS
+}
+
+// A missing index is as good as a corrupt one: either way it is rebuilt
+fn read_index(path: &Path) -> Option<Index> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// The entries on disk, used when they were last modified
+fn scan(root: &Path) -> Vec<Entry> {
+    let mut entries = vec![];
+    for category in Category::ALL {
+        let Ok(dir) = std::fs::read_dir(root.join(category.dir_name())) else {
+            continue;
+        };
+        for file in dir.flatten() {
+            let name = file.file_name().to_string_lossy().to_string();
+            let Ok(metadata) = file.metadata() else {
+                continue;
+            };
+            entries.push(Entry {
+                category,
+                bytes: size(&file.path()).unwrap_or(0),
+                last_access: metadata.modified().map(millis).unwrap_or(0),
+                leases: 0,
+                name,
+            });
+        }
+    }
+    entries
+}
+
+/// Delete what writers left behind when the app last stopped
+fn remove_staged(root: &Path) {
+    for category in Category::ALL {
+        let Ok(dir) = std::fs::read_dir(root.join(category.dir_name())) else {
+            continue;
+        };
+        for file in dir.flatten() {
+            if file
+                .file_name()
+                .to_string_lossy()
+                .starts_with(STAGING_PREFIX)
+            {
+                remove(&file.path()).ok();
+            }
+        }
+    }
+}
+
+fn size(path: &Path) -> std::io::Result<u64> {
+    let metadata = std::fs::metadata(path)?;
+    if metadata.is_dir() {
+        productions::dir_size(path)
+    } else {
+        Ok(metadata.len())
+    }
+}
+
+fn remove(path: &Path) -> std::io::Result<()> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn replace(staging: &Path, path: &Path) -> std::io::Result<()> {
+    // rename replaces a file but not a directory
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    }
+    std::fs::rename(staging, path)
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn now() -> u64 {
+    let now = millis(SystemTime::now());
+    let last = CLOCK
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap_or(0);
+    now.max(last + 1)
+}
+
+/// Size and limits of each cache category
+#[tauri::command]
+pub async fn get_cache_stats(app: AppHandle) -> Result<Vec<CategoryStats>, AppError> {
+    let cache = app_cache(&app)?;
+    let frames = frames_cache::cache(app.settings().scratch_dir.as_deref());
+    Ok(vec![
+        cache.stats(Category::Thumbnails),
+        cache.stats(Category::Metadata),
+        frames.stats(Category::Frames),
+    ])
+}
+
+/// Delete every entry of a category no running job uses, returning the bytes freed
+#[tauri::command]
+pub async fn clear_cache(app: AppHandle, category: Category) -> Result<u64, AppError> {
+    let cache = match category {
+        Category::Frames => frames_cache::cache(app.settings().scratch_dir.as_deref()),
+        _ => app_cache(&app)?,
+    };
+    Ok(cache.clear(category))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn names(cache: &Cache, category: Category) -> Vec<String> {
+        let mut names: Vec<String> = cache
+            .shards
+            .iter()
+            .flat_map(|s| {
+                let shard = s.lock().unwrap();
+                shard
+                    .entries
+                    .values()
+                    .filter(|e| e.category == category)
+                    .map(|e| e.name.clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn least_recently_used_entries_not_leased_are_evicted() {
+        let paths = TempPaths::new();
+        let cache = open(&paths.root().join("lru"), 30);
+        cache
+            .write(Category::Thumbnails, "a.jpg", &[0; 10])
+            .unwrap();
+        cache.write(Category::Metadata, "b.json", &[0; 10]).unwrap();
+        let frames = cache.staging(Category::Frames, "c").unwrap();
+        std::fs::create_dir_all(&frames).unwrap();
+        std::fs::write(frames.join("frame_00001.png"), [0; 10]).unwrap();
+        let (_, lease) = cache.insert_leased(Category::Frames, "c", &frames).unwrap();
+
+        // a is used again, so b is now the oldest
+        assert!(cache.get(Category::Thumbnails, "a.jpg").is_some());
+        cache
+            .write(Category::Thumbnails, "d.jpg", &[0; 10])
+            .unwrap();
+        assert_eq!(names(&cache, Category::Metadata), Vec::<String>::new());
+        assert!(!cache.path(Category::Metadata, "b.json").exists());
+
+        // c is leased, so a goes instead
+        cache
+            .write(Category::Thumbnails, "e.jpg", &[0; 10])
+            .unwrap();
+        assert_eq!(names(&cache, Category::Thumbnails), ["d.jpg", "e.jpg"]);
+        assert_eq!(names(&cache, Category::Frames), ["c"]);
+
+        drop(lease);
+        assert_eq!(cache.clear(Category::Frames), 10);
+        assert_eq!(cache.stats(Category::Frames).entries, 0);
+        assert_eq!(cache.stats(Category::Thumbnails).bytes, 20);
+    }
+
+    #[test]
+    fn a_corrupt_index_is_rebuilt_from_disk() {
+        let paths = TempPaths::new();
+        let root = paths.root().join("rebuild");
+        let cache = open(&root, u64::MAX);
+        for i in 0..20 {
+            let name = format!("{}.json", i);
+            cache.write(Category::Metadata, &name, b"{}").unwrap();
+        }
+        // Indexed, but the crash came before the file was moved in
+        let i = shard_of(Category::Metadata, "0.json");
+        std::fs::remove_file(cache.path(Category::Metadata, "0.json")).unwrap();
+        for shard in 0..SHARDS {
+            if shard != i {
+                std::fs::write(index_path(&root, shard), b"{\"entries\": [").unwrap();
+            }
+        }
+        std::fs::write(cache.path(Category::Metadata, ".partial-7-3.json"), b"{").unwrap();
+
+        let reloaded = Cache::load(&root);
+        let expected: Vec<String> = {
+            let mut names: Vec<String> = (1..20).map(|i| format!("{}.json", i)).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(names(&reloaded, Category::Metadata), expected);
+        assert_eq!(reloaded.total.load(Ordering::Relaxed), 19 * 2);
+        assert!(!cache.path(Category::Metadata, ".partial-7-3.json").exists());
+        assert!(read_index(&index_path(&root, (i + 1) % SHARDS)).is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_readers_and_writers_keep_the_index_consistent() {
+        let paths = TempPaths::new();
+        let root = paths.root().join("concurrent");
+        let cache = open(&root, 1000);
+        let tasks: Vec<_> = (0..8)
+            .map(|task| {
+                let cache = cache.clone();
+                tokio::spawn(async move {
+                    for i in 0..40 {
+                        let thumbnail = format!("{}.jpg", i % 4);
+                        if (task + i) % 2 == 0 {
+                            cache
+                                .write(Category::Thumbnails, &thumbnail, &[0; 100])
+                                .unwrap();
+                        } else if let Some(content) = cache.read(Category::Thumbnails, &thumbnail) {
+                            assert_eq!(content.len(), 100);
+                        }
+                        let metadata = format!("{}.json", (task * 40 + i) % 16);
+                        cache
+                            .write(Category::Metadata, &metadata, &[0; 100])
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        let total = cache.total.load(Ordering::Relaxed);
+        assert!(total <= 1000);
+        let listed =
+            cache.stats(Category::Thumbnails).bytes + cache.stats(Category::Metadata).bytes;
+        assert_eq!(listed, total);
+        let on_disk: u64 = scan(&root).iter().map(|e| e.bytes).sum();
+        assert_eq!(on_disk, total);
+
+        let reloaded = Cache::load(&root);
+        assert_eq!(reloaded.total.load(Ordering::Relaxed), total);
+        for category in [Category::Thumbnails, Category::Metadata] {
+            assert_eq!(names(&reloaded, category), names(&cache, category));
+        }
+    }
+}
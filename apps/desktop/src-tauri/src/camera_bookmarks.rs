@@ -0,0 +1,232 @@
+//! Camera Bookmarks
+//!
+//! Viewpoints reviewers saved in the viewer, by name, so a good angle is not
+//! lost every time the production is reopened. They are kept in
+//! production.gvview.json next to the production's sidecar rather than in it:
+//! jobs rewrite the sidecar, and the bookmarks belong to the production, not
+//! to the artifact they happened to be saved with, so they survive it being
+//! made again. The file is created by the first bookmark, whether or not the
+//! production has a sidecar.
+//!
+//! The camera state is the viewer's to define. Version 1 holds `position`,
+//! `target` and `up` as [x, y, z] and `fov` in degrees; the backend keeps
+//! whatever it is given and only requires it to say which version it is.
+
+use crate::error::AppError;
+use crate::fsutil;
+use crate::job_log::unix_timestamp;
+use crate::path_policy::PathPolicy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+
+pub const VIEW_NAME: &str = "production.gvview.json";
+
+/// Held while a view file is read, changed and written back
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// Where the viewer's camera was, in a format of the viewer's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub version: u32,
+    #[serde(flatten)]
+    pub state: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraBookmark {
+    /// Unique within the production
+    pub name: String,
+    pub camera_state: CameraState,
+    pub saved_at: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ViewFile {
+    #[serde(default)]
+    bookmarks: Vec<CameraBookmark>,
+}
+
+/// Save the camera as `name`, replacing the bookmark of that name if there is one
+#[tauri::command]
+pub async fn save_camera_bookmark(
+    policy: State<'_, PathPolicy>,
+    production: String,
+    name: String,
+    camera_state: CameraState,
+) -> Result<CameraBookmark, AppError> {
+    policy.check_existing(&production)?;
+    save(Path::new(&production), &name, camera_state)
+}
+
+/// The bookmarks of a production, in the order they were first saved
+#[tauri::command]
+pub async fn list_camera_bookmarks(
+    policy: State<'_, PathPolicy>,
+    production: String,
+) -> Result<Vec<CameraBookmark>, AppError> {
+    policy.check_existing(&production)?;
+    Ok(load(&view_path(Path::new(&production))?)?.bookmarks)
+}
+
+/// Delete the bookmark called `name`, if there is one
+#[tauri::command]
+pub async fn delete_camera_bookmark(
+    policy: State<'_, PathPolicy>,
+    production: String,
+    name: String,
+) -> Result<(), AppError> {
+    policy.check_existing(&production)?;
+    delete(Path::new(&production), &name)
+}
+
+fn view_path(production_dir: &Path) -> Result<PathBuf, AppError> {
+    if !production_dir.is_dir() {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a production directory",
+            production_dir.display()
+        )));
+    }
+    Ok(production_dir.join(VIEW_NAME))
+}
+
+fn load(path: &Path) -> Result<ViewFile, AppError> {
+    if !path.exists() {
+        return Ok(ViewFile::default());
+    }
+    let content = std::fs::read_to_string(path)?;
+    // An unreadable file is reported rather than replaced, which would lose its bookmarks
+    serde_json::from_str(&content)
+        .map_err(|e| AppError::Io(format!("{} is not valid: {}", path.display(), e)))
+}
+
+pub fn save(
+    production_dir: &Path,
+    name: &str,
+    camera_state: CameraState,
+) -> Result<CameraBookmark, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput(
+            "A bookmark needs a name".to_string(),
+        ));
+    }
+    if camera_state.version == 0 {
+        return Err(AppError::InvalidInput(
+            "The camera state has no version".to_string(),
+        ));
+    }
+    let path = view_path(production_dir)?;
+    let bookmark = CameraBookmark {
+        name: name.to_string(),
+        camera_state,
+        saved_at: unix_timestamp(),
+    };
+
+    let _writing = WRITING.lock().unwrap();
+    let mut view = load(&path)?;
+    match view.bookmarks.iter_mut().find(|b| b.name == name) {
+        Some(existing) => *existing = bookmark.clone(),
+        None => view.bookmarks.push(bookmark.clone()),
+    }
+    fsutil::write_json_atomic(&path, &view)?;
+    Ok(bookmark)
+}
+
+/// The bookmark of a production called `name`, if there is one
+pub fn find(production_dir: &Path, name: &str) -> Result<Option<CameraBookmark>, AppError> {
+    let view = load(&view_path(production_dir)?)?;
+    Ok(view.bookmarks.into_iter().find(|b| b.name == name.trim()))
+}
+
+pub fn delete(production_dir: &Path, name: &str) -> Result<(), AppError> {
+    let path = view_path(production_dir)?;
+    let _writing = WRITING.lock().unwrap();
+    let mut view = load(&path)?;
+    let count = view.bookmarks.len();
+    view.bookmarks.retain(|b| b.name != name.trim());
+    if view.bookmarks.len() != count {
+        fsutil::write_json_atomic(&path, &view)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use crate::sidecar;
+    use serde_json::json;
+
+    fn camera(fov: f64) -> CameraState {
+        serde_json::from_value(json!({
+            "version": 1,
+            "position": [0.0, 1.5, 4.0],
+            "target": [0.0, 0.0, 0.0],
+            "up": [0.0, 1.0, 0.0],
+            "fov": fov,
+        }))
+        .unwrap()
+    }
+
+    fn names(production: &Path) -> Vec<String> {
+        let view = load(&view_path(production).unwrap()).unwrap();
+        view.bookmarks.into_iter().map(|b| b.name).collect()
+    }
+
+    #[test]
+    fn bookmarks_are_unique_by_name_and_outlive_the_sidecar() {
+        let paths = TempPaths::new();
+        let production = paths.root().join("harbour");
+        std::fs::create_dir_all(&production).unwrap();
+        assert!(names(&production).is_empty());
+
+        // No sidecar yet
+        save(&production, "Front", camera(50.0)).unwrap();
+        save(&production, " Pier ", camera(35.0)).unwrap();
+        save(&production, "Front", camera(70.0)).unwrap();
+        assert_eq!(names(&production), ["Front", "Pier"]);
+
+        // A job making the artifact again rewrites the sidecar only
+        sidecar::write(&production, &sidecar::Sidecar::default()).unwrap();
+        let view = load(&production.join(VIEW_NAME)).unwrap();
+        assert_eq!(view.bookmarks[0].camera_state, camera(70.0));
+        assert_eq!(view.bookmarks[0].camera_state.state["fov"], json!(70.0));
+
+        delete(&production, "Pier").unwrap();
+        delete(&production, "Pier").unwrap();
+        assert_eq!(names(&production), ["Front"]);
+    }
+
+    #[test]
+    fn rejects_unnamed_or_unversioned_bookmarks_and_broken_files() {
+        let paths = TempPaths::new();
+        let production = paths.root().to_path_buf();
+        assert!(matches!(
+            save(&production, "  ", camera(50.0)),
+            Err(AppError::InvalidInput(_))
+        ));
+        let unversioned = CameraState {
+            version: 0,
+            ..camera(50.0)
+        };
+        assert!(matches!(
+            save(&production, "Front", unversioned),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(serde_json::from_value::<CameraState>(json!({ "fov": 50.0 })).is_err());
+
+        std::fs::write(production.join(VIEW_NAME), b"{ not json").unwrap();
+        assert!(matches!(
+            save(&production, "Front", camera(50.0)),
+            Err(AppError::Io(_))
+        ));
+        assert_eq!(
+            std::fs::read(production.join(VIEW_NAME)).unwrap(),
+            b"{ not json"
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! Camera Groups
+//!
+//! COLMAP does better when it knows which clips came from one physical
+//! camera, as they share intrinsics. A clip's options may name its
+//! camera_group. The CLI gets each group's clips together, groups in the order
+//! of their first clip and clips in the order the user gave them, and, when it
+//! supports --camera-group, which inputs each group holds. validate_videos
+//! suggests groups from the devices the clips name, so the UI can start from
+//! them: clips of one make, model, lens and serial number together, as each
+//! lens of a phone is a camera of its own. The grouping a job ran with is kept
+//! in its sidecar.
+
+use crate::camera_intrinsics::CaptureDevice;
+use crate::capabilities::{CliCapabilities, FLAG_CAMERA_GROUP};
+use crate::commands::ProcessArgs;
+use crate::messages::Message;
+use serde::{Deserialize, Serialize};
+
+/// Longest group name
+pub const MAX_NAME_CHARS: usize = 64;
+
+/// Clips shot with one camera
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CameraGroup {
+    pub name: String,
+    pub clips: Vec<String>,
+}
+
+/// What is wrong with a group name, if anything
+pub fn check_name(name: &str) -> Option<Message> {
+    if name.trim().is_empty() {
+        Some(Message::new("args.empty"))
+    } else if name.chars().count() > MAX_NAME_CHARS {
+        Some(Message::new("args.too_long").with("max", MAX_NAME_CHARS))
+    } else if name.chars().any(char::is_control) {
+        Some(Message::new("args.invalid").with("detail", "control characters"))
+    } else {
+        None
+    }
+}
+
+/// The request's clips with each group's together, groups in the order of
+/// their first clip and clips otherwise in the order given
+pub fn ordered(args: &ProcessArgs) -> Vec<String> {
+    let mut ordered: Vec<String> = vec![];
+    for (i, video) in args.videos.iter().enumerate() {
+        if ordered.contains(video) {
+            continue;
+        }
+        ordered.push(video.clone());
+        let Some(group) = group_of(args, video) else {
+            continue;
+        };
+        for later in &args.videos[i + 1..] {
+            if group_of(args, later).as_ref() == Some(&group) && !ordered.contains(later) {
+                ordered.push(later.clone());
+            }
+        }
+    }
+    ordered
+}
+
+/// The named groups of a request, in the order their clips are handed over
+pub fn of(args: &ProcessArgs) -> Vec<CameraGroup> {
+    let mut groups: Vec<CameraGroup> = vec![];
+    for video in &args.videos {
+        let Some(name) = group_of(args, video) else {
+            continue;
+        };
+        match groups.iter_mut().find(|g| g.name == name) {
+            Some(group) => group.clips.push(video.clone()),
+            None => groups.push(CameraGroup {
+                name,
+                clips: vec![video.clone()],
+            }),
+        }
+    }
+    groups
+}
+
+/// The group a clip is in, its name trimmed
+pub fn group_of(args: &ProcessArgs, video: &str) -> Option<String> {
+    let group = args.clip_options(video).camera_group?;
+    Some(group.trim().to_string()).filter(|g| !g.is_empty())
+}
+
+/// The --camera-group arguments for clips handed to the CLI as the given
+/// inputs, each with its group, and the line to log when they could not be
+/// passed. A group's inputs are joined like the entries of PATH.
+pub fn group_args(
+    inputs: &[(String, Option<String>)],
+    caps: &CliCapabilities,
+) -> (Vec<String>, Option<String>) {
+    let mut groups: Vec<(&str, Vec<&str>)> = vec![];
+    for (input, group) in inputs {
+        let Some(group) = group else { continue };
+        match groups.iter_mut().find(|(name, _)| name == group) {
+            Some((_, members)) => members.push(input.as_str()),
+            None => groups.push((group.as_str(), vec![input.as_str()])),
+        }
+    }
+    if groups.is_empty() {
+        return (vec![], None);
+    }
+    if !caps.supports(FLAG_CAMERA_GROUP) {
+        let skipped = format!(
+            "The installed gvcore-cli does not support {}; COLMAP treats every clip as its own camera",
+            FLAG_CAMERA_GROUP
+        );
+        return (vec![], Some(skipped));
+    }
+    let mut args = vec![];
+    for (name, members) in groups {
+        match std::env::join_paths(&members) {
+            Ok(joined) => {
+                args.push(FLAG_CAMERA_GROUP.to_string());
+                args.push(name.to_string());
+                args.push(joined.to_string_lossy().to_string());
+            }
+            Err(_) => {
+                let skipped = format!(
+                    "A clip of camera group {} has a path separator in its name, so no group was passed",
+                    name
+                );
+                return (vec![], Some(skipped));
+            }
+        }
+    }
+    (args, None)
+}
+
+/// Groups of the clips whose metadata names one device, named after it
+pub fn suggest(clips: &[(&str, Option<&CaptureDevice>)]) -> Vec<CameraGroup> {
+    let mut devices: Vec<(&CaptureDevice, Vec<String>)> = vec![];
+    for (clip, device) in clips {
+        let Some(device) = *device else { continue };
+        match devices.iter_mut().find(|(d, _)| same_camera(d, device)) {
+            Some((_, clips)) => clips.push(clip.to_string()),
+            None => devices.push((device, vec![clip.to_string()])),
+        }
+    }
+    let mut groups: Vec<CameraGroup> = vec![];
+    for (device, clips) in devices {
+        let base: String = format!("{} {}", device.make.trim(), device.model.trim())
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(MAX_NAME_CHARS - 4)
+            .collect();
+        // Two lenses or two bodies of one model
+        let mut name = base.clone();
+        let mut n = 1;
+        while groups.iter().any(|g| g.name == name) {
+            n += 1;
+            name = format!("{} {}", base, n);
+        }
+        groups.push(CameraGroup { name, clips });
+    }
+    groups
+}
+
+fn same_camera(a: &CaptureDevice, b: &CaptureDevice) -> bool {
+    let key = |d: &CaptureDevice| {
+        [
+            Some(&d.make),
+            Some(&d.model),
+            d.lens.as_ref(),
+            d.serial.as_ref(),
+        ]
+        .map(|part| part.map(|p| p.trim().to_lowercase()))
+    };
+    key(a) == key(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ClipOptions;
+    use serde_json::json;
+
+    fn request(clips: &[(&str, Option<&str>)]) -> ProcessArgs {
+        let videos: Vec<&str> = clips.iter().map(|(video, _)| *video).collect();
+        let mut args: ProcessArgs = serde_json::from_value(json!({
+            "videos": videos,
+            "output_dir": "/out/harbour",
+            "preset": "balanced",
+        }))
+        .unwrap();
+        args.clips = clips
+            .iter()
+            .map(|(video, group)| ClipOptions {
+                path: video.to_string(),
+                camera_group: group.map(String::from),
+                ..Default::default()
+            })
+            .collect();
+        args
+    }
+
+    fn device(model: &str, lens: Option<&str>, serial: Option<&str>) -> CaptureDevice {
+        CaptureDevice {
+            make: "DJI".to_string(),
+            model: model.to_string(),
+            lens: lens.map(String::from),
+            serial: serial.map(String::from),
+        }
+    }
+
+    #[test]
+    fn names_are_checked() {
+        assert_eq!(check_name("Drone A"), None);
+        assert_eq!(check_name("  ").unwrap().key, "args.empty");
+        assert_eq!(check_name(&"x".repeat(65)).unwrap().key, "args.too_long");
+        assert_eq!(check_name("a\nb").unwrap().key, "args.invalid");
+    }
+
+    #[test]
+    fn clips_of_a_group_are_handed_over_together_in_the_given_order() {
+        let args = request(&[
+            ("/clips/d1.mp4", Some("drone")),
+            ("/clips/p1.mp4", Some("phone")),
+            ("/clips/x.mp4", None),
+            ("/clips/d2.mp4", Some(" drone ")),
+            ("/clips/p2.mp4", Some("phone")),
+        ]);
+        assert_eq!(
+            ordered(&args),
+            [
+                "/clips/d1.mp4",
+                "/clips/d2.mp4",
+                "/clips/p1.mp4",
+                "/clips/p2.mp4",
+                "/clips/x.mp4"
+            ]
+        );
+        assert_eq!(
+            of(&args),
+            [
+                CameraGroup {
+                    name: "drone".to_string(),
+                    clips: vec!["/clips/d1.mp4".to_string(), "/clips/d2.mp4".to_string()],
+                },
+                CameraGroup {
+                    name: "phone".to_string(),
+                    clips: vec!["/clips/p1.mp4".to_string(), "/clips/p2.mp4".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_are_passed_only_to_a_cli_that_takes_them() {
+        let inputs = vec![
+            ("/clips/a.mp4".to_string(), Some("drone".to_string())),
+            ("/frames/b".to_string(), Some("drone".to_string())),
+            ("/clips/c.mp4".to_string(), None),
+        ];
+        let caps = CliCapabilities {
+            flags: vec![FLAG_CAMERA_GROUP.to_string()],
+            ..Default::default()
+        };
+        let (args, skipped) = group_args(&inputs, &caps);
+        let joined = std::env::join_paths(["/clips/a.mp4", "/frames/b"]).unwrap();
+        assert_eq!(
+            args,
+            [
+                FLAG_CAMERA_GROUP.to_string(),
+                "drone".to_string(),
+                joined.to_string_lossy().to_string()
+            ]
+        );
+        assert_eq!(skipped, None);
+
+        let (args, skipped) = group_args(&inputs, &CliCapabilities::default());
+        assert!(args.is_empty());
+        assert!(skipped.unwrap().contains(FLAG_CAMERA_GROUP));
+
+        let ungrouped = [("/clips/c.mp4".to_string(), None)];
+        assert_eq!(group_args(&ungrouped, &caps), (vec![], None));
+    }
+
+    #[test]
+    fn devices_suggest_groups() {
+        let mini_a = device("Mini 4 Pro", None, Some("1581F6"));
+        let mini_b = device("Mini 4 Pro", None, Some("1581F7"));
+        let mini_a_again = device(" mini 4 pro", None, Some("1581F6"));
+        let groups = suggest(&[
+            ("/clips/1.mp4", Some(&mini_a)),
+            ("/clips/2.mp4", None),
+            ("/clips/3.mp4", Some(&mini_b)),
+            ("/clips/4.mp4", Some(&mini_a_again)),
+        ]);
+        let named: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|g| {
+                (
+                    g.name.as_str(),
+                    g.clips.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            named,
+            [
+                ("DJI Mini 4 Pro", vec!["/clips/1.mp4", "/clips/4.mp4"]),
+                ("DJI Mini 4 Pro 2", vec!["/clips/3.mp4"]),
+            ]
+        );
+        assert!(groups.iter().all(|g| check_name(&g.name).is_none()));
+    }
+}
@@ -0,0 +1,451 @@
+//! Camera Intrinsics Hints
+//!
+//! COLMAP estimates every camera's focal length and principal point, which
+//! is slow and sometimes wrong for phones and drones whose optics are well
+//! known. Clips carry the make, model and often the lens of the device that
+//! shot them, and a clip matching an entry of the built-in table or of the
+//! known_devices setting has that device's intrinsics passed to the CLI as a
+//! prior, scaled to the resolution the CLI gets the clip at.
+//!
+//! Clips of one device share --intrinsics-hint; clips of different devices
+//! each get --camera-intrinsics-hint when the CLI supports it and no hints at
+//! all otherwise, as one hint for all of them would be wrong for some.
+//!
+//! Hints are advisory. When COLMAP finds a hint does not fit the images it
+//! warns with MISMATCH_WARNING and uses its own estimate, which is only logged.
+
+use crate::capabilities::{CliCapabilities, FLAG_CAMERA_INTRINSICS_HINT, FLAG_INTRINSICS_HINT};
+use crate::error::AppError;
+use crate::media::Resolution;
+use crate::messages::Message;
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tauri::AppHandle;
+
+/// Warning code of a hint COLMAP did not keep
+pub const MISMATCH_WARNING: &str = "intrinsics-hint-mismatch";
+
+/// Range a 35mm-equivalent focal length may be given in
+pub const MIN_FOCAL_LENGTH_35MM: f64 = 4.0;
+pub const MAX_FOCAL_LENGTH_35MM: f64 = 1200.0;
+
+// Width of the 35mm frame focal lengths are given against
+const FULL_FRAME_WIDTH_MM: f64 = 36.0;
+
+// Tags ffprobe reports a clip's device in, most specific first
+const MAKE_TAGS: [&str; 3] = [
+    "com.apple.quicktime.make",
+    "com.android.manufacturer",
+    "make",
+];
+const MODEL_TAGS: [&str; 3] = ["com.apple.quicktime.model", "com.android.model", "model"];
+const LENS_TAGS: [&str; 2] = ["com.apple.quicktime.camera.lens_model", "lens_model"];
+const SERIAL_TAGS: [&str; 2] = ["com.apple.quicktime.camera.serial_number", "serial_number"];
+
+/// Devices whose video intrinsics are known, as (make, model, lens, focal
+/// length); an entry with a lens matches only clips naming it
+const BUILTIN: [(&str, &str, Option<&str>, f64); 8] = [
+    ("Apple", "iPhone 15 Pro", Some("6.765mm"), 26.0),
+    ("Apple", "iPhone 15 Pro", Some("2.22mm"), 14.0),
+    ("Apple", "iPhone 15 Pro Max", Some("6.765mm"), 26.0),
+    ("Apple", "iPhone 15 Pro Max", Some("2.22mm"), 14.0),
+    ("Apple", "iPhone 14 Pro", Some("6.86mm"), 26.0),
+    ("DJI", "Mini 4 Pro", None, 24.0),
+    ("DJI", "Mini 3 Pro", None, 24.0),
+    ("DJI", "Air 2S", None, 22.0),
+];
+
+/// The device a clip says it was shot with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureDevice {
+    pub make: String,
+    pub model: String,
+    pub lens: Option<String>,
+    /// Tells apart two cameras of one model, when the clip names it
+    #[serde(default)]
+    pub serial: Option<String>,
+}
+
+/// Intrinsics of one device, or of one lens of it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceIntrinsics {
+    pub make: String,
+    pub model: String,
+    /// Part of the clip's lens name, e.g. "2.22mm" for an ultra wide camera;
+    /// any lens of the model when absent
+    #[serde(default)]
+    pub lens: Option<String>,
+    /// Focal length in video, as its 35mm equivalent
+    pub focal_length_35mm: f64,
+    /// Principal point as fractions of the frame's width and height; the
+    /// centre when absent
+    #[serde(default)]
+    pub principal_point: Option<[f64; 2]>,
+}
+
+impl DeviceIntrinsics {
+    fn matches(&self, device: &CaptureDevice) -> bool {
+        let lens_matches = match (&self.lens, &device.lens) {
+            (None, _) => true,
+            (Some(lens), Some(clip)) => normalized(clip).contains(&normalized(lens)),
+            (Some(_), None) => false,
+        };
+        normalized(&self.make) == normalized(&device.make)
+            && normalized(&self.model) == normalized(&device.model)
+            && lens_matches
+    }
+
+    /// The hint for a frame of `resolution`
+    pub fn hint(&self, resolution: Resolution) -> Hint {
+        let [x, y] = self.principal_point.unwrap_or([0.5, 0.5]);
+        Hint {
+            resolution,
+            focal_px: self.focal_length_35mm / FULL_FRAME_WIDTH_MM * resolution.width as f64,
+            cx: x * resolution.width as f64,
+            cy: y * resolution.height as f64,
+        }
+    }
+
+    /// How the device is named in the job log
+    pub fn describe(&self) -> String {
+        match &self.lens {
+            Some(lens) => format!("{} {} ({})", self.make, self.model, lens),
+            None => format!("{} {}", self.make, self.model),
+        }
+    }
+}
+
+/// A device in list_known_devices
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownDevice {
+    #[serde(flatten)]
+    pub intrinsics: DeviceIntrinsics,
+    /// From the built-in table rather than the known_devices setting
+    pub builtin: bool,
+}
+
+/// Prior intrinsics for the frames of one clip, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hint {
+    pub resolution: Resolution,
+    pub focal_px: f64,
+    pub cx: f64,
+    pub cy: f64,
+}
+
+impl Hint {
+    /// The hint for the clip scaled down to fit `target`, keeping its aspect ratio
+    pub fn fit(self, target: Resolution) -> Hint {
+        let factor = (target.width as f64 / self.resolution.width as f64)
+            .min(target.height as f64 / self.resolution.height as f64)
+            .min(1.0);
+        Hint {
+            resolution: Resolution {
+                width: (self.resolution.width as f64 * factor).round() as u32,
+                height: (self.resolution.height as f64 * factor).round() as u32,
+            },
+            focal_px: self.focal_px * factor,
+            cx: self.cx * factor,
+            cy: self.cy * factor,
+        }
+    }
+
+    /// The value of the hint flags
+    pub fn value(&self) -> String {
+        format!("{:.2},{:.2},{:.2}", self.focal_px, self.cx, self.cy)
+    }
+}
+
+/// The hint arguments for clips handed to the CLI as the given inputs, and
+/// the line to log when some hints could not be passed
+pub fn hint_args(
+    inputs: &[(String, Option<Hint>)],
+    caps: &CliCapabilities,
+) -> (Vec<String>, Option<String>) {
+    let hinted: Vec<(&String, &Hint)> = inputs
+        .iter()
+        .filter_map(|(input, hint)| Some((input, hint.as_ref()?)))
+        .collect();
+    let Some((_, first)) = hinted.first() else {
+        return (vec![], None);
+    };
+    let shared =
+        hinted.len() == inputs.len() && hinted.iter().all(|(_, h)| h.value() == first.value());
+    if shared && caps.supports(FLAG_INTRINSICS_HINT) {
+        return (vec![FLAG_INTRINSICS_HINT.to_string(), first.value()], None);
+    }
+    if caps.supports(FLAG_CAMERA_INTRINSICS_HINT) {
+        let mut args = vec![];
+        for (input, hint) in hinted {
+            args.push(FLAG_CAMERA_INTRINSICS_HINT.to_string());
+            args.push(input.clone());
+            args.push(hint.value());
+        }
+        return (args, None);
+    }
+    let flag = if shared {
+        FLAG_INTRINSICS_HINT
+    } else {
+        FLAG_CAMERA_INTRINSICS_HINT
+    };
+    let skipped = format!(
+        "The installed gvcore-cli does not support {}; COLMAP estimates the intrinsics itself",
+        flag
+    );
+    (vec![], Some(skipped))
+}
+
+/// The entry for `device`: the user's own before the built-in ones, and one
+/// naming its lens before one for any lens
+pub fn lookup(device: &CaptureDevice, user: &[DeviceIntrinsics]) -> Option<DeviceIntrinsics> {
+    let builtin = builtin();
+    let entries = || user.iter().chain(&builtin);
+    entries()
+        .find(|e| e.lens.is_some() && e.matches(device))
+        .or_else(|| entries().find(|e| e.matches(device)))
+        .cloned()
+}
+
+/// The device named in the format tags ffprobe reports for a clip
+pub fn device_of(tags: &Value) -> Option<CaptureDevice> {
+    let tag = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| {
+                tags[*name]
+                    .as_str()
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+            })
+            .map(String::from)
+    };
+    let lens = tag(&LENS_TAGS);
+    let serial = tag(&SERIAL_TAGS);
+    if let (Some(make), Some(model)) = (tag(&MAKE_TAGS), tag(&MODEL_TAGS)) {
+        return Some(CaptureDevice {
+            make,
+            model,
+            lens,
+            serial,
+        });
+    }
+    // DJI drones name themselves only in the encoder tag, e.g. "DJI Mini 4 Pro"
+    let encoder = tag(&["encoder"])?;
+    let model = encoder.strip_prefix("DJI")?.trim();
+    (!model.is_empty()).then(|| CaptureDevice {
+        make: "DJI".to_string(),
+        model: model.to_string(),
+        lens,
+        serial,
+    })
+}
+
+/// Problems with the known_devices setting, by field under `field`
+pub fn check(devices: &[DeviceIntrinsics], field: &str) -> Vec<(String, Message)> {
+    let mut problems = vec![];
+    for (i, device) in devices.iter().enumerate() {
+        let field = |name: &str| format!("{}.{}.{}", field, i, name);
+        for (name, value) in [("make", &device.make), ("model", &device.model)] {
+            if value.trim().is_empty() {
+                problems.push((field(name), Message::new("args.empty")));
+            }
+        }
+        let same = |other: &DeviceIntrinsics| {
+            normalized(&other.make) == normalized(&device.make)
+                && normalized(&other.model) == normalized(&device.model)
+                && other.lens.as_deref().map(normalized) == device.lens.as_deref().map(normalized)
+        };
+        if devices[..i].iter().any(same) {
+            let message = Message::new("args.duplicate").with("value", device.describe());
+            problems.push((field("model"), message));
+        }
+        if !(MIN_FOCAL_LENGTH_35MM..=MAX_FOCAL_LENGTH_35MM).contains(&device.focal_length_35mm) {
+            let message = Message::new("args.out_of_range")
+                .with("min", MIN_FOCAL_LENGTH_35MM)
+                .with("max", MAX_FOCAL_LENGTH_35MM);
+            problems.push((field("focalLength35mm"), message));
+        }
+        if let Some(point) = device.principal_point {
+            if point.iter().any(|p| !(0.0..=1.0).contains(p)) {
+                let message = Message::new("args.out_of_range")
+                    .with("min", 0)
+                    .with("max", 1);
+                problems.push((field("principalPoint"), message));
+            }
+        }
+    }
+    problems
+}
+
+fn builtin() -> Vec<DeviceIntrinsics> {
+    BUILTIN
+        .iter()
+        .map(|(make, model, lens, focal_length_35mm)| DeviceIntrinsics {
+            make: make.to_string(),
+            model: model.to_string(),
+            lens: lens.map(String::from),
+            focal_length_35mm: *focal_length_35mm,
+            principal_point: None,
+        })
+        .collect()
+}
+
+fn normalized(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Devices whose clips get intrinsics hints: the user's own first, then the built-in ones
+#[tauri::command]
+pub async fn list_known_devices(app: AppHandle) -> Result<Vec<KnownDevice>, AppError> {
+    let user = app.settings().known_devices;
+    let known = user
+        .into_iter()
+        .map(|intrinsics| KnownDevice {
+            intrinsics,
+            builtin: false,
+        })
+        .chain(builtin().into_iter().map(|intrinsics| KnownDevice {
+            intrinsics,
+            builtin: true,
+        }))
+        .collect();
+    Ok(known)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const UHD: Resolution = Resolution {
+        width: 3840,
+        height: 2160,
+    };
+
+    fn iphone(lens: Option<&str>) -> CaptureDevice {
+        CaptureDevice {
+            make: "Apple".to_string(),
+            model: "iPhone 15 Pro".to_string(),
+            lens: lens.map(String::from),
+            serial: None,
+        }
+    }
+
+    fn caps(flags: &[&str]) -> CliCapabilities {
+        CliCapabilities {
+            flags: flags.iter().map(|f| f.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn devices_are_read_from_format_tags() {
+        let tags = json!({
+            "com.apple.quicktime.make": "Apple",
+            "com.apple.quicktime.model": "iPhone 15 Pro",
+            "com.apple.quicktime.camera.lens_model": "iPhone 15 Pro back camera 6.765mm f/1.78",
+            "serial_number": "F2LXK1ABCD",
+        });
+        let device = device_of(&tags).unwrap();
+        assert_eq!(device.model, "iPhone 15 Pro");
+        assert_eq!(device.serial.as_deref(), Some("F2LXK1ABCD"));
+
+        let drone = device_of(&json!({ "encoder": "DJI Mini 4 Pro" })).unwrap();
+        assert_eq!(
+            (drone.make.as_str(), drone.model.as_str()),
+            ("DJI", "Mini 4 Pro")
+        );
+        assert_eq!(device_of(&json!({ "encoder": "Lavf60.3.100" })), None);
+    }
+
+    #[test]
+    fn lenses_pick_their_own_entry() {
+        let main = lookup(&iphone(Some("back camera 6.765mm f/1.78")), &[]).unwrap();
+        assert_eq!(main.focal_length_35mm, 26.0);
+        let wide = lookup(&iphone(Some("back ultra wide camera 2.22mm f/2.2")), &[]).unwrap();
+        assert_eq!(wide.focal_length_35mm, 14.0);
+        // Without a lens there is no telling which camera shot the clip
+        assert_eq!(lookup(&iphone(None), &[]), None);
+
+        let own = DeviceIntrinsics {
+            make: "apple".to_string(),
+            model: "iphone  15 pro".to_string(),
+            lens: None,
+            focal_length_35mm: 28.0,
+            principal_point: None,
+        };
+        assert_eq!(lookup(&iphone(None), &[own.clone()]), Some(own));
+    }
+
+    #[test]
+    fn hints_scale_with_the_frames() {
+        let hint = lookup(&iphone(Some("6.765mm")), &[]).unwrap().hint(UHD);
+        assert_eq!(hint.value(), "2773.33,1920.00,1080.00");
+        let scaled = hint.fit(Resolution {
+            width: 1920,
+            height: 1080,
+        });
+        assert_eq!(scaled.value(), "1386.67,960.00,540.00");
+        assert_eq!(
+            hint.fit(Resolution {
+                width: 7680,
+                height: 4320
+            }),
+            hint
+        );
+    }
+
+    #[test]
+    fn differing_devices_need_per_camera_hints() {
+        let main = lookup(&iphone(Some("6.765mm")), &[]).unwrap().hint(UHD);
+        let wide = lookup(&iphone(Some("2.22mm")), &[]).unwrap().hint(UHD);
+        let shared = [
+            ("/a.mov".to_string(), Some(main)),
+            ("/b.mov".to_string(), Some(main)),
+        ];
+        let (args, skipped) = hint_args(&shared, &caps(&[FLAG_INTRINSICS_HINT]));
+        assert_eq!(args, [FLAG_INTRINSICS_HINT.to_string(), main.value()]);
+        assert_eq!(skipped, None);
+
+        let mixed = [
+            ("/a.mov".to_string(), Some(main)),
+            ("/b.mov".to_string(), Some(wide)),
+            ("/c.mp4".to_string(), None),
+        ];
+        let (args, _) = hint_args(&mixed, &caps(&[FLAG_CAMERA_INTRINSICS_HINT]));
+        assert_eq!(args.len(), 6);
+        assert_eq!(args[4], "/b.mov");
+        let (args, skipped) = hint_args(&mixed, &caps(&[FLAG_INTRINSICS_HINT]));
+        assert!(args.is_empty());
+        assert!(skipped.unwrap().contains(FLAG_CAMERA_INTRINSICS_HINT));
+    }
+
+    #[test]
+    fn rejects_bad_known_devices() {
+        let device = DeviceIntrinsics {
+            make: "GoPro".to_string(),
+            model: "HERO12 Black".to_string(),
+            lens: None,
+            focal_length_35mm: 2.0,
+            principal_point: Some([0.5, 1.5]),
+        };
+        let problems = check(&[device.clone(), device], "knownDevices");
+        let fields: Vec<&str> = problems.iter().map(|(f, _)| f.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "knownDevices.0.focalLength35mm",
+                "knownDevices.0.principalPoint",
+                "knownDevices.1.model",
+                "knownDevices.1.focalLength35mm",
+                "knownDevices.1.principalPoint",
+            ]
+        );
+    }
+}
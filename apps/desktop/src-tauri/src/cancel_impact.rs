@@ -0,0 +1,456 @@
+//! Cancel Impact
+//!
+//! What cancelling a running job costs against letting it finish, so a job
+//! 70% done is not cancelled blind. The time left comes from the current
+//! stage's progress and from how long the stages took in earlier completed
+//! jobs with the same preset, per second of footage. Without such jobs, or
+//! without the length of this job's clips, it is unknown rather than guessed.
+//!
+//! What cancelling throws away is measured rather than estimated. The job's
+//! working directory, with the CLI's frames, COLMAP database and training
+//! checkpoints, is deleted however the job ends, so all of the job's work is
+//! lost, except frames the backend extracted into the frames cache: when every
+//! clip's are there, a new run starts at camera detection. A previous run's
+//! database the job builds on stays in the production too, but the new run
+//! builds on it just the same, so it saves none of this job's work.
+//!
+//! A job whose progress has not moved for STALL_AFTER is reported with a
+//! processing-stalled warning carrying the same figures.
+
+use crate::clip_progress::ClipProgress;
+use crate::error::AppError;
+use crate::history::{JobRecord, JobStatus};
+use crate::runner::{CliWarning, EventSink, ProcessProgress};
+use crate::volume_watch::RunState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The stages a job goes through, in order
+const STAGES: [&str; 4] = [
+    "extracting_frames",
+    "detecting_cameras",
+    "training_splats",
+    "exporting",
+];
+
+/// Where a job whose frames are all in the frames cache starts again
+const AFTER_FRAMES: &str = "detecting_cameras";
+
+/// Below this percentage a stage's progress says too little about its pace,
+/// and the earlier jobs' time for it is used instead
+const MIN_PERCENT: f64 = 5.0;
+
+/// How long progress may stand still before the job counts as stalled
+pub const STALL_AFTER: Duration = Duration::from_secs(15 * 60);
+
+/// How often the stall watch looks at the progress
+const STALL_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+static LIVE: Mutex<BTreeMap<String, Tracked>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CancelImpact {
+    pub job_id: String,
+    /// The stage and percentage last reported; None before the first
+    pub stage: Option<String>,
+    pub progress: f64,
+    /// Unknown without earlier completed jobs of the preset to go by
+    pub eta_to_finish_secs: Option<f64>,
+    /// Time spent so far on work a new run would have to do again
+    pub work_lost_if_cancelled_secs: f64,
+    /// Where a new run of the job would start, when not at the beginning
+    pub resumable_from_stage: Option<String>,
+}
+
+/// Sent as processing-stalled
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stall {
+    pub job_id: String,
+    pub stage: String,
+    pub progress: f64,
+    pub stalled_secs: u64,
+    pub impact: CancelImpact,
+}
+
+impl Stall {
+    /// The warning kept with the job
+    pub fn warning(&self) -> CliWarning {
+        let eta = match self.impact.eta_to_finish_secs {
+            Some(secs) => format!("about {} left to finish", minutes(secs)),
+            None => "time left unknown".to_string(),
+        };
+        let resume = match &self.impact.resumable_from_stage {
+            Some(stage) => format!("; a new run would start at {}", stage),
+            None => String::new(),
+        };
+        CliWarning {
+            code: "processing-stalled".to_string(),
+            message: format!(
+                "No progress in {} at {:.0}% for {}; {}, {} of work lost if cancelled{}",
+                self.stage,
+                self.progress,
+                minutes(self.stalled_secs as f64),
+                eta,
+                minutes(self.impact.work_lost_if_cancelled_secs),
+                resume
+            ),
+        }
+    }
+}
+
+fn minutes(secs: f64) -> String {
+    format!("{:.0} min", (secs / 60.0).ceil())
+}
+
+struct Tracked {
+    /// Seconds each stage took per second of footage in comparable jobs;
+    /// None without any
+    rates: Option<BTreeMap<String, f64>>,
+    input_secs: Option<f64>,
+    /// Every clip's frames are in the frames cache
+    frames_kept: bool,
+    /// Seconds spent in each stage before the current one
+    done: BTreeMap<String, f64>,
+    /// The stage and percentage last reported, and since when the job has
+    /// been in that stage
+    current: Option<(String, f64, Instant)>,
+}
+
+/// Keeps a job's progress available to get_cancel_impact while it runs
+pub struct ImpactGuard {
+    job_id: String,
+}
+
+impl Drop for ImpactGuard {
+    fn drop(&mut self) {
+        LIVE.lock().unwrap().remove(&self.job_id);
+    }
+}
+
+/// Start following a job's progress, for as long as the guard lives. A
+/// preview is compared with earlier previews, anything else with completed
+/// jobs of its preset; `input_secs` is the length of its clips.
+pub fn track(
+    job_id: &str,
+    preset: &str,
+    preview: bool,
+    input_secs: Option<f64>,
+    history: &[JobRecord],
+) -> ImpactGuard {
+    LIVE.lock().unwrap().insert(
+        job_id.to_string(),
+        Tracked {
+            rates: stage_rates(history, preset, preview),
+            input_secs: input_secs.filter(|secs| *secs > 0.0),
+            frames_kept: false,
+            done: BTreeMap::new(),
+            current: None,
+        },
+    );
+    ImpactGuard {
+        job_id: job_id.to_string(),
+    }
+}
+
+/// Every clip of the job had its frames extracted into the frames cache
+pub fn frames_kept(job_id: &str) {
+    if let Some(tracked) = LIVE.lock().unwrap().get_mut(job_id) {
+        tracked.frames_kept = true;
+    }
+}
+
+/// Where a new run of the job would start, when not at the beginning
+pub fn resumable_from_stage(job_id: &str) -> Option<String> {
+    let live = LIVE.lock().unwrap();
+    impact(job_id, live.get(job_id)?, Instant::now()).resumable_from_stage
+}
+
+fn report(job_id: &str, stage: &str, percent: f64, now: Instant) {
+    let mut live = LIVE.lock().unwrap();
+    let Some(tracked) = live.get_mut(job_id) else {
+        return;
+    };
+    let since = match tracked.current.take() {
+        Some((current, _, since)) if current == stage => since,
+        Some((previous, _, since)) => {
+            let secs = now.saturating_duration_since(since).as_secs_f64();
+            *tracked.done.entry(previous).or_insert(0.0) += secs;
+            now
+        }
+        None => now,
+    };
+    tracked.current = Some((stage.to_string(), percent, since));
+}
+
+/// Passes events on to `events`, following the progress of `job_id` on the way
+pub struct ImpactSink<'a> {
+    pub job_id: &'a str,
+    pub events: &'a mut dyn EventSink,
+}
+
+impl EventSink for ImpactSink<'_> {
+    fn line(&mut self, line: &str) {
+        self.events.line(line);
+    }
+
+    fn progress(&mut self, progress: &ProcessProgress) {
+        report(
+            self.job_id,
+            &progress.stage,
+            progress.progress,
+            Instant::now(),
+        );
+        self.events.progress(progress);
+    }
+
+    fn warning(&mut self, warning: &CliWarning) {
+        self.events.warning(warning);
+    }
+
+    fn clips(&mut self, clips: &[ClipProgress]) {
+        self.events.clips(clips);
+    }
+}
+
+/// How long a running job still needs, and what cancelling it would throw
+/// away; the first running job when `job_id` is None
+#[tauri::command]
+pub async fn get_cancel_impact(job_id: Option<String>) -> Result<CancelImpact, AppError> {
+    let live = LIVE.lock().unwrap();
+    let found = match &job_id {
+        Some(job_id) => live.get_key_value(job_id),
+        None => live.iter().next(),
+    };
+    let (job_id, tracked) = found
+        .ok_or_else(|| AppError::NotFound(job_id.unwrap_or_else(|| "A running job".to_string())))?;
+    Ok(impact(job_id, tracked, Instant::now()))
+}
+
+fn impact(job_id: &str, tracked: &Tracked, now: Instant) -> CancelImpact {
+    let in_stage = |since: Instant| now.saturating_duration_since(since).as_secs_f64();
+    let spent = |stage: &str| {
+        tracked.done.get(stage).copied().unwrap_or(0.0)
+            + match &tracked.current {
+                Some((current, _, since)) if current == stage => in_stage(*since),
+                _ => 0.0,
+            }
+    };
+    let total = tracked.done.values().sum::<f64>()
+        + tracked
+            .current
+            .as_ref()
+            .map_or(0.0, |(_, _, since)| in_stage(*since));
+
+    // The frames are only in the cache once the job has moved past them
+    let resumable_from_stage = tracked
+        .current
+        .as_ref()
+        .filter(|(stage, _, _)| tracked.frames_kept && stage_index(stage) > Some(0))
+        .map(|_| AFTER_FRAMES.to_string());
+    let work_lost_if_cancelled_secs = match resumable_from_stage {
+        Some(_) => total - spent(STAGES[0]),
+        None => total,
+    };
+
+    CancelImpact {
+        job_id: job_id.to_string(),
+        stage: tracked.current.as_ref().map(|(stage, _, _)| stage.clone()),
+        progress: tracked.current.as_ref().map_or(0.0, |(_, p, _)| *p),
+        eta_to_finish_secs: eta(tracked, &spent),
+        work_lost_if_cancelled_secs,
+        resumable_from_stage,
+    }
+}
+
+fn eta(tracked: &Tracked, spent: &dyn Fn(&str) -> f64) -> Option<f64> {
+    let rates = tracked.rates.as_ref()?;
+    let input_secs = tracked.input_secs?;
+    let expected = |stage: &str| rates.get(stage).map_or(0.0, |rate| rate * input_secs);
+    let Some((stage, percent, _)) = &tracked.current else {
+        return Some(STAGES.iter().map(|s| expected(s)).sum());
+    };
+    if stage == "complete" {
+        return Some(0.0);
+    }
+    let index = stage_index(stage)?;
+    let elapsed = spent(stage);
+    let left_in_stage = if *percent >= 100.0 {
+        0.0
+    } else if *percent >= MIN_PERCENT {
+        elapsed * (100.0 - percent) / percent
+    } else {
+        (expected(stage) - elapsed).max(0.0)
+    };
+    Some(left_in_stage + STAGES[index + 1..].iter().map(|s| expected(s)).sum::<f64>())
+}
+
+fn stage_index(stage: &str) -> Option<usize> {
+    STAGES.iter().position(|s| *s == stage)
+}
+
+/// Seconds per second of footage each stage took across comparable completed
+/// jobs, in total; None without any that knew their footage
+fn stage_rates(
+    history: &[JobRecord],
+    preset: &str,
+    preview: bool,
+) -> Option<BTreeMap<String, f64>> {
+    let mut stage_secs: BTreeMap<String, f64> = BTreeMap::new();
+    let mut footage = 0.0;
+    for record in history {
+        let comparable = record.status == JobStatus::Completed
+            && record.preview == preview
+            && (preview || record.preset == preset)
+            && !record.stages.is_empty();
+        let Some(input_secs) = record.input_secs.filter(|secs| comparable && *secs > 0.0) else {
+            continue;
+        };
+        for (stage, secs) in &record.stages {
+            *stage_secs.entry(stage.clone()).or_insert(0.0) += secs;
+        }
+        footage += input_secs;
+    }
+    (footage > 0.0).then(|| {
+        stage_secs
+            .into_iter()
+            .map(|(stage, secs)| (stage, secs / footage))
+            .collect()
+    })
+}
+
+/// Report the job with `notify` each time its progress stands still for
+/// STALL_AFTER. An artifact growing during a silent export counts as
+/// progress. Never returns, so it is meant to be raced against the run.
+pub async fn watch_stall(
+    job_id: &str,
+    state: &RunState,
+    stall_after: Duration,
+    notify: &mut (dyn FnMut(Stall) + Send),
+) {
+    let mut last: (Option<(String, f64)>, Option<u64>) = (None, None);
+    let mut since = Instant::now();
+    let mut reported = false;
+    loop {
+        tokio::time::sleep(STALL_POLL_INTERVAL.min(stall_after)).await;
+        let activity = (
+            state.progress.lock().unwrap().clone(),
+            *state.exported_bytes.lock().unwrap(),
+        );
+        if activity != last {
+            last = activity;
+            since = Instant::now();
+            reported = false;
+            continue;
+        }
+        let Some((stage, percent)) = &last.0 else {
+            continue;
+        };
+        if reported || since.elapsed() < stall_after {
+            continue;
+        }
+        reported = true;
+        let impact = {
+            let live = LIVE.lock().unwrap();
+            match live.get(job_id) {
+                Some(tracked) => impact(job_id, tracked, Instant::now()),
+                None => continue,
+            }
+        };
+        notify(Stall {
+            job_id: job_id.to_string(),
+            stage: stage.clone(),
+            progress: *percent,
+            stalled_secs: since.elapsed().as_secs(),
+            impact,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::tests::job;
+
+    fn completed(preset: &str, input_secs: f64, stages: &[(&str, f64)]) -> JobRecord {
+        let mut record = job("impact-history", JobStatus::Completed);
+        record.preset = preset.to_string();
+        record.input_secs = Some(input_secs);
+        record.stages = stages
+            .iter()
+            .map(|(s, secs)| (s.to_string(), *secs))
+            .collect();
+        record
+    }
+
+    #[test]
+    fn history_and_progress_give_the_time_left() {
+        let history = [
+            completed(
+                "balanced",
+                60.0,
+                &[
+                    ("extracting_frames", 60.0),
+                    ("detecting_cameras", 120.0),
+                    ("training_splats", 600.0),
+                    ("exporting", 30.0),
+                ],
+            ),
+            completed("studio", 60.0, &[("training_splats", 6000.0)]),
+        ];
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let _guard = track("impact-eta", "balanced", false, Some(120.0), &history);
+        let now = |secs| impact("impact-eta", &LIVE.lock().unwrap()["impact-eta"], at(secs));
+
+        // Before any progress it is the earlier job's time, for twice the footage
+        assert_eq!(now(0).eta_to_finish_secs, Some(1620.0));
+
+        report("impact-eta", "extracting_frames", 0.0, at(0));
+        report("impact-eta", "extracting_frames", 50.0, at(100));
+        let extracting = now(100);
+        // Half the stage took 100 s, so the other half takes as long
+        assert_eq!(
+            extracting.eta_to_finish_secs,
+            Some(100.0 + 240.0 + 1200.0 + 60.0)
+        );
+        assert_eq!(extracting.work_lost_if_cancelled_secs, 100.0);
+        assert_eq!(extracting.resumable_from_stage, None);
+
+        report("impact-eta", "detecting_cameras", 1.0, at(200));
+        let detecting = now(230);
+        // Too early in the stage to go by its pace
+        assert_eq!(detecting.eta_to_finish_secs, Some(210.0 + 1200.0 + 60.0));
+        assert_eq!(detecting.work_lost_if_cancelled_secs, 230.0);
+
+        frames_kept("impact-eta");
+        let kept = now(230);
+        assert_eq!(
+            kept.resumable_from_stage.as_deref(),
+            Some("detecting_cameras")
+        );
+        assert_eq!(kept.work_lost_if_cancelled_secs, 30.0);
+    }
+
+    #[test]
+    fn without_comparable_jobs_the_time_left_is_unknown() {
+        let history = [
+            completed("studio", 60.0, &[("training_splats", 600.0)]),
+            job("impact-unknown-footage", JobStatus::Completed),
+        ];
+        let _other = track("impact-none", "balanced", false, Some(60.0), &history);
+        let _unknown = track("impact-no-footage", "studio", false, None, &history);
+        let start = Instant::now();
+        report("impact-none", "training_splats", 40.0, start);
+        report("impact-no-footage", "training_splats", 40.0, start);
+
+        let live = LIVE.lock().unwrap();
+        let later = start + Duration::from_secs(90);
+        let none = impact("impact-none", &live["impact-none"], later);
+        assert_eq!(none.eta_to_finish_secs, None);
+        assert_eq!(none.work_lost_if_cancelled_secs, 90.0);
+        let no_footage = impact("impact-no-footage", &live["impact-no-footage"], later);
+        assert_eq!(no_footage.eta_to_finish_secs, None);
+    }
+}
@@ -0,0 +1,162 @@
+//! CLI Capability Discovery
+//!
+//! Asks the installed gvcore-cli which flags and presets it supports so the
+//! backend only passes options the CLI understands.
+
+use crate::error::AppError;
+use crate::platform::PathProvider;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+/// Flag used to ask the CLI to tone-map an HDR input
+pub const FLAG_TONE_MAP: &str = "--tone-map";
+/// Flag used to split an equirectangular input into perspective views
+pub const FLAG_EQUIRECT_SPLIT: &str = "--equirect-split";
+/// Flag used to pass a directory of mask PNGs
+pub const FLAG_MASKS: &str = "--masks";
+/// Flag used to let the CLI generate masks for moving objects itself
+pub const FLAG_AUTO_MASK: &str = "--auto-mask";
+/// Flag used to hand the CLI a directory of already extracted frames
+pub const FLAG_IMAGES: &str = "--images";
+/// Flag used to skip the start of an input, given per input like --tone-map
+pub const FLAG_START_TIME: &str = "--start-time";
+/// Flag used to let the CLI drop blurred frames itself
+pub const FLAG_MIN_SHARPNESS: &str = "--min-sharpness";
+/// Flags used to let the CLI drop frames too dark or too bright itself
+pub const FLAG_MIN_BRIGHTNESS: &str = "--min-brightness";
+pub const FLAG_MAX_BRIGHTNESS: &str = "--max-brightness";
+/// Flag used to cap the frames a preview reconstructs from
+pub const FLAG_MAX_FRAMES: &str = "--max-frames";
+/// Flag used to hand the CLI a previous run's COLMAP database to register new images into
+pub const FLAG_INCREMENTAL: &str = "--incremental";
+/// Flag used to have the CLI export a checkpoint into checkpoints/ every so many minutes
+pub const FLAG_CHECKPOINT_INTERVAL: &str = "--checkpoint-interval";
+/// Flag used to set how many steps brush trains for
+pub const FLAG_ITERATIONS: &str = "--iterations";
+/// Flag used to set the spherical harmonics degree of the splats
+pub const FLAG_SH_DEGREE: &str = "--sh-degree";
+/// Flag used to set the steps between densification passes
+pub const FLAG_DENSIFY_INTERVAL: &str = "--densify-interval";
+/// Flag used to set the gradient threshold for densifying a splat
+pub const FLAG_DENSIFY_GRAD_THRESHOLD: &str = "--densify-grad-threshold";
+/// Flag used to set the steps between opacity resets
+pub const FLAG_OPACITY_RESET_INTERVAL: &str = "--opacity-reset-interval";
+/// Flag used to point the CLI at the directory for its temporary files
+pub const FLAG_WORK_DIR: &str = "--work-dir";
+/// Flag used to give COLMAP prior intrinsics shared by every input, as "fx,cx,cy" in pixels
+pub const FLAG_INTRINSICS_HINT: &str = "--intrinsics-hint";
+/// Flag used to give COLMAP prior intrinsics for one input, given per input like --tone-map
+pub const FLAG_CAMERA_INTRINSICS_HINT: &str = "--camera-intrinsics-hint";
+/// Flag used to tell COLMAP which inputs share one physical camera, given per
+/// group as its name and its inputs joined like the entries of PATH
+pub const FLAG_CAMERA_GROUP: &str = "--camera-group";
+
+/// Presets every gvcore-cli has
+const BUILTIN_PRESETS: [&str; 4] = ["fast", "balanced", "high", "maximum"];
+
+// Capabilities are cached per CLI path; the CLI is not expected to change while the app runs
+static CAPABILITIES: Mutex<Option<(String, CliCapabilities)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CliCapabilities {
+    pub version: Option<String>,
+    #[serde(default)]
+    pub flags: Vec<String>,
+    #[serde(default)]
+    pub presets: Vec<String>,
+}
+
+impl CliCapabilities {
+    pub fn supports(&self, flag: &str) -> bool {
+        self.flags.iter().any(|f| f == flag)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PresetList {
+    pub presets: Vec<String>,
+    /// The CLI could not report its presets, so these are the built-in ones
+    pub degraded: bool,
+}
+
+/// The presets the CLI reported, or the built-in ones when it reported none
+pub fn preset_list(caps: &CliCapabilities) -> PresetList {
+    if caps.presets.is_empty() {
+        PresetList {
+            presets: BUILTIN_PRESETS.iter().map(|p| p.to_string()).collect(),
+            degraded: true,
+        }
+    } else {
+        PresetList {
+            presets: caps.presets.clone(),
+            degraded: false,
+        }
+    }
+}
+
+/// The presets of the resolved gvcore-cli, which a job's preset must be one of
+pub async fn presets(paths: &impl PathProvider) -> PresetList {
+    match crate::commands::cli_path(paths) {
+        Ok(cli_path) => preset_list(&discover(&cli_path).await),
+        Err(_) => preset_list(&CliCapabilities::default()),
+    }
+}
+
+/// Run `gvcore-cli capabilities --json` and parse the result.
+/// An older CLI without the subcommand yields empty capabilities.
+pub async fn discover(cli_path: &str) -> CliCapabilities {
+    if let Some((path, caps)) = CAPABILITIES.lock().unwrap().as_ref() {
+        if path == cli_path {
+            return caps.clone();
+        }
+    }
+
+    let output = tokio::process::Command::new(cli_path)
+        .args(["capabilities", "--json"])
+        .output()
+        .await;
+
+    let caps = match output {
+        Ok(output) if output.status.success() => {
+            serde_json::from_slice(&output.stdout).unwrap_or_default()
+        }
+        _ => CliCapabilities::default(),
+    };
+
+    *CAPABILITIES.lock().unwrap() = Some((cli_path.to_string(), caps.clone()));
+    caps
+}
+
+/// Get the capabilities of the resolved gvcore-cli
+#[tauri::command]
+pub async fn get_cli_capabilities(app: AppHandle) -> Result<CliCapabilities, String> {
+    let cli_path = crate::commands::cli_path(&app)?;
+    Ok(discover(&cli_path).await)
+}
+
+/// The presets to offer in the preset picker
+#[tauri::command]
+pub async fn list_presets(app: AppHandle) -> Result<PresetList, AppError> {
+    Ok(presets(&app).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_come_from_the_cli_when_it_reports_them() {
+        let caps = CliCapabilities {
+            presets: vec!["fast".to_string(), "turbo".to_string()],
+            ..Default::default()
+        };
+        let list = preset_list(&caps);
+        assert_eq!(list.presets, ["fast", "turbo"]);
+        assert!(!list.degraded);
+
+        let list = preset_list(&CliCapabilities::default());
+        assert_eq!(list.presets, BUILTIN_PRESETS);
+        assert!(list.degraded);
+    }
+}
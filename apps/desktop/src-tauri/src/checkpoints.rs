@@ -0,0 +1,223 @@
+//! Training Checkpoints
+//!
+//! A long training otherwise shows nothing until it ends. When the CLI can
+//! export intermediate splats, it is asked to write one every few minutes into
+//! checkpoints/ in the job's working directory. While the job runs that
+//! directory is polled; a checkpoint is announced with checkpoint-available
+//! once its size has settled and its PLY is complete, and the oldest beyond the
+//! keep count are deleted. They reach the production with the other results,
+//! unless a job that succeeds is set to drop them.
+
+use crate::conversion;
+use crate::error::AppError;
+use crate::history;
+use crate::jobs;
+use crate::platform::PathProvider;
+use crate::workdir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// Directory inside the production the CLI writes checkpoints to
+pub const DIR_NAME: &str = "checkpoints";
+
+/// How often the checkpoints directory is looked at while a job runs
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CheckpointSettings {
+    /// Export checkpoints when the CLI supports it
+    pub enabled: bool,
+    pub interval_minutes: u32,
+    /// Checkpoints kept at once; older ones are deleted as new ones arrive
+    pub keep: usize,
+    /// Delete the checkpoints once a job has succeeded
+    pub delete_on_success: bool,
+}
+
+impl Default for CheckpointSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval_minutes: 10,
+            keep: 3,
+            delete_on_success: false,
+        }
+    }
+}
+
+/// Sent as checkpoint-available
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub job_id: String,
+    pub path: String,
+    /// The training iteration, when the file name gives it
+    pub iteration: Option<u64>,
+    pub splat_count: usize,
+}
+
+pub fn dir(output_dir: &Path) -> PathBuf {
+    output_dir.join(DIR_NAME)
+}
+
+/// Announce each checkpoint of `job_id` that appears in `dir` and prune the
+/// oldest beyond `keep`. Never returns, so it is meant to be raced against
+/// the run.
+pub async fn watch(
+    job_id: &str,
+    dir: &Path,
+    keep: usize,
+    interval: Duration,
+    notify: &mut (dyn FnMut(Checkpoint) + Send),
+) {
+    // Sizes seen on the previous poll; a checkpoint is read once its size stays put
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    let mut announced: Vec<PathBuf> = vec![];
+    loop {
+        tokio::time::sleep(interval).await;
+        for (path, size) in ply_files(dir) {
+            if announced.contains(&path) || sizes.insert(path.clone(), size) != Some(size) {
+                continue;
+            }
+            // Incomplete files are looked at again on the next poll
+            let Ok(splat_count) = conversion::complete_splat_count(&path) else {
+                continue;
+            };
+            announced.push(path.clone());
+            notify(Checkpoint {
+                job_id: job_id.to_string(),
+                path: path.to_string_lossy().to_string(),
+                iteration: iteration(&path),
+                splat_count,
+            });
+        }
+        prune(keep, &mut announced);
+    }
+}
+
+/// Delete the oldest announced checkpoints beyond `keep`
+fn prune(keep: usize, announced: &mut Vec<PathBuf>) {
+    announced.sort_by_key(|path| (iteration(path), path.clone()));
+    let excess = announced.len().saturating_sub(keep.max(1));
+    let old: Vec<PathBuf> = announced.drain(..excess).collect();
+    for path in old {
+        // One that cannot be deleted is tried again on the next poll
+        if std::fs::remove_file(&path).is_err() {
+            announced.push(path);
+        }
+    }
+}
+
+fn ply_files(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter(|e| e.path().extension().is_some_and(|x| x == "ply"))
+        .filter_map(|e| Some((e.path(), e.metadata().ok().filter(|m| m.is_file())?.len())))
+        .collect()
+}
+
+/// The complete checkpoints in `dir`, oldest first
+fn checkpoints_in(dir: &Path, job_id: &str) -> Vec<Checkpoint> {
+    let mut checkpoints: Vec<Checkpoint> = ply_files(dir)
+        .into_iter()
+        .filter_map(|(path, _)| {
+            Some(Checkpoint {
+                job_id: job_id.to_string(),
+                splat_count: conversion::complete_splat_count(&path).ok()?,
+                iteration: iteration(&path),
+                path: path.to_string_lossy().to_string(),
+            })
+        })
+        .collect();
+    checkpoints.sort_by(|a, b| (a.iteration, &a.path).cmp(&(b.iteration, &b.path)));
+    checkpoints
+}
+
+/// The last number in the file name, e.g. 12000 for checkpoint_12000.ply
+fn iteration(path: &Path) -> Option<u64> {
+    let stem = path.file_stem()?.to_string_lossy();
+    stem.rsplit(|c: char| !c.is_ascii_digit())
+        .find(|part| !part.is_empty())?
+        .parse()
+        .ok()
+}
+
+/// The checkpoints of a running or finished job, oldest first
+#[tauri::command]
+pub async fn get_job_checkpoints(
+    app: AppHandle,
+    job_id: String,
+) -> Result<Vec<Checkpoint>, AppError> {
+    job_checkpoints(&app, &job_id)
+}
+
+fn job_checkpoints(paths: &impl PathProvider, job_id: &str) -> Result<Vec<Checkpoint>, AppError> {
+    // A running job's checkpoints are still in its working directory
+    let output_dir = match jobs::output_dir(job_id) {
+        Some(output_dir) => workdir::output_of(Path::new(&output_dir), job_id),
+        None => history::load(paths)?
+            .into_iter()
+            .find(|r| r.job_id == job_id)
+            .map(|r| PathBuf::from(r.output_dir))
+            .ok_or_else(|| AppError::NotFound(job_id.to_string()))?,
+    };
+    Ok(checkpoints_in(&dir(&output_dir), job_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn iterations_come_from_the_file_name() {
+        assert_eq!(
+            iteration(Path::new("/p/checkpoints/checkpoint_12000.ply")),
+            Some(12000)
+        );
+        assert_eq!(
+            iteration(Path::new("/p/checkpoints/v2-iter-500.ply")),
+            Some(500)
+        );
+        assert_eq!(iteration(Path::new("/p/checkpoints/latest.ply")), None);
+    }
+
+    #[tokio::test]
+    async fn settled_checkpoints_are_announced_and_old_ones_pruned() {
+        let paths = TempPaths::new();
+        let dir = dir(paths.root());
+        std::fs::create_dir_all(&dir).unwrap();
+        let ply = sample_ply(&[[0.0; 14]; 2]);
+        for iteration in [1000, 2000, 3000] {
+            std::fs::write(dir.join(format!("checkpoint_{}.ply", iteration)), &ply).unwrap();
+        }
+        // Still being written
+        std::fs::write(dir.join("checkpoint_4000.ply"), &ply[..ply.len() - 4]).unwrap();
+
+        let mut announced = vec![];
+        let mut notify = |checkpoint: Checkpoint| announced.push(checkpoint);
+        let watch = watch("job-1", &dir, 2, Duration::from_millis(10), &mut notify);
+        tokio::select! {
+            () = watch => unreachable!(),
+            () = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+
+        let mut iterations: Vec<Option<u64>> = announced.iter().map(|c| c.iteration).collect();
+        iterations.sort();
+        assert_eq!(iterations, [Some(1000), Some(2000), Some(3000)]);
+        assert!(announced.iter().all(|c| c.splat_count == 2));
+        let left: Vec<Option<u64>> = checkpoints_in(&dir, "job-1")
+            .iter()
+            .map(|c| c.iteration)
+            .collect();
+        assert_eq!(left, [Some(2000), Some(3000)]);
+        assert!(dir.join("checkpoint_4000.ply").exists());
+    }
+}
@@ -0,0 +1,229 @@
+//! Command-Line Arguments
+//!
+//! Launched without a subcommand the binary opens the app as usual. With one,
+//! it forwards a request to the running instance and exits:
+//!
+//! ```text
+//! gameview enqueue --project pier.gvproj [--preset high] [--json]
+//! gameview enqueue --video a.mp4 --video b.mp4 --output dir [--preset fast] [--json]
+//! gameview status <job_id> [--json]
+//! gameview cancel <job_id> [--json]
+//! ```
+//!
+//! Relative paths are resolved against the directory the command was run in,
+//! since the running instance has its own.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const USAGE: &str = "\
+usage: gameview enqueue (--project <file.gvproj> | --video <clip>... --output <dir>) [--preset <name>] [--json]
+       gameview status <job_id> [--json]
+       gameview cancel <job_id> [--json]";
+
+/// What the binary was launched to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Invocation {
+    /// Open the app; minimized when started on behalf of a forwarded request
+    Gui { minimized: bool },
+    /// Send `request` to the running instance, printing the reply as JSON with `json`
+    Remote { request: Request, json: bool },
+}
+
+/// A request forwarded to the running instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Enqueue(EnqueueRequest),
+    Status { job_id: String },
+    Cancel { job_id: String },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EnqueueRequest {
+    /// A .gvproj file naming the clips, output directory and preset
+    pub project: Option<String>,
+    pub videos: Vec<String>,
+    pub output_dir: Option<String>,
+    /// Overrides the project's preset, or the default preset without a project
+    pub preset: Option<String>,
+}
+
+/// The flag the app is relaunched with when a request finds no running instance
+pub const MINIMIZED_FLAG: &str = "--minimized";
+
+/// Parse the arguments after the program name
+pub fn parse(args: &[String], cwd: &Path) -> Result<Invocation, String> {
+    let Some(subcommand) = args.first() else {
+        return Ok(Invocation::Gui { minimized: false });
+    };
+    let mut json = false;
+    let mut rest = vec![];
+    for arg in &args[1..] {
+        if arg == "--json" {
+            json = true;
+        } else {
+            rest.push(arg.as_str());
+        }
+    }
+
+    let request = match subcommand.as_str() {
+        "enqueue" => Request::Enqueue(enqueue(&rest, cwd)?),
+        "status" => Request::Status {
+            job_id: job_id(subcommand, &rest)?,
+        },
+        "cancel" => Request::Cancel {
+            job_id: job_id(subcommand, &rest)?,
+        },
+        // Anything else is left to the app, e.g. the flags the OS launches it with
+        _ => {
+            return Ok(Invocation::Gui {
+                minimized: args.iter().any(|a| a == MINIMIZED_FLAG),
+            })
+        }
+    };
+    Ok(Invocation::Remote { request, json })
+}
+
+fn enqueue(args: &[&str], cwd: &Path) -> Result<EnqueueRequest, String> {
+    let mut request = EnqueueRequest::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(|v| v.to_string())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match *flag {
+            "--project" => request.project = Some(absolute(&value()?, cwd)),
+            "--video" => request.videos.push(absolute(&value()?, cwd)),
+            "--output" => request.output_dir = Some(absolute(&value()?, cwd)),
+            "--preset" => request.preset = Some(value()?),
+            other => return Err(format!("Unknown argument for enqueue: {}", other)),
+        }
+    }
+
+    match (&request.project, request.videos.is_empty()) {
+        (Some(_), false) => Err("Pass either --project or --video, not both".to_string()),
+        (None, true) => Err("enqueue needs --project or at least one --video".to_string()),
+        (None, false) if request.output_dir.is_none() => Err("--video needs --output".to_string()),
+        _ => Ok(request),
+    }
+}
+
+fn job_id(subcommand: &str, args: &[&str]) -> Result<String, String> {
+    match args {
+        [job_id] if !job_id.starts_with('-') => Ok(job_id.to_string()),
+        _ => Err(format!("{} takes exactly one job id", subcommand)),
+    }
+}
+
+fn absolute(path: &str, cwd: &Path) -> String {
+    cwd.join(path).to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Invocation, String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse(&args, Path::new("/work"))
+    }
+
+    #[test]
+    fn launches_without_a_subcommand_open_the_app() {
+        assert_eq!(
+            parse_args(&[]).unwrap(),
+            Invocation::Gui { minimized: false }
+        );
+        assert_eq!(
+            parse_args(&[MINIMIZED_FLAG]).unwrap(),
+            Invocation::Gui { minimized: true }
+        );
+        // macOS passes a process serial number to apps it launches
+        assert_eq!(
+            parse_args(&["-psn_0_12345"]).unwrap(),
+            Invocation::Gui { minimized: false }
+        );
+    }
+
+    #[test]
+    fn parses_enqueue_requests() {
+        let Invocation::Remote { request, json } = parse_args(&[
+            "enqueue",
+            "--video",
+            "clips/a.mp4",
+            "--json",
+            "--video",
+            "/clips/b.mp4",
+            "--output",
+            "out",
+        ])
+        .unwrap() else {
+            panic!("expected a remote request");
+        };
+        assert!(json);
+        assert_eq!(
+            request,
+            Request::Enqueue(EnqueueRequest {
+                project: None,
+                videos: vec!["/work/clips/a.mp4".to_string(), "/clips/b.mp4".to_string()],
+                output_dir: Some("/work/out".to_string()),
+                preset: None,
+            })
+        );
+
+        let Invocation::Remote { request, json } =
+            parse_args(&["enqueue", "--project", "pier.gvproj", "--preset", "high"]).unwrap()
+        else {
+            panic!("expected a remote request");
+        };
+        assert!(!json);
+        assert_eq!(
+            request,
+            Request::Enqueue(EnqueueRequest {
+                project: Some("/work/pier.gvproj".to_string()),
+                preset: Some("high".to_string()),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_incomplete_requests() {
+        for args in [
+            &["enqueue"][..],
+            &["enqueue", "--video", "a.mp4"],
+            &["enqueue", "--project", "p.gvproj", "--video", "a.mp4"],
+            &["enqueue", "--preset"],
+            &["enqueue", "--frames", "dir"],
+            &["status"],
+            &["cancel", "entry-1", "entry-2"],
+        ] {
+            assert!(parse_args(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn parses_status_and_cancel() {
+        assert_eq!(
+            parse_args(&["status", "entry-1", "--json"]).unwrap(),
+            Invocation::Remote {
+                request: Request::Status {
+                    job_id: "entry-1".to_string()
+                },
+                json: true,
+            }
+        );
+        assert_eq!(
+            parse_args(&["cancel", "entry-1"]).unwrap(),
+            Invocation::Remote {
+                request: Request::Cancel {
+                    job_id: "entry-1".to_string()
+                },
+                json: false,
+            }
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! CLI Location
+//!
+//! Resolves which gvcore-cli jobs run. The bundled builds live in
+//! resources/<target-triple>/, one per architecture, because an x86_64 build
+//! runs at half speed under Rosetta on Apple Silicon and a build for the wrong
+//! processor does not start at all elsewhere. The host's architecture is read
+//! at runtime, as an x86_64 app on an arm64 Mac or Windows machine is itself
+//! emulated. In order, the gvcore_cli_path setting wins, then an update
+//! installed into app_data/bin, then the native bundled build, then one the
+//! host can emulate, then the flat resources/ layout of older bundles, and
+//! last whatever is on the PATH.
+
+use crate::error::AppError;
+use crate::platform::PathProvider;
+use crate::settings::AppSettings;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+pub const NAME: &str = "gvcore-cli";
+
+// The gvcore_cli_path setting, kept current by configure
+static CONFIGURED: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Remember the gvcore_cli_path setting, at startup and whenever the settings change
+pub fn configure(settings: &AppSettings) {
+    *CONFIGURED.lock().unwrap() = settings
+        .gvcore_cli_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .map(PathBuf::from);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    pub fn name(self) -> &'static str {
+        match self {
+            Arch::X86_64 => "x86_64",
+            Arch::Aarch64 => "aarch64",
+        }
+    }
+
+    /// The resource directory name of a build for this architecture on this OS
+    pub fn triple(self) -> String {
+        let os = if cfg!(target_os = "macos") {
+            "apple-darwin"
+        } else if cfg!(windows) {
+            "pc-windows-msvc"
+        } else {
+            "unknown-linux-gnu"
+        };
+        format!("{}-{}", self.name(), os)
+    }
+
+    /// Whether a build for `other` runs on this architecture, translated
+    fn emulates(self, other: Arch) -> bool {
+        let translated = cfg!(any(target_os = "macos", windows));
+        translated && self == Arch::Aarch64 && other == Arch::X86_64
+    }
+}
+
+/// The processor of the machine, not of this possibly emulated app
+pub fn host_arch() -> Arch {
+    if native_arm64() {
+        return Arch::Aarch64;
+    }
+    if cfg!(target_arch = "aarch64") {
+        Arch::Aarch64
+    } else {
+        Arch::X86_64
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn native_arm64() -> bool {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>();
+    // SAFETY: the name is NUL-terminated and value and len describe a c_int
+    let found = unsafe {
+        libc::sysctlbyname(
+            b"hw.optional.arm64\0".as_ptr() as *const libc::c_char,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    found == 0 && value == 1
+}
+
+#[cfg(windows)]
+fn native_arm64() -> bool {
+    use windows_sys::Win32::System::SystemInformation::IMAGE_FILE_MACHINE_ARM64;
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, IsWow64Process2};
+
+    let mut process = 0;
+    let mut native = 0;
+    // SAFETY: the pseudo handle of the current process needs no closing
+    let ok = unsafe { IsWow64Process2(GetCurrentProcess(), &mut process, &mut native) };
+    ok != 0 && native == IMAGE_FILE_MACHINE_ARM64
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn native_arm64() -> bool {
+    false
+}
+
+/// Where a candidate gvcore-cli comes from, in the order they are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CliSource {
+    /// The gvcore_cli_path setting
+    Configured,
+    /// Installed into app_data/bin
+    Update,
+    /// Bundled for the host's architecture
+    Native,
+    /// Bundled for an architecture the host emulates
+    Emulated,
+    /// Bundled directly in resources/, for an unknown architecture
+    Legacy,
+    Path,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CliCandidate {
+    pub source: CliSource,
+    pub path: String,
+    pub exists: bool,
+}
+
+/// What explain_cli_resolution reports
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CliResolution {
+    /// Target triple of the host
+    pub host: String,
+    /// Every place looked at, in order
+    pub candidates: Vec<CliCandidate>,
+    /// The first candidate that exists; None when there is none
+    pub chosen: Option<CliCandidate>,
+    /// Bundled builds that cannot run on this host
+    pub incompatible: Vec<String>,
+}
+
+impl CliResolution {
+    pub fn emulated(&self) -> bool {
+        self.chosen
+            .as_ref()
+            .is_some_and(|c| c.source == CliSource::Emulated)
+    }
+
+    /// The program to run, or why there is none
+    pub fn program(&self) -> Result<String, String> {
+        if let Some(chosen) = &self.chosen {
+            return Ok(chosen.path.clone());
+        }
+        if !self.incompatible.is_empty() {
+            return Err(format!(
+                "No bundled gvcore-cli runs on {}; the app only has builds for {}",
+                self.host,
+                self.incompatible.join(", ")
+            ));
+        }
+        // Left to the OS, whose error the spawn diagnosis explains
+        Ok(NAME.to_string())
+    }
+}
+
+/// Resolve gvcore-cli for this machine
+pub fn resolve(paths: &impl PathProvider) -> CliResolution {
+    let configured = CONFIGURED.lock().unwrap().clone();
+    resolve_in(
+        configured.as_deref(),
+        paths.app_data_dir().ok().map(|d| d.join("bin")),
+        paths.resource_dir().ok().map(|d| d.join("resources")),
+        host_arch(),
+        crate::setup::find_in_path,
+    )
+}
+
+fn resolve_in(
+    configured: Option<&Path>,
+    update_dir: Option<PathBuf>,
+    resources: Option<PathBuf>,
+    host: Arch,
+    find_in_path: impl Fn(&str) -> Option<PathBuf>,
+) -> CliResolution {
+    let file = |dir: &Path| {
+        let names: &[&str] = if cfg!(windows) {
+            &["gvcore-cli.exe", "gvcore-cli.bat", "gvcore-cli.cmd"]
+        } else {
+            &["gvcore-cli"]
+        };
+        names
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|p| p.is_file())
+            .unwrap_or_else(|| dir.join(format!("{}{}", NAME, std::env::consts::EXE_SUFFIX)))
+    };
+    let other = match host {
+        Arch::X86_64 => Arch::Aarch64,
+        Arch::Aarch64 => Arch::X86_64,
+    };
+
+    let mut paths: Vec<(CliSource, PathBuf)> = vec![];
+    if let Some(configured) = configured {
+        paths.push((CliSource::Configured, configured.to_path_buf()));
+    }
+    if let Some(dir) = &update_dir {
+        paths.push((CliSource::Update, file(dir)));
+    }
+    let mut incompatible = vec![];
+    if let Some(resources) = &resources {
+        paths.push((CliSource::Native, file(&resources.join(host.triple()))));
+        let foreign = file(&resources.join(other.triple()));
+        if host.emulates(other) {
+            paths.push((CliSource::Emulated, foreign));
+        } else if foreign.is_file() {
+            incompatible.push(other.triple());
+        }
+        paths.push((CliSource::Legacy, file(resources)));
+    }
+    let on_path = find_in_path(NAME);
+    paths.push((
+        CliSource::Path,
+        on_path.clone().unwrap_or_else(|| PathBuf::from(NAME)),
+    ));
+
+    let candidates: Vec<CliCandidate> = paths
+        .into_iter()
+        .map(|(source, path)| CliCandidate {
+            exists: match source {
+                CliSource::Path => on_path.is_some(),
+                _ => path.is_file(),
+            },
+            source,
+            path: path.to_string_lossy().to_string(),
+        })
+        .collect();
+    CliResolution {
+        host: host.triple(),
+        chosen: candidates.iter().find(|c| c.exists).cloned(),
+        candidates,
+        incompatible,
+    }
+}
+
+/// Where each gvcore-cli is looked for and which one jobs run, for debugging
+#[tauri::command]
+pub async fn explain_cli_resolution(app: AppHandle) -> Result<CliResolution, AppError> {
+    Ok(resolve(&app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn install(dir: &Path) -> PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(format!("{}{}", NAME, std::env::consts::EXE_SUFFIX));
+        std::fs::write(&path, b"").unwrap();
+        path
+    }
+
+    #[test]
+    fn native_builds_win_over_emulated_and_legacy_ones() {
+        let paths = TempPaths::new();
+        let resources = paths.root().join("resources");
+        let bin = paths.root().join("bin");
+        let resolve = |host| {
+            resolve_in(
+                None,
+                Some(bin.clone()),
+                Some(resources.clone()),
+                host,
+                |_| None,
+            )
+        };
+
+        let legacy = install(&resources);
+        let x86 = install(&resources.join(Arch::X86_64.triple()));
+        let chosen = resolve(Arch::X86_64).chosen.unwrap();
+        assert_eq!(
+            (chosen.source, chosen.path),
+            (CliSource::Native, x86.to_string_lossy().to_string())
+        );
+
+        let on_arm = resolve(Arch::Aarch64);
+        if cfg!(any(target_os = "macos", windows)) {
+            assert!(on_arm.emulated());
+        } else {
+            // Nothing translates an x86_64 build on arm64 Linux
+            assert_eq!(on_arm.chosen.unwrap().path, legacy.to_string_lossy());
+            assert_eq!(on_arm.incompatible, [Arch::X86_64.triple()]);
+        }
+
+        let arm = install(&resources.join(Arch::Aarch64.triple()));
+        assert_eq!(
+            resolve(Arch::Aarch64).chosen.unwrap().path,
+            arm.to_string_lossy()
+        );
+        let update = install(&bin);
+        assert_eq!(
+            resolve(Arch::Aarch64).chosen.unwrap().source,
+            CliSource::Update
+        );
+        let configured = resolve_in(
+            Some(&update),
+            None,
+            Some(resources.clone()),
+            Arch::X86_64,
+            |_| None,
+        );
+        assert_eq!(configured.chosen.unwrap().source, CliSource::Configured);
+    }
+
+    #[test]
+    fn only_incompatible_builds_is_an_error() {
+        let paths = TempPaths::new();
+        let resources = paths.root().join("resources");
+        install(&resources.join(Arch::Aarch64.triple()));
+
+        let resolution = resolve_in(None, None, Some(resources), Arch::X86_64, |_| None);
+        assert_eq!(resolution.chosen, None);
+        assert!(resolution
+            .program()
+            .unwrap_err()
+            .contains(&Arch::Aarch64.triple()));
+
+        let paths = TempPaths::new();
+        let nothing = resolve_in(
+            None,
+            None,
+            Some(paths.root().to_path_buf()),
+            Arch::X86_64,
+            |_| None,
+        );
+        assert_eq!(nothing.program().unwrap(), NAME);
+    }
+}
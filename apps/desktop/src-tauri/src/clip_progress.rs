@@ -0,0 +1,388 @@
+//! Clip Progress
+//!
+//! Frame extraction and camera matching work through a job's clips one at a
+//! time, so their progress is attributed to a clip: the one the CLI names in a
+//! progress line's `item`, the one a backend status message is about, or
+//! failing both, the clip the stage's percentage falls on given the order of
+//! the inputs. Time between progress lines is credited to the clip they were
+//! about, which gives the per-clip breakdown kept with the queue entry and the
+//! history record. The same time is also summed per stage for the whole job.
+//!
+//! The CLI's extraction percentage starts over with each clip. Weighted by the
+//! frames each clip should yield, which its duration gives, it becomes one
+//! percentage for the whole stage, held from ever moving backwards.
+
+use crate::runner::{CliWarning, EventSink, ItemCount, ItemRef, ProcessProgress};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Instant;
+
+/// Stages that work clip by clip
+const PER_CLIP_STAGES: [&str; 2] = [EXTRACTING_FRAMES, "detecting_cameras"];
+
+const EXTRACTING_FRAMES: &str = "extracting_frames";
+
+/// Share by which the extracted frames may differ from the expected ones
+/// before it is worth a warning
+const FRAME_MISMATCH: f64 = 0.2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipProgress {
+    pub video: String,
+    /// Seconds spent on the clip in each per-clip stage
+    pub seconds: BTreeMap<String, f64>,
+    /// Stages the clip is done with
+    pub finished: Vec<String>,
+    /// Some of its progress was attributed from the clip order, as neither the
+    /// CLI nor the backend named the clip
+    pub estimated: bool,
+}
+
+/// Frames expected from the clips' durations, and those extracted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameCounts {
+    pub expected: usize,
+    /// None when the extracted frames could not be counted
+    pub actual: Option<usize>,
+}
+
+impl FrameCounts {
+    /// Far enough apart to suggest a clip did not decode fully. Filtering
+    /// drops frames on purpose, so then only a surplus counts.
+    pub fn mismatched(&self, filtered: bool) -> bool {
+        let Some(actual) = self.actual else {
+            return false;
+        };
+        let expected = self.expected as f64;
+        let actual = actual as f64;
+        actual > expected * (1.0 + FRAME_MISMATCH)
+            || (!filtered && actual < expected * (1.0 - FRAME_MISMATCH))
+    }
+}
+
+/// Attributes a job's progress to its clips
+pub struct ClipTracker {
+    clips: Vec<ClipProgress>,
+    /// The clip and stage of the last attributed progress, and since when
+    current: Option<(usize, String, Instant)>,
+    /// How much of the extraction each clip is; equal shares unless the
+    /// expected frames are known
+    weights: Vec<usize>,
+    /// Highest overall extraction percentage passed on so far
+    extracted: f64,
+    /// Seconds spent in each stage, clip by clip or not
+    stages: BTreeMap<String, f64>,
+    /// The stage of the last progress, and since when
+    stage: Option<(String, Instant)>,
+}
+
+impl ClipTracker {
+    pub fn new(videos: &[String]) -> Self {
+        Self {
+            clips: videos
+                .iter()
+                .map(|video| ClipProgress {
+                    video: video.clone(),
+                    seconds: BTreeMap::new(),
+                    finished: vec![],
+                    estimated: false,
+                })
+                .collect(),
+            current: None,
+            weights: vec![1; videos.len()],
+            extracted: 0.0,
+            stages: BTreeMap::new(),
+            stage: None,
+        }
+    }
+
+    /// Weight the clips' extraction by the frames they should yield
+    pub fn expect_frames(&mut self, frames: &[usize]) {
+        if frames.len() == self.clips.len() && frames.iter().any(|f| *f > 0) {
+            self.weights = frames.to_vec();
+        }
+    }
+
+    pub fn clips(&self) -> &[ClipProgress] {
+        &self.clips
+    }
+
+    pub fn stages(&self) -> &BTreeMap<String, f64> {
+        &self.stages
+    }
+
+    /// Fill in the clip `progress` is about; true when the clip or stage changed
+    pub fn attribute(&mut self, progress: &mut ProcessProgress, now: Instant) -> bool {
+        let stage = progress.stage.as_str();
+        let per_clip = PER_CLIP_STAGES.contains(&stage) && !self.clips.is_empty();
+        let named = progress
+            .reported_item
+            .as_ref()
+            .and_then(|item| self.find(item))
+            .or_else(|| {
+                let video = progress.message.as_ref()?.params.get("video")?;
+                self.clips.iter().position(|c| c.video == *video)
+            });
+        let index = match (per_clip, named) {
+            (false, _) => None,
+            (true, Some(i)) => Some(i),
+            (true, None) => {
+                let share = progress.progress.clamp(0.0, 100.0) / 100.0;
+                let i = ((share * self.clips.len() as f64) as usize).min(self.clips.len() - 1);
+                self.clips[i].estimated = true;
+                Some(i)
+            }
+        };
+
+        let changed = match &self.current {
+            Some((i, current_stage, _)) => index != Some(*i) || !per_clip || current_stage != stage,
+            None => index.is_some(),
+        };
+        self.credit(now);
+        self.stage = Some((stage.to_string(), now));
+        if changed {
+            if let Some((i, current_stage, _)) = self.current.take() {
+                let finished = &mut self.clips[i].finished;
+                if !finished.contains(&current_stage) {
+                    finished.push(current_stage);
+                }
+            }
+        }
+        self.current = index.map(|i| (i, stage.to_string(), now));
+        if per_clip && stage == EXTRACTING_FRAMES {
+            progress.progress = self.overall_extraction(named, progress.progress);
+        }
+
+        if let Some(i) = index {
+            progress.current_item = Some(self.clips[i].video.clone());
+            progress.items = Some(ItemCount {
+                done: self
+                    .clips
+                    .iter()
+                    .filter(|c| c.finished.iter().any(|s| s == stage))
+                    .count(),
+                total: self.clips.len(),
+            });
+        }
+        changed
+    }
+
+    /// The whole stage's percentage, from one for the `named` clip alone
+    fn overall_extraction(&mut self, named: Option<usize>, percent: f64) -> f64 {
+        let percent = percent.clamp(0.0, 100.0);
+        let overall = match named {
+            Some(i) => {
+                let total: usize = self.weights.iter().sum();
+                let before: usize = self.weights[..i].iter().sum();
+                (before as f64 + self.weights[i] as f64 * percent / 100.0) * 100.0 / total as f64
+            }
+            // Attributed from the clip order, which reads it as the stage's already
+            None => percent,
+        };
+        self.extracted = self.extracted.max(overall);
+        self.extracted
+    }
+
+    /// Credit the time since the last progress to the clip it was about
+    pub fn finish(&mut self, now: Instant) {
+        self.credit(now);
+        self.current = None;
+        self.stage = None;
+    }
+
+    fn credit(&mut self, now: Instant) {
+        if let Some((stage, since)) = &self.stage {
+            let elapsed = now.saturating_duration_since(*since).as_secs_f64();
+            *self.stages.entry(stage.clone()).or_insert(0.0) += elapsed;
+        }
+        if let Some((i, stage, since)) = &mut self.current {
+            let elapsed = now.saturating_duration_since(*since).as_secs_f64();
+            *self.clips[*i].seconds.entry(stage.clone()).or_insert(0.0) += elapsed;
+            *since = now;
+        }
+    }
+
+    fn find(&self, item: &ItemRef) -> Option<usize> {
+        match item {
+            ItemRef::Index(i) => (*i < self.clips.len()).then_some(*i),
+            ItemRef::Name(name) => {
+                let name = Path::new(name);
+                let clip = |c: &ClipProgress| Path::new(&c.video).to_path_buf();
+                self.clips
+                    .iter()
+                    .position(|c| clip(c) == name)
+                    .or_else(|| {
+                        let file_name = name.file_name();
+                        self.clips
+                            .iter()
+                            .position(|c| clip(c).file_name() == file_name)
+                    })
+                    // A preview's proxies keep the stem of their clip
+                    .or_else(|| {
+                        let stem = name.file_stem();
+                        self.clips.iter().position(|c| clip(c).file_stem() == stem)
+                    })
+            }
+        }
+    }
+}
+
+/// Attributes progress on its way to `events`, passing on the breakdown as it changes
+pub struct ClipSink<'a> {
+    pub tracker: ClipTracker,
+    pub events: &'a mut dyn EventSink,
+}
+
+impl EventSink for ClipSink<'_> {
+    fn line(&mut self, line: &str) {
+        self.events.line(line);
+    }
+
+    fn progress(&mut self, progress: &ProcessProgress) {
+        let mut progress = progress.clone();
+        if self.tracker.attribute(&mut progress, Instant::now()) {
+            self.events.clips(self.tracker.clips());
+        }
+        self.events.progress(&progress);
+    }
+
+    fn warning(&mut self, warning: &CliWarning) {
+        self.events.warning(warning);
+    }
+
+    fn clips(&mut self, clips: &[ClipProgress]) {
+        self.events.clips(clips);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Message;
+    use std::time::Duration;
+
+    fn progress(stage: &str, percent: f64, item: Option<ItemRef>) -> ProcessProgress {
+        ProcessProgress {
+            stage: stage.to_string(),
+            progress: percent,
+            message: None,
+            raw: None,
+            current_item: None,
+            items: None,
+            reported_item: item,
+            synthetic: false,
+        }
+    }
+
+    fn clips() -> Vec<String> {
+        ["/clips/cam1.mp4", "/clips/cam2.mp4", "/clips/cam3.mp4"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    #[test]
+    fn named_clips_are_credited_with_their_time() {
+        let mut tracker = ClipTracker::new(&clips());
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut first = progress(
+            "extracting_frames",
+            5.0,
+            Some(ItemRef::Name("cam2.mp4".into())),
+        );
+        assert!(tracker.attribute(&mut first, at(0)));
+        assert_eq!(first.current_item.as_deref(), Some("/clips/cam2.mp4"));
+        assert_eq!(first.items, Some(ItemCount { done: 0, total: 3 }));
+
+        let mut same = progress(
+            "extracting_frames",
+            20.0,
+            Some(ItemRef::Name("cam2.mp4".into())),
+        );
+        assert!(!tracker.attribute(&mut same, at(4)));
+        let mut next = progress("extracting_frames", 40.0, Some(ItemRef::Index(0)));
+        assert!(tracker.attribute(&mut next, at(10)));
+        assert_eq!(next.current_item.as_deref(), Some("/clips/cam1.mp4"));
+        assert_eq!(next.items, Some(ItemCount { done: 1, total: 3 }));
+
+        // Backend extraction names its clip in the status message
+        let mut backend = progress("extracting_frames", 0.0, None);
+        backend.message =
+            Some(Message::new("progress.extracting_frames").with("video", "/clips/cam3.mp4"));
+        tracker.attribute(&mut backend, at(11));
+        assert_eq!(backend.current_item.as_deref(), Some("/clips/cam3.mp4"));
+
+        let mut training = progress("training_splats", 0.0, None);
+        assert!(tracker.attribute(&mut training, at(12)));
+        assert_eq!(training.current_item, None);
+        tracker.finish(at(100));
+
+        let clips = tracker.clips();
+        assert_eq!(clips[1].seconds["extracting_frames"], 10.0);
+        assert_eq!(clips[0].seconds["extracting_frames"], 1.0);
+        assert_eq!(clips[2].seconds["extracting_frames"], 1.0);
+        assert!(clips
+            .iter()
+            .all(|c| !c.estimated && c.finished == ["extracting_frames"]));
+        assert_eq!(tracker.stages()["extracting_frames"], 12.0);
+        assert_eq!(tracker.stages()["training_splats"], 88.0);
+    }
+
+    #[test]
+    fn per_clip_extraction_becomes_one_rising_percentage() {
+        let mut tracker = ClipTracker::new(&clips());
+        // cam2 is twice as long as the others
+        tracker.expect_frames(&[100, 200, 100]);
+        let start = Instant::now();
+
+        let mut overall = vec![];
+        for (percent, clip) in [
+            (0.0, 0),
+            (50.0, 0),
+            (100.0, 0),
+            (0.0, 1),
+            (50.0, 1),
+            (40.0, 1),
+            (100.0, 2),
+        ] {
+            let mut update = progress("extracting_frames", percent, Some(ItemRef::Index(clip)));
+            tracker.attribute(&mut update, start);
+            overall.push(update.progress);
+        }
+        assert_eq!(overall, [0.0, 12.5, 25.0, 25.0, 50.0, 50.0, 100.0]);
+
+        let mut matching = progress("detecting_cameras", 10.0, Some(ItemRef::Index(0)));
+        tracker.attribute(&mut matching, start);
+        assert_eq!(matching.progress, 10.0);
+
+        let counts = |actual| FrameCounts {
+            expected: 400,
+            actual,
+        };
+        assert!(!counts(Some(390)).mismatched(false));
+        assert!(counts(Some(250)).mismatched(false));
+        assert!(!counts(Some(250)).mismatched(true));
+        assert!(counts(Some(600)).mismatched(true));
+        assert!(!counts(None).mismatched(false));
+    }
+
+    #[test]
+    fn unnamed_progress_is_attributed_by_clip_order() {
+        let mut tracker = ClipTracker::new(&clips());
+        let start = Instant::now();
+
+        for (percent, expected) in [(0.0, 0), (40.0, 1), (70.0, 2), (100.0, 2)] {
+            let mut update = progress("detecting_cameras", percent, None);
+            tracker.attribute(&mut update, start);
+            assert_eq!(update.current_item, Some(clips()[expected].clone()));
+        }
+        assert!(tracker.clips().iter().all(|c| c.estimated));
+
+        // An item the job does not have falls back to the estimate
+        let mut unknown = progress("detecting_cameras", 10.0, Some(ItemRef::Index(7)));
+        tracker.attribute(&mut unknown, start);
+        assert_eq!(unknown.current_item, Some(clips()[0].clone()));
+    }
+}
@@ -2,209 +2,3299 @@
 //!
 //! This module contains all the Tauri commands that can be invoked from the frontend.
 
-use crate::settings::AppSettings;
-use regex::Regex;
+use crate::appearance;
+use crate::archive;
+use crate::artifact_search;
+use crate::artifacts::{self, Artifact, JobOutput};
+use crate::auto_retry::{self, Attempt, AutoRetrying, RetrySettings};
+use crate::cache;
+use crate::camera_groups;
+use crate::camera_intrinsics::{self, DeviceIntrinsics, Hint};
+use crate::cancel_impact::{self, ImpactSink, Stall};
+use crate::capabilities::{
+    self, CliCapabilities, FLAG_AUTO_MASK, FLAG_CHECKPOINT_INTERVAL, FLAG_EQUIRECT_SPLIT,
+    FLAG_IMAGES, FLAG_INCREMENTAL, FLAG_MASKS, FLAG_MAX_BRIGHTNESS, FLAG_MAX_FRAMES,
+    FLAG_MIN_BRIGHTNESS, FLAG_MIN_SHARPNESS, FLAG_START_TIME, FLAG_TONE_MAP, FLAG_WORK_DIR,
+};
+use crate::checkpoints::{self, Checkpoint, CheckpointSettings};
+use crate::cli_location;
+use crate::clip_progress::{ClipProgress, ClipSink, ClipTracker, FrameCounts};
+use crate::conversion;
+use crate::disk;
+use crate::error::{self, AppError};
+use crate::export_progress;
+use crate::extraction::{self, EXTRACT_FPS};
+use crate::ffmpeg;
+use crate::fingerprint;
+use crate::frame_filter::{self, FrameFilter, MIN_SURVIVING_FRAMES};
+use crate::frames_cache;
+use crate::gpu;
+use crate::gpu_contention::{self, VramCheck, VramWait};
+use crate::history::{self, JobRecord, JobStatus};
+use crate::hooks::{self, HookContext, PostRunHook};
+use crate::input_readiness;
+use crate::job_events::{self, BusSink, JobEvent};
+use crate::job_log::{self, JobLog};
+use crate::jobs;
+use crate::masks;
+use crate::media::{self, Projection, Resolution, ResolutionMismatch, ResolutionNormalization};
+use crate::messages::{Failure, Message};
+use crate::naming::{self, NameContext};
+use crate::network;
+use crate::output_location;
+use crate::overlap::{self, OverlapVerdict};
+use crate::path_policy::PathPolicy;
+use crate::platform::{PathProvider, ResolvedPaths};
+use crate::platform_support;
+use crate::preferences;
+use crate::prefetch;
+use crate::presets;
+use crate::preview::{self, PREVIEW_MAX_FRAMES};
+use crate::profiles::{self, CaptureType, ProfileOverride};
+use crate::queue::{self, QueueEntry, QueueGuard, QueueStatus};
+use crate::recents;
+use crate::registration::{self, Registration};
+use crate::reuse::{self, ReuseReconstruction};
+use crate::runner::{
+    self, CliSpawner, CliWarning, CommandSpec, EventSink, ProcessProgress, ProcessSpawner,
+    RegistrationSummary, RunError, RunOutcome, Source,
+};
+use crate::scheduler::{self, Pool};
+use crate::secrets;
+use crate::settings::{AppSettings, Persist, SettingsStore};
+use crate::sidecar::{self, ProductionSource, Sidecar};
+use crate::simulator;
+use crate::spawn_diagnosis::{self, diagnose_exit, SpawnDiagnosis};
+use crate::training::{self, TrainingOptions};
+use crate::training_metrics::{self, MetricSample};
+use crate::validation;
+use crate::viewers;
+use crate::volume_watch::{self, RunState, Timing, VolumeChange, VolumeLost};
+use crate::vram_policy::{self, Verdict, VramOverride};
+use crate::workdir::WorkDir;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use tauri::{AppHandle, Emitter, Manager};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_dialog::{DialogExt, FilePath};
+use tokio::sync::oneshot;
+
+/// Extensions offered by the video picker
+const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "mov", "avi", "mkv", "webm", "mts", "m2ts"];
 
 // Global cancellation flag for processing
 static CANCEL_FLAG: AtomicBool = AtomicBool::new(false);
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Held by the request whose jobs are on the CLI, so requests from the GUI and
+/// the command line run one at a time, in the order they were queued
+static TURN: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessArgs {
+    /// Recent production this job belongs to, used to remember its settings
+    #[serde(default)]
+    pub production_id: Option<String>,
     pub videos: Vec<String>,
     pub output_dir: String,
     pub preset: String,
     pub colmap_path: Option<String>,
     pub brush_path: Option<String>,
+    /// Per-clip options, matched to `videos` by path
+    #[serde(default)]
+    pub clips: Vec<ClipOptions>,
+    /// Process 360° and flat clips together despite poor COLMAP results
+    #[serde(default)]
+    pub allow_mixed_projection: bool,
+    /// Directory of per-clip or per-frame mask PNGs
+    #[serde(default)]
+    pub masks: Option<String>,
+    /// Let the CLI mask moving objects automatically
+    #[serde(default)]
+    pub auto_mask: bool,
+    /// Reconstruct all clips together, or each clip into its own production
+    #[serde(default)]
+    pub mode: BatchMode,
+    /// In per-clip mode, skip the remaining clips once one fails
+    #[serde(default)]
+    pub stop_on_error: bool,
+    /// Drop blurred and badly exposed frames before reconstruction
+    #[serde(default)]
+    pub filter_frames: FrameFilter,
+    /// Kind of footage, which selects COLMAP matching flags
+    #[serde(default)]
+    pub capture_type: CaptureType,
+    /// Capture profile flags from settings, replacing the built-in ones
+    #[serde(skip)]
+    pub profile_overrides: Vec<ProfileOverride>,
+    /// The scratch_dir setting
+    #[serde(skip)]
+    pub scratch_dir: Option<String>,
+    /// Overrides the output_name_template setting for this request
+    #[serde(default)]
+    pub output_name_template: Option<String>,
+    /// Set on the per-clip jobs a batch request expands into
+    #[serde(skip)]
+    pub batch_id: Option<String>,
+    /// Run against the built-in simulator instead of gvcore-cli (debug builds only)
+    #[serde(default)]
+    pub simulate: bool,
+    /// Make the simulated run fail partway through this CLI stage
+    #[serde(default)]
+    pub simulate_fail_at_stage: Option<String>,
+    /// Total length of a simulated run
+    #[serde(default)]
+    pub simulate_duration_secs: Option<f64>,
+    /// Extract frames again instead of reusing cached ones
+    #[serde(default)]
+    pub force_reextract: bool,
+    /// Scale clips of different resolutions down to the smallest of them
+    #[serde(default)]
+    pub normalize_resolution: bool,
+    /// Run a quick low-resolution preview instead of the full reconstruction
+    #[serde(default)]
+    pub preview_mode: bool,
+    /// Whether to build on the COLMAP database of a previous run into output_dir
+    #[serde(default)]
+    pub reuse_reconstruction: ReuseReconstruction,
+    /// Brush hyperparameters overriding the preset's
+    #[serde(default)]
+    pub training: Option<TrainingOptions>,
+    /// A name for the job to find it by in the history
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Tags to find the job by in the history
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The checkpoints setting, when it is enabled
+    #[serde(skip)]
+    pub checkpoints: Option<CheckpointSettings>,
+    /// The vram_wait_secs setting, when wait_for_vram is on
+    #[serde(skip)]
+    pub vram_wait_secs: Option<u64>,
+    /// Run even when the GPU has less VRAM than the preset needs
+    #[serde(default, rename = "override")]
+    pub override_vram_policy: bool,
+    /// The preset's entry in the preset_min_vram_gb setting
+    #[serde(skip)]
+    pub min_vram_gb: Option<u32>,
+    /// Who overrode the VRAM policy for this job, when it had to be
+    #[serde(skip)]
+    pub vram_override: Option<VramOverride>,
+    /// Why the VRAM policy could not be checked, when it could not
+    #[serde(skip)]
+    pub vram_warning: Option<Message>,
+    /// The auto_retry setting, when it allows any retries
+    #[serde(skip)]
+    pub retry: Option<RetrySettings>,
+    /// The post_run_hooks setting, when post_run_hooks_enabled is on
+    #[serde(skip)]
+    pub post_run_hooks: Vec<PostRunHook>,
+    /// The known_devices setting
+    #[serde(skip)]
+    pub known_devices: Vec<DeviceIntrinsics>,
+    /// Train even when too few images register
+    #[serde(default)]
+    pub continue_on_poor_registration: bool,
+    /// The min_registered_percent setting
+    #[serde(skip)]
+    pub min_registered_percent: Option<u32>,
+    /// Queue the job even when an identical one is already queued or running
+    #[serde(default)]
+    pub allow_duplicate: bool,
+    /// What the job will make, to tell it apart from the jobs already queued
+    #[serde(skip)]
+    pub fingerprint: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BatchMode {
+    /// One production from every clip
+    #[default]
+    Combined,
+    /// One production per clip, each in a subdirectory of output_dir named after the clip
+    PerClip,
+}
+
+/// Outcome of a per-clip batch, sent with the batch-complete event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub batch_id: String,
+    pub output_dir: String,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub members: Vec<QueueEntry>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ProcessProgress {
-    pub stage: String,
-    pub progress: f64,
-    pub message: Option<String>,
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClipOptions {
+    pub path: String,
+    /// Tone-map HDR footage to SDR before reconstruction
+    #[serde(default)]
+    pub tone_map: bool,
+    #[serde(default)]
+    pub projection: Projection,
+    /// Start offset from analyze_sync, relative to its reference clip
+    #[serde(default)]
+    pub sync_offset_ms: i64,
+    /// Clips naming one group were shot with one physical camera
+    #[serde(default)]
+    pub camera_group: Option<String>,
+}
+
+impl ProcessArgs {
+    /// Options for a clip, falling back to defaults when none were given
+    pub fn clip_options(&self, path: &str) -> ClipOptions {
+        self.clips
+            .iter()
+            .find(|c| c.path == path)
+            .cloned()
+            .unwrap_or_else(|| ClipOptions {
+                path: path.to_string(),
+                ..Default::default()
+            })
+    }
 }
 
 /// Get application settings
 #[tauri::command]
 pub async fn get_settings(app: AppHandle) -> Result<AppSettings, String> {
-    let settings_path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("settings.json");
-
-    if settings_path.exists() {
-        let content = std::fs::read_to_string(&settings_path).map_err(|e| e.to_string())?;
-        serde_json::from_str(&content).map_err(|e| e.to_string())
-    } else {
-        Ok(AppSettings::default())
-    }
+    Ok(app.settings())
 }
 
-/// Save application settings
+/// Save the settings that were sent, merged over the current ones, and return
+/// the result. Programs the app runs are set with set_tool_path, the external
+/// viewer commands and set_post_run_hooks instead.
 #[tauri::command]
-pub async fn save_settings(app: AppHandle, settings: AppSettings) -> Result<(), String> {
-    let settings_path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("settings.json");
+pub async fn save_settings(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    settings: Value,
+) -> Result<AppSettings, AppError> {
+    let presets = capabilities::presets(&app).await.presets;
+    let settings = app.update_settings(Persist::Now, |current| {
+        let merged = validation::settings(settings, &presets, current)?;
+        if let Some(template) = &merged.output_name_template {
+            naming::validate_template(template)?;
+        }
+        network::validate(&merged.network)?;
+        // Trusted from the next launch on, so it must be a folder the user picked
+        let output_dir = &merged.default_output_dir;
+        if *output_dir != current.default_output_dir && !output_dir.is_empty() {
+            policy.check_target(output_dir)?;
+        }
+        *current = merged.clone();
+        Ok::<_, AppError>(merged)
+    })?;
+    cache::configure(&settings);
+    scheduler::configure(&settings);
+    appearance::apply(&app, &settings.appearance);
+    Ok(settings)
+}
+
+/// A program the app runs, which the user may replace with their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tool {
+    Colmap,
+    Brush,
+    Ffmpeg,
+    GvcoreCli,
+}
 
-    let content = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
-    std::fs::write(&settings_path, content).map_err(|e| e.to_string())
+/// Run the program at `path` as `tool`, or the bundled one again without a
+/// path. The program must be an executable picked with pick_tool_executable.
+#[tauri::command]
+pub async fn set_tool_path(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    tool: Tool,
+    path: Option<String>,
+) -> Result<AppSettings, AppError> {
+    let path = path.filter(|p| !p.trim().is_empty());
+    if let Some(path) = &path {
+        policy.check_existing(path)?;
+        viewers::check_executable(Path::new(path))?;
+    }
+    let settings = app.update_settings(Persist::Now, |s| {
+        let configured = match tool {
+            Tool::Colmap => &mut s.colmap_path,
+            Tool::Brush => &mut s.brush_path,
+            Tool::Ffmpeg => &mut s.ffmpeg_path,
+            Tool::GvcoreCli => &mut s.gvcore_cli_path,
+        };
+        *configured = path;
+        Ok::<_, AppError>(s.clone())
+    })?;
+    ffmpeg::configure(&app, &settings);
+    cli_location::configure(&settings);
+    Ok(settings)
 }
 
 /// Open file dialog to pick video files
 #[tauri::command]
-pub async fn pick_videos() -> Result<Vec<String>, String> {
-    // Note: This is a placeholder - actual implementation would use the dialog plugin
-    // The dialog plugin's API differs slightly in Tauri 2.0
-    Ok(vec![])
+pub async fn pick_videos(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+) -> Result<Vec<String>, String> {
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("Select Video Files")
+        .add_filter("Video Files", &VIDEO_EXTENSIONS)
+        .pick_files(move |paths| {
+            tx.send(paths).ok();
+        });
+    allow_picked(&policy, rx).await
 }
 
-/// Open file dialog to pick output directory
+/// Open file dialog to pick output directory, checked against the clips it is for
 #[tauri::command]
-pub async fn pick_output_directory() -> Result<Option<String>, String> {
-    // Note: This is a placeholder - actual implementation would use the dialog plugin
-    Ok(None)
+pub async fn pick_output_directory(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    videos: Option<Vec<String>>,
+) -> Result<Option<String>, AppError> {
+    let Some(output_dir) = pick_folder(&app, &policy, "Select Output Directory").await? else {
+        return Ok(None);
+    };
+    // Clips the webview may not refer to are left out rather than probed
+    let videos = videos.unwrap_or_default();
+    let inputs: Vec<&str> = videos
+        .iter()
+        .map(String::as_str)
+        .filter(|v| policy.check_existing(v).is_ok())
+        .collect();
+    check_output_location(&app, &output_dir, &inputs)?;
+    check_output_writable(&app, &output_dir)?;
+    Ok(Some(output_dir))
 }
 
-/// Get the path to the bundled gvcore-cli executable
+/// Refuse an output directory overlapping the inputs or app_data, or only warn
+/// about it when the unsafe_output_locations setting says so
+fn check_output_location(
+    app: &AppHandle,
+    output_dir: &str,
+    inputs: &[&str],
+) -> Result<(), AppError> {
+    let policy = app.settings().unsafe_output_locations;
+    if let Some(warning) = output_location::check(app, policy, output_dir, inputs)? {
+        job_log::app_line(app, &format!("Unsafe output location: {}", warning));
+        app.emit("output-location-warning", &warning).ok();
+    }
+    Ok(())
+}
+
+/// Refuse an output directory that cannot be written to, suggesting the
+/// default output directory instead
+fn check_output_writable(app: &AppHandle, output_dir: &str) -> Result<(), AppError> {
+    let fallback = app.settings().default_output_dir;
+    output_location::check_writable(Path::new(output_dir), Some(&fallback))
+}
+
+/// Open file dialog to pick a directory of mask images
 #[tauri::command]
-pub async fn get_cli_path(app: AppHandle) -> Result<String, String> {
-    let resource_dir = app.path().resource_dir().map_err(|e| e.to_string())?;
+pub async fn pick_masks_directory(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+) -> Result<Option<String>, String> {
+    pick_folder(&app, &policy, "Select Mask Directory").await
+}
 
-    // Try different extensions based on platform
-    #[cfg(target_os = "windows")]
-    let candidates = ["gvcore-cli.exe", "gvcore-cli.bat", "gvcore-cli.cmd"];
+/// Open file dialog to pick a program for set_tool_path or a post-run hook
+#[tauri::command]
+pub async fn pick_tool_executable(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+) -> Result<Option<String>, String> {
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title("Select Executable")
+        .pick_file(move |path| {
+            tx.send(path.map(|p| vec![p])).ok();
+        });
+    Ok(allow_picked(&policy, rx).await?.pop())
+}
 
-    #[cfg(not(target_os = "windows"))]
-    let candidates = ["gvcore-cli"];
+async fn pick_folder(
+    app: &AppHandle,
+    policy: &PathPolicy,
+    title: &str,
+) -> Result<Option<String>, String> {
+    let (tx, rx) = oneshot::channel();
+    app.dialog()
+        .file()
+        .set_title(title)
+        .pick_folder(move |path| {
+            tx.send(path.map(|p| vec![p])).ok();
+        });
+    Ok(allow_picked(policy, rx).await?.pop())
+}
 
-    for cli_name in candidates {
-        let cli_path = resource_dir.join("resources").join(cli_name);
-        if cli_path.exists() {
-            return Ok(cli_path.to_string_lossy().to_string());
-        }
-    }
+// The user picked these through a native dialog, so the webview may refer to them from now on
+async fn allow_picked(
+    policy: &PathPolicy,
+    rx: oneshot::Receiver<Option<Vec<FilePath>>>,
+) -> Result<Vec<String>, String> {
+    let picked = rx.await.map_err(|e| e.to_string())?.unwrap_or_default();
+    Ok(picked
+        .into_iter()
+        .filter_map(|p| p.into_path().ok())
+        .map(|path| {
+            policy.allow(&path);
+            path.to_string_lossy().to_string()
+        })
+        .collect())
+}
 
-    // Fall back to system PATH
-    Ok("gvcore-cli".to_string())
+/// Get the path to the bundled gvcore-cli executable
+#[tauri::command]
+pub async fn get_cli_path(app: AppHandle) -> Result<String, String> {
+    cli_path(&app)
+}
+
+/// Resolve gvcore-cli for this machine's architecture, falling back to the system PATH
+pub fn cli_path(paths: &impl PathProvider) -> Result<String, String> {
+    cli_location::resolve(paths).program()
 }
 
 /// Process videos using gvcore-cli
 /// CLI command: gvcore-cli run --input /path/cam1.mp4 --input /path/cam2.mp4 --output /path/output --brush-path /path/brush
 /// Progress output format: [stage_name] percent% - message
+/// Returns every artifact the job made and the primary one's path, or in per-clip mode
+/// output_dir, which holds one production per clip
 #[tauri::command]
-pub async fn process_videos(app: AppHandle, args: ProcessArgs) -> Result<String, String> {
-    use std::process::Stdio;
-    use tokio::io::{AsyncBufReadExt, BufReader};
-    use tokio::process::Command;
+pub async fn process_videos(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    args: Value,
+) -> Result<JobOutput, AppError> {
+    // Rather than a CLI that fails to start or a trainer with no GPU to run on
+    platform_support::check(&app)?;
+    let args = prepare_request(&app, &policy, args).await?;
+    match args.mode {
+        BatchMode::Combined => {
+            let (entry_id, queued) = enqueue_combined(&args)?;
+            run_combined(&app, args, &entry_id, queued).await
+        }
+        BatchMode::PerClip => {
+            let _turn = TURN.lock().await;
+            CANCEL_FLAG.store(false, Ordering::SeqCst);
+            let summary = process_batch(&app, &CliSpawner, &mut BusSink, &CANCEL_FLAG, args).await;
+            let failed: Vec<String> = summary
+                .iter()
+                .flat_map(|s| &s.members)
+                .filter(|m| m.status == QueueStatus::Failed)
+                .map(|m| m.entry_id.clone())
+                .collect();
+            job_events::publish(JobEvent::Finished {
+                failed,
+                batch: summary.as_ref().ok().cloned(),
+                cancelled: CANCEL_FLAG.load(Ordering::SeqCst),
+            });
+            let summary = summary?;
+            if summary.completed == 0 {
+                return Err(Message::new("batch.none_completed")
+                    .with("failed", summary.failed)
+                    .with("cancelled", summary.cancelled)
+                    .into_failure()
+                    .into());
+            }
+            Ok(JobOutput {
+                artifact_path: summary.output_dir,
+                artifacts: vec![],
+            })
+        }
+    }
+}
 
-    // Reset cancellation flag
+/// Validate a process_videos request and fill in what comes from the settings
+pub async fn prepare_request(
+    app: &AppHandle,
+    policy: &PathPolicy,
+    args: Value,
+) -> Result<ProcessArgs, AppError> {
+    let mut args = validation::process_args(args, &capabilities::presets(app).await.presets)?;
+    // Each camera group's clips together, for the sidecar as much as the CLI
+    args.videos = camera_groups::ordered(&args);
+    if args.preview_mode {
+        args.output_dir = preview::output_dir(&args.output_dir);
+    }
+    check_job_paths(policy, &args)?;
+    let inputs: Vec<&str> = args
+        .videos
+        .iter()
+        .chain(&args.masks)
+        .map(String::as_str)
+        .collect();
+    check_output_location(app, &args.output_dir, &inputs)?;
+    check_output_writable(app, &args.output_dir)?;
+    archive::check_not_archived(Path::new(&args.output_dir))?;
+    let app_settings = app.settings();
+    if args.output_name_template.is_none() {
+        args.output_name_template = app_settings.output_name_template;
+    }
+    args.profile_overrides = app_settings.capture_profiles;
+    if let Some(defaults) = app_settings.preset_training.get(&args.preset) {
+        let training = args.training.unwrap_or_default().or(defaults);
+        args.training = Some(training).filter(|t| !t.is_empty());
+    }
+    args.scratch_dir = app_settings.scratch_dir;
+    args.label = history::normalize_label(args.label.as_deref());
+    args.tags = recents::normalize_tags(&args.tags);
+    args.checkpoints = Some(app_settings.checkpoints).filter(|c| c.enabled);
+    args.vram_wait_secs = app_settings
+        .wait_for_vram
+        .then_some(app_settings.vram_wait_secs);
+    args.retry = Some(app_settings.auto_retry).filter(|r| r.max_retries > 0);
+    if app_settings.post_run_hooks_enabled {
+        args.post_run_hooks = app_settings.post_run_hooks;
+    }
+    args.min_vram_gb = app_settings.preset_min_vram_gb.get(&args.preset).copied();
+    args.known_devices = app_settings.known_devices;
+    args.min_registered_percent = Some(app_settings.min_registered_percent);
+    if !args.simulate {
+        // Rather than failing partway through extraction on a clip still being copied
+        input_readiness::check(&args.videos).await?;
+        disk::check(&disk::preflight(&args)?)?;
+        // A preview trains with its own light preset
+        if !args.preview_mode {
+            check_vram(app, &mut args).await?;
+        }
+    }
+    if let Some(template) = &args.output_name_template {
+        naming::validate_template(template)?;
+    }
+    let fingerprinted = args.clone();
+    args.fingerprint =
+        Some(scheduler::run_blocking(Pool::Io, move || fingerprint::of(&fingerprinted)).await??);
+    Ok(args)
+}
+
+/// Refuse a job whose preset needs more VRAM than the GPU has, unless the
+/// request overrides the policy; only warn when no VRAM can be detected
+async fn check_vram(app: &AppHandle, args: &mut ProcessArgs) -> Result<(), AppError> {
+    let caps = match cli_path(app) {
+        Ok(cli_path) => capabilities::discover(&cli_path).await,
+        Err(_) => Default::default(),
+    };
+    let required_mb = presets::min_vram_mb(&args.preset, caps.version.as_deref(), args.min_vram_gb);
+    let gpu = gpu::detect().await;
+    let verdict = vram_policy::check(
+        gpu.as_ref(),
+        &args.preset,
+        required_mb,
+        args.override_vram_policy,
+    )
+    .map_err(AppError::InsufficientVram)?;
+    match verdict {
+        Verdict::Enough => {}
+        Verdict::Overridden(vram_override) => args.vram_override = Some(vram_override),
+        Verdict::Undetected(warning) => {
+            app.emit("vram-policy-warning", &warning).ok();
+            args.vram_warning = Some(warning);
+        }
+    }
+    Ok(())
+}
+
+/// Queue a combined request, returning its entry id; fails with
+/// AlreadyQueued when an identical request is queued or running
+pub fn enqueue_combined(args: &ProcessArgs) -> Result<(String, QueueGuard), AppError> {
+    let entry_id = job_log::new_id("entry");
+    let entry = QueueEntry {
+        fingerprint: args.fingerprint.clone(),
+        ..QueueEntry::queued(entry_id.clone(), None, &args.videos, &args.output_dir)
+    };
+    let queued = enqueue_request(args, vec![entry]).map_err(Failure::from)?;
+    Ok((entry_id, queued))
+}
+
+/// Queue the entries of a request, unless it allows duplicates and an
+/// identical one is queued or running
+fn enqueue_request(args: &ProcessArgs, entries: Vec<QueueEntry>) -> Result<QueueGuard, Message> {
+    if args.allow_duplicate {
+        return Ok(queue::enqueue(entries));
+    }
+    queue::enqueue_unique(entries)
+        .map_err(|entry_id| Message::new("job.already_queued").with("entry_id", entry_id))
+}
+
+/// Run a queued combined request once the requests queued before it are over;
+/// the entry leaves the queue when it is over
+pub async fn run_combined(
+    app: &AppHandle,
+    args: ProcessArgs,
+    entry_id: &str,
+    _queued: QueueGuard,
+) -> Result<JobOutput, AppError> {
+    let _turn = TURN.lock().await;
+    // A cancel meant for the request before this one is not carried over
     CANCEL_FLAG.store(false, Ordering::SeqCst);
+    let result = run_entry(app, &CliSpawner, &mut BusSink, &CANCEL_FLAG, entry_id, args).await;
+    let mut failed = vec![];
+    let cancelled = result.is_err()
+        && queue::find(entry_id).is_some_and(|e| e.status == QueueStatus::Cancelled);
+    if result.is_err() && !cancelled {
+        failed.push(entry_id.to_string());
+    }
+    job_events::publish(JobEvent::Finished {
+        failed,
+        batch: None,
+        cancelled,
+    });
+    Ok(result?)
+}
+
+/// Run a per-clip request as one queued job per clip, stopping early only on
+/// cancellation or, with stop_on_error, on the first failure
+pub async fn process_batch(
+    paths: &impl PathProvider,
+    spawner: &impl ProcessSpawner,
+    events: &mut dyn EventSink,
+    cancel: &AtomicBool,
+    args: ProcessArgs,
+) -> Result<BatchSummary, Failure> {
+    // Masks are matched against every clip here, as each job only sees its own
+    if let Some(masks_dir) = &args.masks {
+        check_masks(&args.videos, masks_dir).await?;
+    }
+
+    if let Some(production_id) = &args.production_id {
+        preferences::remember(paths, production_id, &args).ok();
+    }
+
+    let batch_id = job_log::new_id("batch");
+    let jobs = split_per_clip(&args, &batch_id);
+    let entries: Vec<QueueEntry> = jobs
+        .iter()
+        .map(|job| {
+            let entry_id = job_log::new_id("entry");
+            QueueEntry {
+                fingerprint: args.fingerprint.clone(),
+                ..QueueEntry::queued(
+                    entry_id,
+                    Some(batch_id.clone()),
+                    &job.videos,
+                    &job.output_dir,
+                )
+            }
+        })
+        .collect();
+    let entry_ids: Vec<String> = entries.iter().map(|e| e.entry_id.clone()).collect();
+    let _queued = enqueue_request(&args, entries)?;
+
+    let mut stopped = false;
+    for (job, entry_id) in jobs.into_iter().zip(&entry_ids) {
+        if stopped || cancel.load(Ordering::SeqCst) {
+            queue::update(entry_id, |e| e.status = QueueStatus::Cancelled);
+            continue;
+        }
+        if let Err(e) = std::fs::create_dir_all(&job.output_dir) {
+            let error = Message::new("batch.create_dir_failed")
+                .with("dir", &job.output_dir)
+                .into_failure()
+                .raw(e.to_string());
+            queue::update(entry_id, |e| {
+                e.status = QueueStatus::Failed;
+                e.error = Some(error);
+            });
+            stopped = args.stop_on_error;
+            continue;
+        }
+        run_entry(paths, spawner, events, cancel, entry_id, job)
+            .await
+            .ok();
+        // A clip cancelled before its turn does not stop the others
+        stopped = args.stop_on_error
+            && queue::find(entry_id).is_some_and(|e| e.status == QueueStatus::Failed);
+    }
+
+    // Clips cancelled before their turn have already left the queue
+    let members: Vec<QueueEntry> = entry_ids.iter().filter_map(|id| queue::find(id)).collect();
+    let count = |status| members.iter().filter(|e| e.status == status).count();
+    Ok(BatchSummary {
+        batch_id,
+        output_dir: args.output_dir,
+        completed: count(QueueStatus::Completed),
+        failed: count(QueueStatus::Failed),
+        cancelled: count(QueueStatus::Cancelled),
+        members,
+    })
+}
 
-    let cli_path = get_cli_path(app.clone()).await?;
+/// One job per clip, each writing to its own subdirectory named after the clip
+fn split_per_clip(args: &ProcessArgs, batch_id: &str) -> Vec<ProcessArgs> {
+    let mut used = HashSet::new();
+    args.videos
+        .iter()
+        .map(|video| {
+            let output_dir = Path::new(&args.output_dir).join(clip_dir_name(video, &mut used));
+            ProcessArgs {
+                videos: vec![video.clone()],
+                clips: args
+                    .clips
+                    .iter()
+                    .filter(|c| &c.path == video)
+                    .cloned()
+                    .collect(),
+                output_dir: output_dir.to_string_lossy().to_string(),
+                mode: BatchMode::Combined,
+                batch_id: Some(batch_id.to_string()),
+                ..args.clone()
+            }
+        })
+        .collect()
+}
+
+// The clip's file stem made safe as a directory name, and unique within the batch
+// regardless of case so clips from different folders do not share a production
+fn clip_dir_name(video: &str, used: &mut HashSet<String>) -> String {
+    let stem = Path::new(video)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let safe: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let base = match safe.trim().trim_matches('.') {
+        "" => "clip".to_string(),
+        trimmed => trimmed.to_string(),
+    };
+
+    let mut name = base.clone();
+    let mut suffix = 2;
+    while !used.insert(name.to_lowercase()) {
+        name = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+    name
+}
+
+/// Run a queued job, keeping its queue entry up to date
+async fn run_entry(
+    paths: &impl PathProvider,
+    spawner: &impl ProcessSpawner,
+    events: &mut dyn EventSink,
+    cancel: &AtomicBool,
+    entry_id: &str,
+    args: ProcessArgs,
+) -> Result<JobOutput, Failure> {
+    if !queue::start(entry_id) {
+        return Err(Message::new("job.cancelled").into());
+    }
+    let mut events = EntrySink { entry_id, events };
+    let mut due_hooks = None;
+    let result = process(paths, spawner, &mut events, cancel, args, &mut due_hooks).await;
+    let status = match &result {
+        Ok(_) => QueueStatus::Completed,
+        Err(_) if cancel.load(Ordering::SeqCst) => QueueStatus::Cancelled,
+        Err(_) => QueueStatus::Failed,
+    };
+    queue::update(entry_id, |e| {
+        e.status = status;
+        e.artifact_path = match &result {
+            Ok(output) => Some(output.artifact_path.clone()),
+            Err(failure) => partial_artifact(failure).map(String::from),
+        };
+        e.error = result.as_ref().err().cloned();
+    });
+    if let Some(due) = due_hooks {
+        run_hooks(ResolvedPaths::of(paths), entry_id.to_string(), due);
+    }
+    result
+}
+
+/// Run a finished job's post-run hooks in the background, so the next job
+/// does not wait for them, adding their warnings to its record and entry
+fn run_hooks(paths: ResolvedPaths, entry_id: String, due: hooks::Pending) {
+    tauri::async_runtime::spawn(async move {
+        let job_id = due.job_id().to_string();
+        let warnings = due.run().await;
+        if warnings.is_empty() {
+            return;
+        }
+        queue::update(&entry_id, |e| e.warnings.extend(warnings.iter().cloned()));
+        for warning in &warnings {
+            job_events::publish(JobEvent::Warning(warning.clone()));
+        }
+        if let Err(e) = history::add_warnings(&paths, &job_id, &warnings) {
+            job_log::app_line(
+                &paths,
+                &format!("Failed to record hook warnings of job {}: {}", job_id, e),
+            );
+        }
+    });
+}
+
+fn check_job_paths(policy: &PathPolicy, args: &ProcessArgs) -> Result<(), AppError> {
+    for video in &args.videos {
+        policy.check_existing(video)?;
+    }
+    policy.check_target(&args.output_dir)?;
+    for path in [&args.masks, &args.colmap_path, &args.brush_path]
+        .into_iter()
+        .flatten()
+    {
+        policy.check_existing(path)?;
+    }
+    Ok(())
+}
+
+/// Run one processing job: preflight checks, the CLI run, then sidecar and
+/// history. The post-run hooks the job's outcome calls for are left in
+/// due_hooks, for the caller to run once the job is over.
+pub async fn process(
+    paths: &impl PathProvider,
+    spawner: &impl ProcessSpawner,
+    events: &mut dyn EventSink,
+    cancel: &AtomicBool,
+    args: ProcessArgs,
+    due_hooks: &mut Option<hooks::Pending>,
+) -> Result<JobOutput, Failure> {
+    let cli_path = cli_path(paths)?;
+    let caps = capabilities::discover(&cli_path).await;
+
+    let projections: Vec<Projection> = args
+        .videos
+        .iter()
+        .map(|v| args.clip_options(v).projection)
+        .collect();
+    if !args.allow_mixed_projection && media::check_projection_mix(&projections).is_err() {
+        return Err(Message::new("job.mixed_projection").into());
+    }
+    if projections.contains(&Projection::Equirect360) && !caps.supports(FLAG_EQUIRECT_SPLIT) {
+        return Err(Message::new("job.cli_no_equirect").into());
+    }
 
-    // Build command arguments using 'run' subcommand
+    if let Some(masks_dir) = &args.masks {
+        if !caps.supports(FLAG_MASKS) {
+            return Err(Message::new("job.cli_no_masks").into());
+        }
+        // A batch already checked the masks against all of its clips
+        if args.batch_id.is_none() {
+            check_masks(&args.videos, masks_dir).await?;
+        }
+    }
+    if args.auto_mask && !caps.supports(FLAG_AUTO_MASK) {
+        return Err(Message::new("job.cli_no_auto_mask").into());
+    }
+
+    let job_id = job_log::new_job_id();
+    let mut log = JobLog::create(paths, &job_id)?;
+    log.line(&format!(
+        "Job {} started with preset {}",
+        job_id, args.preset
+    ));
+    let overlap = overlap::summary_for(&args.videos);
+    if let Some(summary) = overlap
+        .as_ref()
+        .filter(|s| s.verdict != OverlapVerdict::Good)
+    {
+        log.line(&format!(
+            "Proceeding despite overlap verdict {:?}",
+            summary.verdict
+        ));
+    }
+    if let Some(vram_override) = &args.vram_override {
+        log.line(&vram_override.describe());
+    }
+    if let Some(warning) = &args.vram_warning {
+        log.line(&format!("VRAM policy: {}", warning));
+    }
+    let _job = jobs::register(&job_id, &args.output_dir, args.production_id.as_deref());
+    job_events::publish(JobEvent::Started {
+        job_id: job_id.clone(),
+        production_id: args.production_id.clone(),
+    });
+
+    // A batch remembers the whole request rather than each of its clips
+    if let (Some(production_id), None) = (&args.production_id, &args.batch_id) {
+        if let Err(e) = preferences::remember(paths, production_id, &args) {
+            log.line(&format!("Failed to save production defaults: {}", e));
+        }
+    }
+    let started_at = job_log::unix_timestamp();
+
+    // Clip hashes decide whether the previous run's matching can be kept and are
+    // recorded for the next run; unreadable clips just make this a full run
+    let input_sha256 = if args.reuse_reconstruction == ReuseReconstruction::Never {
+        None
+    } else {
+        match reuse::input_hashes(&args.videos, cancel).await {
+            Ok(hashes) => Some(hashes),
+            Err(AppError::Cancelled) => return Err(Message::new("job.cancelled").into()),
+            Err(e) => {
+                log.line(&format!("Failed to hash clips: {}", e));
+                None
+            }
+        }
+    };
+    let output_dir = Path::new(&args.output_dir);
+    let previous = sidecar::read(output_dir).ok().flatten();
+    let reuse = reuse::decide(
+        args.reuse_reconstruction,
+        output_dir,
+        previous.as_ref(),
+        input_sha256.as_deref(),
+        &caps,
+    );
+    log.line(&reuse.describe());
+
+    // Preview proxies are already scaled down to one height
+    let normalization = if args.normalize_resolution && !args.preview_mode {
+        resolution_normalization(paths, &args.videos, &mut log).await
+    } else {
+        None
+    };
+    // Proxies keep none of the tags a clip's device is known by
+    let hints = if args.preview_mode {
+        HashMap::new()
+    } else {
+        intrinsics_hints(paths, &args, &mut log).await
+    };
+
+    let mut warnings = vec![];
+    let mut command = None;
+    let metrics = training_metrics::track(&job_id);
+    let registration = registration::track(&job_id);
+    let _impact = cancel_impact::track(
+        &job_id,
+        &args.preset,
+        args.preview_mode,
+        input_secs(paths, &args),
+        &history::load(paths).unwrap_or_default(),
+    );
+    let mut impact_events = ImpactSink {
+        job_id: &job_id,
+        events,
+    };
+    let expected_frames = expected_frames(paths, &args);
+    let mut tracker = ClipTracker::new(&args.videos);
+    if let Some(expected) = &expected_frames {
+        tracker.expect_frames(expected);
+    }
+    let mut clip_events = ClipSink {
+        tracker,
+        events: &mut impact_events,
+    };
+    // A preview runs on proxies of the clips, while the history keeps the clips themselves
+    let run_args = if args.preview_mode && !simulator::enabled(&args) {
+        preview::proxied_args(&args, &caps, &mut clip_events, cancel).await
+    } else if args.preview_mode {
+        Ok(ProcessArgs {
+            preset: preview::preset(&caps),
+            ..args.clone()
+        })
+    } else {
+        Ok(args.clone())
+    };
+    let preset = match &run_args {
+        Ok(run_args) => run_args.preset.clone(),
+        Err(_) => args.preset.clone(),
+    };
+    let mut attempts = vec![];
+    let mut resumes_from_stage = None;
+    let located = match run_args {
+        Ok(run_args) => loop {
+            let mut attempt = Attempt::start(attempts.len() as u32 + 1, resumes_from_stage.take());
+            let job = CliJob {
+                job_id: &job_id,
+                args: &run_args,
+                cli_path: &cli_path,
+                caps: &caps,
+                database: reuse.database.as_deref().filter(|_| reuse.reused()),
+                normalization: normalization.as_ref(),
+                hints: &hints,
+            };
+            let result = run_cli(
+                job,
+                spawner,
+                &mut clip_events,
+                cancel,
+                &mut log,
+                &mut warnings,
+                &mut command,
+            )
+            .await;
+            attempt.finish(&result);
+            let number = attempt.attempt;
+            attempts.push(attempt);
+
+            let retry = match (&result, &args.retry) {
+                (Err(failure), Some(retry)) if !cancel.load(Ordering::SeqCst) => retry
+                    .retryable(failure, number)
+                    .map(|code| (code, retry.backoff(number), retry.max_retries + 1)),
+                _ => None,
+            };
+            let Some((exit_code, backoff, max_attempts)) = retry else {
+                break result;
+            };
+            // The frames cache spares the next attempt the extraction
+            resumes_from_stage = cancel_impact::resumable_from_stage(&job_id);
+            let retrying = AutoRetrying {
+                job_id: job_id.clone(),
+                attempt: number + 1,
+                max_attempts,
+                exit_code,
+                backoff_secs: backoff.as_secs(),
+                resumes_from_stage: resumes_from_stage.clone(),
+            };
+            log.line(&format!(
+                "Attempt {} failed with exit code {}; retrying in {}s",
+                number, exit_code, retrying.backoff_secs
+            ));
+            job_events::publish(JobEvent::AutoRetrying(retrying));
+            if !auto_retry::wait(backoff, cancel).await {
+                log.line("Cancelled while waiting to retry");
+                break result;
+            }
+        },
+        Err(failure) => Err(failure),
+    };
+    // The primary artifact stands for the job wherever one path is expected
+    let mut artifacts = vec![];
+    let mut result = located.map(|located| {
+        artifacts = located;
+        artifacts[0].path.clone()
+    });
+    // A job that ran once needs no list of its attempts
+    if attempts.len() < 2 {
+        attempts.clear();
+    }
+    let ClipSink {
+        mut tracker,
+        events,
+    } = clip_events;
+    tracker.finish(Instant::now());
+    events.clips(tracker.clips());
+    let (metrics, update) = metrics.finish();
+    let registration = registration.finish();
+    if let Some(update) = update {
+        job_events::publish(JobEvent::TrainingMetrics(update));
+    }
+
+    // Renamed before the sidecar and history are written so they record the final name
+    if let (Ok(artifact_path), Some(template)) = (&mut result, &args.output_name_template) {
+        let production = Path::new(&args.output_dir)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let context = NameContext {
+            production: &production,
+            preset: &preset,
+            timestamp: started_at,
+        };
+        match naming::rename_artifact(Path::new(artifact_path.as_str()), template, &context) {
+            Ok(renamed) => {
+                log.line(&format!("Renamed artifact to {}", renamed.display()));
+                *artifact_path = renamed.to_string_lossy().to_string();
+                artifacts[0].path = artifact_path.clone();
+            }
+            Err(e) => log.line(&format!("Failed to rename artifact: {}", e)),
+        }
+    }
+
+    let status = match &result {
+        Ok(_) => JobStatus::Completed,
+        Err(_) if cancel.load(Ordering::SeqCst) => JobStatus::Cancelled,
+        Err(_) => JobStatus::Failed,
+    };
+
+    // Far fewer frames than the durations promise usually means a clip did not decode
+    let frames = expected_frames.map(|expected| FrameCounts {
+        expected: expected.iter().sum(),
+        actual: (status == JobStatus::Completed)
+            .then(|| extracted_frames(output_dir, command.as_ref()))
+            .flatten(),
+    });
+    if let Some(counts) = frames.filter(|c| c.mismatched(args.filter_frames.enabled)) {
+        let warning = CliWarning {
+            code: "frame-count-mismatch".to_string(),
+            message: format!(
+                "Expected about {} frames from the clips' durations, but {} were extracted; a clip may not have decoded fully",
+                counts.expected,
+                counts.actual.unwrap_or_default()
+            ),
+        };
+        log.line(&warning.message);
+        events.warning(&warning);
+        warnings.push(warning);
+    }
+
+    // The checksums let a later verify_artifact catch truncated or corrupted copies
+    let mut artifact_sha256 = None;
+    if let Ok(artifact_path) = &result {
+        artifacts::measure(&mut artifacts, cancel, &mut log).await;
+        artifact_sha256 = artifacts[0].sha256.clone();
+
+        let sidecar = Sidecar {
+            job_id: job_id.clone(),
+            production_dir: args.output_dir.clone(),
+            artifact_path: artifact_path.clone(),
+            preset: preset.clone(),
+            videos: args.videos.clone(),
+            created_at: job_log::unix_timestamp(),
+            artifact_sha256: artifact_sha256.clone(),
+            capture_type: args.capture_type,
+            archive: None,
+            preview: args.preview_mode,
+            input_sha256: input_sha256.clone().unwrap_or_default(),
+            command: command.clone(),
+            training: args
+                .training
+                .as_ref()
+                .map(|t| training::apply(t, &caps).options)
+                .filter(|t| !t.is_empty()),
+            // Made from an earlier artifact, which they still name by its checksum
+            derivatives: sidecar::read(Path::new(&args.output_dir))
+                .ok()
+                .flatten()
+                .map(|s| s.derivatives)
+                .unwrap_or_default(),
+            source: ProductionSource::Job,
+            imported_from: None,
+            resolution_normalization: normalization.clone(),
+            camera_groups: camera_groups::of(&args),
+            artifacts: artifacts.clone(),
+        };
+        if let Err(e) = sidecar::write(Path::new(&args.output_dir), &sidecar) {
+            log.line(&format!("Failed to write sidecar: {}", e));
+        }
+    }
+
+    let record = JobRecord {
+        job_id: job_id.clone(),
+        batch_id: args.batch_id.clone(),
+        status,
+        preset,
+        videos: args.videos.clone(),
+        output_dir: args.output_dir.clone(),
+        artifact_path: match &result {
+            Ok(artifact_path) => Some(artifact_path.clone()),
+            Err(failure) => partial_artifact(failure).map(String::from),
+        },
+        error: result.as_ref().err().map(ToString::to_string),
+        started_at,
+        finished_at: job_log::unix_timestamp(),
+        overlap,
+        artifact_sha256,
+        capture_type: args.capture_type,
+        partial: result.as_ref().err().and_then(partial_artifact).is_some(),
+        warnings,
+        clips: tracker.clips().to_vec(),
+        stages: tracker.stages().clone(),
+        input_secs: input_secs(paths, &args),
+        preview: args.preview_mode,
+        args: Some(args.clone()),
+        reuse: Some(reuse),
+        command,
+        metrics,
+        frames,
+        missing: false,
+        label: args.label.clone(),
+        tags: args.tags.clone(),
+        attempts,
+        artifacts: artifacts.clone(),
+        vram_override: args.vram_override.clone(),
+        registration,
+        verification: None,
+    };
+    if let Err(e) = history::record(paths, record) {
+        log.line(&format!("Failed to record history: {}", e));
+    }
+
+    let context = HookContext {
+        job_id: &job_id,
+        status,
+        artifact: match &result {
+            Ok(artifact_path) => Some(artifact_path.as_str()),
+            Err(failure) => partial_artifact(failure),
+        },
+        output_dir: &args.output_dir,
+    };
+    *due_hooks = hooks::Pending::new(&args.post_run_hooks, &context, log);
+
+    result.map(|artifact_path| JobOutput {
+        artifact_path,
+        artifacts,
+    })
+}
+
+/// Sync offsets are relative to a reference clip; the clip that started last skips nothing
+fn earliest_offset(args: &ProcessArgs) -> i64 {
+    args.videos
+        .iter()
+        .map(|v| args.clip_options(v).sync_offset_ms)
+        .min()
+        .unwrap_or(0)
+}
+
+/// Frames each clip should yield, from the durations probed before the job
+/// was started; None unless every clip's is known
+fn expected_frames(paths: &impl PathProvider, args: &ProcessArgs) -> Option<Vec<usize>> {
+    let earliest_offset = earliest_offset(args);
+    args.videos
+        .iter()
+        .map(|video| {
+            let duration = prefetch::cached_metadata(paths, video)?.duration_secs;
+            let start_secs =
+                (args.clip_options(video).sync_offset_ms - earliest_offset) as f64 / 1000.0;
+            Some(extraction::expected_frames(duration, start_secs))
+        })
+        .collect()
+}
+
+/// Total length of the clips, from the durations probed before the job was
+/// started; None unless every clip's is known
+fn input_secs(paths: &impl PathProvider, args: &ProcessArgs) -> Option<f64> {
+    args.videos
+        .iter()
+        .map(|video| Some(prefetch::cached_metadata(paths, video)?.duration_secs))
+        .sum()
+}
+
+/// How the clips are brought to one resolution, when they differ; a clip that
+/// cannot be probed is left as it is
+async fn resolution_normalization(
+    paths: &impl PathProvider,
+    videos: &[String],
+    log: &mut JobLog,
+) -> Option<ResolutionNormalization> {
+    let mut resolutions = vec![];
+    for video in videos {
+        let metadata = match prefetch::cached_metadata(paths, video) {
+            Some(metadata) => Ok(metadata),
+            None => media::probe(video).await,
+        };
+        match metadata {
+            Ok(metadata) => resolutions.push((video.as_str(), metadata.resolution())),
+            Err(e) => log.line(&format!(
+                "Clip {}: resolution unknown, left unscaled: {}",
+                video, e
+            )),
+        }
+    }
+    let normalization = ResolutionMismatch::of(&resolutions)?.normalization();
+    log.line(&format!(
+        "Normalizing resolution to {}, scaling down {}",
+        normalization.target,
+        normalization.scaled.join(", ")
+    ));
+    Some(normalization)
+}
+
+/// Prior intrinsics of the clips shot with a known device, at the clips' own
+/// resolution; equirectangular clips are split into views with intrinsics of
+/// their own, and a clip that cannot be probed gets none
+async fn intrinsics_hints(
+    paths: &impl PathProvider,
+    args: &ProcessArgs,
+    log: &mut JobLog,
+) -> HashMap<String, Hint> {
+    let mut hints = HashMap::new();
+    for video in &args.videos {
+        if args.clip_options(video).projection == Projection::Equirect360 {
+            continue;
+        }
+        let metadata = match prefetch::cached_metadata(paths, video) {
+            Some(metadata) => metadata,
+            None => match media::probe(video).await {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            },
+        };
+        let Some(device) = &metadata.device else {
+            continue;
+        };
+        let Some(intrinsics) = camera_intrinsics::lookup(device, &args.known_devices) else {
+            log.line(&format!(
+                "Clip {}: no intrinsics known for {} {}",
+                video, device.make, device.model
+            ));
+            continue;
+        };
+        log.line(&format!(
+            "Clip {}: shot with {}, hinting its intrinsics",
+            video,
+            intrinsics.describe()
+        ));
+        hints.insert(video.clone(), intrinsics.hint(metadata.resolution()));
+    }
+    hints
+}
+
+/// The failure of a job stopped because too few of its images registered
+fn low_registration(poor: &Registration, args: &ProcessArgs) -> Message {
+    let minimum = args
+        .min_registered_percent
+        .unwrap_or(registration::DEFAULT_MIN_REGISTERED_PERCENT);
+    Message::new("job.aborted_low_registration")
+        .with("registered", poor.registered)
+        .with("total", poor.total)
+        .with("percent", format!("{:.0}", poor.percent()))
+        .with("minimum", minimum)
+        .with("clips", poor.unregistered_clips.join("\n"))
+}
+
+/// Frames the CLI read: those it extracted into the production and those
+/// handed to it with --images; None when there are none to count
+fn extracted_frames(output_dir: &Path, command: Option<&CommandSpec>) -> Option<usize> {
+    let mut count = extraction::count_frames(&output_dir.join("frames"));
+    if let Some(command) = command {
+        for pair in command.args.windows(2) {
+            if pair[0] == FLAG_IMAGES {
+                count += extraction::count_frames(Path::new(&pair[1]));
+            }
+        }
+    }
+    (count > 0).then_some(count)
+}
+
+async fn check_masks(videos: &[String], masks_dir: &str) -> Result<(), Failure> {
+    let validation = masks::validate(videos, masks_dir, false).await?;
+    if validation.valid {
+        return Ok(());
+    }
+    let problems: Vec<String> = validation
+        .issues
+        .iter()
+        .map(|issue| format!("{}: {}", issue.file, issue.problem))
+        .collect();
+    Err(Message::new("job.invalid_masks")
+        .into_failure()
+        .raw(problems.join("; ")))
+}
+
+// Check a clip's masks against its frames as the CLI gets them
+fn check_clip_masks(
+    video: &str,
+    masks_dir: &str,
+    size: Option<Resolution>,
+    frames: Option<usize>,
+    log: &mut JobLog,
+) -> Result<(), Failure> {
+    masks::check_frames(video, masks_dir, size, frames).map_err(|issue| {
+        let problem = format!("{}: {}", issue.file, issue.problem);
+        log.line(&problem);
+        Message::new("job.invalid_masks")
+            .into_failure()
+            .raw(problem)
+    })
+}
+
+/// What run_cli needs to know about the job
+struct CliJob<'a> {
+    job_id: &'a str,
+    args: &'a ProcessArgs,
+    cli_path: &'a str,
+    caps: &'a CliCapabilities,
+    /// A previous run's COLMAP database to register only the new images into
+    database: Option<&'a str>,
+    /// Clips to scale down to the smallest resolution among them
+    normalization: Option<&'a ResolutionNormalization>,
+    /// Prior intrinsics of the clips, by clip
+    hints: &'a HashMap<String, Hint>,
+}
+
+/// Build the CLI arguments, run it and stream its progress to `events`
+async fn run_cli(
+    job: CliJob<'_>,
+    spawner: &impl ProcessSpawner,
+    events: &mut dyn EventSink,
+    cancel: &AtomicBool,
+    log: &mut JobLog,
+    warnings: &mut Vec<CliWarning>,
+    command: &mut Option<CommandSpec>,
+) -> Result<Vec<Artifact>, Failure> {
+    let CliJob {
+        job_id,
+        args,
+        cli_path,
+        caps,
+        database,
+        normalization,
+        hints,
+    } = job;
+
+    // The CLI writes into the job's own directory; results reach the production once it succeeds
+    let output_dir = Path::new(&args.output_dir);
+    let work = WorkDir::create(output_dir, job_id).map_err(|e| e.to_string())?;
     let mut cmd_args = vec![
         "run".to_string(),
         "--output".to_string(),
-        args.output_dir.clone(),
+        work.output().to_string_lossy().to_string(),
     ];
+    if caps.supports(FLAG_WORK_DIR) {
+        cmd_args.push(FLAG_WORK_DIR.to_string());
+        cmd_args.push(work.temp().to_string_lossy().to_string());
+    }
 
-    // Add each video as --input
+    // Blurred and badly exposed frames are dropped by the CLI when it can take
+    // every bound, otherwise here
+    let filter = &args.filter_frames;
+    let cli_filters = [FLAG_MIN_SHARPNESS, FLAG_MIN_BRIGHTNESS, FLAG_MAX_BRIGHTNESS]
+        .iter()
+        .all(|flag| caps.supports(flag));
+    let filter_here = filter.enabled && !cli_filters;
+    if filter.enabled && !filter_here {
+        log.line(&format!(
+            "Frame filtering delegated to CLI at sharpness {} and brightness {}-{}",
+            filter.blur_threshold, filter.min_brightness, filter.max_brightness
+        ));
+        cmd_args.push(FLAG_MIN_SHARPNESS.to_string());
+        cmd_args.push(filter.blur_threshold.to_string());
+        cmd_args.push(FLAG_MIN_BRIGHTNESS.to_string());
+        cmd_args.push(filter.min_brightness.to_string());
+        cmd_args.push(FLAG_MAX_BRIGHTNESS.to_string());
+        cmd_args.push(filter.max_brightness.to_string());
+    }
+
+    let earliest_offset = earliest_offset(args);
+
+    // Frames the backend extracts are cached across runs; eviction spares these until the run ends
+    let frames = frames_cache::cache(args.scratch_dir.as_deref());
+    let mut leases = vec![];
+    let mut hinted_inputs = vec![];
+    let mut grouped_inputs = vec![];
+
+    // Add each video as --input, or as pre-extracted frames when the CLI cannot tone-map,
+    // filter or trim it itself, or it is scaled down to match the other clips
     for video in &args.videos {
-        cmd_args.push("--input".to_string());
-        cmd_args.push(video.clone());
+        let options = args.clip_options(video);
+        let start_secs = (options.sync_offset_ms - earliest_offset) as f64 / 1000.0;
+        let tone_map_here = options.tone_map && !caps.supports(FLAG_TONE_MAP);
+        let trim_here = start_secs > 0.0 && !caps.supports(FLAG_START_TIME);
+        let scale = normalization
+            .filter(|n| n.scaled.contains(video))
+            .map(|n| n.target);
+        // Per-frame masks are counted against the frames, which must be extracted here for that
+        let per_frame_masks = args
+            .masks
+            .as_deref()
+            .is_some_and(|masks_dir| masks::per_frame(video, masks_dir));
+        let extract_here =
+            tone_map_here || filter_here || trim_here || scale.is_some() || per_frame_masks;
+
+        // Per-clip flags refer to the input by the path it was handed to the CLI with
+        let input = if !extract_here {
+            if options.tone_map {
+                log.line(&format!("Clip {}: tone mapping delegated to CLI", video));
+            } else {
+                log.line(&format!("Clip {}: tone mapping off", video));
+            }
+            cmd_args.push("--input".to_string());
+            cmd_args.push(video.clone());
+            if options.tone_map {
+                cmd_args.push(FLAG_TONE_MAP.to_string());
+                cmd_args.push(video.clone());
+            }
+            if start_secs > 0.0 {
+                log.line(&format!(
+                    "Clip {}: starts {:.3}s in to sync",
+                    video, start_secs
+                ));
+                cmd_args.push(FLAG_START_TIME.to_string());
+                cmd_args.push(video.clone());
+                cmd_args.push(format!("{:.3}", start_secs));
+            }
+            if let Some(masks_dir) = &args.masks {
+                // A preview's clip is its proxy, so this is the size the CLI gets
+                let size = media::probe(video).await.map_err(|e| {
+                    log.line(&format!("{}: {}", video, e));
+                    Message::new("job.invalid_masks").into_failure().raw(e)
+                })?;
+                check_clip_masks(video, masks_dir, Some(size.resolution()), None, log)?;
+            }
+            video.clone()
+        } else if caps.supports(FLAG_IMAGES) {
+            let extraction = frames_cache::Extraction {
+                video,
+                fps: EXTRACT_FPS,
+                start_secs,
+                tone_map: options.tone_map,
+                filter: filter_here.then_some(filter),
+                scale,
+            };
+            let key = frames_cache::key(&extraction, cancel)
+                .await
+                .map_err(|e| match e {
+                    AppError::Cancelled => Message::new("job.cancelled").into(),
+                    e => e.failure(),
+                })?;
+            let cached = if args.force_reextract {
+                None
+            } else {
+                frames_cache::lookup(&frames, &key)
+            };
+            let (frames_dir, lease) = match cached {
+                Some((frames_dir, lease)) => {
+                    log.line(&format!(
+                        "Clip {}: reusing frames from {}",
+                        video,
+                        frames_dir.display()
+                    ));
+                    job_events::publish(JobEvent::FramesCacheHit {
+                        video: video.clone(),
+                        frames_dir: frames_dir.to_string_lossy().to_string(),
+                    });
+                    (frames_dir, lease)
+                }
+                None => {
+                    let staging = frames_cache::staging_dir(&frames, &key)?;
+                    let target = FrameTarget {
+                        frames_dir: &staging,
+                        start_secs,
+                        scale,
+                    };
+                    let extracted = extract_clip_frames(video, target, &options, events, log).await;
+                    let filtered = match extracted {
+                        Ok(()) if filter_here => {
+                            filter_clip_frames(video, &staging, filter, events, log).await
+                        }
+                        other => other,
+                    };
+                    if let Err(e) = filtered {
+                        std::fs::remove_dir_all(&staging).ok();
+                        return Err(e);
+                    }
+                    frames_cache::store(&frames, &key, &staging)?
+                }
+            };
+            leases.push(lease);
+            // Per-frame masks can only be counted now that the frames are known
+            if let Some(masks_dir) = &args.masks {
+                let size = masks::frame_size(&frames_dir);
+                let frames = extraction::count_frames(&frames_dir);
+                check_clip_masks(video, masks_dir, size, Some(frames), log)?;
+            }
+
+            let frames_dir = frames_dir.to_string_lossy().to_string();
+            cmd_args.push(FLAG_IMAGES.to_string());
+            cmd_args.push(frames_dir.clone());
+            frames_dir
+        } else if tone_map_here {
+            log.line(&format!(
+                "Clip {}: tone mapping unavailable, CLI supports neither {} nor {}",
+                video, FLAG_TONE_MAP, FLAG_IMAGES
+            ));
+            return Err(Message::new("job.cli_cannot_tone_map")
+                .with("video", video)
+                .into());
+        } else if filter_here {
+            log.line(&format!(
+                "Clip {}: frame filtering unavailable, CLI supports neither {}, {} and {} nor {}",
+                video, FLAG_MIN_SHARPNESS, FLAG_MIN_BRIGHTNESS, FLAG_MAX_BRIGHTNESS, FLAG_IMAGES
+            ));
+            return Err(Message::new("job.cli_cannot_filter")
+                .with("video", video)
+                .into());
+        } else if let Some(target) = scale.filter(|_| !trim_here) {
+            log.line(&format!(
+                "Clip {}: scaling to {} unavailable, CLI does not support {}",
+                video, target, FLAG_IMAGES
+            ));
+            return Err(Message::new("job.cli_cannot_scale")
+                .with("video", video)
+                .into());
+        } else if per_frame_masks && !trim_here {
+            log.line(&format!(
+                "Clip {}: per-frame masks cannot be counted, CLI does not support {}",
+                video, FLAG_IMAGES
+            ));
+            return Err(Message::new("job.cli_cannot_count_masks")
+                .with("video", video)
+                .into());
+        } else {
+            log.line(&format!(
+                "Clip {}: sync offset unavailable, CLI supports neither {} nor {}",
+                video, FLAG_START_TIME, FLAG_IMAGES
+            ));
+            return Err(Message::new("job.cli_cannot_trim")
+                .with("video", video)
+                .into());
+        };
+
+        let hint = hints.get(video).map(|hint| match scale {
+            Some(target) => hint.fit(target),
+            None => *hint,
+        });
+        hinted_inputs.push((input.clone(), hint));
+        grouped_inputs.push((input.clone(), camera_groups::group_of(args, video)));
+
+        if options.projection == Projection::Equirect360 {
+            log.line(&format!(
+                "Clip {}: equirectangular, split into perspective views",
+                video
+            ));
+            cmd_args.push(FLAG_EQUIRECT_SPLIT.to_string());
+            cmd_args.push(input);
+        }
+    }
+
+    // A new run finds these frames again, so cancelling only loses what came after them
+    if !args.videos.is_empty() && leases.len() == args.videos.len() {
+        cancel_impact::frames_kept(job_id);
+    }
+
+    // Hints only save COLMAP work, so a CLI without the flags runs without them
+    let (hint_args, skipped) = camera_intrinsics::hint_args(&hinted_inputs, caps);
+    if let Some(skipped) = skipped {
+        log.line(&skipped);
+    }
+    cmd_args.extend(hint_args);
+
+    let groups = camera_groups::of(args);
+    let (group_args, skipped) = camera_groups::group_args(&grouped_inputs, caps);
+    if let Some(skipped) = skipped {
+        log.line(&skipped);
+    } else {
+        for group in &groups {
+            log.line(&format!(
+                "Camera group {}: {}",
+                group.name,
+                group.clips.join(", ")
+            ));
+        }
+    }
+    cmd_args.extend(group_args);
+
+    if args.preview_mode {
+        if caps.supports(FLAG_MAX_FRAMES) {
+            log.line(&format!("Preview limited to {} frames", PREVIEW_MAX_FRAMES));
+            cmd_args.push(FLAG_MAX_FRAMES.to_string());
+            cmd_args.push(PREVIEW_MAX_FRAMES.to_string());
+        } else {
+            log.line(&format!(
+                "The installed gvcore-cli does not support {}; preview uses every frame",
+                FLAG_MAX_FRAMES
+            ));
+        }
+    }
+
+    if let Some(database) = database {
+        cmd_args.push(FLAG_INCREMENTAL.to_string());
+        cmd_args.push(database.to_string());
+    }
+
+    // Options this CLI has no flag for are left out rather than failing the job
+    if let Some(options) = &args.training {
+        let applied = training::apply(options, caps);
+        if !applied.args.is_empty() {
+            log.line(&format!("Training with {}", applied.args.join(" ")));
+        }
+        for warning in &applied.warnings {
+            log.line(&warning.message);
+            events.warning(warning);
+            warnings.push(warning.clone());
+        }
+        cmd_args.extend(applied.args);
+    }
+
+    let checkpoints = args
+        .checkpoints
+        .as_ref()
+        .filter(|_| caps.supports(FLAG_CHECKPOINT_INTERVAL));
+    if let Some(settings) = checkpoints {
+        log.line(&format!(
+            "Exporting a checkpoint every {} minutes, keeping {}",
+            settings.interval_minutes, settings.keep
+        ));
+        cmd_args.push(FLAG_CHECKPOINT_INTERVAL.to_string());
+        cmd_args.push(settings.interval_minutes.to_string());
+    } else if args.checkpoints.is_some() {
+        log.line(&format!(
+            "The installed gvcore-cli does not support {}; no checkpoints",
+            FLAG_CHECKPOINT_INTERVAL
+        ));
+    }
+
+    let flags = profiles::flags_for(args.capture_type, &args.profile_overrides);
+    let (flags, unsupported) = profiles::supported_flags(&flags, caps);
+    log.line(&format!(
+        "Capture profile {:?}: {}",
+        args.capture_type,
+        flags.join(" ")
+    ));
+    if !unsupported.is_empty() {
+        log.line(&format!(
+            "The installed gvcore-cli does not support {}; profile runs without them",
+            unsupported.join(", ")
+        ));
+    }
+    cmd_args.extend(flags);
+
+    // Add masks if provided
+    if let Some(masks_dir) = &args.masks {
+        log.line(&format!("Using masks from {}", masks_dir));
+        cmd_args.push(FLAG_MASKS.to_string());
+        cmd_args.push(masks_dir.clone());
+    }
+    if args.auto_mask {
+        log.line("Automatic masking enabled");
+        cmd_args.push(FLAG_AUTO_MASK.to_string());
     }
 
     // Add brush path if provided
-    if let Some(brush) = args.brush_path {
+    if let Some(brush) = &args.brush_path {
         cmd_args.push("--brush-path".to_string());
-        cmd_args.push(brush);
+        cmd_args.push(brush.clone());
     }
 
     // Add colmap path if provided
-    if let Some(colmap) = args.colmap_path {
+    if let Some(colmap) = &args.colmap_path {
         cmd_args.push("--colmap-path".to_string());
-        cmd_args.push(colmap);
-    }
-
-    // Spawn the CLI process
-    let mut child = Command::new(&cli_path)
-        .args(&cmd_args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_err(|e| format!("Failed to spawn CLI: {}", e))?;
-
-    let stdout = child.stdout.take().ok_or("Failed to capture stdout")?;
-    let mut reader = BufReader::new(stdout).lines();
-
-    // Regex to parse progress: [stage_name] percent% - message
-    let progress_re = Regex::new(r"\[(\w+)\] (\d+)% - (.+)")
-        .map_err(|e| format!("Failed to compile regex: {}", e))?;
-
-    // Stream output to frontend
-    while let Some(line) = reader.next_line().await.map_err(|e| e.to_string())? {
-        // Check for cancellation
-        if CANCEL_FLAG.load(Ordering::SeqCst) {
-            child.kill().await.ok();
-            return Err("Processing cancelled".to_string());
-        }
-
-        // Parse progress output: [stage_name] percent% - message
-        if let Some(captures) = progress_re.captures(&line) {
-            let stage = captures.get(1).map(|m| m.as_str()).unwrap_or("unknown");
-            let percent: f64 = captures
-                .get(2)
-                .and_then(|m| m.as_str().parse().ok())
-                .unwrap_or(0.0);
-            let message = captures.get(3).map(|m| m.as_str().to_string());
-
-            // Map CLI stage names to frontend stage names
-            let mapped_stage = match stage {
-                "frame_extraction" => "extracting_frames",
-                "colmap" => "detecting_cameras",
-                "brush" => "training_splats",
-                "metadata" => "exporting",
-                "completed" => "complete",
-                "failed" => "failed",
-                other => other,
-            };
+        cmd_args.push(colmap.clone());
+    }
 
-            let progress = ProcessProgress {
-                stage: mapped_stage.to_string(),
-                progress: percent,
-                message,
+    // Spawn the CLI process, or the simulator standing in for it
+    let mut spec = CommandSpec::new(cli_path, cmd_args);
+    work.apply(&mut spec);
+    match serde_json::to_string(&spec) {
+        Ok(json) => log.line(&format!("Command: {}", json)),
+        Err(e) => log.line(&format!("Failed to record the command: {}", e)),
+    }
+    *command = Some(spec.clone());
+    let source = if simulator::enabled(args) {
+        log.line(&format!("Simulating {}", spec.shell_quoted()));
+        let (stdout, handle) = simulator::spawn(args);
+        Source::Simulated { stdout, handle }
+    } else {
+        log.line(&format!("Running {}", spec.shell_quoted()));
+        spawner.spawn(spec)
+    };
+
+    let run_state = RunState::default();
+    let mut sink = JobSink {
+        events,
+        log,
+        export_started: false,
+        warnings,
+        run_state: &run_state,
+        job_id,
+        videos: &args.videos,
+    };
+    let watched = run_watched(
+        source,
+        &mut sink,
+        cancel,
+        output_dir,
+        &run_state,
+        Watches {
+            job_id,
+            checkpoints: checkpoints.map(|c| (c.keep, checkpoints::dir(&work.output()))),
+            vram: (
+                presets::min_vram_mb(&args.preset, caps.version.as_deref(), args.min_vram_gb),
+                args.vram_wait_secs.map(Duration::from_secs),
+            ),
+            export: (work.output(), sh_degree(args, caps)),
+            min_registered_percent: (!args.continue_on_poor_registration).then(|| {
+                args.min_registered_percent
+                    .unwrap_or(registration::DEFAULT_MIN_REGISTERED_PERCENT)
+            }),
+        },
+    )
+    .await;
+    for checkpoint in &watched.checkpoints {
+        sink.log.line(&format!(
+            "Checkpoint {} with {} splats",
+            checkpoint.path, checkpoint.splat_count
+        ));
+    }
+    let Watched {
+        run,
+        lost,
+        poor_registration,
+        changes,
+        vram,
+        stalls,
+        ..
+    } = watched;
+    for stall in &stalls {
+        let warning = stall.warning();
+        sink.log.line(&warning.message);
+        if !sink.warnings.contains(&warning) {
+            sink.warnings.push(warning);
+        }
+    }
+    if let Some(check) = &vram {
+        sink.log.line(&format!(
+            "VRAM at the start of training on {}: {} MiB free of {}, {} MiB needed",
+            check.sample.gpu,
+            check.sample.available_mb(),
+            check.sample.total_mb,
+            check.required_mb
+        ));
+        if check.contended {
+            let warning = CliWarning {
+                code: "gpu-contention".to_string(),
+                message: check.warning_text(),
             };
+            sink.log.line(&warning.message);
+            sink.warnings.push(warning);
+        }
+        match check.wait {
+            Some(VramWait::Freed) => sink.log.line("VRAM freed up; training continued"),
+            Some(VramWait::TimedOut) => sink.log.line("Gave up waiting for VRAM"),
+            Some(VramWait::Waiting) | None => {}
+        }
+    }
+    for change in &changes {
+        sink.log.line(&format!(
+            "Output volume {} {}{}",
+            change.mount,
+            if change.connected {
+                "reconnected"
+            } else {
+                "disconnected"
+            },
+            if change.paused {
+                "; CLI suspended until resumed"
+            } else {
+                ""
+            },
+        ));
+    }
+    if let Some(lost) = lost {
+        sink.log.line(&format!(
+            "Output volume did not come back; stopped at {} {:.0}%",
+            lost.stage.as_deref().unwrap_or("start"),
+            lost.progress
+        ));
+        return Err(Message::new("job.output_volume_lost")
+            .with("path", &args.output_dir)
+            .with("stage", lost.stage.as_deref().unwrap_or(""))
+            .with("progress", format!("{:.0}", lost.progress))
+            .into());
+    }
+    if let Some(poor) = poor_registration {
+        sink.log.line(&format!(
+            "Only {} of {} images registered; stopped before training",
+            poor.registered, poor.total
+        ));
+        return Err(low_registration(&poor, args).into());
+    }
+    let outcome = match run {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            if e == RunError::Cancelled {
+                sink.log.line("Cancelled");
+                if sink.export_started {
+                    if let Some(artifact) = keep_exported_artifact(&work, output_dir, sink.log) {
+                        return Err(Message::new("job.cancelled_partial")
+                            .with("path", artifact)
+                            .into());
+                    }
+                }
+            }
+            return Err(match e {
+                RunError::Cancelled => Message::new("job.cancelled").into(),
+                RunError::Spawn { error, os_code } => {
+                    match spawn_diagnosis::diagnose(cli_path, os_code) {
+                        Some(diagnosis) => {
+                            log.line(&format!(
+                                "Diagnosed the spawn failure as {:?}",
+                                diagnosis.cause
+                            ));
+                            SpawnDiagnosis {
+                                detail: Some(error),
+                                ..diagnosis
+                            }
+                            .failure()
+                        }
+                        None => Message::new("job.spawn_failed").into_failure().raw(error),
+                    }
+                }
+                RunError::Io(e) => e.into(),
+            });
+        }
+    };
+    let log = sink.log;
+    log.line(&format!("CLI exited with status: {}", outcome.status));
+    if !outcome.stderr.is_empty() {
+        log.line(&format!("CLI stderr:\n{}", outcome.stderr.trim_end()));
+    }
+
+    // A CLI whose DLLs are missing or that was built for another processor
+    // starts, and exits with a loader status, on Windows
+    if let Some(diagnosis) = diagnose_exit(cli_path, outcome.exit_code) {
+        log.line(&format!(
+            "Diagnosed the exit status as {:?}",
+            diagnosis.cause
+        ));
+        return Err(diagnosis.failure());
+    }
+
+    if outcome.success {
+        let located = locate_artifacts(&work, &outcome.artifacts, log);
+        // Checkpoints are left behind with the working directory when they are not kept
+        let skip: &[&str] = if checkpoints.is_some_and(|c| c.delete_on_success) {
+            log.line("Deleting the checkpoints");
+            &[checkpoints::DIR_NAME]
+        } else {
+            &[]
+        };
+        match work.publish(output_dir, skip) {
+            Ok(moved) => log.line(&format!("Moved {} into the production", moved.join(", "))),
+            Err(e) => {
+                log.line(&format!("Failed to move the results: {}", e));
+                return Err(Message::new("job.publish_failed")
+                    .with("path", &args.output_dir)
+                    .into_failure()
+                    .raw(e.to_string()));
+            }
+        }
+        match located {
+            Ok(mut located) => {
+                for artifact in &mut located {
+                    artifact.path = output_dir
+                        .join(&artifact.path)
+                        .to_string_lossy()
+                        .to_string();
+                }
+                Ok(located)
+            }
+            // Published all the same, so the user can choose among them
+            Err(tied) => {
+                let tied: Vec<String> = tied
+                    .iter()
+                    .map(|path| output_dir.join(path).to_string_lossy().to_string())
+                    .collect();
+                Err(error::ambiguous_artifact(&tied).into())
+            }
+        }
+    } else {
+        let stderr = outcome.stderr.trim_end();
+        // Running out of VRAM is explained by what else was using it
+        let mut message = match vram.filter(|_| gpu_contention::is_out_of_memory(stderr)) {
+            Some(check) => check.out_of_memory(),
+            None => Message::new("job.cli_exit_status").with("status", &outcome.status),
+        };
+        // Decides whether the job is retried
+        if let Some(code) = outcome.exit_code {
+            message = message.with(auto_retry::EXIT_CODE_PARAM, code);
+        }
+        let failure = message.into_failure();
+        // The stderr tail is the CLI's own words and only ever shown as-is
+        Err(if stderr.is_empty() {
+            failure
+        } else {
+            failure.raw(secrets::redact(stderr))
+        })
+    }
+}
+
+/// What happened while the CLI ran besides the run itself
+struct Watched {
+    run: Result<RunOutcome, RunError>,
+    /// The output volume went away for good, which ended the run
+    lost: Option<VolumeLost>,
+    /// Too few images registered, which ended the run before training
+    poor_registration: Option<Registration>,
+    changes: Vec<VolumeChange>,
+    checkpoints: Vec<Checkpoint>,
+    /// The VRAM check at the start of training, with the last sample taken
+    vram: Option<VramCheck>,
+    /// Each time progress stood still for cancel_impact::STALL_AFTER
+    stalls: Vec<Stall>,
+}
+
+/// What run_watched watches besides the output volume
+struct Watches<'a> {
+    job_id: &'a str,
+    /// How many checkpoints to keep and their directory, when they are made
+    checkpoints: Option<(usize, PathBuf)>,
+    /// The VRAM the preset needs in MiB, and how long to wait for that much to be free
+    vram: (u64, Option<Duration>),
+    /// Where the CLI exports the artifact, and the SH degree it trains with
+    export: (PathBuf, Option<u32>),
+    /// Share of the images that must register; None to train regardless
+    min_registered_percent: Option<u32>,
+}
+
+/// Run the CLI while watching its output volume, pausing it while the volume
+/// is away and stopping it as a cancellation would when it stays away. The
+/// job's checkpoints are watched as well, VRAM is checked once training
+/// starts, a silent export is reported from the artifact's growth, progress
+/// that stands still is reported, and the run is stopped when too few images
+/// register.
+async fn run_watched(
+    source: Source,
+    sink: &mut dyn EventSink,
+    cancel: &AtomicBool,
+    output_dir: &Path,
+    run_state: &RunState,
+    watches: Watches<'_>,
+) -> Watched {
+    let Watches {
+        job_id,
+        checkpoints: checkpoints_of,
+        vram: (required_mb, wait),
+        export: (export_dir, sh_degree),
+        min_registered_percent,
+    } = watches;
+    let mut changes = vec![];
+    let mut notify = |change: VolumeChange| {
+        let event = if change.connected {
+            JobEvent::VolumeReconnected(change.clone())
+        } else {
+            JobEvent::VolumeDisconnected(change.clone())
+        };
+        job_events::publish(event);
+        changes.push(change);
+    };
+    let mut checkpoints = vec![];
+    let mut announce = |checkpoint: Checkpoint| {
+        job_events::publish(JobEvent::CheckpointAvailable(checkpoint.clone()));
+        checkpoints.push(checkpoint);
+    };
+    let watch_checkpoints = async {
+        match checkpoints_of {
+            Some((keep, dir)) => {
+                let interval = checkpoints::POLL_INTERVAL;
+                checkpoints::watch(job_id, &dir, keep, interval, &mut announce).await
+            }
+            None => std::future::pending().await,
+        }
+    };
+    let mut vram = None;
+    let mut report = |check: VramCheck| {
+        if check.contended {
+            job_events::publish(JobEvent::GpuContention(check.clone()));
+        }
+        vram = Some(check);
+    };
+    let watch_vram =
+        gpu_contention::watch(job_id, run_state, required_mb, wait, cancel, &mut report);
+    let mut stalls = vec![];
+    let mut stalled = |stall: Stall| {
+        job_events::publish(JobEvent::ProcessingStalled(stall.clone()));
+        stalls.push(stall);
+    };
+    let watch_stall =
+        cancel_impact::watch_stall(job_id, run_state, cancel_impact::STALL_AFTER, &mut stalled);
+    let mut estimated = |progress: ProcessProgress| {
+        job_events::publish(JobEvent::Progress(progress));
+    };
+    let watch_export = export_progress::watch(
+        job_id,
+        &export_dir,
+        sh_degree,
+        run_state,
+        export_progress::POLL_INTERVAL,
+        &mut estimated,
+    );
+    let watch_registration = registration::watch(
+        job_id,
+        run_state,
+        min_registered_percent,
+        cancel,
+        registration::POLL_INTERVAL,
+    );
+    let run = runner::run(source, sink, cancel);
+    tokio::pin!(run);
+    let watch = volume_watch::watch(
+        output_dir,
+        run_state,
+        volume_watch::resume_flag(),
+        cancel,
+        Timing::default(),
+        &mut notify,
+    );
+    let (run, lost, poor_registration) = tokio::select! {
+        run = &mut run => (run, None, None),
+        () = watch_checkpoints => unreachable!("the checkpoint watch never ends"),
+        () = watch_vram => unreachable!("the VRAM watch never ends"),
+        () = watch_stall => unreachable!("the stall watch never ends"),
+        () = watch_export => unreachable!("the export watch never ends"),
+        lost = watch => (stop(run, cancel).await, Some(lost), None),
+        poor = watch_registration => (stop(run, cancel).await, None, Some(poor)),
+    };
+    Watched {
+        run,
+        lost,
+        poor_registration,
+        changes,
+        checkpoints,
+        vram,
+        stalls,
+    }
+}
+
+/// Stop the run as a cancellation would, though the job fails rather than
+/// being cancelled
+async fn stop<T>(run: impl std::future::Future<Output = T>, cancel: &AtomicBool) -> T {
+    cancel.store(true, Ordering::SeqCst);
+    let run = run.await;
+    cancel.store(false, Ordering::SeqCst);
+    run
+}
+
+/// The SH degree the job trains with, where its options or its built-in preset give one
+fn sh_degree(args: &ProcessArgs, caps: &CliCapabilities) -> Option<u32> {
+    args.training
+        .as_ref()
+        .and_then(|t| t.sh_degree)
+        .or_else(|| presets::builtin_parameters(&args.preset, caps.version.as_deref())?.sh_degree)
+}
 
-            app.emit("processing-progress", &progress).ok();
+/// The job's artifacts in the output, the primary first: those the CLI named
+/// with result lines, or else the PLY a search of the output finds with the SPZ
+/// files beside it, or else output.ply. Equally new PLYs the search found are
+/// the error, for the user to choose from.
+fn locate_artifacts(
+    work: &WorkDir,
+    named: &[Artifact],
+    log: &mut JobLog,
+) -> Result<Vec<Artifact>, Vec<PathBuf>> {
+    let output = work.output();
+    let mut located = vec![];
+    for artifact in named {
+        let reported = Path::new(&artifact.path);
+        let inside = match reported.strip_prefix(&output) {
+            Ok(inside) => inside,
+            Err(_) if reported.is_relative() => reported,
+            Err(_) => {
+                log.line(&format!(
+                    "Ignoring the result line naming {}, outside the output",
+                    reported.display()
+                ));
+                continue;
+            }
+        };
+        located.push(Artifact {
+            path: inside.to_string_lossy().to_string(),
+            ..artifact.clone()
+        });
+    }
+    if !located.is_empty() {
+        artifacts::primary_first(&mut located);
+        return Ok(located);
+    }
+
+    let search = artifact_search::search(&output);
+    for candidate in &search.candidates {
+        match &candidate.rejected {
+            Some(reason) => log.line(&format!(
+                "Artifact candidate {} rejected: {}",
+                candidate.path.display(),
+                reason
+            )),
+            None => log.line(&format!("Artifact candidate {}", candidate.path.display())),
         }
     }
+    let found = match &search.found {
+        artifact_search::Found::Artifact(artifact) => {
+            log.line(&format!("Found the artifact at {}", artifact.display()));
+            let mut found = vec![artifact.clone()];
+            for spz in search.beside(artifact) {
+                log.line(&format!("Found an SPZ beside it at {}", spz.display()));
+                found.push(spz);
+            }
+            found
+        }
+        artifact_search::Found::Ambiguous(tied) => {
+            log.line("More than one artifact is newest; leaving the choice to the user");
+            return Err(tied.clone());
+        }
+        artifact_search::Found::Nothing => {
+            log.line("No artifact was found in the output");
+            vec![PathBuf::from("output.ply")]
+        }
+    };
+    Ok(found
+        .into_iter()
+        .map(|path| Artifact::named(path.to_string_lossy().to_string(), None))
+        .collect())
+}
 
-    let status = child.wait().await.map_err(|e| e.to_string())?;
+/// The artifact a job cancelled during export had already written, moved into
+/// the production if it is complete. A truncated one is left to be deleted
+/// with the working directory, so it cannot be mistaken for a result.
+fn keep_exported_artifact(work: &WorkDir, output_dir: &Path, log: &mut JobLog) -> Option<String> {
+    let path = work.output().join("output.ply");
+    if !path.is_file() {
+        return None;
+    }
+    match conversion::complete_splat_count(&path) {
+        Ok(splats) => {
+            log.line(&format!(
+                "Keeping the exported artifact with {} splats",
+                splats
+            ));
+            match work.publish_one("output.ply", output_dir) {
+                Ok(kept) => Some(kept.to_string_lossy().to_string()),
+                Err(e) => {
+                    log.line(&format!("Failed to move the artifact: {}", e));
+                    None
+                }
+            }
+        }
+        Err(e) => {
+            log.line(&format!("Deleting the incomplete artifact: {}", e));
+            None
+        }
+    }
+}
 
-    if status.success() {
-        // Return path to output PLY file
-        let output_path = PathBuf::from(&args.output_dir)
-            .join("output.ply")
-            .to_string_lossy()
-            .to_string();
-        Ok(output_path)
+/// The artifact a cancelled job kept, when `failure` is that cancellation
+pub fn partial_artifact(failure: &Failure) -> Option<&str> {
+    (failure.message.key == "job.cancelled_partial")
+        .then(|| failure.message.params.get("path"))
+        .flatten()
+        .map(String::as_str)
+}
+
+/// Where a clip's frames are extracted to, and from where in the clip
+struct FrameTarget<'a> {
+    frames_dir: &'a Path,
+    start_secs: f64,
+    /// The resolution to scale the frames down to fit
+    scale: Option<Resolution>,
+}
+
+/// Extract a clip's frames with ffmpeg, tone-mapped if the clip asks for it
+async fn extract_clip_frames(
+    video: &str,
+    target: FrameTarget<'_>,
+    options: &ClipOptions,
+    events: &mut dyn EventSink,
+    log: &mut JobLog,
+) -> Result<(), Failure> {
+    let key = if options.tone_map {
+        "progress.tone_mapping"
     } else {
-        Err(format!("CLI exited with status: {}", status))
+        "progress.extracting_frames"
+    };
+    events.progress(&ProcessProgress {
+        stage: "extracting_frames".to_string(),
+        progress: 0.0,
+        message: Some(Message::new(key).with("video", video)),
+        raw: None,
+        current_item: None,
+        items: None,
+        reported_item: None,
+        synthetic: false,
+    });
+    let FrameTarget {
+        frames_dir,
+        start_secs,
+        scale,
+    } = target;
+    let extracted = if options.tone_map {
+        extraction::extract_tonemapped(video, frames_dir, start_secs, scale).await
+    } else {
+        extraction::extract_frames(video, frames_dir, start_secs, scale).await
+    };
+    if let Err(e) = extracted {
+        log.line(&e);
+        return Err(e.into());
+    }
+    log.line(&format!(
+        "Clip {}: {} with ffmpeg from {:.3}s into {}",
+        video,
+        if options.tone_map {
+            "tone-mapped"
+        } else {
+            "extracted"
+        },
+        start_secs,
+        frames_dir.display()
+    ));
+    if let Some(scale) = scale {
+        log.line(&format!("Clip {}: scaled down to fit {}", video, scale));
+    }
+    Ok(())
+}
+
+/// Delete the blurred and badly exposed frames of one clip, refusing the job when too few remain
+async fn filter_clip_frames(
+    video: &str,
+    frames_dir: &Path,
+    filter: &FrameFilter,
+    events: &mut dyn EventSink,
+    log: &mut JobLog,
+) -> Result<(), Failure> {
+    let report = match frame_filter::filter_dir(frames_dir, filter).await {
+        Ok(report) => report,
+        Err(e) => {
+            log.line(&e);
+            return Err(e.into());
+        }
+    };
+    let summary = Message::new("progress.frames_filtered")
+        .with("video", video)
+        .with("kept", report.kept)
+        .with("rejected", report.rejected())
+        .with("blurred", report.blurred)
+        .with("too_dark", report.too_dark)
+        .with("too_bright", report.too_bright);
+    log.line(&summary.to_string());
+    events.progress(&ProcessProgress {
+        stage: "extracting_frames".to_string(),
+        progress: 100.0,
+        message: Some(summary),
+        raw: None,
+        current_item: None,
+        items: None,
+        reported_item: None,
+        synthetic: false,
+    });
+
+    if report.kept < MIN_SURVIVING_FRAMES {
+        return Err(Message::new("job.too_few_frames")
+            .with("kept", report.kept)
+            .with("video", video)
+            .with("minimum", MIN_SURVIVING_FRAMES)
+            .into());
+    }
+    Ok(())
+}
+
+/// Forwards runner events to the job log and the caller's sink
+struct JobSink<'a> {
+    events: &'a mut dyn EventSink,
+    log: &'a mut JobLog,
+    /// The CLI has begun writing the artifact
+    export_started: bool,
+    warnings: &'a mut Vec<CliWarning>,
+    run_state: &'a RunState,
+    job_id: &'a str,
+    videos: &'a [String],
+}
+
+impl EventSink for JobSink<'_> {
+    fn line(&mut self, line: &str) {
+        self.log.line(line);
+        self.events.line(line);
+    }
+
+    fn progress(&mut self, progress: &ProcessProgress) {
+        if matches!(progress.stage.as_str(), "exporting" | "complete") {
+            self.export_started = true;
+        }
+        *self.run_state.progress.lock().unwrap() =
+            Some((progress.stage.clone(), progress.progress));
+        self.events.progress(progress);
+    }
+
+    fn started(&mut self, pid: Option<u32>) {
+        *self.run_state.pid.lock().unwrap() = pid;
+    }
+
+    fn metric(&mut self, sample: &MetricSample) {
+        if let Some(update) = training_metrics::record(self.job_id, sample) {
+            job_events::publish(JobEvent::TrainingMetrics(update));
+        }
+    }
+
+    fn registration(&mut self, summary: &RegistrationSummary) {
+        let registration = Registration::of(summary, self.videos);
+        self.log.line(&format!(
+            "{} of {} images registered ({:.1}%)",
+            registration.registered,
+            registration.total,
+            registration.percent()
+        ));
+        registration::record(self.job_id, registration);
+    }
+
+    fn warning(&mut self, warning: &CliWarning) {
+        // COLMAP estimated the intrinsics itself, which leaves nothing to act on
+        if warning.code == camera_intrinsics::MISMATCH_WARNING {
+            self.log.line(&format!(
+                "Intrinsics hint not kept by COLMAP: {}",
+                warning.message
+            ));
+            return;
+        }
+        self.log.line(&format!(
+            "CLI warning {}: {}",
+            warning.code, warning.message
+        ));
+        if !self.warnings.contains(warning) {
+            self.warnings.push(warning.clone());
+        }
+        self.events.warning(warning);
+    }
+}
+
+/// Keeps a queue entry's warnings and clip breakdown current while its job runs
+struct EntrySink<'a> {
+    entry_id: &'a str,
+    events: &'a mut dyn EventSink,
+}
+
+impl EventSink for EntrySink<'_> {
+    fn line(&mut self, line: &str) {
+        self.events.line(line);
+    }
+
+    fn progress(&mut self, progress: &ProcessProgress) {
+        self.events.progress(progress);
+    }
+
+    fn warning(&mut self, warning: &CliWarning) {
+        queue::update(self.entry_id, |e| {
+            if !e.warnings.contains(warning) {
+                e.warnings.push(warning.clone());
+            }
+        });
+        self.events.warning(warning);
+    }
+
+    fn clips(&mut self, clips: &[ClipProgress]) {
+        queue::update(self.entry_id, |e| e.clips = clips.to_vec());
+        self.events.clips(clips);
     }
 }
 
-/// Cancel ongoing processing
+/// Cancel ongoing processing, along with the clips of a batch that have not run yet
 #[tauri::command]
 pub async fn cancel_processing() -> Result<(), String> {
     CANCEL_FLAG.store(true, Ordering::SeqCst);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::ArtifactKind;
+    use crate::platform::testing::{RecordingEvents, ScriptedSpawner, TempPaths};
+    use crate::workdir;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn job_args(paths: &TempPaths) -> ProcessArgs {
+        let output_dir = paths.root().join("production");
+        std::fs::create_dir_all(&output_dir).unwrap();
+        ProcessArgs {
+            production_id: None,
+            videos: vec!["/clips/cam1.mp4".to_string()],
+            output_dir: output_dir.to_string_lossy().to_string(),
+            preset: "fast".to_string(),
+            colmap_path: None,
+            brush_path: Some("/opt/brush".to_string()),
+            clips: vec![],
+            allow_mixed_projection: false,
+            masks: None,
+            auto_mask: false,
+            mode: BatchMode::Combined,
+            stop_on_error: false,
+            filter_frames: FrameFilter::default(),
+            capture_type: CaptureType::Generic,
+            profile_overrides: vec![],
+            scratch_dir: None,
+            output_name_template: None,
+            batch_id: None,
+            simulate: false,
+            simulate_fail_at_stage: None,
+            simulate_duration_secs: None,
+            force_reextract: false,
+            normalize_resolution: false,
+            preview_mode: false,
+            reuse_reconstruction: ReuseReconstruction::Never,
+            training: None,
+            label: None,
+            tags: vec![],
+            checkpoints: None,
+            vram_wait_secs: None,
+            override_vram_policy: false,
+            min_vram_gb: None,
+            vram_override: None,
+            vram_warning: None,
+            retry: None,
+            post_run_hooks: vec![],
+            known_devices: vec![],
+            continue_on_poor_registration: false,
+            min_registered_percent: None,
+            allow_duplicate: false,
+            fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn cli_path_prefers_bundled_binary() {
+        let paths = TempPaths::new();
+        assert_eq!(cli_path(&paths).unwrap(), "gvcore-cli");
+
+        let name = if cfg!(target_os = "windows") {
+            "gvcore-cli.exe"
+        } else {
+            "gvcore-cli"
+        };
+        let bundled = paths.resource_dir().unwrap().join("resources").join(name);
+        std::fs::create_dir_all(bundled.parent().unwrap()).unwrap();
+        std::fs::write(&bundled, b"").unwrap();
+        assert_eq!(cli_path(&paths).unwrap(), bundled.to_string_lossy());
+    }
+
+    #[tokio::test]
+    async fn successful_job_reports_progress_and_records_history() {
+        let paths = TempPaths::new();
+        let args = job_args(&paths);
+        let spawner = ScriptedSpawner {
+            stdout: vec![
+                "[colmap] 30% - Running Structure from Motion...".to_string(),
+                "[completed] 100% - Done".to_string(),
+            ],
+            success: true,
+            // What the CLI would have exported
+            artifact: Some(b"abc".to_vec()),
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+
+        let artifact = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            args.clone(),
+            &mut None,
+        )
+        .await
+        .unwrap()
+        .artifact_path;
+
+        assert_eq!(
+            artifact,
+            Path::new(&args.output_dir)
+                .join("output.ply")
+                .to_string_lossy()
+        );
+        let job_id = history::load(&paths).unwrap()[0].job_id.clone();
+        let work_output = workdir::output_of(Path::new(&args.output_dir), &job_id);
+        assert!(!Path::new(&args.output_dir).join(workdir::DIR_NAME).exists());
+        let spawned = spawner.spawned.lock().unwrap();
+        assert_eq!(
+            spawned[0].1,
+            vec![
+                "run",
+                "--output",
+                &work_output.to_string_lossy(),
+                "--input",
+                "/clips/cam1.mp4",
+                "--brush-path",
+                "/opt/brush"
+            ]
+        );
+        let stages: Vec<&str> = events.progress.iter().map(|p| p.stage.as_str()).collect();
+        assert_eq!(stages, vec!["detecting_cameras", "complete"]);
+        assert_eq!(events.lines.len(), 2);
+
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].status, JobStatus::Completed);
+        assert_eq!(records[0].artifact_path.as_deref(), Some(artifact.as_str()));
+        let abc_sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(records[0].artifact_sha256.as_deref(), Some(abc_sha256));
+        let sidecar = sidecar::read(Path::new(&args.output_dir)).unwrap().unwrap();
+        assert_eq!(sidecar.artifact_sha256.as_deref(), Some(abc_sha256));
+
+        // Both keep exactly what was run
+        let command = records[0].command.clone().unwrap();
+        assert_eq!(
+            (&command.program, &command.args),
+            (&spawned[0].0, &spawned[0].1)
+        );
+        assert_eq!(sidecar.command, Some(command));
+    }
+
+    #[tokio::test]
+    async fn a_simulated_job_goes_through_the_same_plumbing_without_the_cli() {
+        let paths = TempPaths::new();
+        let args = ProcessArgs {
+            simulate: true,
+            simulate_duration_secs: Some(0.0),
+            ..job_args(&paths)
+        };
+        // Any run of the CLI itself would fail the job
+        let spawner = ScriptedSpawner::default();
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+
+        let artifact = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            args.clone(),
+            &mut None,
+        )
+        .await
+        .unwrap()
+        .artifact_path;
+
+        assert!(spawner.spawned.lock().unwrap().is_empty());
+        assert_eq!(std::fs::read(&artifact).unwrap(), b"simulated ply data");
+        let mut stages: Vec<&str> = events.progress.iter().map(|p| p.stage.as_str()).collect();
+        stages.dedup();
+        for stage in ["extracting_frames", "detecting_cameras", "training_splats"] {
+            assert!(stages.contains(&stage), "{:?}", stages);
+        }
+        assert_eq!(stages.last(), Some(&"complete"));
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records[0].status, JobStatus::Completed);
+        assert_eq!(records[0].artifact_path.as_deref(), Some(artifact.as_str()));
+
+        let failing = ProcessArgs {
+            simulate_fail_at_stage: Some("brush".to_string()),
+            ..args
+        };
+        let mut events = RecordingEvents::default();
+        let failed = process(&paths, &spawner, &mut events, &cancel, failing, &mut None).await;
+        assert!(failed.is_err());
+        assert!(spawner.spawned.lock().unwrap().is_empty());
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().any(|r| r.status == JobStatus::Failed));
+    }
+
+    #[tokio::test]
+    async fn concurrent_jobs_under_one_root_keep_to_their_own_directories() {
+        let paths = TempPaths::new();
+        let spawner = ScriptedSpawner {
+            stdout: vec![
+                "[training] 50% - Training".to_string(),
+                "[completed] 100% - Done".to_string(),
+            ],
+            success: true,
+            artifact: Some(b"abc".to_vec()),
+            ..Default::default()
+        };
+        let job = |name: &str, video: &str| {
+            let output_dir = paths.root().join("root").join(name);
+            std::fs::create_dir_all(&output_dir).unwrap();
+            ProcessArgs {
+                videos: vec![video.to_string()],
+                output_dir: output_dir.to_string_lossy().to_string(),
+                ..job_args(&paths)
+            }
+        };
+        let (first, second) = (job("first", "/clips/a.mp4"), job("second", "/clips/b.mp4"));
+        let mut first_events = RecordingEvents::default();
+        let mut second_events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+
+        let (a, b) = tokio::join!(
+            process(
+                &paths,
+                &spawner,
+                &mut first_events,
+                &cancel,
+                first.clone(),
+                &mut None
+            ),
+            process(
+                &paths,
+                &spawner,
+                &mut second_events,
+                &cancel,
+                second.clone(),
+                &mut None
+            ),
+        );
+
+        for (artifact, args) in [(a.unwrap(), &first), (b.unwrap(), &second)] {
+            let output_dir = Path::new(&args.output_dir);
+            assert_eq!(artifact, output_dir.join("output.ply").to_string_lossy());
+            assert_eq!(
+                std::fs::read(output_dir.join("output.ply")).unwrap(),
+                b"abc"
+            );
+            assert!(!output_dir.join(workdir::DIR_NAME).exists());
+        }
+        let temp_dirs = spawner.temp_dirs.lock().unwrap();
+        assert_eq!(temp_dirs.len(), 2);
+        assert!(temp_dirs.iter().all(|t| t.is_some()));
+        assert_ne!(temp_dirs[0], temp_dirs[1]);
+        let spawned = spawner.spawned.lock().unwrap();
+        assert_ne!(spawned[0].1[2], spawned[1].1[2]);
+    }
+
+    #[tokio::test]
+    async fn cli_warnings_reach_the_events_and_the_history() {
+        let paths = TempPaths::new();
+        let args = job_args(&paths);
+        let warning = r#"{"type":"warning","code":"deprecated-flag","message":"--preset fast is deprecated, use --preset draft"}"#;
+        let spawner = ScriptedSpawner {
+            stdout: vec![
+                warning.to_string(),
+                warning.to_string(),
+                "[completed] 100% - Done".to_string(),
+            ],
+            success: true,
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+        std::fs::write(Path::new(&args.output_dir).join("output.ply"), b"abc").unwrap();
+
+        process(&paths, &spawner, &mut events, &cancel, args, &mut None)
+            .await
+            .unwrap();
+
+        assert_eq!(events.warnings.len(), 2);
+        assert_eq!(events.progress.len(), 1);
+        let records = history::load(&paths).unwrap();
+        let codes: Vec<&str> = records[0]
+            .warnings
+            .iter()
+            .map(|w| w.code.as_str())
+            .collect();
+        assert_eq!(codes, ["deprecated-flag"]);
+    }
+
+    #[tokio::test]
+    async fn training_options_the_cli_lacks_are_warned_about_and_left_out() {
+        let paths = TempPaths::new();
+        let args = ProcessArgs {
+            training: Some(TrainingOptions {
+                iterations: Some(30_000),
+                ..Default::default()
+            }),
+            ..job_args(&paths)
+        };
+        let spawner = ScriptedSpawner {
+            stdout: vec!["[completed] 100% - Done".to_string()],
+            success: true,
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+        std::fs::write(Path::new(&args.output_dir).join("output.ply"), b"abc").unwrap();
+
+        process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            args.clone(),
+            &mut None,
+        )
+        .await
+        .unwrap();
+
+        let (_, cli_args) = spawner.spawned.lock().unwrap()[0].clone();
+        assert!(!cli_args.iter().any(|a| a == "--iterations"));
+        assert_eq!(events.warnings[0].code, training::WARNING_UNSUPPORTED);
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records[0].warnings, events.warnings);
+        let sidecar = sidecar::read(Path::new(&args.output_dir)).unwrap().unwrap();
+        assert_eq!(sidecar.training, None);
+    }
+
+    #[tokio::test]
+    async fn progress_is_attributed_to_clips() {
+        let paths = TempPaths::new();
+        let args = ProcessArgs {
+            videos: vec!["/clips/cam1.mp4".to_string(), "/clips/cam2.mp4".to_string()],
+            ..job_args(&paths)
+        };
+        let spawner = ScriptedSpawner {
+            stdout: vec![
+                r#"{"type":"progress","stage":"frame_extraction","progress":10,"item":"cam2.mp4"}"#
+                    .to_string(),
+                "[colmap] 75% - Matching".to_string(),
+                "[completed] 100% - Done".to_string(),
+            ],
+            success: true,
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+        std::fs::write(Path::new(&args.output_dir).join("output.ply"), b"abc").unwrap();
+
+        process(&paths, &spawner, &mut events, &cancel, args, &mut None)
+            .await
+            .unwrap();
+
+        let items: Vec<Option<&str>> = events
+            .progress
+            .iter()
+            .map(|p| p.current_item.as_deref())
+            .collect();
+        assert_eq!(
+            items,
+            [Some("/clips/cam2.mp4"), Some("/clips/cam2.mp4"), None]
+        );
+        assert_eq!(events.progress[1].items.map(|i| i.total), Some(2));
+
+        let records = history::load(&paths).unwrap();
+        let clips = &records[0].clips;
+        assert_eq!(clips, &events.clips);
+        assert!(!clips[0].estimated);
+        assert_eq!(
+            clips[1].finished,
+            ["extracting_frames", "detecting_cameras"]
+        );
+        assert!(clips[1].estimated);
+    }
+
+    #[tokio::test]
+    async fn output_name_template_renames_artifact_everywhere() {
+        let paths = TempPaths::new();
+        let args = ProcessArgs {
+            output_name_template: Some("{production}_{preset}_v{version}".to_string()),
+            ..job_args(&paths)
+        };
+        let output_dir = Path::new(&args.output_dir);
+        std::fs::write(output_dir.join("output.ply"), b"abc").unwrap();
+        std::fs::write(output_dir.join("production_fast_v1.ply"), b"earlier run").unwrap();
+        let spawner = ScriptedSpawner {
+            success: true,
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+
+        let artifact = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            args.clone(),
+            &mut None,
+        )
+        .await
+        .unwrap()
+        .artifact_path;
+
+        let expected = output_dir.join("production_fast_v2.ply");
+        assert_eq!(artifact, expected.to_string_lossy());
+        assert!(expected.is_file());
+        assert!(!output_dir.join("output.ply").exists());
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records[0].artifact_path.as_deref(), Some(artifact.as_str()));
+        let sidecar = sidecar::read(output_dir).unwrap().unwrap();
+        assert_eq!(sidecar.artifact_path, artifact);
+        assert_eq!(sidecar.artifacts[0].path, artifact);
+    }
+
+    #[tokio::test]
+    async fn every_artifact_named_is_kept_with_the_ply_first() {
+        let paths = TempPaths::new();
+        let args = job_args(&paths);
+        let spawner = ScriptedSpawner {
+            stdout: vec![
+                r#"{"type":"result","path":"web.spz"}"#.to_string(),
+                r#"{"type":"result","path":"output.ply","kind":"ply"}"#.to_string(),
+            ],
+            success: true,
+            artifact: Some(b"abc".to_vec()),
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+
+        let output = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            args.clone(),
+            &mut None,
+        )
+        .await
+        .unwrap();
+
+        let output_dir = Path::new(&args.output_dir);
+        let ply = output_dir.join("output.ply").to_string_lossy().to_string();
+        assert_eq!(output.artifact_path, ply);
+        let listed: Vec<(&str, ArtifactKind)> = output
+            .artifacts
+            .iter()
+            .map(|a| (a.path.as_str(), a.kind))
+            .collect();
+        let spz = output_dir.join("web.spz").to_string_lossy().to_string();
+        assert_eq!(
+            listed,
+            [
+                (ply.as_str(), ArtifactKind::Ply),
+                (spz.as_str(), ArtifactKind::Spz)
+            ]
+        );
+        assert_eq!(output.artifacts[0].bytes, 3);
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records[0].artifacts, output.artifacts);
+        let sidecar = sidecar::read(output_dir).unwrap().unwrap();
+        assert_eq!(sidecar.artifacts, output.artifacts);
+    }
+
+    #[tokio::test]
+    async fn failed_job_records_error() {
+        let paths = TempPaths::new();
+        let spawner = ScriptedSpawner::default();
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+
+        let err = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            job_args(&paths),
+            &mut None,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.message.key, "job.cli_exit_status");
+        assert_eq!(err.message.params["status"], "simulated failure");
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records[0].status, JobStatus::Failed);
+        assert_eq!(
+            records[0].error.as_deref(),
+            Some("CLI exited with status: simulated failure")
+        );
+    }
+
+    #[tokio::test]
+    async fn retryable_exit_codes_rerun_the_job_under_one_record() {
+        let paths = TempPaths::new();
+        let spawner = ScriptedSpawner::default();
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+        let mut bus = job_events::subscribe();
+        let retried = |codes: Vec<i32>| ProcessArgs {
+            retry: Some(RetrySettings {
+                max_retries: 2,
+                backoff_secs: 0,
+                retryable_codes: codes,
+            }),
+            ..job_args(&paths)
+        };
+
+        // The simulated CLI exits with 1
+        let err = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            retried(vec![1]),
+            &mut None,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.message.key, "job.cli_exit_status");
+        assert_eq!(spawner.spawned.lock().unwrap().len(), 3);
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records.len(), 1);
+        let attempts: Vec<(u32, Option<i32>)> = records[0]
+            .attempts
+            .iter()
+            .map(|a| (a.attempt, a.exit_code))
+            .collect();
+        assert_eq!(attempts, [(1, Some(1)), (2, Some(1)), (3, Some(1))]);
+        let mut announced = vec![];
+        while let Some(routed) = bus.try_recv() {
+            if let JobEvent::AutoRetrying(retrying) = routed.event {
+                if retrying.job_id == records[0].job_id {
+                    announced.push((retrying.attempt, retrying.max_attempts));
+                }
+            }
+        }
+        assert_eq!(announced, [(2, 3), (3, 3)]);
+
+        // Other failures are not retried
+        process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            retried(vec![75]),
+            &mut None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(spawner.spawned.lock().unwrap().len(), 4);
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records.iter().filter(|r| r.attempts.is_empty()).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_silent_job_records_cancellation() {
+        let paths = TempPaths::new();
+        let spawner = ScriptedSpawner {
+            hang: true,
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let flag = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+        let err = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &cancel,
+            job_args(&paths),
+            &mut None,
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "Processing cancelled");
+        assert_eq!(
+            history::load(&paths).unwrap()[0].status,
+            JobStatus::Cancelled
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelling_during_export_keeps_a_complete_artifact() {
+        let paths = TempPaths::new();
+        let args = job_args(&paths);
+        let artifact = Path::new(&args.output_dir).join("output.ply");
+        let mut ply = crate::conversion::tests::sample_ply(&[[0.0; 14]]);
+
+        for complete in [true, false] {
+            if !complete {
+                ply.truncate(ply.len() - 1);
+            }
+            let spawner = ScriptedSpawner {
+                stdout: vec!["[metadata] 90% - Exporting".to_string()],
+                hang: true,
+                artifact: Some(ply.clone()),
+                ..Default::default()
+            };
+            let mut events = RecordingEvents::default();
+            let cancel = Arc::new(AtomicBool::new(false));
+            let flag = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                flag.store(true, Ordering::SeqCst);
+            });
+            let err = process(
+                &paths,
+                &spawner,
+                &mut events,
+                &cancel,
+                args.clone(),
+                &mut None,
+            )
+            .await
+            .unwrap_err();
+
+            let record = history::load(&paths).unwrap().pop().unwrap();
+            assert_eq!(record.status, JobStatus::Cancelled);
+            if complete {
+                let kept = artifact.to_string_lossy();
+                assert_eq!(partial_artifact(&err), Some(kept.as_ref()));
+                assert!(record.partial);
+                assert_eq!(record.artifact_path.as_deref(), Some(kept.as_ref()));
+                std::fs::remove_file(&artifact).unwrap();
+            } else {
+                assert_eq!(err.message.key, "job.cancelled");
+                assert!(!record.partial);
+                assert!(!artifact.exists());
+            }
+            let work = workdir::dir(Path::new(&args.output_dir), &record.job_id);
+            assert!(!work.exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn mixed_projection_is_rejected_before_spawning() {
+        let paths = TempPaths::new();
+        let mut args = job_args(&paths);
+        args.videos.push("/clips/360.mp4".to_string());
+        args.clips.push(ClipOptions {
+            path: "/clips/360.mp4".to_string(),
+            projection: Projection::Equirect360,
+            ..Default::default()
+        });
+        let spawner = ScriptedSpawner::default();
+        let mut events = RecordingEvents::default();
+
+        let result = process(
+            &paths,
+            &spawner,
+            &mut events,
+            &AtomicBool::new(false),
+            args,
+            &mut None,
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(spawner.spawned.lock().unwrap().is_empty());
+        assert!(history::load(&paths).unwrap().is_empty());
+    }
+
+    fn batch_args(paths: &TempPaths) -> ProcessArgs {
+        ProcessArgs {
+            videos: vec![
+                "/clips/scene 1.mp4".to_string(),
+                "/clips/scene2.mp4".to_string(),
+                "/backup/Scene2.MP4".to_string(),
+            ],
+            mode: BatchMode::PerClip,
+            ..job_args(paths)
+        }
+    }
+
+    fn succeeding() -> ScriptedSpawner {
+        ScriptedSpawner {
+            stdout: vec!["[completed] 100% - Done".to_string()],
+            success: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn clip_directories_are_named_after_clips() {
+        let mut used = HashSet::new();
+        let names: Vec<String> = [
+            "/a/Harbour Dawn.mp4",
+            "/b/harbour dawn.MOV",
+            "/c/../.hidden",
+            "/d/take:1?.mp4",
+            "/e/Harbour Dawn.mkv",
+        ]
+        .iter()
+        .map(|video| clip_dir_name(video, &mut used))
+        .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Harbour Dawn",
+                "harbour dawn-2",
+                "hidden",
+                "take_1_",
+                "Harbour Dawn-3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn per_clip_batch_runs_each_clip_into_its_own_directory() {
+        let paths = TempPaths::new();
+        let args = batch_args(&paths);
+        let spawner = succeeding();
+        let mut events = RecordingEvents::default();
+
+        let summary = process_batch(
+            &paths,
+            &spawner,
+            &mut events,
+            &AtomicBool::new(false),
+            args.clone(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            (summary.completed, summary.failed, summary.cancelled),
+            (3, 0, 0)
+        );
+        let output = Path::new(&args.output_dir);
+        let dirs: Vec<String> = summary
+            .members
+            .iter()
+            .map(|m| m.output_dir.clone())
+            .collect();
+        assert_eq!(
+            dirs,
+            ["scene 1", "scene2", "Scene2-2"]
+                .map(|name| output.join(name).to_string_lossy().to_string())
+        );
+
+        let spawned = spawner.spawned.lock().unwrap();
+        assert_eq!(spawned.len(), 3);
+        let work_dir = Path::new(&dirs[1]).join(workdir::DIR_NAME);
+        assert!(Path::new(&spawned[1].1[2]).starts_with(work_dir));
+        assert_eq!(spawned[1].1[4], "/clips/scene2.mp4");
+        assert!(!spawned[1].1.contains(&"/backup/Scene2.MP4".to_string()));
+
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(records
+            .iter()
+            .all(|r| r.batch_id.as_deref() == Some(summary.batch_id.as_str())));
+        assert!(sidecar::read(&output.join("scene2")).unwrap().is_some());
+        assert!(queue::snapshot()
+            .iter()
+            .all(|e| e.batch_id.as_deref() != Some(summary.batch_id.as_str())));
+    }
+
+    #[tokio::test]
+    async fn batch_continues_past_failed_clips_unless_stop_on_error() {
+        let paths = TempPaths::new();
+        let spawner = ScriptedSpawner {
+            fail_on: Some("/clips/scene 1.mp4".to_string()),
+            ..succeeding()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = AtomicBool::new(false);
+
+        let summary = process_batch(&paths, &spawner, &mut events, &cancel, batch_args(&paths))
+            .await
+            .unwrap();
+        assert_eq!(
+            (summary.completed, summary.failed, summary.cancelled),
+            (2, 1, 0)
+        );
+        assert_eq!(summary.members[0].status, QueueStatus::Failed);
+        assert!(summary.members[0].error.is_some());
+
+        let args = ProcessArgs {
+            stop_on_error: true,
+            ..batch_args(&paths)
+        };
+        let summary = process_batch(&paths, &spawner, &mut events, &cancel, args)
+            .await
+            .unwrap();
+        assert_eq!(
+            (summary.completed, summary.failed, summary.cancelled),
+            (0, 1, 2)
+        );
+        assert_eq!(spawner.spawned.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_batch_cancels_remaining_clips() {
+        let paths = TempPaths::new();
+        let spawner = ScriptedSpawner {
+            hang: true,
+            ..Default::default()
+        };
+        let mut events = RecordingEvents::default();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let flag = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+        let summary = process_batch(&paths, &spawner, &mut events, &cancel, batch_args(&paths))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            (summary.completed, summary.failed, summary.cancelled),
+            (0, 0, 3)
+        );
+        assert_eq!(spawner.spawned.lock().unwrap().len(), 1);
+        assert_eq!(history::load(&paths).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,421 @@
+//! Artifact Comparison
+//!
+//! Two runs' numbers say which did better but not where. render_comparison
+//! renders the artifacts of two jobs from the same camera, with the CPU
+//! renderer and bounds of splat previews, and writes a heatmap of how much
+//! each pixel differs between them. The camera is a saved bookmark of either
+//! job's production, or the view framed on the older of the two artifacts,
+//! applied to both so they line up.
+//!
+//! The three images go to the thumbnail cache, named by both artifacts'
+//! checksums and the camera, so comparing the same pair again is instant and
+//! a remade artifact gets images of its own.
+
+use crate::cache::{self, Cache, Category};
+use crate::camera_bookmarks::{self, CameraBookmark};
+use crate::error::AppError;
+use crate::history::{self, JobRecord};
+use crate::operations::{self, OperationHandle, OperationKind};
+use crate::scheduler::{self, Pool};
+use crate::splat_preview::{self, PreviewCamera};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use tauri::AppHandle;
+
+/// Side of each square image, in pixels
+const SIZE: u32 = 512;
+
+/// Difference of a pixel, from 0 to 1, above which it counts as changed
+const CHANGED: f32 = 0.1;
+
+/// The two renders of a comparison and the difference between them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonRender {
+    pub job_id_a: String,
+    pub job_id_b: String,
+    /// PNGs in the thumbnail cache
+    pub image_a: String,
+    pub image_b: String,
+    /// Black where the renders agree, through red and yellow to white where
+    /// they differ most; transparent where neither drew anything
+    pub heatmap: String,
+    pub size: u32,
+    pub camera: PreviewCamera,
+    /// The bookmark the camera came from; None when it was framed on the
+    /// older artifact
+    pub bookmark: Option<String>,
+    pub difference: Difference,
+    /// Taken from the cache rather than rendered now
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Difference {
+    /// Average over the pixels either render drew, from 0 to 1
+    pub mean: f32,
+    /// Share of those pixels that differ by more than CHANGED
+    pub changed_share: f32,
+}
+
+/// One artifact of a comparison
+struct Side {
+    job_id: String,
+    artifact: PathBuf,
+    /// Checksum, or what splat_preview::version tells versions apart by
+    version: String,
+}
+
+/// Render the artifacts of `job_id_a` and `job_id_b` side by side, from the
+/// camera saved as `bookmark` in either production or framed on the older one
+#[tauri::command]
+pub async fn render_comparison(
+    app: AppHandle,
+    job_id_a: String,
+    job_id_b: String,
+    bookmark: Option<String>,
+) -> Result<ComparisonRender, AppError> {
+    let records = history::load(&app)?;
+    let record = |job_id: &str| {
+        records
+            .iter()
+            .find(|r| r.job_id == job_id)
+            .ok_or_else(|| AppError::NotFound(job_id.to_string()))
+    };
+    let (a, b) = (record(&job_id_a)?, record(&job_id_b)?);
+    let a_is_older = a.started_at <= b.started_at;
+    let (older, newer) = if a_is_older { (a, b) } else { (b, a) };
+    let camera = match bookmark {
+        Some(name) => {
+            let found = [older, newer]
+                .into_iter()
+                .find_map(|r| camera_bookmarks::find(Path::new(&r.output_dir), &name).ok()?);
+            let bookmark = found.ok_or_else(|| {
+                AppError::NotFound(format!("The camera bookmark {}", name.trim()))
+            })?;
+            Some((bookmark.name.clone(), bookmark_camera(&bookmark)?))
+        }
+        None => None,
+    };
+    let (a, b) = (side(a)?, side(b)?);
+
+    let cache = cache::app_cache(&app)?;
+    let subject = format!("{} / {}", a.job_id, b.job_id);
+    let operation = OperationHandle::start(&app, OperationKind::Comparison, &subject);
+    scheduler::run_blocking(Pool::Background, move || {
+        compare(&cache, &a, &b, a_is_older, camera, operation.token())
+    })
+    .await?
+}
+
+/// Cancel the comparisons being rendered
+#[tauri::command]
+pub async fn cancel_comparison() -> Result<(), AppError> {
+    operations::cancel_kind(OperationKind::Comparison);
+    Ok(())
+}
+
+fn side(record: &JobRecord) -> Result<Side, AppError> {
+    let artifact = record
+        .artifact_path
+        .as_deref()
+        .map(PathBuf::from)
+        .filter(|path| path.is_file())
+        .ok_or_else(|| AppError::NotFound(format!("The artifact of {}", record.job_id)))?;
+    let version = match &record.artifact_sha256 {
+        Some(sha256) => sha256.to_lowercase(),
+        None => splat_preview::version(&artifact)?,
+    };
+    Ok(Side {
+        job_id: record.job_id.clone(),
+        artifact,
+        version,
+    })
+}
+
+/// The preview camera of a bookmark saved by the viewer
+fn bookmark_camera(bookmark: &CameraBookmark) -> Result<PreviewCamera, AppError> {
+    #[derive(Deserialize)]
+    struct Version1 {
+        position: [f32; 3],
+        target: [f32; 3],
+        up: Option<[f32; 3]>,
+        fov: Option<f32>,
+    }
+    let state = &bookmark.camera_state;
+    if state.version != 1 {
+        return Err(AppError::InvalidInput(format!(
+            "The camera bookmark {} was saved by a newer viewer",
+            bookmark.name
+        )));
+    }
+    let camera: Version1 = serde_json::from_value(state.state.clone().into()).map_err(|e| {
+        AppError::InvalidInput(format!(
+            "The camera bookmark {} is not valid: {}",
+            bookmark.name, e
+        ))
+    })?;
+    Ok(PreviewCamera {
+        position: camera.position,
+        target: camera.target,
+        up: camera.up,
+        fov_degrees: camera.fov,
+    })
+}
+
+/// Render or take from the cache the comparison of `a` and `b`; without a
+/// bookmark's camera, both are seen from the view framed on the older one
+fn compare(
+    cache: &Cache,
+    a: &Side,
+    b: &Side,
+    a_is_older: bool,
+    camera: Option<(String, PreviewCamera)>,
+    cancel: &AtomicBool,
+) -> Result<ComparisonRender, AppError> {
+    let (bookmark, camera) = camera.unzip();
+    let key = format!(
+        "{}\0{}\0{}\0{}",
+        a.version,
+        b.version,
+        serde_json::to_string(&camera).unwrap_or_default(),
+        SIZE
+    );
+    let name: String = Sha256::digest(key.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let names = [
+        format!("compare-{}-a.png", name),
+        format!("compare-{}-b.png", name),
+        format!("compare-{}-diff.png", name),
+    ];
+    let rendered_name = format!("compare-{}.json", name);
+
+    let cached = names
+        .iter()
+        .map(|image| cache.get(Category::Thumbnails, image))
+        .collect::<Option<Vec<PathBuf>>>()
+        .zip(cache.read(Category::Metadata, &rendered_name))
+        .and_then(|(images, rendered)| {
+            Some((images, serde_json::from_slice::<Rendered>(&rendered).ok()?))
+        });
+    if let Some((images, rendered)) = cached {
+        return Ok(result(a, b, &images, rendered, bookmark, true));
+    }
+
+    let (older, newer) = if a_is_older { (a, b) } else { (b, a) };
+    let (older_pixels, camera) =
+        splat_preview::render_pixels(&older.artifact, SIZE, camera, cancel)?;
+    let (newer_pixels, _) =
+        splat_preview::render_pixels(&newer.artifact, SIZE, Some(camera), cancel)?;
+    let (pixels_a, pixels_b) = if a_is_older {
+        (older_pixels, newer_pixels)
+    } else {
+        (newer_pixels, older_pixels)
+    };
+    let (heat, difference) = heatmap(&pixels_a, &pixels_b);
+
+    let mut images = vec![];
+    for (image_name, pixels) in names.iter().zip([&pixels_a, &pixels_b, &heat]) {
+        let staging = cache.staging(Category::Thumbnails, image_name)?;
+        let image = splat_preview::write_png(&staging, SIZE, pixels)
+            .map_err(AppError::from)
+            .and_then(|()| Ok(cache.insert(Category::Thumbnails, image_name, &staging)?));
+        match image {
+            Ok(image) => images.push(image),
+            Err(e) => {
+                std::fs::remove_file(&staging).ok();
+                return Err(e);
+            }
+        }
+    }
+    let rendered = Rendered { camera, difference };
+    if let Ok(json) = serde_json::to_vec(&rendered) {
+        cache.write(Category::Metadata, &rendered_name, &json).ok();
+    }
+    Ok(result(a, b, &images, rendered, bookmark, false))
+}
+
+/// What a cached comparison was rendered with, kept next to its images
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Rendered {
+    camera: PreviewCamera,
+    difference: Difference,
+}
+
+fn result(
+    a: &Side,
+    b: &Side,
+    images: &[PathBuf],
+    rendered: Rendered,
+    bookmark: Option<String>,
+    cached: bool,
+) -> ComparisonRender {
+    let path = |i: usize| images[i].to_string_lossy().to_string();
+    ComparisonRender {
+        job_id_a: a.job_id.clone(),
+        job_id_b: b.job_id.clone(),
+        image_a: path(0),
+        image_b: path(1),
+        heatmap: path(2),
+        size: SIZE,
+        camera: rendered.camera,
+        bookmark,
+        difference: rendered.difference,
+        cached,
+    }
+}
+
+/// RGBA pixels of how much each pixel of `a` and `b` differs, both seen over
+/// black, and the difference overall
+fn heatmap(a: &[u8], b: &[u8]) -> (Vec<u8>, Difference) {
+    let over_black = |pixel: &[u8]| {
+        let alpha = pixel[3] as f32 / 255.0;
+        [0, 1, 2].map(|c| pixel[c] as f32 / 255.0 * alpha)
+    };
+    let mut heat = Vec::with_capacity(a.len());
+    let (mut drawn, mut total, mut changed) = (0usize, 0.0f32, 0usize);
+    for (pa, pb) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+        if pa[3] == 0 && pb[3] == 0 {
+            heat.extend_from_slice(&[0, 0, 0, 0]);
+            continue;
+        }
+        let (ca, cb) = (over_black(pa), over_black(pb));
+        let t = (0..3).map(|c| (ca[c] - cb[c]).abs()).fold(0.0f32, f32::max);
+        drawn += 1;
+        total += t;
+        if t > CHANGED {
+            changed += 1;
+        }
+        let ramp = |from: f32| ((t * 3.0 - from).clamp(0.0, 1.0) * 255.0).round() as u8;
+        heat.extend_from_slice(&[ramp(0.0), ramp(1.0), ramp(2.0), 255]);
+    }
+    let share = |n: f32| if drawn == 0 { 0.0 } else { n / drawn as f32 };
+    let difference = Difference {
+        mean: share(total),
+        changed_share: share(changed as f32),
+    };
+    (heat, difference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera_bookmarks::CameraState;
+    use crate::conversion::tests::sample_ply;
+    use crate::conversion::SH_C0;
+    use crate::platform::testing::TempPaths;
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+
+    /// A square of splats of one color, `dc` per channel, offset along x
+    fn square(paths: &TempPaths, name: &str, x: f32, dc: [f32; 3]) -> Side {
+        let vertices: Vec<[f32; 14]> = (0..400)
+            .map(|i| {
+                let (px, py) = (x + (i % 20) as f32 * 0.05, (i / 20) as f32 * 0.05);
+                [
+                    px, py, 0.0, -3.0, -3.0, -3.0, 1.0, 0.0, 0.0, 0.0, 4.0, dc[0], dc[1], dc[2],
+                ]
+            })
+            .collect();
+        let artifact = paths.root().join(format!("{}.ply", name));
+        std::fs::write(&artifact, sample_ply(&vertices)).unwrap();
+        Side {
+            job_id: name.to_string(),
+            version: splat_preview::version(&artifact).unwrap(),
+            artifact,
+        }
+    }
+
+    #[test]
+    fn both_runs_are_rendered_from_the_older_ones_view_and_cached() {
+        let paths = TempPaths::new();
+        let cache = cache::open(&paths.root().join("cache"), 64 * 1024 * 1024);
+        let red = [0.5 / SH_C0, -0.5 / SH_C0, -0.5 / SH_C0];
+        let green = [-0.5 / SH_C0, 0.5 / SH_C0, -0.5 / SH_C0];
+        let before = square(&paths, "job-1", 0.0, red);
+        let same = square(&paths, "job-2", 0.0, red);
+        let recolored = square(&paths, "job-3", 0.0, green);
+        let cancel = AtomicBool::new(false);
+
+        let unchanged = compare(&cache, &before, &same, true, None, &cancel).unwrap();
+        assert!(!unchanged.cached);
+        assert_eq!(unchanged.difference.changed_share, 0.0);
+        assert_eq!(unchanged.bookmark, None);
+
+        let changed = compare(&cache, &recolored, &before, false, None, &cancel).unwrap();
+        assert!(
+            changed.difference.changed_share > 0.5,
+            "{:?}",
+            changed.difference
+        );
+        assert_eq!(changed.camera, unchanged.camera);
+        let decoder = png::Decoder::new(std::fs::File::open(&changed.heatmap).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!((info.width, info.height), (SIZE, SIZE));
+
+        let again = compare(&cache, &recolored, &before, false, None, &cancel).unwrap();
+        assert!(again.cached);
+        assert_eq!(again.heatmap, changed.heatmap);
+        assert_eq!(again.difference, changed.difference);
+
+        // Another camera is another comparison
+        let bookmark = CameraBookmark {
+            name: "front".to_string(),
+            camera_state: serde_json::from_value::<CameraState>(json!({
+                "version": 1,
+                "position": [0.5, 0.5, -3.0],
+                "target": [0.5, 0.5, 0.0],
+                "up": [0.0, -1.0, 0.0],
+                "fov": 40.0,
+            }))
+            .unwrap(),
+            saved_at: 0,
+        };
+        let camera = bookmark_camera(&bookmark).unwrap();
+        assert_eq!(camera.fov_degrees, Some(40.0));
+        let from_bookmark = compare(
+            &cache,
+            &recolored,
+            &before,
+            false,
+            Some(("front".to_string(), camera)),
+            &cancel,
+        )
+        .unwrap();
+        assert!(!from_bookmark.cached);
+        assert_eq!(from_bookmark.camera, camera);
+        assert_ne!(from_bookmark.heatmap, changed.heatmap);
+
+        cancel.store(true, Ordering::SeqCst);
+        let moved = square(&paths, "job-4", 0.3, red);
+        assert!(matches!(
+            compare(&cache, &before, &moved, true, None, &cancel),
+            Err(AppError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn the_heatmap_measures_difference_over_drawn_pixels_only() {
+        let a = [255, 0, 0, 255, 0, 0, 0, 0, 255, 0, 0, 255];
+        let b = [255, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0];
+        let (heat, difference) = heatmap(&a, &b);
+        // Agreeing, empty in both, and white where only `a` drew
+        assert_eq!(heat, [0, 0, 0, 255, 0, 0, 0, 0, 255, 255, 255, 255]);
+        assert_eq!(difference.mean, 0.5);
+        assert_eq!(difference.changed_share, 0.5);
+
+        let newer = CameraBookmark {
+            name: "top".to_string(),
+            camera_state: serde_json::from_value(json!({ "version": 2, "orbit": [1.0] })).unwrap(),
+            saved_at: 0,
+        };
+        assert!(matches!(
+            bookmark_camera(&newer),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,668 @@
+//! Splat Conversion
+//!
+//! Converts the Gaussian splat PLY written by the trainer into the compact
+//! `.splat` layout web viewers stream: 32 bytes per splat holding position,
+//! scale, RGBA color and rotation, most visible splats first. It also writes
+//! Niantic's gzipped `.spz`, version 2 without higher spherical harmonics,
+//! which is smaller still for downloading. Splats made by
+//! other tools in that layout, or in Niantic's gzipped `.spz`, are read back
+//! into the REQUIRED properties of a PLY for import; what those layouts
+//! quantized stays quantized, and higher spherical harmonics are dropped.
+
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+pub const SH_C0: f32 = 0.282_094_8;
+
+/// A PLY header longer than this is not one the CLI wrote
+pub const MAX_HEADER_LEN: u64 = 64 * 1024;
+
+/// Bytes per splat in the `.splat` layout
+pub const SPLAT_RECORD_LEN: usize = 32;
+
+const SPZ_MAGIC: u32 = 0x5053_474e;
+const SPZ_HEADER_LEN: usize = 16;
+/// Fractional bits of the fixed-point positions written to `.spz`
+const SPZ_FRACTIONAL_BITS: u8 = 12;
+/// Scale of the DC color terms in `.spz`
+const SPZ_COLOR_SCALE: f32 = 0.15;
+
+/// Vertex properties a Gaussian splat PLY must provide
+pub const REQUIRED: [&str; 14] = [
+    "x", "y", "z", "scale_0", "scale_1", "scale_2", "rot_0", "rot_1", "rot_2", "rot_3", "opacity",
+    "f_dc_0", "f_dc_1", "f_dc_2",
+];
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    fn read(self, bytes: &[u8]) -> f32 {
+        match self {
+            Self::I8 => bytes[0] as i8 as f32,
+            Self::U8 => bytes[0] as f32,
+            Self::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f32,
+            Self::I32 => i32::from_le_bytes(bytes[..4].try_into().unwrap()) as f32,
+            Self::U32 => u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f32,
+            Self::F32 => f32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            Self::F64 => f64::from_le_bytes(bytes[..8].try_into().unwrap()) as f32,
+        }
+    }
+}
+
+/// Where a Gaussian splat PLY keeps its vertices
+pub struct Layout {
+    pub vertex_count: usize,
+    /// Bytes per vertex
+    pub stride: usize,
+    /// Offset and type of each REQUIRED property, in the same order
+    fields: Vec<(usize, ScalarType)>,
+    /// Bytes before the first vertex, the header included
+    pub body_offset: usize,
+}
+
+impl Layout {
+    /// The REQUIRED properties of one vertex, in order
+    pub fn values(&self, vertex: &[u8]) -> Vec<f32> {
+        self.fields
+            .iter()
+            .map(|&(offset, ty)| ty.read(&vertex[offset..]))
+            .collect()
+    }
+}
+
+/// Convert a binary little-endian Gaussian splat PLY to the `.splat` layout
+pub fn ply_to_splat(ply: &[u8]) -> Result<Vec<u8>, String> {
+    let layout = parse_header(ply)?;
+    let body = &ply[layout.body_offset..];
+    let needed = layout
+        .vertex_count
+        .checked_mul(layout.stride)
+        .ok_or("PLY vertex count is too large")?;
+    if body.len() < needed {
+        return Err("PLY file is truncated".to_string());
+    }
+
+    let mut splats: Vec<(f32, [u8; SPLAT_RECORD_LEN])> = body[..needed]
+        .chunks_exact(layout.stride)
+        .map(|vertex| encode(&layout.values(vertex)))
+        .collect();
+
+    // Viewers that stop early still show the most visible splats
+    splats.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut out = Vec::with_capacity(splats.len() * SPLAT_RECORD_LEN);
+    for (_, record) in &splats {
+        out.extend_from_slice(record);
+    }
+    Ok(out)
+}
+
+// v holds the REQUIRED properties in order; returns the sort weight and the record
+fn encode(v: &[f32]) -> (f32, [u8; SPLAT_RECORD_LEN]) {
+    let scale = [v[3].exp(), v[4].exp(), v[5].exp()];
+    let alpha = 1.0 / (1.0 + (-v[10]).exp());
+
+    let mut record = [0u8; SPLAT_RECORD_LEN];
+    for (i, value) in [v[0], v[1], v[2], scale[0], scale[1], scale[2]]
+        .iter()
+        .enumerate()
+    {
+        record[i * 4..i * 4 + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    for i in 0..3 {
+        record[24 + i] = to_u8((0.5 + SH_C0 * v[11 + i]) * 255.0);
+    }
+    record[27] = to_u8(alpha * 255.0);
+
+    let rot = [v[6], v[7], v[8], v[9]];
+    let len = rot.iter().map(|r| r * r).sum::<f32>().sqrt();
+    for (i, r) in rot.iter().enumerate() {
+        let normalized = if len > 0.0 {
+            r / len
+        } else if i == 0 {
+            1.0
+        } else {
+            0.0
+        };
+        record[28 + i] = to_u8(normalized * 128.0 + 128.0);
+    }
+
+    (scale[0] * scale[1] * scale[2] * alpha, record)
+}
+
+fn to_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+/// Convert a binary little-endian Gaussian splat PLY to a gzipped `.spz`,
+/// version 2 with spherical harmonics of degree 0
+pub fn ply_to_spz(ply: &[u8]) -> Result<Vec<u8>, String> {
+    let layout = parse_header(ply)?;
+    let body = &ply[layout.body_offset..];
+    let needed = layout
+        .vertex_count
+        .checked_mul(layout.stride)
+        .ok_or("PLY vertex count is too large")?;
+    if body.len() < needed {
+        return Err("PLY file is truncated".to_string());
+    }
+    let count = u32::try_from(layout.vertex_count).map_err(|_| "Too many splats for .spz")?;
+    let vertices: Vec<Vec<f32>> = body[..needed]
+        .chunks_exact(layout.stride)
+        .map(|vertex| layout.values(vertex))
+        .collect();
+
+    let mut raw = Vec::with_capacity(SPZ_HEADER_LEN + vertices.len() * 19);
+    for word in [SPZ_MAGIC, 2, count] {
+        raw.extend_from_slice(&word.to_le_bytes());
+    }
+    raw.extend_from_slice(&[0, SPZ_FRACTIONAL_BITS, 0, 0]);
+    // The PLY is right-down-forward; .spz is right-up-back
+    let limit = (1 << 23) - 1;
+    for v in &vertices {
+        for value in [v[0], -v[1], -v[2]] {
+            let fixed = (value * (1u32 << SPZ_FRACTIONAL_BITS) as f32).round() as i32;
+            raw.extend_from_slice(&fixed.clamp(-limit - 1, limit).to_le_bytes()[..3]);
+        }
+    }
+    for v in &vertices {
+        raw.push(to_u8(255.0 / (1.0 + (-v[10]).exp())));
+    }
+    for v in &vertices {
+        for c in 0..3 {
+            raw.push(to_u8((v[11 + c] * SPZ_COLOR_SCALE + 0.5) * 255.0));
+        }
+    }
+    for v in &vertices {
+        for axis in 0..3 {
+            raw.push(to_u8((v[3 + axis] + 10.0) * 16.0));
+        }
+    }
+    for v in &vertices {
+        // Version 2 keeps x, y and z of a rotation whose w is not negative
+        let rot = [v[6], v[7], -v[8], -v[9]];
+        let len = rot.iter().map(|r| r * r).sum::<f32>().sqrt();
+        let [w, x, y, z] = if len > 0.0 {
+            rot.map(|r| r / len)
+        } else {
+            [1.0, 0.0, 0.0, 0.0]
+        };
+        let sign = if w < 0.0 { -1.0 } else { 1.0 };
+        for r in [x, y, z] {
+            raw.push(to_u8((r * sign + 1.0) * 127.5));
+        }
+    }
+
+    let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+    gz.write_all(&raw).map_err(|e| e.to_string())?;
+    gz.finish().map_err(|e| e.to_string())
+}
+
+/// The REQUIRED properties of each splat in the `.splat` layout
+pub fn splat_to_vertices(splat: &[u8]) -> Result<Vec<[f32; 14]>, String> {
+    if splat.is_empty() || splat.len() % SPLAT_RECORD_LEN != 0 {
+        return Err(format!(
+            "Not a .splat file: its size is not a multiple of {} bytes",
+            SPLAT_RECORD_LEN
+        ));
+    }
+    splat
+        .chunks_exact(SPLAT_RECORD_LEN)
+        .map(|record| {
+            let f = |i: usize| f32::from_le_bytes(record[i * 4..i * 4 + 4].try_into().unwrap());
+            let scale = [f(3), f(4), f(5)];
+            if ![f(0), f(1), f(2)].iter().all(|v| v.is_finite())
+                || !scale.iter().all(|s| s.is_finite() && *s > 0.0)
+            {
+                return Err("Corrupt .splat file: invalid position or scale".to_string());
+            }
+            let rot = |i: usize| (record[28 + i] as f32 - 128.0) / 128.0;
+            let dc = |i: usize| (record[24 + i] as f32 / 255.0 - 0.5) / SH_C0;
+            Ok([
+                f(0),
+                f(1),
+                f(2),
+                scale[0].ln(),
+                scale[1].ln(),
+                scale[2].ln(),
+                rot(0),
+                rot(1),
+                rot(2),
+                rot(3),
+                logit(record[27]),
+                dc(0),
+                dc(1),
+                dc(2),
+            ])
+        })
+        .collect()
+}
+
+/// The REQUIRED properties of each splat in a gzipped `.spz` file, version 2 or 3
+pub fn spz_to_vertices(compressed: &[u8]) -> Result<Vec<[f32; 14]>, String> {
+    let mut gz = flate2::read::GzDecoder::new(compressed);
+    let mut header = [0u8; SPZ_HEADER_LEN];
+    gz.read_exact(&mut header)
+        .map_err(|_| "Not a .spz file: not gzip-compressed splats".to_string())?;
+    let word = |i: usize| u32::from_le_bytes(header[i * 4..i * 4 + 4].try_into().unwrap());
+    if word(0) != SPZ_MAGIC {
+        return Err("Not a .spz file: missing its magic number".to_string());
+    }
+    let version = word(1);
+    if !(2..=3).contains(&version) {
+        return Err(format!("Unsupported .spz version {}", version));
+    }
+    let count = word(2) as usize;
+    let sh_coefficients = match header[12] {
+        0 => 0,
+        1 => 3,
+        2 => 8,
+        3 => 15,
+        degree => {
+            return Err(format!(
+                "Corrupt .spz file: spherical harmonics degree {}",
+                degree
+            ))
+        }
+    };
+    let fractional_bits = header[13];
+    if count == 0 {
+        return Err("The .spz file has no splats".to_string());
+    }
+    let rotation_len = if version == 2 { 3 } else { 4 };
+    let needed = count
+        .checked_mul(9 + 1 + 3 + 3 + rotation_len + sh_coefficients * 3)
+        .ok_or("Corrupt .spz file: too many splats")?;
+    let mut body = Vec::with_capacity(needed);
+    // Never inflate more than the header announces
+    gz.take(needed as u64)
+        .read_to_end(&mut body)
+        .map_err(|e| format!("Corrupt .spz file: {}", e))?;
+    if body.len() < needed {
+        return Err("The .spz file is truncated".to_string());
+    }
+
+    let (positions, rest) = body.split_at(count * 9);
+    let (alphas, rest) = rest.split_at(count);
+    let (colors, rest) = rest.split_at(count * 3);
+    let (scales, rotations) = rest.split_at(count * 3);
+    let position = |i: usize| {
+        let b = &positions[i * 3..i * 3 + 3];
+        // 24-bit two's complement, sign-extended through the top byte
+        let fixed = i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8;
+        fixed as f32 / (1u32 << fractional_bits) as f32
+    };
+    Ok((0..count)
+        .map(|i| {
+            // .spz is right-up-back; the PLY is right-down-forward
+            let [w, x, y, z] = spz_rotation(&rotations[i * rotation_len..(i + 1) * rotation_len]);
+            let scale = |axis: usize| scales[i * 3 + axis] as f32 / 16.0 - 10.0;
+            let dc = |c: usize| (colors[i * 3 + c] as f32 / 255.0 - 0.5) / SPZ_COLOR_SCALE;
+            [
+                position(i * 3),
+                -position(i * 3 + 1),
+                -position(i * 3 + 2),
+                scale(0),
+                scale(1),
+                scale(2),
+                w,
+                x,
+                -y,
+                -z,
+                logit(alphas[i]),
+                dc(0),
+                dc(1),
+                dc(2),
+            ]
+        })
+        .collect())
+}
+
+/// A `.spz` rotation as w, x, y, z: three bytes of x, y and z in version 2,
+/// the smallest three of four packed into 32 bits in version 3
+fn spz_rotation(bytes: &[u8]) -> [f32; 4] {
+    let mut xyzw = [0f32; 4];
+    if let [x, y, z] = bytes {
+        for (out, b) in xyzw.iter_mut().zip([x, y, z]) {
+            *out = *b as f32 / 127.5 - 1.0;
+        }
+        let squares: f32 = xyzw[..3].iter().map(|v| v * v).sum();
+        xyzw[3] = (1.0 - squares).max(0.0).sqrt();
+    } else {
+        const MASK: u32 = (1 << 9) - 1;
+        let mut packed = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let largest = (packed >> 30) as usize;
+        let mut squares = 0.0;
+        for i in (0..4).rev().filter(|i| *i != largest) {
+            let magnitude = std::f32::consts::FRAC_1_SQRT_2 * (packed & MASK) as f32 / MASK as f32;
+            let negative = (packed >> 9) & 1 == 1;
+            packed >>= 10;
+            xyzw[i] = if negative { -magnitude } else { magnitude };
+            squares += xyzw[i] * xyzw[i];
+        }
+        xyzw[largest] = (1.0 - squares).max(0.0).sqrt();
+    }
+    [xyzw[3], xyzw[0], xyzw[1], xyzw[2]]
+}
+
+/// The opacity whose sigmoid an 8-bit alpha holds
+fn logit(alpha: u8) -> f32 {
+    let a = (alpha as f32 / 255.0).clamp(0.5 / 255.0, 254.5 / 255.0);
+    (a / (1.0 - a)).ln()
+}
+
+/// Write a binary little-endian Gaussian splat PLY holding exactly the
+/// REQUIRED properties of each vertex, in order
+pub fn write_ply(out: &mut impl Write, vertices: &[[f32; 14]]) -> io::Result<()> {
+    let mut header = format!(
+        "ply\nformat binary_little_endian 1.0\nelement vertex {}\n",
+        vertices.len()
+    );
+    for name in REQUIRED {
+        header.push_str(&format!("property float {}\n", name));
+    }
+    header.push_str("end_header\n");
+    out.write_all(header.as_bytes())?;
+    for vertex in vertices {
+        for value in vertex {
+            out.write_all(&value.to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// The number of splats in a Gaussian splat PLY, read from its header alone
+pub fn splat_count(header: &[u8]) -> Result<usize, String> {
+    parse_header(header).map(|layout| layout.vertex_count)
+}
+
+/// The number of splats in a PLY file, failing unless the file holds all the
+/// vertex data its header announces
+pub fn complete_splat_count(path: &Path) -> Result<usize, String> {
+    let file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut header = vec![];
+    file.take(MAX_HEADER_LEN)
+        .read_to_end(&mut header)
+        .map_err(|e| e.to_string())?;
+    let layout = parse_header(&header)?;
+    if layout.vertex_count == 0 {
+        return Err("PLY file has no splats".to_string());
+    }
+    let needed = layout.body_offset as u64 + layout.vertex_count as u64 * layout.stride as u64;
+    if len < needed {
+        return Err("PLY file is truncated".to_string());
+    }
+    Ok(layout.vertex_count)
+}
+
+/// The vertex layout of a binary little-endian Gaussian splat PLY, from a
+/// prefix of the file that holds its whole header
+pub fn parse_header(ply: &[u8]) -> Result<Layout, String> {
+    const END: &[u8] = b"end_header\n";
+    let end = ply
+        .windows(END.len())
+        .position(|w| w == END)
+        .ok_or("Not a PLY file: missing end_header")?;
+    let header = std::str::from_utf8(&ply[..end]).map_err(|_| "PLY header is not text")?;
+
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err("Not a PLY file".to_string());
+    }
+
+    let mut format = None;
+    let mut vertex_count = None;
+    let mut in_vertex = false;
+    let mut offset = 0;
+    let mut properties = vec![];
+
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", f, _] => format = Some(f.to_string()),
+            ["element", name, count] => {
+                if vertex_count.is_some() {
+                    // Later elements follow the vertex data and are not read
+                    in_vertex = false;
+                } else if *name == "vertex" {
+                    let count = count.parse().map_err(|_| "Invalid PLY vertex count")?;
+                    vertex_count = Some(count);
+                    in_vertex = true;
+                } else {
+                    return Err("PLY vertex element must come first".to_string());
+                }
+            }
+            ["property", "list", ..] if in_vertex => {
+                return Err("PLY vertex list properties are not supported".to_string())
+            }
+            ["property", ty, name] if in_vertex => {
+                let ty = ScalarType::parse(ty)
+                    .ok_or_else(|| format!("Unsupported PLY property type: {}", ty))?;
+                properties.push((name.to_string(), offset, ty));
+                offset += ty.size();
+            }
+            _ => {}
+        }
+    }
+
+    if format.as_deref() != Some("binary_little_endian") {
+        return Err("Only binary little-endian PLY files can be converted".to_string());
+    }
+    let vertex_count = vertex_count.ok_or("PLY file has no vertices")?;
+    // Positions and perhaps colors, but nothing a splat is drawn from
+    if !REQUIRED[3..]
+        .iter()
+        .any(|required| properties.iter().any(|(name, _, _)| name == required))
+    {
+        return Err(
+            "Not a Gaussian splat PLY: plain point cloud without SH coefficients".to_string(),
+        );
+    }
+
+    let fields = REQUIRED
+        .iter()
+        .map(|required| {
+            properties
+                .iter()
+                .find(|(name, _, _)| name == required)
+                .map(|&(_, offset, ty)| (offset, ty))
+                .ok_or_else(|| format!("Not a Gaussian splat PLY: missing {}", required))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Layout {
+        vertex_count,
+        stride: offset,
+        fields,
+        body_offset: end + END.len(),
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// A Gaussian splat PLY with an extra property, as trainers write them
+    pub fn sample_ply(vertices: &[[f32; 14]]) -> Vec<u8> {
+        let mut ply = format!(
+            "ply\nformat binary_little_endian 1.0\nelement vertex {}\nproperty float nx\n",
+            vertices.len()
+        );
+        for name in REQUIRED {
+            ply.push_str(&format!("property float {}\n", name));
+        }
+        ply.push_str("end_header\n");
+
+        let mut bytes = ply.into_bytes();
+        for vertex in vertices {
+            bytes.extend_from_slice(&0f32.to_le_bytes());
+            for value in vertex {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    fn f32_at(record: &[u8], index: usize) -> f32 {
+        f32::from_le_bytes(record[index * 4..index * 4 + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn converts_and_orders_by_visibility() {
+        let faint = [
+            1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, -4.0, 0.0, 0.0, 0.0,
+        ];
+        let bold = [
+            4.0, 5.0, 6.0, 1.0, 1.0, 1.0, 0.0, 0.0, 2.0, 0.0, 4.0, 1.0, -10.0, 0.0,
+        ];
+        let splat = ply_to_splat(&sample_ply(&[faint, bold])).unwrap();
+        assert_eq!(splat.len(), 2 * SPLAT_RECORD_LEN);
+
+        let first = &splat[..SPLAT_RECORD_LEN];
+        assert_eq!(
+            [f32_at(first, 0), f32_at(first, 1), f32_at(first, 2)],
+            [4.0, 5.0, 6.0]
+        );
+        assert!((f32_at(first, 3) - 1f32.exp()).abs() < 1e-5);
+        assert_eq!(first[24], 199);
+        assert_eq!(first[25], 0);
+        assert_eq!(first[26], 128);
+        assert_eq!(first[27], 250);
+        assert_eq!(&first[28..32], &[128, 128, 255, 128]);
+
+        let second = &splat[SPLAT_RECORD_LEN..];
+        assert_eq!(f32_at(second, 0), 1.0);
+        assert_eq!(&second[28..32], &[255, 128, 128, 128]);
+    }
+
+    #[test]
+    fn rejects_non_splat_files() {
+        assert!(ply_to_splat(b"not a ply").is_err());
+
+        let points = b"ply\nformat binary_little_endian 1.0\nelement vertex 1\nproperty float x\nproperty float y\nproperty float z\nend_header\n\0\0\0\0\0\0\0\0\0\0\0\0";
+        let err = ply_to_splat(points).unwrap_err();
+        assert!(err.contains("plain point cloud"), "{}", err);
+
+        let ascii = String::from_utf8(sample_ply(&[]))
+            .unwrap()
+            .replace("binary_little_endian", "ascii");
+        assert!(ply_to_splat(ascii.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let mut ply = sample_ply(&[[0.0; 14]]);
+        ply.truncate(ply.len() - 1);
+        assert_eq!(ply_to_splat(&ply).unwrap_err(), "PLY file is truncated");
+    }
+
+    #[test]
+    fn complete_files_report_their_splats() {
+        let paths = crate::platform::testing::TempPaths::new();
+        let path = paths.root().join("output.ply");
+        let mut ply = sample_ply(&[[0.0; 14], [1.0; 14]]);
+        std::fs::write(&path, &ply).unwrap();
+        assert_eq!(complete_splat_count(&path), Ok(2));
+
+        ply.truncate(ply.len() - 1);
+        std::fs::write(&path, &ply).unwrap();
+        assert_eq!(
+            complete_splat_count(&path).unwrap_err(),
+            "PLY file is truncated"
+        );
+        std::fs::write(&path, sample_ply(&[])).unwrap();
+        assert!(complete_splat_count(&path).is_err());
+    }
+
+    #[test]
+    fn reads_spz_back_into_ply_coordinates() {
+        use flate2::write::GzEncoder;
+
+        let mut raw = vec![];
+        for word in [SPZ_MAGIC, 2, 1] {
+            raw.extend_from_slice(&word.to_le_bytes());
+        }
+        // Degree 0, 12 fractional bits
+        raw.extend_from_slice(&[0, 12, 0, 0]);
+        // x = 1.0, y = -2.0, z = 0.5
+        for fixed in [4096i32, -8192, 2048] {
+            raw.extend_from_slice(&fixed.to_le_bytes()[..3]);
+        }
+        raw.push(255);
+        raw.extend_from_slice(&[128, 128, 128]);
+        // Log scales of -2
+        raw.extend_from_slice(&[128, 128, 128]);
+        // The identity rotation
+        raw.extend_from_slice(&[128, 128, 128]);
+        let mut gz = GzEncoder::new(vec![], flate2::Compression::fast());
+        gz.write_all(&raw).unwrap();
+        let spz = gz.finish().unwrap();
+
+        let vertices = spz_to_vertices(&spz).unwrap();
+        assert_eq!(vertices.len(), 1);
+        let v = vertices[0];
+        assert_eq!(&v[..6], &[1.0, 2.0, -0.5, -2.0, -2.0, -2.0]);
+        assert!((v[6] - 1.0).abs() < 0.01 && v[7..10].iter().all(|r| r.abs() < 0.01));
+        assert!(v[10] > 5.0);
+        assert!(spz_to_vertices(&spz[..spz.len() / 2]).is_err());
+        assert!(spz_to_vertices(b"not gzip").is_err());
+    }
+
+    #[test]
+    fn writes_spz_that_reads_back() {
+        let vertex = [
+            1.0, 2.0, -0.5, -2.0, -1.0, 0.5, 0.0, 0.0, 0.0, 1.0, 3.0, 1.0, -1.0, 0.0,
+        ];
+        let spz = ply_to_spz(&sample_ply(&[vertex])).unwrap();
+        let read = spz_to_vertices(&spz).unwrap();
+        assert_eq!(read.len(), 1);
+        let v = read[0];
+        assert_eq!(&v[..3], &[1.0, 2.0, -0.5]);
+        for axis in 3..6 {
+            assert!((v[axis] - vertex[axis]).abs() < 0.07, "{:?}", v);
+        }
+        // A half turn about z either way round
+        assert!(v[6].abs() < 0.01 && v[7].abs() < 0.01 && v[8].abs() < 0.01);
+        assert!((v[9].abs() - 1.0).abs() < 0.01, "{:?}", v);
+        assert!((v[10] - vertex[10]).abs() < 0.1);
+        for c in 11..14 {
+            assert!((v[c] - vertex[c]).abs() < 0.03, "{:?}", v);
+        }
+        assert!(ply_to_spz(b"not a ply").is_err());
+    }
+}
@@ -0,0 +1,214 @@
+//! Data Files
+//!
+//! Shared handling of the files the backend keeps in app_data. A file that no
+//! longer parses is moved aside to `<name>.corrupt-<timestamp>` instead of being
+//! overwritten, and a data-file-recovered event names what was lost with it.
+//! Files that change often are written through a [`Debounced`] writer, which
+//! coalesces updates into one write shortly afterwards; every write goes
+//! through a temporary file and a rename.
+
+use crate::error::AppError;
+use crate::fsutil;
+use crate::job_log;
+use crate::written_by;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+/// Delay before a debounced update is written
+pub const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Files recovered this session, for a frontend that was not listening yet
+static RECOVERED: Mutex<Vec<Recovered>> = Mutex::new(Vec::new());
+
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recovered {
+    pub file: String,
+    /// Where the unreadable file was moved
+    pub moved_to: Option<String>,
+    /// What could not be read back, e.g. "3 job history records"
+    pub lost: String,
+    pub error: String,
+}
+
+/// Emit recoveries from now on; those found earlier wait for get_data_file_recoveries
+pub fn start(app: &AppHandle) {
+    APP.set(app.clone()).ok();
+}
+
+/// Data files found unreadable this session
+#[tauri::command]
+pub async fn get_data_file_recoveries() -> Result<Vec<Recovered>, AppError> {
+    Ok(recoveries())
+}
+
+/// Data files found unreadable this session
+pub fn recoveries() -> Vec<Recovered> {
+    RECOVERED.lock().unwrap().clone()
+}
+
+/// Move an unreadable file aside and report what was lost with it
+pub fn quarantine(path: &Path, lost: impl Into<String>, error: impl Into<String>) {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Numbered after the first, so a file quarantined twice in a second
+    // keeps both copies
+    let stamp = job_log::unix_timestamp();
+    let moved_to = (1..)
+        .map(|n| match n {
+            1 => format!("{}.corrupt-{}", file_name, stamp),
+            n => format!("{}.corrupt-{}-{}", file_name, stamp, n),
+        })
+        .map(|name| path.with_file_name(name))
+        .find(|candidate| !candidate.exists())
+        .unwrap();
+    let moved = std::fs::rename(path, &moved_to).is_ok();
+    let recovered = Recovered {
+        file: path.to_string_lossy().to_string(),
+        moved_to: moved.then(|| moved_to.to_string_lossy().to_string()),
+        lost: lost.into(),
+        error: error.into(),
+    };
+    eprintln!(
+        "Recovered {}: {} ({})",
+        recovered.file, recovered.lost, recovered.error
+    );
+    if let Some(app) = APP.get() {
+        app.emit("data-file-recovered", &recovered).ok();
+    }
+    RECOVERED.lock().unwrap().push(recovered);
+}
+
+/// Read a JSON file; None when there is none, or when it did not parse and
+/// was quarantined as holding `lost`
+pub fn read_json<T: DeserializeOwned>(path: &Path, lost: &str) -> Result<Option<T>, String> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    match serde_json::from_str(&content) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => {
+            quarantine(path, lost, written_by::explain(&content, e));
+            Ok(None)
+        }
+    }
+}
+
+/// A JSON file rewritten whole, at most once per DEBOUNCE
+pub struct Debounced {
+    path: PathBuf,
+    /// The latest content not written yet
+    pending: Mutex<Option<Vec<u8>>>,
+    flush_scheduled: AtomicBool,
+}
+
+impl Debounced {
+    pub fn new(path: PathBuf) -> Arc<Self> {
+        Arc::new(Self {
+            path,
+            pending: Mutex::new(None),
+            flush_scheduled: AtomicBool::new(false),
+        })
+    }
+
+    /// Write `value` shortly, replacing any content still waiting
+    pub fn write<T: Serialize + ?Sized>(self: &Arc<Self>, value: &T) {
+        let Ok(content) = serde_json::to_vec_pretty(value) else {
+            return;
+        };
+        *self.pending.lock().unwrap() = Some(content);
+        if !self.flush_scheduled.swap(true, Ordering::SeqCst) {
+            let file = self.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(DEBOUNCE).await;
+                file.flush_scheduled.store(false, Ordering::SeqCst);
+                file.flush();
+            });
+        }
+    }
+
+    /// Write `value` before returning, for transitions that must not be lost
+    pub fn write_now<T: Serialize + ?Sized>(&self, value: &T) {
+        if let Ok(content) = serde_json::to_vec_pretty(value) {
+            *self.pending.lock().unwrap() = Some(content);
+            self.flush();
+        }
+    }
+
+    /// Write what is waiting, if anything
+    pub fn flush(&self) {
+        // Held during the write so writes of the file never interleave
+        let mut pending = self.pending.lock().unwrap();
+        if let Some(content) = pending.take() {
+            if let Err(e) = fsutil::write_atomic(&self.path, &content) {
+                eprintln!("Failed to write {}: {}", self.path.display(), e);
+                *pending = Some(content);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn unreadable_files_are_moved_aside() {
+        let paths = TempPaths::new();
+        let path = paths.root().join("queue.json");
+        assert_eq!(read_json::<Vec<u32>>(&path, "the queue").unwrap(), None);
+
+        std::fs::write(&path, b"[1, 2").unwrap();
+        assert_eq!(read_json::<Vec<u32>>(&path, "the queue").unwrap(), None);
+        assert!(!path.exists());
+        // Other tests quarantine files too
+        let last_of = |path: &Path| {
+            let recovered = RECOVERED.lock().unwrap();
+            let file = path.to_string_lossy();
+            recovered.iter().rfind(|r| r.file == file).cloned().unwrap()
+        };
+        let recovered = last_of(&path);
+        assert_eq!(recovered.lost, "the queue");
+        let moved_to = recovered.moved_to.unwrap();
+        assert!(moved_to.contains("queue.json.corrupt-"));
+        assert_eq!(std::fs::read(&moved_to).unwrap(), b"[1, 2");
+
+        // Again, most likely within the same second
+        std::fs::write(&path, b"{").unwrap();
+        quarantine(&path, "the queue", "unreadable");
+        let again = last_of(&path).moved_to.unwrap();
+        assert_ne!(again, moved_to);
+        assert_eq!(std::fs::read(&moved_to).unwrap(), b"[1, 2");
+        assert_eq!(std::fs::read(again).unwrap(), b"{");
+    }
+
+    #[tokio::test]
+    async fn debounced_writes_coalesce() {
+        let paths = TempPaths::new();
+        let path = paths.root().join("queue.json");
+        let file = Debounced::new(path.clone());
+
+        file.write(&[1]);
+        file.write(&[1, 2]);
+        assert!(!path.exists());
+        tokio::time::sleep(DEBOUNCE * 3).await;
+        assert_eq!(read_json::<Vec<u32>>(&path, "").unwrap(), Some(vec![1, 2]));
+
+        file.write(&[3]);
+        file.write_now(&[4]);
+        assert_eq!(read_json::<Vec<u32>>(&path, "").unwrap(), Some(vec![4]));
+        tokio::time::sleep(DEBOUNCE * 3).await;
+        assert_eq!(read_json::<Vec<u32>>(&path, "").unwrap(), Some(vec![4]));
+    }
+}
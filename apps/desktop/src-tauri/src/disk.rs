@@ -0,0 +1,330 @@
+//! Disk Space
+//!
+//! Resolves which volume a path lives on and how much of it is free, and
+//! checks a job's projected footprint against it before the job starts. Each
+//! role (inputs, scratch, output) may fit on its own while the roles sharing
+//! one small drive together do not, so the check adds up what every volume
+//! has to hold.
+
+use crate::capabilities;
+use crate::commands::ProcessArgs;
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::path_policy::PathPolicy;
+use crate::settings::SettingsStore;
+use crate::validation;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+
+/// Extracted frames take several times the space of the compressed video
+const FRAME_BYTES_PER_INPUT_BYTE: u64 = 3;
+
+/// COLMAP's database and models, as a fraction of the frames
+const COLMAP_FRACTION: u64 = 4;
+
+/// Room for the trained splat, which does not depend on the input size
+const ARTIFACT_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Inputs,
+    /// Frames the backend extracts itself
+    Scratch,
+    /// Frames, COLMAP data and the artifact the CLI writes
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    /// Tells volumes apart; not meant for display
+    pub id: String,
+    /// Where the volume is mounted, or its drive on Windows
+    pub mount: String,
+    /// None when the platform cannot tell
+    pub available_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeVerdict {
+    pub mount: String,
+    pub roles: Vec<Role>,
+    pub required_bytes: u64,
+    pub available_bytes: Option<u64>,
+    pub fits: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskPreflight {
+    pub input_bytes: u64,
+    pub volumes: Vec<VolumeVerdict>,
+}
+
+/// The space process_videos would need on each volume, for the UI to show
+/// before the job is started
+#[tauri::command]
+pub async fn preflight_disk_space(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    args: Value,
+) -> Result<DiskPreflight, AppError> {
+    let mut args = validation::process_args(args, &capabilities::presets(&app).await.presets)?;
+    for video in &args.videos {
+        policy.check_existing(video)?;
+    }
+    policy.check_target(&args.output_dir)?;
+    args.scratch_dir = app.settings().scratch_dir;
+    preflight(&args)
+}
+
+/// The volume a path is on. Paths that do not exist yet, such as a new output
+/// directory, are resolved through their nearest existing ancestor.
+pub fn volume_of(path: &Path) -> io::Result<Volume> {
+    let existing = path
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, path.display().to_string()))?;
+    let canonical = existing.canonicalize()?;
+    let (id, mount) = platform::identify(&canonical)?;
+    Ok(Volume {
+        id,
+        available_bytes: platform::available_bytes(&mount).ok(),
+        mount: mount.to_string_lossy().to_string(),
+    })
+}
+
+/// Where a job's scratch data goes: the configured directory, or the system's
+pub fn scratch_dir(configured: Option<&str>) -> PathBuf {
+    configured
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// Project the space a job needs on each volume it touches
+pub fn preflight(args: &ProcessArgs) -> Result<DiskPreflight, AppError> {
+    let mut input_bytes = 0;
+    let mut roles = vec![];
+    for video in &args.videos {
+        input_bytes += std::fs::metadata(video)?.len();
+        roles.push((Role::Inputs, volume_of(Path::new(video))?));
+    }
+    let scratch = scratch_dir(args.scratch_dir.as_deref());
+    roles.push((Role::Scratch, volume_of(&scratch)?));
+    roles.push((Role::Output, volume_of(Path::new(&args.output_dir))?));
+    Ok(DiskPreflight {
+        input_bytes,
+        volumes: verdicts(&roles, input_bytes),
+    })
+}
+
+/// Fail when a volume cannot hold what the job would write to it
+pub fn check(preflight: &DiskPreflight) -> Result<(), AppError> {
+    let Some(full) = preflight.volumes.iter().find(|v| !v.fits) else {
+        return Ok(());
+    };
+    // Moving the scratch data elsewhere is the easy fix when it shares the drive
+    let key = if full.roles.contains(&Role::Scratch) && full.roles.len() > 1 {
+        "error.shared_volume_full"
+    } else {
+        "error.volume_full"
+    };
+    let message = Message::new(key)
+        .with("volume", &full.mount)
+        .with("required", format_bytes(full.required_bytes))
+        .with("available", format_bytes(full.available_bytes.unwrap_or(0)));
+    Err(AppError::NotEnoughSpace(message))
+}
+
+fn verdicts(roles: &[(Role, Volume)], input_bytes: u64) -> Vec<VolumeVerdict> {
+    let frame_bytes = input_bytes * FRAME_BYTES_PER_INPUT_BYTE;
+    let needed = |role: Role| match role {
+        // Already on disk
+        Role::Inputs => 0,
+        Role::Scratch => frame_bytes,
+        Role::Output => frame_bytes + frame_bytes / COLMAP_FRACTION + ARTIFACT_BYTES,
+    };
+
+    let mut volumes: Vec<(Volume, Vec<Role>)> = vec![];
+    for (role, volume) in roles {
+        match volumes.iter_mut().find(|(v, _)| v.id == volume.id) {
+            Some((_, roles)) if roles.contains(role) => {}
+            Some((_, roles)) => roles.push(*role),
+            None => volumes.push((volume.clone(), vec![*role])),
+        }
+    }
+    volumes
+        .into_iter()
+        .map(|(volume, roles)| {
+            let required_bytes = roles.iter().map(|r| needed(*r)).sum();
+            VolumeVerdict {
+                fits: volume
+                    .available_bytes
+                    .map_or(true, |available| available >= required_bytes),
+                mount: volume.mount,
+                roles,
+                required_bytes,
+                available_bytes: volume.available_bytes,
+            }
+        })
+        .collect()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    format!("{:.1} GB", bytes as f64 / GB)
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+
+    /// The device number, and the topmost directory still on that device
+    pub fn identify(path: &Path) -> io::Result<(String, PathBuf)> {
+        let device = std::fs::metadata(path)?.dev();
+        let mount = path
+            .ancestors()
+            .take_while(|p| std::fs::metadata(p).is_ok_and(|m| m.dev() == device))
+            .last()
+            .unwrap_or(path);
+        Ok((device.to_string(), mount.to_path_buf()))
+    }
+
+    // The field widths differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    pub fn available_bytes(path: &Path) -> io::Result<u64> {
+        let path = CString::new(path.as_os_str().as_bytes())?;
+        // SAFETY: statvfs only writes into the zeroed struct it is given
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Component, Path, PathBuf};
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    /// The drive letter or network share of a canonical path
+    pub fn identify(path: &Path) -> io::Result<(String, PathBuf)> {
+        match path.components().next() {
+            Some(Component::Prefix(prefix)) => {
+                let mut root = PathBuf::from(prefix.as_os_str());
+                root.push("\\");
+                let id = prefix.as_os_str().to_string_lossy().to_uppercase();
+                Ok((id, root))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                path.display().to_string(),
+            )),
+        }
+    }
+
+    pub fn available_bytes(path: &Path) -> io::Result<u64> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+        let mut available = 0u64;
+        // SAFETY: the path is NUL-terminated and the out pointers are valid or null
+        let ok = unsafe {
+            GetDiskFreeSpaceExW(
+                wide.as_ptr(),
+                &mut available,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(available)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn volume(id: &str, available_gb: u64) -> Volume {
+        Volume {
+            id: id.to_string(),
+            mount: format!("/mnt/{}", id),
+            available_bytes: Some(available_gb * GB),
+        }
+    }
+
+    #[test]
+    fn roles_on_one_volume_add_up() {
+        let nvme = volume("nvme", 40);
+        let roles = [
+            (Role::Inputs, nvme.clone()),
+            (Role::Scratch, nvme.clone()),
+            (Role::Output, nvme.clone()),
+        ];
+        // 8 GB of clips: 24 GB of frames in scratch, 24 + 6 + 2 GB in the output
+        let verdicts = verdicts(&roles, 8 * GB);
+        assert_eq!(verdicts.len(), 1);
+        assert_eq!(
+            verdicts[0].roles,
+            [Role::Inputs, Role::Scratch, Role::Output]
+        );
+        assert_eq!(verdicts[0].required_bytes, 56 * GB);
+        assert!(!verdicts[0].fits);
+
+        let preflight = DiskPreflight {
+            input_bytes: 8 * GB,
+            volumes: verdicts,
+        };
+        let err = check(&preflight).unwrap_err();
+        assert_eq!(err.code(), "not_enough_space");
+        assert_eq!(err.failure().message.key, "error.shared_volume_full");
+        assert!(err.to_string().contains("/mnt/nvme"));
+    }
+
+    #[test]
+    fn separate_volumes_each_hold_their_share() {
+        let roles = [
+            (Role::Inputs, volume("nvme", 40)),
+            (Role::Scratch, volume("hdd", 100)),
+            (Role::Output, volume("nvme", 40)),
+        ];
+        let preflight = DiskPreflight {
+            input_bytes: 8 * GB,
+            volumes: verdicts(&roles, 8 * GB),
+        };
+        assert!(preflight.volumes.iter().all(|v| v.fits));
+        check(&preflight).unwrap();
+
+        let roles = [(Role::Output, volume("usb", 1))];
+        let preflight = DiskPreflight {
+            input_bytes: GB,
+            volumes: verdicts(&roles, GB),
+        };
+        let err = check(&preflight).unwrap_err();
+        assert_eq!(err.failure().message.key, "error.volume_full");
+    }
+
+    #[test]
+    fn resolves_paths_that_do_not_exist_yet() {
+        let paths = TempPaths::new();
+        let existing = volume_of(paths.root()).unwrap();
+        let planned = volume_of(&paths.root().join("new").join("production")).unwrap();
+        assert_eq!((existing.id, existing.mount), (planned.id, planned.mount));
+        #[cfg(unix)]
+        assert!(planned.available_bytes.is_some());
+    }
+}
@@ -0,0 +1,573 @@
+//! Splat Downsampling
+//!
+//! Full-quality artifacts run to a gigabyte or two, too much to email or to
+//! load in a phone's browser. Downsampling keeps the splats that contribute
+//! most to the image, scored by opacity times the area of their largest
+//! cross-section, until the copy fits a splat count or a file size. The source
+//! is read twice in blocks, once to score and hash it and once to copy the
+//! kept vertices in their original order, so memory stays at a few bytes per
+//! splat. Ties are broken by position in the file, so a source and target
+//! always give the same copy. The copy is recorded as a derivative in the
+//! sidecar of the directory it is written to, naming its source by SHA-256.
+//! A run the app does not live to finish is left as a paused pending task.
+
+use crate::conversion::{self, MAX_HEADER_LEN};
+use crate::error::AppError;
+use crate::job_log;
+use crate::operations::{self, OperationHandle, OperationKind};
+use crate::path_policy::PathPolicy;
+use crate::pending_tasks::{self, PendingTask, TaskWork};
+use crate::scheduler::{self, Pool};
+use crate::sidecar::{self, Derivative, DerivativeKind, ProductionSource, Sidecar};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering as CmpOrdering;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, State};
+
+/// Vertices read at a time
+const BLOCK_VERTICES: usize = 16 * 1024;
+
+/// How small the copy must be
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownsampleTarget {
+    MaxSplats(usize),
+    MaxBytes(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownsampleStage {
+    Scoring,
+    Writing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownsampleProgress {
+    pub output_path: String,
+    pub stage: DownsampleStage,
+    pub done_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DownsampleResult {
+    pub output_path: String,
+    pub splat_count: usize,
+    pub source_splat_count: usize,
+    pub bytes: u64,
+    /// Share of the source's total score left out, from 0 to 1; a rough
+    /// guide to how much of the image the copy loses
+    pub estimated_quality_loss: f64,
+    pub sha256: String,
+    pub source_sha256: String,
+}
+
+/// Write a copy of a splat PLY with fewer splats and record it in the sidecar
+/// of its directory. Progress arrives as "downsample-progress" events.
+#[tauri::command]
+pub async fn downsample_splats(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    input: String,
+    output: String,
+    target: DownsampleTarget,
+) -> Result<DownsampleResult, AppError> {
+    policy.check_existing(&input)?;
+    policy.check_target(&output)?;
+    run(app, input, output, target, None).await
+}
+
+/// Downsample as a pending task; `resumed` is the paused task it runs again
+pub async fn run(
+    app: AppHandle,
+    input: String,
+    output: String,
+    target: DownsampleTarget,
+    resumed: Option<PendingTask>,
+) -> Result<DownsampleResult, AppError> {
+    let task = match resumed {
+        Some(task) => task,
+        None => PendingTask::start(TaskWork::Downsample {
+            input: input.clone(),
+            output: output.clone(),
+            target,
+        })?,
+    };
+    if let Err(e) = pending_tasks::begin(&app, &task) {
+        job_log::app_line(&app, &format!("Failed to record a pending task: {}", e));
+    }
+    let operation = OperationHandle::start(&app, OperationKind::Conversion, &output);
+
+    let (events, task_id) = (app.clone(), task.task_id.clone());
+    let result = scheduler::run_blocking(Pool::CpuHeavy, move || {
+        let app = events;
+        let (input, output_path) = (PathBuf::from(&input), PathBuf::from(&output));
+        let result = downsample(
+            &input,
+            &output_path,
+            target,
+            operation.token(),
+            &mut |sha256| pending_tasks::source_hashed(&app, &task_id, sha256),
+            &mut |stage, done_bytes, total_bytes| {
+                // Scoring and writing each read the source once
+                let done = match stage {
+                    DownsampleStage::Scoring => done_bytes,
+                    DownsampleStage::Writing => total_bytes + done_bytes,
+                };
+                operation.progress(done, Some(total_bytes * 2));
+                let progress = DownsampleProgress {
+                    output_path: output.clone(),
+                    stage,
+                    done_bytes,
+                    total_bytes,
+                };
+                app.emit("downsample-progress", &progress).ok();
+            },
+        )?;
+        record_derivative(&input, &output_path, &result)?;
+        Ok(result)
+    })
+    .await
+    .and_then(|result| result);
+    if let Err(e) = pending_tasks::finish(&app, &task.task_id) {
+        job_log::app_line(&app, &format!("Failed to clear a pending task: {}", e));
+    }
+    result
+}
+
+/// Cancel a running downsample; nothing is written
+#[tauri::command]
+pub async fn cancel_downsample() -> Result<(), AppError> {
+    operations::cancel_kind(OperationKind::Conversion);
+    Ok(())
+}
+
+/// How much a splat contributes to the image; `v` holds conversion::REQUIRED
+/// in order, with the log scales at 3..6 and the opacity logit at 10
+pub fn score(v: &[f32]) -> f32 {
+    let alpha = 1.0 / (1.0 + (-v[10]).exp());
+    let mut scales = [v[3], v[4], v[5]];
+    scales.sort_by(|a, b| a.total_cmp(b));
+    // The two largest axes span the cross-section most views see
+    let area = (scales[1] + scales[2]).exp();
+    let score = alpha * area;
+    if score.is_finite() {
+        score
+    } else {
+        0.0
+    }
+}
+
+/// Write the splats of `input` that score highest into `output`, as many as
+/// `target` allows. `source_hashed` hears the source's SHA-256 once scoring
+/// has read it, before anything is written, and stops the copy by failing.
+pub fn downsample(
+    input: &Path,
+    output: &Path,
+    target: DownsampleTarget,
+    cancel: &AtomicBool,
+    source_hashed: &mut dyn FnMut(&str) -> Result<(), AppError>,
+    progress: &mut dyn FnMut(DownsampleStage, u64, u64),
+) -> Result<DownsampleResult, AppError> {
+    if output == input {
+        return Err(AppError::InvalidInput(
+            "The downsampled copy cannot replace its source".to_string(),
+        ));
+    }
+    let mut file = File::open(input)?;
+    let total_bytes = file.metadata()?.len();
+    let mut header = vec![];
+    (&mut file).take(MAX_HEADER_LEN).read_to_end(&mut header)?;
+    let layout = conversion::parse_header(&header).map_err(AppError::InvalidInput)?;
+    let header = &header[..layout.body_offset];
+    let header_text = String::from_utf8_lossy(header);
+    let count = layout.vertex_count;
+    let body_bytes = count as u64 * layout.stride as u64;
+    if layout.body_offset as u64 + body_bytes > total_bytes {
+        return Err(AppError::InvalidInput("PLY file is truncated".to_string()));
+    }
+    if count > u32::MAX as usize {
+        return Err(AppError::InvalidInput(
+            "PLY file has too many splats".to_string(),
+        ));
+    }
+
+    let keep_count = match target {
+        DownsampleTarget::MaxSplats(max) => max.min(count),
+        DownsampleTarget::MaxBytes(max) => {
+            // The count in the header takes at most as many digits as the source's
+            let header_len = rewrite_header(&header_text, count).len() as u64;
+            (max.saturating_sub(header_len) / layout.stride as u64).min(count as u64) as usize
+        }
+    };
+    if keep_count == 0 {
+        return Err(AppError::InvalidInput(
+            "The target is too small to hold a single splat".to_string(),
+        ));
+    }
+
+    // Score every splat, hashing the whole file on the way
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(file);
+    let mut source_hash = Sha256::new();
+    let mut head = vec![0u8; layout.body_offset];
+    reader.read_exact(&mut head)?;
+    source_hash.update(&head);
+    let mut scores = Vec::with_capacity(count);
+    for_each_block(&mut reader, &layout, cancel, |block, done| {
+        source_hash.update(block);
+        for vertex in block.chunks_exact(layout.stride) {
+            scores.push(score(&layout.values(vertex)));
+        }
+        progress(DownsampleStage::Scoring, done, body_bytes);
+    })?;
+    // Elements after the vertices are not copied but are part of the checksum
+    io::copy(&mut reader, &mut source_hash)?;
+    let source_sha256 = hex(&source_hash.finalize());
+    source_hashed(&source_sha256)?;
+
+    // Highest score first, earlier splats first among equals
+    let rank = |a: &u32, b: &u32| -> CmpOrdering {
+        scores[*b as usize]
+            .total_cmp(&scores[*a as usize])
+            .then(a.cmp(b))
+    };
+    let mut order: Vec<u32> = (0..count as u32).collect();
+    if keep_count < count {
+        order.select_nth_unstable_by(keep_count - 1, rank);
+    }
+    let mut keep = vec![false; count];
+    for &i in &order[..keep_count] {
+        keep[i as usize] = true;
+    }
+    drop(order);
+    let total_score: f64 = scores.iter().map(|&s| s as f64).sum();
+    let kept_score: f64 = scores
+        .iter()
+        .zip(&keep)
+        .filter(|(_, kept)| **kept)
+        .map(|(score, _)| *score as f64)
+        .sum();
+    let estimated_quality_loss = if total_score > 0.0 {
+        (1.0 - kept_score / total_score).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    drop(scores);
+
+    let partial = pending_tasks::temp_path(output);
+    let written = write_copy(
+        input,
+        &partial,
+        &layout,
+        &header_text,
+        &keep,
+        keep_count,
+        cancel,
+        &mut |done| progress(DownsampleStage::Writing, done, body_bytes),
+    )
+    .and_then(|written| {
+        std::fs::rename(&partial, output)?;
+        Ok(written)
+    });
+    let (bytes, sha256) = match written {
+        Ok(written) => written,
+        Err(e) => {
+            std::fs::remove_file(&partial).ok();
+            return Err(e);
+        }
+    };
+
+    Ok(DownsampleResult {
+        output_path: output.to_string_lossy().to_string(),
+        splat_count: keep_count,
+        source_splat_count: count,
+        bytes,
+        estimated_quality_loss,
+        sha256,
+        source_sha256,
+    })
+}
+
+/// Copy the header with the new count and the kept vertices; returns the
+/// copy's size and SHA-256
+#[allow(clippy::too_many_arguments)]
+fn write_copy(
+    input: &Path,
+    partial: &Path,
+    layout: &conversion::Layout,
+    header_text: &str,
+    keep: &[bool],
+    keep_count: usize,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(u64),
+) -> Result<(u64, String), AppError> {
+    let mut reader = BufReader::new(File::open(input)?);
+    reader.seek(SeekFrom::Start(layout.body_offset as u64))?;
+    let mut writer = BufWriter::new(File::create(partial)?);
+    let mut hash = Sha256::new();
+    let mut bytes = 0u64;
+    let mut write = |data: &[u8], writer: &mut BufWriter<File>| -> io::Result<()> {
+        hash.update(data);
+        bytes += data.len() as u64;
+        writer.write_all(data)
+    };
+
+    write(
+        rewrite_header(header_text, keep_count).as_bytes(),
+        &mut writer,
+    )?;
+    let mut index = 0;
+    let mut failed = None;
+    let copied = for_each_block(&mut reader, layout, cancel, |block, done| {
+        for vertex in block.chunks_exact(layout.stride) {
+            if keep[index] && failed.is_none() {
+                failed = write(vertex, &mut writer).err();
+            }
+            index += 1;
+        }
+        progress(done);
+    });
+    if let Some(e) = failed {
+        return Err(e.into());
+    }
+    copied?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    Ok((bytes, hex(&hash.finalize())))
+}
+
+/// Hand the vertex data to `each` a block at a time, with the bytes done so far
+fn for_each_block(
+    reader: &mut impl Read,
+    layout: &conversion::Layout,
+    cancel: &AtomicBool,
+    mut each: impl FnMut(&[u8], u64),
+) -> Result<(), AppError> {
+    let mut block = vec![0u8; BLOCK_VERTICES * layout.stride];
+    let mut left = layout.vertex_count;
+    let mut done = 0u64;
+    while left > 0 {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(AppError::Cancelled);
+        }
+        let vertices = left.min(BLOCK_VERTICES);
+        let block = &mut block[..vertices * layout.stride];
+        reader.read_exact(block)?;
+        done += block.len() as u64;
+        each(block, done);
+        left -= vertices;
+    }
+    Ok(())
+}
+
+/// The header with a new vertex count and without the elements after the
+/// vertices, whose data is not copied
+fn rewrite_header(header: &str, vertex_count: usize) -> String {
+    let mut out = String::new();
+    let mut skipping = false;
+    for line in header.lines() {
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["end_header"] => break,
+            ["element", "vertex", _] => {
+                out.push_str(&format!("element vertex {}\n", vertex_count));
+                continue;
+            }
+            ["element", ..] => skipping = true,
+            _ => {}
+        }
+        if !skipping {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out.push_str("end_header\n");
+    out
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Name the copy in the sidecar of its directory. A directory without one,
+/// such as a folder for sharing, gets a sidecar of the source's production
+/// with the copy as its artifact.
+fn record_derivative(input: &Path, output: &Path, result: &DownsampleResult) -> Result<(), String> {
+    let dir = output.parent().unwrap_or(Path::new("."));
+    let derivative = Derivative {
+        path: result.output_path.clone(),
+        kind: DerivativeKind::Downsampled,
+        sha256: result.sha256.clone(),
+        source_path: input.to_string_lossy().to_string(),
+        source_sha256: result.source_sha256.clone(),
+        created_at: job_log::unix_timestamp(),
+    };
+    let mut meta = match sidecar::read(dir)? {
+        Some(meta) => meta,
+        None => {
+            let source = input.parent().and_then(|d| sidecar::read(d).ok().flatten());
+            Sidecar {
+                production_dir: dir.to_string_lossy().to_string(),
+                artifact_path: result.output_path.clone(),
+                artifact_sha256: Some(result.sha256.clone()),
+                created_at: derivative.created_at,
+                archive: None,
+                derivatives: vec![],
+                source: ProductionSource::Job,
+                imported_from: None,
+                ..source.unwrap_or_default()
+            }
+        }
+    };
+    meta.derivatives.retain(|d| d.path != derivative.path);
+    meta.derivatives.push(derivative);
+    sidecar::write(dir, &meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::TempPaths;
+
+    fn splat(x: f32, log_scale: f32, opacity: f32) -> [f32; 14] {
+        let s = log_scale;
+        [
+            x, 0.0, 0.0, s, s, s, 1.0, 0.0, 0.0, 0.0, opacity, 0.0, 0.0, 0.0,
+        ]
+    }
+
+    #[test]
+    fn keeps_the_most_visible_splats_in_file_order() {
+        let paths = TempPaths::new();
+        let source = paths.root().join("production");
+        std::fs::create_dir_all(&source).unwrap();
+        let input = source.join("output.ply");
+        let splats = [
+            splat(0.0, -2.0, 0.0),
+            splat(1.0, 1.0, 3.0),
+            splat(2.0, 0.0, -6.0),
+            splat(3.0, 0.5, 2.0),
+            // Ties with the one before it, and loses by coming later
+            splat(4.0, 0.5, 2.0),
+        ];
+        std::fs::write(&input, sample_ply(&splats)).unwrap();
+        sidecar::write(
+            &source,
+            &Sidecar {
+                job_id: "job-1".to_string(),
+                preset: "high".to_string(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let shared = paths.root().join("shared");
+        std::fs::create_dir_all(&shared).unwrap();
+        let output = shared.join("light.ply");
+        let never = AtomicBool::new(false);
+
+        let mut heard = None;
+        let result = downsample(
+            &input,
+            &output,
+            DownsampleTarget::MaxSplats(2),
+            &never,
+            &mut |sha256| {
+                heard = Some(sha256.to_string());
+                Ok(())
+            },
+            &mut |_, _, _| {},
+        )
+        .unwrap();
+        record_derivative(&input, &output, &result).unwrap();
+
+        let copy = std::fs::read(&output).unwrap();
+        let layout = conversion::parse_header(&copy).unwrap();
+        let xs: Vec<f32> = copy[layout.body_offset..]
+            .chunks_exact(layout.stride)
+            .map(|v| layout.values(v)[0])
+            .collect();
+        assert_eq!(xs, [1.0, 3.0]);
+        assert_eq!((result.splat_count, result.source_splat_count), (2, 5));
+        assert_eq!(result.bytes, copy.len() as u64);
+        assert!(result.estimated_quality_loss > 0.0 && result.estimated_quality_loss < 0.5);
+        assert_eq!(
+            result.source_sha256,
+            hex(&Sha256::digest(std::fs::read(&input).unwrap()))
+        );
+        assert_eq!(result.sha256, hex(&Sha256::digest(&copy)));
+        assert_eq!(heard.as_ref(), Some(&result.source_sha256));
+
+        let meta = sidecar::read(&shared).unwrap().unwrap();
+        assert_eq!(
+            (meta.job_id.as_str(), meta.preset.as_str()),
+            ("job-1", "high")
+        );
+        assert_eq!(meta.artifact_path, result.output_path);
+        assert_eq!(meta.derivatives[0].source_sha256, result.source_sha256);
+
+        // As large as the header and three splats allow
+        let budget = copy.len() as u64 + layout.stride as u64 + 1;
+        let again = downsample(
+            &input,
+            &output,
+            DownsampleTarget::MaxBytes(budget),
+            &never,
+            &mut |_| Ok(()),
+            &mut |_, _, _| {},
+        )
+        .unwrap();
+        assert_eq!(again.splat_count, 3);
+        assert!(again.bytes <= budget);
+    }
+
+    #[test]
+    fn cancelling_leaves_nothing_behind() {
+        let paths = TempPaths::new();
+        let input = paths.root().join("output.ply");
+        std::fs::write(&input, sample_ply(&[splat(0.0, 0.0, 0.0)])).unwrap();
+        let output = paths.root().join("light.ply");
+
+        let cancelled = AtomicBool::new(true);
+        let err = downsample(
+            &input,
+            &output,
+            DownsampleTarget::MaxSplats(1),
+            &cancelled,
+            &mut |_| Ok(()),
+            &mut |_, _, _| {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, AppError::Cancelled));
+        assert!(!output.exists());
+        assert!(!paths.root().join("light.ply.partial").exists());
+    }
+
+    #[test]
+    fn a_refused_source_is_not_copied() {
+        let paths = TempPaths::new();
+        let input = paths.root().join("output.ply");
+        std::fs::write(&input, sample_ply(&[splat(0.0, 0.0, 0.0)])).unwrap();
+        let output = paths.root().join("light.ply");
+
+        let never = AtomicBool::new(false);
+        let mut writing = false;
+        let refused = downsample(
+            &input,
+            &output,
+            DownsampleTarget::MaxSplats(1),
+            &never,
+            &mut |_| Err(AppError::InvalidInput("changed".to_string())),
+            &mut |stage, _, _| writing |= stage == DownsampleStage::Writing,
+        );
+        assert!(matches!(refused, Err(AppError::InvalidInput(_))));
+        assert!(!writing);
+        assert!(!output.exists());
+        assert!(!paths.root().join("light.ply.partial").exists());
+    }
+}
@@ -0,0 +1,595 @@
+//! Duplicate Artifacts
+//!
+//! Finds the same splat saved in several places across library roots. The
+//! roots are scanned as scan_productions does, through its cache, and the
+//! artifacts grouped by checksum: the one a sidecar or history record stored,
+//! or one computed now. Only files that share their size with another are
+//! hashed, as nothing else can be a copy. Several paths to one file, such as
+//! a symlink or a copy already replaced with a link, count as one.
+//!
+//! resolve_duplicates keeps one copy and moves the others to the trash, or
+//! replaces them with links to it. Either goes through the undo journal. It
+//! is given paths, not a group: one that turns out to be the kept file is left
+//! alone, and every copy is hashed again first, so a file that changed since
+//! the scan is never taken for a copy.
+
+use crate::error::AppError;
+use crate::hashing::{self, Reuse};
+use crate::history;
+use crate::integrity;
+use crate::jobs;
+use crate::library::{self, LibraryEntry};
+use crate::messages::Message;
+use crate::path_policy::PathPolicy;
+use crate::scheduler::{self, Pool};
+use crate::settings::SettingsStore;
+use crate::sidecar;
+use crate::undo::{self, Restore};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, State};
+
+/// Suffix of a link made next to a copy before it takes the copy's place
+const LINK_SUFFIX: &str = ".gvlink";
+
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateCopy {
+    pub artifact_path: String,
+    /// The production directory, or the directory of a loose splat
+    pub production_path: String,
+    /// A recent production is this copy's directory
+    pub in_recents: bool,
+    /// A history record names this copy as its artifact
+    pub in_history: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub sha256: String,
+    /// Of each copy
+    pub size: u64,
+    /// Ordered by path
+    pub copies: Vec<DuplicateCopy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolveAction {
+    /// Move the other copies to the trash
+    Trash,
+    /// Replace the other copies with links to the kept one: a hard link on
+    /// the same volume, a symbolic link elsewhere
+    Link,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResolvedDuplicates {
+    /// The copies trashed or replaced
+    pub resolved: Vec<String>,
+    /// Space given back once the trash is emptied
+    pub reclaimable_bytes: u64,
+}
+
+/// An artifact the scan found
+#[derive(Debug, Clone)]
+struct Candidate {
+    artifact: PathBuf,
+    production: String,
+    size: u64,
+    /// Stored by a sidecar or history record, lowercased
+    sha256: Option<String>,
+}
+
+/// Find artifacts stored more than once under `roots`, largest groups of
+/// wasted space first. Progress arrives as "library-scan-progress" while
+/// scanning, then as "hash-progress" for each file hashed.
+#[tauri::command]
+pub async fn find_duplicate_artifacts(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    roots: Vec<String>,
+) -> Result<Vec<DuplicateGroup>, AppError> {
+    for root in &roots {
+        policy.check_existing(root)?;
+    }
+    CANCEL.store(false, Ordering::SeqCst);
+
+    let emitter = app.clone();
+    let entries = scheduler::run_blocking(Pool::Io, move || {
+        let mut entries = vec![];
+        for root in &roots {
+            entries.extend(library::scan_root(&emitter, root, &CANCEL, &mut |_, _| {})?.entries);
+        }
+        Ok::<_, AppError>(entries)
+    })
+    .await??;
+
+    let records = history::load(&app)?;
+    let stored: HashMap<PathBuf, String> = records
+        .iter()
+        .filter_map(|r| {
+            Some((
+                canonical(r.artifact_path.as_deref()?)?,
+                r.artifact_sha256.clone()?,
+            ))
+        })
+        .collect();
+    let mut candidates = candidates(&entries, &stored);
+    hash_candidates(&mut candidates, &CANCEL, &mut |path, hashed, total| {
+        integrity::emit_progress(&app, path, hashed, total)
+    })
+    .await?;
+
+    let recents: Vec<String> = app
+        .settings()
+        .recent_productions
+        .into_iter()
+        .map(|r| r.path)
+        .collect();
+    let referenced: Vec<PathBuf> = records
+        .iter()
+        .filter_map(|r| canonical(r.artifact_path.as_deref()?))
+        .collect();
+    Ok(group(candidates, &recents, &referenced))
+}
+
+/// Cancel a running find_duplicate_artifacts
+#[tauri::command]
+pub async fn cancel_duplicate_scan() -> Result<(), AppError> {
+    CANCEL.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Keep the copy at `keep_path` and trash or link the other `copies`. The
+/// group is made up again here rather than taken from the scan: any path that
+/// is the kept file itself, through a link or given twice, is left alone, and
+/// every other one must still hash as the kept copy does.
+#[tauri::command]
+pub async fn resolve_duplicates(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    copies: Vec<String>,
+    keep_path: String,
+    action: ResolveAction,
+) -> Result<ResolvedDuplicates, AppError> {
+    policy.check_existing(&keep_path)?;
+    for copy in &copies {
+        policy.check_existing(copy)?;
+    }
+    let keep = Path::new(&keep_path).canonicalize()?;
+    let size = std::fs::metadata(&keep)?.len();
+    // Canonical, as the trash records them
+    let targets = others(&keep, &copies)?;
+    if targets.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "No other copy of {} was given",
+            keep_path
+        )));
+    }
+    for target in &targets {
+        let dir = target.parent().unwrap_or(target);
+        if jobs::is_targeting(dir) {
+            return Err(AppError::ProductionBusy(dir.to_string_lossy().to_string()));
+        }
+    }
+
+    CANCEL.store(false, Ordering::SeqCst);
+    let mut kept_sha256 = None;
+    for path in std::iter::once(&keep).chain(&targets) {
+        let shown = path.to_string_lossy().to_string();
+        let sha256 = hashing::sha256(path, Reuse::Fresh, &CANCEL, &mut |hashed, total| {
+            integrity::emit_progress(&app, &shown, hashed, total)
+        })
+        .await?;
+        let kept = kept_sha256.get_or_insert_with(|| sha256.clone());
+        if std::fs::metadata(path)?.len() != size || !sha256.eq_ignore_ascii_case(kept) {
+            return Err(AppError::InvalidInput(format!(
+                "{} is no longer a copy of {}; look for duplicates again",
+                shown, keep_path
+            )));
+        }
+    }
+
+    let name = keep
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let (key, artifacts, links) = match action {
+        ResolveAction::Trash => {
+            // Found before the copies are gone, as the records may not name them canonically
+            let mut changed: Vec<_> = history::load(&app)?
+                .into_iter()
+                .filter(|r| {
+                    r.artifact_path
+                        .as_deref()
+                        .and_then(canonical)
+                        .is_some_and(|p| targets.contains(&p))
+                })
+                .collect();
+            trash(&targets)?;
+            let mut artifacts = vec![];
+            for record in &mut changed {
+                let path = record.artifact_path.take().unwrap_or_default();
+                artifacts.push((record.job_id.clone(), path));
+            }
+            history::amend(&app, &changed)?;
+            ("undo.trash_duplicates", artifacts, vec![])
+        }
+        ResolveAction::Link => {
+            replace_with_links(&keep, &targets, trash)?;
+            ("undo.link_duplicates", vec![], targets.clone())
+        }
+    };
+
+    // Nothing to offer when the trash cannot give the files back
+    let trash_ids = undo::trashed::find(&targets);
+    if trash_ids.len() == targets.len() {
+        let description = Message::new(key)
+            .with("count", targets.len())
+            .with("name", name);
+        let restore = Restore::Trashed {
+            trash_ids,
+            recents: vec![],
+            artifacts,
+            links,
+        };
+        undo::record(&app, description, restore);
+    }
+
+    Ok(ResolvedDuplicates {
+        resolved: targets
+            .iter()
+            .map(|t| t.to_string_lossy().to_string())
+            .collect(),
+        reclaimable_bytes: size * targets.len() as u64,
+    })
+}
+
+/// The files of `copies` other than `keep`, canonical and each once
+fn others(keep: &Path, copies: &[String]) -> Result<Vec<PathBuf>, AppError> {
+    let mut seen = vec![identity(keep, &std::fs::metadata(keep)?)];
+    let mut others = vec![];
+    for copy in copies {
+        let path = Path::new(copy).canonicalize()?;
+        let identity = identity(&path, &std::fs::metadata(&path)?);
+        if !seen.contains(&identity) {
+            seen.push(identity);
+            others.push(path);
+        }
+    }
+    Ok(others)
+}
+
+/// The artifacts of `entries`, each file once, with the checksums stored for them
+fn candidates(entries: &[LibraryEntry], stored: &HashMap<PathBuf, String>) -> Vec<Candidate> {
+    let mut seen = vec![];
+    let mut candidates = vec![];
+    for entry in entries {
+        let Some(artifact) = entry.artifact_path.as_deref().and_then(canonical) else {
+            continue;
+        };
+        let Ok(metadata) = std::fs::metadata(&artifact) else {
+            continue;
+        };
+        let identity = identity(&artifact, &metadata);
+        if seen.contains(&identity) {
+            continue;
+        }
+        seen.push(identity);
+
+        let from_sidecar = sidecar::read(Path::new(&entry.path))
+            .ok()
+            .flatten()
+            .filter(|s| canonical(&s.artifact_path).as_ref() == Some(&artifact))
+            .and_then(|s| s.artifact_sha256);
+        candidates.push(Candidate {
+            sha256: from_sidecar
+                .or_else(|| stored.get(&artifact).cloned())
+                .map(|h| h.to_lowercase()),
+            artifact,
+            production: entry.path.clone(),
+            size: metadata.len(),
+        });
+    }
+    candidates
+}
+
+/// Hash the candidates without a stored checksum that share their size with another
+async fn hash_candidates(
+    candidates: &mut [Candidate],
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(&str, u64, u64) + Send),
+) -> Result<(), AppError> {
+    let mut sizes: HashMap<u64, usize> = HashMap::new();
+    for candidate in candidates.iter() {
+        *sizes.entry(candidate.size).or_insert(0) += 1;
+    }
+    for candidate in candidates.iter_mut() {
+        if candidate.sha256.is_some() || sizes[&candidate.size] < 2 {
+            continue;
+        }
+        let path = candidate.artifact.to_string_lossy().to_string();
+        let sha256 = hashing::sha256(
+            &candidate.artifact,
+            Reuse::Cached,
+            cancel,
+            &mut |hashed, total| progress(&path, hashed, total),
+        )
+        .await?;
+        candidate.sha256 = Some(sha256);
+    }
+    Ok(())
+}
+
+/// Candidates sharing size and checksum, marked with what refers to them
+fn group(
+    candidates: Vec<Candidate>,
+    recents: &[String],
+    referenced: &[PathBuf],
+) -> Vec<DuplicateGroup> {
+    let mut groups: BTreeMap<(u64, String), Vec<DuplicateCopy>> = BTreeMap::new();
+    for candidate in candidates {
+        let Some(sha256) = candidate.sha256 else {
+            continue;
+        };
+        groups
+            .entry((candidate.size, sha256))
+            .or_default()
+            .push(DuplicateCopy {
+                in_recents: recents.contains(&candidate.production),
+                in_history: referenced.contains(&candidate.artifact),
+                artifact_path: candidate.artifact.to_string_lossy().to_string(),
+                production_path: candidate.production,
+            });
+    }
+    let mut groups: Vec<DuplicateGroup> = groups
+        .into_iter()
+        .filter(|(_, copies)| copies.len() > 1)
+        .map(|((size, sha256), mut copies)| {
+            copies.sort_by(|a, b| a.artifact_path.cmp(&b.artifact_path));
+            DuplicateGroup {
+                sha256,
+                size,
+                copies,
+            }
+        })
+        .collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.size * (g.copies.len() as u64 - 1)));
+    groups
+}
+
+fn trash(targets: &[PathBuf]) -> Result<(), AppError> {
+    trash::delete_all(targets).map_err(|e| AppError::Io(format!("Failed to move to trash: {}", e)))
+}
+
+/// Link each target to `keep`, trashing what was there. The links are made
+/// next to the targets first, so nothing is trashed when one cannot be made.
+fn replace_with_links(
+    keep: &Path,
+    targets: &[PathBuf],
+    trash: impl FnOnce(&[PathBuf]) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let staged: Vec<PathBuf> = targets
+        .iter()
+        .map(|target| {
+            let mut staged = target.as_os_str().to_owned();
+            staged.push(LINK_SUFFIX);
+            PathBuf::from(staged)
+        })
+        .collect();
+    let remove_staged = || {
+        for path in &staged {
+            std::fs::remove_file(path).ok();
+        }
+    };
+    for path in &staged {
+        if let Err(e) = link(keep, path) {
+            remove_staged();
+            return Err(AppError::Io(format!(
+                "Failed to link {} to {}: {}",
+                path.display(),
+                keep.display(),
+                e
+            )));
+        }
+    }
+    if let Err(e) = trash(targets) {
+        remove_staged();
+        return Err(e);
+    }
+    for (path, target) in staged.iter().zip(targets) {
+        std::fs::rename(path, target)?;
+    }
+    Ok(())
+}
+
+/// A hard link, or a symbolic one where the volumes differ
+fn link(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::fs::hard_link(original, link).or_else(|_| {
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(original, link);
+        #[cfg(windows)]
+        return std::os::windows::fs::symlink_file(original, link);
+    })
+}
+
+fn canonical(path: &str) -> Option<PathBuf> {
+    Path::new(path).canonicalize().ok()
+}
+
+/// What tells two paths to one file apart from two copies
+#[cfg(unix)]
+fn identity(_path: &Path, metadata: &std::fs::Metadata) -> (u64, u64, PathBuf) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino(), PathBuf::new())
+}
+
+/// The volume and file index, which hard links share
+#[cfg(windows)]
+fn identity(path: &Path, _metadata: &std::fs::Metadata) -> (u64, u64, PathBuf) {
+    use std::os::windows::io::AsRawHandle;
+    use windows_sys::Win32::Storage::FileSystem::{
+        GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION,
+    };
+
+    let Ok(file) = std::fs::File::open(path) else {
+        return (0, 0, path.to_path_buf());
+    };
+    // SAFETY: BY_HANDLE_FILE_INFORMATION is plain data, for which all zeroes
+    // is valid, and the handle stays open for the call
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { std::mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle(), &mut info) } == 0 {
+        return (0, 0, path.to_path_buf());
+    }
+    let index = (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow);
+    (u64::from(info.dwVolumeSerialNumber), index, PathBuf::new())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn identity(path: &Path, _metadata: &std::fs::Metadata) -> (u64, u64, PathBuf) {
+    (0, 0, path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::library::LibraryKind;
+    use crate::platform::testing::TempPaths;
+    use crate::sidecar::Sidecar;
+
+    fn entry(dir: &Path, artifact: &Path) -> LibraryEntry {
+        LibraryEntry {
+            kind: LibraryKind::Splat,
+            name: String::new(),
+            path: dir.to_string_lossy().to_string(),
+            artifact_path: Some(artifact.to_string_lossy().to_string()),
+            artifact_size: None,
+            splat_count: None,
+            created_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn copies_are_grouped_by_checksum_and_links_count_once() {
+        let paths = TempPaths::new();
+        let splat = sample_ply(&[[0.0; 14]; 4]);
+        let mut other = splat.clone();
+        *other.last_mut().unwrap() = 1;
+        let mut entries = vec![];
+        let mut artifacts = vec![];
+        for (name, bytes) in [("harbour", &splat), ("copy", &splat), ("edited", &other)] {
+            let dir = paths.root().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            let artifact = dir.join("splat.ply");
+            std::fs::write(&artifact, bytes).unwrap();
+            entries.push(entry(&dir, &artifact));
+            artifacts.push(artifact.canonicalize().unwrap());
+        }
+        // The checksum a job stored is trusted rather than computed again
+        let harbour = paths.root().join("harbour");
+        let sidecar = Sidecar {
+            artifact_path: artifacts[0].to_string_lossy().to_string(),
+            artifact_sha256: Some("ABC".to_string()),
+            ..Sidecar::default()
+        };
+        sidecar::write(&harbour, &sidecar).unwrap();
+        let linked = paths.root().join("linked");
+        std::fs::create_dir_all(&linked).unwrap();
+        std::fs::hard_link(&artifacts[1], linked.join("splat.ply")).unwrap();
+        entries.push(entry(&linked, &linked.join("splat.ply")));
+
+        let mut candidates = candidates(&entries, &HashMap::new());
+        assert_eq!(candidates.len(), 3);
+        assert_eq!(candidates[0].sha256.as_deref(), Some("abc"));
+        let mut hashed = vec![];
+        hash_candidates(
+            &mut candidates,
+            &AtomicBool::new(false),
+            &mut |path, _, _| {
+                if !hashed.iter().any(|p| p == path) {
+                    hashed.push(path.to_string());
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(hashed.len(), 2);
+        assert_eq!(group(candidates.clone(), &[], &[]), vec![]);
+
+        let actual = candidates[1].sha256.clone();
+        candidates[0].sha256 = actual;
+        let recents = [harbour.to_string_lossy().to_string()];
+        let groups = group(candidates, &recents, &artifacts[1..2]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].size, splat.len() as u64);
+        let copies: Vec<(bool, bool)> = groups[0]
+            .copies
+            .iter()
+            .map(|c| (c.in_recents, c.in_history))
+            .collect();
+        // copy sorts before harbour
+        assert_eq!(copies, [(false, true), (true, false)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_kept_file_is_never_among_the_others() {
+        let paths = TempPaths::new();
+        let keep = paths.root().join("keep.ply");
+        let copy = paths.root().join("copy.ply");
+        std::fs::write(&keep, b"splat").unwrap();
+        std::fs::write(&copy, b"splat").unwrap();
+        let symlink = paths.root().join("symlink.ply");
+        std::os::unix::fs::symlink(&keep, &symlink).unwrap();
+        let hard_link = paths.root().join("hard-link.ply");
+        std::fs::hard_link(&keep, &hard_link).unwrap();
+
+        let given =
+            [&keep, &symlink, &hard_link, &copy, &copy].map(|p| p.to_string_lossy().to_string());
+        let keep = keep.canonicalize().unwrap();
+        assert_eq!(
+            others(&keep, &given).unwrap(),
+            [copy.canonicalize().unwrap()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copies_are_replaced_with_links_to_the_one_kept() {
+        use std::os::unix::fs::MetadataExt;
+        let paths = TempPaths::new();
+        let keep = paths.root().join("keep.ply");
+        let copy = paths.root().join("copy.ply");
+        std::fs::write(&keep, b"splat").unwrap();
+        std::fs::write(&copy, b"splat").unwrap();
+        let copies = [copy.clone()];
+        let mut staged = copy.as_os_str().to_owned();
+        staged.push(LINK_SUFFIX);
+
+        // A copy the trash refuses stays as it was
+        let refused = replace_with_links(&keep, &copies, |_| Err(AppError::Io("no trash".into())));
+        assert!(refused.is_err());
+        assert!(!PathBuf::from(&staged).exists());
+        assert_ne!(
+            copy.metadata().unwrap().ino(),
+            keep.metadata().unwrap().ino()
+        );
+
+        replace_with_links(&keep, &copies, |targets| {
+            targets.iter().try_for_each(std::fs::remove_file)?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            copy.metadata().unwrap().ino(),
+            keep.metadata().unwrap().ino()
+        );
+        assert!(!PathBuf::from(&staged).exists());
+    }
+}
@@ -0,0 +1,317 @@
+//! Application Errors
+//!
+//! Typed errors returned by commands. They serialize as `{ code, message }` so the
+//! frontend can branch on the code and show the message, along with the message
+//! key, parameters and raw detail it needs to show a translation instead.
+//! Validation errors also carry the failing `fields`.
+
+use crate::input_readiness::NotReadyInput;
+use crate::messages::{Failure, Message};
+use crate::output_location::NotWritable;
+use crate::spawn_diagnosis::SpawnDiagnosis;
+use crate::validation::FieldError;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AppError {
+    /// A job is running against the production
+    ProductionBusy(String),
+    NotFound(String),
+    InvalidInput(String),
+    /// Fields of a command input that failed validation
+    InvalidArguments(Vec<FieldError>),
+    /// The webview referred to a path outside what the path policy allows
+    PathNotAllowed(String),
+    /// The user cancelled the operation
+    Cancelled,
+    /// Offline mode is on, so nothing is sent over the network
+    OfflineMode,
+    /// Refused while a processing job runs
+    JobRunning,
+    /// The production's intermediates are archived and must be restored first
+    NeedsRestore(String),
+    /// A volume cannot hold what a job would write to it
+    NotEnoughSpace(Message),
+    /// An output directory holds or sits inside an input or app_data
+    UnsafeOutputLocation(Message),
+    /// An output directory refuses writes, e.g. because it is read-only or
+    /// belongs to another user
+    OutputNotWritable(NotWritable),
+    /// The GPU has less VRAM than the job's preset needs and the request does
+    /// not override the policy
+    InsufficientVram(Message),
+    /// The output volume disconnected mid-run and did not come back in time;
+    /// says how far the job had got
+    OutputVolumeLost(Message),
+    /// Too few images registered, so the job stopped before training; says
+    /// how many did and which clips had none
+    AbortedLowRegistration(Message),
+    /// The CLI could not be started, for a reason found by inspecting it
+    SpawnDiagnosis(SpawnDiagnosis),
+    /// An identical job is already queued or running; holds its entry id
+    AlreadyQueued(String),
+    /// What open_production was given is gone; holds where it may be now
+    TargetMissing {
+        target: String,
+        suggestion: Option<String>,
+    },
+    /// A job wrote several equally new artifacts; holds their paths for the
+    /// user to choose from
+    AmbiguousArtifact(Vec<String>),
+    /// The host is outside the support matrix; says how
+    UnsupportedPlatform(Message),
+    /// Inputs are empty or still being copied; holds each with why
+    NotReady(Vec<NotReadyInput>),
+    /// A processing job failed
+    Job(Failure),
+    Io(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::ProductionBusy(_) => "production_busy",
+            AppError::NotFound(_) => "not_found",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::InvalidArguments(_) => "invalid_arguments",
+            AppError::PathNotAllowed(_) => "path_not_allowed",
+            AppError::Cancelled => "cancelled",
+            AppError::OfflineMode => "offline_mode",
+            AppError::JobRunning => "job_running",
+            AppError::NeedsRestore(_) => "needs_restore",
+            AppError::NotEnoughSpace(_) => "not_enough_space",
+            AppError::UnsafeOutputLocation(_) => "unsafe_output_location",
+            AppError::OutputNotWritable(_) => "output_not_writable",
+            AppError::InsufficientVram(_) => "insufficient_vram",
+            AppError::OutputVolumeLost(_) => "output_volume_lost",
+            AppError::AbortedLowRegistration(_) => "aborted_low_registration",
+            AppError::SpawnDiagnosis(_) => "spawn_diagnosis",
+            AppError::AlreadyQueued(_) => "already_queued",
+            AppError::TargetMissing { .. } => "target_missing",
+            AppError::AmbiguousArtifact(_) => "ambiguous_artifact",
+            AppError::UnsupportedPlatform(_) => "unsupported_platform",
+            AppError::NotReady(_) => "not_ready",
+            AppError::Job(_) => "job_failed",
+            AppError::Io(_) => "io",
+        }
+    }
+
+    /// The error as a translatable message
+    pub fn failure(&self) -> Failure {
+        match self {
+            AppError::ProductionBusy(path) => Message::new("error.production_busy")
+                .with("path", path)
+                .into(),
+            AppError::NotFound(what) => Message::new("error.not_found").with("what", what).into(),
+            AppError::InvalidInput(message) => Message::new("error.invalid_input")
+                .into_failure()
+                .raw(message.clone()),
+            AppError::InvalidArguments(errors) => Message::new("error.invalid_arguments")
+                .with("count", errors.len())
+                .into(),
+            AppError::PathNotAllowed(path) => Message::new("error.path_not_allowed")
+                .with("path", path)
+                .into(),
+            AppError::Cancelled => Message::new("error.cancelled").into(),
+            AppError::OfflineMode => Message::new("error.offline_mode").into(),
+            AppError::JobRunning => Message::new("error.job_running").into(),
+            AppError::NeedsRestore(path) => Message::new("error.needs_restore")
+                .with("path", path)
+                .into(),
+            AppError::NotEnoughSpace(message)
+            | AppError::UnsafeOutputLocation(message)
+            | AppError::InsufficientVram(message)
+            | AppError::OutputVolumeLost(message)
+            | AppError::AbortedLowRegistration(message)
+            | AppError::UnsupportedPlatform(message) => message.clone().into(),
+            AppError::OutputNotWritable(refusal) => refusal
+                .message
+                .clone()
+                .into_failure()
+                .raw(refusal.detail.clone()),
+            AppError::SpawnDiagnosis(diagnosis) => diagnosis.failure(),
+            AppError::AlreadyQueued(entry_id) => Message::new("job.already_queued")
+                .with("entry_id", entry_id)
+                .into(),
+            AppError::TargetMissing {
+                target,
+                suggestion: None,
+            } => Message::new("error.target_missing")
+                .with("target", target)
+                .into(),
+            AppError::TargetMissing {
+                target,
+                suggestion: Some(suggestion),
+            } => Message::new("error.target_missing_suggestion")
+                .with("target", target)
+                .with("suggestion", suggestion)
+                .into(),
+            AppError::AmbiguousArtifact(paths) => ambiguous_artifact(paths).into(),
+            AppError::NotReady(inputs) => {
+                let paths: Vec<&str> = inputs.iter().map(|i| i.path.as_str()).collect();
+                Message::new("error.not_ready")
+                    .with("count", inputs.len())
+                    .with("paths", paths.join("\n"))
+                    .into()
+            }
+            AppError::Job(failure) => failure.clone(),
+            AppError::Io(message) => Message::new("error.io").into_failure().raw(message.clone()),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::ProductionBusy(path) => {
+                write!(f, "Production has an active job: {}", path)
+            }
+            AppError::NotFound(what) => write!(f, "Not found: {}", what),
+            AppError::InvalidArguments(errors) => {
+                let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+                write!(f, "Invalid arguments: {}", errors.join("; "))
+            }
+            AppError::PathNotAllowed(path) => write!(f, "Path is not allowed: {}", path),
+            AppError::Cancelled => write!(f, "Cancelled"),
+            AppError::OfflineMode => write!(f, "Offline mode is on"),
+            AppError::JobRunning => write!(f, "Not while a job is running"),
+            AppError::NeedsRestore(path) => {
+                write!(
+                    f,
+                    "The intermediates of {} are archived; restore them first",
+                    path
+                )
+            }
+            AppError::NotEnoughSpace(message)
+            | AppError::UnsafeOutputLocation(message)
+            | AppError::InsufficientVram(message)
+            | AppError::OutputVolumeLost(message)
+            | AppError::AbortedLowRegistration(message)
+            | AppError::UnsupportedPlatform(message) => write!(f, "{}", message),
+            AppError::OutputNotWritable(refusal) => {
+                write!(f, "{}: {}", refusal.message, refusal.detail)
+            }
+            AppError::SpawnDiagnosis(diagnosis) => write!(f, "{}", diagnosis.failure()),
+            AppError::AlreadyQueued(entry_id) => {
+                write!(f, "An identical job is already queued: {}", entry_id)
+            }
+            AppError::TargetMissing { .. } | AppError::AmbiguousArtifact(_) => {
+                write!(f, "{}", self.failure())
+            }
+            AppError::NotReady(inputs) => {
+                let inputs: Vec<String> = inputs.iter().map(|i| i.message.to_string()).collect();
+                write!(f, "Inputs are not ready: {}", inputs.join("; "))
+            }
+            AppError::Job(failure) => write!(f, "{}", failure),
+            AppError::InvalidInput(message) | AppError::Io(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let failure = self.failure();
+        let mut state = serializer.serialize_struct("AppError", 14)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("key", &failure.message.key)?;
+        state.serialize_field("params", &failure.message.params)?;
+        state.serialize_field("raw", &failure.raw)?;
+        match self {
+            AppError::InvalidArguments(errors) => state.serialize_field("fields", errors)?,
+            _ => state.skip_field("fields")?,
+        }
+        match self {
+            AppError::SpawnDiagnosis(diagnosis) => {
+                state.serialize_field("cause", &diagnosis.cause)?;
+                state.serialize_field("repair", &diagnosis.repair)?;
+            }
+            _ => {
+                state.skip_field("cause")?;
+                state.skip_field("repair")?;
+            }
+        }
+        match self {
+            AppError::OutputNotWritable(refusal) => {
+                state.serialize_field("fallback", &refusal.fallback)?
+            }
+            _ => state.skip_field("fallback")?,
+        }
+        match self {
+            AppError::AlreadyQueued(entry_id) => {
+                state.serialize_field("entry_id", entry_id)?;
+                state.serialize_field("already_queued", &true)?;
+            }
+            _ => {
+                state.skip_field("entry_id")?;
+                state.skip_field("already_queued")?;
+            }
+        }
+        match self {
+            AppError::TargetMissing { suggestion, .. } => {
+                state.serialize_field("suggestion", suggestion)?
+            }
+            _ => state.skip_field("suggestion")?,
+        }
+        match self {
+            AppError::AmbiguousArtifact(paths) => state.serialize_field("candidates", paths)?,
+            _ => state.skip_field("candidates")?,
+        }
+        match self {
+            AppError::NotReady(inputs) => state.serialize_field("not_ready", inputs)?,
+            _ => state.skip_field("not_ready")?,
+        }
+        state.end()
+    }
+}
+
+/// The message of a job that wrote several equally new artifacts, one path
+/// per line
+pub fn ambiguous_artifact(paths: &[String]) -> Message {
+    Message::new("job.ambiguous_artifact").with("paths", paths.join("\n"))
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Io(message)
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Io(e.to_string())
+    }
+}
+
+impl From<Failure> for AppError {
+    fn from(failure: Failure) -> Self {
+        if failure.message.key == "job.output_volume_lost" {
+            return AppError::OutputVolumeLost(failure.message);
+        }
+        if failure.message.key == "job.aborted_low_registration" {
+            return AppError::AbortedLowRegistration(failure.message);
+        }
+        if failure.message.key == "job.ambiguous_artifact" {
+            let paths = failure.message.params.get("paths").cloned();
+            return AppError::AmbiguousArtifact(
+                paths
+                    .unwrap_or_default()
+                    .lines()
+                    .map(String::from)
+                    .collect(),
+            );
+        }
+        if failure.message.key == "job.already_queued" {
+            let entry_id = failure.message.params.get("entry_id").cloned();
+            return AppError::AlreadyQueued(entry_id.unwrap_or_default());
+        }
+        match SpawnDiagnosis::from_failure(&failure) {
+            Some(diagnosis) => AppError::SpawnDiagnosis(diagnosis),
+            None => AppError::Job(failure),
+        }
+    }
+}
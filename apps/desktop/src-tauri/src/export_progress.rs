@@ -0,0 +1,214 @@
+//! Export Progress
+//!
+//! gvcore-cli reports nothing while it writes the artifact, which for a large
+//! splat takes minutes, so jobs sat at 99% and looked hung. Once the CLI has
+//! reached the exporting stage and then printed no progress for QUIET_POLLS
+//! polls, the newest PLY or SPZ in the output is watched instead. Its size
+//! against the size expected from the last splat count seen in training, at
+//! the bytes a splat takes in its format, gives progress events marked
+//! synthetic, from the CLI's last percentage up to at most MAX_PERCENT, which
+//! only the CLI itself goes past.
+//!
+//! The size is also kept in the run state, so the stall watch counts a slow
+//! export that keeps writing as alive.
+
+use crate::artifact_search::{MAX_DEPTH, SKIPPED};
+use crate::artifacts::ArtifactKind;
+use crate::disk::format_bytes;
+use crate::messages::Message;
+use crate::runner::ProcessProgress;
+use crate::training_metrics;
+use crate::volume_watch::RunState;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// The stage the CLI writes the artifact in
+pub const EXPORT_STAGE: &str = "exporting";
+
+/// How often the artifact's size is looked at
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls without progress from the CLI before its silence is filled in
+const QUIET_POLLS: u32 = 3;
+
+/// Highest percentage estimated
+const MAX_PERCENT: f64 = 99.0;
+
+/// SH degree brush trains with unless told otherwise
+const DEFAULT_SH_DEGREE: u32 = 3;
+
+/// Bytes one splat takes in an artifact of `kind`, leaving out the header
+pub fn bytes_per_splat(kind: ArtifactKind, sh_degree: u32) -> u64 {
+    let rest = 3 * (u64::from(sh_degree + 1).pow(2) - 1);
+    match kind {
+        // Position, scale, opacity, rotation, colour and the rest of the SH
+        // coefficients as 32-bit floats
+        ArtifactKind::Ply | ArtifactKind::Other => (14 + rest) * 4,
+        // 24-bit fixed-point positions and a byte for every other value,
+        // before compression
+        ArtifactKind::Spz => 9 + 1 + 3 + 3 + 3 + rest,
+    }
+}
+
+/// The job's percentage with `written` of `expected` bytes exported, having
+/// reached `from` when the CLI fell silent
+pub fn percent(written: u64, expected: u64, from: f64) -> f64 {
+    let fraction = if expected == 0 {
+        0.0
+    } else {
+        (written as f64 / expected as f64).min(1.0)
+    };
+    (from + (MAX_PERCENT - from).max(0.0) * fraction).min(MAX_PERCENT.max(from))
+}
+
+/// Report estimated progress of `job_id`'s export into `dir` with `notify`
+/// while the CLI is silent. Never returns, so it is meant to be raced
+/// against the run.
+pub async fn watch(
+    job_id: &str,
+    dir: &Path,
+    sh_degree: Option<u32>,
+    state: &RunState,
+    interval: Duration,
+    notify: &mut (dyn FnMut(ProcessProgress) + Send),
+) {
+    let sh_degree = sh_degree.unwrap_or(DEFAULT_SH_DEGREE);
+    let mut last = None;
+    let mut quiet = 0;
+    loop {
+        tokio::time::sleep(interval).await;
+        let progress = state.progress.lock().unwrap().clone();
+        if progress != last {
+            last = progress;
+            quiet = 0;
+            continue;
+        }
+        let Some((stage, from)) = &last else {
+            continue;
+        };
+        quiet += 1;
+        if stage != EXPORT_STAGE || quiet < QUIET_POLLS {
+            continue;
+        }
+        let Some((path, written)) = newest_artifact(dir) else {
+            continue;
+        };
+        let previous = state.exported_bytes.lock().unwrap().replace(written);
+        if previous == Some(written) {
+            continue;
+        }
+        let Some(splats) = training_metrics::latest(job_id, "splat_count") else {
+            continue;
+        };
+        let expected = splats.max(0.0) as u64 * bytes_per_splat(ArtifactKind::of(&path), sh_degree);
+        notify(ProcessProgress {
+            stage: EXPORT_STAGE.to_string(),
+            progress: percent(written, expected, *from),
+            message: Some(
+                Message::new("progress.exporting")
+                    .with("written", format_bytes(written))
+                    .with("expected", format_bytes(expected)),
+            ),
+            raw: None,
+            current_item: None,
+            items: None,
+            reported_item: None,
+            synthetic: true,
+        });
+    }
+}
+
+/// The most recently modified PLY or SPZ under `dir`, and its size
+fn newest_artifact(dir: &Path) -> Option<(PathBuf, u64)> {
+    let mut newest: Option<(SystemTime, PathBuf, u64)> = None;
+    collect(dir, 0, &mut newest);
+    newest.map(|(_, path, size)| (path, size))
+}
+
+fn collect(dir: &Path, depth: usize, newest: &mut Option<(SystemTime, PathBuf, u64)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            let skipped = SKIPPED.iter().any(|name| entry.file_name() == *name);
+            if !skipped && depth < MAX_DEPTH {
+                collect(&path, depth + 1, newest);
+            }
+            continue;
+        }
+        if !file_type.is_file() || ArtifactKind::of(&path) == ArtifactKind::Other {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        if newest.as_ref().map_or(true, |(m, _, _)| modified > *m) {
+            *newest = Some((modified, path, metadata.len()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use crate::training_metrics::MetricSample;
+
+    #[test]
+    fn estimates_stay_below_completion() {
+        assert_eq!(bytes_per_splat(ArtifactKind::Ply, 0), 56);
+        assert_eq!(bytes_per_splat(ArtifactKind::Ply, 3), 236);
+        assert_eq!(bytes_per_splat(ArtifactKind::Spz, 3), 64);
+        assert_eq!(percent(0, 1000, 90.0), 90.0);
+        assert_eq!(percent(500, 1000, 90.0), 94.5);
+        // A file larger than estimated still leaves completion to the CLI
+        assert_eq!(percent(5000, 1000, 90.0), 99.0);
+        assert_eq!(percent(10, 0, 90.0), 90.0);
+    }
+
+    #[tokio::test]
+    async fn a_growing_export_is_reported_while_the_cli_is_silent() {
+        let paths = TempPaths::new();
+        let export = paths.root().join("export");
+        std::fs::create_dir_all(&export).unwrap();
+        let job_id = "export-progress-job";
+        let _metrics = training_metrics::track(job_id);
+        training_metrics::record(
+            job_id,
+            &MetricSample {
+                name: "splats".to_string(),
+                step: Some(30_000),
+                value: 1000.0,
+            },
+        );
+        let state = RunState::default();
+        *state.progress.lock().unwrap() = Some((EXPORT_STAGE.to_string(), 90.0));
+        std::fs::write(export.join("splat.ply"), vec![0u8; 56_000 / 2]).unwrap();
+
+        let mut reported = vec![];
+        let mut notify = |progress: ProcessProgress| reported.push(progress);
+        let watch = watch(
+            job_id,
+            paths.root(),
+            Some(0),
+            &state,
+            Duration::from_millis(10),
+            &mut notify,
+        );
+        tokio::time::timeout(Duration::from_millis(200), watch)
+            .await
+            .unwrap_err();
+
+        // Reported once, as the file did not grow again
+        assert_eq!(reported.len(), 1);
+        assert!(reported[0].synthetic);
+        assert_eq!(reported[0].progress, 94.5);
+        assert_eq!(*state.exported_bytes.lock().unwrap(), Some(28_000));
+    }
+}
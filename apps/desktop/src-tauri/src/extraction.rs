@@ -0,0 +1,271 @@
+//! Frame Extraction
+//!
+//! Backend-side frame extraction through ffmpeg, used when the installed CLI
+//! cannot handle a clip's requirements itself.
+
+use crate::ffmpeg::{self, Tool};
+use crate::media::Resolution;
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::Duration;
+
+/// Decoding a whole clip
+const CLIP_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Decoding a single frame
+const FRAME_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Decoding the audio analyzed for sync
+const AUDIO_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Frame rate used when the backend extracts frames itself
+pub const EXTRACT_FPS: u32 = 2;
+
+// Linearize, convert to bt709 primaries, tone-map with hable, then back to SDR
+const TONEMAP_FILTER: &str = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,\
+tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p";
+
+/// Frames extraction yields from a clip of `duration_secs`, skipping `start_secs`
+pub fn expected_frames(duration_secs: f64, start_secs: f64) -> usize {
+    ((duration_secs - start_secs).max(0.0) * EXTRACT_FPS as f64).ceil() as usize
+}
+
+/// Extracted frames in `dir` and the directories below it
+pub fn count_frames(dir: &Path) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            if path.is_dir() {
+                return count_frames(&path);
+            }
+            let ext = path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            usize::from(matches!(ext.as_str(), "png" | "jpg" | "jpeg"))
+        })
+        .sum()
+}
+
+/// Extract tone-mapped SDR frames from an HDR clip into `frames_dir`, skipping
+/// `start_secs`, scaled down to fit `scale` if given
+pub async fn extract_tonemapped(
+    input: &str,
+    frames_dir: &Path,
+    start_secs: f64,
+    scale: Option<Resolution>,
+) -> Result<(), String> {
+    let filter = format!(
+        "fps={},{}{}",
+        EXTRACT_FPS,
+        TONEMAP_FILTER,
+        scale_filter(scale)
+    );
+    extract_with_filter(input, frames_dir, start_secs, &filter, "tone mapping").await
+}
+
+/// Extract frames as they are into `frames_dir`, skipping `start_secs`,
+/// scaled down to fit `scale` if given
+pub async fn extract_frames(
+    input: &str,
+    frames_dir: &Path,
+    start_secs: f64,
+    scale: Option<Resolution>,
+) -> Result<(), String> {
+    let filter = format!("fps={}{}", EXTRACT_FPS, scale_filter(scale));
+    extract_with_filter(input, frames_dir, start_secs, &filter, "frame extraction").await
+}
+
+/// Filter appended to scale frames down to fit `scale`, keeping their aspect ratio
+fn scale_filter(scale: Option<Resolution>) -> String {
+    match scale {
+        Some(target) => format!(
+            ",scale={}:{}:force_original_aspect_ratio=decrease:flags=lanczos",
+            target.width, target.height
+        ),
+        None => String::new(),
+    }
+}
+
+async fn extract_with_filter(
+    input: &str,
+    frames_dir: &Path,
+    start_secs: f64,
+    filter: &str,
+    what: &str,
+) -> Result<(), String> {
+    std::fs::create_dir_all(frames_dir).map_err(|e| e.to_string())?;
+
+    let start = format!("{:.3}", start_secs);
+    let mut args: Vec<OsString> = [
+        "-v", "error", "-y", "-ss", &start, "-i", input, "-vf", filter,
+    ]
+    .iter()
+    .map(OsString::from)
+    .collect();
+    args.push(frames_dir.join("frame_%05d.png").into());
+    let output = ffmpeg::run(Tool::Ffmpeg, args, CLIP_TIMEOUT).await?;
+
+    if output.success {
+        Ok(())
+    } else {
+        Err(format!(
+            "ffmpeg {} failed for {}: {}",
+            what, input, output.stderr
+        ))
+    }
+}
+
+/// Transcode a clip to a smaller proxy at most `height` pixels high, tone-mapped
+/// to SDR if asked, without its audio
+pub async fn make_proxy(
+    input: &str,
+    output: &Path,
+    height: u32,
+    tone_map: bool,
+) -> Result<(), String> {
+    let scale = format!("scale=-2:'min({},ih)'", height);
+    let filter = if tone_map {
+        format!("{},{}", TONEMAP_FILTER, scale)
+    } else {
+        scale
+    };
+    let mut args: Vec<OsString> = [
+        "-v", "error", "-y", "-i", input, "-vf", &filter, "-an", "-c:v", "libx264", "-preset",
+        "veryfast", "-crf", "23", "-f", "mp4",
+    ]
+    .iter()
+    .map(OsString::from)
+    .collect();
+    args.push(output.into());
+    let output = ffmpeg::run(Tool::Ffmpeg, args, CLIP_TIMEOUT).await?;
+
+    if output.success {
+        Ok(())
+    } else {
+        Err(format!(
+            "ffmpeg could not make a preview proxy of {}: {}",
+            input, output.stderr
+        ))
+    }
+}
+
+/// Grab one frame at `at_secs` as 8-bit grayscale, scaled to `width` x `height`
+pub async fn extract_gray_frame(
+    input: &str,
+    at_secs: f64,
+    width: usize,
+    height: usize,
+) -> Result<Vec<u8>, String> {
+    let filter = format!("scale={}:{},format=gray", width, height);
+    // Seeking before -i jumps to the nearest keyframe instead of decoding up to it
+    let at = format!("{:.3}", at_secs);
+    let args = [
+        "-v",
+        "error",
+        "-ss",
+        &at,
+        "-i",
+        input,
+        "-frames:v",
+        "1",
+        "-vf",
+        &filter,
+        "-f",
+        "rawvideo",
+        "pipe:1",
+    ];
+    let output = ffmpeg::run(Tool::Ffmpeg, args, FRAME_TIMEOUT).await?;
+
+    if output.success && output.stdout.len() == width * height {
+        Ok(output.stdout)
+    } else {
+        Err(format!(
+            "ffmpeg could not read a frame of {} at {:.1}s: {}",
+            input, at_secs, output.stderr
+        ))
+    }
+}
+
+/// Write a JPEG thumbnail of the frame at `at_secs`, `width` pixels wide
+pub async fn extract_thumbnail(
+    input: &str,
+    at_secs: f64,
+    width: u32,
+    output_path: &Path,
+) -> Result<(), String> {
+    if let Some(dir) = output_path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let filter = format!("scale={}:-2", width);
+    let at = format!("{:.3}", at_secs);
+    let mut args: Vec<OsString> = [
+        "-v",
+        "error",
+        "-y",
+        "-ss",
+        &at,
+        "-i",
+        input,
+        "-frames:v",
+        "1",
+        "-vf",
+        &filter,
+        "-q:v",
+        "4",
+    ]
+    .iter()
+    .map(OsString::from)
+    .collect();
+    args.push(output_path.into());
+    let output = ffmpeg::run(Tool::Ffmpeg, args, FRAME_TIMEOUT).await?;
+
+    if output.success && output_path.is_file() {
+        Ok(())
+    } else {
+        Err(format!(
+            "ffmpeg could not create a thumbnail of {}: {}",
+            input, output.stderr
+        ))
+    }
+}
+
+/// Decode up to `max_secs` of a clip's first audio stream as mono samples; None if it has none
+pub async fn extract_mono_audio(
+    input: &str,
+    sample_rate: u32,
+    max_secs: f64,
+) -> Result<Option<Vec<f32>>, String> {
+    let (rate, duration) = (sample_rate.to_string(), format!("{:.3}", max_secs));
+    let args = [
+        "-v", "error", "-i", input, "-map", "0:a:0?", "-ac", "1", "-ar", &rate, "-t", &duration,
+        "-f", "f32le", "pipe:1",
+    ];
+    let output = ffmpeg::run(Tool::Ffmpeg, args, AUDIO_TIMEOUT).await?;
+
+    // With the optional map, a clip without audio leaves ffmpeg with nothing to write
+    if output.stderr.contains("does not contain any stream") {
+        return Ok(None);
+    }
+    if !output.success {
+        return Err(format!(
+            "ffmpeg could not read the audio of {}: {}",
+            input, output.stderr
+        ));
+    }
+    if output.stdout.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(
+        output
+            .stdout
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    ))
+}
@@ -0,0 +1,307 @@
+//! ffmpeg and ffprobe
+//!
+//! Resolves which ffmpeg and ffprobe the backend runs, and runs them. The
+//! ffmpeg_path setting wins, then the build bundled in the resources, then one
+//! installed into app_data/tools, then whatever is on the PATH. Every
+//! invocation goes through `run`, which passes arguments without a shell,
+//! enforces a timeout and captures stderr for the error message.
+
+use crate::platform::PathProvider;
+use crate::settings::AppSettings;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::process::Command;
+
+/// The oldest release with every filter the backend uses
+pub const MIN_VERSION: (u32, u32) = (4, 4);
+
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+static LOCATIONS: Mutex<Locations> = Mutex::new(Locations {
+    configured: None,
+    bundled: None,
+    tools: None,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tool {
+    Ffmpeg,
+    Ffprobe,
+}
+
+impl Tool {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Ffmpeg => "ffmpeg",
+            Self::Ffprobe => "ffprobe",
+        }
+    }
+
+    fn file_name(self) -> String {
+        format!("{}{}", self.name(), std::env::consts::EXE_SUFFIX)
+    }
+}
+
+/// Where a resolved tool was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    /// The ffmpeg_path setting, or the ffprobe next to it
+    Configured,
+    Bundled,
+    /// Installed into app_data/tools
+    AppData,
+    Path,
+    /// Not found anywhere; running it fails
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolStatus {
+    pub tool: Tool,
+    pub path: String,
+    pub source: ToolSource,
+    /// The version it reports, e.g. "6.1.1"; None when it did not run or has no
+    /// release number, as with nightly builds
+    pub version: Option<String>,
+    /// Found, and not older than MIN_VERSION
+    pub supported: bool,
+}
+
+/// What a finished ffmpeg or ffprobe run printed
+#[derive(Debug)]
+pub struct ToolOutput {
+    pub success: bool,
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+}
+
+/// The directories searched before the PATH
+#[derive(Debug, Clone, Default)]
+struct Locations {
+    configured: Option<PathBuf>,
+    bundled: Option<PathBuf>,
+    tools: Option<PathBuf>,
+}
+
+/// Remember where to look, at startup and whenever the settings change
+pub fn configure(paths: &impl PathProvider, settings: &AppSettings) {
+    let mut locations = LOCATIONS.lock().unwrap();
+    locations.configured = settings
+        .ffmpeg_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .map(PathBuf::from);
+    locations.bundled = paths.resource_dir().ok().map(|dir| dir.join("resources"));
+    locations.tools = paths.app_data_dir().ok().map(|dir| dir.join("tools"));
+}
+
+/// The executable to run for `tool`, and where it was found
+pub fn resolve(tool: Tool) -> (PathBuf, ToolSource) {
+    let locations = LOCATIONS.lock().unwrap().clone();
+    resolve_in(&locations, tool, crate::setup::find_in_path)
+}
+
+fn resolve_in(
+    locations: &Locations,
+    tool: Tool,
+    find_in_path: impl Fn(&str) -> Option<PathBuf>,
+) -> (PathBuf, ToolSource) {
+    // The setting names ffmpeg; ffprobe is expected beside it
+    let configured = locations.configured.as_ref().map(|ffmpeg| match tool {
+        Tool::Ffmpeg => ffmpeg.clone(),
+        Tool::Ffprobe => ffmpeg.with_file_name(tool.file_name()),
+    });
+    let candidates = [
+        (configured, ToolSource::Configured),
+        (
+            locations.bundled.as_ref().map(|d| d.join(tool.file_name())),
+            ToolSource::Bundled,
+        ),
+        (
+            locations.tools.as_ref().map(|d| d.join(tool.file_name())),
+            ToolSource::AppData,
+        ),
+    ];
+    for (path, source) in candidates {
+        if let Some(path) = path.filter(|p| p.is_file()) {
+            return (path, source);
+        }
+    }
+    match find_in_path(tool.name()) {
+        Some(path) => (path, ToolSource::Path),
+        None => (PathBuf::from(tool.name()), ToolSource::Missing),
+    }
+}
+
+/// Run `tool` to completion, killing it after `timeout`
+pub async fn run<I, S>(tool: Tool, args: I, timeout: Duration) -> Result<ToolOutput, String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let (program, source) = resolve(tool);
+    if source == ToolSource::Missing {
+        return Err(format!(
+            "{} was not found; install it or set its path in settings",
+            tool.name()
+        ));
+    }
+    let child = Command::new(&program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(timeout, child)
+        .await
+        .map_err(|_| format!("{} timed out after {}s", tool.name(), timeout.as_secs()))?
+        .map_err(|e| format!("Failed to run {}: {}", program.display(), e))?;
+    Ok(ToolOutput {
+        success: output.status.success(),
+        stdout: output.stdout,
+        stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    })
+}
+
+/// Which build of `tool` is used, and whether it is recent enough
+pub async fn status(tool: Tool) -> ToolStatus {
+    let (path, source) = resolve(tool);
+    let version = match source {
+        ToolSource::Missing => None,
+        _ => run(tool, ["-version"], VERSION_TIMEOUT)
+            .await
+            .ok()
+            .filter(|output| output.success)
+            .and_then(|output| {
+                let stdout = String::from_utf8_lossy(&output.stdout);
+                stdout.lines().next().and_then(parse_version)
+            }),
+    };
+    ToolStatus {
+        tool,
+        path: path.to_string_lossy().to_string(),
+        source,
+        supported: source != ToolSource::Missing && version.as_deref().map_or(true, meets_minimum),
+        version,
+    }
+}
+
+/// The release number in the first line of `-version`, e.g. "6.1.1" from
+/// "ffmpeg version n6.1.1-static Copyright ..."
+fn parse_version(first_line: &str) -> Option<String> {
+    let word = first_line.split_whitespace().nth(2)?;
+    let word = word.strip_prefix('n').unwrap_or(word);
+    let release: String = word
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let release = release.trim_end_matches('.');
+    // Nightly builds report a git revision such as "N-113179-g..."
+    (!release.is_empty()).then(|| release.to_string())
+}
+
+fn meets_minimum(version: &str) -> bool {
+    let mut parts = version.split('.').map(|p| p.parse::<u32>().unwrap_or(0));
+    let major = parts.next().unwrap_or(0);
+    let minor = parts.next().unwrap_or(0);
+    (major, minor) >= MIN_VERSION
+}
+
+/// How the setup check reports a tool, e.g. "ffmpeg 6.1.1 (bundled)"
+pub fn describe(status: &ToolStatus) -> String {
+    let source = match status.source {
+        ToolSource::Configured => "from settings",
+        ToolSource::Bundled => "bundled",
+        ToolSource::AppData => "from app data",
+        ToolSource::Path => "from PATH",
+        ToolSource::Missing => return format!("{} not found", status.tool.name()),
+    };
+    match &status.version {
+        Some(version) if !status.supported => format!(
+            "{} {} ({}) is older than {}.{}",
+            status.tool.name(),
+            version,
+            source,
+            MIN_VERSION.0,
+            MIN_VERSION.1
+        ),
+        Some(version) => format!("{} {} ({})", status.tool.name(), version, source),
+        None => format!("{} ({}, {})", status.tool.name(), source, status.path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn resolves_in_order_of_precedence() {
+        let paths = TempPaths::new();
+        let dir = |name: &str| {
+            let dir = paths.root().join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        };
+        let (own, bundled, tools) = (dir("own"), dir("bundled"), dir("tools"));
+        let locations = Locations {
+            configured: Some(own.join(Tool::Ffmpeg.file_name())),
+            bundled: Some(bundled.clone()),
+            tools: Some(tools.clone()),
+        };
+        let on_path = |name: &str| Some(PathBuf::from(format!("/usr/bin/{}", name)));
+
+        assert_eq!(
+            resolve_in(&locations, Tool::Ffmpeg, on_path),
+            (PathBuf::from("/usr/bin/ffmpeg"), ToolSource::Path)
+        );
+        assert_eq!(
+            resolve_in(&locations, Tool::Ffprobe, |_| None).1,
+            ToolSource::Missing
+        );
+
+        std::fs::write(tools.join(Tool::Ffprobe.file_name()), b"").unwrap();
+        assert_eq!(
+            resolve_in(&locations, Tool::Ffprobe, on_path).1,
+            ToolSource::AppData
+        );
+        std::fs::write(bundled.join(Tool::Ffprobe.file_name()), b"").unwrap();
+        assert_eq!(
+            resolve_in(&locations, Tool::Ffprobe, on_path).1,
+            ToolSource::Bundled
+        );
+        std::fs::write(own.join(Tool::Ffprobe.file_name()), b"").unwrap();
+        assert_eq!(
+            resolve_in(&locations, Tool::Ffprobe, on_path),
+            (own.join(Tool::Ffprobe.file_name()), ToolSource::Configured)
+        );
+        // A configured ffmpeg that is gone falls back to the others
+        assert_eq!(
+            resolve_in(&locations, Tool::Ffmpeg, on_path).1,
+            ToolSource::Path
+        );
+    }
+
+    #[test]
+    fn parses_and_checks_versions() {
+        let version = |line: &str| parse_version(line);
+        assert_eq!(
+            version("ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023").as_deref(),
+            Some("6.1.1")
+        );
+        assert_eq!(
+            version("ffprobe version n7.0-static https://johnvansickle.com").as_deref(),
+            Some("7.0")
+        );
+        assert_eq!(version("ffmpeg version N-113179-g0d9f3e5 Copyright"), None);
+        assert!(meets_minimum("4.4.2"));
+        assert!(meets_minimum("10.0"));
+        assert!(!meets_minimum("4.3.1"));
+        assert!(!meets_minimum("3"));
+    }
+}
@@ -0,0 +1,195 @@
+//! Job Fingerprints
+//!
+//! What a job will make, reduced to one hash, so a request identical to one
+//! already queued or running can be turned away instead of repeating an hour
+//! of work. Clips count by their content rather than their path, and in no
+//! particular order; fields that only describe the job, such as its label and
+//! tags, or only lift a check it must pass, are left out. Every other field counts, including ones added later,
+//! so two requests are only ever taken for the same job when they are.
+//!
+//! A clip's content is hashed from its length and a few windows of it rather
+//! than all of it, so fingerprinting takes moments however long the clips are.
+
+use crate::camera_groups;
+use crate::commands::ProcessArgs;
+use crate::error::AppError;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Fields that change nothing about the output
+const IGNORED: [&str; 7] = [
+    "production_id",
+    "label",
+    "tags",
+    "allow_duplicate",
+    "allow_mixed_projection",
+    "override_vram_policy",
+    "continue_on_poor_registration",
+];
+
+/// Bytes hashed from the start, middle and end of a clip
+const WINDOW: u64 = 1024 * 1024;
+
+/// The fingerprint of a validated request; reads its clips
+pub fn of(args: &ProcessArgs) -> Result<String, AppError> {
+    of_with(args, |video| Ok(sample_hash(Path::new(video))?))
+}
+
+fn of_with(
+    args: &ProcessArgs,
+    content: impl Fn(&str) -> Result<String, AppError>,
+) -> Result<String, AppError> {
+    let mut clips = vec![];
+    for video in &args.videos {
+        let options = args.clip_options(video);
+        clips.push(json!({
+            "content": content(video)?,
+            "tone_map": options.tone_map,
+            "projection": options.projection,
+            "sync_offset_ms": options.sync_offset_ms,
+            "camera_group": camera_groups::group_of(args, video),
+        }));
+    }
+    clips.sort_by_key(|clip| clip.to_string());
+
+    let mut canonical = serde_json::to_value(args).map_err(|e| e.to_string())?;
+    if let Value::Object(fields) = &mut canonical {
+        for field in IGNORED {
+            fields.remove(field);
+        }
+        fields.remove("clips");
+        fields.insert("videos".to_string(), Value::Array(clips));
+        let output_dir: PathBuf = Path::new(&args.output_dir).components().collect();
+        fields.insert("output_dir".to_string(), json!(output_dir));
+    }
+    // Objects serialize with their keys sorted
+    Ok(hex(&Sha256::digest(canonical.to_string().as_bytes())))
+}
+
+/// SHA-256 of a file's length and of up to WINDOW bytes at its start, middle
+/// and end
+fn sample_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    hasher.update(len.to_le_bytes());
+    let mut offsets = vec![
+        0,
+        len.saturating_sub(WINDOW) / 2,
+        len.saturating_sub(WINDOW),
+    ];
+    offsets.dedup();
+    let mut window = Vec::with_capacity(WINDOW.min(len) as usize);
+    for offset in offsets {
+        file.seek(SeekFrom::Start(offset))?;
+        window.clear();
+        (&mut file).take(WINDOW).read_to_end(&mut window)?;
+        hasher.update(&window);
+    }
+    Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ClipOptions;
+    use crate::platform::testing::TempPaths;
+
+    fn request(videos: &[&str]) -> ProcessArgs {
+        serde_json::from_value(json!({
+            "videos": videos,
+            "output_dir": "/out/harbour",
+            "preset": "balanced",
+        }))
+        .unwrap()
+    }
+
+    /// Clips named after their content, so a renamed copy has the same
+    fn fingerprint(args: &ProcessArgs) -> String {
+        of_with(args, |video| {
+            let name = Path::new(video).file_stem().unwrap().to_string_lossy();
+            Ok(name.trim_end_matches("-copy").to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn clip_order_and_descriptive_fields_do_not_count() {
+        let mut args = request(&["/clips/a.mp4", "/clips/b.mp4", "/clips/c.mp4"]);
+        args.clips = vec![ClipOptions {
+            path: "/clips/b.mp4".to_string(),
+            sync_offset_ms: 120,
+            ..Default::default()
+        }];
+        let base = fingerprint(&args);
+
+        let mut reordered = request(&["/clips/c.mp4", "/clips/b.mp4", "/clips/a.mp4"]);
+        reordered.clips = args.clips.clone();
+        assert_eq!(fingerprint(&reordered), base);
+
+        let described = ProcessArgs {
+            label: Some("Harbour at dusk".to_string()),
+            tags: vec!["client".to_string()],
+            production_id: Some("production-1".to_string()),
+            allow_duplicate: true,
+            allow_mixed_projection: true,
+            override_vram_policy: true,
+            continue_on_poor_registration: true,
+            output_dir: "/out/harbour/".to_string(),
+            ..args.clone()
+        };
+        assert_eq!(fingerprint(&described), base);
+
+        // A copy of a clip under another name is the same clip
+        let mut copied = request(&["/clips/a.mp4", "/other/b-copy.mp4", "/clips/c.mp4"]);
+        copied.clips = vec![ClipOptions {
+            path: "/other/b-copy.mp4".to_string(),
+            sync_offset_ms: 120,
+            ..Default::default()
+        }];
+        assert_eq!(fingerprint(&copied), base);
+
+        // The options follow their clip when the list is reordered
+        let mut moved = reordered.clone();
+        moved.clips[0].path = "/clips/a.mp4".to_string();
+        assert_ne!(fingerprint(&moved), base);
+        let other_preset = ProcessArgs {
+            preset: "fast".to_string(),
+            ..args.clone()
+        };
+        assert_ne!(fingerprint(&other_preset), base);
+        let other_output = ProcessArgs {
+            output_dir: "/out/harbour-2".to_string(),
+            ..args.clone()
+        };
+        assert_ne!(fingerprint(&other_output), base);
+        assert_ne!(
+            fingerprint(&request(&["/clips/a.mp4", "/clips/b.mp4"])),
+            base
+        );
+    }
+
+    #[test]
+    fn sampled_hashes_follow_the_content() {
+        let paths = TempPaths::new();
+        let write = |name: &str, content: &[u8]| {
+            let path = paths.root().join(name);
+            std::fs::write(&path, content).unwrap();
+            sample_hash(&path).unwrap()
+        };
+        let long: Vec<u8> = (0..3 * WINDOW + 17).map(|i| (i % 251) as u8).collect();
+        let mut changed_end = long.clone();
+        *changed_end.last_mut().unwrap() ^= 1;
+
+        assert_eq!(write("a.mp4", &long), write("b.mp4", &long));
+        assert_ne!(write("a.mp4", &long), write("c.mp4", &changed_end));
+        assert_ne!(write("short.mp4", b"abc"), write("shorter.mp4", b"ab"));
+    }
+}
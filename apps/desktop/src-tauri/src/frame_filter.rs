@@ -0,0 +1,227 @@
+//! Frame Quality Filtering
+//!
+//! Scores extracted frames for motion blur (variance of the Laplacian) and
+//! exposure (mean of the luminance histogram) and deletes the ones that would
+//! hurt the reconstruction.
+
+use crate::scheduler::{self, Pool};
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Fewest frames a clip may keep before the job is refused
+pub const MIN_SURVIVING_FRAMES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameFilter {
+    pub enabled: bool,
+    /// Laplacian variance below which a frame counts as blurred
+    pub blur_threshold: f64,
+    /// Accepted range of mean luminance, 0-255
+    pub min_brightness: u8,
+    pub max_brightness: u8,
+}
+
+impl Default for FrameFilter {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            blur_threshold: 100.0,
+            min_brightness: 20,
+            max_brightness: 235,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameScore {
+    pub sharpness: f64,
+    pub brightness: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Blurred,
+    TooDark,
+    TooBright,
+}
+
+/// How many frames of a clip were kept and why the others were deleted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterReport {
+    pub kept: usize,
+    pub blurred: usize,
+    pub too_dark: usize,
+    pub too_bright: usize,
+}
+
+impl FilterReport {
+    pub fn rejected(&self) -> usize {
+        self.blurred + self.too_dark + self.too_bright
+    }
+}
+
+impl FrameFilter {
+    pub fn judge(&self, score: &FrameScore) -> Option<Rejection> {
+        if score.brightness < f64::from(self.min_brightness) {
+            Some(Rejection::TooDark)
+        } else if score.brightness > f64::from(self.max_brightness) {
+            Some(Rejection::TooBright)
+        } else if score.sharpness < self.blur_threshold {
+            Some(Rejection::Blurred)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn score(image: &GrayImage) -> FrameScore {
+    FrameScore {
+        sharpness: laplacian_variance(image),
+        brightness: mean_brightness(image),
+    }
+}
+
+/// Score every PNG in `frames_dir` and delete the rejects
+pub async fn filter_dir(frames_dir: &Path, filter: &FrameFilter) -> Result<FilterReport, String> {
+    let frames_dir = frames_dir.to_path_buf();
+    let filter = filter.clone();
+    scheduler::run_blocking(Pool::CpuHeavy, move || {
+        filter_dir_blocking(&frames_dir, &filter)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+fn filter_dir_blocking(frames_dir: &Path, filter: &FrameFilter) -> Result<FilterReport, String> {
+    let mut frames: Vec<_> = std::fs::read_dir(frames_dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "png"))
+        .collect();
+    frames.sort();
+
+    let mut report = FilterReport::default();
+    for frame in frames {
+        let image = image::open(&frame)
+            .map_err(|e| format!("Failed to read frame {}: {}", frame.display(), e))?
+            .into_luma8();
+        match filter.judge(&score(&image)) {
+            None => {
+                report.kept += 1;
+                continue;
+            }
+            Some(Rejection::Blurred) => report.blurred += 1,
+            Some(Rejection::TooDark) => report.too_dark += 1,
+            Some(Rejection::TooBright) => report.too_bright += 1,
+        }
+        std::fs::remove_file(&frame).map_err(|e| e.to_string())?;
+    }
+    Ok(report)
+}
+
+// Sharp edges give a wide spread of second derivatives; blur flattens them
+fn laplacian_variance(image: &GrayImage) -> f64 {
+    let (width, height) = image.dimensions();
+    if width < 3 || height < 3 {
+        return 0.0;
+    }
+    let at = |x: u32, y: u32| f64::from(image.get_pixel(x, y).0[0]);
+
+    let mut sum = 0.0;
+    let mut sum_sq = 0.0;
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let response =
+                at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y);
+            sum += response;
+            sum_sq += response * response;
+        }
+    }
+    let count = f64::from((width - 2) * (height - 2));
+    let mean = sum / count;
+    sum_sq / count - mean * mean
+}
+
+fn mean_brightness(image: &GrayImage) -> f64 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let weighted: u64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(level, count)| level as u64 * count)
+        .sum();
+    weighted as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use image::Luma;
+
+    fn checkerboard(dark: u8, light: u8) -> GrayImage {
+        GrayImage::from_fn(64, 64, |x, y| {
+            if (x / 4 + y / 4) % 2 == 0 {
+                Luma([dark])
+            } else {
+                Luma([light])
+            }
+        })
+    }
+
+    fn flat(level: u8) -> GrayImage {
+        GrayImage::from_pixel(64, 64, Luma([level]))
+    }
+
+    #[test]
+    fn judges_blur_and_exposure() {
+        let filter = FrameFilter {
+            enabled: true,
+            ..Default::default()
+        };
+        assert_eq!(filter.judge(&score(&checkerboard(40, 200))), None);
+        assert_eq!(filter.judge(&score(&flat(128))), Some(Rejection::Blurred));
+        assert_eq!(filter.judge(&score(&flat(5))), Some(Rejection::TooDark));
+        assert_eq!(
+            filter.judge(&score(&checkerboard(240, 255))),
+            Some(Rejection::TooBright)
+        );
+    }
+
+    #[tokio::test]
+    async fn filter_dir_deletes_rejects() {
+        let paths = TempPaths::new();
+        let dir = paths.root().join("frames");
+        std::fs::create_dir_all(&dir).unwrap();
+        checkerboard(40, 200)
+            .save(dir.join("frame_00001.png"))
+            .unwrap();
+        flat(128).save(dir.join("frame_00002.png")).unwrap();
+        flat(0).save(dir.join("frame_00003.png")).unwrap();
+        checkerboard(30, 220)
+            .save(dir.join("frame_00004.png"))
+            .unwrap();
+
+        let report = filter_dir(&dir, &FrameFilter::default()).await.unwrap();
+        assert_eq!(
+            report,
+            FilterReport {
+                kept: 2,
+                blurred: 1,
+                too_dark: 1,
+                too_bright: 0
+            }
+        );
+        assert!(dir.join("frame_00001.png").exists());
+        assert!(!dir.join("frame_00002.png").exists());
+        assert!(!dir.join("frame_00003.png").exists());
+    }
+}
@@ -0,0 +1,247 @@
+//! Frames Cache
+//!
+//! Frames the backend extracts itself are kept under the scratch directory and
+//! reused when the same clip is extracted the same way again, as when a
+//! production is rerun with another preset. A set is keyed by the clip's
+//! content hash and everything that shapes its frames: frame rate, start
+//! offset, tone mapping, the frame filter and any downscaling. The sets are entries of the
+//! shared cache, in a root of their own so that they neither fill app_data nor
+//! push out thumbnails; the least recently used are evicted once the sets
+//! outgrow MAX_BYTES.
+
+use crate::cache::{self, Cache, Category, Lease};
+use crate::disk;
+use crate::error::AppError;
+use crate::frame_filter::FrameFilter;
+use crate::hashing::{self, Reuse};
+use crate::media::Resolution;
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+/// Directory under the scratch directory that holds the cache
+const DIR_NAME: &str = "gameview-frames-cache";
+
+/// Manifest of the layout before the shared cache, whose sets sat directly in the root
+const LEGACY_MANIFEST: &str = "manifest.json";
+
+/// Sets are evicted, oldest use first, beyond this size
+pub const MAX_BYTES: u64 = 20 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FramesCacheUsage {
+    pub dir: String,
+    pub sets: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+/// What a set of extracted frames depends on
+pub struct Extraction<'a> {
+    pub video: &'a str,
+    pub fps: u32,
+    pub start_secs: f64,
+    pub tone_map: bool,
+    /// The filter applied to the frames, if the backend filters them
+    pub filter: Option<&'a FrameFilter>,
+    /// The resolution the frames are scaled down to fit, if they are
+    pub scale: Option<Resolution>,
+}
+
+/// How much the cache holds
+#[tauri::command]
+pub async fn get_frames_cache_usage(app: AppHandle) -> Result<FramesCacheUsage, AppError> {
+    let stats = cache(app.settings().scratch_dir.as_deref()).stats(Category::Frames);
+    Ok(FramesCacheUsage {
+        dir: stats.dir,
+        sets: stats.entries,
+        bytes: stats.bytes,
+        max_bytes: stats.max_bytes,
+    })
+}
+
+/// Delete every cached set no running job uses, returning the bytes freed
+#[tauri::command]
+pub async fn clear_frames_cache(app: AppHandle) -> Result<u64, AppError> {
+    Ok(cache(app.settings().scratch_dir.as_deref()).clear(Category::Frames))
+}
+
+/// The cache directory under the configured scratch directory
+pub fn root(scratch_dir: Option<&str>) -> PathBuf {
+    disk::scratch_dir(scratch_dir).join(DIR_NAME)
+}
+
+/// The cache under the configured scratch directory
+pub fn cache(scratch_dir: Option<&str>) -> Arc<Cache> {
+    let root = root(scratch_dir);
+    remove_legacy(&root);
+    cache::open(&root, MAX_BYTES)
+}
+
+/// The key of a set of frames, from the clip's content and the extraction settings
+pub async fn key(extraction: &Extraction<'_>, cancel: &AtomicBool) -> Result<String, AppError> {
+    let content = hashing::sha256(
+        Path::new(extraction.video),
+        Reuse::Cached,
+        cancel,
+        &mut |_, _| {},
+    )
+    .await?;
+    let filter = match extraction.filter {
+        Some(filter) => serde_json::to_string(filter).map_err(|e| e.to_string())?,
+        None => String::new(),
+    };
+    let mut settings = format!(
+        "{}|{}|{:.3}|{}|{}",
+        content, extraction.fps, extraction.start_secs, extraction.tone_map, filter
+    );
+    // Unscaled sets keep the keys they were cached under
+    if let Some(scale) = extraction.scale {
+        settings.push_str(&format!("|{}", scale));
+    }
+    Ok(Sha256::digest(settings.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// The cached frames of `key`, claimed for the caller
+pub fn lookup(cache: &Arc<Cache>, key: &str) -> Option<(PathBuf, Lease)> {
+    let (dir, lease) = cache.lease(Category::Frames, key)?;
+    let has_frames = std::fs::read_dir(&dir).is_ok_and(|mut files| files.next().is_some());
+    has_frames.then_some((dir, lease))
+}
+
+/// An empty directory to extract the frames of `key` into before `store`
+pub fn staging_dir(cache: &Cache, key: &str) -> Result<PathBuf, String> {
+    let dir = cache.staging(Category::Frames, key)?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+/// Move fully extracted frames into the cache, evicting old sets to make room
+pub fn store(cache: &Arc<Cache>, key: &str, staging: &Path) -> Result<(PathBuf, Lease), String> {
+    cache.insert_leased(Category::Frames, key, staging)
+}
+
+// Sets of the old layout are only a cache, so they are deleted rather than moved
+fn remove_legacy(root: &Path) {
+    #[derive(Deserialize)]
+    struct Manifest {
+        entries: Vec<Entry>,
+    }
+    #[derive(Deserialize)]
+    struct Entry {
+        key: String,
+    }
+
+    let manifest = root.join(LEGACY_MANIFEST);
+    let Ok(content) = std::fs::read_to_string(&manifest) else {
+        return;
+    };
+    if let Ok(manifest) = serde_json::from_str::<Manifest>(&content) {
+        for entry in manifest.entries {
+            std::fs::remove_dir_all(root.join(&entry.key)).ok();
+        }
+    }
+    std::fs::remove_file(&manifest).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn extract(cache: &Arc<Cache>, key: &str, frame_bytes: usize) -> (PathBuf, Lease) {
+        let staging = staging_dir(cache, key).unwrap();
+        std::fs::write(staging.join("frame_00001.png"), vec![0u8; frame_bytes]).unwrap();
+        store(cache, key, &staging).unwrap()
+    }
+
+    #[tokio::test]
+    async fn keys_depend_on_content_and_settings() {
+        let paths = TempPaths::new();
+        let video = paths.root().join("cam1.mp4");
+        std::fs::write(&video, b"frames").unwrap();
+        let video = video.to_string_lossy().to_string();
+        let cancel = AtomicBool::new(false);
+        let default_filter = FrameFilter::default();
+        let extraction = |start_secs, filter| Extraction {
+            video: &video,
+            fps: 2,
+            start_secs,
+            tone_map: false,
+            filter,
+            scale: None,
+        };
+
+        let plain = key(&extraction(0.0, None), &cancel).await.unwrap();
+        assert_eq!(plain, key(&extraction(0.0, None), &cancel).await.unwrap());
+        let trimmed = key(&extraction(1.5, None), &cancel).await.unwrap();
+        let filtered = key(&extraction(0.0, Some(&default_filter)), &cancel)
+            .await
+            .unwrap();
+        assert_ne!(plain, trimmed);
+        assert_ne!(plain, filtered);
+        let scaled = Extraction {
+            scale: Some(Resolution {
+                width: 1920,
+                height: 1080,
+            }),
+            ..extraction(0.0, None)
+        };
+        assert_ne!(plain, key(&scaled, &cancel).await.unwrap());
+
+        std::fs::write(&video, b"other frames").unwrap();
+        assert_ne!(plain, key(&extraction(0.0, None), &cancel).await.unwrap());
+    }
+
+    #[test]
+    fn stored_sets_are_found_again() {
+        let paths = TempPaths::new();
+        let cache = cache::open(&paths.root().join(DIR_NAME), MAX_BYTES);
+        assert!(lookup(&cache, "a").is_none());
+
+        let (dir, lease) = extract(&cache, "a", 10);
+        drop(lease);
+        let (found, _lease) = lookup(&cache, "a").unwrap();
+        assert_eq!(found, dir);
+        assert!(found.join("frame_00001.png").is_file());
+
+        // A set whose frames were deleted behind the cache's back is not used
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(lookup(&cache, "a").is_none());
+    }
+
+    #[test]
+    fn sets_in_use_survive_eviction_and_the_old_layout_is_removed() {
+        let paths = TempPaths::new();
+        let root = paths.root().join(DIR_NAME);
+        std::fs::create_dir_all(root.join("0123")).unwrap();
+        std::fs::write(
+            root.join(LEGACY_MANIFEST),
+            r#"{"entries": [{"key": "0123", "bytes": 1}]}"#,
+        )
+        .unwrap();
+        remove_legacy(&root);
+        assert!(!root.join("0123").exists());
+        assert!(!root.join(LEGACY_MANIFEST).exists());
+
+        let cache = cache::open(&root, 15);
+        let (_, old) = extract(&cache, "old", 10);
+        let (_, newer) = extract(&cache, "newer", 10);
+        assert!(lookup(&cache, "old").is_some());
+        drop(old);
+        drop(newer);
+
+        extract(&cache, "newest", 10);
+        assert!(lookup(&cache, "old").is_none());
+        assert_eq!(cache.clear(Category::Frames), 10);
+        assert_eq!(cache.stats(Category::Frames).entries, 0);
+    }
+}
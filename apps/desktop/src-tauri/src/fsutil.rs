@@ -0,0 +1,63 @@
+//! Filesystem Helpers
+//!
+//! Small helpers shared by modules that persist files.
+
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+/// Write a file via a temporary sibling and rename so readers never see a partial file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, contents).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp_path, path).map_err(|e| {
+        std::fs::remove_file(&tmp_path).ok();
+        e.to_string()
+    })
+}
+
+/// Serialize a value as pretty JSON and write it atomically
+pub fn write_json_atomic<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    write_atomic(path, content.as_bytes())
+}
+
+/// Like write_json_atomic, for files holding secrets: the temporary sibling is
+/// created readable only by the user before anything is written to it, so
+/// there is no moment the contents can be read by others
+pub fn write_json_private<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    let tmp_path = path.with_extension("tmp");
+    // A leftover would keep the permissions it was created with
+    std::fs::remove_file(&tmp_path).ok();
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&tmp_path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|e| {
+            std::fs::remove_file(&tmp_path).ok();
+            e.to_string()
+        })
+}
+
+/// Re-root `path` from `old_root` onto `new_root` if it lies inside `old_root`
+pub fn rebase(path: &str, old_root: &Path, new_root: &Path) -> Option<String> {
+    Path::new(path)
+        .strip_prefix(old_root)
+        .ok()
+        .map(|rest| {
+            if rest.as_os_str().is_empty() {
+                new_root.to_path_buf()
+            } else {
+                new_root.join(rest)
+            }
+        })
+        .map(|p| p.to_string_lossy().to_string())
+}
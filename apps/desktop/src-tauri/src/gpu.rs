@@ -0,0 +1,54 @@
+//! GPU Detection
+//!
+//! Best-effort detection of the GPU used for training.
+
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+
+/// Minimum VRAM considered adequate for training with the default preset
+pub const MIN_VRAM_MB: u64 = 6 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuInfo {
+    pub name: String,
+    /// Total VRAM, unknown on unified-memory systems
+    pub vram_mb: Option<u64>,
+}
+
+/// Detect the primary GPU; None when nothing could be detected
+pub async fn detect() -> Option<GpuInfo> {
+    if let Some(gpu) = detect_nvidia().await {
+        return Some(gpu);
+    }
+
+    // Apple Silicon GPUs share system memory and are always Metal-capable
+    if cfg!(all(target_os = "macos", target_arch = "aarch64")) {
+        return Some(GpuInfo {
+            name: "Apple Silicon".to_string(),
+            vram_mb: None,
+        });
+    }
+
+    None
+}
+
+async fn detect_nvidia() -> Option<GpuInfo> {
+    let output = Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=name,memory.total",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (name, vram) = stdout.lines().next()?.split_once(',')?;
+    Some(GpuInfo {
+        name: name.trim().to_string(),
+        vram_mb: vram.trim().parse().ok(),
+    })
+}
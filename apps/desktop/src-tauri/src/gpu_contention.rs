@@ -0,0 +1,386 @@
+//! GPU Contention
+//!
+//! Training regularly runs out of VRAM because a browser, a game or another
+//! training run holds some of it. When a job reaches the training stage, the
+//! processes using the GPU are sampled with nvidia-smi, and when less VRAM is
+//! free than the preset needs, a gpu-contention event names the largest of
+//! them so the user can close them. With the wait_for_vram setting the CLI is
+//! also suspended right there until enough VRAM frees up or the wait times out.
+//! Other GPUs go unsampled, as their drivers offer no process list.
+//!
+//! The sample is kept for the rest of the job, so a failure that turns out to
+//! be the GPU running out of memory can say what else was using it.
+
+use crate::job_log;
+use crate::messages::Message;
+use crate::volume_watch::{self, RunState};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// The CLI stage that needs the VRAM
+pub const STAGE: &str = "training_splats";
+
+/// How often the stage is checked for
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often VRAM is sampled again while the CLI waits for it
+const WAIT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long nvidia-smi may take to answer
+const SAMPLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Consumers named in the warning
+const TOP_CONSUMERS: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VramConsumer {
+    pub pid: u32,
+    pub name: String,
+    /// Unknown where the driver does not report it, as on Windows
+    pub vram_mb: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VramSample {
+    pub gpu: String,
+    pub total_mb: u64,
+    pub free_mb: u64,
+    /// VRAM the job's own CLI already holds, which it can use for training
+    pub job_mb: u64,
+    /// Other processes using the GPU, largest first
+    pub consumers: Vec<VramConsumer>,
+    pub taken_at: u64,
+}
+
+impl VramSample {
+    /// VRAM training can count on
+    pub fn available_mb(&self) -> u64 {
+        self.free_mb + self.job_mb
+    }
+
+    /// "chrome (2.1 GB), steam (0.4 GB)" for the largest consumers
+    pub fn top_consumers(&self) -> String {
+        self.consumers
+            .iter()
+            .take(TOP_CONSUMERS)
+            .map(|c| match c.vram_mb {
+                Some(mb) => format!("{} ({})", c.name, format_mb(mb)),
+                None => c.name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VramWait {
+    /// The CLI is suspended until VRAM frees up
+    Waiting,
+    /// Enough VRAM freed up and the CLI continued
+    Freed,
+    /// The CLI continued once the wait_for_vram timeout passed
+    TimedOut,
+}
+
+/// The VRAM check at the start of training. Sent as gpu-contention when too
+/// little was free, and again when a wait for VRAM ends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VramCheck {
+    pub job_id: String,
+    /// What the job's preset needs
+    pub required_mb: u64,
+    pub sample: VramSample,
+    pub contended: bool,
+    /// None when the CLI was not suspended
+    pub wait: Option<VramWait>,
+}
+
+impl VramCheck {
+    /// The warning kept with the job
+    pub fn warning_text(&self) -> String {
+        let consumers = self.sample.top_consumers();
+        format!(
+            "{} of the {} the preset needs was free when training started{}",
+            format_mb(self.sample.available_mb()),
+            format_mb(self.required_mb),
+            if consumers.is_empty() {
+                String::new()
+            } else {
+                format!("; also using the GPU: {}", consumers)
+            }
+        )
+    }
+
+    /// The failure message of a job whose CLI ran out of GPU memory
+    pub fn out_of_memory(&self) -> Message {
+        let consumers = self.sample.top_consumers();
+        Message::new("job.gpu_out_of_memory")
+            .with("free", format_mb(self.sample.available_mb()))
+            .with("required", format_mb(self.required_mb))
+            .with(
+                "consumers",
+                if consumers.is_empty() {
+                    "none".to_string()
+                } else {
+                    consumers
+                },
+            )
+    }
+}
+
+/// Whether CLI stderr says the GPU ran out of memory
+pub fn is_out_of_memory(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    stderr.contains("out of memory") || stderr.contains("out_of_memory")
+}
+
+/// Wait for the run to reach the training stage, then check VRAM, passing
+/// the check to `notify`. Given a wait, a contended check suspends the CLI
+/// until VRAM frees up, the wait is over or the job is cancelled. Never
+/// returns, so it can be raced against the run.
+pub async fn watch(
+    job_id: &str,
+    state: &RunState,
+    required_mb: u64,
+    wait: Option<Duration>,
+    cancel: &AtomicBool,
+    notify: &mut (dyn FnMut(VramCheck) + Send),
+) {
+    while !state
+        .progress
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|(stage, _)| stage == STAGE)
+    {
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    let pid = *state.pid.lock().unwrap();
+    let Some(first) = sample(pid).await else {
+        return std::future::pending().await;
+    };
+    let contended = first.available_mb() < required_mb;
+    let paused = contended && wait.is_some() && volume_watch::suspend(state);
+    let mut check = VramCheck {
+        job_id: job_id.to_string(),
+        required_mb,
+        sample: first,
+        contended,
+        wait: paused.then_some(VramWait::Waiting),
+    };
+    notify(check.clone());
+
+    if let (true, Some(wait)) = (paused, wait) {
+        let deadline = Instant::now() + wait;
+        let outcome = loop {
+            tokio::time::sleep(WAIT_INTERVAL).await;
+            if cancel.load(Ordering::SeqCst) {
+                break None;
+            }
+            if let Some(again) = sample(pid).await {
+                check.sample = again;
+                if check.sample.available_mb() >= required_mb {
+                    break Some(VramWait::Freed);
+                }
+            }
+            if Instant::now() >= deadline {
+                break Some(VramWait::TimedOut);
+            }
+        };
+        volume_watch::continue_run(state);
+        if outcome.is_some() {
+            check.wait = outcome;
+            notify(check);
+        }
+    }
+    std::future::pending().await
+}
+
+/// Sample the first NVIDIA GPU, leaving `job_pid` out of the consumers;
+/// None without nvidia-smi
+pub async fn sample(job_pid: Option<u32>) -> Option<VramSample> {
+    let gpu = nvidia_smi(&[
+        "--query-gpu=name,memory.total,memory.free",
+        "--format=csv,noheader,nounits",
+    ])
+    .await?;
+    let (gpu, total_mb, free_mb) = parse_gpu(&gpu)?;
+    // A driver that cannot list processes still reports free VRAM
+    let processes = nvidia_smi(&["-q", "-d", "PIDS"]).await.unwrap_or_default();
+    let (job, mut consumers): (Vec<_>, Vec<_>) = parse_processes(&processes)
+        .into_iter()
+        .partition(|c| Some(c.pid) == job_pid);
+    consumers.sort_by_key(|c| std::cmp::Reverse(c.vram_mb));
+    Some(VramSample {
+        gpu,
+        total_mb,
+        free_mb,
+        job_mb: job.iter().filter_map(|c| c.vram_mb).sum(),
+        consumers,
+        taken_at: job_log::unix_timestamp(),
+    })
+}
+
+async fn nvidia_smi(args: &[&str]) -> Option<String> {
+    let output = Command::new("nvidia-smi")
+        .args(["-i", "0"])
+        .args(args)
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(SAMPLE_TIMEOUT, output)
+        .await
+        .ok()?
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The name, total and free MiB from --query-gpu
+fn parse_gpu(csv: &str) -> Option<(String, u64, u64)> {
+    let mut fields = csv.lines().next()?.split(',').map(str::trim);
+    let name = fields.next()?.to_string();
+    let total = fields.next()?.parse().ok()?;
+    let free = fields.next()?.parse().ok()?;
+    Some((name, total, free))
+}
+
+/// The processes section of `nvidia-smi -q -d PIDS`, which unlike
+/// --query-compute-apps also lists graphics processes such as browsers
+fn parse_processes(report: &str) -> Vec<VramConsumer> {
+    let mut consumers: Vec<VramConsumer> = vec![];
+    for line in report.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match (key.trim(), consumers.last_mut()) {
+            ("Process ID", _) => {
+                if let Ok(pid) = value.parse() {
+                    consumers.push(VramConsumer {
+                        pid,
+                        name: String::new(),
+                        vram_mb: None,
+                    });
+                }
+            }
+            ("Name", Some(consumer)) => {
+                // The full path of the executable on Linux
+                let name = value.rsplit(['/', '\\']).next().unwrap_or(value);
+                consumer.name = name.to_string();
+            }
+            ("Used GPU Memory", Some(consumer)) => {
+                consumer.vram_mb = value
+                    .split_whitespace()
+                    .next()
+                    .and_then(|mb| mb.parse().ok());
+            }
+            _ => {}
+        }
+    }
+    consumers
+}
+
+pub fn format_mb(mb: u64) -> String {
+    format!("{:.1} GB", mb as f64 / 1024.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIDS: &str = "
+==============NVSMI LOG==============
+
+Timestamp                                 : Wed Oct 14 10:00:00 2026
+Driver Version                            : 550.54.14
+
+Attached GPUs                             : 1
+GPU 00000000:01:00.0
+    Processes
+        GPU instance ID                   : N/A
+        Compute instance ID               : N/A
+        Process ID                        : 1201
+            Type                          : G
+            Name                          : /opt/google/chrome/chrome
+            Used GPU Memory               : 2150 MiB
+        GPU instance ID                   : N/A
+        Compute instance ID               : N/A
+        Process ID                        : 4410
+            Type                          : C
+            Name                          : /usr/local/bin/gvcore-cli
+            Used GPU Memory               : 900 MiB
+        GPU instance ID                   : N/A
+        Compute instance ID               : N/A
+        Process ID                        : 5120
+            Type                          : C+G
+            Name                          : C:\\Games\\game.exe
+            Used GPU Memory               : Not available in WDDM driver model
+";
+
+    #[test]
+    fn graphics_and_compute_processes_are_read_from_the_report() {
+        assert_eq!(
+            parse_processes(PIDS),
+            [
+                VramConsumer {
+                    pid: 1201,
+                    name: "chrome".to_string(),
+                    vram_mb: Some(2150),
+                },
+                VramConsumer {
+                    pid: 4410,
+                    name: "gvcore-cli".to_string(),
+                    vram_mb: Some(900),
+                },
+                VramConsumer {
+                    pid: 5120,
+                    name: "game.exe".to_string(),
+                    vram_mb: None,
+                },
+            ]
+        );
+        assert!(parse_processes("    Processes                     : None").is_empty());
+        assert_eq!(
+            parse_gpu("NVIDIA GeForce RTX 3070, 8192, 3100\n"),
+            Some(("NVIDIA GeForce RTX 3070".to_string(), 8192, 3100))
+        );
+        assert_eq!(parse_gpu("NVIDIA GeForce RTX 3070, [N/A], 3100"), None);
+    }
+
+    #[test]
+    fn the_job_counts_its_own_vram_and_the_failure_names_the_others() {
+        let check = VramCheck {
+            job_id: "job-1".to_string(),
+            required_mb: 6 * 1024,
+            sample: VramSample {
+                gpu: "NVIDIA GeForce RTX 3070".to_string(),
+                total_mb: 8192,
+                free_mb: 3100,
+                job_mb: 900,
+                consumers: vec![VramConsumer {
+                    pid: 1201,
+                    name: "chrome".to_string(),
+                    vram_mb: Some(2150),
+                }],
+                taken_at: 0,
+            },
+            contended: true,
+            wait: None,
+        };
+        assert_eq!(check.sample.available_mb(), 4000);
+        assert_eq!(
+            check.warning_text(),
+            "3.9 GB of the 6.0 GB the preset needs was free when training started; \
+             also using the GPU: chrome (2.1 GB)"
+        );
+        let message = check.out_of_memory();
+        assert_eq!(message.params["consumers"], "chrome (2.1 GB)");
+        assert!(is_out_of_memory("RuntimeError: CUDA out of memory."));
+        assert!(!is_out_of_memory("COLMAP failed to register images"));
+    }
+}
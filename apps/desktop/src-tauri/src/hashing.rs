@@ -0,0 +1,502 @@
+//! File Hashing
+//!
+//! Input dedup, frame reuse, artifact checksums, verification and duplicate
+//! finding all need SHA-256 of files that can run to gigabytes, and each used
+//! to read the whole file itself. They now share sha256 here. A file is hashed
+//! in chunks, each run on the CpuHeavy pool of the WorkScheduler, so other
+//! work gets a turn between them. The result is kept in the app's cache under
+//! the file's path, size and modification time, so a file hashed once is not
+//! read again until it changes. Two callers asking for one file at once share
+//! a single read.
+//!
+//! Hashing a file over RESUME_THRESHOLD can be resumed. Every CHECKPOINT_BYTES,
+//! and when it is cancelled, the state of the hash is saved in the cache. The
+//! next request for the same version of the file carries on from there. The
+//! file is checked between chunks, and when it changed, hashing starts over
+//! on the new version.
+
+use crate::cache::{Cache, Category};
+use crate::error::AppError;
+use crate::operations::{OperationHandle, OperationKind};
+use crate::path_policy::PathPolicy;
+use crate::scheduler::{self, Pool};
+use serde::{Deserialize, Serialize};
+use sha2::digest::generic_array::GenericArray;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, State};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Bytes read per chunk; a multiple of the SHA-256 block size
+pub const CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+/// Files over this size save their progress, so hashing them can be resumed
+const RESUME_THRESHOLD: u64 = 256 * 1024 * 1024;
+
+/// How much is hashed between saves of the progress
+const CHECKPOINT_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Times hashing starts over on a file that keeps changing before giving up
+const MAX_RESTARTS: u32 = 3;
+
+/// SHA-256 of the empty message, before any block
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// The app's cache, once setup has configured it
+static CACHE: Mutex<Option<Arc<Cache>>> = Mutex::new(None);
+
+// A lock per file being hashed, so a second request waits for the first's result
+static IN_FLIGHT: Mutex<BTreeMap<PathBuf, Arc<tokio::sync::Mutex<()>>>> =
+    Mutex::new(BTreeMap::new());
+
+/// Whether a checksum cached for the file's current version will do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reuse {
+    Cached,
+    /// Read the file again, as verification must to catch corruption that
+    /// left its size and modification time alone
+    Fresh,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileHash {
+    pub path: String,
+    pub sha256: String,
+    pub bytes: u64,
+    /// Taken from the cache rather than read now
+    pub cached: bool,
+}
+
+/// Keep checksums in `cache`, at startup
+pub fn configure(cache: Arc<Cache>) {
+    *CACHE.lock().unwrap() = Some(cache);
+}
+
+/// SHA-256 of a file, as the frontend asks for it
+#[tauri::command]
+pub async fn get_file_hash(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+) -> Result<FileHash, AppError> {
+    policy.check_existing(&path)?;
+    let operation = OperationHandle::start(&app, OperationKind::Hashing, &path);
+    let cache = CACHE.lock().unwrap().clone();
+    hash(
+        cache.as_deref(),
+        &Tuning::default(),
+        Path::new(&path),
+        Reuse::Cached,
+        operation.token(),
+        &mut |hashed, total| operation.progress(hashed, Some(total)),
+    )
+    .await
+}
+
+/// Hex SHA-256 of the file at `path`, reporting progress once per chunk
+pub async fn sha256(
+    path: &Path,
+    reuse: Reuse,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(u64, u64) + Send),
+) -> Result<String, AppError> {
+    let cache = CACHE.lock().unwrap().clone();
+    let hashed = hash(
+        cache.as_deref(),
+        &Tuning::default(),
+        path,
+        reuse,
+        cancel,
+        progress,
+    )
+    .await?;
+    Ok(hashed.sha256)
+}
+
+/// Sizes hashing works with; smaller in tests
+struct Tuning {
+    chunk_bytes: usize,
+    resume_threshold: u64,
+    checkpoint_bytes: u64,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Tuning {
+            chunk_bytes: CHUNK_BYTES,
+            resume_threshold: RESUME_THRESHOLD,
+            checkpoint_bytes: CHECKPOINT_BYTES,
+        }
+    }
+}
+
+/// A version of a file, as far as its metadata tells
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Version {
+    bytes: u64,
+    /// Nanoseconds since the epoch
+    modified: u128,
+}
+
+impl Version {
+    async fn of(path: &Path) -> Result<Version, AppError> {
+        let metadata = tokio::fs::metadata(path).await?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        Ok(Version {
+            bytes: metadata.len(),
+            modified,
+        })
+    }
+
+    /// Name of the cache entries of this version of the file at `path`
+    fn key(&self, path: &Path) -> String {
+        let key = format!("{}\0{}\0{}", path.display(), self.bytes, self.modified);
+        Sha256::digest(key.as_bytes())
+            .iter()
+            .take(16)
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// SHA-256 part way through a file, which unlike sha2's hasher can be saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Progress {
+    state: [u32; 8],
+    /// Hashed so far; a multiple of 64
+    hashed: u64,
+}
+
+impl Progress {
+    fn new() -> Self {
+        Progress {
+            state: INITIAL_STATE,
+            hashed: 0,
+        }
+    }
+
+    /// Hash whole 64-byte blocks
+    fn update(&mut self, blocks: &[u8]) {
+        for block in blocks.chunks_exact(64) {
+            sha2::compress256(&mut self.state, &[*GenericArray::from_slice(block)]);
+        }
+        self.hashed += blocks.len() as u64;
+    }
+
+    /// The hex digest, after the bytes of `tail` that did not fill a block
+    fn finish(mut self, tail: &[u8]) -> String {
+        let bits = (self.hashed + tail.len() as u64) * 8;
+        let mut last = tail.to_vec();
+        last.push(0x80);
+        while last.len() % 64 != 56 {
+            last.push(0);
+        }
+        last.extend_from_slice(&bits.to_be_bytes());
+        self.update(&last);
+        self.state
+            .iter()
+            .map(|word| format!("{:08x}", word))
+            .collect()
+    }
+}
+
+async fn hash(
+    cache: Option<&Cache>,
+    tuning: &Tuning,
+    path: &Path,
+    reuse: Reuse,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(u64, u64) + Send),
+) -> Result<FileHash, AppError> {
+    let lock = IN_FLIGHT
+        .lock()
+        .unwrap()
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone();
+    let hashed = {
+        let _hashing = lock.lock().await;
+        hash_versions(cache, tuning, path, reuse, cancel, progress).await
+    };
+    let mut in_flight = IN_FLIGHT.lock().unwrap();
+    // Held by the map and this request only
+    if Arc::strong_count(&lock) <= 2 {
+        in_flight.remove(path);
+    }
+    hashed
+}
+
+/// Hash the file as it is, starting over while it changes under the hash
+async fn hash_versions(
+    cache: Option<&Cache>,
+    tuning: &Tuning,
+    path: &Path,
+    reuse: Reuse,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(u64, u64) + Send),
+) -> Result<FileHash, AppError> {
+    for _ in 0..=MAX_RESTARTS {
+        let version = Version::of(path).await?;
+        let key = version.key(path);
+        let file_hash = |sha256: String, cached: bool| FileHash {
+            path: path.to_string_lossy().to_string(),
+            sha256,
+            bytes: version.bytes,
+            cached,
+        };
+        let done_name = format!("sha256-{}.json", key);
+        if reuse == Reuse::Cached {
+            let cached = cache
+                .and_then(|c| c.read(Category::Metadata, &done_name))
+                .and_then(|json| serde_json::from_slice::<String>(&json).ok());
+            if let Some(sha256) = cached {
+                progress(version.bytes, version.bytes);
+                return Ok(file_hash(sha256, true));
+            }
+        }
+        let resumable = cache.filter(|_| version.bytes > tuning.resume_threshold);
+        let partial_name = format!("sha256-{}.partial.json", key);
+        let saved = resumable
+            .and_then(|c| c.read(Category::Metadata, &partial_name))
+            .and_then(|json| serde_json::from_slice::<Progress>(&json).ok())
+            .filter(|p| p.hashed <= version.bytes);
+        let save = |at: &Progress| {
+            if let Some(cache) = resumable {
+                if let Ok(json) = serde_json::to_vec(at) {
+                    cache.write(Category::Metadata, &partial_name, &json).ok();
+                }
+            }
+        };
+
+        let mut at = saved.unwrap_or_else(Progress::new);
+        let mut file = tokio::fs::File::open(path).await?;
+        file.seek(SeekFrom::Start(at.hashed)).await?;
+        let mut buffer = vec![0u8; tuning.chunk_bytes];
+        let mut last_saved = at.hashed;
+        let digest = loop {
+            if cancel.load(Ordering::SeqCst) {
+                save(&at);
+                return Err(AppError::Cancelled);
+            }
+            let _worker = scheduler::global().acquire(Pool::CpuHeavy).await;
+            let mut filled = 0;
+            while filled < buffer.len() {
+                let read = file.read(&mut buffer[filled..]).await?;
+                if read == 0 {
+                    break;
+                }
+                filled += read;
+            }
+            if Version::of(path).await? != version {
+                break None;
+            }
+            let whole = filled - filled % 64;
+            at.update(&buffer[..whole]);
+            progress(at.hashed + (filled - whole) as u64, version.bytes);
+            if filled < buffer.len() {
+                break Some(at.finish(&buffer[whole..filled]));
+            }
+            if at.hashed - last_saved >= tuning.checkpoint_bytes {
+                save(&at);
+                last_saved = at.hashed;
+            }
+        };
+        // The file changed while it was read; its new version gets a hash of its own
+        let Some(sha256) = digest else {
+            continue;
+        };
+        if let Some(cache) = cache {
+            if let Ok(json) = serde_json::to_vec(&sha256) {
+                cache.write(Category::Metadata, &done_name, &json).ok();
+            }
+        }
+        return Ok(file_hash(sha256, false));
+    }
+    Err(AppError::InvalidInput(format!(
+        "{} kept changing while it was hashed",
+        path.display()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache;
+    use crate::platform::testing::TempPaths;
+    use std::time::{Duration, SystemTime};
+
+    fn tuning() -> Tuning {
+        Tuning {
+            chunk_bytes: 256,
+            resume_threshold: 512,
+            checkpoint_bytes: 256,
+        }
+    }
+
+    fn hex(content: &[u8]) -> String {
+        Sha256::digest(content)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Write `content` with a modification time of its own
+    fn write(path: &Path, content: &[u8], secs: u64) {
+        std::fs::write(path, content).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn saved_progress_hashes_as_sha2_does() {
+        for len in [0, 3, 55, 56, 63, 64, 65, 1000] {
+            let content: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+            let whole = len - len % 64;
+            let mut at = Progress::new();
+            at.update(&content[..whole]);
+            assert_eq!(at.finish(&content[whole..]), hex(&content), "{} bytes", len);
+        }
+    }
+
+    #[tokio::test]
+    async fn hashes_are_cached_per_version_and_resumed_after_cancelling() {
+        let paths = TempPaths::new();
+        let cache = cache::open(&paths.root().join("cache"), 64 * 1024 * 1024);
+        let path = paths.root().join("clip.mp4");
+        let content: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+        write(&path, &content, 1_000);
+        let not = AtomicBool::new(false);
+
+        // Cancelled after two chunks, with the progress saved
+        let cancel = AtomicBool::new(false);
+        let mut chunks = 0;
+        let cancelled = hash(
+            Some(&cache),
+            &tuning(),
+            &path,
+            Reuse::Cached,
+            &cancel,
+            &mut |_, _| {
+                chunks += 1;
+                if chunks == 2 {
+                    cancel.store(true, Ordering::SeqCst);
+                }
+            },
+        )
+        .await;
+        assert!(matches!(cancelled, Err(AppError::Cancelled)));
+
+        let mut reports = vec![];
+        let resumed = hash(
+            Some(&cache),
+            &tuning(),
+            &path,
+            Reuse::Cached,
+            &not,
+            &mut |hashed, _| reports.push(hashed),
+        )
+        .await
+        .unwrap();
+        assert_eq!(resumed.sha256, hex(&content));
+        assert!(!resumed.cached);
+        assert_eq!(reports.first(), Some(&768));
+        assert_eq!(reports.last(), Some(&2000));
+
+        let again = hash(
+            Some(&cache),
+            &tuning(),
+            &path,
+            Reuse::Cached,
+            &not,
+            &mut |_, _| {},
+        )
+        .await
+        .unwrap();
+        assert!(again.cached);
+        let fresh = hash(
+            Some(&cache),
+            &tuning(),
+            &path,
+            Reuse::Fresh,
+            &not,
+            &mut |_, _| {},
+        )
+        .await
+        .unwrap();
+        assert!(!fresh.cached);
+        assert_eq!(fresh.sha256, again.sha256);
+
+        // A new version is hashed again
+        let changed: Vec<u8> = content.iter().map(|b| b ^ 0xff).collect();
+        write(&path, &changed, 2_000);
+        let rehashed = hash(
+            Some(&cache),
+            &tuning(),
+            &path,
+            Reuse::Cached,
+            &not,
+            &mut |_, _| {},
+        )
+        .await
+        .unwrap();
+        assert!(!rehashed.cached);
+        assert_eq!(rehashed.sha256, hex(&changed));
+    }
+
+    #[tokio::test]
+    async fn a_file_modified_between_chunks_is_hashed_as_it_ends_up() {
+        let paths = TempPaths::new();
+        let cache = cache::open(&paths.root().join("cache"), 64 * 1024 * 1024);
+        let path = paths.root().join("artifact.ply");
+        let original = vec![1u8; 1500];
+        let rewritten = vec![2u8; 1800];
+        write(&path, &original, 1_000);
+        let not = AtomicBool::new(false);
+
+        let mut rewrote = false;
+        let hashed = hash(
+            Some(&cache),
+            &tuning(),
+            &path,
+            Reuse::Cached,
+            &not,
+            &mut |_, _| {
+                if !rewrote {
+                    rewrote = true;
+                    write(&path, &rewritten, 2_000);
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(hashed.sha256, hex(&rewritten));
+        assert_eq!(hashed.bytes, 1800);
+
+        // Nothing was cached for the version that changed under the hash
+        write(&path, &original, 1_000);
+        let original_hash = hash(
+            Some(&cache),
+            &tuning(),
+            &path,
+            Reuse::Cached,
+            &not,
+            &mut |_, _| {},
+        )
+        .await
+        .unwrap();
+        assert!(!original_hash.cached);
+        assert_eq!(original_hash.sha256, hex(&original));
+    }
+}
@@ -0,0 +1,436 @@
+//! Backend Health
+//!
+//! The frontend asks for get_backend_health before showing anything else, and
+//! routes to a recovery screen when a problem is blocking, rather than leaving
+//! users in front of a blank window when app data is read-only or the webview
+//! and backend come from different builds. Checks that touch the disk run with
+//! a timeout of their own, so a hung network drive shows up as a timed-out
+//! check rather than a command that never returns.
+//!
+//! A session marker in app_data is written at startup and removed on a clean
+//! exit; finding it at startup means the previous session crashed.
+
+use crate::app_data::{self, DataDirSource, RejectedDir};
+use crate::cli_location;
+use crate::datafile::{self, Recovered};
+use crate::disk;
+use crate::error::AppError;
+use crate::fsutil;
+use crate::inspection::{self, InspectionStatus};
+use crate::job_log;
+use crate::messages::Message;
+use crate::platform_support::{self, PlatformSupport};
+use crate::safe_mode;
+use crate::settings::SettingsState;
+use crate::settings_profiles;
+use crate::setup;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// Present in app_data while a session runs
+pub const SESSION_FILE: &str = "session.json";
+
+/// How long any one check may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Free space below which app data is reported as nearly full
+const LOW_SPACE: u64 = 512 * 1024 * 1024;
+
+static PREVIOUS_CRASHED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Session {
+    pid: u32,
+    started_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsHealth {
+    pub loaded: bool,
+    /// The settings file did not parse and defaults are in use
+    pub recovered: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppDataHealth {
+    pub path: String,
+    /// Relocated with relocate_app_data
+    pub overridden: bool,
+    pub source: DataDirSource,
+    /// False in inspection mode, where app data is an overlay lost on quitting
+    pub persistent: bool,
+    /// None when the check timed out
+    pub writable: Option<bool>,
+    pub available_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CliHealth {
+    pub resolved: bool,
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthProblem {
+    pub code: String,
+    /// The UI cannot work until it is dealt with
+    pub blocking: bool,
+    pub message: Message,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackendHealth {
+    pub app_version: String,
+    pub settings: SettingsHealth,
+    /// None when no location for app data could be written to
+    pub app_data: Option<AppDataHealth>,
+    /// Locations passed over for app data at startup, and why
+    pub rejected_app_data: Vec<RejectedDir>,
+    /// None when the check timed out
+    pub cli: Option<CliHealth>,
+    /// The host checked against the support matrix at startup
+    pub platform: Option<PlatformSupport>,
+    /// Data files found unreadable this session
+    pub recovered: Vec<Recovered>,
+    pub previous_session_crashed: bool,
+    /// Started in safe mode, and some of what it left out is still off
+    pub safe_mode: bool,
+    /// Launched without being installed, and what that turns off
+    pub inspection: InspectionStatus,
+    /// Names of the checks that did not finish in time
+    pub timed_out: Vec<String>,
+    /// Blocking problems first
+    pub problems: Vec<HealthProblem>,
+    /// No problem is blocking
+    pub ok: bool,
+}
+
+/// Record that a session started in `app_data`, noting whether the previous
+/// one ended without removing its marker
+pub fn begin(app_data: &Path) {
+    PREVIOUS_CRASHED.store(mark_session(app_data), Ordering::SeqCst);
+}
+
+/// Remove the session marker on a clean exit
+pub fn end(app_data: &Path) {
+    std::fs::remove_file(app_data.join(SESSION_FILE)).ok();
+}
+
+/// Whether the previous session crashed
+pub fn previous_session_crashed() -> bool {
+    PREVIOUS_CRASHED.load(Ordering::SeqCst)
+}
+
+fn mark_session(app_data: &Path) -> bool {
+    let path = app_data.join(SESSION_FILE);
+    let crashed = path.exists();
+    let session = Session {
+        pid: std::process::id(),
+        started_at: job_log::unix_timestamp(),
+    };
+    if let Err(e) = fsutil::write_json_atomic(&path, &session) {
+        eprintln!("Failed to write the session marker: {}", e);
+    }
+    crashed
+}
+
+/// Everything the frontend needs to know before it shows the UI.
+/// `frontend_version` is the version the webview was built as.
+#[tauri::command]
+pub async fn get_backend_health(
+    app: AppHandle,
+    frontend_version: Option<String>,
+) -> Result<BackendHealth, AppError> {
+    let app_version = app.package_info().version.to_string();
+    let resolved = app_data::resolved(&app);
+    let app_data = resolved.path.clone();
+    let settings_recovered = app_data.as_ref().is_some_and(|dir| {
+        let path = settings_profiles::active_settings_path(dir);
+        datafile::recoveries()
+            .iter()
+            .any(|r| Path::new(&r.file) == path)
+    });
+
+    let probe_dir = app_data.clone();
+    let disk_dir = app_data.clone();
+    let cli_app = app.clone();
+    let (writable, available_bytes, cli) = tokio::join!(
+        within(move || probe_dir.map(|dir| setup::is_writable(&dir))),
+        within(move || disk_dir.and_then(|dir| disk::volume_of(&dir).ok()?.available_bytes)),
+        within(move || {
+            let resolution = cli_location::resolve(&cli_app);
+            CliHealth {
+                resolved: resolution.chosen.is_some(),
+                path: resolution.chosen.map(|c| c.path),
+                error: None,
+            }
+        }),
+    );
+    let mut timed_out = vec![];
+    for (name, finished) in [
+        ("app_data_writable", writable.is_some()),
+        ("app_data_space", available_bytes.is_some()),
+        ("cli", cli.is_some()),
+    ] {
+        if !finished {
+            timed_out.push(name.to_string());
+        }
+    }
+
+    let mut health = BackendHealth {
+        app_version,
+        settings: SettingsHealth {
+            loaded: app.try_state::<SettingsState>().is_some(),
+            recovered: settings_recovered,
+        },
+        app_data: app_data
+            .zip(resolved.source)
+            .map(|(dir, source)| AppDataHealth {
+                path: dir.to_string_lossy().to_string(),
+                overridden: source == DataDirSource::Relocated,
+                source,
+                persistent: source != DataDirSource::Inspection,
+                writable: writable.flatten(),
+                available_bytes: available_bytes.flatten(),
+            }),
+        rejected_app_data: resolved.rejected,
+        cli: cli.map(|mut cli| {
+            if !cli.resolved {
+                cli.error = Some(format!("{} was not found", cli_location::NAME));
+            }
+            cli
+        }),
+        platform: platform_support::of(&app),
+        recovered: datafile::recoveries(),
+        previous_session_crashed: previous_session_crashed(),
+        safe_mode: safe_mode::skipped_any(),
+        inspection: inspection::status(),
+        timed_out,
+        problems: vec![],
+        ok: true,
+    };
+    health.problems = problems(&health, frontend_version.as_deref());
+    health.ok = !health.problems.iter().any(|p| p.blocking);
+    Ok(health)
+}
+
+/// Run a blocking check, giving up on it after CHECK_TIMEOUT
+async fn within<T: Send + 'static>(check: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+    let task = tokio::task::spawn_blocking(check);
+    tokio::time::timeout(CHECK_TIMEOUT, task).await.ok()?.ok()
+}
+
+fn problems(health: &BackendHealth, frontend_version: Option<&str>) -> Vec<HealthProblem> {
+    let problem = |code: &str, blocking: bool, message: Message| HealthProblem {
+        code: code.to_string(),
+        blocking,
+        message,
+    };
+    let mut problems = vec![];
+    if let Some(frontend) = frontend_version.filter(|v| *v != health.app_version) {
+        problems.push(problem(
+            "version_mismatch",
+            true,
+            Message::new("health.version_mismatch")
+                .with("frontend", frontend)
+                .with("backend", &health.app_version),
+        ));
+    }
+    if !health.settings.loaded {
+        problems.push(problem(
+            "settings_unavailable",
+            true,
+            Message::new("health.settings_unavailable"),
+        ));
+    }
+    match &health.app_data {
+        None => problems.push(problem(
+            "app_data_missing",
+            true,
+            Message::new("health.app_data_missing"),
+        )),
+        Some(app_data) => {
+            // Repaired with relocate_app_data
+            if app_data.writable == Some(false) {
+                problems.push(problem(
+                    "app_data_unwritable",
+                    true,
+                    Message::new("health.app_data_unwritable").with("dir", &app_data.path),
+                ));
+            }
+            if app_data
+                .available_bytes
+                .is_some_and(|bytes| bytes < LOW_SPACE)
+            {
+                problems.push(problem(
+                    "app_data_low_space",
+                    false,
+                    Message::new("health.app_data_low_space").with("dir", &app_data.path),
+                ));
+            }
+        }
+    }
+    if health.cli.as_ref().is_some_and(|cli| !cli.resolved) {
+        problems.push(problem(
+            "cli_missing",
+            false,
+            Message::new("health.cli_missing"),
+        ));
+    }
+    // Processing is refused, but productions can still be opened and viewed
+    for finding in health.platform.iter().flat_map(|p| &p.findings) {
+        problems.push(problem(
+            "unsupported_platform",
+            false,
+            finding.message.clone(),
+        ));
+    }
+    if health.settings.recovered {
+        problems.push(problem(
+            "settings_recovered",
+            false,
+            Message::new("health.settings_recovered"),
+        ));
+    } else if !health.recovered.is_empty() {
+        problems.push(problem(
+            "data_recovered",
+            false,
+            Message::new("health.data_recovered").with("count", health.recovered.len()),
+        ));
+    }
+    if health.previous_session_crashed {
+        problems.push(problem(
+            "previous_session_crashed",
+            false,
+            Message::new("health.previous_session_crashed"),
+        ));
+    }
+    if let Some(not_installed) = &health.inspection.not_installed {
+        problems.push(problem(
+            "not_installed",
+            false,
+            Message::new("health.not_installed").with("location", &not_installed.location),
+        ));
+    }
+    if health.safe_mode {
+        problems.push(problem(
+            "safe_mode",
+            false,
+            Message::new("health.safe_mode"),
+        ));
+    }
+    for check in &health.timed_out {
+        problems.push(problem(
+            "check_timed_out",
+            false,
+            Message::new("health.check_timed_out").with("check", check),
+        ));
+    }
+    problems.sort_by_key(|p| !p.blocking);
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inspection::{NotInstalled, NotInstalledReason};
+    use crate::platform::testing::TempPaths;
+    use crate::platform_support::{PlatformDescriptor, PlatformFinding};
+
+    fn healthy() -> BackendHealth {
+        BackendHealth {
+            app_version: "0.1.0".to_string(),
+            settings: SettingsHealth {
+                loaded: true,
+                recovered: false,
+            },
+            app_data: Some(AppDataHealth {
+                path: "/data".to_string(),
+                overridden: false,
+                source: DataDirSource::Standard,
+                persistent: true,
+                writable: Some(true),
+                available_bytes: Some(LOW_SPACE * 4),
+            }),
+            rejected_app_data: vec![],
+            cli: Some(CliHealth {
+                resolved: true,
+                path: Some("/bin/gvcore-cli".to_string()),
+                error: None,
+            }),
+            platform: None,
+            recovered: vec![],
+            previous_session_crashed: false,
+            safe_mode: false,
+            inspection: InspectionStatus {
+                active: false,
+                not_installed: None,
+                disabled: vec![],
+            },
+            timed_out: vec![],
+            problems: vec![],
+            ok: true,
+        }
+    }
+
+    #[test]
+    fn only_what_leaves_the_ui_unusable_is_blocking() {
+        let health = healthy();
+        assert!(problems(&health, Some("0.1.0")).is_empty());
+        assert!(problems(&health, None).is_empty());
+
+        let mut health = healthy();
+        health.app_data.as_mut().unwrap().writable = Some(false);
+        health.cli.as_mut().unwrap().resolved = false;
+        health.platform = Some(PlatformSupport {
+            platform: PlatformDescriptor {
+                os: "windows".to_string(),
+                os_version: Some("10.0.19045".to_string()),
+                arch: "x86_64".to_string(),
+                gpu_apis: vec![],
+            },
+            supported: false,
+            findings: vec![PlatformFinding {
+                code: "no_gpu_api".to_string(),
+                message: Message::new("platform.no_gpu_api").with("apis", "Vulkan"),
+            }],
+        });
+        health.timed_out = vec!["app_data_space".to_string()];
+        health.previous_session_crashed = true;
+        health.inspection.not_installed = Some(NotInstalled {
+            reason: NotInstalledReason::ReadOnlyVolume,
+            location: "/Volumes/Game View/Game View.app".to_string(),
+        });
+        let found = problems(&health, Some("0.2.0"));
+        let codes: Vec<(&str, bool)> = found
+            .iter()
+            .map(|p| (p.code.as_str(), p.blocking))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                ("version_mismatch", true),
+                ("app_data_unwritable", true),
+                ("cli_missing", false),
+                ("unsupported_platform", false),
+                ("previous_session_crashed", false),
+                ("not_installed", false),
+                ("check_timed_out", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_session_marker_left_behind_means_a_crash() {
+        let paths = TempPaths::new();
+        let app_data = paths.root().join("data");
+        assert!(!mark_session(&app_data));
+        assert!(mark_session(&app_data));
+        end(&app_data);
+        assert!(!mark_session(&app_data));
+    }
+}
@@ -0,0 +1,796 @@
+//! Job History
+//!
+//! Persists a record of every finished job to app_data/history.jsonl, one JSON
+//! record per line below a written_by header line. Finished jobs are appended
+//! rather than rewriting the file, and the file is compacted once it outgrows
+//! COMPACT_BYTES.
+//!
+//! get_job_history filters and pages through an index kept in memory, read
+//! again only when the file has changed since, so searching hundreds of runs
+//! does not parse the file on every keystroke.
+
+use crate::artifacts::Artifact;
+use crate::auto_retry::Attempt;
+use crate::clip_progress::{ClipProgress, FrameCounts};
+use crate::commands::ProcessArgs;
+use crate::datafile;
+use crate::error::AppError;
+use crate::fsutil;
+use crate::history_sweep::{self, Verification};
+use crate::inspection::{self, Feature};
+use crate::overlap::OverlapSummary;
+use crate::platform::PathProvider;
+use crate::preview;
+use crate::profiles::CaptureType;
+use crate::recents;
+use crate::reconcile;
+use crate::registration::Registration;
+use crate::reuse::ReuseDecision;
+use crate::runner::{CliWarning, CommandSpec};
+use crate::training_metrics::TrainingMetrics;
+use crate::vram_policy::VramOverride;
+use crate::written_by::{self, FileFormat, WrittenBy};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    /// As serialized, for text the status is written into
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRecord {
+    pub job_id: String,
+    /// Shared by the per-clip jobs one batch request expanded into
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    pub status: JobStatus,
+    pub preset: String,
+    pub videos: Vec<String>,
+    pub output_dir: String,
+    pub artifact_path: Option<String>,
+    pub error: Option<String>,
+    pub started_at: u64,
+    pub finished_at: u64,
+    /// Overlap verdict for these clips, if they were analyzed before the job ran
+    #[serde(default)]
+    pub overlap: Option<OverlapSummary>,
+    /// Hex SHA-256 of the artifact, when the job produced one
+    #[serde(default)]
+    pub artifact_sha256: Option<String>,
+    #[serde(default)]
+    pub capture_type: CaptureType,
+    /// Cancelled during export, keeping the complete artifact it had written
+    #[serde(default)]
+    pub partial: bool,
+    /// Warnings the CLI printed, such as deprecated flags
+    #[serde(default)]
+    pub warnings: Vec<CliWarning>,
+    /// Time spent on each clip while extracting frames and matching cameras
+    #[serde(default)]
+    pub clips: Vec<ClipProgress>,
+    /// Seconds the whole job spent in each stage
+    #[serde(default)]
+    pub stages: BTreeMap<String, f64>,
+    /// Total length of the clips in seconds; absent when a clip's was not known
+    #[serde(default)]
+    pub input_secs: Option<f64>,
+    /// A quick preview run on proxies of the clips
+    #[serde(default)]
+    pub preview: bool,
+    /// What the job was submitted with, for clone_job_args; absent in older records
+    #[serde(default)]
+    pub args: Option<ProcessArgs>,
+    /// Whether the job built on the previous run's COLMAP database
+    #[serde(default)]
+    pub reuse: Option<ReuseDecision>,
+    /// What was run, absent when the job failed before the CLI was started
+    #[serde(default)]
+    pub command: Option<CommandSpec>,
+    /// Loss, PSNR and the other series brush reported while training
+    #[serde(default, skip_serializing_if = "TrainingMetrics::is_empty")]
+    pub metrics: TrainingMetrics,
+    /// Frames expected from the clips' durations and those extracted; absent
+    /// when a clip's duration was not known
+    #[serde(default)]
+    pub frames: Option<FrameCounts>,
+    /// The artifact was gone when the library was last reconciled
+    #[serde(default)]
+    pub missing: bool,
+    /// Given at enqueue time or with set_job_metadata
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Every run of the CLI when the job was retried; empty when it ran once
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<Attempt>,
+    /// Every artifact the job made, the primary, artifact_path, first; empty
+    /// in records from before artifacts were listed
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+    /// Who ran the job despite the GPU having too little VRAM for its preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vram_override: Option<VramOverride>,
+    /// How many images registered, whatever became of the job
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration: Option<Registration>,
+    /// What the last history sweep found on disk; absent until one has looked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<Verification>,
+}
+
+/// What get_job_history returns records matching; every part given must match
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryFilter {
+    /// Words that must each appear in the label, production or preset, in any case
+    pub query: String,
+    /// Tags the record must all carry
+    pub tags: Vec<String>,
+    /// Any of these; every status when empty
+    pub statuses: Vec<JobStatus>,
+    /// Started at or after, in seconds since the Unix epoch
+    pub since: Option<u64>,
+    /// Started before
+    pub until: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryPage {
+    /// Newest first
+    pub records: Vec<JobRecord>,
+    /// Records matching across all pages
+    pub total: usize,
+}
+
+/// The history as last read, newest first
+struct Index {
+    path: PathBuf,
+    /// Size and modification time of the file it was read from
+    stamp: Option<(u64, SystemTime)>,
+    records: Vec<JobRecord>,
+    /// What a query is matched against, lowercased, per record
+    text: Vec<String>,
+}
+
+/// One record per line, appended as jobs finish
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// Earlier versions rewrote one JSON array; it is migrated on first load
+const LEGACY_FILE: &str = "history.json";
+
+/// Beyond this size a load rewrites the file without superseded lines
+const COMPACT_BYTES: u64 = 1024 * 1024;
+
+static INDEX: Mutex<Option<Index>> = Mutex::new(None);
+
+fn history_path(paths: &impl PathProvider) -> Result<PathBuf, String> {
+    Ok(paths.app_data_dir()?.join(HISTORY_FILE))
+}
+
+/// Every record, oldest first. A record appended again replaces the earlier
+/// one in place. Lines that do not parse, such as one cut short by a crash,
+/// are dropped after the file is quarantined.
+pub fn load(paths: &impl PathProvider) -> Result<Vec<JobRecord>, String> {
+    let path = history_path(paths)?;
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return migrate(paths, &path);
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut records: Vec<JobRecord> = vec![];
+    let mut positions = HashMap::new();
+    let (mut lines, mut unreadable, mut error) = (0, 0, String::new());
+    // Files of versions from before the header start with a record
+    let skip = written_by::header(&content).is_some() as usize;
+    for line in content.lines().filter(|l| !l.trim().is_empty()).skip(skip) {
+        lines += 1;
+        match serde_json::from_str::<JobRecord>(line) {
+            Ok(record) => match positions.get(&record.job_id) {
+                Some(&i) => records[i] = record,
+                None => {
+                    positions.insert(record.job_id.clone(), records.len());
+                    records.push(record);
+                }
+            },
+            Err(e) => {
+                unreadable += 1;
+                error = written_by::explain(&content, e);
+            }
+        }
+    }
+
+    if unreadable > 0 {
+        datafile::quarantine(&path, format!("{} job history records", unreadable), error);
+        save(paths, &records)?;
+    } else if lines > records.len() && content.len() as u64 > COMPACT_BYTES {
+        save(paths, &records)?;
+    }
+    Ok(records)
+}
+
+fn migrate(paths: &impl PathProvider, path: &Path) -> Result<Vec<JobRecord>, String> {
+    let legacy = path.with_file_name(LEGACY_FILE);
+    let Some(records) = datafile::read_json::<Vec<JobRecord>>(&legacy, "the job history")? else {
+        return Ok(vec![]);
+    };
+    save(paths, &records)?;
+    std::fs::remove_file(&legacy).ok();
+    Ok(records)
+}
+
+/// Replace the whole history
+pub fn save(paths: &impl PathProvider, records: &[JobRecord]) -> Result<(), String> {
+    let header = written_by::stamp(FileFormat::History, &serde_json::Map::new());
+    let mut content = serde_json::to_string(&header).map_err(|e| e.to_string())?;
+    content.push('\n');
+    for record in records {
+        content.push_str(&serde_json::to_string(record).map_err(|e| e.to_string())?);
+        content.push('\n');
+    }
+    INDEX.lock().unwrap().take();
+    fsutil::write_atomic(&history_path(paths)?, content.as_bytes())
+}
+
+/// Append a finished job to the history
+pub fn record(paths: &impl PathProvider, record: JobRecord) -> Result<(), String> {
+    // A launch that is not installed keeps no history
+    if !inspection::allows(Feature::History) {
+        return Ok(());
+    }
+    amend(paths, &[record])
+}
+
+/// Append records, replacing any earlier ones of the same jobs
+pub fn amend(paths: &impl PathProvider, records: &[JobRecord]) -> Result<(), String> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    let path = history_path(paths)?;
+    // A file of an earlier version is migrated, and one another version wrote
+    // is rewritten under this version's header, before anything is added to it
+    if header_of(&path) != Some(WrittenBy::current(FileFormat::History)) {
+        let records = load(paths)?;
+        save(paths, &records)?;
+    }
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record).map_err(|e| e.to_string())?);
+        lines.push('\n');
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| e.to_string())?;
+    // A line cut short by a crash must not swallow the first new one
+    if ends_mid_line(&mut file).map_err(|e| e.to_string())? {
+        lines.insert(0, '\n');
+    }
+    INDEX.lock().unwrap().take();
+    // One write, so a crash leaves at most the last line incomplete
+    file.write_all(lines.as_bytes()).map_err(|e| e.to_string())
+}
+
+fn header_of(path: &Path) -> Option<WrittenBy> {
+    let mut first_line = String::new();
+    BufReader::new(std::fs::File::open(path).ok()?)
+        .read_line(&mut first_line)
+        .ok()?;
+    written_by::header(&first_line)
+}
+
+fn ends_mid_line(file: &mut std::fs::File) -> std::io::Result<bool> {
+    if file.metadata()?.len() == 0 {
+        return Ok(false);
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    Ok(last[0] != b'\n')
+}
+
+/// The arguments a finished job was submitted with, to run it again. A
+/// preview's come back for the full-quality run, into the production itself.
+#[tauri::command]
+pub async fn clone_job_args(app: AppHandle, job_id: String) -> Result<ProcessArgs, AppError> {
+    clone_args(&app, &job_id)
+}
+
+fn clone_args(paths: &impl PathProvider, job_id: &str) -> Result<ProcessArgs, AppError> {
+    let record = load(paths)?
+        .into_iter()
+        .find(|r| r.job_id == job_id)
+        .ok_or_else(|| AppError::NotFound(job_id.to_string()))?;
+    let mut args = record
+        .args
+        .ok_or_else(|| AppError::NotFound(format!("The arguments of {}", job_id)))?;
+    if args.preview_mode {
+        args.preview_mode = false;
+        args.output_dir = preview::full_output_dir(&args.output_dir);
+    }
+    Ok(args)
+}
+
+/// What a job ran: the structured spec, or with `shell_quoted` one line to
+/// paste into this platform's shell
+#[tauri::command]
+pub async fn get_job_command(
+    app: AppHandle,
+    job_id: String,
+    shell_quoted: bool,
+) -> Result<JobCommand, AppError> {
+    let spec = job_command(&app, &job_id)?;
+    Ok(if shell_quoted {
+        JobCommand::ShellQuoted(spec.shell_quoted())
+    } else {
+        JobCommand::Spec(spec)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum JobCommand {
+    Spec(CommandSpec),
+    ShellQuoted(String),
+}
+
+fn job_command(paths: &impl PathProvider, job_id: &str) -> Result<CommandSpec, AppError> {
+    load(paths)?
+        .into_iter()
+        .find(|r| r.job_id == job_id)
+        .ok_or_else(|| AppError::NotFound(job_id.to_string()))?
+        .command
+        .ok_or_else(|| AppError::NotFound(format!("The command of {}", job_id)))
+}
+
+/// Get the history of finished jobs matching `filter`, newest first, skipping
+/// `offset` records and returning at most `limit`
+#[tauri::command]
+pub async fn get_job_history(
+    app: AppHandle,
+    filter: Option<HistoryFilter>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<HistoryPage, String> {
+    reconcile::check(&app).await;
+    history_sweep::start_if_due(&app);
+    query(
+        &app,
+        &filter.unwrap_or_default(),
+        offset.unwrap_or(0),
+        limit.unwrap_or(usize::MAX),
+    )
+}
+
+/// Replace the label and tags of a finished job
+#[tauri::command]
+pub async fn set_job_metadata(
+    app: AppHandle,
+    job_id: String,
+    label: Option<String>,
+    tags: Vec<String>,
+) -> Result<JobRecord, AppError> {
+    set_metadata(&app, &job_id, label.as_deref(), &tags)
+}
+
+/// Add warnings to a recorded job, e.g. of hooks that ran after it was
+/// recorded; nothing is done when the job has no record
+pub fn add_warnings(
+    paths: &impl PathProvider,
+    job_id: &str,
+    warnings: &[CliWarning],
+) -> Result<(), String> {
+    let Some(mut record) = load(paths)?.into_iter().find(|r| r.job_id == job_id) else {
+        return Ok(());
+    };
+    record.warnings.extend(warnings.iter().cloned());
+    amend(paths, std::slice::from_ref(&record))
+}
+
+/// A label trimmed, or None when nothing is left
+pub fn normalize_label(label: Option<&str>) -> Option<String> {
+    label
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+}
+
+fn set_metadata(
+    paths: &impl PathProvider,
+    job_id: &str,
+    label: Option<&str>,
+    tags: &[String],
+) -> Result<JobRecord, AppError> {
+    let mut record = load(paths)?
+        .into_iter()
+        .find(|r| r.job_id == job_id)
+        .ok_or_else(|| AppError::NotFound(job_id.to_string()))?;
+    record.label = normalize_label(label);
+    record.tags = recents::normalize_tags(tags);
+    amend(paths, std::slice::from_ref(&record))?;
+    Ok(record)
+}
+
+fn query(
+    paths: &impl PathProvider,
+    filter: &HistoryFilter,
+    offset: usize,
+    limit: usize,
+) -> Result<HistoryPage, String> {
+    with_matching(paths, filter, |matching| HistoryPage {
+        total: matching.len(),
+        records: matching
+            .iter()
+            .skip(offset)
+            .take(limit)
+            .map(|r| (*r).clone())
+            .collect(),
+    })
+}
+
+/// The records matching `filter`, newest first
+pub fn matching(
+    paths: &impl PathProvider,
+    filter: &HistoryFilter,
+) -> Result<Vec<JobRecord>, String> {
+    with_matching(paths, filter, |matching| {
+        matching.iter().map(|r| (*r).clone()).collect()
+    })
+}
+
+/// Call `f` with the records matching `filter`, reading the history again
+/// first when the file has changed since the index was built
+fn with_matching<T>(
+    paths: &impl PathProvider,
+    filter: &HistoryFilter,
+    f: impl FnOnce(&[&JobRecord]) -> T,
+) -> Result<T, String> {
+    let path = history_path(paths)?;
+    let current = {
+        let index = INDEX.lock().unwrap();
+        index
+            .as_ref()
+            .is_some_and(|i| i.path == path && i.stamp == stamp(&path))
+    };
+    if !current {
+        // Read outside the lock, as loading may rewrite the file
+        let mut records = load(paths)?;
+        records.reverse();
+        let text = records.iter().map(searchable).collect();
+        *INDEX.lock().unwrap() = Some(Index {
+            stamp: stamp(&path),
+            path,
+            records,
+            text,
+        });
+    }
+
+    let words: Vec<String> = filter
+        .query
+        .to_lowercase()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    let tags = recents::normalize_tags(&filter.tags);
+    let index = INDEX.lock().unwrap();
+    let matching: Vec<&JobRecord> = index
+        .iter()
+        .flat_map(|index| index.records.iter().zip(&index.text))
+        .filter(|(record, text)| matches(record, text, filter, &words, &tags))
+        .map(|(record, _)| record)
+        .collect();
+    Ok(f(&matching))
+}
+
+fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+/// The label, production and preset of a record, lowercased
+fn searchable(record: &JobRecord) -> String {
+    let production = Path::new(&record.output_dir)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    [
+        record.label.as_deref().unwrap_or(""),
+        &production,
+        &record.preset,
+    ]
+    .join("\n")
+    .to_lowercase()
+}
+
+/// `words` and `tags` are the filter's, lowercased
+fn matches(
+    record: &JobRecord,
+    text: &str,
+    filter: &HistoryFilter,
+    words: &[String],
+    tags: &[String],
+) -> bool {
+    words.iter().all(|word| text.contains(word.as_str()))
+        && tags.iter().all(|tag| record.tags.contains(tag))
+        && (filter.statuses.is_empty() || filter.statuses.contains(&record.status))
+        && filter
+            .since
+            .map_or(true, |since| record.started_at >= since)
+        && filter.until.map_or(true, |until| record.started_at < until)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    pub fn job(job_id: &str, status: JobStatus) -> JobRecord {
+        JobRecord {
+            job_id: job_id.to_string(),
+            batch_id: None,
+            status,
+            preset: "fast".to_string(),
+            videos: vec![],
+            output_dir: "/productions/harbour".to_string(),
+            artifact_path: None,
+            error: None,
+            started_at: 0,
+            finished_at: 0,
+            overlap: None,
+            artifact_sha256: None,
+            capture_type: Default::default(),
+            partial: false,
+            warnings: vec![],
+            clips: vec![],
+            stages: BTreeMap::new(),
+            input_secs: None,
+            preview: false,
+            args: None,
+            reuse: None,
+            command: None,
+            metrics: Default::default(),
+            frames: None,
+            missing: false,
+            label: None,
+            tags: vec![],
+            attempts: vec![],
+            artifacts: vec![],
+            vram_override: None,
+            registration: None,
+            verification: None,
+        }
+    }
+
+    fn job_ids(paths: &TempPaths) -> Vec<(String, JobStatus)> {
+        load(paths)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.job_id, r.status))
+            .collect()
+    }
+
+    #[test]
+    fn records_are_appended_and_amended_in_place() {
+        let paths = TempPaths::new();
+        record(&paths, job("job-1", JobStatus::Failed)).unwrap();
+        record(&paths, job("job-2", JobStatus::Completed)).unwrap();
+        amend(&paths, &[job("job-1", JobStatus::Cancelled)]).unwrap();
+
+        assert_eq!(
+            job_ids(&paths),
+            [
+                ("job-1".to_string(), JobStatus::Cancelled),
+                ("job-2".to_string(), JobStatus::Completed)
+            ]
+        );
+        let content = std::fs::read_to_string(history_path(&paths).unwrap()).unwrap();
+        // The header and three records
+        assert_eq!(content.lines().count(), 4);
+        assert_eq!(
+            written_by::header(&content),
+            Some(WrittenBy::current(FileFormat::History))
+        );
+    }
+
+    #[test]
+    fn a_line_cut_short_loses_only_that_record() {
+        let paths = TempPaths::new();
+        record(&paths, job("job-1", JobStatus::Completed)).unwrap();
+        let path = history_path(&paths).unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(br#"{"jobId":"job-2","sta"#).unwrap();
+        record(&paths, job("job-3", JobStatus::Completed)).unwrap();
+
+        let ids: Vec<String> = job_ids(&paths).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, ["job-1", "job-3"]);
+        // The damaged file was set aside and replaced by the readable records
+        let set_aside = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .any(|e| {
+                e.file_name()
+                    .to_string_lossy()
+                    .starts_with("history.jsonl.corrupt-")
+            });
+        assert!(set_aside);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn previews_are_cloned_for_the_full_quality_run() {
+        let paths = TempPaths::new();
+        let args: ProcessArgs = serde_json::from_value(serde_json::json!({
+            "videos": ["/clips/cam1.mp4"],
+            "output_dir": preview::output_dir("/productions/harbour"),
+            "preset": "high",
+            "preview_mode": true,
+        }))
+        .unwrap();
+        let preview = JobRecord {
+            preview: true,
+            args: Some(args.clone()),
+            ..job("job-1", JobStatus::Completed)
+        };
+        record(&paths, preview).unwrap();
+        record(&paths, job("job-2", JobStatus::Completed)).unwrap();
+
+        let cloned = clone_args(&paths, "job-1").unwrap();
+        assert!(!cloned.preview_mode);
+        assert_eq!(cloned.output_dir, "/productions/harbour");
+        assert_eq!(cloned.preset, "high");
+
+        // Records from before arguments were kept cannot be cloned
+        assert_eq!(clone_args(&paths, "job-2").unwrap_err().code(), "not_found");
+        assert_eq!(clone_args(&paths, "job-3").unwrap_err().code(), "not_found");
+    }
+
+    #[test]
+    fn the_json_history_of_earlier_versions_is_migrated() {
+        let paths = TempPaths::new();
+        let legacy = paths.app_data_dir().unwrap().join(LEGACY_FILE);
+        fsutil::write_json_atomic(&legacy, &[job("job-1", JobStatus::Completed)]).unwrap();
+
+        record(&paths, job("job-2", JobStatus::Failed)).unwrap();
+        let ids: Vec<String> = job_ids(&paths).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, ["job-1", "job-2"]);
+        assert!(!legacy.exists());
+    }
+
+    fn found(paths: &TempPaths, filter: serde_json::Value) -> Vec<String> {
+        let filter: HistoryFilter = serde_json::from_value(filter).unwrap();
+        query(paths, &filter, 0, usize::MAX)
+            .unwrap()
+            .records
+            .into_iter()
+            .map(|r| r.job_id)
+            .collect()
+    }
+
+    #[test]
+    fn the_history_is_searched_in_any_case_and_script() {
+        let paths = TempPaths::new();
+        let tagged = |id: &str, status, label: &str, tags: &[&str], started_at| JobRecord {
+            label: Some(label.to_string()),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            started_at,
+            ..job(id, status)
+        };
+        amend(
+            &paths,
+            &[
+                tagged(
+                    "job-1",
+                    JobStatus::Completed,
+                    "Drohnenflug über Köln",
+                    &["drone"],
+                    100,
+                ),
+                tagged(
+                    "job-2",
+                    JobStatus::Failed,
+                    "Harbour at dusk",
+                    &["drone", "client a"],
+                    200,
+                ),
+                JobRecord {
+                    output_dir: "/productions/Ωmega Stage".to_string(),
+                    preset: "high".to_string(),
+                    started_at: 300,
+                    ..job("job-3", JobStatus::Completed)
+                },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            found(&paths, serde_json::json!({})),
+            ["job-3", "job-2", "job-1"]
+        );
+        assert_eq!(
+            found(&paths, serde_json::json!({"query": "ÜBER köln"})),
+            ["job-1"]
+        );
+        assert_eq!(
+            found(&paths, serde_json::json!({"query": "ωMEGA high"})),
+            ["job-3"]
+        );
+        assert_eq!(
+            found(&paths, serde_json::json!({"query": "harbour"})),
+            ["job-2", "job-1"]
+        );
+        assert_eq!(
+            found(&paths, serde_json::json!({"tags": [" Drone ", "CLIENT A"]})),
+            ["job-2"]
+        );
+        assert_eq!(
+            found(
+                &paths,
+                serde_json::json!({"statuses": ["completed", "cancelled"]})
+            ),
+            ["job-3", "job-1"]
+        );
+        assert_eq!(
+            found(&paths, serde_json::json!({"since": 200, "until": 300})),
+            ["job-2"]
+        );
+
+        let page = query(&paths, &HistoryFilter::default(), 1, 1).unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.records[0].job_id, "job-2");
+
+        // Changes made since the last search are found by the next one
+        let updated = set_metadata(
+            &paths,
+            "job-3",
+            Some("  Rehearsal "),
+            &["Stage".to_string()],
+        )
+        .unwrap();
+        assert_eq!(updated.label.as_deref(), Some("Rehearsal"));
+        assert_eq!(
+            found(
+                &paths,
+                serde_json::json!({"query": "rehearsal", "tags": ["stage"]})
+            ),
+            ["job-3"]
+        );
+        record(&paths, job("job-4", JobStatus::Completed)).unwrap();
+        assert_eq!(
+            found(&paths, serde_json::json!({"query": "harbour"})),
+            ["job-4", "job-2", "job-1"]
+        );
+        assert_eq!(
+            set_metadata(&paths, "job-5", None, &[]).unwrap_err().code(),
+            "not_found"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! History Export
+//!
+//! Writes the job history as CSV for reporting in a spreadsheet, one row per
+//! job matching the same filter get_job_history takes, newest first. Rows go
+//! out as they are made, to a `.partial` file renamed into place once it is
+//! complete, so a long history is never held as text and an interrupted
+//! export leaves nothing half written. Artifact size and splat count are read
+//! from the primary artifact, and left empty when it is gone; the paths of a
+//! job's other artifacts follow, separated by semicolons.
+
+use crate::conversion;
+use crate::error::AppError;
+use crate::history::{self, HistoryFilter, JobRecord};
+use crate::naming;
+use crate::path_policy::PathPolicy;
+use crate::reconcile;
+use crate::scheduler::{self, Pool};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+
+/// Stages given a duration column, in the order a job runs them
+const STAGES: [&str; 4] = [
+    "extracting_frames",
+    "detecting_cameras",
+    "training_splats",
+    "exporting",
+];
+
+/// Write the jobs matching `filter` to a CSV file at `path`; returns the
+/// number of rows written, not counting the header
+#[tauri::command]
+pub async fn export_history_csv(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+    filter: Option<HistoryFilter>,
+) -> Result<usize, AppError> {
+    policy.check_target(&path)?;
+    reconcile::check(&app).await;
+    let records = history::matching(&app, &filter.unwrap_or_default())?;
+    scheduler::run_blocking(Pool::Io, move || export(&records, Path::new(&path))).await?
+}
+
+fn export(records: &[JobRecord], path: &Path) -> Result<usize, AppError> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    match write_rows(records, &partial) {
+        Ok(rows) => {
+            std::fs::rename(&partial, path)?;
+            Ok(rows)
+        }
+        Err(e) => {
+            std::fs::remove_file(&partial).ok();
+            Err(e.into())
+        }
+    }
+}
+
+fn write_rows(records: &[JobRecord], path: &Path) -> io::Result<usize> {
+    let mut out = BufWriter::new(std::fs::File::create(path)?);
+    let mut header = vec![
+        "job_id".to_string(),
+        "label".to_string(),
+        "preset".to_string(),
+        "clips".to_string(),
+        "clip_minutes".to_string(),
+        "started_at".to_string(),
+        "finished_at".to_string(),
+    ];
+    header.extend(STAGES.iter().map(|stage| format!("{}_seconds", stage)));
+    header.extend(
+        ["status", "artifact_bytes", "splat_count", "other_artifacts"]
+            .iter()
+            .map(|c| c.to_string()),
+    );
+    write_row(&mut out, &header)?;
+    for record in records {
+        write_row(&mut out, &row(record))?;
+    }
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    Ok(records.len())
+}
+
+fn row(record: &JobRecord) -> Vec<String> {
+    let artifact = record
+        .artifact_path
+        .as_deref()
+        .map(Path::new)
+        .filter(|_| !record.missing);
+    let mut row = vec![
+        record.job_id.clone(),
+        record.label.clone().unwrap_or_default(),
+        record.preset.clone(),
+        record.videos.len().to_string(),
+        record
+            .input_secs
+            .map(|secs| format!("{:.2}", secs / 60.0))
+            .unwrap_or_default(),
+        naming::iso8601(record.started_at),
+        naming::iso8601(record.finished_at),
+    ];
+    row.extend(STAGES.iter().map(|stage| {
+        record
+            .stages
+            .get(*stage)
+            .map(|secs| format!("{:.1}", secs))
+            .unwrap_or_default()
+    }));
+    row.push(record.status.as_str().to_string());
+    row.push(
+        artifact
+            .and_then(|a| std::fs::metadata(a).ok())
+            .map(|m| m.len().to_string())
+            .unwrap_or_default(),
+    );
+    row.push(
+        artifact
+            .and_then(|a| conversion::complete_splat_count(a).ok())
+            .map(|count| count.to_string())
+            .unwrap_or_default(),
+    );
+    let others: Vec<&str> = record
+        .artifacts
+        .iter()
+        .map(|a| a.path.as_str())
+        .filter(|path| Some(*path) != record.artifact_path.as_deref())
+        .collect();
+    row.push(others.join(";"));
+    row
+}
+
+fn write_row(out: &mut impl Write, fields: &[String]) -> io::Result<()> {
+    let fields: Vec<String> = fields.iter().map(|f| escape(f)).collect();
+    // CRLF, as RFC 4180 has it and spreadsheets expect
+    write!(out, "{}\r\n", fields.join(","))
+}
+
+/// Quote a field holding a comma, quote or line break, doubling its quotes
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::Artifact;
+    use crate::conversion::tests::sample_ply;
+    use crate::history::tests::job;
+    use crate::history::JobStatus;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn rows_are_escaped_and_read_the_artifact() {
+        let paths = TempPaths::new();
+        let artifact = paths.root().join("splat.ply");
+        std::fs::write(&artifact, sample_ply(&[[0.0; 14]; 3])).unwrap();
+        let completed = JobRecord {
+            label: Some("Harbour, \"take 2\"\nat dusk".to_string()),
+            videos: vec!["/clips/cam1.mp4".to_string(), "/clips/cam2.mp4".to_string()],
+            input_secs: Some(150.0),
+            started_at: 1_709_993_109,
+            finished_at: 1_709_996_709,
+            stages: [("training_splats".to_string(), 3000.0)].into(),
+            artifact_path: Some(artifact.to_string_lossy().to_string()),
+            artifacts: vec![
+                Artifact::named(artifact.to_string_lossy().to_string(), None),
+                Artifact::named("/p/web.spz".to_string(), None),
+            ],
+            ..job("job-1", JobStatus::Completed)
+        };
+        let failed = job("job-2", JobStatus::Failed);
+
+        let path = paths.root().join("history.csv");
+        assert_eq!(export(&[completed, failed], &path).unwrap(), 2);
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(
+            lines[0],
+            "job_id,label,preset,clips,clip_minutes,started_at,finished_at,\
+             extracting_frames_seconds,detecting_cameras_seconds,training_splats_seconds,\
+             exporting_seconds,status,artifact_bytes,splat_count,other_artifacts"
+        );
+        let size = std::fs::metadata(&artifact).unwrap().len();
+        assert_eq!(
+            lines[1],
+            format!(
+                "job-1,\"Harbour, \"\"take 2\"\"\nat dusk\",fast,2,2.50,\
+                 2024-03-09T14:05:09Z,2024-03-09T15:05:09Z,,,3000.0,,completed,{},3,/p/web.spz",
+                size
+            )
+        );
+        assert_eq!(
+            lines[2],
+            "job-2,,fast,0,,1970-01-01T00:00:00Z,1970-01-01T00:00:00Z,,,,,failed,,,"
+        );
+        assert_eq!(lines[3], "");
+        assert!(!paths.root().join("history.csv.partial").exists());
+    }
+}
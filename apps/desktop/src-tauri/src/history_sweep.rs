@@ -0,0 +1,505 @@
+//! History Sweep
+//!
+//! History records outlive what they describe: logs are cleaned up, artifacts
+//! are moved or rewritten and sidecars are replaced by later runs, while the
+//! history goes on showing them as they were. A sweep checks each record
+//! against the disk: that every artifact is there with the size and SHA-256
+//! it had, that the job's log is still there and that the production's
+//! sidecar still describes the job. Artifacts above FULL_HASH_BYTES are hashed
+//! in samples instead, compared with the samples of the previous sweep. What
+//! a sweep finds is kept on the record as its verification; artifacts, logs
+//! and sidecars are only ever read.
+//!
+//! verify_history sweeps on demand, and opening the history view starts a
+//! sweep in the background once the last is over a day old. Either runs on
+//! the scheduler's background pool as an operation, which cancel_operation or
+//! cancel_history_verification stops. Records checked by then keep their
+//! verification, and the next sweep starts with those checked longest ago.
+
+use crate::artifacts::Artifact;
+use crate::error::AppError;
+use crate::history::{self, JobRecord};
+use crate::job_log;
+use crate::operations::{self, OperationHandle, OperationKind};
+use crate::path_policy::resolve_app_data;
+use crate::platform::PathProvider;
+use crate::scheduler::{self, Pool};
+use crate::sidecar;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+/// Artifacts up to this size are hashed whole and compared with the SHA-256
+/// recorded when their job finished
+const FULL_HASH_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Blocks hashed from a larger artifact, spread evenly across it
+const SAMPLES: u64 = 16;
+const SAMPLE_BYTES: u64 = 1024 * 1024;
+
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+/// Age of the last sweep at which opening the history view starts another
+const SWEEP_AFTER_SECS: u64 = 24 * 60 * 60;
+
+// Held while a sweep runs; one started in the background skips when it is taken
+static SWEEPING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+// When the last sweep finished, read from the records on first use
+static LAST_SWEEP: Mutex<Option<u64>> = Mutex::new(None);
+
+/// What a sweep found for one record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Verification {
+    /// Seconds since the Unix epoch
+    pub verified_at: u64,
+    /// Empty when the disk matched the record
+    pub discrepancies: Vec<Discrepancy>,
+    /// SHA-256 of the samples of each artifact too large to hash whole, by path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub samples: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Discrepancy {
+    ArtifactMissing {
+        path: String,
+    },
+    SizeChanged {
+        path: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// The artifact's SHA-256 differs from the one recorded when the job finished
+    HashMismatch {
+        path: String,
+    },
+    /// Samples of an artifact too large to hash whole differ from the last sweep's
+    SamplesChanged {
+        path: String,
+    },
+    ArtifactUnreadable {
+        path: String,
+        error: String,
+    },
+    LogMissing,
+    SidecarMissing {
+        dir: String,
+    },
+    SidecarUnreadable {
+        dir: String,
+        error: String,
+    },
+    /// The sidecar describes another job, such as a later run into the same production
+    SidecarReplaced {
+        dir: String,
+        job_id: String,
+    },
+    /// The sidecar's checksum of the artifact differs from the record's
+    SidecarMismatch {
+        dir: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SweepSummary {
+    /// Records checked
+    pub checked: usize,
+    /// The sweep stopped before every record was checked
+    pub cancelled: bool,
+    /// What was found, by job id, for the records where the disk did not match
+    pub discrepancies: BTreeMap<String, Vec<Discrepancy>>,
+}
+
+/// Check every history record against the disk and note what no longer matches
+#[tauri::command]
+pub async fn verify_history(app: AppHandle) -> Result<SweepSummary, AppError> {
+    let _sweeping = SWEEPING.lock().await;
+    sweep(&app).await
+}
+
+/// Stop a running history sweep, keeping what it found so far
+#[tauri::command]
+pub async fn cancel_history_verification() -> Result<(), AppError> {
+    operations::cancel_kind(OperationKind::HistorySweep);
+    Ok(())
+}
+
+/// Sweep in the background when the last sweep is over a day old and none runs
+pub fn start_if_due(app: &AppHandle) {
+    let last = *LAST_SWEEP
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| last_verified(&history::load(app).unwrap_or_default()));
+    if job_log::unix_timestamp() < last + SWEEP_AFTER_SECS {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let Ok(_sweeping) = SWEEPING.try_lock() else {
+            return;
+        };
+        if let Err(e) = sweep(&app).await {
+            eprintln!("Failed to sweep the job history: {}", e);
+        }
+    });
+}
+
+fn last_verified(records: &[JobRecord]) -> u64 {
+    records
+        .iter()
+        .filter_map(|r| r.verification.as_ref())
+        .map(|v| v.verified_at)
+        .max()
+        .unwrap_or(0)
+}
+
+async fn sweep(app: &AppHandle) -> Result<SweepSummary, AppError> {
+    let app_data = app.app_data_dir()?;
+    let mut records = history::load(app)?;
+    // Those checked longest ago first, so a cancelled sweep is taken up where it stopped
+    records.sort_by_key(|r| r.verification.as_ref().map(|v| v.verified_at));
+    let subject = app_data.join(history::HISTORY_FILE);
+    let operation =
+        OperationHandle::start(app, OperationKind::HistorySweep, &subject.to_string_lossy());
+    let (verified, cancelled) = scheduler::run_blocking(Pool::Background, move || {
+        let total = records.len() as u64;
+        let mut verified = vec![];
+        for record in &records {
+            match verify(&app_data, record, operation.token()) {
+                Ok(verification) => verified.push((record.job_id.clone(), verification)),
+                Err(_) => return (verified, true),
+            }
+            operation.progress(verified.len() as u64, Some(total));
+        }
+        (verified, false)
+    })
+    .await?;
+
+    let summary = annotate(app, verified, cancelled)?;
+    *LAST_SWEEP.lock().unwrap() = Some(job_log::unix_timestamp());
+    app.emit("history-verified", &summary).ok();
+    Ok(summary)
+}
+
+/// Put each verification on its record, as the history is now
+fn annotate(
+    paths: &impl PathProvider,
+    verified: Vec<(String, Verification)>,
+    cancelled: bool,
+) -> Result<SweepSummary, AppError> {
+    let mut summary = SweepSummary {
+        checked: verified.len(),
+        cancelled,
+        discrepancies: BTreeMap::new(),
+    };
+    let mut verified: HashMap<String, Verification> = verified.into_iter().collect();
+    // Read again, so changes made during the sweep are kept
+    let mut records = history::load(paths)?;
+    for record in &mut records {
+        if let Some(verification) = verified.remove(&record.job_id) {
+            if !verification.discrepancies.is_empty() {
+                let found = verification.discrepancies.clone();
+                summary.discrepancies.insert(record.job_id.clone(), found);
+            }
+            record.verification = Some(verification);
+        }
+    }
+    history::save(paths, &records)?;
+    Ok(summary)
+}
+
+/// Compare a record with the disk; fails only when cancelled
+fn verify(
+    app_data: &Path,
+    record: &JobRecord,
+    cancel: &AtomicBool,
+) -> Result<Verification, AppError> {
+    let previous = record.verification.as_ref().map(|v| &v.samples);
+    let mut discrepancies = vec![];
+    let mut samples = BTreeMap::new();
+
+    for artifact in recorded_artifacts(record) {
+        let path = Path::new(&artifact.path);
+        let Ok(metadata) = std::fs::metadata(path) else {
+            discrepancies.push(Discrepancy::ArtifactMissing {
+                path: artifact.path.clone(),
+            });
+            continue;
+        };
+        let actual = metadata.len();
+        // A size that differs already says the artifact changed
+        if artifact.bytes > 0 && actual != artifact.bytes {
+            discrepancies.push(Discrepancy::SizeChanged {
+                path: artifact.path.clone(),
+                expected: artifact.bytes,
+                actual,
+            });
+            continue;
+        }
+        let sampled = actual > FULL_HASH_BYTES;
+        if !sampled && artifact.sha256.is_none() {
+            continue;
+        }
+        let digest = match digest(path, actual, sampled, cancel) {
+            Ok(digest) => digest,
+            Err(AppError::Cancelled) => return Err(AppError::Cancelled),
+            Err(e) => {
+                discrepancies.push(Discrepancy::ArtifactUnreadable {
+                    path: artifact.path.clone(),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if sampled {
+            let before = previous.and_then(|p| p.get(&artifact.path));
+            if before.is_some_and(|before| *before != digest) {
+                discrepancies.push(Discrepancy::SamplesChanged {
+                    path: artifact.path.clone(),
+                });
+            }
+            samples.insert(artifact.path.clone(), digest);
+        } else if artifact
+            .sha256
+            .as_deref()
+            .is_some_and(|expected| !expected.eq_ignore_ascii_case(&digest))
+        {
+            discrepancies.push(Discrepancy::HashMismatch {
+                path: artifact.path.clone(),
+            });
+        }
+    }
+
+    let log = resolve_app_data(app_data, &format!("logs/{}.log", record.job_id));
+    if !log.is_ok_and(|log| log.is_file()) {
+        discrepancies.push(Discrepancy::LogMissing);
+    }
+
+    if let Some(discrepancy) = sidecar_discrepancy(record) {
+        discrepancies.push(discrepancy);
+    }
+
+    Ok(Verification {
+        verified_at: job_log::unix_timestamp(),
+        discrepancies,
+        samples,
+    })
+}
+
+// The artifacts a record lists, or for records from before they were listed its one artifact
+fn recorded_artifacts(record: &JobRecord) -> Vec<Artifact> {
+    if !record.artifacts.is_empty() {
+        return record.artifacts.clone();
+    }
+    record
+        .artifact_path
+        .iter()
+        // In per-clip mode, the directory of the productions
+        .filter(|path| !Path::new(path).is_dir())
+        .map(|path| Artifact {
+            sha256: record.artifact_sha256.clone(),
+            ..Artifact::named(path.clone(), None)
+        })
+        .collect()
+}
+
+fn sidecar_discrepancy(record: &JobRecord) -> Option<Discrepancy> {
+    let dir = Path::new(record.artifact_path.as_deref()?).parent()?;
+    // Gone with the artifact, which is reported already
+    if !dir.is_dir() {
+        return None;
+    }
+    let dir_name = dir.to_string_lossy().to_string();
+    match sidecar::read(dir) {
+        Ok(None) => Some(Discrepancy::SidecarMissing { dir: dir_name }),
+        Err(error) => Some(Discrepancy::SidecarUnreadable {
+            dir: dir_name,
+            error,
+        }),
+        Ok(Some(sidecar)) if sidecar.job_id != record.job_id => {
+            Some(Discrepancy::SidecarReplaced {
+                dir: dir_name,
+                job_id: sidecar.job_id,
+            })
+        }
+        Ok(Some(sidecar)) => match (&sidecar.artifact_sha256, &record.artifact_sha256) {
+            (Some(theirs), Some(ours)) if !theirs.eq_ignore_ascii_case(ours) => {
+                Some(Discrepancy::SidecarMismatch { dir: dir_name })
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Hex SHA-256 of a file of `len` bytes, or with `sampled` of its length and
+/// SAMPLES blocks spread across it
+fn digest(path: &Path, len: u64, sampled: bool, cancel: &AtomicBool) -> Result<String, AppError> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let ranges: Vec<(u64, u64)> = if sampled && len > SAMPLES * SAMPLE_BYTES {
+        hasher.update(len.to_le_bytes());
+        let step = (len - SAMPLE_BYTES) / (SAMPLES - 1);
+        (0..SAMPLES).map(|i| (i * step, SAMPLE_BYTES)).collect()
+    } else {
+        vec![(0, len)]
+    };
+
+    let mut buffer = vec![0u8; CHUNK_BYTES];
+    for (start, count) in ranges {
+        file.seek(SeekFrom::Start(start))?;
+        let mut left = count;
+        while left > 0 {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(AppError::Cancelled);
+            }
+            let wanted = left.min(CHUNK_BYTES as u64) as usize;
+            let read = file.read(&mut buffer[..wanted])?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            left -= read as u64;
+        }
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::ArtifactKind;
+    use crate::history::tests::job;
+    use crate::history::JobStatus;
+    use crate::platform::testing::TempPaths;
+    use crate::sidecar::Sidecar;
+
+    fn sha256(contents: &[u8]) -> String {
+        Sha256::digest(contents)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    // A finished job with its artifact, sidecar and log in place
+    fn finished(paths: &TempPaths, job_id: &str, contents: &[u8]) -> JobRecord {
+        let dir = paths.root().join(job_id);
+        std::fs::create_dir_all(&dir).unwrap();
+        let artifact = dir.join("output.ply");
+        std::fs::write(&artifact, contents).unwrap();
+        let artifact_path = artifact.to_string_lossy().to_string();
+        let sidecar = Sidecar {
+            job_id: job_id.to_string(),
+            artifact_path: artifact_path.clone(),
+            artifact_sha256: Some(sha256(contents)),
+            ..Default::default()
+        };
+        sidecar::write(&dir, &sidecar).unwrap();
+        let logs = paths.app_data_dir().unwrap().join("logs");
+        std::fs::create_dir_all(&logs).unwrap();
+        std::fs::write(logs.join(format!("{}.log", job_id)), "done").unwrap();
+        JobRecord {
+            artifact_path: Some(artifact_path.clone()),
+            artifact_sha256: Some(sha256(contents)),
+            artifacts: vec![Artifact {
+                kind: ArtifactKind::Ply,
+                path: artifact_path,
+                bytes: contents.len() as u64,
+                sha256: Some(sha256(contents)),
+            }],
+            ..job(job_id, JobStatus::Completed)
+        }
+    }
+
+    fn found(paths: &TempPaths, record: &JobRecord) -> Vec<Discrepancy> {
+        let app_data = paths.app_data_dir().unwrap();
+        verify(&app_data, record, &AtomicBool::new(false))
+            .unwrap()
+            .discrepancies
+    }
+
+    #[test]
+    fn records_are_checked_against_the_disk_without_touching_it() {
+        let paths = TempPaths::new();
+        let intact = finished(&paths, "job-1", b"splats");
+        assert!(found(&paths, &intact).is_empty());
+
+        let rewritten = finished(&paths, "job-2", b"splats");
+        let artifact = rewritten.artifact_path.clone().unwrap();
+        std::fs::write(&artifact, b"SPLATS").unwrap();
+        let log = paths.app_data_dir().unwrap().join("logs/job-2.log");
+        std::fs::remove_file(&log).unwrap();
+        let dir = Path::new(&artifact).parent().unwrap();
+        let replaced = Sidecar {
+            job_id: "job-9".to_string(),
+            ..sidecar::read(dir).unwrap().unwrap()
+        };
+        sidecar::write(dir, &replaced).unwrap();
+        assert_eq!(
+            found(&paths, &rewritten),
+            [
+                Discrepancy::HashMismatch {
+                    path: artifact.clone()
+                },
+                Discrepancy::LogMissing,
+                Discrepancy::SidecarReplaced {
+                    dir: dir.to_string_lossy().to_string(),
+                    job_id: "job-9".to_string()
+                },
+            ]
+        );
+        // Only read
+        assert_eq!(std::fs::read(&artifact).unwrap(), b"SPLATS");
+
+        let truncated = finished(&paths, "job-3", b"splats");
+        let artifact = truncated.artifact_path.clone().unwrap();
+        std::fs::write(&artifact, b"spl").unwrap();
+        assert_eq!(
+            found(&paths, &truncated)[0],
+            Discrepancy::SizeChanged {
+                path: artifact.clone(),
+                expected: 6,
+                actual: 3
+            }
+        );
+        std::fs::remove_file(&artifact).unwrap();
+        assert_eq!(
+            found(&paths, &truncated)[0],
+            Discrepancy::ArtifactMissing { path: artifact }
+        );
+    }
+
+    #[test]
+    fn verifications_are_kept_on_the_records() {
+        let paths = TempPaths::new();
+        let record = finished(&paths, "job-1", b"splats");
+        history::save(&paths, &[record.clone(), job("job-2", JobStatus::Failed)]).unwrap();
+        let app_data = paths.app_data_dir().unwrap();
+        let verification = verify(&app_data, &record, &AtomicBool::new(false)).unwrap();
+
+        let summary = annotate(&paths, vec![("job-1".to_string(), verification)], true).unwrap();
+        assert_eq!((summary.checked, summary.cancelled), (1, true));
+        assert!(summary.discrepancies.is_empty());
+        let records = history::load(&paths).unwrap();
+        assert!(records[0].verification.is_some());
+        assert!(records[1].verification.is_none());
+        assert_eq!(
+            last_verified(&records),
+            records[0].verification.as_ref().unwrap().verified_at
+        );
+
+        let cancelled = verify(&app_data, &record, &AtomicBool::new(true));
+        assert!(matches!(cancelled, Err(AppError::Cancelled)));
+    }
+}
@@ -0,0 +1,615 @@
+//! Post-run Hooks
+//!
+//! Programs the user configured to run once a job is over, so a pipeline can
+//! pick up the splat, e.g. ingest it into an asset tracker or copy it to a
+//! render farm. Hooks are off until the postRunHooksEnabled setting is turned
+//! on. They run one after another, in the order they were configured, once the
+//! job's history is recorded and its queue entry is final, in the background
+//! so the next job in the queue never waits for them. They are spawned
+//! directly, never through a shell: the argument template is split on
+//! whitespace and the placeholders below are substituted into each argument.
+//!
+//! Hooks are only set through set_post_run_hooks, which checks that each one's
+//! program is an executable the user picked, never through save_settings.
+//!
+//! What a hook prints goes to the job log. A hook that fails, times out or is
+//! cancelled adds a warning to the job's record and queue entry after the
+//! fact, but never changes its status.
+
+use crate::error::AppError;
+use crate::history::JobStatus;
+use crate::job_events::{self, JobEvent};
+use crate::job_log::JobLog;
+use crate::messages::Message;
+use crate::path_policy::PathPolicy;
+use crate::runner::CliWarning;
+use crate::settings::{Persist, SettingsStore};
+use crate::validation::FieldError;
+use crate::viewers::{self, SHELL_METACHARACTERS};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::{mpsc, Notify};
+
+/// Placeholders an argument template may use
+pub const PLACEHOLDERS: [&str; 4] = ["{artifact}", "{output_dir}", "{job_id}", "{status}"];
+
+/// Longest a hook may be given to run
+pub const MAX_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+/// Warning code of a hook that did not succeed
+pub const WARNING_HOOK_FAILED: &str = "post-run-hook-failed";
+
+/// How long output still arriving after a hook exits is waited for, per line
+const DRAIN: Duration = Duration::from_millis(500);
+
+static RUNNING: Mutex<Vec<Running>> = Mutex::new(vec![]);
+
+/// Which job outcomes a hook runs after
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HookTrigger {
+    #[default]
+    Success,
+    Failure,
+    /// Cancelled jobs included
+    Always,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostRunHook {
+    /// Unique among the hooks; names the hook in the job log and to cancel it
+    pub name: String,
+    pub program: String,
+    /// Whitespace-separated arguments, with placeholders
+    #[serde(default)]
+    pub args_template: String,
+    #[serde(default)]
+    pub on: HookTrigger,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    10 * 60
+}
+
+impl PostRunHook {
+    pub fn runs_after(&self, status: JobStatus) -> bool {
+        match self.on {
+            HookTrigger::Success => status == JobStatus::Completed,
+            HookTrigger::Failure => status == JobStatus::Failed,
+            HookTrigger::Always => true,
+        }
+    }
+}
+
+/// What the placeholders of a finished job stand for
+#[derive(Debug, Clone, Copy)]
+pub struct HookContext<'a> {
+    pub job_id: &'a str,
+    pub status: JobStatus,
+    /// Empty in the arguments when the job left no artifact
+    pub artifact: Option<&'a str>,
+    pub output_dir: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookState {
+    Running,
+    Succeeded,
+    Failed,
+    TimedOut,
+    Cancelled,
+}
+
+/// A hook starting or ending, sent to the frontend as post-run-hook
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookRun {
+    pub job_id: String,
+    pub name: String,
+    pub state: HookState,
+}
+
+/// A hook being run, with what cancels it
+struct Running {
+    job_id: String,
+    name: String,
+    cancel: Arc<Notify>,
+}
+
+/// Takes a hook off RUNNING however its run ends
+struct RunningGuard(Arc<Notify>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        RUNNING
+            .lock()
+            .unwrap()
+            .retain(|r| !Arc::ptr_eq(&r.cancel, &self.0));
+    }
+}
+
+/// Replace the post-run hooks and turn them on or off. Each hook's program
+/// must be an executable picked with pick_tool_executable.
+#[tauri::command]
+pub async fn set_post_run_hooks(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    hooks: Vec<PostRunHook>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    validate(&policy, &hooks)?;
+    app.update_settings(Persist::Now, |s| {
+        s.post_run_hooks = hooks;
+        s.post_run_hooks_enabled = enabled;
+        Ok(())
+    })
+}
+
+/// Stop the hook named `name` that is running for job `job_id`; the hooks
+/// after it still run
+#[tauri::command]
+pub async fn cancel_post_run_hook(job_id: String, name: String) -> Result<(), AppError> {
+    let running = RUNNING.lock().unwrap();
+    let hook = running
+        .iter()
+        .find(|r| r.job_id == job_id && r.name == name)
+        .ok_or_else(|| AppError::NotFound(format!("post-run hook {} of {}", name, job_id)))?;
+    // Stores a permit, so a cancel just before the hook waits is not lost
+    hook.cancel.notify_one();
+    Ok(())
+}
+
+/// Problems with the hooks passed to set_post_run_hooks, by field under `field`
+fn check(hooks: &[PostRunHook], field: &str) -> Vec<(String, Message)> {
+    let mut problems = vec![];
+    for (i, hook) in hooks.iter().enumerate() {
+        let field = |name: &str| format!("{}.{}.{}", field, i, name);
+        if hook.name.trim().is_empty() {
+            problems.push((field("name"), Message::new("args.empty")));
+        } else if hooks[..i].iter().any(|h| h.name == hook.name) {
+            let message = Message::new("args.duplicate").with("value", &hook.name);
+            problems.push((field("name"), message));
+        }
+        if !Path::new(&hook.program).is_absolute() {
+            let message = Message::new("args.not_absolute").with("path", &hook.program);
+            problems.push((field("program"), message));
+        }
+        if let Err(detail) = build_args(&hook.args_template, &placeholder_context()) {
+            let message = Message::new("args.invalid").with("detail", detail);
+            problems.push((field("argsTemplate"), message));
+        }
+        if !(1..=MAX_TIMEOUT_SECS).contains(&hook.timeout_secs) {
+            let message = Message::new("args.out_of_range")
+                .with("min", 1)
+                .with("max", MAX_TIMEOUT_SECS);
+            problems.push((field("timeoutSecs"), message));
+        }
+    }
+    problems
+}
+
+/// Check hooks before they are saved: every field, then each program
+pub fn validate(policy: &PathPolicy, hooks: &[PostRunHook]) -> Result<(), AppError> {
+    let problems: Vec<FieldError> = check(hooks, "hooks")
+        .into_iter()
+        .map(|(field, message)| FieldError {
+            message: message.with("field", &field),
+            field,
+            options: vec![],
+        })
+        .collect();
+    if !problems.is_empty() {
+        return Err(AppError::InvalidArguments(problems));
+    }
+    for hook in hooks {
+        policy.check_existing(&hook.program)?;
+        viewers::check_executable(Path::new(&hook.program))?;
+    }
+    Ok(())
+}
+
+/// Stand-ins for checking a template before any job has run
+fn placeholder_context() -> HookContext<'static> {
+    HookContext {
+        job_id: "",
+        status: JobStatus::Completed,
+        artifact: None,
+        output_dir: "",
+    }
+}
+
+/// Split a template into arguments and substitute the placeholders
+pub fn build_args(template: &str, context: &HookContext) -> Result<Vec<String>, String> {
+    if let Some(c) = template.chars().find(|c| SHELL_METACHARACTERS.contains(c)) {
+        return Err(format!(
+            "{:?} is not allowed; arguments are passed to the program as-is, without a shell",
+            c
+        ));
+    }
+    let status = context.status.as_str();
+    let values = [
+        context.artifact.unwrap_or_default(),
+        context.output_dir,
+        context.job_id,
+        status,
+    ];
+    template
+        .split_whitespace()
+        .map(|arg| substitute(arg, &values))
+        .collect()
+}
+
+/// Replace each placeholder in one pass, so a value that happens to contain one
+/// is left as it is
+fn substitute(arg: &str, values: &[&str; 4]) -> Result<String, String> {
+    let mut substituted = String::new();
+    let mut rest = arg;
+    while !rest.is_empty() {
+        if let Some(i) = PLACEHOLDERS.iter().position(|p| rest.starts_with(p)) {
+            substituted.push_str(values[i]);
+            rest = &rest[PLACEHOLDERS[i].len()..];
+        } else if rest.starts_with(['{', '}']) {
+            return Err(format!(
+                "{} has an unknown placeholder; use {}",
+                arg,
+                PLACEHOLDERS.join(", ")
+            ));
+        } else {
+            let next = rest.chars().next().unwrap();
+            substituted.push(next);
+            rest = &rest[next.len_utf8()..];
+        }
+    }
+    Ok(substituted)
+}
+
+/// The hooks due once a job is over, with everything they need to run after
+/// the job has returned
+pub struct Pending {
+    hooks: Vec<PostRunHook>,
+    job_id: String,
+    status: JobStatus,
+    artifact: Option<String>,
+    output_dir: String,
+    log: JobLog,
+}
+
+impl Pending {
+    /// None when no hook runs after a job that ended this way
+    pub fn new(hooks: &[PostRunHook], context: &HookContext, log: JobLog) -> Option<Self> {
+        let hooks: Vec<PostRunHook> = hooks
+            .iter()
+            .filter(|h| h.runs_after(context.status))
+            .cloned()
+            .collect();
+        if hooks.is_empty() {
+            return None;
+        }
+        Some(Self {
+            hooks,
+            job_id: context.job_id.to_string(),
+            status: context.status,
+            artifact: context.artifact.map(String::from),
+            output_dir: context.output_dir.to_string(),
+            log,
+        })
+    }
+
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    /// Run the hooks, returning a warning for each that did not succeed
+    pub async fn run(mut self) -> Vec<CliWarning> {
+        let context = HookContext {
+            job_id: &self.job_id,
+            status: self.status,
+            artifact: self.artifact.as_deref(),
+            output_dir: &self.output_dir,
+        };
+        run_all(&self.hooks, &context, &mut self.log).await
+    }
+}
+
+/// Run the hooks that match the job's outcome, returning a warning for each
+/// that did not succeed
+pub async fn run_all(
+    hooks: &[PostRunHook],
+    context: &HookContext<'_>,
+    log: &mut JobLog,
+) -> Vec<CliWarning> {
+    let mut warnings = vec![];
+    for hook in hooks.iter().filter(|h| h.runs_after(context.status)) {
+        let cancel = Arc::new(Notify::new());
+        RUNNING.lock().unwrap().push(Running {
+            job_id: context.job_id.to_string(),
+            name: hook.name.clone(),
+            cancel: cancel.clone(),
+        });
+        let _running = RunningGuard(cancel.clone());
+        let event = |state| {
+            job_events::publish(JobEvent::Hook(HookRun {
+                job_id: context.job_id.to_string(),
+                name: hook.name.clone(),
+                state,
+            }))
+        };
+
+        event(HookState::Running);
+        let (state, failure) = match run(hook, context, &cancel, log).await {
+            Ok(()) => (HookState::Succeeded, None),
+            Err((state, detail)) => (state, Some(detail)),
+        };
+        event(state);
+        match failure {
+            None => log.line(&format!("Post-run hook {} succeeded", hook.name)),
+            Some(detail) => {
+                let warning = CliWarning {
+                    code: WARNING_HOOK_FAILED.to_string(),
+                    message: format!("Post-run hook {} {}", hook.name, detail),
+                };
+                log.line(&warning.message);
+                warnings.push(warning);
+            }
+        }
+    }
+    warnings
+}
+
+/// Run one hook to its end, logging its output
+async fn run(
+    hook: &PostRunHook,
+    context: &HookContext<'_>,
+    cancel: &Notify,
+    log: &mut JobLog,
+) -> Result<(), (HookState, String)> {
+    let failed = |detail: String| (HookState::Failed, detail);
+    let args = build_args(&hook.args_template, context).map_err(failed)?;
+    log.line(&format!(
+        "Running post-run hook {}: {} {:?}",
+        hook.name, hook.program, args
+    ));
+    let mut child = Command::new(&hook.program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| failed(format!("could not be started: {}", e)))?;
+
+    let (sender, mut lines) = mpsc::unbounded_channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward(stdout, sender.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward(stderr, sender);
+    }
+    let deadline = tokio::time::sleep(Duration::from_secs(hook.timeout_secs));
+    tokio::pin!(deadline);
+    let ended = loop {
+        tokio::select! {
+            Some(line) = lines.recv() => log.line(&format!("{}: {}", hook.name, line)),
+            status = child.wait() => break match status {
+                Ok(status) if status.success() => Ok(()),
+                Ok(status) => Err(failed(format!("failed: {}", status))),
+                Err(e) => Err(failed(format!("failed: {}", e))),
+            },
+            () = &mut deadline => {
+                break Err((
+                    HookState::TimedOut,
+                    format!("timed out after {}s", hook.timeout_secs),
+                ))
+            }
+            () = cancel.notified() => break Err((HookState::Cancelled, "was cancelled".to_string())),
+        }
+    };
+    if ended.is_err() {
+        child.kill().await.ok();
+    }
+    // Programs the hook started may keep its output open after it exits
+    while let Ok(Some(line)) = tokio::time::timeout(DRAIN, lines.recv()).await {
+        log.line(&format!("{}: {}", hook.name, line));
+    }
+    ended
+}
+
+fn forward(stream: impl AsyncRead + Unpin + Send + 'static, lines: mpsc::UnboundedSender<String>) {
+    tauri::async_runtime::spawn(async move {
+        let mut reader = BufReader::new(stream).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn hook(name: &str, program: &str, args_template: &str, on: HookTrigger) -> PostRunHook {
+        PostRunHook {
+            name: name.to_string(),
+            program: program.to_string(),
+            args_template: args_template.to_string(),
+            on,
+            timeout_secs: 60,
+        }
+    }
+
+    #[test]
+    fn templates_are_substituted_and_checked() {
+        let context = HookContext {
+            job_id: "job-1",
+            status: JobStatus::Failed,
+            artifact: Some("/p/{job_id}/a b.ply"),
+            output_dir: "/p",
+        };
+        assert_eq!(
+            build_args("--file={artifact} {output_dir} {job_id}:{status}", &context).unwrap(),
+            ["--file=/p/{job_id}/a b.ply", "/p", "job-1:failed"]
+        );
+        assert_eq!(build_args("  ", &context).unwrap(), Vec::<String>::new());
+        assert!(build_args("{artefact}", &context).is_err());
+        assert!(build_args("{artifact} | tee", &context).is_err());
+
+        let hooks = [
+            hook(
+                "ingest",
+                "/usr/bin/ingest",
+                "{artifact}",
+                HookTrigger::Success,
+            ),
+            hook("ingest", "ingest", "$HOME", HookTrigger::Always),
+            PostRunHook {
+                timeout_secs: 0,
+                ..hook(" ", "/usr/bin/copy", "", HookTrigger::Failure)
+            },
+        ];
+        let fields: Vec<(String, String)> = check(&hooks, "postRunHooks")
+            .into_iter()
+            .map(|(field, message)| (field, message.key))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("postRunHooks.1.name", "args.duplicate"),
+                ("postRunHooks.1.program", "args.not_absolute"),
+                ("postRunHooks.1.argsTemplate", "args.invalid"),
+                ("postRunHooks.2.name", "args.empty"),
+                ("postRunHooks.2.timeoutSecs", "args.out_of_range"),
+            ]
+            .map(|(field, key)| (field.to_string(), key.to_string()))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn only_picked_executables_are_saved() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let paths = TempPaths::new();
+        let program = paths.root().join("ingest");
+        std::fs::write(&program, b"#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let hooks = [hook(
+            "ingest",
+            &program.to_string_lossy(),
+            "{artifact}",
+            HookTrigger::Success,
+        )];
+
+        let policy = PathPolicy::default();
+        let err = validate(&policy, &hooks).unwrap_err();
+        assert_eq!(err.code(), "path_not_allowed");
+        policy.allow(paths.root());
+        validate(&policy, &hooks).unwrap();
+
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let err = validate(&policy, &hooks).unwrap_err();
+        assert_eq!(err.code(), "invalid_input");
+        let err = validate(&policy, &[hook("", "/bin/true", "", HookTrigger::Always)]).unwrap_err();
+        assert_eq!(err.code(), "invalid_arguments");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn matching_hooks_run_in_order_and_failures_become_warnings() {
+        let paths = TempPaths::new();
+        let mut log = JobLog::create(&paths, "job-hooks").unwrap();
+        let context = HookContext {
+            job_id: "job-hooks",
+            status: JobStatus::Completed,
+            artifact: Some("/p/output.ply"),
+            output_dir: "/p",
+        };
+        let hooks = [
+            hook(
+                "announce",
+                "/bin/echo",
+                "{job_id} {status} {artifact}",
+                HookTrigger::Success,
+            ),
+            hook(
+                "on-failure",
+                "/bin/echo",
+                "unexpected",
+                HookTrigger::Failure,
+            ),
+            hook("broken", "/bin/false", "", HookTrigger::Always),
+            hook("slow", "/bin/sleep", "30", HookTrigger::Always),
+            hook("after", "/bin/echo", "still ran", HookTrigger::Always),
+        ];
+
+        let cancel_slow = async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                if cancel_post_run_hook("job-hooks".to_string(), "slow".to_string())
+                    .await
+                    .is_ok()
+                {
+                    break;
+                }
+            }
+        };
+        let (warnings, ()) = tokio::join!(run_all(&hooks, &context, &mut log), cancel_slow);
+        drop(log);
+
+        let messages: Vec<&str> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(messages.len(), 2, "{:?}", messages);
+        assert!(messages[0].starts_with("Post-run hook broken failed"));
+        assert_eq!(messages[1], "Post-run hook slow was cancelled");
+        assert!(warnings.iter().all(|w| w.code == WARNING_HOOK_FAILED));
+        assert!(!RUNNING
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|r| r.job_id == "job-hooks"));
+
+        let text = std::fs::read_to_string(paths.root().join("data/logs/job-hooks.log")).unwrap();
+        assert!(
+            text.contains("announce: job-hooks completed /p/output.ply"),
+            "{}",
+            text
+        );
+        assert!(text.contains("after: still ran"), "{}", text);
+        assert!(!text.contains("unexpected"), "{}", text);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pending_hooks_run_after_the_job_has_returned() {
+        let paths = TempPaths::new();
+        let context = HookContext {
+            job_id: "job-later",
+            status: JobStatus::Cancelled,
+            artifact: None,
+            output_dir: "/p",
+        };
+        let on_success = [hook("ingest", "/bin/echo", "", HookTrigger::Success)];
+        let log = JobLog::create(&paths, "job-later").unwrap();
+        assert!(Pending::new(&on_success, &context, log).is_none());
+
+        let always = [hook("broken", "/bin/false", "", HookTrigger::Always)];
+        let log = JobLog::create(&paths, "job-later").unwrap();
+        let due = Pending::new(&always, &context, log).unwrap();
+        assert_eq!(due.job_id(), "job-later");
+        let warnings = due.run().await;
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0]
+            .message
+            .starts_with("Post-run hook broken failed"));
+    }
+}
@@ -0,0 +1,315 @@
+//! Splat Import
+//!
+//! Brings splats made with other tools into the library. A Gaussian splat PLY,
+//! a `.splat` or a `.spz` file is validated, the latter two converted to PLY,
+//! and a production directory created for it in the default output directory,
+//! with a sidecar marked as imported and an entry in the recent productions.
+//! A PLY can be imported by reference instead of copied, which matters for
+//! files of several gigabytes: the sidecar then names the original file.
+
+use crate::conversion;
+use crate::error::AppError;
+use crate::job_log;
+use crate::naming;
+use crate::path_policy::PathPolicy;
+use crate::scheduler::{self, Pool};
+use crate::settings::{Persist, RecentProduction, SettingsStore};
+use crate::sidecar::{self, ProductionSource, Sidecar, DEFAULT_ARTIFACT};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplatFormat {
+    Ply,
+    Splat,
+    Spz,
+}
+
+impl SplatFormat {
+    fn of(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_string_lossy().to_lowercase();
+        Some(match ext.as_str() {
+            "ply" => Self::Ply,
+            "splat" => Self::Splat,
+            "spz" => Self::Spz,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedProduction {
+    pub production: RecentProduction,
+    pub artifact_path: String,
+    pub format: SplatFormat,
+    pub splat_count: usize,
+    /// The artifact is a copy in the production directory rather than the
+    /// original file
+    pub copied: bool,
+}
+
+/// Import the splat at `path` as a production called `name`, or after the
+/// file. Only a PLY can stay where it is when `copy_into_library` is false;
+/// other formats are always converted into the production directory.
+#[tauri::command]
+pub async fn import_artifact(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+    name: Option<String>,
+    copy_into_library: bool,
+) -> Result<ImportedProduction, AppError> {
+    policy.check_existing(&path)?;
+    let library = app.settings().default_output_dir;
+    if library.is_empty() {
+        return Err(AppError::InvalidInput(
+            "Choose a default output directory to import into".to_string(),
+        ));
+    }
+    policy.check_target(&library)?;
+
+    let input = PathBuf::from(&path);
+    let imported = scheduler::run_blocking(Pool::CpuHeavy, move || {
+        import(
+            &input,
+            Path::new(&library),
+            name.as_deref(),
+            copy_into_library,
+        )
+    })
+    .await??;
+    let imported = register(&app, imported)?;
+    policy.allow_configured(&app.settings());
+    if !imported.copied {
+        policy.allow(Path::new(&imported.artifact_path));
+    }
+    Ok(imported)
+}
+
+/// Create the production directory in `library`, removing it again when
+/// anything fails
+fn import(
+    input: &Path,
+    library: &Path,
+    name: Option<&str>,
+    copy_into_library: bool,
+) -> Result<ImportedProduction, AppError> {
+    let format = SplatFormat::of(input).ok_or_else(|| {
+        AppError::InvalidInput(format!(
+            "{} is not a .ply, .splat or .spz file",
+            input.display()
+        ))
+    })?;
+    let input = input.canonicalize()?;
+    // Decoded up front, so a file that cannot be imported leaves nothing behind
+    let vertices = match format {
+        SplatFormat::Ply => {
+            conversion::complete_splat_count(&input).map_err(AppError::InvalidInput)?;
+            None
+        }
+        SplatFormat::Splat => Some(conversion::splat_to_vertices(&std::fs::read(&input)?)),
+        SplatFormat::Spz => Some(conversion::spz_to_vertices(&std::fs::read(&input)?)),
+    }
+    .transpose()
+    .map_err(AppError::InvalidInput)?;
+
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = name
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .unwrap_or(&stem)
+        .to_string();
+    let dir = unique_dir(library, &naming::sanitize(&name));
+    std::fs::create_dir_all(&dir)?;
+    let result = populate(&dir, &input, vertices.as_deref(), copy_into_library);
+    let (artifact, splat_count) = match result {
+        Ok(populated) => populated,
+        Err(e) => {
+            std::fs::remove_dir_all(&dir).ok();
+            return Err(e);
+        }
+    };
+
+    Ok(ImportedProduction {
+        production: RecentProduction {
+            id: job_log::new_id("imported"),
+            name,
+            path: dir.to_string_lossy().to_string(),
+            last_opened: String::new(),
+            tags: vec![],
+            notes: String::new(),
+            imported: true,
+            missing: false,
+            pinned: false,
+        },
+        copied: artifact != input,
+        artifact_path: artifact.to_string_lossy().to_string(),
+        format,
+        splat_count,
+    })
+}
+
+/// Write or copy the artifact and the sidecar into `dir`; returns the
+/// artifact and its splat count
+fn populate(
+    dir: &Path,
+    input: &Path,
+    vertices: Option<&[[f32; 14]]>,
+    copy_into_library: bool,
+) -> Result<(PathBuf, usize), AppError> {
+    let target = dir.join(DEFAULT_ARTIFACT);
+    let partial = dir.join(format!("{}.partial", DEFAULT_ARTIFACT));
+    let artifact = match vertices {
+        Some(vertices) => {
+            let mut out = io::BufWriter::new(std::fs::File::create(&partial)?);
+            conversion::write_ply(&mut out, vertices)?;
+            out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            std::fs::rename(&partial, &target)?;
+            target
+        }
+        None if copy_into_library => {
+            std::fs::copy(input, &partial)?;
+            std::fs::rename(&partial, &target)?;
+            target
+        }
+        None => input.to_path_buf(),
+    };
+    let splat_count =
+        conversion::complete_splat_count(&artifact).map_err(AppError::InvalidInput)?;
+
+    let sidecar = Sidecar {
+        job_id: job_log::new_id("import"),
+        production_dir: dir.to_string_lossy().to_string(),
+        artifact_path: artifact.to_string_lossy().to_string(),
+        created_at: job_log::unix_timestamp(),
+        artifact_sha256: Some(sha256(&artifact)?),
+        source: ProductionSource::Imported,
+        imported_from: Some(input.to_string_lossy().to_string()),
+        ..Sidecar::default()
+    };
+    sidecar::write(dir, &sidecar)?;
+    Ok((artifact, splat_count))
+}
+
+/// Add the production to the recent productions
+fn register(
+    store: &impl SettingsStore,
+    imported: ImportedProduction,
+) -> Result<ImportedProduction, AppError> {
+    store.update_settings(Persist::Now, |settings| {
+        settings
+            .recent_productions
+            .push(imported.production.clone());
+        Ok::<_, AppError>(())
+    })?;
+    Ok(imported)
+}
+
+/// `library/name`, or `library/name 2` and so on when that is taken
+fn unique_dir(library: &Path, name: &str) -> PathBuf {
+    let name = if name.is_empty() {
+        "Imported splat"
+    } else {
+        name
+    };
+    (1..)
+        .map(|n| match n {
+            1 => library.join(name),
+            n => library.join(format!("{} {}", name, n)),
+        })
+        .find(|dir| !dir.exists())
+        .unwrap()
+}
+
+fn sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    hasher.flush()?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::AppSettings;
+
+    const SPLAT: [f32; 14] = [
+        1.0, 2.0, 3.0, -1.0, -2.0, -3.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.5, 0.0, -0.5,
+    ];
+
+    #[test]
+    fn a_splat_file_is_converted_into_a_new_production() {
+        let paths = TempPaths::new();
+        let library = paths.root().join("library");
+        std::fs::create_dir_all(library.join("Stadium")).unwrap();
+        let input = paths.root().join("stadium.splat");
+        let splat = conversion::ply_to_splat(&sample_ply(&[SPLAT, SPLAT])).unwrap();
+        std::fs::write(&input, splat).unwrap();
+
+        let imported = import(&input, &library, Some("Stadium"), false).unwrap();
+        assert_eq!(imported.format, SplatFormat::Splat);
+        assert_eq!(imported.splat_count, 2);
+        assert!(imported.copied);
+        let dir = library.join("Stadium 2");
+        assert_eq!(imported.production.path, dir.to_string_lossy());
+        assert_eq!(
+            sidecar::find_artifact(&dir).unwrap(),
+            dir.join(DEFAULT_ARTIFACT)
+        );
+        let sidecar = sidecar::read(&dir).unwrap().unwrap();
+        assert_eq!(sidecar.source, ProductionSource::Imported);
+
+        let ply = std::fs::read(dir.join(DEFAULT_ARTIFACT)).unwrap();
+        let layout = conversion::parse_header(&ply).unwrap();
+        let values = layout.values(&ply[layout.body_offset..]);
+        for (value, expected) in values.iter().zip(SPLAT) {
+            assert!((value - expected).abs() < 0.05, "{} != {}", value, expected);
+        }
+
+        let store = MemorySettings::with(AppSettings::default());
+        register(&store, imported).unwrap();
+        assert!(store.settings().recent_productions[0].imported);
+    }
+
+    #[test]
+    fn a_ply_can_stay_where_it_is_and_a_point_cloud_is_refused() {
+        let paths = TempPaths::new();
+        let library = paths.root().join("library");
+        let input = paths.root().join("nerfstudio.ply");
+        std::fs::write(&input, sample_ply(&[SPLAT])).unwrap();
+
+        let imported = import(&input, &library, None, false).unwrap();
+        assert!(!imported.copied);
+        let dir = library.join("nerfstudio");
+        assert!(!dir.join(DEFAULT_ARTIFACT).exists());
+        assert_eq!(
+            sidecar::find_artifact(&dir).unwrap(),
+            input.canonicalize().unwrap()
+        );
+
+        let cloud = paths.root().join("cloud.ply");
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 1\n\
+            property float x\nproperty float y\nproperty float z\nend_header\n";
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(&[0; 12]);
+        std::fs::write(&cloud, bytes).unwrap();
+        let Err(AppError::InvalidInput(reason)) = import(&cloud, &library, None, true) else {
+            panic!("a plain point cloud was imported");
+        };
+        assert!(reason.contains("plain point cloud without SH coefficients"));
+        assert!(!library.join("cloud").exists());
+    }
+}
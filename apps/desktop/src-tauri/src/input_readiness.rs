@@ -0,0 +1,274 @@
+//! Input Readiness
+//!
+//! Clips dragged in from a camera card while the system is still copying them
+//! used to be accepted, and the job failed partway through extraction on
+//! reaching the end of what had been copied so far. Before a job starts, each
+//! input is now looked at twice, SAMPLE_INTERVAL apart. An input is not ready
+//! when it is empty, when its size changed in between, or when another process
+//! holds it open for writing. That last one can only be seen on Windows, which
+//! also names the program, usually the one copying it. process_videos refuses
+//! the job with NotReady, listing the inputs.
+
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::scheduler::{self, Pool};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Time between the two looks at the inputs' sizes
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotReadyReason {
+    Empty,
+    /// Another process holds it open for writing
+    Locked,
+    /// Its size changed between the two looks
+    Growing,
+}
+
+/// An input a job cannot read yet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotReadyInput {
+    pub path: String,
+    pub reason: NotReadyReason,
+    /// The program holding it open, when the system can tell
+    pub copier: Option<String>,
+    pub message: Message,
+}
+
+/// Refuse inputs that are not ready to be read
+pub async fn check(paths: &[String]) -> Result<(), AppError> {
+    let not_ready = inspect(paths, SAMPLE_INTERVAL).await?;
+    if not_ready.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::NotReady(not_ready))
+    }
+}
+
+async fn inspect(paths: &[String], interval: Duration) -> Result<Vec<NotReadyInput>, AppError> {
+    let before = sizes(paths).await;
+    tokio::time::sleep(interval).await;
+    let after = sizes(paths).await;
+
+    let mut not_ready = vec![];
+    for ((path, before), after) in paths.iter().zip(before).zip(after) {
+        // A missing input is for validation to report
+        let Some(after) = after else { continue };
+        let owned = path.clone();
+        let copier = scheduler::run_blocking(Pool::Io, move || {
+            locked(Path::new(&owned)).then(|| copier(Path::new(&owned)))
+        })
+        .await?;
+        let reason = match copier {
+            Some(_) => NotReadyReason::Locked,
+            None if before.is_some_and(|before| before != after) => NotReadyReason::Growing,
+            None if after == 0 => NotReadyReason::Empty,
+            None => continue,
+        };
+        let copier = copier.flatten();
+        not_ready.push(NotReadyInput {
+            path: path.clone(),
+            reason,
+            message: message(path, reason, copier.as_deref()),
+            copier,
+        });
+    }
+    Ok(not_ready)
+}
+
+async fn sizes(paths: &[String]) -> Vec<Option<u64>> {
+    let mut sizes = vec![];
+    for path in paths {
+        let metadata = tokio::fs::metadata(path).await.ok();
+        // A directory of images has no size of its own to watch
+        sizes.push(metadata.filter(|m| m.is_file()).map(|m| m.len()));
+    }
+    sizes
+}
+
+fn message(path: &str, reason: NotReadyReason, copier: Option<&str>) -> Message {
+    match (reason, copier) {
+        (NotReadyReason::Empty, _) => Message::new("input.empty"),
+        (NotReadyReason::Locked, None) => Message::new("input.locked"),
+        (NotReadyReason::Locked, Some(copier)) => {
+            Message::new("input.locked_by").with("program", copier)
+        }
+        (NotReadyReason::Growing, _) => Message::new("input.growing"),
+    }
+    .with("path", path)
+}
+
+/// Whether another process has the file open for writing
+#[cfg(windows)]
+fn locked(path: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+    use windows_sys::Win32::Foundation::ERROR_SHARING_VIOLATION;
+    use windows_sys::Win32::Storage::FileSystem::FILE_SHARE_READ;
+
+    // Letting others only read fails while anyone holds it open for writing
+    let opened = std::fs::OpenOptions::new()
+        .read(true)
+        .share_mode(FILE_SHARE_READ)
+        .open(path);
+    matches!(opened, Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION as i32))
+}
+
+/// Unix has no mandatory locks to find; a copy in progress shows as growing
+#[cfg(not(windows))]
+fn locked(_path: &Path) -> bool {
+    false
+}
+
+/// The program that has the file open, as the Restart Manager names it
+#[cfg(windows)]
+fn copier(path: &Path) -> Option<String> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Foundation::{ERROR_MORE_DATA, ERROR_SUCCESS};
+    use windows_sys::Win32::System::RestartManager::{
+        RmEndSession, RmGetList, RmRegisterResources, RmStartSession, CCH_RM_SESSION_KEY,
+        RM_PROCESS_INFO,
+    };
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut session = 0u32;
+    let mut key = [0u16; CCH_RM_SESSION_KEY as usize + 1];
+    // SAFETY: session and key are valid for writes and key has the size
+    // RmStartSession requires
+    if unsafe { RmStartSession(&mut session, 0, key.as_mut_ptr()) } != ERROR_SUCCESS {
+        return None;
+    }
+    let files = [wide.as_ptr()];
+    // SAFETY: files holds one pointer to a NUL-terminated path that outlives the
+    // session; the other lists are empty
+    let registered = unsafe {
+        RmRegisterResources(
+            session,
+            1,
+            files.as_ptr(),
+            0,
+            std::ptr::null(),
+            0,
+            std::ptr::null(),
+        )
+    };
+    let mut name = None;
+    if registered == ERROR_SUCCESS {
+        // SAFETY: RM_PROCESS_INFO is plain data, for which all zeroes is valid
+        let mut processes: [RM_PROCESS_INFO; 4] = unsafe { std::mem::zeroed() };
+        let mut needed = 0u32;
+        let mut count = processes.len() as u32;
+        let mut reasons = 0u32;
+        // SAFETY: processes has room for count entries and the counts and
+        // reasons are valid for writes
+        let listed = unsafe {
+            RmGetList(
+                session,
+                &mut needed,
+                &mut count,
+                processes.as_mut_ptr(),
+                &mut reasons,
+            )
+        };
+        // ERROR_MORE_DATA still fills in the first entries
+        if (listed == ERROR_SUCCESS || listed == ERROR_MORE_DATA) && count > 0 {
+            let app_name = &processes[0].strAppName;
+            let len = app_name
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(app_name.len());
+            name = Some(String::from_utf16_lossy(&app_name[..len])).filter(|n| !n.is_empty());
+        }
+    }
+    // SAFETY: session was started above and is ended once
+    unsafe { RmEndSession(session) };
+    name
+}
+
+#[cfg(not(windows))]
+fn copier(_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use std::io::Write;
+
+    fn reasons(not_ready: &[NotReadyInput]) -> Vec<(&str, NotReadyReason)> {
+        not_ready
+            .iter()
+            .map(|input| {
+                let name = Path::new(&input.path).file_name().unwrap();
+                (name.to_str().unwrap(), input.reason)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn empty_and_growing_inputs_are_not_ready() {
+        let paths = TempPaths::new();
+        let path = |name: &str| paths.root().join(name).to_string_lossy().to_string();
+        std::fs::write(path("copied.mp4"), b"complete clip").unwrap();
+        std::fs::write(path("empty.mp4"), b"").unwrap();
+        std::fs::write(path("copying.mp4"), b"first part").unwrap();
+
+        let copying = path("copying.mp4");
+        let copy = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(copying)
+                .unwrap();
+            file.write_all(b" and the rest").unwrap();
+        });
+        let inputs = [
+            path("copied.mp4"),
+            path("empty.mp4"),
+            path("copying.mp4"),
+            path("missing.mp4"),
+        ];
+        let not_ready = inspect(&inputs, Duration::from_millis(200)).await.unwrap();
+        copy.await.unwrap();
+
+        assert_eq!(
+            reasons(&not_ready),
+            [
+                ("empty.mp4", NotReadyReason::Empty),
+                ("copying.mp4", NotReadyReason::Growing)
+            ]
+        );
+        assert_eq!(not_ready[1].message.key, "input.growing");
+        assert_eq!(not_ready[1].copier, None);
+
+        let not_ready = inspect(&inputs, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(reasons(&not_ready), [("empty.mp4", NotReadyReason::Empty)]);
+    }
+
+    #[test]
+    fn the_error_lists_each_input() {
+        let input = |path: &str, reason| NotReadyInput {
+            path: path.to_string(),
+            reason,
+            copier: None,
+            message: message(path, reason, Some("Explorer")),
+        };
+        let err = AppError::NotReady(vec![
+            input("/clips/a.mp4", NotReadyReason::Locked),
+            input("/clips/b.mp4", NotReadyReason::Empty),
+        ]);
+        assert_eq!(err.code(), "not_ready");
+        assert_eq!(
+            err.to_string(),
+            "Inputs are not ready: /clips/a.mp4 is still open in Explorer, which may be copying it; /clips/b.mp4 is empty"
+        );
+        let json = serde_json::to_value(&err).unwrap();
+        assert_eq!(json["params"]["count"], "2");
+        assert_eq!(json["not_ready"][0]["reason"], "locked");
+        assert_eq!(json["not_ready"][1]["path"], "/clips/b.mp4");
+    }
+}
@@ -0,0 +1,371 @@
+//! Inspection Mode
+//!
+//! On macOS, an app run straight from its mounted DMG sits on a read-only
+//! volume, as does one Gatekeeper has translocated to a randomized copy
+//! because it was never moved out of Downloads. Linux users do the same with
+//! an AppImage kept on a read-only mount. Such a launch used to fail
+//! confusingly at every settings write. Now it is detected at startup, before
+//! app data is resolved, and the app starts in inspection mode instead. App
+//! data then lives in an overlay in the temp directory, seeded with the
+//! settings already saved. Features whose point is state that lasts are off:
+//! the history and the persisted queue. get_backend_health flags app data as
+//! not persistent, and an "app-not-installed" event is sent once, when the
+//! frontend has loaded, so it can walk the user through installing the app.
+
+use crate::settings_profiles;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter};
+
+/// Directory under Gatekeeper's translocation mount point
+const TRANSLOCATION_DIR: &str = "AppTranslocation";
+
+/// Overlays are named after it and the process they belong to
+const OVERLAY_PREFIX: &str = "gameview-inspection-";
+
+static NOT_INSTALLED: Mutex<Option<NotInstalled>> = Mutex::new(None);
+
+static ANNOUNCED: AtomicBool = AtomicBool::new(false);
+
+/// Why the app is taken not to be installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotInstalledReason {
+    /// macOS runs a quarantined app from a randomized read-only copy
+    Translocated,
+    /// Launched from a disk image or other read-only mount
+    ReadOnlyVolume,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotInstalled {
+    pub reason: NotInstalledReason,
+    /// The app bundle or AppImage that was launched
+    pub location: String,
+}
+
+/// What inspection mode turns off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Keeping the queue in queue.json and taking over an earlier run's
+    QueuePersistence,
+    /// Recording finished jobs
+    History,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InspectionStatus {
+    pub active: bool,
+    /// None when the app runs installed
+    pub not_installed: Option<NotInstalled>,
+    /// Off for this launch
+    pub disabled: Vec<Feature>,
+}
+
+/// What detection asks of the system, so tests can describe any launch
+pub trait EnvironmentProbe {
+    /// As in std::env::consts::OS
+    fn os(&self) -> &str;
+    fn executable(&self) -> Option<PathBuf>;
+    /// The AppImage file the app runs from, if it does
+    fn appimage(&self) -> Option<PathBuf>;
+    /// Whether the volume holding `path` is mounted read-only
+    fn read_only_volume(&self, path: &Path) -> bool;
+}
+
+/// The running system
+struct HostProbe;
+
+impl EnvironmentProbe for HostProbe {
+    fn os(&self) -> &str {
+        std::env::consts::OS
+    }
+
+    fn executable(&self) -> Option<PathBuf> {
+        std::env::current_exe().ok()
+    }
+
+    fn appimage(&self) -> Option<PathBuf> {
+        std::env::var_os("APPIMAGE")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    }
+
+    #[cfg(unix)]
+    fn read_only_volume(&self, path: &Path) -> bool {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+            return false;
+        };
+        // SAFETY: statvfs only writes into the zeroed struct it is given
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+            return false;
+        }
+        stat.f_flag & libc::ST_RDONLY != 0
+    }
+
+    #[cfg(not(unix))]
+    fn read_only_volume(&self, _path: &Path) -> bool {
+        false
+    }
+}
+
+/// Detect a launch of the app without installing it, for setup to do before
+/// resolving app data
+pub fn begin() -> InspectionStatus {
+    *NOT_INSTALLED.lock().unwrap() = detect(&HostProbe);
+    status()
+}
+
+/// Whether the app runs in inspection mode
+pub fn active() -> bool {
+    NOT_INSTALLED.lock().unwrap().is_some()
+}
+
+/// Whether `feature` is on for this launch
+pub fn allows(feature: Feature) -> bool {
+    !status().disabled.contains(&feature)
+}
+
+pub fn status() -> InspectionStatus {
+    status_of(NOT_INSTALLED.lock().unwrap().clone())
+}
+
+/// Send "app-not-installed" the first time the frontend is ready for it
+pub fn announce(app: &AppHandle) {
+    let Some(not_installed) = NOT_INSTALLED.lock().unwrap().clone() else {
+        return;
+    };
+    if !ANNOUNCED.swap(true, Ordering::SeqCst) {
+        app.emit("app-not-installed", &not_installed).ok();
+    }
+}
+
+/// Where app data lives in inspection mode, created with the settings saved in
+/// `saved`, the app data directory the installed app would use
+pub fn overlay(saved: Option<&Path>) -> Result<PathBuf, String> {
+    let dir = std::env::temp_dir().join(format!("{}{}", OVERLAY_PREFIX, std::process::id()));
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    if let Some(saved) = saved {
+        seed(saved, &dir);
+    }
+    Ok(dir)
+}
+
+/// The overlays of every launch in inspection mode, running or not
+pub fn overlays() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(OVERLAY_PREFIX)
+        })
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Copy the active profile's settings and what names it, leaving anything
+/// already in the overlay alone
+fn seed(saved: &Path, overlay: &Path) {
+    let files = [
+        saved.join(settings_profiles::ACTIVE_FILE),
+        settings_profiles::active_settings_path(saved),
+    ];
+    for from in files {
+        let Ok(relative) = from.strip_prefix(saved) else {
+            continue;
+        };
+        let to = overlay.join(relative);
+        if !from.is_file() || to.exists() {
+            continue;
+        }
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent).ok();
+        }
+        if let Err(e) = std::fs::copy(&from, &to) {
+            eprintln!("Failed to copy {} into the overlay: {}", from.display(), e);
+        }
+    }
+}
+
+fn detect(probe: &impl EnvironmentProbe) -> Option<NotInstalled> {
+    let launched = match probe.os() {
+        "macos" => {
+            let exe = probe.executable()?;
+            let bundle = exe
+                .ancestors()
+                .find(|p| p.extension().is_some_and(|e| e == "app"))
+                .unwrap_or(&exe)
+                .to_path_buf();
+            if exe.components().any(|c| c.as_os_str() == TRANSLOCATION_DIR) {
+                return Some(NotInstalled {
+                    reason: NotInstalledReason::Translocated,
+                    location: bundle.to_string_lossy().to_string(),
+                });
+            }
+            bundle
+        }
+        // An installed package may well sit on a read-only /usr
+        "linux" => probe.appimage()?,
+        _ => return None,
+    };
+    probe.read_only_volume(&launched).then(|| NotInstalled {
+        reason: NotInstalledReason::ReadOnlyVolume,
+        location: launched.to_string_lossy().to_string(),
+    })
+}
+
+fn status_of(not_installed: Option<NotInstalled>) -> InspectionStatus {
+    let disabled = if not_installed.is_some() {
+        vec![Feature::QueuePersistence, Feature::History]
+    } else {
+        vec![]
+    };
+    InspectionStatus {
+        active: not_installed.is_some(),
+        not_installed,
+        disabled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    struct FakeProbe {
+        os: &'static str,
+        executable: &'static str,
+        appimage: Option<&'static str>,
+        read_only: &'static [&'static str],
+    }
+
+    impl EnvironmentProbe for FakeProbe {
+        fn os(&self) -> &str {
+            self.os
+        }
+
+        fn executable(&self) -> Option<PathBuf> {
+            Some(PathBuf::from(self.executable))
+        }
+
+        fn appimage(&self) -> Option<PathBuf> {
+            self.appimage.map(PathBuf::from)
+        }
+
+        fn read_only_volume(&self, path: &Path) -> bool {
+            self.read_only.iter().any(|mount| path.starts_with(mount))
+        }
+    }
+
+    fn mac(executable: &'static str) -> FakeProbe {
+        FakeProbe {
+            os: "macos",
+            executable,
+            appimage: None,
+            read_only: &["/Volumes/Game View"],
+        }
+    }
+
+    fn linux(appimage: Option<&'static str>) -> FakeProbe {
+        FakeProbe {
+            os: "linux",
+            executable: "/usr/bin/gameview-desktop",
+            appimage,
+            read_only: &["/usr", "/media/cdrom"],
+        }
+    }
+
+    #[test]
+    fn launches_from_disk_images_and_translocated_copies_are_detected() {
+        let from_dmg = detect(&mac(
+            "/Volumes/Game View/Game View.app/Contents/MacOS/game-view",
+        ));
+        assert_eq!(
+            from_dmg,
+            Some(NotInstalled {
+                reason: NotInstalledReason::ReadOnlyVolume,
+                location: "/Volumes/Game View/Game View.app".to_string(),
+            })
+        );
+
+        let translocated = detect(&mac(
+            "/private/var/folders/xy/T/AppTranslocation/1A2B/d/Game View.app/Contents/MacOS/game-view",
+        ))
+        .unwrap();
+        assert_eq!(translocated.reason, NotInstalledReason::Translocated);
+        assert!(translocated.location.ends_with("/d/Game View.app"));
+
+        let appimage = detect(&linux(Some("/media/cdrom/Game_View.AppImage"))).unwrap();
+        assert_eq!(appimage.reason, NotInstalledReason::ReadOnlyVolume);
+        assert_eq!(appimage.location, "/media/cdrom/Game_View.AppImage");
+    }
+
+    #[test]
+    fn installed_launches_are_left_alone() {
+        assert_eq!(
+            detect(&mac("/Applications/Game View.app/Contents/MacOS/game-view")),
+            None
+        );
+        // A package's read-only /usr, and an AppImage kept somewhere writable
+        assert_eq!(detect(&linux(None)), None);
+        assert_eq!(detect(&linux(Some("/home/user/Game_View.AppImage"))), None);
+        let windows = FakeProbe {
+            os: "windows",
+            executable: "D:\\Game View\\game-view.exe",
+            appimage: None,
+            read_only: &["D:\\"],
+        };
+        assert_eq!(detect(&windows), None);
+    }
+
+    #[test]
+    fn inspection_mode_turns_off_what_needs_durable_state() {
+        let installed = status_of(None);
+        assert!(!installed.active);
+        assert!(installed.disabled.is_empty());
+
+        let not_installed = detect(&linux(Some("/media/cdrom/Game_View.AppImage")));
+        let inspecting = status_of(not_installed);
+        assert!(inspecting.active);
+        assert_eq!(
+            inspecting.disabled,
+            [Feature::QueuePersistence, Feature::History]
+        );
+    }
+
+    #[test]
+    fn the_overlay_starts_with_the_saved_settings() {
+        let paths = TempPaths::new();
+        let saved = paths.root().join("data");
+        let settings = settings_profiles::active_settings_path(&saved);
+        std::fs::create_dir_all(settings.parent().unwrap()).unwrap();
+        std::fs::write(&settings, br#"{"theme":"dark"}"#).unwrap();
+        std::fs::write(saved.join("queue.json"), b"[]").unwrap();
+
+        let overlay = paths.root().join("overlay");
+        seed(&saved, &overlay);
+        let seeded = settings_profiles::active_settings_path(&overlay);
+        assert_eq!(std::fs::read(seeded).unwrap(), br#"{"theme":"dark"}"#);
+        assert!(!overlay.join("queue.json").exists());
+
+        // Changes made in the overlay survive seeding again
+        std::fs::write(settings_profiles::active_settings_path(&overlay), b"{}").unwrap();
+        seed(&saved, &overlay);
+        assert_eq!(
+            std::fs::read(settings_profiles::active_settings_path(&overlay)).unwrap(),
+            b"{}"
+        );
+    }
+}
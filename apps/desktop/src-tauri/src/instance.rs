@@ -0,0 +1,445 @@
+//! Running Instance
+//!
+//! Lets a launch of the binary with a subcommand hand its request to the
+//! instance already running, so scripts can enqueue jobs without the GUI. The
+//! running instance listens on a loopback port and records the port and a
+//! fresh token in app_data/instance.json, readable only by the user; a request
+//! is one JSON line carrying the token, answered by one JSON line. When no
+//! instance is running, the app is started minimized and the request is sent
+//! once it listens.
+
+use crate::app_data;
+use crate::cli_args::{EnqueueRequest, Request, MINIMIZED_FLAG};
+use crate::commands;
+use crate::error::AppError;
+use crate::fsutil;
+use crate::messages::Failure;
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::queue::{self, QueueEntry, QueueStatus};
+use crate::settings::SettingsStore;
+use crate::share;
+use crate::written_by::{self, FileFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+const INFO_FILE: &str = "instance.json";
+
+/// How long either side waits for the other's line
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a request waits for an app it started to listen
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+const STARTUP_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct InstanceInfo {
+    port: u16,
+    token: String,
+    pid: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    token: String,
+    request: Request,
+}
+
+/// The answer to a request: the job it concerns, or why it failed
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+    Job(QueueEntry),
+    Error(Failure),
+}
+
+/// Accept forwarded requests for as long as the app runs
+pub fn listen(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = serve(app).await {
+            eprintln!("Not accepting requests from the command line: {}", e);
+        }
+    });
+}
+
+/// Forget the port once the app exits, so requests start a new instance
+pub fn stop(paths: &impl PathProvider) {
+    if let Ok(app_data) = paths.app_data_dir() {
+        std::fs::remove_file(app_data.join(INFO_FILE)).ok();
+    }
+}
+
+async fn serve(app: AppHandle) -> Result<(), String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .map_err(|e| e.to_string())?;
+    let info = InstanceInfo {
+        port: listener.local_addr().map_err(|e| e.to_string())?.port(),
+        token: share::generate_token(),
+        pid: std::process::id(),
+    };
+    // Holds the token, so it is never readable by other users
+    fsutil::write_json_private(&app.app_data_dir()?.join(INFO_FILE), &info)?;
+
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let app = app.clone();
+        let token = info.token.clone();
+        tauri::async_runtime::spawn(async move {
+            let (read, mut write) = stream.into_split();
+            let mut lines = tokio::io::BufReader::new(read).lines();
+            let Ok(Ok(Some(line))) = tokio::time::timeout(IO_TIMEOUT, lines.next_line()).await
+            else {
+                return;
+            };
+            let reply = match answer(&app, &token, &line).await {
+                Ok(entry) => Reply::Job(entry),
+                Err(e) => Reply::Error(e.failure()),
+            };
+            if let Ok(mut json) = serde_json::to_string(&reply) {
+                json.push('\n');
+                write.write_all(json.as_bytes()).await.ok();
+            }
+        });
+    }
+}
+
+async fn answer(app: &AppHandle, token: &str, line: &str) -> Result<QueueEntry, AppError> {
+    let envelope: Envelope =
+        serde_json::from_str(line).map_err(|e| AppError::InvalidInput(e.to_string()))?;
+    if !share::constant_time_eq(envelope.token.as_bytes(), token.as_bytes()) {
+        return Err(AppError::InvalidInput("Wrong instance token".to_string()));
+    }
+    match envelope.request {
+        Request::Enqueue(request) => enqueue(app, request).await,
+        Request::Status { job_id } => queue::find(&job_id).ok_or(AppError::NotFound(job_id)),
+        Request::Cancel { job_id } => {
+            // A job still waiting for its turn just leaves the queue
+            if let Some(cancelled) = queue::cancel_queued(&job_id) {
+                return Ok(cancelled);
+            }
+            let entry = queue::find(&job_id).ok_or_else(|| AppError::NotFound(job_id.clone()))?;
+            // Cancelling stops the whole running request, as the GUI's cancel does
+            if entry.status == QueueStatus::Running {
+                commands::cancel_processing().await?;
+            }
+            Ok(entry)
+        }
+    }
+}
+
+/// Queue a combined job, returning its queue entry; it runs once the requests
+/// queued before it are over
+async fn enqueue(app: &AppHandle, request: EnqueueRequest) -> Result<QueueEntry, AppError> {
+    let (mut videos, mut output_dir, mut preset) = (request.videos, request.output_dir, None);
+    if let Some(project) = &request.project {
+        let from_project = project_request(Path::new(project))?;
+        videos = from_project.videos;
+        output_dir = output_dir.or(from_project.output_dir);
+        preset = from_project.preset;
+    }
+    let preset = request
+        .preset
+        .or(preset)
+        .unwrap_or_else(|| app.settings().default_preset);
+    let output_dir =
+        output_dir.ok_or_else(|| AppError::InvalidInput("No output directory".to_string()))?;
+
+    // Paths named on the command line are the user's choice, as if picked in a dialog
+    std::fs::create_dir_all(&output_dir)?;
+    let policy = app.state::<PathPolicy>();
+    for path in videos.iter().chain([&output_dir]) {
+        policy.allow(Path::new(path));
+    }
+
+    let args = serde_json::json!({
+        "videos": videos,
+        "output_dir": output_dir,
+        "preset": preset,
+    });
+    let args = commands::prepare_request(app, &policy, args).await?;
+    let (entry_id, queued) = match commands::enqueue_combined(&args) {
+        Ok(queued) => queued,
+        // Asking again for a job that is already on its way gets that job
+        Err(AppError::AlreadyQueued(entry_id)) => {
+            return queue::find(&entry_id).ok_or(AppError::NotFound(entry_id));
+        }
+        Err(e) => return Err(e),
+    };
+    let entry = queue::find(&entry_id).ok_or_else(|| AppError::NotFound(entry_id.clone()))?;
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        // Waits for its turn; the outcome is kept on the queue entry and in the history
+        commands::run_combined(&app, args, &entry_id, queued)
+            .await
+            .ok();
+    });
+    Ok(entry)
+}
+
+/// The clips, output directory and preset of a .gvproj file, whose paths are
+/// relative to the file
+fn project_request(path: &Path) -> Result<EnqueueRequest, AppError> {
+    let content = std::fs::read_to_string(path)?;
+    let project: Value = serde_json::from_str(&content).map_err(|e| {
+        AppError::InvalidInput(format!(
+            "{}: {}",
+            path.display(),
+            written_by::explain(&content, e)
+        ))
+    })?;
+    if let Some(written_by) = written_by::header(&content)
+        .filter(|w| w.schema_version > FileFormat::Project.schema_version())
+    {
+        return Err(AppError::InvalidInput(format!(
+            "{} was written by Game View {}, a newer version; update Game View to open it",
+            path.display(),
+            written_by.app_version
+        )));
+    }
+    if project["type"] != "gameview-project" {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a Game View project",
+            path.display()
+        )));
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let resolve = |relative: &str| dir.join(relative).to_string_lossy().to_string();
+    let production = &project["production"];
+    Ok(EnqueueRequest {
+        project: None,
+        videos: production["videoFiles"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(resolve)
+            .collect(),
+        output_dir: production["outputDir"].as_str().map(resolve),
+        preset: production["settings"]["preset"].as_str().map(String::from),
+    })
+}
+
+/// Send a request to the running instance, starting the app first when none
+/// is, and print the reply. Returns the process exit code.
+pub fn run_remote(request: &Request, json: bool) -> i32 {
+    #[cfg(windows)]
+    attach_console();
+
+    let reply = forward(&app_data::possible_dirs(), request);
+    let reply = reply.unwrap_or_else(|e| Reply::Error(AppError::Io(e).failure()));
+    if json {
+        println!("{}", serde_json::to_string(&reply).unwrap_or_default());
+    }
+    match reply {
+        Reply::Job(entry) => {
+            if !json {
+                print_entry(request, &entry);
+            }
+            0
+        }
+        Reply::Error(failure) => {
+            if !json {
+                eprintln!("{}", failure);
+            }
+            1
+        }
+    }
+}
+
+fn forward(app_data: &[PathBuf], request: &Request) -> Result<Reply, String> {
+    let mut connection = connect(app_data);
+    if connection.is_none() {
+        let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+        Command::new(exe)
+            .arg(MINIMIZED_FLAG)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to start Game View: {}", e))?;
+        let started = Instant::now();
+        while connection.is_none() && started.elapsed() < STARTUP_TIMEOUT {
+            std::thread::sleep(STARTUP_POLL_INTERVAL);
+            connection = connect(app_data);
+        }
+    }
+    let (mut stream, token) =
+        connection.ok_or_else(|| "Game View did not start in time".to_string())?;
+
+    let envelope = Envelope {
+        token,
+        request: request.clone(),
+    };
+    let mut line = serde_json::to_string(&envelope).map_err(|e| e.to_string())?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&reply).map_err(|e| format!("Unexpected reply: {}", e))
+}
+
+/// A connection to the running instance and its token, or None when no
+/// instance is listening, including when a crashed one left its file behind.
+/// Which of `app_data` the instance resolved is not known, so each is tried.
+fn connect(app_data: &[PathBuf]) -> Option<(TcpStream, String)> {
+    app_data.iter().find_map(|dir| connect_to(dir))
+}
+
+fn connect_to(app_data: &Path) -> Option<(TcpStream, String)> {
+    let content = std::fs::read_to_string(app_data.join(INFO_FILE)).ok()?;
+    let info: InstanceInfo = serde_json::from_str(&content).ok()?;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, info.port));
+    let stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(IO_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(IO_TIMEOUT)).ok()?;
+    Some((stream, info.token))
+}
+
+fn print_entry(request: &Request, entry: &QueueEntry) {
+    if let Request::Enqueue(_) = request {
+        println!("{}", entry.entry_id);
+        return;
+    }
+    let status = serde_json::to_value(entry.status).unwrap_or_default();
+    println!("{} {}", entry.entry_id, status.as_str().unwrap_or_default());
+    if let Some(artifact) = &entry.artifact_path {
+        println!("{}", artifact);
+    }
+    if let Some(error) = &entry.error {
+        println!("{}", error);
+    }
+}
+
+/// Release builds have no console of their own; print to the one the
+/// command was run from
+#[cfg(windows)]
+fn attach_console() {
+    use windows_sys::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    // SAFETY: AttachConsole takes no pointers and fails harmlessly without a parent console
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn projects_name_their_paths_relative_to_the_file() {
+        let paths = TempPaths::new();
+        let project = paths.root().join("pier.gvproj");
+        std::fs::write(
+            &project,
+            r#"{
+                "type": "gameview-project",
+                "production": {
+                    "videoFiles": ["clips/a.mp4", "clips/b.mp4"],
+                    "outputDir": "output",
+                    "settings": { "preset": "high" }
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let request = project_request(&project).unwrap();
+        assert_eq!(
+            request.videos,
+            [
+                paths.root().join("clips/a.mp4").to_string_lossy(),
+                paths.root().join("clips/b.mp4").to_string_lossy()
+            ]
+        );
+        assert_eq!(
+            request.output_dir,
+            Some(paths.root().join("output").to_string_lossy().to_string())
+        );
+        assert_eq!(request.preset.as_deref(), Some("high"));
+
+        std::fs::write(&project, r#"{"type": "gameview-vvs"}"#).unwrap();
+        assert!(matches!(
+            project_request(&project),
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn requests_without_a_listening_instance_find_none() {
+        let paths = TempPaths::new();
+        let dirs = [paths.root().to_path_buf()];
+        assert!(connect(&dirs).is_none());
+
+        // A crashed instance's file points at a port nobody listens on
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let info = InstanceInfo {
+            port,
+            token: "t".to_string(),
+            pid: 1,
+        };
+        fsutil::write_json_atomic(&paths.root().join(INFO_FILE), &info).unwrap();
+        assert!(connect(&dirs).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_instance_file_is_only_ever_readable_by_the_user() {
+        use std::os::unix::fs::PermissionsExt;
+        let paths = TempPaths::new();
+        let path = paths.root().join(INFO_FILE);
+        // Left by a crash with the default permissions
+        std::fs::write(path.with_extension("tmp"), b"").unwrap();
+        let info = InstanceInfo {
+            port: 1,
+            token: "t".to_string(),
+            pid: 1,
+        };
+
+        fsutil::write_json_private(&path, &info).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!path.with_extension("tmp").exists());
+        let written: InstanceInfo =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written.token, "t");
+    }
+
+    #[test]
+    fn each_possible_app_data_dir_is_tried_in_turn() {
+        let paths = TempPaths::new();
+        let stale = paths.root().to_path_buf();
+        let live = paths.root().join("data");
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        for (dir, port, token) in [(&stale, 1, "stale"), (&live, port, "live")] {
+            let info = InstanceInfo {
+                port,
+                token: token.to_string(),
+                pid: 1,
+            };
+            fsutil::write_json_atomic(&dir.join(INFO_FILE), &info).unwrap();
+        }
+
+        let (_, token) = connect(&[stale, live]).unwrap();
+        assert_eq!(token, "live");
+    }
+}
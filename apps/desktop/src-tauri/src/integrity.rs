@@ -0,0 +1,236 @@
+//! Artifact Integrity
+//!
+//! SHA-256 checksums of finished artifacts, recorded in the sidecar when a job
+//! completes and compared later to catch files truncated or corrupted in a copy.
+
+use crate::error::AppError;
+use crate::hashing::{self, Reuse};
+use crate::operations::{self, OperationHandle, OperationKind};
+use crate::path_policy::PathPolicy;
+use crate::sidecar;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, State};
+
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    Ok,
+    Mismatch,
+    /// No sidecar, or one written before checksums were recorded
+    MissingSidecar,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyResult {
+    pub status: VerifyStatus,
+    pub artifact_path: String,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashProgress {
+    pub path: String,
+    pub hashed_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// Re-hash an artifact and compare it with the checksum in its production's sidecar
+#[tauri::command]
+pub async fn verify_artifact(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+) -> Result<VerifyResult, AppError> {
+    policy.check_existing(&path)?;
+    let operation = OperationHandle::start(&app, OperationKind::Hashing, &path);
+    verify(Path::new(&path), operation.token(), &mut |hashed, total| {
+        operation.progress(hashed, Some(total));
+        emit_progress(&app, &path, hashed, total)
+    })
+    .await
+}
+
+/// Cancel a running verification
+#[tauri::command]
+pub async fn cancel_verification() -> Result<(), AppError> {
+    CANCEL.store(true, Ordering::SeqCst);
+    operations::cancel_kind(OperationKind::Hashing);
+    Ok(())
+}
+
+/// Clear and return the flag cancel_verification sets
+pub fn start_verification() -> &'static AtomicBool {
+    CANCEL.store(false, Ordering::SeqCst);
+    &CANCEL
+}
+
+pub fn emit_progress(app: &AppHandle, path: &str, hashed_bytes: u64, total_bytes: u64) {
+    let progress = HashProgress {
+        path: path.to_string(),
+        hashed_bytes,
+        total_bytes,
+    };
+    app.emit("hash-progress", &progress).ok();
+}
+
+/// Compare an artifact against the checksum its sidecar recorded
+pub async fn verify(
+    artifact: &Path,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(u64, u64) + Send),
+) -> Result<VerifyResult, AppError> {
+    let artifact_path = artifact.to_string_lossy().to_string();
+    if !artifact.is_file() {
+        return Err(AppError::NotFound(artifact_path));
+    }
+
+    let expected = artifact
+        .parent()
+        .map(sidecar::read)
+        .transpose()?
+        .flatten()
+        .filter(|s| Path::new(&s.artifact_path).file_name() == artifact.file_name())
+        .and_then(|s| s.artifact_sha256);
+    let Some(expected) = expected else {
+        return Ok(VerifyResult {
+            status: VerifyStatus::MissingSidecar,
+            artifact_path,
+            expected: None,
+            actual: None,
+        });
+    };
+
+    // A cached checksum would hide corruption that left the file's size and time alone
+    let actual = hashing::sha256(artifact, Reuse::Fresh, cancel, progress).await?;
+    let status = if actual.eq_ignore_ascii_case(&expected) {
+        VerifyStatus::Ok
+    } else {
+        VerifyStatus::Mismatch
+    };
+    Ok(VerifyResult {
+        status,
+        artifact_path,
+        expected: Some(expected),
+        actual: Some(actual),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use crate::sidecar::{ProductionSource, Sidecar};
+    use std::path::PathBuf;
+
+    fn production(paths: &TempPaths, contents: &[u8], sha256: Option<&str>) -> PathBuf {
+        let dir = paths.root().join("production");
+        std::fs::create_dir_all(&dir).unwrap();
+        let artifact = dir.join("output.ply");
+        std::fs::write(&artifact, contents).unwrap();
+        let sidecar = Sidecar {
+            job_id: "job-1".to_string(),
+            production_dir: dir.to_string_lossy().to_string(),
+            artifact_path: artifact.to_string_lossy().to_string(),
+            preset: "balanced".to_string(),
+            videos: vec![],
+            created_at: 0,
+            artifact_sha256: sha256.map(str::to_string),
+            capture_type: Default::default(),
+            archive: None,
+            preview: false,
+            input_sha256: vec![],
+            command: None,
+            training: None,
+            derivatives: vec![],
+            source: ProductionSource::Job,
+            imported_from: None,
+            resolution_normalization: None,
+            camera_groups: vec![],
+            artifacts: vec![],
+        };
+        sidecar::write(&dir, &sidecar).unwrap();
+        artifact
+    }
+
+    async fn hash(path: &Path) -> String {
+        hashing::sha256(path, Reuse::Fresh, &AtomicBool::new(false), &mut |_, _| {})
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn hashes_known_content() {
+        let paths = TempPaths::new();
+        let path = paths.root().join("abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            hash(&path).await,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_progress_and_can_be_cancelled() {
+        let paths = TempPaths::new();
+        let path = paths.root().join("big.ply");
+        std::fs::write(&path, vec![7u8; 3 * hashing::CHUNK_BYTES + 10]).unwrap();
+
+        let mut reports = vec![];
+        let cancel = AtomicBool::new(false);
+        hashing::sha256(&path, Reuse::Fresh, &cancel, &mut |hashed, total| {
+            reports.push((hashed, total))
+        })
+        .await
+        .unwrap();
+        assert_eq!(reports.len(), 4);
+        let total = 3 * hashing::CHUNK_BYTES as u64 + 10;
+        assert_eq!(reports.last(), Some(&(total, total)));
+
+        let cancel = AtomicBool::new(true);
+        let err = hashing::sha256(&path, Reuse::Fresh, &cancel, &mut |_, _| {})
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "cancelled");
+    }
+
+    #[tokio::test]
+    async fn verify_detects_truncation() {
+        let paths = TempPaths::new();
+        let artifact = production(&paths, b"ply\nfull contents", None);
+        let expected = hash(&artifact).await;
+        let artifact = production(&paths, b"ply\nfull contents", Some(&expected));
+
+        let cancel = AtomicBool::new(false);
+        let result = verify(&artifact, &cancel, &mut |_, _| {}).await.unwrap();
+        assert_eq!(result.status, VerifyStatus::Ok);
+
+        std::fs::write(&artifact, b"ply\nfull").unwrap();
+        let result = verify(&artifact, &cancel, &mut |_, _| {}).await.unwrap();
+        assert_eq!(result.status, VerifyStatus::Mismatch);
+        assert_eq!(result.expected.as_deref(), Some(expected.as_str()));
+    }
+
+    #[tokio::test]
+    async fn verify_without_recorded_checksum() {
+        let paths = TempPaths::new();
+        let artifact = production(&paths, b"ply", None);
+        let cancel = AtomicBool::new(false);
+        let result = verify(&artifact, &cancel, &mut |_, _| {}).await.unwrap();
+        assert_eq!(result.status, VerifyStatus::MissingSidecar);
+
+        std::fs::remove_file(sidecar::sidecar_path(artifact.parent().unwrap())).unwrap();
+        let result = verify(&artifact, &cancel, &mut |_, _| {}).await.unwrap();
+        assert_eq!(result.status, VerifyStatus::MissingSidecar);
+
+        std::fs::remove_file(&artifact).unwrap();
+        let err = verify(&artifact, &cancel, &mut |_, _| {})
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "not_found");
+    }
+}
@@ -0,0 +1,415 @@
+//! Job Events
+//!
+//! Running jobs publish what happens to them to every subscriber, and each
+//! feature that observes jobs, such as the frontend events or the window title,
+//! runs as its own subscriber task. The process loop so needs no hook per
+//! feature. Each subscriber has a queue of its own, in the order events were
+//! published, and publishing never waits for it. A subscriber that falls
+//! behind misses the oldest progress and training metrics, which the next ones
+//! supersede or get_training_metrics reads back, but never an event that
+//! starts, pauses or ends something, such as job-finished or batch-complete.
+//! The history record is still written by the job itself before its command
+//! returns.
+//!
+//! Each event is published with the job it is about and that job's
+//! production, so the frontend subscriber can send it only to the windows
+//! interested in them.
+
+use crate::auto_retry::AutoRetrying;
+use crate::cancel_impact::Stall;
+use crate::checkpoints::Checkpoint;
+use crate::commands::BatchSummary;
+use crate::gpu_contention::VramCheck;
+use crate::hooks::HookRun;
+use crate::jobs;
+use crate::messages::Message;
+use crate::notifications;
+use crate::progress_indicator;
+use crate::runner::{CliWarning, EventSink, ProcessProgress};
+use crate::secrets;
+use crate::training_metrics::MetricsUpdate;
+use crate::volume_watch::VolumeChange;
+use crate::window_events;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Notify;
+
+/// Events that may be missed a subscriber may fall behind by before it misses
+/// the oldest of them
+const CAPACITY: usize = 256;
+
+static SUBSCRIBERS: Mutex<Vec<Weak<Queue>>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    /// A job began; sent to every window
+    Started {
+        job_id: String,
+        production_id: Option<String>,
+    },
+    /// A progress update of the running job
+    Progress(ProcessProgress),
+    /// The running job's CLI printed a warning
+    Warning(CliWarning),
+    /// A clip's frames were reused from an earlier run instead of extracted
+    FramesCacheHit { video: String, frames_dir: String },
+    /// The running job's output volume went away, and the job is paused
+    VolumeDisconnected(VolumeChange),
+    /// The output volume is back; a paused job waits for resume_processing
+    VolumeReconnected(VolumeChange),
+    /// A checkpoint of the running job is ready to open
+    CheckpointAvailable(Checkpoint),
+    /// Training metrics of a job, batched
+    TrainingMetrics(MetricsUpdate),
+    /// Too little VRAM was free when the running job started training
+    GpuContention(VramCheck),
+    /// A post-run hook of a finished job started or ended
+    Hook(HookRun),
+    /// The running job's progress has stood still for a while
+    ProcessingStalled(Stall),
+    /// A failed job is about to run again by itself
+    AutoRetrying(AutoRetrying),
+    /// A process_videos request is over
+    Finished {
+        /// The queue entries that failed, not counting cancelled ones
+        failed: Vec<String>,
+        /// The outcome of a per-clip batch
+        batch: Option<BatchSummary>,
+        /// The user cancelled it
+        cancelled: bool,
+    },
+}
+
+impl JobEvent {
+    /// The job the event names itself
+    fn job_id(&self) -> Option<&str> {
+        match self {
+            JobEvent::Started { job_id, .. } => Some(job_id),
+            JobEvent::CheckpointAvailable(checkpoint) => Some(&checkpoint.job_id),
+            JobEvent::TrainingMetrics(update) => Some(&update.job_id),
+            JobEvent::GpuContention(check) => Some(&check.job_id),
+            JobEvent::Hook(run) => Some(&run.job_id),
+            JobEvent::ProcessingStalled(stall) => Some(&stall.job_id),
+            JobEvent::AutoRetrying(retrying) => Some(&retrying.job_id),
+            JobEvent::Progress(_)
+            | JobEvent::Warning(_)
+            | JobEvent::FramesCacheHit { .. }
+            | JobEvent::VolumeDisconnected(_)
+            | JobEvent::VolumeReconnected(_)
+            | JobEvent::Finished { .. } => None,
+        }
+    }
+
+    /// Whether a subscriber that falls behind may miss it: progress is
+    /// superseded by the next, and metrics are kept with the job
+    fn may_be_missed(&self) -> bool {
+        matches!(self, JobEvent::Progress(_) | JobEvent::TrainingMetrics(_))
+    }
+}
+
+/// An event with the job it is about: the one it names, or else the one job
+/// running when it was published
+#[derive(Debug, Clone)]
+pub struct Routed {
+    pub job_id: Option<String>,
+    pub production_id: Option<String>,
+    pub event: JobEvent,
+}
+
+/// The events one subscriber has yet to handle
+#[derive(Default)]
+struct Queue {
+    pending: Mutex<Pending>,
+    ready: Notify,
+}
+
+/// Queued events, with a running count of those that may be missed
+#[derive(Default)]
+struct Pending {
+    events: VecDeque<Routed>,
+    missable: usize,
+}
+
+impl Queue {
+    fn push(&self, routed: Routed) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.missable += usize::from(routed.event.may_be_missed());
+        pending.events.push_back(routed);
+        if pending.missable > CAPACITY {
+            if let Some(oldest) = pending.events.iter().position(|r| r.event.may_be_missed()) {
+                pending.events.remove(oldest);
+                pending.missable -= 1;
+            }
+        }
+        drop(pending);
+        self.ready.notify_one();
+    }
+
+    fn pop(&self) -> Option<Routed> {
+        let mut pending = self.pending.lock().unwrap();
+        let routed = pending.events.pop_front()?;
+        pending.missable -= usize::from(routed.event.may_be_missed());
+        Some(routed)
+    }
+}
+
+/// The events published since subscribing, until it is dropped
+pub struct Subscription {
+    queue: Arc<Queue>,
+}
+
+impl Subscription {
+    /// The next event, waiting for one to be published
+    pub async fn recv(&mut self) -> Routed {
+        loop {
+            if let Some(routed) = self.try_recv() {
+                return routed;
+            }
+            self.queue.ready.notified().await;
+        }
+    }
+
+    /// The next event, if one is waiting
+    pub fn try_recv(&mut self) -> Option<Routed> {
+        self.queue.pop()
+    }
+}
+
+/// Send an event to every subscriber
+pub fn publish(event: JobEvent) {
+    let job = match event.job_id() {
+        Some(job_id) => jobs::find(job_id),
+        None => jobs::sole(),
+    };
+    let routed = Routed {
+        job_id: event
+            .job_id()
+            .map(String::from)
+            .or_else(|| job.as_ref().map(|j| j.job_id.clone())),
+        production_id: job.and_then(|j| j.production_id),
+        event,
+    };
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.retain(|queue| queue.strong_count() > 0);
+    for queue in subscribers.iter().filter_map(Weak::upgrade) {
+        queue.push(routed.clone());
+    }
+}
+
+pub fn subscribe() -> Subscription {
+    let queue = Arc::new(Queue::default());
+    SUBSCRIBERS.lock().unwrap().push(Arc::downgrade(&queue));
+    Subscription { queue }
+}
+
+/// Publishes the progress of a job; its CLI output only goes to the job log
+pub struct BusSink;
+
+impl EventSink for BusSink {
+    fn line(&mut self, _line: &str) {}
+
+    fn progress(&mut self, progress: &ProcessProgress) {
+        publish(JobEvent::Progress(progress.clone()));
+    }
+
+    fn warning(&mut self, warning: &CliWarning) {
+        publish(JobEvent::Warning(warning.clone()));
+    }
+}
+
+/// The processing-progress payload, with secrets in the CLI's text redacted
+pub fn frontend_progress(progress: &ProcessProgress) -> ProcessProgress {
+    let redact = |text: &str| secrets::redact(text).into_owned();
+    ProcessProgress {
+        message: progress.message.as_ref().map(|message| Message {
+            key: message.key.clone(),
+            params: message
+                .params
+                .iter()
+                .map(|(name, value)| (name.clone(), redact(value)))
+                .collect(),
+        }),
+        raw: progress.raw.as_deref().map(redact),
+        ..progress.clone()
+    }
+}
+
+/// Start the subscribers that run for the life of the app
+pub fn start(app: &AppHandle) {
+    let frontend = app.clone();
+    spawn_subscriber(move |routed| {
+        let Routed {
+            job_id,
+            production_id,
+            event,
+        } = routed;
+        let emit = |name: &str, payload: serde_json::Value| {
+            window_events::emit(
+                &frontend,
+                job_id.as_deref(),
+                production_id.as_deref(),
+                name,
+                payload,
+            )
+        };
+        match event {
+            JobEvent::Started {
+                job_id,
+                production_id,
+            } => {
+                let payload =
+                    serde_json::json!({ "job_id": job_id, "production_id": production_id });
+                frontend.emit("job-started", payload).ok();
+            }
+            JobEvent::Progress(progress) => {
+                emit("processing-progress", json(&frontend_progress(&progress)));
+            }
+            JobEvent::Warning(warning) => emit("processing-warning", json(&warning)),
+            JobEvent::Finished {
+                failed,
+                batch,
+                cancelled,
+            } => {
+                let payload = serde_json::json!({
+                    "job_id": job_id,
+                    "failed": failed,
+                    "cancelled": cancelled,
+                });
+                frontend.emit("job-finished", payload).ok();
+                if let Some(summary) = batch {
+                    emit("batch-complete", json(&summary));
+                }
+            }
+            JobEvent::FramesCacheHit { video, frames_dir } => {
+                let payload = serde_json::json!({ "video": video, "frames_dir": frames_dir });
+                emit("frames-cache-hit", payload);
+            }
+            JobEvent::VolumeDisconnected(change) => emit("volume-disconnected", json(&change)),
+            JobEvent::VolumeReconnected(change) => emit("volume-reconnected", json(&change)),
+            JobEvent::CheckpointAvailable(checkpoint) => {
+                emit("checkpoint-available", json(&checkpoint))
+            }
+            JobEvent::TrainingMetrics(update) => emit("training-metrics", json(&update)),
+            JobEvent::GpuContention(check) => emit("gpu-contention", json(&check)),
+            JobEvent::Hook(run) => emit("post-run-hook", json(&run)),
+            JobEvent::ProcessingStalled(stall) => emit("processing-stalled", json(&stall)),
+            JobEvent::AutoRetrying(retrying) => emit("job-auto-retrying", json(&retrying)),
+        }
+    });
+
+    let title = app.clone();
+    spawn_subscriber(move |routed| match routed.event {
+        JobEvent::Progress(progress) => {
+            progress_indicator::progress(&title, &progress.stage, progress.progress)
+        }
+        JobEvent::Finished { failed, .. } => progress_indicator::finish(&title, &failed),
+        JobEvent::VolumeDisconnected(_) => progress_indicator::paused(&title),
+        JobEvent::Started { .. }
+        | JobEvent::Warning(_)
+        | JobEvent::FramesCacheHit { .. }
+        | JobEvent::VolumeReconnected(_)
+        | JobEvent::CheckpointAvailable(_)
+        | JobEvent::TrainingMetrics(_)
+        | JobEvent::GpuContention(_)
+        | JobEvent::Hook(_)
+        | JobEvent::ProcessingStalled(_)
+        | JobEvent::AutoRetrying(_) => {}
+    });
+
+    let notify = app.clone();
+    spawn_subscriber(move |routed| {
+        if let JobEvent::Finished {
+            failed,
+            batch,
+            cancelled,
+        } = routed.event
+        {
+            notifications::job_finished(
+                &notify,
+                routed.job_id.as_deref(),
+                &failed,
+                batch.as_ref(),
+                cancelled,
+            );
+        }
+    });
+}
+
+/// A payload as JSON, so one copy serves every window it goes to
+fn json(payload: &impl Serialize) -> serde_json::Value {
+    serde_json::to_value(payload).unwrap_or_default()
+}
+
+fn spawn_subscriber(mut handle: impl FnMut(Routed) + Send + 'static) {
+    let mut events = subscribe();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            handle(events.recv().await);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(percent: f64) -> ProcessProgress {
+        ProcessProgress {
+            stage: "training_splats".to_string(),
+            progress: percent,
+            message: None,
+            raw: None,
+            current_item: None,
+            items: None,
+            reported_item: None,
+            synthetic: false,
+        }
+    }
+
+    fn routed(event: JobEvent) -> Routed {
+        Routed {
+            job_id: None,
+            production_id: None,
+            event,
+        }
+    }
+
+    #[test]
+    fn lagging_subscribers_skip_the_oldest_progress_only() {
+        // Its own queue, so events other tests publish cannot get in
+        let queue = Queue::default();
+        queue.push(routed(JobEvent::Progress(progress(0.0))));
+        queue.push(routed(JobEvent::Finished {
+            failed: vec!["entry-lagged".to_string()],
+            batch: None,
+            cancelled: false,
+        }));
+        for i in 1..CAPACITY + 10 {
+            queue.push(routed(JobEvent::Progress(progress(i as f64))));
+        }
+        assert_eq!(queue.pending.lock().unwrap().missable, CAPACITY);
+
+        let mut finished = false;
+        let mut first_progress = None;
+        let mut received = 0;
+        while let Some(routed) = queue.pop() {
+            received += 1;
+            match routed.event {
+                JobEvent::Finished { failed, .. } if failed == ["entry-lagged"] => {
+                    assert!(first_progress.is_none());
+                    finished = true;
+                }
+                JobEvent::Progress(p) if first_progress.is_none() => {
+                    first_progress = Some(p.progress)
+                }
+                _ => {}
+            }
+        }
+        assert!(finished);
+        assert_eq!(first_progress, Some(10.0));
+        assert_eq!(received, CAPACITY + 1);
+        assert_eq!(queue.pending.lock().unwrap().missable, 0);
+    }
+}
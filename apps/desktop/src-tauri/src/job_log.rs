@@ -0,0 +1,86 @@
+//! Job Logs
+//!
+//! Writes a plain-text log per processing job under the app data directory, plus
+//! an app log for events that belong to no job.
+
+use crate::path_policy::resolve_app_data;
+use crate::platform::PathProvider;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct JobLog {
+    file: File,
+}
+
+impl JobLog {
+    /// Create the log file for a job at app_data/logs/<job_id>.log
+    pub fn create(paths: &impl PathProvider, job_id: &str) -> Result<Self, String> {
+        let path = resolve_app_data(&paths.app_data_dir()?, &format!("logs/{}.log", job_id))
+            .map_err(|e| e.to_string())?;
+        if let Some(logs_dir) = path.parent() {
+            std::fs::create_dir_all(logs_dir).map_err(|e| e.to_string())?;
+        }
+
+        let file = File::create(&path).map_err(|e| e.to_string())?;
+        Ok(Self { file })
+    }
+
+    /// Append a timestamped line; logging failures never abort a job
+    pub fn line(&mut self, message: &str) {
+        writeln!(
+            self.file,
+            "[{}] {}",
+            unix_timestamp(),
+            crate::secrets::redact(message)
+        )
+        .ok();
+    }
+}
+
+/// Append a timestamped line to app_data/logs/app.log; like job logs, failures are ignored
+pub fn app_line(paths: &impl PathProvider, message: &str) {
+    let Ok(app_data) = paths.app_data_dir() else {
+        return;
+    };
+    let Ok(path) = resolve_app_data(&app_data, "logs/app.log") else {
+        return;
+    };
+    if let Some(logs_dir) = path.parent() {
+        std::fs::create_dir_all(logs_dir).ok();
+    }
+    if let Ok(file) = OpenOptions::new().create(true).append(true).open(&path) {
+        JobLog { file }.line(message);
+    }
+}
+
+/// Seconds since the Unix epoch
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Generate a job id from the current time
+pub fn new_job_id() -> String {
+    new_id("job")
+}
+
+/// Generate a time-based id, unique within this process even when called in the same millisecond
+pub fn new_id(prefix: &str) -> String {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    // The closure never declines, so this always succeeds
+    let previous = LAST
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap();
+    let millis = now.max(previous + 1);
+    format!("{}-{}", prefix, millis)
+}
@@ -0,0 +1,112 @@
+//! Job Registry
+//!
+//! Tracks which jobs are currently running, which output directories they
+//! write to and the productions they belong to.
+
+use crate::scheduler;
+use std::path::Path;
+use std::sync::Mutex;
+
+static ACTIVE_JOBS: Mutex<Vec<ActiveJob>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone)]
+pub struct ActiveJob {
+    pub job_id: String,
+    pub output_dir: String,
+    /// Recent production the job belongs to, when the request named one
+    pub production_id: Option<String>,
+}
+
+/// Removes the job from the registry when dropped
+pub struct JobGuard {
+    job_id: String,
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        ACTIVE_JOBS
+            .lock()
+            .unwrap()
+            .retain(|job| job.job_id != self.job_id);
+        // Work the job held back may use every worker again
+        scheduler::global().refresh();
+    }
+}
+
+/// Register a running job for as long as the returned guard lives
+pub fn register(job_id: &str, output_dir: &str, production_id: Option<&str>) -> JobGuard {
+    ACTIVE_JOBS.lock().unwrap().push(ActiveJob {
+        job_id: job_id.to_string(),
+        output_dir: output_dir.to_string(),
+        production_id: production_id.map(String::from),
+    });
+    JobGuard {
+        job_id: job_id.to_string(),
+    }
+}
+
+/// Where a running job writes
+pub fn output_dir(job_id: &str) -> Option<String> {
+    ACTIVE_JOBS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|job| job.job_id == job_id)
+        .map(|job| job.output_dir.clone())
+}
+
+/// A running job
+pub fn find(job_id: &str) -> Option<ActiveJob> {
+    ACTIVE_JOBS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|job| job.job_id == job_id)
+        .cloned()
+}
+
+/// The running job, unless there are several or none
+pub fn sole() -> Option<ActiveJob> {
+    match ACTIVE_JOBS.lock().unwrap().as_slice() {
+        [job] => Some(job.clone()),
+        _ => None,
+    }
+}
+
+/// Whether a processing job is running
+pub fn any_active() -> bool {
+    !ACTIVE_JOBS.lock().unwrap().is_empty()
+}
+
+/// Whether any active job writes into `path` or one of its parents/children
+pub fn is_targeting(path: &Path) -> bool {
+    ACTIVE_JOBS.lock().unwrap().iter().any(|job| {
+        let output_dir = Path::new(&job.output_dir);
+        output_dir.starts_with(path) || path.starts_with(output_dir)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_tracks_job_until_dropped() {
+        let output_dir = "/jobs-test/productions/harbour";
+        let job = register("job-guard-test", output_dir, Some("harbour"));
+
+        assert!(is_targeting(Path::new(output_dir)));
+        assert!(is_targeting(Path::new("/jobs-test/productions")));
+        assert!(is_targeting(Path::new(
+            "/jobs-test/productions/harbour/frames"
+        )));
+        assert!(!is_targeting(Path::new("/jobs-test/productions/studio")));
+
+        let found = find("job-guard-test").unwrap();
+        assert_eq!(found.production_id.as_deref(), Some("harbour"));
+
+        drop(job);
+        assert!(find("job-guard-test").is_none());
+        assert!(!is_targeting(Path::new(output_dir)));
+    }
+}
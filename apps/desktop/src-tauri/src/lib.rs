@@ -3,32 +3,420 @@
 //! This module provides the Rust backend for the Game View desktop application.
 //! It handles file operations, CLI spawning, and settings management.
 
+mod actions;
+mod app_data;
+mod appearance;
+mod archive;
+mod artifact_search;
+mod artifacts;
+mod auto_retry;
+mod cache;
+mod camera_bookmarks;
+mod camera_groups;
+mod camera_intrinsics;
+mod cancel_impact;
+mod capabilities;
+mod checkpoints;
+mod cli_args;
+mod cli_location;
+mod clip_progress;
 mod commands;
+mod comparison;
+mod conversion;
+mod datafile;
+mod disk;
+mod downsample;
+mod duplicates;
+mod error;
+mod export_progress;
+mod extraction;
+mod ffmpeg;
+mod fingerprint;
+mod frame_filter;
+mod frames_cache;
+mod fsutil;
+mod gpu;
+mod gpu_contention;
+mod hashing;
+mod health;
+mod history;
+mod history_export;
+mod history_sweep;
+mod hooks;
+mod import;
+mod input_readiness;
+mod inspection;
+mod instance;
+mod integrity;
+pub mod job_events;
+mod job_log;
+mod jobs;
+mod library;
+mod masks;
+mod media;
+mod messages;
+mod naming;
+mod network;
+mod notifications;
+mod opening;
+mod operations;
+mod output_location;
+mod overlap;
+mod path_policy;
+mod pending_tasks;
+mod performance;
+mod platform;
+mod platform_support;
+mod preferences;
+mod prefetch;
+mod presets;
+mod preview;
+mod productions;
+mod profiles;
+mod progress_indicator;
+mod queue;
+mod quick_look;
+mod recents;
+mod reconcile;
+mod registration;
+mod retention;
+mod reuse;
+pub mod runner;
+mod safe_mode;
+mod scheduler;
+mod secrets;
 mod settings;
+mod settings_profiles;
+mod setup;
+mod share;
+mod shell_quote;
+mod sidecar;
+mod simulator;
+mod spawn_diagnosis;
+mod splat_preview;
+mod sync;
+mod training;
+mod training_metrics;
+mod undo;
+mod validation;
+mod viewers;
+mod volume_watch;
+mod vram_policy;
+mod web_export;
+mod window_events;
+mod workdir;
+mod written_by;
 
+use cli_args::Invocation;
+use path_policy::PathPolicy;
+use secrets::Secrets;
+use settings::{SettingsState, SettingsStore};
+use share::ShareState;
 use tauri::Manager;
 
+/// Build the invoke handler, recording the same commands in the action registry
+macro_rules! commands {
+    ($($module:ident::$command:ident),* $(,)?) => {{
+        actions::register(vec![$(actions::Registered {
+            module: stringify!($module),
+            name: stringify!($command),
+        }),*]);
+        tauri::generate_handler![$($module::$command),*]
+    }};
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let minimized = match cli_args::parse(&args, &cwd) {
+        Ok(Invocation::Gui { minimized }) => minimized,
+        Ok(Invocation::Remote { request, json }) => {
+            std::process::exit(instance::run_remote(&request, json))
+        }
+        Err(e) => {
+            eprintln!("{}\n{}", e, cli_args::USAGE);
+            std::process::exit(2)
+        }
+    };
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
-        .setup(|app| {
-            // Initialize app data directory
-            let app_data = app.path().app_data_dir().expect("Failed to get app data dir");
-            std::fs::create_dir_all(&app_data).ok();
+        .plugin(tauri_plugin_notification::init())
+        .on_page_load(|webview, payload| {
+            if payload.event() == tauri::webview::PageLoadEvent::Started {
+                appearance::apply_to_window(webview.app_handle(), webview.label());
+            }
+            if payload.event() == tauri::webview::PageLoadEvent::Finished {
+                inspection::announce(webview.app_handle());
+            }
+        })
+        .setup(move |app| {
+            // Before app data, which it moves to an overlay
+            let inspection = inspection::begin();
+            // Resolved once, wherever it was relocated to, for every command to use
+            let resolved = app_data::resolve(app.handle());
+            let app_data = resolved.path.clone();
+            app.manage(resolved);
+            let platform = platform_support::detect();
+            datafile::start(app.handle());
+            let policy = PathPolicy::default();
+            // Without app data only get_backend_health and relocate_app_data are
+            // of use, and the frontend shows its recovery screen
+            if let Some(app_data) = app_data {
+                let safe_mode = safe_mode::begin(&app_data);
+                if safe_mode.active {
+                    job_log::app_line(
+                        app.handle(),
+                        &format!(
+                            "Starting in safe mode after {} launches in a row ended early",
+                            safe_mode.failed_launches
+                        ),
+                    );
+                }
+                health::begin(&app_data);
+                if !platform.supported {
+                    let findings: Vec<String> = platform
+                        .findings
+                        .iter()
+                        .map(|f| f.message.to_string())
+                        .collect();
+                    job_log::app_line(
+                        app.handle(),
+                        &format!("Unsupported platform: {}", findings.join("; ")),
+                    );
+                }
+                if let Some(not_installed) = &inspection.not_installed {
+                    job_log::app_line(
+                        app.handle(),
+                        &format!(
+                            "Running from {} without being installed; nothing is kept after quitting",
+                            not_installed.location
+                        ),
+                    );
+                }
+                if !safe_mode::skipped(safe_mode::Piece::Queue)
+                    && inspection::allows(inspection::Feature::QueuePersistence)
+                {
+                    if let Err(e) = queue::persist(app.handle()) {
+                        eprintln!("Failed to persist the job queue: {}", e);
+                    }
+                }
+                let settings = SettingsState::load(app.handle())?;
+                policy.allow_configured(&settings.settings());
+                ffmpeg::configure(app.handle(), &settings.settings());
+                cli_location::configure(&settings.settings());
+                cache::configure(&settings.settings());
+                if let Ok(cache) = cache::app_cache(app.handle()) {
+                    hashing::configure(cache);
+                }
+                scheduler::configure(&settings.settings());
+                appearance::start(app.handle(), &settings.settings().appearance);
+                app.manage(settings);
+                if let Err(e) = pending_tasks::reconcile(app.handle()) {
+                    eprintln!("Failed to reconcile pending tasks: {}", e);
+                }
+                retention::start(app.handle());
+                let secrets = Secrets::open(app.handle());
+                let backend = secrets.backend();
+                if backend.backend == secrets::SecretBackend::Memory {
+                    job_log::app_line(
+                        app.handle(),
+                        &format!(
+                            "Secrets are only kept until quitting: {}",
+                            backend.fallback_reason.unwrap_or_default()
+                        ),
+                    );
+                }
+                app.manage(secrets);
+                if !safe_mode::skipped(safe_mode::Piece::RemoteRequests) {
+                    instance::listen(app.handle());
+                }
+                safe_mode::watch(app.handle(), app_data);
+            } else {
+                eprintln!("No writable directory for app data; starting in recovery");
+                // Commands still reach for settings and secrets; neither is kept
+                app.manage(SettingsState::in_memory());
+                app.manage(Secrets::memory(
+                    "No writable directory for app data".to_string(),
+                ));
+            }
+            app.manage(policy);
+            app.manage(platform);
+            app.manage(ShareState::default());
+            job_events::start(app.handle());
+            if minimized {
+                if let Some(window) = app.get_webview_window("main") {
+                    window.minimize().ok();
+                }
+            }
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(commands![
             commands::get_settings,
             commands::save_settings,
+            appearance::get_effective_theme,
+            settings_profiles::list_profiles,
+            settings_profiles::create_profile,
+            settings_profiles::switch_profile,
+            settings_profiles::delete_profile,
             commands::pick_videos,
             commands::pick_output_directory,
+            commands::pick_masks_directory,
+            commands::pick_tool_executable,
+            commands::set_tool_path,
             commands::process_videos,
             commands::cancel_processing,
+            volume_watch::resume_processing,
+            disk::preflight_disk_space,
+            frames_cache::get_frames_cache_usage,
+            frames_cache::clear_frames_cache,
+            cache::get_cache_stats,
+            cache::clear_cache,
+            progress_indicator::acknowledge_job_result,
             commands::get_cli_path,
+            cli_location::explain_cli_resolution,
+            performance::analyze_performance_environment,
+            capabilities::get_cli_capabilities,
+            capabilities::list_presets,
+            presets::get_preset_descriptions,
+            media::get_video_metadata,
+            media::validate_videos,
+            camera_intrinsics::list_known_devices,
+            overlap::analyze_overlap,
+            overlap::cancel_overlap_analysis,
+            sync::analyze_sync,
+            sync::cancel_sync_analysis,
+            masks::validate_masks,
+            history::get_job_history,
+            history::set_job_metadata,
+            history_sweep::verify_history,
+            history_sweep::cancel_history_verification,
+            history::clone_job_args,
+            history::get_job_command,
+            history_export::export_history_csv,
+            training_metrics::get_training_metrics,
+            checkpoints::get_job_checkpoints,
+            cancel_impact::get_cancel_impact,
+            datafile::get_data_file_recoveries,
+            health::get_backend_health,
+            safe_mode::get_safe_mode_status,
+            safe_mode::exit_safe_mode,
+            app_data::relocate_app_data,
+            queue::get_queue,
+            productions::move_production,
+            productions::preview_delete_production,
+            productions::delete_production,
+            retention::preview_retention,
+            retention::run_retention,
+            archive::archive_production_intermediates,
+            archive::restore_production_intermediates,
+            archive::cancel_archive_operation,
+            operations::list_operations,
+            operations::cancel_operation,
+            downsample::downsample_splats,
+            downsample::cancel_downsample,
+            duplicates::find_duplicate_artifacts,
+            duplicates::cancel_duplicate_scan,
+            duplicates::resolve_duplicates,
+            recents::set_production_tags,
+            recents::set_production_notes,
+            recents::set_production_pinned,
+            recents::search_productions,
+            recents::list_all_tags,
+            recents::open_recent_production,
+            opening::open_production,
+            written_by::get_file_compatibility,
+            camera_bookmarks::save_camera_bookmark,
+            camera_bookmarks::list_camera_bookmarks,
+            camera_bookmarks::delete_camera_bookmark,
+            reconcile::reconcile_library,
+            reconcile::purge_missing_records,
+            import::import_artifact,
+            library::scan_productions,
+            library::cancel_library_scan,
+            preferences::get_production_defaults,
+            preferences::clear_production_defaults,
+            setup::get_setup_status,
+            setup::complete_setup_step,
+            spawn_diagnosis::repair_cli_issue,
+            scheduler::get_scheduler_status,
+            secrets::set_secret,
+            secrets::has_secret,
+            secrets::delete_secret,
+            secrets::get_secret_backend,
+            hooks::set_post_run_hooks,
+            hooks::cancel_post_run_hook,
+            splat_preview::render_splat_preview,
+            splat_preview::cancel_splat_preview,
+            comparison::render_comparison,
+            comparison::cancel_comparison,
+            quick_look::quick_look,
+            share::start_share_server,
+            share::stop_share_server,
+            share::get_share_status,
+            hashing::get_file_hash,
+            integrity::verify_artifact,
+            integrity::cancel_verification,
+            web_export::export_web_artifact,
+            pending_tasks::get_pending_tasks,
+            pending_tasks::resume_pending_task,
+            pending_tasks::discard_pending_task,
+            prefetch::queue_media_prefetch,
+            prefetch::cancel_media_prefetch,
+            viewers::list_external_viewers,
+            viewers::add_external_viewer,
+            viewers::update_external_viewer,
+            viewers::remove_external_viewer,
+            viewers::open_with_external_viewer,
+            network::test_network_connection,
+            messages::get_message_catalog,
+            undo::get_undo_stack,
+            undo::undo_last_operation,
+            actions::get_available_actions,
+            actions::dispatch_action,
+            window_events::subscribe_events,
+            window_events::unsubscribe_events,
+            notifications::get_pending_notifications,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::WindowEvent {
+                label,
+                event: tauri::WindowEvent::Destroyed,
+                ..
+            } = &event
+            {
+                window_events::forget(label);
+            }
+            if let tauri::RunEvent::WindowEvent {
+                event: tauri::WindowEvent::Focused(true),
+                ..
+            } = &event
+            {
+                notifications::window_focused(app);
+            }
+            if let tauri::RunEvent::WindowEvent {
+                event: tauri::WindowEvent::ThemeChanged(theme),
+                ..
+            } = &event
+            {
+                appearance::theme_changed(app, *theme);
+            }
+            if let tauri::RunEvent::Exit = event {
+                app.state::<ShareState>().stop();
+                if let Some(settings) = app.try_state::<SettingsState>() {
+                    settings.flush();
+                }
+                queue::flush();
+                instance::stop(app);
+                if let Ok(app_data) = app_data::dir(app) {
+                    health::end(&app_data);
+                    safe_mode::healthy(&app_data);
+                }
+            }
+        });
 }
@@ -0,0 +1,554 @@
+//! Productions Library
+//!
+//! Finds productions under a folder, including ones made on other machines: a
+//! directory with a production.gvmeta sidecar, a .gvproj project file, or a
+//! loose Gaussian splat PLY. Scans are bounded in depth and size and can be
+//! cancelled. Each root keeps a cache of what every directory held at its last
+//! modification time, so a rescan only reads directories that changed.
+
+use crate::conversion;
+use crate::error::AppError;
+use crate::fsutil;
+use crate::job_log;
+use crate::operations::{self, OperationHandle, OperationKind};
+use crate::path_policy::{resolve_app_data, PathPolicy};
+use crate::platform::PathProvider;
+use crate::scheduler::{self, Pool};
+use crate::settings::{Persist, RecentProduction, SettingsStore};
+use crate::sidecar::{self, SIDECAR_NAME};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, State};
+
+/// Directories below the root that are still searched
+const MAX_DEPTH: usize = 8;
+
+/// Directory entries looked at before the scan stops
+const MAX_ENTRIES: usize = 50_000;
+
+/// Progress is reported after this many directories
+const PROGRESS_EVERY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LibraryKind {
+    /// A directory with a production.gvmeta sidecar
+    Production,
+    /// A .gvproj file
+    Project,
+    /// A Gaussian splat PLY with neither
+    Splat,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub kind: LibraryKind,
+    pub name: String,
+    /// The production directory, or the project file
+    pub path: String,
+    pub artifact_path: Option<String>,
+    pub artifact_size: Option<u64>,
+    pub splat_count: Option<usize>,
+    /// Seconds since the Unix epoch
+    pub created_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryScan {
+    pub root: String,
+    pub entries: Vec<LibraryEntry>,
+    /// Directories read from disk rather than taken from the cache
+    pub directories_read: usize,
+    pub directories_cached: usize,
+    /// The depth or entry limit cut the scan short
+    pub truncated: bool,
+    /// Entries newly added to the recent productions
+    pub imported: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ScanProgress<'a> {
+    root: &'a str,
+    directories: usize,
+    found: usize,
+}
+
+/// What one directory held when it was last read
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedDir {
+    modified_millis: u128,
+    entries: Vec<LibraryEntry>,
+    subdirs: Vec<String>,
+    entry_count: usize,
+}
+
+type ScanCache = BTreeMap<String, CachedDir>;
+
+/// Find productions under `root_dir`, optionally adding them to the recent
+/// productions marked as imported. Progress arrives as "library-scan-progress".
+#[tauri::command]
+pub async fn scan_productions(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    root_dir: String,
+    import: bool,
+) -> Result<LibraryScan, AppError> {
+    policy.check_existing(&root_dir)?;
+    let operation = OperationHandle::start(&app, OperationKind::LibraryScan, &root_dir);
+
+    let emitter = app.clone();
+    let mut scan = scheduler::run_blocking(Pool::Io, move || {
+        scan_root(
+            &emitter,
+            &root_dir,
+            operation.token(),
+            &mut |directories, _| operation.progress(directories as u64, None),
+        )
+    })
+    .await??;
+
+    if import {
+        scan.imported = import_recents(&app, &scan.entries)?;
+        policy.allow_configured(&app.settings());
+    }
+    Ok(scan)
+}
+
+/// Cancel a running library scan
+#[tauri::command]
+pub async fn cancel_library_scan() -> Result<(), AppError> {
+    operations::cancel_kind(OperationKind::LibraryScan);
+    Ok(())
+}
+
+/// Scan `root` through its cache, reporting progress as "library-scan-progress"
+/// and to `progress` as directories scanned and productions found
+pub fn scan_root(
+    app: &AppHandle,
+    root: &str,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<LibraryScan, AppError> {
+    let cache_path = cache_path(app, root)?;
+    let mut cache = load_cache(&cache_path);
+    let scan = scan(
+        Path::new(root),
+        &mut cache,
+        cancel,
+        &mut |directories, found| {
+            progress(directories, found);
+            let event = ScanProgress {
+                root,
+                directories,
+                found,
+            };
+            app.emit("library-scan-progress", &event).ok();
+        },
+    )?;
+    fsutil::write_json_atomic(&cache_path, &cache)?;
+    Ok(scan)
+}
+
+fn scan(
+    root: &Path,
+    cache: &mut ScanCache,
+    cancel: &AtomicBool,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<LibraryScan, AppError> {
+    if !root.is_dir() {
+        return Err(AppError::NotFound(root.display().to_string()));
+    }
+
+    let mut result = LibraryScan {
+        root: root.to_string_lossy().to_string(),
+        entries: vec![],
+        directories_read: 0,
+        directories_cached: 0,
+        truncated: false,
+        imported: 0,
+    };
+    let mut seen = BTreeMap::new();
+    let mut visited_entries = 0;
+    let mut pending = vec![(root.to_path_buf(), 0)];
+
+    while let Some((dir, depth)) = pending.pop() {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(AppError::Cancelled);
+        }
+        let key = dir.to_string_lossy().to_string();
+        let Some(modified_millis) = modified_millis(&dir) else {
+            continue;
+        };
+        let listing = match cache.get(&key) {
+            Some(cached) if cached.modified_millis == modified_millis => {
+                result.directories_cached += 1;
+                cached.clone()
+            }
+            _ => {
+                result.directories_read += 1;
+                let listing = read_dir(&dir, modified_millis);
+                cache.insert(key.clone(), listing.clone());
+                listing
+            }
+        };
+        seen.insert(key, ());
+
+        visited_entries += listing.entry_count;
+        result.entries.extend(listing.entries);
+        if visited_entries > MAX_ENTRIES {
+            result.truncated = true;
+            break;
+        }
+        if depth == MAX_DEPTH && !listing.subdirs.is_empty() {
+            result.truncated = true;
+        } else {
+            pending.extend(
+                listing
+                    .subdirs
+                    .iter()
+                    .map(|s| (PathBuf::from(s), depth + 1)),
+            );
+        }
+
+        let directories = result.directories_read + result.directories_cached;
+        if directories % PROGRESS_EVERY == 0 {
+            progress(directories, result.entries.len());
+        }
+    }
+
+    // Directories that were deleted since the last scan
+    if !result.truncated {
+        cache.retain(|dir, _| seen.contains_key(dir));
+    }
+    result.entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(result)
+}
+
+/// What a directory holds. A production directory is not searched further, as
+/// everything in it belongs to that production.
+fn read_dir(dir: &Path, modified_millis: u128) -> CachedDir {
+    let mut listing = CachedDir {
+        modified_millis,
+        entries: vec![],
+        subdirs: vec![],
+        entry_count: 0,
+    };
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return listing;
+    };
+    let mut children: Vec<(PathBuf, bool)> = read
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            (entry.path(), is_dir)
+        })
+        .collect();
+    children.sort();
+    listing.entry_count = children.len();
+
+    if children
+        .iter()
+        .any(|(path, _)| path.ends_with(SIDECAR_NAME))
+    {
+        listing.entries.extend(production_entry(dir));
+        return listing;
+    }
+
+    let extension = |path: &Path, wanted: &str| {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(wanted))
+    };
+    for (path, is_dir) in children {
+        if is_dir {
+            // Hidden directories hold caches and version control, not productions
+            let hidden = path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if !hidden {
+                listing.subdirs.push(path.to_string_lossy().to_string());
+            }
+        } else if extension(&path, "gvproj") {
+            listing.entries.push(project_entry(&path));
+        } else if extension(&path, "ply") {
+            listing.entries.extend(splat_entry(&path));
+        }
+    }
+    listing
+}
+
+fn production_entry(dir: &Path) -> Option<LibraryEntry> {
+    let sidecar = sidecar::read(dir).ok().flatten();
+    let artifact = sidecar::find_artifact(dir).ok();
+    let created_at = sidecar
+        .as_ref()
+        .map(|s| s.created_at)
+        .or_else(|| created_at(dir));
+    Some(LibraryEntry {
+        kind: LibraryKind::Production,
+        name: file_name(dir),
+        path: dir.to_string_lossy().to_string(),
+        artifact_size: artifact.as_deref().and_then(file_size),
+        splat_count: artifact.as_deref().and_then(splat_count),
+        artifact_path: artifact.map(|a| a.to_string_lossy().to_string()),
+        created_at,
+    })
+}
+
+fn project_entry(path: &Path) -> LibraryEntry {
+    // The name the project was saved under, falling back to its file name
+    let name = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|project| project["metadata"]["name"].as_str().map(String::from))
+        .unwrap_or_else(|| file_stem(path));
+    LibraryEntry {
+        kind: LibraryKind::Project,
+        name,
+        path: path.to_string_lossy().to_string(),
+        artifact_path: None,
+        artifact_size: None,
+        splat_count: None,
+        created_at: created_at(path),
+    }
+}
+
+/// A PLY counts only when its header describes Gaussian splats
+fn splat_entry(path: &Path) -> Option<LibraryEntry> {
+    let splat_count = splat_count(path)?;
+    Some(LibraryEntry {
+        kind: LibraryKind::Splat,
+        name: file_stem(path),
+        path: path
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        artifact_path: Some(path.to_string_lossy().to_string()),
+        artifact_size: file_size(path),
+        splat_count: Some(splat_count),
+        created_at: created_at(path),
+    })
+}
+
+fn splat_count(path: &Path) -> Option<usize> {
+    let mut header = vec![];
+    std::fs::File::open(path)
+        .ok()?
+        .take(conversion::MAX_HEADER_LEN)
+        .read_to_end(&mut header)
+        .ok()?;
+    conversion::splat_count(&header).ok()
+}
+
+/// Add entries that are not recent productions yet, returning how many were added
+fn import_recents(store: &impl SettingsStore, entries: &[LibraryEntry]) -> Result<usize, AppError> {
+    store.update_settings(Persist::Now, |settings| {
+        let mut added = 0;
+        for entry in entries {
+            if settings
+                .recent_productions
+                .iter()
+                .any(|r| r.path == entry.path)
+            {
+                continue;
+            }
+            settings.recent_productions.push(RecentProduction {
+                id: job_log::new_id("imported"),
+                name: entry.name.clone(),
+                path: entry.path.clone(),
+                last_opened: String::new(),
+                tags: vec![],
+                notes: String::new(),
+                imported: true,
+                missing: false,
+                pinned: false,
+            });
+            added += 1;
+        }
+        Ok(added)
+    })
+}
+
+fn cache_path(paths: &impl PathProvider, root: &str) -> Result<PathBuf, AppError> {
+    let hex: String = Sha256::digest(root.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    resolve_app_data(&paths.app_data_dir()?, &format!("library/{}.json", hex))
+}
+
+// A missing or unreadable cache only costs a full scan
+fn load_cache(path: &Path) -> ScanCache {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn modified_millis(path: &Path) -> Option<u128> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_millis())
+}
+
+fn created_at(path: &Path) -> Option<u64> {
+    let metadata = std::fs::metadata(path).ok()?;
+    // Not every filesystem records a creation time
+    let time: SystemTime = metadata.created().or_else(|_| metadata.modified()).ok()?;
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_secs())
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path).ok().map(|m| m.len())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::AppSettings;
+    use crate::sidecar::{ProductionSource, Sidecar};
+
+    fn splat(points: usize) -> Vec<u8> {
+        sample_ply(&vec![[0.0; 14]; points])
+    }
+
+    fn library(paths: &TempPaths) -> PathBuf {
+        let root = paths.root().join("captures");
+        let production = root.join("harbour");
+        std::fs::create_dir_all(&production).unwrap();
+        std::fs::write(production.join("output.ply"), splat(3)).unwrap();
+        sidecar::write(
+            &production,
+            &Sidecar {
+                job_id: "job-1".to_string(),
+                production_dir: production.to_string_lossy().to_string(),
+                artifact_path: production.join("output.ply").to_string_lossy().to_string(),
+                preset: "balanced".to_string(),
+                videos: vec![],
+                created_at: 1_700_000_000,
+                artifact_sha256: None,
+                capture_type: Default::default(),
+                archive: None,
+                preview: false,
+                input_sha256: vec![],
+                command: None,
+                training: None,
+                derivatives: vec![],
+                source: ProductionSource::Job,
+                imported_from: None,
+                resolution_normalization: None,
+                camera_groups: vec![],
+                artifacts: vec![],
+            },
+        )
+        .unwrap();
+
+        let elsewhere = root.join("from-laptop").join("day 2");
+        std::fs::create_dir_all(&elsewhere).unwrap();
+        std::fs::write(elsewhere.join("bridge.ply"), splat(2)).unwrap();
+        std::fs::write(
+            elsewhere.join("scan.ply"),
+            b"ply\nformat ascii 1.0\nend_header\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("pier.gvproj"),
+            r#"{"type":"gameview-project","metadata":{"name":"Pier at dusk"}}"#,
+        )
+        .unwrap();
+        root
+    }
+
+    fn run(root: &Path, cache: &mut ScanCache) -> LibraryScan {
+        scan(root, cache, &AtomicBool::new(false), &mut |_, _| {}).unwrap()
+    }
+
+    #[test]
+    fn finds_sidecars_projects_and_splats() {
+        let paths = TempPaths::new();
+        let root = library(&paths);
+        let scan = run(&root, &mut ScanCache::new());
+
+        let found: Vec<(LibraryKind, &str, Option<usize>)> = scan
+            .entries
+            .iter()
+            .map(|e| (e.kind, e.name.as_str(), e.splat_count))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (LibraryKind::Splat, "bridge", Some(2)),
+                (LibraryKind::Production, "harbour", Some(3)),
+                (LibraryKind::Project, "Pier at dusk", None),
+            ]
+        );
+        assert_eq!(scan.entries[1].created_at, Some(1_700_000_000));
+        assert!(scan.entries[1].artifact_size.unwrap() > 0);
+    }
+
+    #[test]
+    fn rescans_read_only_changed_directories() {
+        let paths = TempPaths::new();
+        let root = library(&paths);
+        let mut cache = ScanCache::new();
+        let first = run(&root, &mut cache);
+        assert_eq!(first.directories_cached, 0);
+
+        let second = run(&root, &mut cache);
+        assert_eq!(second.directories_read, 0);
+        assert_eq!(second.entries, first.entries);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let day_2 = root.join("from-laptop").join("day 2");
+        std::fs::write(day_2.join("ferry.ply"), splat(1)).unwrap();
+        let third = run(&root, &mut cache);
+        assert_eq!(third.directories_read, 1);
+        assert_eq!(third.entries.len(), 4);
+    }
+
+    #[test]
+    fn cancelled_scan_stops() {
+        let paths = TempPaths::new();
+        let root = library(&paths);
+        let err = scan(
+            &root,
+            &mut ScanCache::new(),
+            &AtomicBool::new(true),
+            &mut |_, _| {},
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), "cancelled");
+    }
+
+    #[test]
+    fn import_adds_only_new_productions() {
+        let paths = TempPaths::new();
+        let root = library(&paths);
+        let scan = run(&root, &mut ScanCache::new());
+        let store = MemorySettings::with(AppSettings::default());
+
+        assert_eq!(import_recents(&store, &scan.entries).unwrap(), 3);
+        assert_eq!(import_recents(&store, &scan.entries).unwrap(), 0);
+        let recents = store.settings().recent_productions;
+        assert!(recents.iter().all(|r| r.imported));
+    }
+}
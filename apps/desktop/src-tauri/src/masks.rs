@@ -0,0 +1,392 @@
+//! Mask Validation
+//!
+//! Checks a directory of mask PNGs against the input clips before a job starts.
+//! Masks are named either `<clip>.png` (one mask for the whole clip) or
+//! `<clip>_<frame>.png` (one mask per extracted frame, 1-based). How many
+//! frames a clip yields, and at what size, is only known once the job has
+//! settled how it runs: normalization and previews scale frames down. A job
+//! therefore checks each clip's masks against its frames as the CLI gets
+//! them. Per-frame masks make the backend extract the frames itself, so they
+//! can be counted rather than estimated from the clip's duration.
+
+use crate::error::AppError;
+use crate::media::{self, Resolution};
+use crate::path_policy::PathPolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use tauri::State;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+// PNG color types without chroma: grayscale and grayscale with alpha
+const PNG_GRAYSCALE: u8 = 0;
+const PNG_GRAYSCALE_ALPHA: u8 = 4;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaskIssue {
+    pub file: String,
+    pub problem: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaskValidation {
+    pub valid: bool,
+    pub mask_count: usize,
+    pub issues: Vec<MaskIssue>,
+}
+
+/// Validate a mask directory against the clips it will be applied to
+#[tauri::command]
+pub async fn validate_masks(
+    policy: State<'_, PathPolicy>,
+    videos: Vec<String>,
+    masks_dir: String,
+) -> Result<MaskValidation, AppError> {
+    for path in &videos {
+        policy.check_existing(path)?;
+    }
+    policy.check_existing(&masks_dir)?;
+    Ok(validate(&videos, &masks_dir, true).await?)
+}
+
+/// Check every mask's name, header and color type, and with `compare_sizes`
+/// its size against the clip it is named after. A job leaves sizes to
+/// check_frames, as its frames may be scaled down.
+pub async fn validate(
+    videos: &[String],
+    masks_dir: &str,
+    compare_sizes: bool,
+) -> Result<MaskValidation, String> {
+    let dir = Path::new(masks_dir);
+    if !dir.is_dir() {
+        return Err(format!("Mask directory not found: {}", masks_dir));
+    }
+
+    let mut masks: Vec<String> = std::fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.to_lowercase().ends_with(".png"))
+        .collect();
+    masks.sort();
+
+    let mut issues = vec![];
+    let mut per_clip: HashMap<String, Vec<String>> = HashMap::new();
+
+    let stems: Vec<String> = videos.iter().map(|v| file_stem(v)).collect();
+    for mask in &masks {
+        let stem = &mask[..mask.len() - 4];
+        match clip_for_mask(stem, &stems) {
+            Some(clip) => per_clip.entry(clip).or_default().push(mask.clone()),
+            None => issues.push(MaskIssue {
+                file: mask.clone(),
+                problem: "Name does not match any input clip".to_string(),
+            }),
+        }
+    }
+
+    for video in videos {
+        let stem = file_stem(video);
+        let files = per_clip.remove(&stem).unwrap_or_default();
+        if files.is_empty() {
+            issues.push(MaskIssue {
+                file: video.clone(),
+                problem: "No masks for this clip".to_string(),
+            });
+            continue;
+        }
+
+        let size = if compare_sizes {
+            match media::probe(video).await {
+                Ok(metadata) => Some(metadata.resolution()),
+                Err(e) => {
+                    issues.push(MaskIssue {
+                        file: video.clone(),
+                        problem: e,
+                    });
+                    continue;
+                }
+            }
+        } else {
+            None
+        };
+
+        for file in files {
+            match read_png_header(&dir.join(&file)) {
+                Ok((width, height, color_type)) => {
+                    if let Some(issue) = size_issue(&file, (width, height), size) {
+                        issues.push(issue);
+                    }
+                    if color_type != PNG_GRAYSCALE && color_type != PNG_GRAYSCALE_ALPHA {
+                        issues.push(MaskIssue {
+                            file,
+                            problem: "Mask is not a grayscale image".to_string(),
+                        });
+                    }
+                }
+                Err(e) => issues.push(MaskIssue { file, problem: e }),
+            }
+        }
+    }
+
+    Ok(MaskValidation {
+        valid: issues.is_empty(),
+        mask_count: masks.len(),
+        issues,
+    })
+}
+
+/// Whether the masks for `video` are one per frame rather than one for the
+/// whole clip
+pub fn per_frame(video: &str, masks_dir: &str) -> bool {
+    let stem = file_stem(video);
+    clip_masks(&stem, masks_dir)
+        .is_ok_and(|files| !files.is_empty() && files != [format!("{}.png", stem)])
+}
+
+/// Check the masks for `video` against its frames as the CLI gets them: each
+/// mask against `size`, when known, and per-frame masks against the number of
+/// `frames`, which must be known for them. A single `<clip>.png` covers any
+/// number of frames.
+pub fn check_frames(
+    video: &str,
+    masks_dir: &str,
+    size: Option<Resolution>,
+    frames: Option<usize>,
+) -> Result<(), MaskIssue> {
+    let stem = file_stem(video);
+    let files = clip_masks(&stem, masks_dir).map_err(|e| MaskIssue {
+        file: masks_dir.to_string(),
+        problem: e.to_string(),
+    })?;
+    for file in &files {
+        let (width, height, _) =
+            read_png_header(&Path::new(masks_dir).join(file)).map_err(|problem| MaskIssue {
+                file: file.clone(),
+                problem,
+            })?;
+        if let Some(issue) = size_issue(file, (width, height), size) {
+            return Err(issue);
+        }
+    }
+    if !per_frame(video, masks_dir) {
+        return Ok(());
+    }
+    match frames {
+        Some(frames) if frames == files.len() => Ok(()),
+        Some(frames) => Err(MaskIssue {
+            file: video.to_string(),
+            problem: format!(
+                "{} frames were extracted, but there are {} per-frame masks",
+                frames,
+                files.len()
+            ),
+        }),
+        None => Err(MaskIssue {
+            file: video.to_string(),
+            problem: "Per-frame masks cannot be counted against frames the CLI extracts"
+                .to_string(),
+        }),
+    }
+}
+
+/// Size of the frames extracted into `frames_dir`, read from the first; None
+/// when there are none
+pub fn frame_size(frames_dir: &Path) -> Option<Resolution> {
+    let first = std::fs::read_dir(frames_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        })
+        .min()?;
+    let (width, height, _) = read_png_header(&first).ok()?;
+    Some(Resolution { width, height })
+}
+
+// The masks named after the clip with `stem`
+fn clip_masks(stem: &str, masks_dir: &str) -> std::io::Result<Vec<String>> {
+    let mut files: Vec<String> = std::fs::read_dir(masks_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.to_lowercase().ends_with(".png"))
+        .filter(|name| clip_for_mask(&name[..name.len() - 4], &[stem.to_string()]).is_some())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn size_issue(
+    file: &str,
+    (width, height): (u32, u32),
+    size: Option<Resolution>,
+) -> Option<MaskIssue> {
+    let size = size.filter(|size| (width, height) != (size.width, size.height))?;
+    Some(MaskIssue {
+        file: file.to_string(),
+        problem: format!("Mask is {}x{} but frames are {}", width, height, size),
+    })
+}
+
+// Match `<clip>` or `<clip>_<frame>` to an input clip stem
+fn clip_for_mask(mask_stem: &str, clip_stems: &[String]) -> Option<String> {
+    if clip_stems.iter().any(|s| s == mask_stem) {
+        return Some(mask_stem.to_string());
+    }
+    let (clip, frame) = mask_stem.rsplit_once('_')?;
+    if frame.chars().all(|c| c.is_ascii_digit()) && clip_stems.iter().any(|s| s == clip) {
+        Some(clip.to_string())
+    } else {
+        None
+    }
+}
+
+fn file_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+// Read width, height and color type from the IHDR chunk without decoding the image
+fn read_png_header(path: &Path) -> Result<(u32, u32, u8), String> {
+    let mut header = [0u8; 26];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .map_err(|_| "Not a readable PNG file".to_string())?;
+
+    if header[..8] != PNG_SIGNATURE || &header[12..16] != b"IHDR" {
+        return Err("Not a valid PNG file".to_string());
+    }
+
+    let width = u32::from_be_bytes([header[16], header[17], header[18], header[19]]);
+    let height = u32::from_be_bytes([header[20], header[21], header[22], header[23]]);
+    Ok((width, height, header[25]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn write_png(path: &Path, width: u32, height: u32, color_type: u8) {
+        let mut header = PNG_SIGNATURE.to_vec();
+        header.extend(13u32.to_be_bytes());
+        header.extend(b"IHDR");
+        header.extend(width.to_be_bytes());
+        header.extend(height.to_be_bytes());
+        header.extend([8, color_type]);
+        std::fs::write(path, header).unwrap();
+    }
+
+    #[test]
+    fn masks_are_matched_to_clips_by_name() {
+        let stems = vec!["harbour".to_string(), "pier_2".to_string()];
+        assert_eq!(clip_for_mask("harbour", &stems).as_deref(), Some("harbour"));
+        assert_eq!(
+            clip_for_mask("harbour_0012", &stems).as_deref(),
+            Some("harbour")
+        );
+        assert_eq!(clip_for_mask("pier_2", &stems).as_deref(), Some("pier_2"));
+        assert_eq!(clip_for_mask("harbour_a", &stems), None);
+        assert_eq!(clip_for_mask("beach_1", &stems), None);
+    }
+
+    #[test]
+    fn png_headers_are_read_without_decoding() {
+        let paths = TempPaths::new();
+        let png = paths.root().join("mask.png");
+        write_png(&png, 1920, 1080, PNG_GRAYSCALE);
+        assert_eq!(read_png_header(&png).unwrap(), (1920, 1080, PNG_GRAYSCALE));
+
+        let text = paths.root().join("text.png");
+        std::fs::write(&text, [b'x'; 32]).unwrap();
+        assert!(read_png_header(&text).is_err());
+    }
+
+    #[test]
+    fn per_frame_masks_are_counted_against_the_extracted_frames() {
+        let paths = TempPaths::new();
+        let dir = paths.root();
+        for frame in 1..=3 {
+            write_png(
+                &dir.join(format!("harbour_{}.png", frame)),
+                4,
+                4,
+                PNG_GRAYSCALE,
+            );
+        }
+        write_png(&dir.join("pier.png"), 4, 4, PNG_GRAYSCALE);
+        let masks_dir = dir.to_string_lossy().to_string();
+
+        assert!(per_frame("/clips/harbour.mp4", &masks_dir));
+        assert!(!per_frame("/clips/pier.mp4", &masks_dir));
+        assert!(!per_frame("/clips/beach.mp4", &masks_dir));
+
+        assert!(check_frames("/clips/harbour.mp4", &masks_dir, None, Some(3)).is_ok());
+        let issue = check_frames("/clips/harbour.mp4", &masks_dir, None, Some(5)).unwrap_err();
+        assert_eq!(issue.file, "/clips/harbour.mp4");
+        assert!(issue.problem.contains("5 frames"), "{}", issue.problem);
+        // Frames the CLI extracts cannot be counted
+        assert!(check_frames("/clips/harbour.mp4", &masks_dir, None, None).is_err());
+        // One mask for the whole clip fits however many frames it yields
+        assert!(check_frames("/clips/pier.mp4", &masks_dir, None, Some(40)).is_ok());
+        assert!(check_frames("/clips/pier.mp4", &masks_dir, None, None).is_ok());
+    }
+
+    #[test]
+    fn masks_are_sized_against_the_frames_as_run() {
+        let paths = TempPaths::new();
+        let masks = paths.root().join("masks");
+        let frames = paths.root().join("frames");
+        std::fs::create_dir_all(&masks).unwrap();
+        std::fs::create_dir_all(&frames).unwrap();
+        write_png(&masks.join("harbour.png"), 1920, 1080, PNG_GRAYSCALE);
+        write_png(&frames.join("frame_00002.png"), 1280, 720, PNG_GRAYSCALE);
+        write_png(&frames.join("frame_00001.png"), 1280, 720, PNG_GRAYSCALE);
+        let masks_dir = masks.to_string_lossy().to_string();
+
+        let full = Resolution {
+            width: 1920,
+            height: 1080,
+        };
+        assert!(check_frames("/clips/harbour.mp4", &masks_dir, Some(full), None).is_ok());
+        // Scaled down to match other clips, the frames no longer fit the mask
+        let scaled = frame_size(&frames).unwrap();
+        assert_eq!((scaled.width, scaled.height), (1280, 720));
+        let issue =
+            check_frames("/clips/harbour.mp4", &masks_dir, Some(scaled), Some(2)).unwrap_err();
+        assert_eq!(issue.file, "harbour.png");
+        assert_eq!(issue.problem, "Mask is 1920x1080 but frames are 1280x720");
+        assert_eq!(frame_size(&masks.join("none")), None);
+    }
+
+    #[tokio::test]
+    async fn unmatched_masks_and_clips_without_masks_are_issues() {
+        let paths = TempPaths::new();
+        write_png(&paths.root().join("beach_1.png"), 4, 4, PNG_GRAYSCALE);
+        let masks_dir = paths.root().to_string_lossy().to_string();
+
+        let validation = validate(&["/clips/harbour.mp4".to_string()], &masks_dir, true)
+            .await
+            .unwrap();
+        assert!(!validation.valid);
+        assert_eq!(validation.mask_count, 1);
+        let issues: Vec<(&str, &str)> = validation
+            .issues
+            .iter()
+            .map(|i| (i.file.as_str(), i.problem.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                ("beach_1.png", "Name does not match any input clip"),
+                ("/clips/harbour.mp4", "No masks for this clip"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,548 @@
+//! Media Inspection
+//!
+//! Probes input videos with ffprobe and validates them before processing.
+//!
+//! Clips of different resolutions, such as 4K drone footage with 1080p phone
+//! clips, make COLMAP's intrinsics estimation flaky and spend time on frames
+//! larger than the rest. Validation groups the clips by resolution and, when
+//! there is more than one group, warns with the groups and the smallest of
+//! them, which a job given normalize_resolution scales the others down to.
+
+use crate::camera_groups::{self, CameraGroup};
+use crate::camera_intrinsics::{self, CaptureDevice};
+use crate::error::AppError;
+use crate::ffmpeg::{self, Tool};
+use crate::path_policy::PathPolicy;
+use crate::prefetch;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Transfer characteristics used by HDR footage (PQ and HLG)
+const HDR_TRANSFERS: [&str; 2] = ["smpte2084", "arib-std-b67"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    #[default]
+    Flat,
+    Equirect360,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    fn pixels(self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// No larger than `target` either way, so it needs no scaling down to it
+    pub fn fits(self, target: Resolution) -> bool {
+        self.width <= target.width && self.height <= target.height
+    }
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// Clips of one resolution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolutionGroup {
+    pub resolution: Resolution,
+    pub clips: Vec<String>,
+}
+
+/// Clips of more than one resolution, and the smallest, which
+/// normalize_resolution scales the larger ones down to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolutionMismatch {
+    /// Largest first
+    pub groups: Vec<ResolutionGroup>,
+    pub target: Resolution,
+    pub warning: String,
+}
+
+/// How a job brought its clips to one resolution, as its sidecar records it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolutionNormalization {
+    pub target: Resolution,
+    /// Clips whose frames were scaled to fit the target, keeping their aspect ratio
+    pub scaled: Vec<String>,
+    /// Clips already no larger than the target, left at full quality
+    pub unscaled: Vec<String>,
+}
+
+impl ResolutionMismatch {
+    /// The clips' resolutions when they differ; None when there is one
+    pub fn of(clips: &[(&str, Resolution)]) -> Option<ResolutionMismatch> {
+        let mut groups: Vec<ResolutionGroup> = vec![];
+        for (clip, resolution) in clips {
+            match groups.iter_mut().find(|g| g.resolution == *resolution) {
+                Some(group) => group.clips.push(clip.to_string()),
+                None => groups.push(ResolutionGroup {
+                    resolution: *resolution,
+                    clips: vec![clip.to_string()],
+                }),
+            }
+        }
+        if groups.len() < 2 {
+            return None;
+        }
+        groups.sort_by_key(|g| std::cmp::Reverse((g.resolution.pixels(), g.resolution)));
+        let target = groups.last()?.resolution;
+        let sizes: Vec<String> = groups.iter().map(|g| g.resolution.to_string()).collect();
+        let warning = format!(
+            "Clips have different resolutions ({}); camera estimation is more reliable with normalize_resolution, which scales them down to {}",
+            sizes.join(", "),
+            target
+        );
+        Some(ResolutionMismatch {
+            groups,
+            target,
+            warning,
+        })
+    }
+
+    /// The clips to scale down to the target and those to leave as they are
+    pub fn normalization(&self) -> ResolutionNormalization {
+        let (unscaled, scaled): (Vec<_>, Vec<_>) = self
+            .groups
+            .iter()
+            .partition(|g| g.resolution.fits(self.target));
+        let clips = |groups: Vec<&ResolutionGroup>| {
+            let mut clips: Vec<String> = groups.into_iter().flat_map(|g| g.clips.clone()).collect();
+            clips.sort();
+            clips
+        };
+        ResolutionNormalization {
+            target: self.target,
+            scaled: clips(scaled),
+            unscaled: clips(unscaled),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoMetadata {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub duration_secs: f64,
+    pub frame_rate: f64,
+    pub codec: String,
+    pub pixel_format: String,
+    pub bit_depth: u32,
+    pub color_transfer: Option<String>,
+    pub color_primaries: Option<String>,
+    pub is_hdr: bool,
+    pub projection: Projection,
+    /// The phone, drone or camera the clip says it was shot with
+    #[serde(default)]
+    pub device: Option<CaptureDevice>,
+}
+
+impl VideoMetadata {
+    pub fn resolution(&self) -> Resolution {
+        Resolution {
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClipValidation {
+    pub path: String,
+    pub metadata: Option<VideoMetadata>,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoValidation {
+    pub valid: bool,
+    pub clips: Vec<ClipValidation>,
+    /// Problems with the set of clips as a whole
+    pub errors: Vec<String>,
+    /// Present when the clips differ in resolution
+    pub resolution_mismatch: Option<ResolutionMismatch>,
+    /// Clips the metadata says one camera shot, for the camera groups to start from
+    pub suggested_groups: Vec<CameraGroup>,
+}
+
+/// Get metadata for a video file using ffprobe
+#[tauri::command]
+pub async fn get_video_metadata(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+) -> Result<VideoMetadata, AppError> {
+    policy.check_existing(&path)?;
+    if let Some(metadata) = prefetch::cached_metadata(&app, &path) {
+        return Ok(metadata);
+    }
+    Ok(probe(&path).await?)
+}
+
+/// Validate a set of input videos before processing
+#[tauri::command]
+pub async fn validate_videos(
+    policy: State<'_, PathPolicy>,
+    videos: Vec<String>,
+    allow_mixed_projection: Option<bool>,
+) -> Result<VideoValidation, AppError> {
+    // Missing files are reported per clip below, so only the location is checked here
+    for path in &videos {
+        policy.check_target(path)?;
+    }
+
+    let mut clips = Vec::with_capacity(videos.len());
+
+    for path in videos {
+        let mut clip = ClipValidation {
+            path: path.clone(),
+            metadata: None,
+            warnings: vec![],
+            errors: vec![],
+        };
+
+        if !Path::new(&path).is_file() {
+            clip.errors.push("File not found".to_string());
+            clips.push(clip);
+            continue;
+        }
+
+        match probe(&path).await {
+            Ok(metadata) => {
+                if metadata.is_hdr {
+                    clip.warnings.push(format!(
+                        "HDR video ({}, {}-bit): enable tone mapping to avoid washed-out frames",
+                        metadata.color_transfer.as_deref().unwrap_or("bt2020"),
+                        metadata.bit_depth
+                    ));
+                } else if metadata.bit_depth > 8 {
+                    clip.warnings.push(format!(
+                        "{}-bit video: frames will be converted to 8-bit",
+                        metadata.bit_depth
+                    ));
+                }
+                clip.metadata = Some(metadata);
+            }
+            Err(e) => clip.errors.push(e),
+        }
+
+        clips.push(clip);
+    }
+
+    let mut errors = vec![];
+    let projections: Vec<Projection> = clips
+        .iter()
+        .filter_map(|c| c.metadata.as_ref().map(|m| m.projection))
+        .collect();
+    if !allow_mixed_projection.unwrap_or(false) {
+        if let Err(e) = check_projection_mix(&projections) {
+            errors.push(e);
+        }
+    }
+
+    let resolutions: Vec<(&str, Resolution)> = clips
+        .iter()
+        .filter_map(|c| Some((c.path.as_str(), c.metadata.as_ref()?.resolution())))
+        .collect();
+    let resolution_mismatch = ResolutionMismatch::of(&resolutions);
+    let devices: Vec<(&str, Option<&CaptureDevice>)> = clips
+        .iter()
+        .map(|c| {
+            (
+                c.path.as_str(),
+                c.metadata.as_ref().and_then(|m| m.device.as_ref()),
+            )
+        })
+        .collect();
+    let suggested_groups = camera_groups::suggest(&devices);
+
+    let valid = errors.is_empty() && clips.iter().all(|c| c.errors.is_empty());
+    Ok(VideoValidation {
+        valid,
+        clips,
+        errors,
+        resolution_mismatch,
+        suggested_groups,
+    })
+}
+
+/// COLMAP handles a mix of 360 and flat clips poorly, so refuse it by default
+pub fn check_projection_mix(projections: &[Projection]) -> Result<(), String> {
+    let has_360 = projections.contains(&Projection::Equirect360);
+    let has_flat = projections.contains(&Projection::Flat);
+    if has_360 && has_flat {
+        Err(
+            "Cannot mix 360° and flat clips in one job; process them separately or force the mix"
+                .to_string(),
+        )
+    } else {
+        Ok(())
+    }
+}
+
+/// Run ffprobe on a file and extract the first video stream's metadata
+pub async fn probe(path: &str) -> Result<VideoMetadata, String> {
+    let args = [
+        "-v",
+        "error",
+        "-print_format",
+        "json",
+        "-show_format",
+        "-show_streams",
+        path,
+    ];
+    let output = ffmpeg::run(Tool::Ffprobe, args, PROBE_TIMEOUT).await?;
+
+    if !output.success {
+        return Err(format!("ffprobe failed: {}", output.stderr));
+    }
+
+    let json: Value = serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    metadata_of(path, &json)
+}
+
+// The metadata of the first video stream in ffprobe's JSON output
+fn metadata_of(path: &str, json: &Value) -> Result<VideoMetadata, String> {
+    let stream = json["streams"]
+        .as_array()
+        .and_then(|streams| streams.iter().find(|s| s["codec_type"] == "video"))
+        .ok_or("No video stream found")?;
+
+    let pixel_format = stream["pix_fmt"].as_str().unwrap_or_default().to_string();
+    let bit_depth = stream["bits_per_raw_sample"]
+        .as_str()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(|| bit_depth_from_pix_fmt(&pixel_format));
+    let color_transfer = stream["color_transfer"].as_str().map(String::from);
+    let color_primaries = stream["color_primaries"].as_str().map(String::from);
+
+    let is_hdr = color_transfer
+        .as_deref()
+        .is_some_and(|t| HDR_TRANSFERS.contains(&t))
+        || (color_primaries.as_deref() == Some("bt2020") && bit_depth > 8);
+
+    let width = stream["width"].as_u64().unwrap_or(0) as u32;
+    let height = stream["height"].as_u64().unwrap_or(0) as u32;
+    let projection = detect_projection(stream, width, height);
+
+    let duration_secs = stream["duration"]
+        .as_str()
+        .or_else(|| json["format"]["duration"].as_str())
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0.0);
+
+    Ok(VideoMetadata {
+        path: path.to_string(),
+        width,
+        height,
+        duration_secs,
+        frame_rate: parse_frame_rate(stream["avg_frame_rate"].as_str().unwrap_or("0/1")),
+        codec: stream["codec_name"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        pixel_format,
+        bit_depth,
+        color_transfer,
+        color_primaries,
+        is_hdr,
+        projection,
+        device: camera_intrinsics::device_of(&json["format"]["tags"]),
+    })
+}
+
+// Equirectangular clips carry spherical metadata (Spherical Video V1/V2 atoms)
+// and, for stitched 360 footage, a 2:1 frame
+fn detect_projection(stream: &Value, width: u32, height: u32) -> Projection {
+    let spherical = stream["side_data_list"].as_array().and_then(|list| {
+        list.iter()
+            .find(|d| d["side_data_type"] == "Spherical Mapping")
+    });
+
+    match spherical {
+        Some(data) if data["projection"] == "equirectangular" => Projection::Equirect360,
+        Some(_) if height > 0 && width == height * 2 => Projection::Equirect360,
+        _ => Projection::Flat,
+    }
+}
+
+// ffprobe reports rates as fractions like "30000/1001"
+fn parse_frame_rate(rate: &str) -> f64 {
+    match rate.split_once('/') {
+        Some((num, den)) => {
+            let num: f64 = num.parse().unwrap_or(0.0);
+            let den: f64 = den.parse().unwrap_or(1.0);
+            if den == 0.0 {
+                0.0
+            } else {
+                num / den
+            }
+        }
+        None => rate.parse().unwrap_or(0.0),
+    }
+}
+
+// Pixel formats encode depth in their name, e.g. yuv420p10le or p010le
+fn bit_depth_from_pix_fmt(pix_fmt: &str) -> u32 {
+    if pix_fmt.contains("12") {
+        12
+    } else if pix_fmt.contains("10") {
+        10
+    } else {
+        8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UHD: Resolution = Resolution {
+        width: 3840,
+        height: 2160,
+    };
+    const HD: Resolution = Resolution {
+        width: 1920,
+        height: 1080,
+    };
+
+    #[test]
+    fn mixed_resolutions_scale_down_to_the_smallest() {
+        assert_eq!(
+            ResolutionMismatch::of(&[("/a.mp4", HD), ("/b.mp4", HD)]),
+            None
+        );
+
+        let two_k = Resolution {
+            width: 2704,
+            height: 1520,
+        };
+        let mismatch = ResolutionMismatch::of(&[
+            ("/phone.mp4", HD),
+            ("/drone.mp4", UHD),
+            ("/phone-2.mp4", HD),
+            ("/drone-2.mp4", two_k),
+        ])
+        .unwrap();
+        let groups: Vec<(Resolution, usize)> = mismatch
+            .groups
+            .iter()
+            .map(|g| (g.resolution, g.clips.len()))
+            .collect();
+        assert_eq!(groups, [(UHD, 1), (two_k, 1), (HD, 2)]);
+        assert_eq!(mismatch.target, HD);
+        assert!(mismatch.warning.contains("3840x2160, 2704x1520, 1920x1080"));
+
+        let normalization = mismatch.normalization();
+        assert_eq!(normalization.scaled, ["/drone-2.mp4", "/drone.mp4"]);
+        assert_eq!(normalization.unscaled, ["/phone-2.mp4", "/phone.mp4"]);
+    }
+
+    fn probed(stream: Value) -> VideoMetadata {
+        let json = serde_json::json!({
+            "streams": [{"codec_type": "audio"}, stream],
+            "format": {"duration": "12.5"},
+        });
+        metadata_of("/clips/harbour.mp4", &json).unwrap()
+    }
+
+    #[test]
+    fn hdr_is_read_from_the_transfer_or_a_deep_bt2020_stream() {
+        let pq = probed(serde_json::json!({
+            "codec_type": "video",
+            "pix_fmt": "yuv420p10le",
+            "color_transfer": "smpte2084",
+            "color_primaries": "bt2020",
+        }));
+        assert!(pq.is_hdr);
+        assert_eq!(pq.bit_depth, 10);
+        assert_eq!(pq.duration_secs, 12.5);
+
+        let hlg = probed(serde_json::json!({
+            "codec_type": "video",
+            "pix_fmt": "yuv420p10le",
+            "color_transfer": "arib-std-b67",
+        }));
+        assert!(hlg.is_hdr);
+
+        // Wide gamut without an HDR transfer counts only beyond 8 bits
+        let deep = probed(serde_json::json!({
+            "codec_type": "video",
+            "pix_fmt": "yuv420p",
+            "bits_per_raw_sample": "12",
+            "color_primaries": "bt2020",
+        }));
+        assert!(deep.is_hdr);
+        assert_eq!(deep.bit_depth, 12);
+        let shallow = probed(serde_json::json!({
+            "codec_type": "video",
+            "pix_fmt": "yuv420p",
+            "color_primaries": "bt2020",
+        }));
+        assert!(!shallow.is_hdr);
+
+        let sdr = probed(serde_json::json!({
+            "codec_type": "video",
+            "pix_fmt": "yuv420p",
+            "color_transfer": "bt709",
+        }));
+        assert!(!sdr.is_hdr);
+        assert_eq!(sdr.bit_depth, 8);
+    }
+
+    #[test]
+    fn equirectangular_clips_are_told_by_their_spherical_metadata() {
+        let spherical = |mapping: Value, width: u32, height: u32| {
+            probed(serde_json::json!({
+                "codec_type": "video",
+                "width": width,
+                "height": height,
+                "side_data_list": [mapping],
+            }))
+            .projection
+        };
+        let equirect = serde_json::json!({
+            "side_data_type": "Spherical Mapping",
+            "projection": "equirectangular",
+        });
+        assert_eq!(spherical(equirect, 1920, 1080), Projection::Equirect360);
+        // Spherical without a projection named, in a stitched 2:1 frame
+        let unnamed = serde_json::json!({"side_data_type": "Spherical Mapping"});
+        assert_eq!(
+            spherical(unnamed.clone(), 5760, 2880),
+            Projection::Equirect360
+        );
+        assert_eq!(spherical(unnamed, 1920, 1080), Projection::Flat);
+        // A 2:1 frame alone is not enough
+        let display = serde_json::json!({"side_data_type": "Display Matrix"});
+        assert_eq!(spherical(display, 5760, 2880), Projection::Flat);
+
+        let json = serde_json::json!({"streams": [{"codec_type": "audio"}]});
+        assert!(metadata_of("/clips/harbour.mp4", &json).is_err());
+    }
+
+    #[test]
+    fn mixing_360_and_flat_clips_is_refused() {
+        use Projection::{Equirect360, Flat};
+        assert!(check_projection_mix(&[Flat, Flat]).is_ok());
+        assert!(check_projection_mix(&[Equirect360, Equirect360]).is_ok());
+        assert!(check_projection_mix(&[]).is_ok());
+        assert!(check_projection_mix(&[Flat, Equirect360, Flat]).is_err());
+    }
+}
@@ -0,0 +1,494 @@
+//! Backend Messages
+//!
+//! Text the backend shows the user is sent as a stable key with parameters, so
+//! the frontend can translate it. The built-in English catalog below is what
+//! the frontend falls back to for keys its own translations lack. Free-form
+//! text such as CLI output travels separately as `raw` and is never translated.
+
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// English text of every key; `{name}` is replaced by the parameter of that name
+const CATALOG: &[(&str, &str)] = &[
+    ("error.production_busy", "Production has an active job: {path}"),
+    ("error.not_found", "Not found: {what}"),
+    ("error.invalid_input", "Invalid input"),
+    ("error.invalid_arguments", "{count} fields are not valid"),
+    ("error.path_not_allowed", "Path is not allowed: {path}"),
+    ("error.cancelled", "Cancelled"),
+    ("error.offline_mode", "Offline mode is on"),
+    ("error.job_running", "Not while a job is running"),
+    (
+        "error.needs_restore",
+        "The intermediates of {path} are archived; restore them first",
+    ),
+    (
+        "error.volume_full",
+        "{volume} has {available} free but the job needs {required}",
+    ),
+    (
+        "error.shared_volume_full",
+        "{volume} has {available} free but the job's scratch data and output need {required} together; set a scratch directory on another drive",
+    ),
+    (
+        "error.output_contains_input",
+        "{path} holds the input {other}; choose an output directory of its own",
+    ),
+    (
+        "error.output_inside_input",
+        "{path} is inside the input {other}; choose an output directory of its own",
+    ),
+    (
+        "error.output_inside_app_data",
+        "{path} is inside the app's data directory {other}",
+    ),
+    (
+        "error.output_contains_app_data",
+        "{path} holds the app's data directory {other}",
+    ),
+    (
+        "error.output_not_writable",
+        "{path} cannot be written to; choose a folder of your own",
+    ),
+    (
+        "error.output_read_only",
+        "{path} is on a read-only drive; choose another folder",
+    ),
+    (
+        "error.insufficient_vram",
+        "{gpu} has {detected} of VRAM but the {preset} preset needs {required}; choose a lighter preset or override the VRAM policy",
+    ),
+    ("error.target_missing", "{target} was not found"),
+    (
+        "error.target_missing_suggestion",
+        "{target} was not found; it may be {suggestion}",
+    ),
+    (
+        "error.not_ready",
+        "{count} inputs are still being copied or are empty:\n{paths}",
+    ),
+    ("error.io", "The operation failed"),
+    ("args.not_an_object", "The arguments must be an object"),
+    ("args.required", "{field} is required"),
+    ("args.wrong_type", "{field} has the wrong type: {detail}"),
+    ("args.empty", "{field} must not be empty"),
+    ("args.not_absolute", "{field} must be an absolute path"),
+    ("args.unknown_preset", "{field} is not a known preset: {preset}"),
+    ("args.too_long", "{field} must be at most {max} characters"),
+    ("args.out_of_range", "{field} must be between {min} and {max}"),
+    ("args.duplicate", "{field} is already used: {value}"),
+    ("args.invalid", "{field} is not valid: {detail}"),
+    ("args.set_by_command", "{field} can only be changed with {command}"),
+    ("progress.tone_mapping", "Tone-mapping {video}"),
+    ("progress.extracting_frames", "Extracting frames from {video}"),
+    ("progress.preview_proxy", "Making a preview proxy of {video}"),
+    (
+        "progress.exporting",
+        "Writing the splat file: {written} of about {expected}",
+    ),
+    (
+        "progress.frames_filtered",
+        "Clip {video}: kept {kept} frames, rejected {rejected} ({blurred} blurred, {too_dark} too dark, {too_bright} too bright)",
+    ),
+    ("job.failed", "The job failed"),
+    (
+        "job.publish_failed",
+        "The results could not be moved into {path}",
+    ),
+    ("job.cancelled", "Processing cancelled"),
+    ("job.already_queued", "An identical job is already queued: {entry_id}"),
+    (
+        "job.ambiguous_artifact",
+        "The job wrote more than one artifact at the same time; choose one of:\n{paths}",
+    ),
+    (
+        "job.cancelled_partial",
+        "Processing cancelled during export; the artifact at {path} may be undertrained",
+    ),
+    ("job.spawn_failed", "Failed to spawn CLI"),
+    (
+        "job.spawn_not_found",
+        "gvcore-cli was not found at {path}; reinstall the app or set the CLI location",
+    ),
+    (
+        "job.spawn_not_executable",
+        "gvcore-cli at {path} is not marked executable",
+    ),
+    (
+        "job.spawn_quarantined",
+        "macOS has quarantined the downloaded gvcore-cli at {path}",
+    ),
+    (
+        "job.spawn_wrong_architecture",
+        "gvcore-cli at {path} is built for a different processor; install the build for this machine",
+    ),
+    (
+        "job.spawn_missing_runtime",
+        "gvcore-cli needs the Microsoft Visual C++ Redistributable, which is not installed",
+    ),
+    (
+        "job.spawn_selinux_denied",
+        "SELinux does not allow running gvcore-cli at {path}; restore its security context with restorecon",
+    ),
+    ("job.interrupted", "The app closed before this job finished"),
+    (
+        "job.output_volume_lost",
+        "The drive holding {path} disconnected and did not come back; the job had reached {stage} {progress}%",
+    ),
+    (
+        "job.aborted_low_registration",
+        "Only {registered} of {total} images ({percent}%) registered, below the {minimum}% needed, so training was skipped. Clips with no registered images:\n{clips}",
+    ),
+    ("job.cli_exit_status", "CLI exited with status: {status}"),
+    (
+        "job.mixed_projection",
+        "Cannot mix 360° and flat clips in one job; process them separately or force the mix",
+    ),
+    (
+        "job.cli_no_equirect",
+        "The installed gvcore-cli cannot process 360° footage; update the CLI or mark the clips as flat",
+    ),
+    ("job.cli_no_masks", "The installed gvcore-cli does not support mask images"),
+    (
+        "job.cli_no_auto_mask",
+        "The installed gvcore-cli does not support automatic masking",
+    ),
+    ("job.invalid_masks", "Invalid masks"),
+    (
+        "job.cli_cannot_tone_map",
+        "The installed gvcore-cli cannot tone-map {video}; update the CLI or disable tone mapping",
+    ),
+    (
+        "job.cli_cannot_filter",
+        "The installed gvcore-cli cannot filter the frames of {video}; update the CLI or disable frame filtering",
+    ),
+    (
+        "job.cli_cannot_trim",
+        "The installed gvcore-cli cannot skip the start of {video}; update the CLI or clear its sync offset",
+    ),
+    (
+        "job.cli_cannot_scale",
+        "The installed gvcore-cli cannot take {video} scaled down to match the other clips; update the CLI or turn off resolution normalization",
+    ),
+    (
+        "job.cli_cannot_count_masks",
+        "The installed gvcore-cli cannot take the frames of {video} extracted by Game View, which its per-frame masks are counted against; update the CLI or use one mask for the whole clip",
+    ),
+    (
+        "job.too_few_frames",
+        "Only {kept} usable frames remain in {video} after filtering (at least {minimum} are needed); lower the blur threshold or widen the brightness range",
+    ),
+    (
+        "job.gpu_out_of_memory",
+        "The GPU ran out of memory. When training started {free} of the {required} the preset needs was free; also using the GPU: {consumers}",
+    ),
+    (
+        "vram.undetected",
+        "Could not detect the GPU's VRAM; the {preset} preset needs {required} and may fail if the GPU has less",
+    ),
+    (
+        "production.leftover",
+        "The production was moved, but some of it is still in {path}: {detail}",
+    ),
+    ("undo.delete_production", "Moved {name} to the trash"),
+    (
+        "undo.delete_intermediates",
+        "Moved the intermediates of {name} to the trash",
+    ),
+    (
+        "undo.trash_duplicates",
+        "Moved {count} duplicate copies of {name} to the trash",
+    ),
+    (
+        "undo.link_duplicates",
+        "Replaced {count} duplicate copies of {name} with links",
+    ),
+    ("undo.remove_external_viewer", "Removed the viewer {name}"),
+    (
+        "undo.archive_intermediates",
+        "Archived the intermediates of {name}",
+    ),
+    (
+        "compat.newer_version",
+        "{file} was written by Game View {version}, newer than this version ({current}); settings it added may be ignored",
+    ),
+    (
+        "compat.newer_schema",
+        "{file} was written by Game View {version}, newer than this version ({current}); update Game View to open it",
+    ),
+    ("title.progress", "{stage} {percent}%"),
+    ("title.failed", "{count} failed"),
+    ("title.volume_disconnected", "Paused: the output drive disconnected"),
+    (
+        "performance.defender_scanning",
+        "Microsoft Defender scans {dir} in real time, which can slow reconstruction several times over; consider excluding it from real-time protection",
+    ),
+    (
+        "performance.defender_exclusions_unknown",
+        "Microsoft Defender real-time protection is on and its exclusions can only be read by an administrator; unless {dirs} are excluded, reconstruction may run several times slower",
+    ),
+    (
+        "performance.third_party_antivirus",
+        "{product} is installed; if it scans files as they are written, excluding {dirs} can speed up reconstruction considerably",
+    ),
+    (
+        "performance.usb_drive",
+        "{dir} is on a USB drive; frames and COLMAP data on an internal drive are processed much faster",
+    ),
+    (
+        "performance.slow_random_writes",
+        "Small synced writes to {dir} take {millis} ms, typical of a spinning or shingled (SMR) drive; an SSD processes frames much faster",
+    ),
+    (
+        "performance.security_unknown",
+        "Could not query Windows security settings; antivirus scanning was not checked",
+    ),
+    (
+        "preset.fast",
+        "Quick draft for checking coverage; soft detail and fewer splats",
+    ),
+    (
+        "preset.balanced",
+        "Good quality in reasonable time for most clips",
+    ),
+    (
+        "preset.high",
+        "Sharper detail and view-dependent colour for final results",
+    ),
+    (
+        "preset.maximum",
+        "Best quality for long or detailed captures; slow and memory hungry",
+    ),
+    ("preset.custom", "{preset}: described from its saved training options"),
+    (
+        "preset.local_average",
+        "On this machine, {preset} averaged {minutes} min per minute of footage over {jobs} jobs",
+    ),
+    (
+        "health.version_mismatch",
+        "The interface ({frontend}) and the backend ({backend}) come from different builds; reinstall Game View",
+    ),
+    (
+        "health.settings_unavailable",
+        "Settings could not be loaded",
+    ),
+    (
+        "health.app_data_missing",
+        "Game View found no writable directory for its data; move its data somewhere writable or set GAMEVIEW_DATA_DIR",
+    ),
+    (
+        "health.app_data_unwritable",
+        "Game View cannot write to its data directory {dir}; move its data somewhere writable",
+    ),
+    (
+        "health.app_data_low_space",
+        "The drive holding {dir} is nearly full; settings and history may fail to save",
+    ),
+    ("health.cli_missing", "gvcore-cli was not found; processing is unavailable"),
+    (
+        "health.settings_recovered",
+        "The settings file could not be read and defaults are in use",
+    ),
+    ("health.data_recovered", "{count} data files could not be read and were set aside"),
+    (
+        "health.previous_session_crashed",
+        "Game View did not shut down cleanly last time",
+    ),
+    (
+        "health.safe_mode",
+        "Game View started in safe mode after failing to start; some features are off until turned back on",
+    ),
+    (
+        "health.not_installed",
+        "Game View is running from {location} without being installed; settings are not kept after quitting and the job history is off",
+    ),
+    ("health.check_timed_out", "The {check} check did not finish in time"),
+    ("notify.job_completed", "Processing finished"),
+    ("notify.job_failed", "Processing failed"),
+    (
+        "notify.batch_finished",
+        "Batch finished: {completed} clips completed, {failed} failed, {cancelled} cancelled",
+    ),
+    ("platform.unknown_os", "Game View does not support {os}"),
+    (
+        "platform.os_too_old",
+        "Game View needs {required} or later; this system is version {version}",
+    ),
+    (
+        "platform.unsupported_arch",
+        "Game View does not run on {arch} processors here, only on {archs}",
+    ),
+    (
+        "platform.no_gpu_api",
+        "No {apis} driver was found, which training needs; install or update the graphics driver",
+    ),
+    ("input.empty", "{path} is empty"),
+    (
+        "input.locked",
+        "{path} is still open in another program, which may be copying it",
+    ),
+    (
+        "input.locked_by",
+        "{path} is still open in {program}, which may be copying it",
+    ),
+    (
+        "input.growing",
+        "{path} is still growing; wait until it has finished copying",
+    ),
+    ("batch.create_dir_failed", "Cannot create {dir}"),
+    (
+        "batch.none_completed",
+        "No clip in the batch completed ({failed} failed, {cancelled} cancelled)",
+    ),
+];
+
+/// A translatable message: a catalog key and the values for its placeholders
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Message {
+    pub key: String,
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+}
+
+impl Message {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            params: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.params.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn into_failure(self) -> Failure {
+        self.into()
+    }
+}
+
+/// The English text; unknown keys render as the key itself
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(template) = english(&self.key) else {
+            return write!(f, "{}", self.key);
+        };
+        let mut text = template.to_string();
+        for (name, value) in &self.params {
+            text = text.replace(&format!("{{{}}}", name), value);
+        }
+        write!(f, "{}", text)
+    }
+}
+
+/// Why an operation failed: a message, plus free-form detail such as CLI stderr
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failure {
+    #[serde(flatten)]
+    pub message: Message,
+    pub raw: Option<String>,
+}
+
+impl Failure {
+    pub fn raw(mut self, raw: impl Into<String>) -> Self {
+        self.raw = Some(raw.into());
+        self
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.raw {
+            Some(raw) => write!(f, "{}: {}", self.message, raw),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl From<Message> for Failure {
+    fn from(message: Message) -> Self {
+        Self { message, raw: None }
+    }
+}
+
+/// Text assembled elsewhere, kept as the raw detail of a generic failure
+impl From<String> for Failure {
+    fn from(raw: String) -> Self {
+        Message::new("job.failed").into_failure().raw(raw)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageCatalog {
+    /// The locale of the messages, which is always "en" for the built-in catalog
+    pub locale: String,
+    pub messages: BTreeMap<String, String>,
+}
+
+/// The built-in catalog. Only English ships with the backend, so other locales
+/// get it too, for the frontend to fall back to.
+#[tauri::command]
+pub async fn get_message_catalog(locale: String) -> Result<MessageCatalog, AppError> {
+    let _ = locale;
+    Ok(catalog())
+}
+
+fn catalog() -> MessageCatalog {
+    MessageCatalog {
+        locale: "en".to_string(),
+        messages: CATALOG
+            .iter()
+            .map(|(key, text)| (key.to_string(), text.to_string()))
+            .collect(),
+    }
+}
+
+fn english(key: &str) -> Option<&'static str> {
+    CATALOG
+        .iter()
+        .find(|(catalog_key, _)| *catalog_key == key)
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_parameters_into_english() {
+        let message = Message::new("job.cli_exit_status").with("status", 3);
+        assert_eq!(message.to_string(), "CLI exited with status: 3");
+        assert_eq!(Message::new("no.such.key").to_string(), "no.such.key");
+    }
+
+    #[test]
+    fn raw_detail_stays_out_of_the_message() {
+        let failure = Message::new("job.cli_exit_status")
+            .with("status", 1)
+            .into_failure()
+            .raw("CUDA out of memory");
+        let json = serde_json::to_value(&failure).unwrap();
+        assert_eq!(json["key"], "job.cli_exit_status");
+        assert_eq!(json["params"]["status"], "1");
+        assert_eq!(json["raw"], "CUDA out of memory");
+        assert_eq!(
+            failure.to_string(),
+            "CLI exited with status: 1: CUDA out of memory"
+        );
+    }
+
+    #[test]
+    fn catalog_has_unique_keys_whose_placeholders_are_words() {
+        let catalog = catalog();
+        assert_eq!(catalog.messages.len(), CATALOG.len());
+        for text in catalog.messages.values() {
+            for placeholder in text.split('{').skip(1) {
+                let name = placeholder.split('}').next().unwrap();
+                assert!(name.chars().all(|c| c.is_ascii_lowercase() || c == '_'));
+            }
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Artifact Naming
+//!
+//! Renders output filename templates such as `{production}_{date}_{preset}_v{version}`
+//! and renames finished artifacts to match. Templates name the file stem; the
+//! artifact keeps the extension the CLI gave it.
+
+use crate::error::AppError;
+use std::path::{Path, PathBuf};
+
+/// Tokens a template may use
+pub const TOKENS: &[&str] = &["production", "preset", "date", "time", "version"];
+
+/// Characters that are illegal in a file name on at least one supported platform
+const ILLEGAL_CHARACTERS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Names Windows reserves for devices, with or without an extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Values substituted into a template
+pub struct NameContext<'a> {
+    /// Name of the production directory
+    pub production: &'a str,
+    pub preset: &'a str,
+    /// Seconds since the Unix epoch; {date} and {time} are rendered in UTC
+    pub timestamp: u64,
+}
+
+enum Part<'a> {
+    Literal(&'a str),
+    Token(&'a str),
+}
+
+/// Check a template without rendering it
+pub fn validate_template(template: &str) -> Result<(), AppError> {
+    let parts = parse(template)?;
+    let literal: String = parts
+        .iter()
+        .filter_map(|part| match part {
+            Part::Literal(text) => Some(*text),
+            Part::Token(_) => None,
+        })
+        .collect();
+    if let Some(c) = literal
+        .chars()
+        .find(|c| ILLEGAL_CHARACTERS.contains(c) || c.is_control())
+    {
+        return Err(AppError::InvalidInput(format!(
+            "Output name template contains {:?}, which is not allowed in file names",
+            c
+        )));
+    }
+    if template.ends_with(['.', ' ']) {
+        return Err(AppError::InvalidInput(
+            "Output name template may not end with a dot or a space".to_string(),
+        ));
+    }
+    let stem = strip_ply(template);
+    if RESERVED_NAMES.iter().any(|r| stem.eq_ignore_ascii_case(r)) {
+        return Err(AppError::InvalidInput(format!(
+            "Output name template renders to the reserved name {}",
+            stem
+        )));
+    }
+    Ok(())
+}
+
+/// Render a template into a file name in `dir` that no existing file uses.
+/// {version} counts up from 1 past existing files; a template without it gets a
+/// -2, -3, ... suffix on collision instead.
+pub fn render(
+    template: &str,
+    context: &NameContext,
+    dir: &Path,
+    extension: &str,
+) -> Result<String, AppError> {
+    let parts = parse(strip_ply(template))?;
+    // Values can come from directory and preset names, which may hold anything
+    let production = sanitize(context.production);
+    let preset = sanitize(context.preset);
+    let (date, time) = utc_date_time(context.timestamp);
+
+    let name_for = |version: u32| -> String {
+        let stem: String = parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.to_string(),
+                Part::Token("production") => production.clone(),
+                Part::Token("preset") => preset.clone(),
+                Part::Token("date") => date.clone(),
+                Part::Token("time") => time.clone(),
+                Part::Token(_) => version.to_string(),
+            })
+            .collect();
+        format!("{}.{}", stem, extension)
+    };
+
+    let versioned = parts.iter().any(|p| matches!(p, Part::Token("version")));
+    if versioned {
+        let name = (1..)
+            .map(name_for)
+            .find(|name| !dir.join(name).exists())
+            .unwrap();
+        return Ok(name);
+    }
+
+    let name = name_for(1);
+    if !dir.join(&name).exists() {
+        return Ok(name);
+    }
+    let stem = name
+        .trim_end_matches(&format!(".{}", extension))
+        .to_string();
+    Ok((2..)
+        .map(|n| format!("{}-{}.{}", stem, n, extension))
+        .find(|name| !dir.join(name).exists())
+        .unwrap())
+}
+
+/// Rename an artifact according to a template, returning its new path
+pub fn rename_artifact(
+    artifact: &Path,
+    template: &str,
+    context: &NameContext,
+) -> Result<PathBuf, AppError> {
+    let dir = artifact
+        .parent()
+        .ok_or_else(|| AppError::InvalidInput(artifact.display().to_string()))?;
+    let extension = artifact
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| "ply".to_string());
+    if !artifact.is_file() {
+        return Err(AppError::NotFound(artifact.display().to_string()));
+    }
+
+    let name = render(template, context, dir, &extension)?;
+    let renamed = dir.join(name);
+    std::fs::rename(artifact, &renamed)?;
+    Ok(renamed)
+}
+
+fn parse(template: &str) -> Result<Vec<Part<'_>>, AppError> {
+    if template.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "Output name template is empty".to_string(),
+        ));
+    }
+
+    let mut parts = vec![];
+    let mut rest = template;
+    while !rest.is_empty() {
+        let offset = template.len() - rest.len();
+        match rest.find(['{', '}']) {
+            Some(i) if rest[i..].starts_with('}') => {
+                return Err(AppError::InvalidInput(format!(
+                    "Unmatched '}}' at position {} of the output name template",
+                    offset + i
+                )));
+            }
+            Some(i) => {
+                let Some(close) = rest[i..].find('}') else {
+                    return Err(AppError::InvalidInput(format!(
+                        "Unclosed '{{' at position {} of the output name template",
+                        offset + i
+                    )));
+                };
+                let token = &rest[i + 1..i + close];
+                if !TOKENS.contains(&token) {
+                    return Err(AppError::InvalidInput(format!(
+                        "Unknown token {{{}}} at position {} of the output name template; use {}",
+                        token,
+                        offset + i,
+                        TOKENS
+                            .iter()
+                            .map(|t| format!("{{{}}}", t))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )));
+                }
+                if i > 0 {
+                    parts.push(Part::Literal(&rest[..i]));
+                }
+                parts.push(Part::Token(token));
+                rest = &rest[i + close + 1..];
+            }
+            None => {
+                parts.push(Part::Literal(rest));
+                rest = "";
+            }
+        }
+    }
+    Ok(parts)
+}
+
+// A template that spells out the extension means the same as one that leaves it off
+fn strip_ply(template: &str) -> &str {
+    let split = template.len().saturating_sub(4);
+    match template.get(split..) {
+        Some(extension) if split > 0 && extension.eq_ignore_ascii_case(".ply") => {
+            &template[..split]
+        }
+        _ => template,
+    }
+}
+
+pub fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if ILLEGAL_CHARACTERS.contains(&c) || c.is_control() {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// YYYY-MM-DDTHH:MM:SSZ, for spreadsheets and other tools that parse ISO 8601
+pub fn iso8601(timestamp: u64) -> String {
+    let (date, time) = utc_date_time(timestamp);
+    format!("{}T{}:{}:{}Z", date, &time[..2], &time[2..4], &time[4..])
+}
+
+/// YYYY-MM-DD and HHMMSS in UTC
+fn utc_date_time(timestamp: u64) -> (String, String) {
+    let days = (timestamp / 86_400) as i64;
+    let seconds = timestamp % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!(
+            "{:02}{:02}{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    // 2024-03-09 14:05:09 UTC
+    const TIMESTAMP: u64 = 1_709_993_109;
+
+    fn context() -> NameContext<'static> {
+        NameContext {
+            production: "Harbour",
+            preset: "balanced",
+            timestamp: TIMESTAMP,
+        }
+    }
+
+    #[test]
+    fn renders_every_token() {
+        let paths = TempPaths::new();
+        let name = render(
+            "{production}_{date}_{time}_{preset}_v{version}",
+            &context(),
+            paths.root(),
+            "ply",
+        )
+        .unwrap();
+        assert_eq!(name, "Harbour_2024-03-09_140509_balanced_v1.ply");
+    }
+
+    #[test]
+    fn version_and_suffix_avoid_existing_files() {
+        let paths = TempPaths::new();
+        std::fs::write(paths.root().join("Harbour_v1.ply"), b"").unwrap();
+        std::fs::write(paths.root().join("Harbour_v2.ply"), b"").unwrap();
+        let name = render("{production}_v{version}", &context(), paths.root(), "ply").unwrap();
+        assert_eq!(name, "Harbour_v3.ply");
+
+        std::fs::write(paths.root().join("Harbour.ply"), b"").unwrap();
+        let name = render("{production}.ply", &context(), paths.root(), "ply").unwrap();
+        assert_eq!(name, "Harbour-2.ply");
+    }
+
+    #[test]
+    fn rejects_bad_templates_precisely() {
+        let message = |template: &str| validate_template(template).unwrap_err().to_string();
+
+        assert!(message("{production}_{take}").contains("Unknown token {take} at position 13"));
+        assert!(message("{production").contains("Unclosed '{' at position 0"));
+        assert!(message("v}{version}").contains("Unmatched '}' at position 1"));
+        assert!(message("{production}:{date}").contains("':'"));
+        assert!(message("scenes/{production}").contains("'/'"));
+        assert!(message("{production}.").contains("dot"));
+        assert!(message("con.ply").contains("reserved"));
+        assert!(message(" ").contains("empty"));
+        assert!(validate_template("{production}_{date}_{preset}_v{version}.ply").is_ok());
+    }
+
+    #[test]
+    fn rename_keeps_extension_and_sanitizes_values() {
+        let paths = TempPaths::new();
+        let artifact = paths.root().join("output.ply");
+        std::fs::write(&artifact, b"ply").unwrap();
+        let context = NameContext {
+            production: "Harbour: day 2",
+            ..context()
+        };
+
+        let renamed = rename_artifact(&artifact, "{production}_{preset}", &context).unwrap();
+        assert_eq!(renamed, paths.root().join("Harbour_ day 2_balanced.ply"));
+        assert!(renamed.is_file());
+        assert!(!artifact.exists());
+    }
+
+    #[test]
+    fn utc_dates_cross_leap_days() {
+        assert_eq!(utc_date_time(0).0, "1970-01-01");
+        assert_eq!(utc_date_time(951_782_400).0, "2000-02-29");
+        assert_eq!(utc_date_time(TIMESTAMP).1, "140509");
+    }
+}
@@ -0,0 +1,440 @@
+//! Job Notifications
+//!
+//! A finished or failed job is announced with an OS notification, unless the
+//! user asked not to be disturbed: a notification popping up over a
+//! presentation is worse than a late one. Before each is shown, the OS is
+//! asked whether do-not-disturb is on, as far as it says: Focus Assist through
+//! its WNF state and presentation or full-screen mode through
+//! SHQueryUserNotificationState on Windows, the Focus assertions on macOS, and
+//! the notification server's Inhibited property or GNOME's banner setting on
+//! Linux. Where none of that can be read, notifications are shown.
+//!
+//! While do-not-disturb is on, notifications are held rather than dropped.
+//! They are shown once it lifts, which is checked every DND_POLL, or handed to
+//! the frontend in a "notifications-flushed" event when a window gains focus,
+//! whichever comes first. get_pending_notifications lists those still held for
+//! an in-app inbox, and each is dropped once older than the settings allow.
+
+use crate::commands::BatchSummary;
+use crate::error::AppError;
+use crate::job_log::unix_timestamp;
+use crate::messages::Message;
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tauri_plugin_notification::NotificationExt;
+
+pub const DEFAULT_EXPIRY_HOURS: u32 = 24;
+
+/// How often held notifications check whether do-not-disturb has lifted
+const DND_POLL: Duration = Duration::from_secs(30);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+// Notifications held while do-not-disturb is on, oldest first
+static PENDING: Mutex<Vec<PendingNotification>> = Mutex::new(vec![]);
+
+// Whether a task is waiting for do-not-disturb to lift
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NotificationSettings {
+    /// Announce finished and failed jobs with OS notifications
+    pub enabled: bool,
+    /// Hold notifications while do-not-disturb is on instead of showing them
+    pub respect_do_not_disturb: bool,
+    /// Held notifications older than this are dropped
+    pub expiry_hours: u32,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            enabled: true,
+            respect_do_not_disturb: true,
+            expiry_hours: DEFAULT_EXPIRY_HOURS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Completed,
+    Failed,
+}
+
+/// A notification held while do-not-disturb was on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingNotification {
+    pub id: String,
+    pub kind: NotificationKind,
+    pub message: Message,
+    pub job_id: Option<String>,
+    /// Seconds since the Unix epoch
+    pub held_at: u64,
+    /// When it is dropped unless shown before
+    pub expires_at: u64,
+}
+
+/// Notifications held while do-not-disturb was on and not yet shown
+#[tauri::command]
+pub async fn get_pending_notifications() -> Result<Vec<PendingNotification>, AppError> {
+    let mut pending = PENDING.lock().unwrap();
+    prune(&mut pending, unix_timestamp());
+    Ok(pending.clone())
+}
+
+/// Announce the end of a process_videos request; nothing is said about one
+/// the user cancelled
+pub fn job_finished(
+    app: &AppHandle,
+    job_id: Option<&str>,
+    failed: &[String],
+    batch: Option<&BatchSummary>,
+    cancelled: bool,
+) {
+    let settings = app.settings().notifications;
+    if !settings.enabled {
+        return;
+    }
+    let Some((kind, message)) = describe(failed, batch, cancelled) else {
+        return;
+    };
+    let now = unix_timestamp();
+    let notification = PendingNotification {
+        id: format!("notification-{}", NEXT_ID.fetch_add(1, Ordering::SeqCst)),
+        kind,
+        message,
+        job_id: job_id.map(String::from),
+        held_at: now,
+        expires_at: now + settings.expiry_hours as u64 * 3600,
+    };
+    let app = app.clone();
+    // Asking the OS may run a program
+    tauri::async_runtime::spawn_blocking(move || {
+        if settings.respect_do_not_disturb && do_not_disturb() == Some(true) {
+            PENDING.lock().unwrap().push(notification);
+            watch(&app);
+        } else {
+            show(&app, &notification);
+        }
+    });
+}
+
+/// A window gained focus: hand the held notifications to the frontend, as
+/// the user is there to see them
+pub fn window_focused(app: &AppHandle) {
+    let held = take_all();
+    if !held.is_empty() {
+        app.emit("notifications-flushed", &held).ok();
+    }
+}
+
+fn describe(
+    failed: &[String],
+    batch: Option<&BatchSummary>,
+    cancelled: bool,
+) -> Option<(NotificationKind, Message)> {
+    match batch {
+        Some(summary) => {
+            if cancelled && summary.failed == 0 {
+                return None;
+            }
+            let kind = if summary.completed == 0 {
+                NotificationKind::Failed
+            } else {
+                NotificationKind::Completed
+            };
+            let message = Message::new("notify.batch_finished")
+                .with("completed", summary.completed)
+                .with("failed", summary.failed)
+                .with("cancelled", summary.cancelled);
+            Some((kind, message))
+        }
+        None if !failed.is_empty() => {
+            Some((NotificationKind::Failed, Message::new("notify.job_failed")))
+        }
+        None if cancelled => None,
+        None => Some((
+            NotificationKind::Completed,
+            Message::new("notify.job_completed"),
+        )),
+    }
+}
+
+fn show(app: &AppHandle, notification: &PendingNotification) {
+    let shown = app
+        .notification()
+        .builder()
+        .title("Game View")
+        .body(notification.message.to_string())
+        .show();
+    if let Err(e) = shown {
+        eprintln!("Failed to show a notification: {}", e);
+    }
+}
+
+fn take_all() -> Vec<PendingNotification> {
+    let mut pending = PENDING.lock().unwrap();
+    prune(&mut pending, unix_timestamp());
+    std::mem::take(&mut *pending)
+}
+
+fn prune(pending: &mut Vec<PendingNotification>, now: u64) {
+    pending.retain(|n| n.expires_at > now);
+}
+
+/// Show the held notifications once do-not-disturb lifts, unless a window
+/// gaining focus took them first
+fn watch(app: &AppHandle) {
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(DND_POLL).await;
+            let quiet = tauri::async_runtime::spawn_blocking(do_not_disturb)
+                .await
+                .ok()
+                .flatten();
+            let done = {
+                let mut pending = PENDING.lock().unwrap();
+                prune(&mut pending, unix_timestamp());
+                pending.is_empty()
+            };
+            if done {
+                break;
+            }
+            if quiet != Some(true) {
+                for notification in take_all() {
+                    show(&app, &notification);
+                }
+                break;
+            }
+        }
+        WATCHING.store(false, Ordering::SeqCst);
+    });
+}
+
+/// Whether the user asked not to be disturbed; None when the OS did not say
+#[cfg(windows)]
+fn do_not_disturb() -> Option<bool> {
+    use windows_sys::Win32::UI::Shell::{
+        SHQueryUserNotificationState, QUNS_ACCEPTS_NOTIFICATIONS, QUNS_APP,
+    };
+
+    let mut state = 0;
+    // SAFETY: state is a valid place for the state to be written to
+    let queried = unsafe { SHQueryUserNotificationState(&mut state) } == 0;
+    let busy = queried.then_some(!matches!(state, QUNS_ACCEPTS_NOTIFICATIONS | QUNS_APP));
+    match (focus_assist(), busy) {
+        (Some(true), _) | (_, Some(true)) => Some(true),
+        (None, None) => None,
+        _ => Some(false),
+    }
+}
+
+/// Whether Focus Assist is on, from the WNF state the shell keeps it in; not
+/// documented, so read only as a hint
+#[cfg(windows)]
+fn focus_assist() -> Option<bool> {
+    use std::ffi::c_void;
+
+    // WNF_SHEL_QUIETHOURS_ACTIVE_PROFILE_CHANGED
+    const STATE_NAME: u64 = 0x0d83_063e_a3bf_1c75;
+
+    #[link(name = "ntdll")]
+    extern "system" {
+        fn NtQueryWnfStateData(
+            state_name: *const u64,
+            type_id: *const c_void,
+            explicit_scope: *const c_void,
+            change_stamp: *mut u32,
+            buffer: *mut c_void,
+            buffer_size: *mut u32,
+        ) -> i32;
+    }
+
+    let mut stamp = 0u32;
+    let mut profile = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    // SAFETY: every pointer is to a local of the size it is described as
+    let status = unsafe {
+        NtQueryWnfStateData(
+            &STATE_NAME,
+            std::ptr::null(),
+            std::ptr::null(),
+            &mut stamp,
+            &mut profile as *mut u32 as *mut c_void,
+            &mut size,
+        )
+    };
+    // 0 is off; 1 lets priority notifications through and 2 only alarms
+    (status == 0 && size as usize == std::mem::size_of::<u32>()).then_some(profile != 0)
+}
+
+#[cfg(target_os = "macos")]
+fn do_not_disturb() -> Option<bool> {
+    let home = std::env::var_os("HOME")?;
+    let assertions = std::path::Path::new(&home).join("Library/DoNotDisturb/DB/Assertions.json");
+    if let Ok(content) = std::fs::read_to_string(assertions) {
+        return focus_asserted(&content);
+    }
+    // Before Focus, in macOS 11 and older
+    let output = std::process::Command::new("defaults")
+        .args([
+            "-currentHost",
+            "read",
+            "com.apple.notificationcenterui",
+            "doNotDisturb",
+        ])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim() == "1")
+}
+
+/// Whether the Focus assertions macOS keeps hold an active focus
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn focus_asserted(content: &str) -> Option<bool> {
+    let assertions: serde_json::Value = serde_json::from_str(content).ok()?;
+    let stores = assertions["data"].as_array()?;
+    Some(stores.iter().any(|store| {
+        store["storeAssertionRecords"]
+            .as_array()
+            .is_some_and(|records| !records.is_empty())
+    }))
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn do_not_disturb() -> Option<bool> {
+    let query = |program: &str, args: &[&str]| -> Option<String> {
+        let output = std::process::Command::new(program)
+            .args(args)
+            .output()
+            .ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let inhibited = query(
+        "gdbus",
+        &[
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.Notifications",
+            "--object-path",
+            "/org/freedesktop/Notifications",
+            "--method",
+            "org.freedesktop.DBus.Properties.Get",
+            "org.freedesktop.Notifications",
+            "Inhibited",
+        ],
+    )
+    .and_then(|reply| gdbus_bool(&reply));
+    if inhibited.is_some() {
+        return inhibited;
+    }
+    let banners = query(
+        "gsettings",
+        &["get", "org.gnome.desktop.notifications", "show-banners"],
+    )?;
+    match banners.trim() {
+        "true" => Some(false),
+        "false" => Some(true),
+        _ => None,
+    }
+}
+
+/// The boolean in a gdbus reply such as "(<true>,)"
+#[cfg_attr(any(target_os = "macos", windows), allow(dead_code))]
+fn gdbus_bool(reply: &str) -> Option<bool> {
+    let value = reply
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim_end_matches(',')
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(completed: usize, failed: usize, cancelled: usize) -> BatchSummary {
+        BatchSummary {
+            batch_id: "batch-1".to_string(),
+            output_dir: "/renders".to_string(),
+            completed,
+            failed,
+            cancelled,
+            members: vec![],
+        }
+    }
+
+    #[test]
+    fn jobs_are_described_unless_the_user_cancelled_them() {
+        let (kind, message) = describe(&[], None, false).unwrap();
+        assert_eq!(kind, NotificationKind::Completed);
+        assert_eq!(message.key, "notify.job_completed");
+        let failed = ["entry-1".to_string()];
+        assert_eq!(
+            describe(&failed, None, false).unwrap().0,
+            NotificationKind::Failed
+        );
+        assert_eq!(describe(&[], None, true), None);
+
+        let (kind, message) = describe(&failed, Some(&summary(2, 1, 0)), false).unwrap();
+        assert_eq!(kind, NotificationKind::Completed);
+        assert_eq!(message.params["failed"], "1");
+        assert_eq!(
+            describe(&failed, Some(&summary(0, 3, 0)), false).unwrap().0,
+            NotificationKind::Failed
+        );
+        assert_eq!(describe(&[], Some(&summary(1, 0, 2)), true), None);
+    }
+
+    #[test]
+    fn held_notifications_expire() {
+        let held = |id: &str, expires_at: u64| PendingNotification {
+            id: id.to_string(),
+            kind: NotificationKind::Completed,
+            message: Message::new("notify.job_completed"),
+            job_id: None,
+            held_at: 100,
+            expires_at,
+        };
+        let mut pending = vec![held("old", 200), held("new", 500)];
+        prune(&mut pending, 300);
+        let ids: Vec<&str> = pending.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["new"]);
+    }
+
+    #[test]
+    fn do_not_disturb_is_read_from_what_each_os_reports() {
+        assert_eq!(gdbus_bool("(<true>,)\n"), Some(true));
+        assert_eq!(gdbus_bool("(<false>,)"), Some(false));
+        assert_eq!(gdbus_bool("Error: no such name"), None);
+
+        let focused = r#"{"data":[{"storeAssertionRecords":[{"assertionDetails":{"assertionDetailsModeIdentifier":"com.apple.donotdisturb.mode.default"}}]}]}"#;
+        assert_eq!(focus_asserted(focused), Some(true));
+        assert_eq!(focus_asserted(r#"{"data":[{}]}"#), Some(false));
+        assert_eq!(focus_asserted("not json"), None);
+    }
+}
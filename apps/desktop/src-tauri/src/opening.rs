@@ -0,0 +1,477 @@
+//! Opening Productions
+//!
+//! One command for everything the UI opens from the recents, the library or a
+//! file dropped on the window. The target is a recent production's id or a
+//! path, and what it is decides what happens: a production directory or a
+//! splat PLY goes to the viewer, checked against its recorded checksum when
+//! the settings ask for it; a .gvproj project is loaded; a directory with
+//! nothing to open is revealed in the file manager. The recent production the
+//! target belongs to has its last_opened bumped.
+//!
+//! A target that is gone comes back as target_missing with a suggestion when
+//! one is found: where a full reconciliation found the production moved to,
+//! or failing that, the recent production whose name is closest.
+
+use crate::artifacts::Artifact;
+use crate::error::AppError;
+use crate::fsutil;
+use crate::integrity::{self, VerifyResult};
+use crate::job_log;
+use crate::naming;
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::recents;
+use crate::reconcile;
+use crate::settings::{Persist, RecentProduction, SettingsStore};
+use crate::sidecar;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use tauri::{AppHandle, State};
+
+/// What open_production did, for the UI to route on
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Opened {
+    /// Show the artifact in the viewer; a production without one opens empty
+    Viewer {
+        /// The primary artifact of the production
+        artifact_path: Option<String>,
+        /// The production's other artifacts, such as a web-optimized SPZ
+        other_artifacts: Vec<Artifact>,
+        production: Option<RecentProduction>,
+        /// Present when verify_artifacts_on_open is set and there is an artifact
+        verification: Option<VerifyResult>,
+        warning: Option<String>,
+    },
+    /// Load the project, as read from its file
+    Project {
+        path: String,
+        project: Value,
+        production: Option<RecentProduction>,
+    },
+    /// A directory with nothing to open, shown in the file manager
+    Revealed { path: String },
+}
+
+/// What the target turned out to be
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Production(PathBuf),
+    Artifact(PathBuf),
+    Project(PathBuf),
+    Folder(PathBuf),
+}
+
+/// Open a recent production by id, or a production directory, splat or
+/// project by path
+#[tauri::command]
+pub async fn open_production(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    id_or_path: String,
+) -> Result<Opened, AppError> {
+    let cancel = integrity::start_verification();
+    let opened = open(
+        &app,
+        &app,
+        &policy,
+        &id_or_path,
+        cancel,
+        &mut |path, hashed, total| integrity::emit_progress(&app, path, hashed, total),
+    )
+    .await?;
+    if let Opened::Revealed { path } = &opened {
+        reveal(Path::new(path))?;
+    }
+    Ok(opened)
+}
+
+pub async fn open(
+    paths: &impl PathProvider,
+    store: &impl SettingsStore,
+    policy: &PathPolicy,
+    id_or_path: &str,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(&str, u64, u64) + Send),
+) -> Result<Opened, AppError> {
+    let app_settings = store.settings();
+    let recent = app_settings
+        .recent_productions
+        .iter()
+        .find(|r| r.id == id_or_path);
+    let path = match recent {
+        Some(recent) => PathBuf::from(&recent.path),
+        None => {
+            policy.check_target(id_or_path)?;
+            PathBuf::from(id_or_path)
+        }
+    };
+    if !path.exists() {
+        return Err(AppError::TargetMissing {
+            target: id_or_path.to_string(),
+            suggestion: suggestion(paths, store, id_or_path).await,
+        });
+    }
+    let is_recent = |dir: &Path| {
+        app_settings
+            .recent_productions
+            .iter()
+            .any(|r| Path::new(&r.path) == dir)
+    };
+    let target = classify(&path, &is_recent)?;
+    let production = bump(store, &target)?;
+
+    Ok(match target {
+        Target::Production(dir) => {
+            let artifact = sidecar::find_artifact(&dir).ok();
+            let others = artifact
+                .as_ref()
+                .map(|a| sidecar::other_artifacts(&dir, a))
+                .unwrap_or_default();
+            viewer(
+                artifact,
+                others,
+                production,
+                app_settings.verify_artifacts_on_open,
+                cancel,
+                progress,
+            )
+            .await?
+        }
+        Target::Artifact(artifact) => {
+            let artifact = artifact.canonicalize().unwrap_or(artifact);
+            // The others are listed in the sidecar of the production it lies in
+            let others = artifact
+                .parent()
+                .map(|dir| sidecar::other_artifacts(dir, &artifact))
+                .unwrap_or_default();
+            viewer(
+                Some(artifact),
+                others,
+                production,
+                app_settings.verify_artifacts_on_open,
+                cancel,
+                progress,
+            )
+            .await?
+        }
+        Target::Project(path) => Opened::Project {
+            project: read_project(&path)?,
+            path: path.to_string_lossy().to_string(),
+            production,
+        },
+        Target::Folder(dir) => Opened::Revealed {
+            path: dir.to_string_lossy().to_string(),
+        },
+    })
+}
+
+/// A directory is a production when it has a sidecar, an artifact or a
+/// recent production
+fn classify(path: &Path, is_recent: &dyn Fn(&Path) -> bool) -> Result<Target, AppError> {
+    if path.is_dir() {
+        let production = is_recent(path)
+            || sidecar::read(path).ok().flatten().is_some()
+            || sidecar::find_artifact(path).is_ok();
+        return Ok(if production {
+            Target::Production(path.to_path_buf())
+        } else {
+            Target::Folder(path.to_path_buf())
+        });
+    }
+    let extension = |wanted: &str| {
+        path.extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(wanted))
+    };
+    if extension("ply") {
+        Ok(Target::Artifact(path.to_path_buf()))
+    } else if extension("gvproj") {
+        Ok(Target::Project(path.to_path_buf()))
+    } else {
+        Err(AppError::InvalidInput(format!(
+            "{} is neither a production, a splat PLY nor a project",
+            path.display()
+        )))
+    }
+}
+
+async fn viewer(
+    artifact: Option<PathBuf>,
+    other_artifacts: Vec<Artifact>,
+    production: Option<RecentProduction>,
+    verify: bool,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(&str, u64, u64) + Send),
+) -> Result<Opened, AppError> {
+    let (verification, warning) = match &artifact {
+        Some(artifact) if verify => recents::verify_on_open(artifact, cancel, progress).await?,
+        _ => (None, None),
+    };
+    Ok(Opened::Viewer {
+        artifact_path: artifact.map(|a| a.to_string_lossy().to_string()),
+        other_artifacts,
+        production,
+        verification,
+        warning,
+    })
+}
+
+/// Bump last_opened of the recent production the target is or lies in
+fn bump(store: &impl SettingsStore, target: &Target) -> Result<Option<RecentProduction>, AppError> {
+    let path = match target {
+        Target::Production(dir) | Target::Folder(dir) => dir.as_path(),
+        Target::Artifact(file) | Target::Project(file) => file.parent().unwrap_or(file),
+    };
+    let Some(id) = store
+        .settings()
+        .recent_productions
+        .into_iter()
+        .find(|r| same_dir(Path::new(&r.path), path))
+        .map(|r| r.id)
+    else {
+        return Ok(None);
+    };
+    let now = naming::iso8601(job_log::unix_timestamp());
+    recents::update_recent(store, &id, Persist::Now, |recent| recent.last_opened = now).map(Some)
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+/// The project file's contents, when it is a Game View project
+fn read_project(path: &Path) -> Result<Value, AppError> {
+    let content = std::fs::read_to_string(path)?;
+    let project: Value = serde_json::from_str(&content)
+        .map_err(|e| AppError::InvalidInput(format!("{}: {}", path.display(), e)))?;
+    if project["type"] != "gameview-project" {
+        return Err(AppError::InvalidInput(format!(
+            "{} is not a Game View project",
+            path.display()
+        )));
+    }
+    Ok(project)
+}
+
+/// Where a missing target may be now: where a full reconciliation moved it,
+/// or the recent production with the closest name
+async fn suggestion(
+    paths: &impl PathProvider,
+    store: &impl SettingsStore,
+    id_or_path: &str,
+) -> Option<String> {
+    let recent_path = |id: &str| {
+        store
+            .settings()
+            .recent_productions
+            .into_iter()
+            .find(|r| r.id == id)
+            .map(|r| r.path)
+    };
+    let before = recent_path(id_or_path);
+    if let Ok(summary) = reconcile::reconcile(paths, store, true).await {
+        // A recent production's path was rebased along with its move
+        if let Some(after) = recent_path(id_or_path).filter(|after| Some(after) != before.as_ref())
+        {
+            return Some(after);
+        }
+        let moved = summary.moved.iter().find_map(|moved| {
+            fsutil::rebase(id_or_path, Path::new(&moved.from), Path::new(&moved.to))
+        });
+        if moved.is_some() {
+            return moved;
+        }
+    }
+    let wanted = match &before {
+        Some(path) => name_of(path),
+        None => name_of(id_or_path),
+    };
+    closest_name(&store.settings().recent_productions, &wanted)
+}
+
+fn name_of(path: &str) -> String {
+    let path = Path::new(path);
+    path.file_stem()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .to_lowercase()
+}
+
+/// The path of the recent production still there whose name or directory is
+/// within a third of `wanted`'s length of edits of it
+fn closest_name(recents: &[RecentProduction], wanted: &str) -> Option<String> {
+    let allowed = (wanted.chars().count() / 3).max(1);
+    recents
+        .iter()
+        .filter(|r| !r.missing)
+        .filter_map(|r| {
+            let distance = edit_distance(&r.name.to_lowercase(), wanted)
+                .min(edit_distance(&name_of(&r.path), wanted));
+            (distance <= allowed).then_some((distance, r))
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, r)| r.path.clone())
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Show a directory in the platform's file manager
+fn reveal(dir: &Path) -> Result<(), AppError> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(program).arg(dir).spawn()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::AppSettings;
+
+    fn recent(id: &str, name: &str, path: &Path) -> RecentProduction {
+        RecentProduction {
+            id: id.to_string(),
+            name: name.to_string(),
+            path: path.to_string_lossy().to_string(),
+            last_opened: String::new(),
+            tags: vec![],
+            notes: String::new(),
+            imported: false,
+            missing: false,
+            pinned: false,
+        }
+    }
+
+    async fn open_in(
+        paths: &TempPaths,
+        store: &MemorySettings,
+        id_or_path: &str,
+    ) -> Result<Opened, AppError> {
+        let policy = PathPolicy::default();
+        policy.allow(paths.root());
+        let cancel = AtomicBool::new(false);
+        open(
+            paths,
+            store,
+            &policy,
+            id_or_path,
+            &cancel,
+            &mut |_, _, _| {},
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn each_kind_of_target_gets_its_action() {
+        let paths = TempPaths::new();
+        let harbour = paths.root().join("harbour");
+        std::fs::create_dir_all(&harbour).unwrap();
+        std::fs::write(harbour.join("output.ply"), b"ply\n").unwrap();
+        let project = paths.root().join("pier.gvproj");
+        std::fs::write(&project, r#"{"type":"gameview-project","version":"1"}"#).unwrap();
+        let notes = paths.root().join("notes");
+        std::fs::create_dir_all(&notes).unwrap();
+        std::fs::write(paths.root().join("notes.txt"), b"").unwrap();
+        let store = MemorySettings::with(AppSettings {
+            recent_productions: vec![recent("harbour", "Harbour", &harbour)],
+            ..Default::default()
+        });
+
+        match open_in(&paths, &store, "harbour").await.unwrap() {
+            Opened::Viewer {
+                artifact_path,
+                production,
+                ..
+            } => {
+                assert!(artifact_path.unwrap().ends_with("output.ply"));
+                assert!(!production.unwrap().last_opened.is_empty());
+            }
+            other => panic!("{:?}", other),
+        }
+        let artifact = harbour.join("output.ply");
+        assert!(matches!(
+            open_in(&paths, &store, &artifact.to_string_lossy())
+                .await
+                .unwrap(),
+            Opened::Viewer {
+                production: Some(_),
+                ..
+            }
+        ));
+        match open_in(&paths, &store, &project.to_string_lossy())
+            .await
+            .unwrap()
+        {
+            Opened::Project {
+                project,
+                production,
+                ..
+            } => {
+                assert_eq!(project["version"], "1");
+                assert!(production.is_none());
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            open_in(&paths, &store, &notes.to_string_lossy())
+                .await
+                .unwrap(),
+            Opened::Revealed { .. }
+        ));
+        let text = paths.root().join("notes.txt");
+        assert!(matches!(
+            open_in(&paths, &store, &text.to_string_lossy()).await,
+            Err(AppError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_targets_suggest_the_closest_recent_production() {
+        let paths = TempPaths::new();
+        let harbour = paths.root().join("harbour-dusk");
+        std::fs::create_dir_all(&harbour).unwrap();
+        let store = MemorySettings::with(AppSettings {
+            recent_productions: vec![recent("harbour", "Harbour dusk", &harbour)],
+            ..Default::default()
+        });
+
+        let missing = paths.root().join("harbour-dsk");
+        match open_in(&paths, &store, &missing.to_string_lossy()).await {
+            Err(AppError::TargetMissing { suggestion, .. }) => {
+                assert_eq!(suggestion, Some(harbour.to_string_lossy().to_string()))
+            }
+            other => panic!("{:?}", other),
+        }
+        let unrelated = paths.root().join("warehouse");
+        assert!(matches!(
+            open_in(&paths, &store, &unrelated.to_string_lossy()).await,
+            Err(AppError::TargetMissing {
+                suggestion: None,
+                ..
+            })
+        ));
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+}
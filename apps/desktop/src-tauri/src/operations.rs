@@ -0,0 +1,251 @@
+//! Backend Operations
+//!
+//! Hashing, downsampling, archiving, library scans, history sweeps and
+//! artifact comparisons run for minutes in the backend itself rather than in the CLI, and each used to
+//! have its own cancel flag and progress event. Now each registers an
+//! OperationHandle for as long as it runs, asks it between blocks of work
+//! whether it was cancelled, and tells it how far it has got. list_operations
+//! and cancel_operation cover all of them, every report is sent as an
+//! "operation-progress" event, and "operation-finished" follows when an
+//! operation ends, however it ended. The commands and events of each kind keep
+//! working alongside.
+
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+// The operations running now, by id
+static RUNNING: Mutex<BTreeMap<String, Running>> = Mutex::new(BTreeMap::new());
+
+struct Running {
+    seq: u64,
+    info: OperationInfo,
+    cancel: Arc<AtomicBool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Hashing,
+    Conversion,
+    Archive,
+    LibraryScan,
+    HistorySweep,
+    Comparison,
+}
+
+/// A running operation as list_operations and "operation-progress" report it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OperationInfo {
+    pub id: String,
+    pub kind: OperationKind,
+    /// What it works on, usually a path
+    pub subject: String,
+    pub done: u64,
+    /// None while the amount of work is not known
+    pub total: Option<u64>,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationFinished {
+    pub id: String,
+    pub kind: OperationKind,
+}
+
+/// A registered operation; dropping it unregisters the operation
+pub struct OperationHandle {
+    id: String,
+    kind: OperationKind,
+    cancel: Arc<AtomicBool>,
+    app: Option<AppHandle>,
+}
+
+impl OperationHandle {
+    /// Register an operation of `kind` on `subject`, reporting it to the frontend
+    pub fn start(app: &AppHandle, kind: OperationKind, subject: &str) -> OperationHandle {
+        register(Some(app.clone()), kind, subject)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The flag cancel_operation sets, for code that checks a flag between blocks
+    pub fn token(&self) -> &AtomicBool {
+        &self.cancel
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// Fail with Cancelled once the operation is cancelled
+    pub fn check(&self) -> Result<(), AppError> {
+        if self.is_cancelled() {
+            return Err(AppError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Record how far the operation has got and send it as "operation-progress"
+    pub fn progress(&self, done: u64, total: Option<u64>) {
+        let info = {
+            let mut running = RUNNING.lock().unwrap();
+            let Some(entry) = running.get_mut(&self.id) else {
+                return;
+            };
+            entry.info.done = done;
+            entry.info.total = total;
+            entry.info.cancelled = self.is_cancelled();
+            entry.info.clone()
+        };
+        if let Some(app) = &self.app {
+            app.emit("operation-progress", &info).ok();
+        }
+    }
+}
+
+impl Drop for OperationHandle {
+    fn drop(&mut self) {
+        RUNNING.lock().unwrap().remove(&self.id);
+        if let Some(app) = &self.app {
+            let finished = OperationFinished {
+                id: self.id.clone(),
+                kind: self.kind,
+            };
+            app.emit("operation-finished", &finished).ok();
+        }
+    }
+}
+
+fn register(app: Option<AppHandle>, kind: OperationKind, subject: &str) -> OperationHandle {
+    let seq = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    let id = format!("op-{}", seq);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let info = OperationInfo {
+        id: id.clone(),
+        kind,
+        subject: subject.to_string(),
+        done: 0,
+        total: None,
+        started_at,
+        cancelled: false,
+    };
+    RUNNING.lock().unwrap().insert(
+        id.clone(),
+        Running {
+            seq,
+            info,
+            cancel: cancel.clone(),
+        },
+    );
+    OperationHandle {
+        id,
+        kind,
+        cancel,
+        app,
+    }
+}
+
+/// The operations running now, oldest first
+pub fn list() -> Vec<OperationInfo> {
+    let running = RUNNING.lock().unwrap();
+    let mut operations: Vec<&Running> = running.values().collect();
+    operations.sort_by_key(|r| r.seq);
+    operations.into_iter().map(|r| r.info.clone()).collect()
+}
+
+/// Ask operation `id` to stop; false when no such operation runs
+pub fn cancel(id: &str) -> bool {
+    let mut running = RUNNING.lock().unwrap();
+    let Some(entry) = running.get_mut(id) else {
+        return false;
+    };
+    entry.cancel.store(true, Ordering::SeqCst);
+    entry.info.cancelled = true;
+    true
+}
+
+/// Ask every running operation of `kind` to stop
+pub fn cancel_kind(kind: OperationKind) {
+    for entry in RUNNING.lock().unwrap().values_mut() {
+        if entry.info.kind == kind {
+            entry.cancel.store(true, Ordering::SeqCst);
+            entry.info.cancelled = true;
+        }
+    }
+}
+
+/// The backend operations running now
+#[tauri::command]
+pub async fn list_operations() -> Result<Vec<OperationInfo>, AppError> {
+    Ok(list())
+}
+
+/// Cancel a running backend operation; it stops at its next block of work
+#[tauri::command]
+pub async fn cancel_operation(id: String) -> Result<(), AppError> {
+    if !cancel(&id) {
+        return Err(AppError::NotFound(format!("operation {}", id)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn a_registered_operation_cancels_promptly() {
+        let handle = register(None, OperationKind::Hashing, "/renders/harbour.ply");
+        let id = handle.id().to_string();
+        handle.progress(10, Some(1000));
+        let listed = list().into_iter().find(|op| op.id == id).unwrap();
+        assert_eq!((listed.done, listed.total), (10, Some(1000)));
+
+        let (stopped_tx, stopped_rx) = std::sync::mpsc::channel();
+        let worker = std::thread::spawn(move || {
+            let mut done = 10;
+            // Stands in for a hash working through 1 MB blocks
+            while handle.check().is_ok() {
+                std::thread::sleep(Duration::from_millis(5));
+                done += 1;
+                handle.progress(done, Some(1000));
+            }
+            stopped_tx.send(Instant::now()).unwrap();
+        });
+
+        std::thread::sleep(Duration::from_millis(20));
+        let requested = Instant::now();
+        assert!(cancel(&id));
+        let stopped = stopped_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(stopped.duration_since(requested) < Duration::from_millis(200));
+        worker.join().unwrap();
+
+        assert!(list().iter().all(|op| op.id != id));
+        assert!(!cancel(&id));
+    }
+
+    #[test]
+    fn cancelling_a_kind_leaves_the_others_running() {
+        let scan = register(None, OperationKind::LibraryScan, "/productions");
+        let archive = register(None, OperationKind::Archive, "/productions/pier");
+        cancel_kind(OperationKind::LibraryScan);
+        assert!(matches!(scan.check(), Err(AppError::Cancelled)));
+        assert!(archive.check().is_ok());
+    }
+}
@@ -0,0 +1,408 @@
+//! Output Location
+//!
+//! A production directory is deleted and cleaned wholesale later, so picking
+//! the folder that holds the source clips, or app_data, as the output puts
+//! them at risk. Output directories are checked against the job's inputs and
+//! app_data when picked and again before a job is queued. Paths are compared
+//! after following symlinks, component by component, ignoring case where the
+//! filesystem does.
+//!
+//! The directory must also be writable, which is probed by creating it when
+//! missing and writing a file into it, so a read-only or root-owned folder is
+//! refused with output_not_writable up front instead of failing the CLI late.
+//! The error names the owner and permissions where the platform gives them
+//! cheaply, and the default output directory as an alternative. Elevation is
+//! never attempted.
+
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::path_policy;
+use crate::platform::PathProvider;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Whether paths differing only in case name the same file on this platform
+const CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Written into an output directory to probe it and removed again
+const PROBE_NAME: &str = ".gameview-write-probe";
+
+/// Why an output directory cannot be written to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotWritable {
+    pub message: Message,
+    /// The error the OS gave
+    pub detail: String,
+    /// The default output directory, when it is another writable one
+    pub fallback: Option<String>,
+}
+
+/// What happens when an output directory overlaps an input or app_data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsafeOutputPolicy {
+    /// Refuse the directory with unsafe_output_location
+    #[default]
+    Reject,
+    /// Accept it, sending an output-location-warning event
+    Warn,
+}
+
+/// Check `output_dir` against `inputs` and app_data. Ok(Some) carries the
+/// warning to show when `policy` lets an overlapping directory through.
+pub fn check(
+    paths: &impl PathProvider,
+    policy: UnsafeOutputPolicy,
+    output_dir: &str,
+    inputs: &[&str],
+) -> Result<Option<Message>, AppError> {
+    let app_data = paths.app_data_dir()?;
+    let Some(conflict) = conflict(Path::new(output_dir), inputs, &app_data, CASE_INSENSITIVE)
+    else {
+        return Ok(None);
+    };
+    match policy {
+        UnsafeOutputPolicy::Reject => Err(AppError::UnsafeOutputLocation(conflict)),
+        UnsafeOutputPolicy::Warn => Ok(Some(conflict)),
+    }
+}
+
+/// Create `output_dir` when missing and write a probe file into it.
+/// `fallback` is the default output directory to suggest instead.
+pub fn check_writable(output_dir: &Path, fallback: Option<&str>) -> Result<(), AppError> {
+    let probed = std::fs::create_dir_all(output_dir).and_then(|()| {
+        let probe = output_dir.join(PROBE_NAME);
+        std::fs::write(&probe, b"")?;
+        std::fs::remove_file(&probe)
+    });
+    let Err(e) = probed else {
+        return Ok(());
+    };
+    let Some(key) = denial(&e) else {
+        return Err(e.into());
+    };
+    let mut message = Message::new(key).with("path", output_dir.display());
+    if let Some((owner, permissions)) = ownership(output_dir) {
+        message = message
+            .with("owner", owner)
+            .with("permissions", permissions);
+    }
+    let fallback = fallback
+        .filter(|dir| !dir.is_empty() && Path::new(dir) != output_dir)
+        .filter(|dir| check_writable(Path::new(dir), None).is_ok())
+        .map(str::to_string);
+    Err(AppError::OutputNotWritable(NotWritable {
+        message,
+        detail: e.to_string(),
+        fallback,
+    }))
+}
+
+/// The message key for an error that means the location refuses writes
+fn denial(e: &std::io::Error) -> Option<&'static str> {
+    #[cfg(unix)]
+    const READ_ONLY: i32 = libc::EROFS;
+    // ERROR_WRITE_PROTECT
+    #[cfg(windows)]
+    const READ_ONLY: i32 = 19;
+    #[cfg(not(any(unix, windows)))]
+    const READ_ONLY: i32 = -1;
+
+    if e.raw_os_error() == Some(READ_ONLY) {
+        Some("error.output_read_only")
+    } else if e.kind() == std::io::ErrorKind::PermissionDenied {
+        // EACCES and EPERM, or ERROR_ACCESS_DENIED on Windows
+        Some("error.output_not_writable")
+    } else {
+        None
+    }
+}
+
+/// The owner and permissions of `path`, or of the nearest directory above it
+/// that exists
+fn ownership(path: &Path) -> Option<(String, String)> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let metadata = std::fs::metadata(existing).ok()?;
+    Some(platform::ownership(&metadata))
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::ffi::CStr;
+    use std::os::unix::fs::MetadataExt;
+
+    /// The owning user's name, or their uid, and the mode as `rwxr-xr-x`
+    pub fn ownership(metadata: &std::fs::Metadata) -> (String, String) {
+        let uid = metadata.uid();
+        let owner = user_name(uid).unwrap_or_else(|| uid.to_string());
+        let mode = metadata.mode();
+        let permissions = (0..9)
+            .map(|bit| {
+                let set = mode & (0o400 >> bit) != 0;
+                match (set, bit % 3) {
+                    (false, _) => '-',
+                    (true, 0) => 'r',
+                    (true, 1) => 'w',
+                    (true, _) => 'x',
+                }
+            })
+            .collect();
+        (owner, permissions)
+    }
+
+    fn user_name(uid: u32) -> Option<String> {
+        let mut buffer = vec![0 as libc::c_char; 1024];
+        // SAFETY: zeroed passwd is a valid out parameter for getpwuid_r
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        // SAFETY: the buffer and the out parameters live for the whole call
+        let status = unsafe {
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut found,
+            )
+        };
+        if status != 0 || found.is_null() {
+            return None;
+        }
+        // SAFETY: getpwuid_r succeeded, so pw_name points into the buffer
+        let name = unsafe { CStr::from_ptr(passwd.pw_name) };
+        Some(name.to_string_lossy().to_string())
+    }
+}
+
+#[cfg(not(unix))]
+mod platform {
+    /// Windows owners need a security descriptor lookup, which is not cheap;
+    /// only the read-only attribute is reported
+    pub fn ownership(metadata: &std::fs::Metadata) -> (String, String) {
+        let permissions = if metadata.permissions().readonly() {
+            "read-only"
+        } else {
+            "writable"
+        };
+        (String::new(), permissions.to_string())
+    }
+}
+
+fn conflict(
+    output_dir: &Path,
+    inputs: &[&str],
+    app_data: &Path,
+    case_insensitive: bool,
+) -> Option<Message> {
+    let output = path_policy::resolve_target(output_dir)?;
+    let within = |path: &Path, base: &Path| is_within(path, base, case_insensitive);
+    let message = |key: &str, other: &Path| {
+        Message::new(key)
+            .with("path", output_dir.display())
+            .with("other", other.display())
+    };
+
+    if let Some(app_data) = path_policy::resolve_target(app_data) {
+        if within(&output, &app_data) {
+            return Some(message("error.output_inside_app_data", &app_data));
+        }
+        if within(&app_data, &output) {
+            return Some(message("error.output_contains_app_data", &app_data));
+        }
+    }
+    for input in inputs {
+        let Some(canonical) = path_policy::resolve_target(Path::new(input)) else {
+            continue;
+        };
+        if within(&canonical, &output) {
+            return Some(message("error.output_contains_input", Path::new(input)));
+        }
+        if within(&output, &canonical) {
+            return Some(message("error.output_inside_input", Path::new(input)));
+        }
+    }
+    None
+}
+
+/// `path` is `base` or below it
+fn is_within(path: &Path, base: &Path, case_insensitive: bool) -> bool {
+    let fold = |path: &Path| -> PathBuf {
+        if case_insensitive {
+            PathBuf::from(path.to_string_lossy().to_lowercase())
+        } else {
+            path.to_path_buf()
+        }
+    };
+    fold(path).starts_with(fold(base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    struct Fixture {
+        paths: TempPaths,
+        clips: PathBuf,
+        video: String,
+    }
+
+    fn fixture() -> Fixture {
+        let paths = TempPaths::new();
+        let clips = paths.root().join("Footage/Clips");
+        std::fs::create_dir_all(&clips).unwrap();
+        let video = clips.join("cam1.mp4");
+        std::fs::write(&video, b"").unwrap();
+        Fixture {
+            video: video.to_string_lossy().to_string(),
+            clips,
+            paths,
+        }
+    }
+
+    fn key(f: &Fixture, output_dir: &Path) -> Option<String> {
+        let app_data = f.paths.app_data_dir().unwrap();
+        conflict(output_dir, &[&f.video], &app_data, false).map(|m| m.key)
+    }
+
+    #[test]
+    fn a_location_that_refuses_writes_is_told_apart_from_other_failures() {
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(denial(&denied), Some("error.output_not_writable"));
+        #[cfg(unix)]
+        {
+            let read_only = std::io::Error::from_raw_os_error(libc::EROFS);
+            assert_eq!(denial(&read_only), Some("error.output_read_only"));
+            let full = std::io::Error::from_raw_os_error(libc::ENOSPC);
+            assert_eq!(denial(&full), None);
+        }
+
+        let f = fixture();
+        let output = f.paths.root().join("Productions/Harbour");
+        check_writable(&output, None).unwrap();
+        assert!(output.is_dir());
+        assert!(!output.join(PROBE_NAME).exists());
+        #[cfg(unix)]
+        {
+            std::fs::set_permissions(
+                &f.clips,
+                std::os::unix::fs::PermissionsExt::from_mode(0o555),
+            )
+            .unwrap();
+            let refused = f.clips.join("Harbour");
+            let (_, permissions) = ownership(&refused).unwrap();
+            assert_eq!(permissions, "r-xr-xr-x");
+            // SAFETY: geteuid has no preconditions
+            let root = unsafe { libc::geteuid() } == 0;
+            // Permissions do not stop root
+            if !root {
+                let fallback = output.to_string_lossy();
+                let Err(AppError::OutputNotWritable(refusal)) =
+                    check_writable(&refused, Some(&fallback))
+                else {
+                    panic!("a read-only directory was accepted");
+                };
+                assert_eq!(refusal.message.key, "error.output_not_writable");
+                assert!(refusal.message.params.contains_key("owner"));
+                assert_eq!(refusal.fallback.as_deref(), Some(fallback.as_ref()));
+            }
+            std::fs::set_permissions(
+                &f.clips,
+                std::os::unix::fs::PermissionsExt::from_mode(0o755),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn output_may_not_hold_or_sit_in_inputs() {
+        let f = fixture();
+        assert_eq!(
+            key(&f, &f.clips).as_deref(),
+            Some("error.output_contains_input")
+        );
+        assert_eq!(
+            key(&f, &f.paths.root().join("Footage")).as_deref(),
+            Some("error.output_contains_input")
+        );
+        // A trailing slash and a directory that does not exist yet change nothing
+        let trailing = format!("{}/", f.clips.display());
+        assert_eq!(
+            key(&f, Path::new(&trailing)).as_deref(),
+            Some("error.output_contains_input")
+        );
+        let masks = f.clips.join("masks");
+        std::fs::create_dir_all(&masks).unwrap();
+        let app_data = f.paths.app_data_dir().unwrap();
+        let masks = masks.to_string_lossy().to_string();
+        let inside = conflict(&f.clips.join("masks/out"), &[&masks], &app_data, false);
+        assert_eq!(inside.unwrap().key, "error.output_inside_input");
+
+        assert_eq!(key(&f, &f.paths.root().join("Productions/pier")), None);
+        // A sibling sharing a name prefix is unrelated
+        assert_eq!(key(&f, &f.paths.root().join("Footage/Clips-out")), None);
+    }
+
+    #[test]
+    fn output_may_not_overlap_app_data() {
+        let f = fixture();
+        let app_data = f.paths.app_data_dir().unwrap();
+        assert_eq!(
+            key(&f, &app_data.join("productions")).as_deref(),
+            Some("error.output_inside_app_data")
+        );
+
+        let policy = UnsafeOutputPolicy::Warn;
+        let output_dir = app_data.to_string_lossy().to_string();
+        let warning = check(&f.paths, policy, &output_dir, &[]).unwrap();
+        assert_eq!(warning.unwrap().key, "error.output_inside_app_data");
+        let rejected = check(&f.paths, UnsafeOutputPolicy::Reject, &output_dir, &[]);
+        assert_eq!(rejected.unwrap_err().code(), "unsafe_output_location");
+    }
+
+    #[test]
+    fn case_is_ignored_where_the_filesystem_ignores_it() {
+        let f = fixture();
+        let app_data = f.paths.app_data_dir().unwrap();
+        let lower = f.paths.root().join("Footage/clips");
+        let lower_key = |case_insensitive| {
+            conflict(&lower, &[&f.video], &app_data, case_insensitive).map(|m| m.key)
+        };
+        assert_eq!(
+            lower_key(true).as_deref(),
+            Some("error.output_contains_input")
+        );
+        if !lower.exists() {
+            assert_eq!(lower_key(false), None);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_followed() {
+        let f = fixture();
+        let link = f.paths.root().join("shortcut");
+        std::os::unix::fs::symlink(&f.clips, &link).unwrap();
+        // Beside the clips rather than holding them
+        assert_eq!(key(&f, &link.join("out")), None);
+        assert_eq!(
+            key(&f, &link).as_deref(),
+            Some("error.output_contains_input")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn other_drives_never_overlap() {
+        assert!(!is_within(
+            Path::new(r"D:\Clips\out"),
+            Path::new(r"C:\Clips"),
+            true
+        ));
+        assert!(is_within(
+            Path::new(r"c:\clips\out"),
+            Path::new(r"C:\Clips"),
+            true
+        ));
+    }
+}
@@ -0,0 +1,520 @@
+//! Overlap Analysis
+//!
+//! Estimates, before any COLMAP run, how much the clips of a multi-camera capture
+//! see of each other. A few frames per clip are reduced to corner features with
+//! binary descriptors and matched across clips; the weakest links a single
+//! reconstruction would depend on decide the verdict.
+
+use crate::error::AppError;
+use crate::extraction;
+use crate::job_log::unix_timestamp;
+use crate::media;
+use crate::path_policy::PathPolicy;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tauri::State;
+
+const SAMPLES_PER_CLIP: usize = 6;
+const FRAME_WIDTH: usize = 160;
+const FRAME_HEIGHT: usize = 120;
+
+/// Features are picked per grid cell so they spread over the whole frame
+const GRID_COLUMNS: usize = 8;
+const GRID_ROWS: usize = 6;
+
+/// Descriptors sample point pairs within this radius of a feature
+const PATCH_RADIUS: i32 = 7;
+
+/// Matches need at most this many differing bits out of 256, and a clear lead over the runner-up
+const MAX_MATCH_DISTANCE: u32 = 48;
+const MATCH_RATIO: f64 = 0.8;
+
+/// Clip overlap is the mean of this many best-matching frame pairs
+const TOP_FRAME_PAIRS: usize = 3;
+
+/// Weakest required link at or above which reconstruction usually succeeds, and below which it usually fails
+const GOOD_OVERLAP: f64 = 0.25;
+const MARGINAL_OVERLAP: f64 = 0.1;
+
+/// How many of the weakest links to name
+const WEAKEST_PAIRS: usize = 3;
+
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+// The last report, kept so a job on the same clips can record the verdict it ran against
+static LAST: Mutex<Option<(Vec<String>, OverlapSummary)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapVerdict {
+    Good,
+    Marginal,
+    LikelyToFail,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClipPair {
+    pub a: String,
+    pub b: String,
+    pub overlap: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverlapReport {
+    pub clips: Vec<String>,
+    /// Estimated share of features seen by both clips, indexed like `clips`
+    pub matrix: Vec<Vec<f64>>,
+    pub verdict: OverlapVerdict,
+    /// Weakest of the links that connect all clips, weakest first
+    pub weakest_pairs: Vec<ClipPair>,
+}
+
+/// What the job history keeps of an analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OverlapSummary {
+    pub verdict: OverlapVerdict,
+    pub weakest_pairs: Vec<ClipPair>,
+    pub analyzed_at: u64,
+}
+
+/// 256-bit binary descriptor of the patch around a feature
+type Descriptor = [u64; 4];
+
+/// Estimate pairwise visual overlap between clips before processing them
+#[tauri::command]
+pub async fn analyze_overlap(
+    policy: State<'_, PathPolicy>,
+    videos: Vec<String>,
+) -> Result<OverlapReport, AppError> {
+    for video in &videos {
+        policy.check_existing(video)?;
+    }
+    CANCEL.store(false, Ordering::SeqCst);
+    analyze(&videos, &CANCEL).await
+}
+
+/// Cancel a running overlap analysis
+#[tauri::command]
+pub async fn cancel_overlap_analysis() -> Result<(), AppError> {
+    CANCEL.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Verdict of the last analysis of exactly these clips, in any order
+pub fn summary_for(videos: &[String]) -> Option<OverlapSummary> {
+    let last = LAST.lock().unwrap();
+    let (clips, summary) = last.as_ref()?;
+    (sorted(clips) == sorted(videos)).then(|| summary.clone())
+}
+
+pub async fn analyze(videos: &[String], cancel: &AtomicBool) -> Result<OverlapReport, AppError> {
+    if videos.len() < 2 {
+        return Err(AppError::InvalidInput(
+            "Overlap analysis needs at least two clips".to_string(),
+        ));
+    }
+
+    let mut clip_features = vec![];
+    for video in videos {
+        let duration = media::probe(video).await?.duration_secs;
+        let mut frames = vec![];
+        for i in 0..SAMPLES_PER_CLIP {
+            if cancel.load(Ordering::SeqCst) {
+                return Err(AppError::Cancelled);
+            }
+            let at = duration * (i as f64 + 0.5) / SAMPLES_PER_CLIP as f64;
+            let pixels =
+                extraction::extract_gray_frame(video, at, FRAME_WIDTH, FRAME_HEIGHT).await?;
+            frames.push(features(&pixels, FRAME_WIDTH, FRAME_HEIGHT));
+        }
+        clip_features.push(frames);
+    }
+
+    let report = report(videos.to_vec(), &clip_features);
+    *LAST.lock().unwrap() = Some((
+        videos.to_vec(),
+        OverlapSummary {
+            verdict: report.verdict,
+            weakest_pairs: report.weakest_pairs.clone(),
+            analyzed_at: unix_timestamp(),
+        },
+    ));
+    Ok(report)
+}
+
+/// Build the report from the features of every sampled frame of every clip
+fn report(clips: Vec<String>, clip_features: &[Vec<Vec<Descriptor>>]) -> OverlapReport {
+    let n = clips.len();
+    let mut matrix = vec![vec![1.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let overlap = clip_overlap(&clip_features[i], &clip_features[j]);
+            matrix[i][j] = overlap;
+            matrix[j][i] = overlap;
+        }
+    }
+    assess(clips, matrix)
+}
+
+/// Judge an overlap matrix
+fn assess(clips: Vec<String>, matrix: Vec<Vec<f64>>) -> OverlapReport {
+    // Every clip must register against the rest through some chain of overlapping clips,
+    // so what matters is the weakest link of the strongest tree connecting them
+    let mut links = strongest_links(&matrix);
+    links.sort_by(|a, b| a.2.total_cmp(&b.2));
+    let weakest = links.first().map_or(1.0, |link| link.2);
+    let verdict = if weakest >= GOOD_OVERLAP {
+        OverlapVerdict::Good
+    } else if weakest >= MARGINAL_OVERLAP {
+        OverlapVerdict::Marginal
+    } else {
+        OverlapVerdict::LikelyToFail
+    };
+    let weakest_pairs = links
+        .iter()
+        .take(WEAKEST_PAIRS)
+        .map(|&(i, j, overlap)| ClipPair {
+            a: clips[i].clone(),
+            b: clips[j].clone(),
+            overlap,
+        })
+        .collect();
+
+    OverlapReport {
+        clips,
+        matrix,
+        verdict,
+        weakest_pairs,
+    }
+}
+
+// Maximum spanning tree of the overlap matrix (Prim's algorithm)
+fn strongest_links(matrix: &[Vec<f64>]) -> Vec<(usize, usize, f64)> {
+    let n = matrix.len();
+    let mut in_tree = vec![false; n];
+    let mut best: Vec<(f64, usize)> = vec![(f64::NEG_INFINITY, 0); n];
+    let mut links = vec![];
+    let mut next = 0;
+
+    for _ in 0..n {
+        in_tree[next] = true;
+        for (k, row) in best.iter_mut().enumerate() {
+            if !in_tree[k] && matrix[next][k] > row.0 {
+                *row = (matrix[next][k], next);
+            }
+        }
+        let Some(k) = (0..n)
+            .filter(|&k| !in_tree[k])
+            .max_by(|&a, &b| best[a].0.total_cmp(&best[b].0))
+        else {
+            break;
+        };
+        let (i, j) = (best[k].1.min(k), best[k].1.max(k));
+        links.push((i, j, best[k].0));
+        next = k;
+    }
+    links
+}
+
+fn clip_overlap(a: &[Vec<Descriptor>], b: &[Vec<Descriptor>]) -> f64 {
+    let mut scores: Vec<f64> = a
+        .iter()
+        .flat_map(|fa| b.iter().map(move |fb| frame_overlap(fa, fb)))
+        .collect();
+    scores.sort_by(|x, y| y.total_cmp(x));
+    let top = &scores[..scores.len().min(TOP_FRAME_PAIRS)];
+    if top.is_empty() {
+        0.0
+    } else {
+        top.iter().sum::<f64>() / top.len() as f64
+    }
+}
+
+/// Share of the smaller feature set with an unambiguous match in the other
+fn frame_overlap(a: &[Descriptor], b: &[Descriptor]) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let matches = a
+        .iter()
+        .filter(|da| {
+            let (mut first, mut second) = (u32::MAX, u32::MAX);
+            for db in b {
+                let d = hamming(da, db);
+                if d < first {
+                    second = first;
+                    first = d;
+                } else if d < second {
+                    second = d;
+                }
+            }
+            first <= MAX_MATCH_DISTANCE && (first as f64) < MATCH_RATIO * second as f64
+        })
+        .count();
+    matches as f64 / a.len().min(b.len()) as f64
+}
+
+fn hamming(a: &Descriptor, b: &Descriptor) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+/// Corner features of a grayscale frame: the strongest corner per grid cell, described by
+/// intensity comparisons between fixed point pairs around it
+fn features(pixels: &[u8], width: usize, height: usize) -> Vec<Descriptor> {
+    let smooth = box_blur(pixels, width, height);
+    let response = corner_response(&smooth, width, height);
+    let strongest = response.iter().cloned().fold(0.0, f32::max);
+    let threshold = (strongest * 0.01).max(1.0);
+    let margin = PATCH_RADIUS as usize + 1;
+
+    let (cell_w, cell_h) = (width / GRID_COLUMNS, height / GRID_ROWS);
+    let mut descriptors = vec![];
+    for row in 0..GRID_ROWS {
+        for column in 0..GRID_COLUMNS {
+            let mut best: Option<(usize, usize, f32)> = None;
+            let ys = (row * cell_h).max(margin)..((row + 1) * cell_h).min(height - margin);
+            for y in ys {
+                let xs = (column * cell_w).max(margin)..((column + 1) * cell_w).min(width - margin);
+                for x in xs {
+                    let r = response[y * width + x];
+                    if r > threshold && best.map_or(true, |b| r > b.2) {
+                        best = Some((x, y, r));
+                    }
+                }
+            }
+            if let Some((x, y, _)) = best {
+                descriptors.push(describe(&smooth, width, x as i32, y as i32));
+            }
+        }
+    }
+    descriptors
+}
+
+fn describe(smooth: &[f32], width: usize, x: i32, y: i32) -> Descriptor {
+    let at = |dx: i8, dy: i8| smooth[(y + dy as i32) as usize * width + (x + dx as i32) as usize];
+    let mut descriptor = [0u64; 4];
+    for (bit, &(x1, y1, x2, y2)) in sample_pairs().iter().enumerate() {
+        if at(x1, y1) < at(x2, y2) {
+            descriptor[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+    descriptor
+}
+
+// Fixed pseudo-random point pairs, the same on every run so descriptors are comparable
+fn sample_pairs() -> &'static [(i8, i8, i8, i8); 256] {
+    static PAIRS: OnceLock<[(i8, i8, i8, i8); 256]> = OnceLock::new();
+    PAIRS.get_or_init(|| {
+        let mut state: u32 = 0x9e37_79b9;
+        let mut offset = || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % (2 * PATCH_RADIUS as u32 + 1)) as i8 - PATCH_RADIUS as i8
+        };
+        std::array::from_fn(|_| (offset(), offset(), offset(), offset()))
+    })
+}
+
+/// 5x5 box blur; descriptors compare smoothed intensities so noise flips fewer bits
+fn box_blur(pixels: &[u8], width: usize, height: usize) -> Vec<f32> {
+    let mut out = vec![0.0; pixels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (mut sum, mut count) = (0.0, 0.0);
+            for yy in y.saturating_sub(2)..(y + 3).min(height) {
+                for xx in x.saturating_sub(2)..(x + 3).min(width) {
+                    sum += pixels[yy * width + xx] as f32;
+                    count += 1.0;
+                }
+            }
+            out[y * width + x] = sum / count;
+        }
+    }
+    out
+}
+
+/// Harris corner response over a 3x3 window
+fn corner_response(smooth: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let mut gradients = vec![(0.0f32, 0.0f32); smooth.len()];
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let i = y * width + x;
+            let gx = (smooth[i + 1] - smooth[i - 1]) / 2.0;
+            let gy = (smooth[i + width] - smooth[i - width]) / 2.0;
+            gradients[i] = (gx, gy);
+        }
+    }
+
+    let mut response = vec![0.0; smooth.len()];
+    for y in 2..height - 2 {
+        for x in 2..width - 2 {
+            let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+            for wy in y - 1..=y + 1 {
+                for wx in x - 1..=x + 1 {
+                    let (gx, gy) = gradients[wy * width + wx];
+                    xx += gx * gx;
+                    yy += gy * gy;
+                    xy += gx * gy;
+                }
+            }
+            let trace = xx + yy;
+            response[y * width + x] = xx * yy - xy * xy - 0.04 * trace * trace;
+        }
+    }
+    response
+}
+
+fn sorted(videos: &[String]) -> Vec<&String> {
+    let mut videos: Vec<&String> = videos.iter().collect();
+    videos.sort();
+    videos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blobby grayscale texture, `width` x `height`, deterministic for a seed
+    fn texture(seed: u32, width: usize, height: usize) -> Vec<u8> {
+        let mut state = seed.max(1);
+        let noise: Vec<u8> = (0..width * height)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 24) as u8
+            })
+            .collect();
+        box_blur(&noise, width, height)
+            .iter()
+            .map(|&v| ((v - 128.0) * 4.0 + 128.0).clamp(0.0, 255.0) as u8)
+            .collect()
+    }
+
+    fn crop(pixels: &[u8], width: usize, x0: usize) -> Vec<u8> {
+        pixels
+            .chunks(width)
+            .flat_map(|row| row[x0..x0 + FRAME_WIDTH].to_vec())
+            .collect()
+    }
+
+    fn frame_features(pixels: &[u8]) -> Vec<Vec<Descriptor>> {
+        vec![features(pixels, FRAME_WIDTH, FRAME_HEIGHT)]
+    }
+
+    #[test]
+    fn shifted_views_of_one_scene_overlap() {
+        let scene = texture(7, 280, FRAME_HEIGHT);
+        let left = frame_features(&crop(&scene, 280, 0));
+        let middle = frame_features(&crop(&scene, 280, 40));
+        let far = frame_features(&crop(&scene, 280, 120));
+        let elsewhere = frame_features(&texture(99, FRAME_WIDTH, FRAME_HEIGHT));
+
+        let near = clip_overlap(&left, &middle);
+        let edge = clip_overlap(&left, &far);
+        let none = clip_overlap(&left, &elsewhere);
+        assert!(!left[0].is_empty());
+        assert!(near > GOOD_OVERLAP, "shifted view: {}", near);
+        assert!(near > edge, "{} vs {}", near, edge);
+        assert!(none < MARGINAL_OVERLAP, "unrelated view: {}", none);
+    }
+
+    #[test]
+    fn flat_frames_have_no_features() {
+        let flat = vec![128u8; FRAME_WIDTH * FRAME_HEIGHT];
+        assert!(features(&flat, FRAME_WIDTH, FRAME_HEIGHT).is_empty());
+        assert_eq!(frame_overlap(&[], &[[0; 4]]), 0.0);
+    }
+
+    fn clips(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("/clips/cam{}.mp4", i)).collect()
+    }
+
+    fn assess_matrix(matrix: Vec<Vec<f64>>) -> (OverlapVerdict, Vec<(String, String)>) {
+        let report = assess(clips(matrix.len()), matrix);
+        let pairs = report
+            .weakest_pairs
+            .into_iter()
+            .map(|pair| (pair.a, pair.b))
+            .collect();
+        (report.verdict, pairs)
+    }
+
+    #[test]
+    fn verdict_follows_the_weakest_required_link() {
+        // cam1 and cam3 barely overlap, but both overlap cam2 well
+        let chained = vec![
+            vec![1.0, 0.5, 0.02],
+            vec![0.5, 1.0, 0.4],
+            vec![0.02, 0.4, 1.0],
+        ];
+        let (verdict, links) = assess_matrix(chained);
+        assert_eq!(verdict, OverlapVerdict::Good);
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            links[0],
+            ("/clips/cam2.mp4".to_string(), "/clips/cam3.mp4".to_string())
+        );
+
+        let isolated = vec![
+            vec![1.0, 0.5, 0.05],
+            vec![0.5, 1.0, 0.03],
+            vec![0.05, 0.03, 1.0],
+        ];
+        let (verdict, links) = assess_matrix(isolated);
+        assert_eq!(verdict, OverlapVerdict::LikelyToFail);
+        assert_eq!(
+            links[0],
+            ("/clips/cam1.mp4".to_string(), "/clips/cam3.mp4".to_string())
+        );
+
+        let (verdict, _) = assess_matrix(vec![vec![1.0, 0.15], vec![0.15, 1.0]]);
+        assert_eq!(verdict, OverlapVerdict::Marginal);
+    }
+
+    #[test]
+    fn report_names_weakest_pairs_from_features() {
+        let scene = texture(7, 280, FRAME_HEIGHT);
+        let features = vec![
+            frame_features(&crop(&scene, 280, 0)),
+            frame_features(&crop(&scene, 280, 40)),
+            frame_features(&texture(99, FRAME_WIDTH, FRAME_HEIGHT)),
+        ];
+        let report = report(clips(3), &features);
+
+        assert_eq!(report.verdict, OverlapVerdict::LikelyToFail);
+        assert_eq!(report.matrix[0][1], report.matrix[1][0]);
+        assert_eq!(report.weakest_pairs.len(), 2);
+        assert_eq!(report.weakest_pairs[0].b, "/clips/cam3.mp4");
+    }
+
+    #[test]
+    fn summary_matches_clips_in_any_order() {
+        let videos = clips(2);
+        *LAST.lock().unwrap() = Some((
+            videos.clone(),
+            OverlapSummary {
+                verdict: OverlapVerdict::Marginal,
+                weakest_pairs: vec![],
+                analyzed_at: 0,
+            },
+        ));
+        let reversed: Vec<String> = videos.iter().rev().cloned().collect();
+        assert_eq!(
+            summary_for(&reversed).map(|s| s.verdict),
+            Some(OverlapVerdict::Marginal)
+        );
+        assert!(summary_for(&clips(3)).is_none());
+    }
+
+    #[tokio::test]
+    async fn single_clip_is_rejected() {
+        let err = analyze(&clips(1), &AtomicBool::new(false))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "invalid_input");
+    }
+}
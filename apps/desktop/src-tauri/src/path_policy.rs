@@ -0,0 +1,359 @@
+//! Path Policy
+//!
+//! Decides which filesystem paths the webview may hand to the backend. User media
+//! must resolve, after following symlinks, under a root the user picked through a
+//! native dialog this session or one already recorded in settings. App-managed
+//! files must resolve inside app_data.
+
+use crate::error::AppError;
+use crate::settings::AppSettings;
+use std::path::{Component, Path, PathBuf};
+use std::sync::RwLock;
+
+/// Canonicalized roots the webview may refer to, kept for the current session
+#[derive(Default)]
+pub struct PathPolicy {
+    roots: RwLock<Vec<PathBuf>>,
+}
+
+impl PathPolicy {
+    /// Trust `path` and everything under it for the rest of the session
+    pub fn allow(&self, path: &Path) {
+        let Ok(canonical) = path.canonicalize() else {
+            return;
+        };
+        let mut roots = self.roots.write().unwrap();
+        if !roots.iter().any(|root| canonical.starts_with(root)) {
+            roots.retain(|root| !root.starts_with(&canonical));
+            roots.push(canonical);
+        }
+    }
+
+    /// Trust the output and production directories, tools and viewers the user chose in earlier sessions
+    pub fn allow_configured(&self, settings: &AppSettings) {
+        let configured = [
+            Some(settings.default_output_dir.as_str()),
+            settings.colmap_path.as_deref(),
+            settings.brush_path.as_deref(),
+            settings.ffmpeg_path.as_deref(),
+        ];
+        for path in configured.into_iter().flatten() {
+            if !path.is_empty() {
+                self.allow(Path::new(path));
+            }
+        }
+        for viewer in &settings.external_viewers {
+            self.allow(Path::new(&viewer.path));
+        }
+        for recent in &settings.recent_productions {
+            self.allow(Path::new(&recent.path));
+        }
+    }
+
+    /// Check a path that must already exist, such as an input clip
+    pub fn check_existing(&self, path: &str) -> Result<(), AppError> {
+        // Check the location first so paths outside the roots never reveal whether they exist
+        self.check_target(path)?;
+        if Path::new(path).exists() {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(path.to_string()))
+        }
+    }
+
+    /// Check a path that may not exist yet, such as a move destination or a new output directory
+    pub fn check_target(&self, path: &str) -> Result<(), AppError> {
+        let requested = syntactically_safe(path)?;
+        let canonical =
+            resolve_target(requested).ok_or_else(|| AppError::PathNotAllowed(path.to_string()))?;
+        self.check_canonical(path, &canonical)
+    }
+
+    fn check_canonical(&self, path: &str, canonical: &Path) -> Result<(), AppError> {
+        let roots = self.roots.read().unwrap();
+        if roots.iter().any(|root| canonical.starts_with(root)) {
+            Ok(())
+        } else {
+            Err(AppError::PathNotAllowed(path.to_string()))
+        }
+    }
+}
+
+/// Canonicalize a path that may not exist yet: its deepest existing ancestor
+/// with symlinks followed, then the missing rest. None when that ancestor
+/// cannot be resolved, such as a dangling symlink, which is counted as existing
+/// so nothing is ever created through it.
+pub fn resolve_target(path: &Path) -> Option<PathBuf> {
+    let mut existing = path;
+    let mut missing = vec![];
+    while existing.symlink_metadata().is_err() {
+        missing.push(existing.file_name()?.to_os_string());
+        existing = existing.parent()?;
+    }
+    let mut canonical = existing.canonicalize().ok()?;
+    canonical.extend(missing.iter().rev());
+    Some(canonical)
+}
+
+/// Resolve a relative path inside app_data, rejecting anything that would escape it
+pub fn resolve_app_data(app_data: &Path, relative: &str) -> Result<PathBuf, AppError> {
+    let relative_path = Path::new(relative);
+    let only_names = relative_path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if relative.is_empty() || !only_names {
+        return Err(AppError::PathNotAllowed(relative.to_string()));
+    }
+
+    let resolved = app_data.join(relative_path);
+    if resolved.symlink_metadata().is_ok() {
+        let canonical_root = app_data.canonicalize()?;
+        let escapes = resolved
+            .canonicalize()
+            .map_or(true, |canonical| !canonical.starts_with(&canonical_root));
+        if escapes {
+            return Err(AppError::PathNotAllowed(relative.to_string()));
+        }
+    }
+    Ok(resolved)
+}
+
+// Absolute and free of `..`, before touching the filesystem
+fn syntactically_safe(path: &str) -> Result<&Path, AppError> {
+    let requested = Path::new(path);
+    if !requested.is_absolute() || requested.components().any(|c| c == Component::ParentDir) {
+        return Err(AppError::PathNotAllowed(path.to_string()));
+    }
+    Ok(requested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use std::fs;
+
+    struct Fixture {
+        // Keeps the directory alive for the duration of the test
+        _temp: TempPaths,
+        picked: PathBuf,
+        outside: PathBuf,
+        policy: PathPolicy,
+    }
+
+    fn fixture() -> Fixture {
+        let temp = TempPaths::new();
+        let picked = temp.root().join("media");
+        let outside = temp.root().join("media-private");
+        fs::create_dir_all(picked.join("clips")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(picked.join("clips/cam1.mp4"), b"").unwrap();
+        fs::write(outside.join("secret.mp4"), b"").unwrap();
+
+        let policy = PathPolicy::default();
+        policy.allow(&picked);
+        Fixture {
+            _temp: temp,
+            picked,
+            outside,
+            policy,
+        }
+    }
+
+    fn s(path: &Path) -> String {
+        path.to_string_lossy().to_string()
+    }
+
+    fn assert_not_allowed(result: Result<(), AppError>) {
+        assert_eq!(result.unwrap_err().code(), "path_not_allowed");
+    }
+
+    #[test]
+    fn allows_picked_root_and_its_contents() {
+        let f = fixture();
+        f.policy.check_existing(&s(&f.picked)).unwrap();
+        f.policy
+            .check_existing(&s(&f.picked.join("clips/cam1.mp4")))
+            .unwrap();
+    }
+
+    #[test]
+    fn empty_policy_allows_nothing() {
+        let f = fixture();
+        let policy = PathPolicy::default();
+        assert_not_allowed(policy.check_existing(&s(&f.picked.join("clips/cam1.mp4"))));
+        assert_not_allowed(policy.check_target(&s(&f.picked.join("new"))));
+    }
+
+    #[test]
+    fn rejects_sibling_sharing_a_name_prefix() {
+        let f = fixture();
+        assert_not_allowed(f.policy.check_existing(&s(&f.outside.join("secret.mp4"))));
+    }
+
+    #[test]
+    fn rejects_relative_paths() {
+        let f = fixture();
+        assert_not_allowed(f.policy.check_existing("clips/cam1.mp4"));
+        assert_not_allowed(f.policy.check_target("new-production"));
+        assert_not_allowed(f.policy.check_existing(""));
+    }
+
+    #[test]
+    fn rejects_parent_traversal_even_when_it_stays_inside() {
+        let f = fixture();
+        let escaping = format!("{}/../media-private/secret.mp4", s(&f.picked));
+        assert_not_allowed(f.policy.check_existing(&escaping));
+        let staying = format!("{}/clips/../clips/cam1.mp4", s(&f.picked));
+        assert_not_allowed(f.policy.check_existing(&staying));
+    }
+
+    #[test]
+    fn missing_inputs_are_not_found() {
+        let f = fixture();
+        let err = f
+            .policy
+            .check_existing(&s(&f.picked.join("clips/missing.mp4")))
+            .unwrap_err();
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[test]
+    fn missing_paths_outside_roots_are_not_revealed() {
+        let f = fixture();
+        assert_not_allowed(f.policy.check_existing(&s(&f.outside.join("missing.mp4"))));
+    }
+
+    #[test]
+    fn targets_may_not_exist_yet() {
+        let f = fixture();
+        f.policy
+            .check_target(&s(&f.picked.join("productions/new/output")))
+            .unwrap();
+        assert_not_allowed(f.policy.check_target(&s(&f.outside.join("new"))));
+    }
+
+    #[test]
+    fn later_picks_extend_the_allowed_roots() {
+        let f = fixture();
+        f.policy.allow(&f.outside);
+        f.policy
+            .check_existing(&s(&f.outside.join("secret.mp4")))
+            .unwrap();
+    }
+
+    #[test]
+    fn configured_paths_are_trusted() {
+        let f = fixture();
+        let policy = PathPolicy::default();
+        let mut settings = AppSettings {
+            default_output_dir: s(&f.outside),
+            ..Default::default()
+        };
+        settings
+            .recent_productions
+            .push(crate::settings::RecentProduction {
+                id: "p1".to_string(),
+                name: "Clips".to_string(),
+                path: s(&f.picked.join("clips")),
+                last_opened: String::new(),
+                tags: vec![],
+                notes: String::new(),
+                imported: false,
+                missing: false,
+                pinned: false,
+            });
+        policy.allow_configured(&settings);
+
+        policy
+            .check_existing(&s(&f.outside.join("secret.mp4")))
+            .unwrap();
+        policy
+            .check_existing(&s(&f.picked.join("clips/cam1.mp4")))
+            .unwrap();
+        assert_not_allowed(policy.check_existing(&s(&f.picked)));
+    }
+
+    #[cfg(unix)]
+    mod symlinks {
+        use super::*;
+        use std::os::unix::fs::symlink;
+
+        #[test]
+        fn rejects_file_link_escaping_the_root() {
+            let f = fixture();
+            let link = f.picked.join("clips/innocent.mp4");
+            symlink(f.outside.join("secret.mp4"), &link).unwrap();
+            assert_not_allowed(f.policy.check_existing(&s(&link)));
+        }
+
+        #[test]
+        fn rejects_directory_link_escaping_the_root() {
+            let f = fixture();
+            let link = f.picked.join("linked");
+            symlink(&f.outside, &link).unwrap();
+            assert_not_allowed(f.policy.check_existing(&s(&link.join("secret.mp4"))));
+            assert_not_allowed(f.policy.check_target(&s(&link.join("new-production"))));
+        }
+
+        #[test]
+        fn rejects_dangling_link_as_target() {
+            let f = fixture();
+            let link = f.picked.join("dangling");
+            symlink(f.outside.join("not-yet-created"), &link).unwrap();
+            assert_not_allowed(f.policy.check_target(&s(&link)));
+            assert_not_allowed(f.policy.check_target(&s(&link.join("output"))));
+        }
+
+        #[test]
+        fn allows_link_that_stays_inside_the_root() {
+            let f = fixture();
+            let link = f.picked.join("latest.mp4");
+            symlink(f.picked.join("clips/cam1.mp4"), &link).unwrap();
+            f.policy.check_existing(&s(&link)).unwrap();
+        }
+
+        #[test]
+        fn picked_root_behind_a_link_is_resolved() {
+            let f = fixture();
+            let link = f.outside.join("shortcut");
+            symlink(&f.picked, &link).unwrap();
+            let policy = PathPolicy::default();
+            policy.allow(&link);
+
+            policy
+                .check_existing(&s(&f.picked.join("clips/cam1.mp4")))
+                .unwrap();
+            policy
+                .check_existing(&s(&link.join("clips/cam1.mp4")))
+                .unwrap();
+            assert_not_allowed(policy.check_existing(&s(&f.outside.join("secret.mp4"))));
+        }
+
+        #[test]
+        fn app_data_link_cannot_escape() {
+            let f = fixture();
+            symlink(&f.outside, f.picked.join("logs")).unwrap();
+            let err = resolve_app_data(&f.picked, "logs/secret.mp4").unwrap_err();
+            assert_eq!(err.code(), "path_not_allowed");
+        }
+    }
+
+    #[test]
+    fn app_data_paths_stay_inside() {
+        let f = fixture();
+        assert_eq!(
+            resolve_app_data(&f.picked, "logs/job-1.log").unwrap(),
+            f.picked.join("logs/job-1.log")
+        );
+        for bad in [
+            "",
+            "../media-private/secret.mp4",
+            "logs/../../x",
+            "/etc/passwd",
+        ] {
+            let err = resolve_app_data(&f.picked, bad).unwrap_err();
+            assert_eq!(err.code(), "path_not_allowed", "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+//! Pending Tasks
+//!
+//! Long conversions, web exports and downsamples alike, write a temp file
+//! beside their output and rename it into place once complete; if the app
+//! quits or crashes first, the temp file is all that is left. While one runs it is recorded in
+//! app_data/pending_tasks.json with what it needs to run again, the size and
+//! modification time of its source and, once the task has read it, the
+//! source's SHA-256; no task reads its source an extra time to hash it. At launch, tasks the last run left behind are marked
+//! paused, for the user to resume or discard. Resuming refuses when the source
+//! changed since, as the output would no longer be of what was asked for;
+//! discarding removes the temp file.
+
+use crate::downsample::{self, DownsampleResult, DownsampleTarget};
+use crate::error::AppError;
+use crate::fsutil;
+use crate::job_log::{self, unix_timestamp};
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::web_export::{self, WebExport, WebFormat};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, State};
+
+/// Written in place of the output until it is complete
+const TEMP_SUFFIX: &str = ".partial";
+
+/// Held while the task file is read, changed and written back
+static WRITING: Mutex<()> = Mutex::new(());
+
+/// What a task does, with what it needs to do it again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TaskWork {
+    /// A splat PLY converted to a format web viewers load
+    Conversion {
+        input: String,
+        output: String,
+        format: WebFormat,
+    },
+    /// A splat PLY copied with fewer splats
+    Downsample {
+        input: String,
+        output: String,
+        target: DownsampleTarget,
+    },
+}
+
+impl TaskWork {
+    fn source(&self) -> &str {
+        match self {
+            TaskWork::Conversion { input, .. } | TaskWork::Downsample { input, .. } => input,
+        }
+    }
+
+    fn output(&self) -> &str {
+        match self {
+            TaskWork::Conversion { output, .. } | TaskWork::Downsample { output, .. } => output,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    /// Left unfinished by an earlier run of the app, or refused on resuming
+    Paused,
+}
+
+/// What a source looked like when its task started; cheap to take, so a task
+/// is recorded before the source has been read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceStamp {
+    pub bytes: u64,
+    pub modified: u64,
+}
+
+impl SourceStamp {
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(Self {
+            bytes: metadata.len(),
+            modified,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingTask {
+    pub task_id: String,
+    pub work: TaskWork,
+    /// Where the output is written until it is complete
+    pub temp_path: String,
+    pub source: SourceStamp,
+    /// Known once the task has read its source
+    pub source_sha256: Option<String>,
+    pub started_at: u64,
+    pub state: TaskState,
+}
+
+impl PendingTask {
+    /// A new running task for `work`, stamped with its source as it is now
+    pub fn start(work: TaskWork) -> Result<Self, AppError> {
+        Ok(Self {
+            task_id: job_log::new_id("task"),
+            temp_path: temp_path(Path::new(work.output()))
+                .to_string_lossy()
+                .to_string(),
+            source: SourceStamp::of(Path::new(work.source()))?,
+            source_sha256: None,
+            started_at: unix_timestamp(),
+            state: TaskState::Running,
+            work,
+        })
+    }
+}
+
+/// What a resumed task produced
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TaskResult {
+    Conversion(WebExport),
+    Downsample(DownsampleResult),
+}
+
+/// Where a task writes `output` until it is complete
+pub fn temp_path(output: &Path) -> PathBuf {
+    PathBuf::from(format!("{}{}", output.display(), TEMP_SUFFIX))
+}
+
+fn tasks_path(paths: &impl PathProvider) -> Result<PathBuf, String> {
+    Ok(paths.app_data_dir()?.join("pending_tasks.json"))
+}
+
+fn load(paths: &impl PathProvider) -> Result<Vec<PendingTask>, String> {
+    let path = tasks_path(paths)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+/// Change the recorded tasks under the lock and write them back
+fn update<T>(
+    paths: &impl PathProvider,
+    change: impl FnOnce(&mut Vec<PendingTask>) -> Result<T, AppError>,
+) -> Result<T, AppError> {
+    let _writing = WRITING.lock().unwrap();
+    let mut tasks = load(paths)?;
+    let changed = change(&mut tasks)?;
+    fsutil::write_json_atomic(&tasks_path(paths)?, &tasks)?;
+    Ok(changed)
+}
+
+fn find<'a>(tasks: &'a mut [PendingTask], task_id: &str) -> Result<&'a mut PendingTask, AppError> {
+    tasks
+        .iter_mut()
+        .find(|t| t.task_id == task_id)
+        .ok_or_else(|| AppError::NotFound(format!("Pending task {}", task_id)))
+}
+
+/// Record a task as running, replacing an earlier record of it
+pub fn begin(paths: &impl PathProvider, task: &PendingTask) -> Result<(), AppError> {
+    update(paths, |tasks| {
+        tasks.retain(|t| t.task_id != task.task_id);
+        tasks.push(task.clone());
+        Ok(())
+    })
+}
+
+/// Called once a task has hashed its source: records the hash the first time,
+/// and on resuming refuses to go on if it is not the one recorded, leaving
+/// the task paused
+pub fn source_hashed(
+    paths: &impl PathProvider,
+    task_id: &str,
+    sha256: &str,
+) -> Result<(), AppError> {
+    update(paths, |tasks| {
+        let task = find(tasks, task_id)?;
+        let recorded = task.source_sha256.get_or_insert_with(|| sha256.to_string());
+        if *recorded != sha256 {
+            task.state = TaskState::Paused;
+            return Ok(Err(changed(task)));
+        }
+        Ok(Ok(()))
+    })?
+}
+
+/// Forget a task that finished, failed or was cancelled, whose temp file is
+/// gone with it; one paused on resuming is kept for the user to discard
+pub fn finish(paths: &impl PathProvider, task_id: &str) -> Result<(), AppError> {
+    update(paths, |tasks| {
+        tasks.retain(|t| t.task_id != task_id || t.state == TaskState::Paused);
+        Ok(())
+    })
+}
+
+/// Mark the tasks an earlier run of the app left running as paused; called
+/// at launch, before any task can start
+pub fn reconcile(paths: &impl PathProvider) -> Result<usize, AppError> {
+    update(paths, |tasks| {
+        let mut paused = 0;
+        for task in tasks.iter_mut().filter(|t| t.state == TaskState::Running) {
+            task.state = TaskState::Paused;
+            paused += 1;
+        }
+        Ok(paused)
+    })
+}
+
+/// Take a paused task to resume it, so it is only resumed once. A source of
+/// another size or modification time has surely changed; one that looks the
+/// same is checked against its hash as the task reads it again.
+fn claim(paths: &impl PathProvider, task_id: &str) -> Result<PendingTask, AppError> {
+    update(paths, |tasks| {
+        let task = find(tasks, task_id)?;
+        if task.state != TaskState::Paused {
+            return Err(AppError::InvalidInput(
+                "The task is already running".to_string(),
+            ));
+        }
+        if SourceStamp::of(Path::new(task.work.source())).ok() != Some(task.source) {
+            return Ok(Err(changed(task)));
+        }
+        task.state = TaskState::Running;
+        Ok(Ok(task.clone()))
+    })?
+}
+
+fn changed(task: &PendingTask) -> AppError {
+    AppError::InvalidInput(format!(
+        "{} changed since the task started; discard it and start again",
+        task.work.source()
+    ))
+}
+
+/// Forget a paused task and remove its temp file
+fn discard(paths: &impl PathProvider, task_id: &str) -> Result<(), AppError> {
+    update(paths, |tasks| {
+        let task = find(tasks, task_id)?;
+        if task.state != TaskState::Paused {
+            return Err(AppError::InvalidInput(
+                "A running task cannot be discarded".to_string(),
+            ));
+        }
+        // Derived from the output rather than taken from the file, so an
+        // edited record cannot name something else to delete
+        match std::fs::remove_file(temp_path(Path::new(task.work.output()))) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        tasks.retain(|t| t.task_id != task_id);
+        Ok(())
+    })
+}
+
+/// Tasks running now or left unfinished by an earlier run, in the order they
+/// last started
+#[tauri::command]
+pub async fn get_pending_tasks(app: AppHandle) -> Result<Vec<PendingTask>, AppError> {
+    Ok(load(&app)?)
+}
+
+/// Run a paused task again from the start, if its source is unchanged
+#[tauri::command]
+pub async fn resume_pending_task(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    task_id: String,
+) -> Result<TaskResult, AppError> {
+    let recorded = load(&app)?
+        .into_iter()
+        .find(|t| t.task_id == task_id)
+        .ok_or_else(|| AppError::NotFound(format!("Pending task {}", task_id)))?;
+    policy.check_existing(recorded.work.source())?;
+    policy.check_target(recorded.work.output())?;
+    let task = claim(&app, &task_id)?;
+    match task.work.clone() {
+        TaskWork::Conversion {
+            input,
+            output,
+            format,
+        } => web_export::run(app, input, output, format, Some(task))
+            .await
+            .map(TaskResult::Conversion),
+        TaskWork::Downsample {
+            input,
+            output,
+            target,
+        } => downsample::run(app, input, output, target, Some(task))
+            .await
+            .map(TaskResult::Downsample),
+    }
+}
+
+/// Forget a paused task and remove the temp file it left
+#[tauri::command]
+pub async fn discard_pending_task(app: AppHandle, task_id: String) -> Result<(), AppError> {
+    discard(&app, &task_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn task(paths: &TempPaths) -> PendingTask {
+        let input = paths.root().join("output.ply");
+        std::fs::write(&input, b"ply").unwrap();
+        PendingTask::start(TaskWork::Conversion {
+            input: input.to_string_lossy().to_string(),
+            output: paths.root().join("web.splat").to_string_lossy().to_string(),
+            format: WebFormat::Splat,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn tasks_left_running_are_paused_at_launch() {
+        let paths = TempPaths::new();
+        let (left, finished) = (task(&paths), task(&paths));
+        begin(&paths, &left).unwrap();
+        begin(&paths, &finished).unwrap();
+        finish(&paths, &finished.task_id).unwrap();
+
+        assert_eq!(reconcile(&paths).unwrap(), 1);
+        let tasks = load(&paths).unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].task_id, left.task_id);
+        assert_eq!(tasks[0].state, TaskState::Paused);
+        assert!(tasks[0].temp_path.ends_with("web.splat.partial"));
+
+        // Only one resume gets it
+        claim(&paths, &left.task_id).unwrap();
+        assert!(claim(&paths, &left.task_id).is_err());
+    }
+
+    #[test]
+    fn resuming_refuses_a_changed_source() {
+        let paths = TempPaths::new();
+        let task = task(&paths);
+        begin(&paths, &task).unwrap();
+        source_hashed(&paths, &task.task_id, "abc").unwrap();
+        reconcile(&paths).unwrap();
+
+        // Looks the same, but hashes differently when read again
+        let resumed = claim(&paths, &task.task_id).unwrap();
+        assert_eq!(resumed.source_sha256.as_deref(), Some("abc"));
+        assert!(source_hashed(&paths, &task.task_id, "def").is_err());
+        finish(&paths, &task.task_id).unwrap();
+        assert_eq!(load(&paths).unwrap()[0].state, TaskState::Paused);
+
+        // Another size is refused before reading it at all
+        std::fs::write(task.work.source(), b"a larger ply").unwrap();
+        assert!(matches!(
+            claim(&paths, &task.task_id),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert_eq!(load(&paths).unwrap()[0].state, TaskState::Paused);
+    }
+
+    #[test]
+    fn discarding_removes_the_temp_file() {
+        let paths = TempPaths::new();
+        let task = task(&paths);
+        begin(&paths, &task).unwrap();
+        std::fs::write(&task.temp_path, b"half a copy").unwrap();
+
+        assert!(matches!(
+            discard(&paths, &task.task_id),
+            Err(AppError::InvalidInput(_))
+        ));
+        assert!(Path::new(&task.temp_path).exists());
+
+        reconcile(&paths).unwrap();
+        discard(&paths, &task.task_id).unwrap();
+        assert!(!Path::new(&task.temp_path).exists());
+        assert!(load(&paths).unwrap().is_empty());
+        assert!(matches!(
+            discard(&paths, &task.task_id),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,414 @@
+//! Performance Environment
+//!
+//! COLMAP writes and rereads thousands of small frames, the load that suffers
+//! most from real-time antivirus scanning and from slow drives; Windows users
+//! have seen reconstruction run five times slower with Defender scanning the
+//! output. This looks for both, best effort, and only gives advice: antivirus
+//! settings are never changed. On Windows, Defender's real-time state and
+//! exclusions, other registered antivirus products and each drive's bus are
+//! read through PowerShell; the exclusions need an administrator, so without
+//! one they are reported as unknown. On every platform a few synced random
+//! writes hint at a spinning or shingled (SMR) drive.
+
+use crate::disk;
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::path_policy::PathPolicy;
+use crate::scheduler::{self, Pool};
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+use tokio::process::Command;
+
+const DEFENDER_EXCLUSIONS_DOCS: &str =
+    "https://learn.microsoft.com/en-us/defender-endpoint/configure-exclusions-microsoft-defender-antivirus";
+
+/// How long the PowerShell queries may take; Defender's cmdlets are slow on a cold start
+const QUERY_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Median synced random write above which a drive is likely spinning or SMR
+const SLOW_WRITE: Duration = Duration::from_millis(15);
+
+const PROBE_WRITES: u64 = 24;
+const PROBE_BLOCK: usize = 4096;
+/// Size of the sparse file the probe writes into
+const PROBE_SPAN: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Worth knowing, such as a check that could not be made
+    Info,
+    /// Likely to slow jobs down noticeably
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub code: String,
+    pub severity: Severity,
+    pub message: Message,
+    /// The directory the finding is about, when it is about one
+    pub path: Option<String>,
+    pub docs_url: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceEnvironment {
+    /// The directories looked at
+    pub dirs: Vec<String>,
+    /// Warnings first
+    pub findings: Vec<Finding>,
+}
+
+/// What PowerShell reported; None where it could not tell
+#[derive(Debug, Default, PartialEq)]
+struct WindowsSecurity {
+    realtime: Option<bool>,
+    exclusions: Option<Vec<String>>,
+    /// Antivirus products registered with the Security Center, Defender included
+    products: Vec<String>,
+    /// Bus type by drive letter, e.g. "USB" or "NVMe"
+    buses: BTreeMap<char, String>,
+}
+
+/// Look for antivirus scanning and slow drives that would slow jobs writing to
+/// the scratch directory and `output_dir`, or the default output directory
+#[tauri::command]
+pub async fn analyze_performance_environment(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    output_dir: Option<String>,
+) -> Result<PerformanceEnvironment, AppError> {
+    let settings = app.settings();
+    if let Some(output_dir) = &output_dir {
+        policy.check_target(output_dir)?;
+    }
+    let output_dir = output_dir.or(Some(settings.default_output_dir).filter(|d| !d.is_empty()));
+    let mut dirs = vec![disk::scratch_dir(settings.scratch_dir.as_deref())];
+    dirs.extend(output_dir.map(PathBuf::from));
+    dirs.dedup();
+
+    let mut findings = vec![];
+    if cfg!(windows) {
+        match query_windows_security(&dirs).await {
+            Some(report) => findings.extend(security_findings(&parse_report(&report), &dirs)),
+            None => findings.push(finding(
+                "security_unknown",
+                Severity::Info,
+                Message::new("performance.security_unknown"),
+                None,
+            )),
+        }
+    }
+    for dir in probe_dirs(&dirs) {
+        let probed = scheduler::run_blocking(Pool::Io, {
+            let dir = dir.clone();
+            move || probe_write_latency(&dir)
+        })
+        .await;
+        if let Ok(Ok(median)) = probed {
+            findings.extend(latency_finding(&dir, median));
+        }
+    }
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+
+    Ok(PerformanceEnvironment {
+        dirs: dirs
+            .iter()
+            .map(|d| d.to_string_lossy().to_string())
+            .collect(),
+        findings,
+    })
+}
+
+fn finding(code: &str, severity: Severity, message: Message, path: Option<&Path>) -> Finding {
+    Finding {
+        code: code.to_string(),
+        severity,
+        message,
+        path: path.map(|p| p.to_string_lossy().to_string()),
+        docs_url: None,
+    }
+}
+
+/// Defender's state, the antivirus products and the drives' buses, one
+/// `key=value` per line
+async fn query_windows_security(dirs: &[PathBuf]) -> Option<String> {
+    let mut script = String::from(
+        "$ErrorActionPreference = 'SilentlyContinue'\n\
+         $s = Get-MpComputerStatus\n\
+         if ($s) { \"realtime=$($s.RealTimeProtectionEnabled)\" }\n\
+         $p = Get-MpPreference\n\
+         if ($p) { 'exclusions=read'; foreach ($e in $p.ExclusionPath) { \"exclusion=$e\" } }\n\
+         Get-CimInstance -Namespace root/SecurityCenter2 -ClassName AntiVirusProduct | \
+         ForEach-Object { \"product=$($_.displayName)\" }\n",
+    );
+    let mut drives: Vec<char> = dirs.iter().filter_map(|d| drive_letter(d)).collect();
+    drives.dedup();
+    for drive in drives {
+        script.push_str(&format!(
+            "$b = (Get-Partition -DriveLetter {0} | Get-Disk).BusType\n\
+             if ($b) {{ \"bus={0}=$b\" }}\n",
+            drive
+        ));
+    }
+
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(QUERY_TIMEOUT, output)
+        .await
+        .ok()?
+        .ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn parse_report(report: &str) -> WindowsSecurity {
+    let mut security = WindowsSecurity::default();
+    let mut exclusions = vec![];
+    let mut exclusions_read = false;
+    let mut exclusions_hidden = false;
+    for line in report.lines() {
+        let Some((key, value)) = line.trim().split_once('=') else {
+            continue;
+        };
+        match key {
+            "realtime" => {
+                security.realtime = match value.to_ascii_lowercase().as_str() {
+                    "true" => Some(true),
+                    "false" => Some(false),
+                    _ => None,
+                }
+            }
+            "exclusions" => exclusions_read = true,
+            // What Defender lists instead of the exclusions for anyone but an administrator
+            "exclusion" if value.starts_with("N/A") => exclusions_hidden = true,
+            "exclusion" => exclusions.push(value.to_string()),
+            "product" if !value.is_empty() => security.products.push(value.to_string()),
+            "bus" => {
+                if let Some((drive, bus)) = value.split_once('=') {
+                    if let Some(drive) = drive.chars().next() {
+                        security
+                            .buses
+                            .insert(drive.to_ascii_uppercase(), bus.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if exclusions_read && !exclusions_hidden {
+        security.exclusions = Some(exclusions);
+    }
+    security
+}
+
+fn security_findings(security: &WindowsSecurity, dirs: &[PathBuf]) -> Vec<Finding> {
+    let mut findings = vec![];
+    let listed = dirs
+        .iter()
+        .map(|d| d.to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if security.realtime == Some(true) {
+        match &security.exclusions {
+            Some(exclusions) => {
+                for dir in dirs {
+                    if !exclusions.iter().any(|e| is_under(dir, e)) {
+                        findings.push(Finding {
+                            docs_url: Some(DEFENDER_EXCLUSIONS_DOCS.to_string()),
+                            ..finding(
+                                "defender_scanning",
+                                Severity::Warning,
+                                Message::new("performance.defender_scanning")
+                                    .with("dir", dir.to_string_lossy()),
+                                Some(dir),
+                            )
+                        });
+                    }
+                }
+            }
+            None => findings.push(Finding {
+                docs_url: Some(DEFENDER_EXCLUSIONS_DOCS.to_string()),
+                ..finding(
+                    "defender_exclusions_unknown",
+                    Severity::Info,
+                    Message::new("performance.defender_exclusions_unknown").with("dirs", &listed),
+                    None,
+                )
+            }),
+        }
+    }
+    for product in &security.products {
+        if !product.to_lowercase().contains("defender") {
+            findings.push(finding(
+                "third_party_antivirus",
+                Severity::Info,
+                Message::new("performance.third_party_antivirus")
+                    .with("product", product)
+                    .with("dirs", &listed),
+                None,
+            ));
+        }
+    }
+    for dir in dirs {
+        let bus = drive_letter(dir).and_then(|d| security.buses.get(&d));
+        if bus.is_some_and(|bus| bus.eq_ignore_ascii_case("USB")) {
+            findings.push(finding(
+                "usb_drive",
+                Severity::Warning,
+                Message::new("performance.usb_drive").with("dir", dir.to_string_lossy()),
+                Some(dir),
+            ));
+        }
+    }
+    findings
+}
+
+/// Whether Windows path `dir` is `exclusion` or inside it, ignoring case
+fn is_under(dir: &Path, exclusion: &str) -> bool {
+    let normalize = |p: &str| p.replace('/', "\\").trim_end_matches('\\').to_lowercase();
+    let dir = normalize(&dir.to_string_lossy());
+    let exclusion = normalize(exclusion);
+    !exclusion.is_empty()
+        && (dir == exclusion
+            || dir
+                .strip_prefix(&exclusion)
+                .is_some_and(|rest| rest.starts_with('\\')))
+}
+
+fn drive_letter(path: &Path) -> Option<char> {
+    let path = path.to_string_lossy();
+    let mut chars = path.chars();
+    let letter = chars.next().filter(|c| c.is_ascii_alphabetic())?;
+    (chars.next() == Some(':')).then_some(letter.to_ascii_uppercase())
+}
+
+/// One existing directory per volume, as a probe of each is enough
+fn probe_dirs(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut volumes: Vec<String> = vec![];
+    let mut probed = vec![];
+    for dir in dirs.iter().filter(|d| d.is_dir()) {
+        let Ok(volume) = disk::volume_of(dir) else {
+            continue;
+        };
+        if !volumes.contains(&volume.id) {
+            volumes.push(volume.id);
+            probed.push(dir.clone());
+        }
+    }
+    probed
+}
+
+/// The median time of a synced 4 KiB write at a random offset in a file in `dir`
+fn probe_write_latency(dir: &Path) -> io::Result<Duration> {
+    let path = dir.join(format!(".gameview-write-probe-{}", std::process::id()));
+    let result = (|| {
+        let mut file = std::fs::File::create(&path)?;
+        file.set_len(PROBE_SPAN)?;
+        let block = [0x5au8; PROBE_BLOCK];
+        let blocks = PROBE_SPAN / PROBE_BLOCK as u64;
+        let mut times = vec![];
+        // A fixed spread of offsets; any that defeats sequential write-back will do
+        let mut offset: u64 = 7;
+        for _ in 0..PROBE_WRITES {
+            offset = (offset * 2_654_435_761 + 1) % blocks;
+            let started = Instant::now();
+            file.seek(SeekFrom::Start(offset * PROBE_BLOCK as u64))?;
+            file.write_all(&block)?;
+            file.sync_data()?;
+            times.push(started.elapsed());
+        }
+        times.sort();
+        Ok(times[times.len() / 2])
+    })();
+    std::fs::remove_file(&path).ok();
+    result
+}
+
+fn latency_finding(dir: &Path, median: Duration) -> Option<Finding> {
+    (median > SLOW_WRITE).then(|| {
+        finding(
+            "slow_random_writes",
+            Severity::Warning,
+            Message::new("performance.slow_random_writes")
+                .with("dir", dir.to_string_lossy())
+                .with("millis", median.as_millis()),
+            Some(dir),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn unexcluded_directories_and_usb_drives_are_flagged() {
+        let report = "realtime=True\n\
+                      exclusions=read\n\
+                      exclusion=D:\\Renders\\\n\
+                      product=Windows Defender\n\
+                      product=Norton Security\n\
+                      bus=E=USB\n";
+        let security = parse_report(report);
+        assert_eq!(security.realtime, Some(true));
+        assert_eq!(security.exclusions, Some(vec!["D:\\Renders\\".to_string()]));
+
+        let dirs = [
+            PathBuf::from("C:\\Users\\ana\\AppData\\Local\\Temp"),
+            PathBuf::from("d:\\renders\\shoot-12"),
+            PathBuf::from("E:\\Captures"),
+        ];
+        let findings = security_findings(&security, &dirs);
+        let codes: Vec<(&str, Option<&str>)> = findings
+            .iter()
+            .map(|f| (f.code.as_str(), f.path.as_deref()))
+            .collect();
+        assert_eq!(
+            codes,
+            [
+                (
+                    "defender_scanning",
+                    Some("C:\\Users\\ana\\AppData\\Local\\Temp")
+                ),
+                ("defender_scanning", Some("E:\\Captures")),
+                ("third_party_antivirus", None),
+                ("usb_drive", Some("E:\\Captures")),
+            ]
+        );
+        assert!(!is_under(Path::new("D:\\Renders2"), "D:\\Renders"));
+
+        // Without an administrator Defender hides its exclusions
+        let hidden = parse_report(
+            "realtime=True\nexclusions=read\nexclusion=N/A: Must be an administrator to view exclusions\n",
+        );
+        assert_eq!(hidden.exclusions, None);
+        let findings = security_findings(&hidden, &dirs);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].code, "defender_exclusions_unknown");
+        assert_eq!(findings[0].severity, Severity::Info);
+    }
+
+    #[test]
+    fn slow_synced_writes_suggest_a_spinning_drive() {
+        let paths = TempPaths::new();
+        let dir = paths.root().join("scratch");
+        std::fs::create_dir_all(&dir).unwrap();
+        probe_write_latency(&dir).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        assert!(latency_finding(&dir, Duration::from_millis(2)).is_none());
+        let slow = latency_finding(&dir, Duration::from_millis(40)).unwrap();
+        assert_eq!(slow.code, "slow_random_writes");
+        assert_eq!(slow.message.params["millis"], "40");
+    }
+}
@@ -0,0 +1,219 @@
+//! Platform Seams
+//!
+//! Small traits that stand between the backend logic and the Tauri runtime, so
+//! the logic can run against in-memory implementations in unit tests. Commands
+//! adapt their `AppHandle` to these traits and delegate.
+
+use std::path::PathBuf;
+use tauri::{Manager, Runtime};
+
+/// Where the app keeps its data and bundled resources
+pub trait PathProvider: Send + Sync {
+    fn app_data_dir(&self) -> Result<PathBuf, String>;
+    fn resource_dir(&self) -> Result<PathBuf, String>;
+}
+
+impl<R: Runtime> PathProvider for tauri::AppHandle<R> {
+    fn app_data_dir(&self) -> Result<PathBuf, String> {
+        crate::app_data::dir(self)
+    }
+
+    fn resource_dir(&self) -> Result<PathBuf, String> {
+        self.path().resource_dir().map_err(|e| e.to_string())
+    }
+}
+
+/// Directories resolved up front, for work that outlives the command that
+/// started it
+#[derive(Debug, Clone)]
+pub struct ResolvedPaths {
+    app_data: Result<PathBuf, String>,
+    resources: Result<PathBuf, String>,
+}
+
+impl ResolvedPaths {
+    pub fn of(paths: &impl PathProvider) -> Self {
+        Self {
+            app_data: paths.app_data_dir(),
+            resources: paths.resource_dir(),
+        }
+    }
+}
+
+impl PathProvider for ResolvedPaths {
+    fn app_data_dir(&self) -> Result<PathBuf, String> {
+        self.app_data.clone()
+    }
+
+    fn resource_dir(&self) -> Result<PathBuf, String> {
+        self.resources.clone()
+    }
+}
+
+#[cfg(test)]
+pub mod testing {
+    //! In-memory and temp-dir implementations of the platform seams.
+
+    use super::PathProvider;
+    use crate::clip_progress::ClipProgress;
+    use crate::runner::{
+        CliWarning, CommandSpec, EventSink, ProcessProgress, ProcessSpawner, Source,
+    };
+    use crate::settings::{AppSettings, Persist, SettingsStore};
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use tokio::io::AsyncWriteExt;
+
+    /// App data and resources in a fresh directory under the system temp dir
+    pub struct TempPaths {
+        root: PathBuf,
+    }
+
+    impl TempPaths {
+        pub fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let root = std::env::temp_dir().join(format!(
+                "gameview-test-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::SeqCst)
+            ));
+            std::fs::create_dir_all(root.join("data")).unwrap();
+            std::fs::create_dir_all(root.join("resources")).unwrap();
+            Self { root }
+        }
+
+        pub fn root(&self) -> &std::path::Path {
+            &self.root
+        }
+    }
+
+    impl Drop for TempPaths {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.root).ok();
+        }
+    }
+
+    impl PathProvider for TempPaths {
+        fn app_data_dir(&self) -> Result<PathBuf, String> {
+            Ok(self.root.join("data"))
+        }
+
+        fn resource_dir(&self) -> Result<PathBuf, String> {
+            Ok(self.root.join("resources"))
+        }
+    }
+
+    /// Settings kept in memory only
+    #[derive(Default)]
+    pub struct MemorySettings {
+        pub settings: Mutex<AppSettings>,
+    }
+
+    impl MemorySettings {
+        pub fn with(settings: AppSettings) -> Self {
+            Self {
+                settings: Mutex::new(settings),
+            }
+        }
+    }
+
+    impl SettingsStore for MemorySettings {
+        fn settings(&self) -> AppSettings {
+            self.settings.lock().unwrap().clone()
+        }
+
+        fn update_settings<T, E: From<String>>(
+            &self,
+            _persist: Persist,
+            update: impl FnOnce(&mut AppSettings) -> Result<T, E>,
+        ) -> Result<T, E> {
+            let mut settings = self.settings.lock().unwrap();
+            let mut updated = settings.clone();
+            let result = update(&mut updated)?;
+            *settings = updated;
+            Ok(result)
+        }
+    }
+
+    /// Collects every event a job reports
+    #[derive(Default)]
+    pub struct RecordingEvents {
+        pub lines: Vec<String>,
+        pub progress: Vec<ProcessProgress>,
+        pub warnings: Vec<CliWarning>,
+        /// The latest per-clip breakdown
+        pub clips: Vec<ClipProgress>,
+    }
+
+    impl EventSink for RecordingEvents {
+        fn line(&mut self, line: &str) {
+            self.lines.push(line.to_string());
+        }
+
+        fn progress(&mut self, progress: &ProcessProgress) {
+            self.progress.push(progress.clone());
+        }
+
+        fn warning(&mut self, warning: &CliWarning) {
+            self.warnings.push(warning.clone());
+        }
+
+        fn clips(&mut self, clips: &[ClipProgress]) {
+            self.clips = clips.to_vec();
+        }
+    }
+
+    /// Records the command it was asked to run and replays scripted stdout instead.
+    /// With `hang` set the output never ends, as if the CLI stopped responding.
+    #[derive(Default)]
+    pub struct ScriptedSpawner {
+        pub stdout: Vec<String>,
+        pub success: bool,
+        pub hang: bool,
+        /// Runs whose arguments include this value fail regardless of `success`
+        pub fail_on: Option<String>,
+        /// Written as output.ply into the run's --output directory
+        pub artifact: Option<Vec<u8>>,
+        pub spawned: Mutex<Vec<(String, Vec<String>)>>,
+        /// The TMPDIR each run was given
+        pub temp_dirs: Mutex<Vec<Option<String>>>,
+    }
+
+    impl ProcessSpawner for ScriptedSpawner {
+        fn spawn(&self, spec: CommandSpec) -> Source {
+            let CommandSpec {
+                program, args, env, ..
+            } = spec;
+            self.spawned.lock().unwrap().push((program, args.clone()));
+            self.temp_dirs
+                .lock()
+                .unwrap()
+                .push(env.get("TMPDIR").cloned());
+            if let Some(artifact) = &self.artifact {
+                let output = args.iter().position(|a| a == "--output");
+                if let Some(dir) = output.and_then(|i| args.get(i + 1)) {
+                    std::fs::write(Path::new(dir).join("output.ply"), artifact).unwrap();
+                }
+            }
+
+            let success = self.success && !self.fail_on.as_ref().is_some_and(|f| args.contains(f));
+            let (stdout, mut writer) = tokio::io::duplex(64 * 1024);
+            let lines = self.stdout.clone();
+            let hang = self.hang;
+            let handle = tokio::spawn(async move {
+                for line in lines {
+                    writer
+                        .write_all(format!("{}\n", line).as_bytes())
+                        .await
+                        .ok();
+                }
+                if hang {
+                    std::future::pending::<()>().await;
+                }
+                success
+            });
+            Source::Simulated { stdout, handle }
+        }
+    }
+}
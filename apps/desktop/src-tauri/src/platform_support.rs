@@ -0,0 +1,335 @@
+//! Platform Support
+//!
+//! On 32-bit Windows, an old macOS or a machine without a graphics API wgpu
+//! can use, the bundled CLI either does not start or fails partway into
+//! training with nothing to say why. The platforms Game View supports are
+//! listed in platform_support/matrix.json; at startup the host is described
+//! (its OS and version, processor and which graphics APIs it has a driver for,
+//! found by looking for their libraries rather than loading them) and checked
+//! against the list. get_backend_health and get_setup_status report what does
+//! not match, and process_videos refuses to start with UnsupportedPlatform.
+//! Supporting another platform means changing the list, not this module.
+
+use crate::cli_location;
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::written_by::compare_versions;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+
+const MATRIX: &str = include_str!("platform_support/matrix.json");
+
+/// A graphics API the trainer can run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpuApi {
+    Vulkan,
+    Metal,
+    Dx12,
+}
+
+impl GpuApi {
+    fn name(self) -> &'static str {
+        match self {
+            GpuApi::Vulkan => "Vulkan",
+            GpuApi::Metal => "Metal",
+            GpuApi::Dx12 => "Direct3D 12",
+        }
+    }
+}
+
+/// What the support matrix is checked against
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlatformDescriptor {
+    /// As in std::env::consts::OS
+    pub os: String,
+    /// None when it could not be read
+    pub os_version: Option<String>,
+    /// The machine's processor, not this possibly emulated app's
+    pub arch: String,
+    /// Graphics APIs with a driver installed
+    pub gpu_apis: Vec<GpuApi>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SupportMatrix {
+    platforms: Vec<SupportedPlatform>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SupportedPlatform {
+    os: String,
+    /// The oldest supported release, as the user knows it
+    name: String,
+    min_version: Option<String>,
+    archs: Vec<String>,
+    /// Any one of them will do
+    gpu_apis: Vec<GpuApi>,
+}
+
+/// One way the host falls outside the support matrix
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlatformFinding {
+    pub code: String,
+    pub message: Message,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformSupport {
+    pub platform: PlatformDescriptor,
+    /// No finding stands in the way of processing
+    pub supported: bool,
+    pub findings: Vec<PlatformFinding>,
+}
+
+impl PlatformSupport {
+    /// The error process_videos fails with, if the host is unsupported
+    pub fn check(&self) -> Result<(), AppError> {
+        match self.findings.first() {
+            Some(finding) => Err(AppError::UnsupportedPlatform(finding.message.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Describe the host and check it against the support matrix, for setup to
+/// keep in the app's state
+pub fn detect() -> PlatformSupport {
+    evaluate(&matrix(), describe_host())
+}
+
+/// The host's support as found at startup
+pub fn of(app: &AppHandle) -> Option<PlatformSupport> {
+    app.try_state::<PlatformSupport>()
+        .map(|support| support.inner().clone())
+}
+
+/// Refuse processing on a host outside the support matrix
+pub fn check(app: &AppHandle) -> Result<(), AppError> {
+    of(app).map_or(Ok(()), |support| support.check())
+}
+
+fn matrix() -> SupportMatrix {
+    serde_json::from_str(MATRIX).expect("the support matrix is valid JSON")
+}
+
+fn evaluate(matrix: &SupportMatrix, platform: PlatformDescriptor) -> PlatformSupport {
+    let finding = |code: &str, message: Message| PlatformFinding {
+        code: code.to_string(),
+        message,
+    };
+    let mut findings = vec![];
+    match matrix.platforms.iter().find(|p| p.os == platform.os) {
+        None => findings.push(finding(
+            "unknown_os",
+            Message::new("platform.unknown_os").with("os", &platform.os),
+        )),
+        Some(supported) => {
+            // A version that could not be read is given the benefit of the doubt
+            if let Some((min, version)) = supported
+                .min_version
+                .as_deref()
+                .zip(platform.os_version.as_deref())
+            {
+                if compare_versions(version, min) == Ordering::Less {
+                    findings.push(finding(
+                        "os_too_old",
+                        Message::new("platform.os_too_old")
+                            .with("required", &supported.name)
+                            .with("version", version),
+                    ));
+                }
+            }
+            if !supported.archs.contains(&platform.arch) {
+                findings.push(finding(
+                    "unsupported_arch",
+                    Message::new("platform.unsupported_arch")
+                        .with("arch", &platform.arch)
+                        .with("archs", supported.archs.join(", ")),
+                ));
+            }
+            if !supported
+                .gpu_apis
+                .iter()
+                .any(|api| platform.gpu_apis.contains(api))
+            {
+                let apis: Vec<&str> = supported.gpu_apis.iter().map(|api| api.name()).collect();
+                findings.push(finding(
+                    "no_gpu_api",
+                    Message::new("platform.no_gpu_api").with("apis", apis.join(" or ")),
+                ));
+            }
+        }
+    }
+    PlatformSupport {
+        platform,
+        supported: findings.is_empty(),
+        findings,
+    }
+}
+
+fn describe_host() -> PlatformDescriptor {
+    // A 32-bit build may be running on a 64-bit machine, but it is the build
+    // that has to be supported
+    let arch = if cfg!(target_pointer_width = "32") {
+        std::env::consts::ARCH.to_string()
+    } else {
+        cli_location::host_arch().name().to_string()
+    };
+    PlatformDescriptor {
+        os: std::env::consts::OS.to_string(),
+        os_version: os_version(),
+        arch,
+        gpu_apis: gpu_apis(),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn os_version() -> Option<String> {
+    let plist = std::fs::read_to_string("/System/Library/CoreServices/SystemVersion.plist").ok()?;
+    let after_key = plist.split("<key>ProductVersion</key>").nth(1)?;
+    let value = after_key
+        .split("<string>")
+        .nth(1)?
+        .split("</string>")
+        .next()?;
+    Some(value.trim().to_string())
+}
+
+#[cfg(windows)]
+fn os_version() -> Option<String> {
+    use windows_sys::Wdk::System::SystemServices::RtlGetVersion;
+    use windows_sys::Win32::System::SystemInformation::OSVERSIONINFOW;
+
+    // GetVersionEx reports Windows 8 to apps not manifested for later versions
+    // SAFETY: OSVERSIONINFOW is plain data, for which all zeroes is valid
+    let mut info: OSVERSIONINFOW = unsafe { std::mem::zeroed() };
+    info.dwOSVersionInfoSize = std::mem::size_of::<OSVERSIONINFOW>() as u32;
+    // SAFETY: info is an OSVERSIONINFOW whose size is set as RtlGetVersion requires
+    let status = unsafe { RtlGetVersion(&mut info) };
+    (status == 0).then(|| {
+        format!(
+            "{}.{}.{}",
+            info.dwMajorVersion, info.dwMinorVersion, info.dwBuildNumber
+        )
+    })
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn os_version() -> Option<String> {
+    let release = std::fs::read_to_string("/proc/sys/kernel/osrelease").ok()?;
+    Some(release.trim().to_string())
+}
+
+/// The graphics APIs whose runtime libraries are installed
+fn gpu_apis() -> Vec<GpuApi> {
+    let mut apis = vec![];
+    if cfg!(target_os = "macos") {
+        if Path::new("/System/Library/Frameworks/Metal.framework").exists() {
+            apis.push(GpuApi::Metal);
+        }
+    } else if cfg!(windows) {
+        let system32 =
+            Path::new(&std::env::var_os("SystemRoot").unwrap_or_else(|| "C:\\Windows".into()))
+                .join("System32");
+        if system32.join("d3d12.dll").exists() {
+            apis.push(GpuApi::Dx12);
+        }
+        if system32.join("vulkan-1.dll").exists() {
+            apis.push(GpuApi::Vulkan);
+        }
+    } else if LIBRARY_DIRS
+        .iter()
+        .any(|dir| Path::new(dir).join("libvulkan.so.1").exists())
+    {
+        apis.push(GpuApi::Vulkan);
+    }
+    apis
+}
+
+/// Where distributions install the Vulkan loader
+const LIBRARY_DIRS: &[&str] = &[
+    "/usr/lib",
+    "/usr/lib64",
+    "/usr/lib/x86_64-linux-gnu",
+    "/usr/lib/aarch64-linux-gnu",
+    "/lib/x86_64-linux-gnu",
+    "/usr/local/lib",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(os: &str, version: Option<&str>, arch: &str, apis: &[GpuApi]) -> PlatformDescriptor {
+        PlatformDescriptor {
+            os: os.to_string(),
+            os_version: version.map(str::to_string),
+            arch: arch.to_string(),
+            gpu_apis: apis.to_vec(),
+        }
+    }
+
+    fn codes(support: &PlatformSupport) -> Vec<&str> {
+        support.findings.iter().map(|f| f.code.as_str()).collect()
+    }
+
+    #[test]
+    fn supported_platforms_have_no_findings() {
+        let matrix = matrix();
+        for platform in [
+            host("windows", Some("10.0.19045"), "x86_64", &[GpuApi::Dx12]),
+            host("windows", Some("10.0.22631"), "aarch64", &[GpuApi::Vulkan]),
+            host("macos", Some("14.5"), "aarch64", &[GpuApi::Metal]),
+            host("macos", None, "x86_64", &[GpuApi::Metal]),
+            host(
+                "linux",
+                Some("6.8.0-45-generic"),
+                "x86_64",
+                &[GpuApi::Vulkan],
+            ),
+        ] {
+            let support = evaluate(&matrix, platform.clone());
+            assert!(support.supported, "{:?}: {:?}", platform, support.findings);
+            assert!(support.check().is_ok());
+        }
+    }
+
+    #[test]
+    fn each_way_of_falling_outside_the_matrix_is_reported() {
+        let matrix = matrix();
+
+        let old_32_bit = evaluate(&matrix, host("windows", Some("6.1.7601"), "x86", &[]));
+        assert!(!old_32_bit.supported);
+        assert_eq!(
+            codes(&old_32_bit),
+            ["os_too_old", "unsupported_arch", "no_gpu_api"]
+        );
+        assert_eq!(
+            old_32_bit.findings[2].message.params["apis"],
+            "Direct3D 12 or Vulkan"
+        );
+
+        let old_mac = evaluate(
+            &matrix,
+            host("macos", Some("10.15.7"), "x86_64", &[GpuApi::Metal]),
+        );
+        assert_eq!(codes(&old_mac), ["os_too_old"]);
+        assert_eq!(old_mac.findings[0].message.params["required"], "macOS 12");
+        assert!(matches!(
+            old_mac.check(),
+            Err(AppError::UnsupportedPlatform(message)) if message.key == "platform.os_too_old"
+        ));
+
+        let arm_linux = evaluate(&matrix, host("linux", None, "aarch64", &[GpuApi::Vulkan]));
+        assert_eq!(codes(&arm_linux), ["unsupported_arch"]);
+
+        let bsd = evaluate(
+            &matrix,
+            host("freebsd", Some("14.1"), "x86_64", &[GpuApi::Vulkan]),
+        );
+        assert_eq!(codes(&bsd), ["unknown_os"]);
+    }
+}
@@ -0,0 +1,221 @@
+//! Production Preferences
+//!
+//! Remembers the last-used processing arguments per production in
+//! app_data/productions.json so the processing form can be pre-filled.
+
+use crate::commands::ProcessArgs;
+use crate::error::AppError;
+use crate::fsutil;
+use crate::job_log::unix_timestamp;
+use crate::platform::PathProvider;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedDefaults {
+    pub args: ProcessArgs,
+    pub saved_at: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductionDefaults {
+    pub args: ProcessArgs,
+    pub saved_at: u64,
+    /// Saved clip paths that no longer exist on disk
+    pub missing_clips: Vec<String>,
+}
+
+fn preferences_path(paths: &impl PathProvider) -> Result<PathBuf, String> {
+    Ok(paths.app_data_dir()?.join("productions.json"))
+}
+
+fn load(paths: &impl PathProvider) -> Result<HashMap<String, SavedDefaults>, String> {
+    let path = preferences_path(paths)?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+fn save(
+    paths: &impl PathProvider,
+    defaults: &HashMap<String, SavedDefaults>,
+) -> Result<(), String> {
+    fsutil::write_json_atomic(&preferences_path(paths)?, defaults)
+}
+
+/// Remember the arguments a job was started with for its production
+pub fn remember(
+    paths: &impl PathProvider,
+    production_id: &str,
+    args: &ProcessArgs,
+) -> Result<(), String> {
+    let temp_dir = std::env::temp_dir();
+    let is_temp = |path: &str| Path::new(path).starts_with(&temp_dir);
+
+    // Temp paths won't exist next time, so never persist them
+    let mut args = args.clone();
+    args.videos.retain(|v| !is_temp(v));
+    args.clips.retain(|c| !is_temp(&c.path));
+    if args.masks.as_deref().is_some_and(is_temp) {
+        args.masks = None;
+    }
+
+    let mut defaults = load(paths)?;
+    defaults.insert(
+        production_id.to_string(),
+        SavedDefaults {
+            args,
+            saved_at: unix_timestamp(),
+        },
+    );
+    save(paths, &defaults)
+}
+
+/// Get the last-used processing arguments for a production
+#[tauri::command]
+pub async fn get_production_defaults(
+    app: AppHandle,
+    production_id: String,
+) -> Result<Option<ProductionDefaults>, AppError> {
+    Ok(defaults(&app, &production_id)?)
+}
+
+/// Forget the saved processing arguments for a production
+#[tauri::command]
+pub async fn clear_production_defaults(
+    app: AppHandle,
+    production_id: String,
+) -> Result<(), AppError> {
+    Ok(clear(&app, &production_id)?)
+}
+
+fn defaults(
+    paths: &impl PathProvider,
+    production_id: &str,
+) -> Result<Option<ProductionDefaults>, String> {
+    let Some(saved) = load(paths)?.remove(production_id) else {
+        return Ok(None);
+    };
+
+    let missing_clips = saved
+        .args
+        .videos
+        .iter()
+        .filter(|v| !Path::new(v).is_file())
+        .cloned()
+        .collect();
+
+    Ok(Some(ProductionDefaults {
+        args: saved.args,
+        saved_at: saved.saved_at,
+        missing_clips,
+    }))
+}
+
+fn clear(paths: &impl PathProvider, production_id: &str) -> Result<(), String> {
+    let mut defaults = load(paths)?;
+    if defaults.remove(production_id).is_some() {
+        save(paths, &defaults)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ClipOptions;
+    use crate::platform::testing::TempPaths;
+    use serde_json::json;
+
+    fn request(videos: &[String], preset: &str) -> ProcessArgs {
+        serde_json::from_value(json!({
+            "videos": videos,
+            "output_dir": "/out/harbour",
+            "preset": preset,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn defaults_are_kept_per_production_and_list_missing_clips() {
+        let paths = TempPaths::new();
+        // Outside the temp dir, which is never remembered
+        let clip = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml").to_string();
+        let gone = "/clips/gone.mp4".to_string();
+
+        assert!(defaults(&paths, "p1").unwrap().is_none());
+        remember(
+            &paths,
+            "p1",
+            &request(&[clip.clone(), gone.clone()], "fast"),
+        )
+        .unwrap();
+        remember(&paths, "p2", &request(&[clip.clone()], "balanced")).unwrap();
+
+        let saved = defaults(&paths, "p1").unwrap().unwrap();
+        assert_eq!(saved.args.preset, "fast");
+        assert_eq!(saved.args.videos, [clip.clone(), gone.clone()]);
+        assert_eq!(saved.missing_clips, [gone]);
+        assert!(defaults(&paths, "p2")
+            .unwrap()
+            .unwrap()
+            .missing_clips
+            .is_empty());
+
+        // Remembering again replaces only that production's defaults
+        remember(&paths, "p1", &request(&[clip.clone()], "quality")).unwrap();
+        assert_eq!(
+            defaults(&paths, "p1").unwrap().unwrap().args.preset,
+            "quality"
+        );
+        assert_eq!(
+            defaults(&paths, "p2").unwrap().unwrap().args.preset,
+            "balanced"
+        );
+    }
+
+    #[test]
+    fn temp_paths_are_not_remembered() {
+        let paths = TempPaths::new();
+        let temp = std::env::temp_dir()
+            .join("upload.mp4")
+            .to_string_lossy()
+            .to_string();
+        let mut args = request(&["/clips/harbour.mp4".to_string(), temp.clone()], "fast");
+        args.clips = vec![ClipOptions {
+            path: temp,
+            ..Default::default()
+        }];
+        args.masks = Some(
+            std::env::temp_dir()
+                .join("masks")
+                .to_string_lossy()
+                .to_string(),
+        );
+
+        remember(&paths, "p1", &args).unwrap();
+        let saved = defaults(&paths, "p1").unwrap().unwrap().args;
+        assert_eq!(saved.videos, ["/clips/harbour.mp4"]);
+        assert!(saved.clips.is_empty());
+        assert_eq!(saved.masks, None);
+    }
+
+    #[test]
+    fn clearing_forgets_one_production() {
+        let paths = TempPaths::new();
+        let args = request(&["/clips/harbour.mp4".to_string()], "fast");
+        remember(&paths, "p1", &args).unwrap();
+        remember(&paths, "p2", &args).unwrap();
+
+        clear(&paths, "p1").unwrap();
+        assert!(defaults(&paths, "p1").unwrap().is_none());
+        assert!(defaults(&paths, "p2").unwrap().is_some());
+        // Clearing what is not there is fine
+        clear(&paths, "p3").unwrap();
+    }
+}
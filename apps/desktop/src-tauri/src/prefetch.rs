@@ -0,0 +1,372 @@
+//! Media Prefetch
+//!
+//! Probes and thumbnails clips in the background through a small worker pool, so
+//! dropping many clips at once does not start an ffprobe and an ffmpeg per clip.
+//! While a processing job runs the pool shrinks to one worker to leave the disk
+//! to COLMAP. Results go to the shared cache, per version of each file, so a
+//! changed clip is probed again and an unchanged one is not, across restarts.
+
+use crate::cache::{self, Category};
+use crate::error::AppError;
+use crate::extraction;
+use crate::jobs;
+use crate::media::{self, VideoMetadata};
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::scheduler;
+use crate::settings::SettingsStore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Emitter, State};
+
+/// Width of cached thumbnails
+const THUMBNAIL_WIDTH: u32 = 320;
+
+/// Upper bound on the prefetch_concurrency setting
+pub const MAX_CONCURRENCY: u32 = 8;
+
+static POOL: Mutex<Pool> = Mutex::new(Pool::new());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefetchResult {
+    pub path: String,
+    pub metadata: Option<VideoMetadata>,
+    pub thumbnail_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The size and modification time a cached result was computed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    len: u64,
+    modified_millis: u128,
+}
+
+/// Paths waiting for a worker, and how many workers are running
+struct Pool {
+    queue: VecDeque<String>,
+    running: usize,
+    /// Bumped on cancel so results already in flight are not reported
+    generation: u64,
+}
+
+impl Pool {
+    const fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            running: 0,
+            generation: 0,
+        }
+    }
+
+    fn push(&mut self, path: String) {
+        if !self.queue.contains(&path) {
+            self.queue.push_back(path);
+        }
+    }
+
+    /// Account for and return the number of workers to start so `allowed` run
+    fn claim_workers(&mut self, allowed: usize) -> usize {
+        let start = allowed.saturating_sub(self.running).min(self.queue.len());
+        self.running += start;
+        start
+    }
+
+    /// The next path for a running worker, or None when it should stop because
+    /// the queue is empty or more workers run than are allowed
+    fn next(&mut self, allowed: usize) -> Option<(String, u64)> {
+        let path = if self.running > allowed {
+            None
+        } else {
+            self.queue.pop_front()
+        };
+        if path.is_none() {
+            self.running -= 1;
+        }
+        path.map(|path| (path, self.generation))
+    }
+
+    fn cancel(&mut self) {
+        self.queue.clear();
+        self.generation += 1;
+    }
+}
+
+/// Probe and thumbnail clips in the background. Results already cached are
+/// returned at once; the rest arrive as "media-prefetched" events.
+#[tauri::command]
+pub async fn queue_media_prefetch(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    paths: Vec<String>,
+) -> Result<Vec<PrefetchResult>, AppError> {
+    for path in &paths {
+        policy.check_existing(path)?;
+    }
+
+    let mut cached = vec![];
+    {
+        let mut pool = POOL.lock().unwrap();
+        for path in paths {
+            match cached_result(&app, &path) {
+                Some(result) => cached.push(result),
+                None => pool.push(path),
+            }
+        }
+    }
+    start_workers(&app);
+    Ok(cached)
+}
+
+/// Drop queued prefetches; ones already running finish but are not reported
+#[tauri::command]
+pub async fn cancel_media_prefetch() -> Result<(), AppError> {
+    POOL.lock().unwrap().cancel();
+    Ok(())
+}
+
+/// Cached metadata for a file, if it has not changed since it was probed
+pub fn cached_metadata(paths: &impl PathProvider, path: &str) -> Option<VideoMetadata> {
+    cached_result(paths, path).and_then(|result| result.metadata)
+}
+
+/// Workers allowed right now: the configured concurrency, or one while a job runs
+fn allowed_workers(store: &impl SettingsStore) -> usize {
+    if jobs::any_active() {
+        1
+    } else {
+        store
+            .settings()
+            .prefetch_concurrency
+            .clamp(1, MAX_CONCURRENCY) as usize
+    }
+}
+
+fn start_workers(app: &AppHandle) {
+    let start = POOL.lock().unwrap().claim_workers(allowed_workers(app));
+    for _ in 0..start {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { work(app).await });
+    }
+}
+
+async fn work(app: AppHandle) {
+    loop {
+        let next = POOL.lock().unwrap().next(allowed_workers(&app));
+        let Some((path, generation)) = next else {
+            return;
+        };
+
+        let result = match cached_result(&app, &path) {
+            Some(result) => result,
+            None => {
+                let _worker = scheduler::global()
+                    .acquire(scheduler::Pool::Background)
+                    .await;
+                prefetch(&app, &path).await
+            }
+        };
+        if POOL.lock().unwrap().generation == generation {
+            app.emit("media-prefetched", &result).ok();
+        }
+        // Grow back once a processing job that shrank the pool has finished
+        start_workers(&app);
+    }
+}
+
+async fn prefetch(paths: &impl PathProvider, path: &str) -> PrefetchResult {
+    let mut result = PrefetchResult {
+        path: path.to_string(),
+        metadata: None,
+        thumbnail_path: None,
+        error: None,
+    };
+    // Taken before probing so a file that changes meanwhile is probed again next time
+    let Some(fingerprint) = fingerprint(path) else {
+        result.error = Some("File not found".to_string());
+        return result;
+    };
+
+    let metadata = match media::probe(path).await {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+
+    let cache = match cache::app_cache(paths) {
+        Ok(cache) => cache,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            result.metadata = Some(metadata);
+            return result;
+        }
+    };
+    let name = entry_name(path, fingerprint);
+    let thumbnail = format!("{}.jpg", name);
+    match cache.staging(Category::Thumbnails, &thumbnail) {
+        Ok(staging) => {
+            // A frame a second in skips the black frames many cameras start with
+            let at_secs = (metadata.duration_secs / 2.0).min(1.0);
+            let extracted =
+                extraction::extract_thumbnail(path, at_secs, THUMBNAIL_WIDTH, &staging).await;
+            match extracted.and_then(|()| cache.insert(Category::Thumbnails, &thumbnail, &staging))
+            {
+                Ok(thumbnail) => {
+                    result.thumbnail_path = Some(thumbnail.to_string_lossy().to_string())
+                }
+                Err(e) => {
+                    std::fs::remove_file(&staging).ok();
+                    result.error = Some(e);
+                }
+            }
+        }
+        Err(e) => result.error = Some(e),
+    }
+    result.metadata = Some(metadata);
+
+    // Metadata is kept without the thumbnail's path, which the lookup checks for itself
+    let cached = PrefetchResult {
+        thumbnail_path: None,
+        ..result.clone()
+    };
+    if let Ok(json) = serde_json::to_vec(&cached) {
+        cache
+            .write(Category::Metadata, &format!("{}.json", name), &json)
+            .ok();
+    }
+    result
+}
+
+fn cached_result(paths: &impl PathProvider, path: &str) -> Option<PrefetchResult> {
+    let name = entry_name(path, fingerprint(path)?);
+    let cache = cache::app_cache(paths).ok()?;
+    let json = cache.read(Category::Metadata, &format!("{}.json", name))?;
+    let mut result: PrefetchResult = serde_json::from_slice(&json).ok()?;
+    result.path = path.to_string();
+    if result.error.is_none() {
+        // A thumbnail evicted before its metadata is made again
+        let thumbnail = cache.get(Category::Thumbnails, &format!("{}.jpg", name))?;
+        result.thumbnail_path = Some(thumbnail.to_string_lossy().to_string());
+    }
+    Some(result)
+}
+
+fn fingerprint(path: &str) -> Option<Fingerprint> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified_millis = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_millis();
+    Some(Fingerprint {
+        len: metadata.len(),
+        modified_millis,
+    })
+}
+
+// One entry per version of a clip, so a changed clip never shows a stale thumbnail
+fn entry_name(path: &str, fingerprint: Fingerprint) -> String {
+    let key = format!(
+        "{}\0{}\0{}",
+        path, fingerprint.len, fingerprint.modified_millis
+    );
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn pool(paths: &[&str]) -> Pool {
+        let mut pool = Pool::new();
+        for path in paths {
+            pool.push(path.to_string());
+        }
+        pool
+    }
+
+    #[test]
+    fn pool_starts_at_most_allowed_workers() {
+        let mut pool = pool(&["a", "b", "a", "c", "d"]);
+        assert_eq!(pool.queue.len(), 4);
+        assert_eq!(pool.claim_workers(3), 3);
+        assert_eq!(pool.claim_workers(3), 0);
+
+        assert_eq!(pool.next(3).map(|(p, _)| p).as_deref(), Some("a"));
+        assert_eq!(pool.next(3).map(|(p, _)| p).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn pool_shrinks_to_allowed_and_stops_when_empty() {
+        let mut pool = pool(&["a", "b", "c", "d"]);
+        pool.claim_workers(3);
+
+        // A job started: two of the three workers stop before taking more work
+        assert!(pool.next(1).is_none());
+        assert!(pool.next(1).is_none());
+        assert_eq!(pool.running, 1);
+        assert_eq!(pool.next(1).map(|(p, _)| p).as_deref(), Some("a"));
+
+        // The job finished: more workers may start again
+        assert_eq!(pool.claim_workers(3), 2);
+        assert_eq!(pool.running, 3);
+        for _ in 0..3 {
+            assert!(pool.next(3).is_some());
+        }
+        for _ in 0..3 {
+            assert!(pool.next(3).is_none());
+        }
+        assert_eq!(pool.running, 0);
+    }
+
+    #[test]
+    fn cancel_clears_queue_and_invalidates_in_flight_work() {
+        let mut pool = pool(&["a", "b"]);
+        pool.claim_workers(1);
+        let (_, generation) = pool.next(1).unwrap();
+
+        pool.cancel();
+        assert_ne!(pool.generation, generation);
+        assert!(pool.next(1).is_none());
+        assert_eq!(pool.running, 0);
+    }
+
+    #[test]
+    fn cache_is_dropped_when_the_file_changes() {
+        let paths = TempPaths::new();
+        let clip = paths.root().join("clip.mp4");
+        std::fs::write(&clip, b"first take").unwrap();
+        let clip = clip.to_string_lossy().to_string();
+
+        let result = PrefetchResult {
+            path: clip.clone(),
+            metadata: None,
+            thumbnail_path: None,
+            error: Some("no video stream".to_string()),
+        };
+        let name = entry_name(&clip, fingerprint(&clip).unwrap());
+        cache::app_cache(&paths)
+            .unwrap()
+            .write(
+                Category::Metadata,
+                &format!("{}.json", name),
+                &serde_json::to_vec(&result).unwrap(),
+            )
+            .unwrap();
+        assert!(cached_result(&paths, &clip).is_some());
+
+        std::fs::write(&clip, b"second, longer take").unwrap();
+        assert!(cached_result(&paths, &clip).is_none());
+    }
+}
@@ -0,0 +1,423 @@
+//! Preset Descriptions
+//!
+//! Explains what each preset in the picker trades off: how long it takes
+//! relative to balanced, the quality to expect, the footage it suits, the
+//! VRAM it needs and the training parameters behind it. Built-in presets come
+//! from a table per gvcore-cli release, as a release may retune them; presets
+//! the CLI reports beyond those are described from the training options saved
+//! for them, measured against the built-in one of the same quality. Saved
+//! options change a built-in preset's description the same way. Where this
+//! machine has finished jobs with a preset, their average speed is included.
+
+use crate::capabilities;
+use crate::error::AppError;
+use crate::gpu;
+use crate::history::{self, JobRecord, JobStatus};
+use crate::messages::Message;
+use crate::platform::PathProvider;
+use crate::prefetch;
+use crate::settings::{AppSettings, SettingsStore};
+use crate::training::TrainingOptions;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityTier {
+    Draft,
+    Standard,
+    High,
+    Maximum,
+}
+
+/// Footage length a preset is best suited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FootageRange {
+    pub min_secs: u32,
+    /// None when longer footage is fine
+    pub max_secs: Option<u32>,
+}
+
+/// How long the preset's jobs took on this machine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalAverage {
+    pub jobs: usize,
+    pub minutes_per_footage_minute: f64,
+    pub message: Message,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PresetDescription {
+    pub name: String,
+    /// Described from the table rather than synthesized from saved options
+    pub builtin: bool,
+    pub summary: Message,
+    /// Expected duration, balanced being 1
+    pub relative_duration: f64,
+    pub quality: QualityTier,
+    pub footage: FootageRange,
+    pub min_vram_gb: u32,
+    /// The training parameters jobs start from
+    pub parameters: TrainingOptions,
+    /// Parameters that come from the options saved for the preset
+    pub overridden: Vec<String>,
+    pub local: Option<LocalAverage>,
+}
+
+struct Builtin {
+    name: &'static str,
+    relative_duration: f64,
+    quality: QualityTier,
+    footage: FootageRange,
+    min_vram_gb: u32,
+    parameters: TrainingOptions,
+}
+
+const fn builtin(
+    name: &'static str,
+    relative_duration: f64,
+    quality: QualityTier,
+    footage: (u32, Option<u32>),
+    min_vram_gb: u32,
+    (iterations, sh_degree, densify_interval, densify_grad_threshold): (u32, u32, u32, f64),
+) -> Builtin {
+    Builtin {
+        name,
+        relative_duration,
+        quality,
+        footage: FootageRange {
+            min_secs: footage.0,
+            max_secs: footage.1,
+        },
+        min_vram_gb,
+        parameters: TrainingOptions {
+            iterations: Some(iterations),
+            sh_degree: Some(sh_degree),
+            densify_interval: Some(densify_interval),
+            densify_grad_threshold: Some(densify_grad_threshold),
+            opacity_reset_interval: Some(3000),
+        },
+    }
+}
+
+const PRESETS_V0: [Builtin; 4] = [
+    builtin(
+        "fast",
+        0.35,
+        QualityTier::Draft,
+        (0, Some(60)),
+        4,
+        (7_000, 0, 200, 0.0004),
+    ),
+    builtin(
+        "balanced",
+        1.0,
+        QualityTier::Standard,
+        (30, Some(300)),
+        6,
+        (15_000, 2, 200, 0.0002),
+    ),
+    builtin(
+        "high",
+        2.2,
+        QualityTier::High,
+        (60, Some(600)),
+        8,
+        (30_000, 3, 100, 0.0002),
+    ),
+    builtin(
+        "maximum",
+        4.5,
+        QualityTier::Maximum,
+        (120, None),
+        12,
+        (60_000, 3, 100, 0.0001),
+    ),
+];
+
+/// The built-in presets by the first CLI release they apply to, oldest first.
+/// A release that retunes its presets gets a table of its own.
+const TABLES: [(&str, &[Builtin]); 1] = [("0.0", &PRESETS_V0)];
+
+/// The table for a CLI version; an unknown version gets the newest
+fn table_for<'a>(version: Option<&str>, tables: &[(&str, &'a [Builtin])]) -> &'a [Builtin] {
+    let release = |v: &str| -> Vec<u32> {
+        v.trim_start_matches('v')
+            .split('.')
+            .map(|p| p.parse().unwrap_or(0))
+            .collect()
+    };
+    let newest = tables.last().map(|(_, t)| *t).unwrap_or(&[]);
+    let Some(version) = version.map(release) else {
+        return newest;
+    };
+    tables
+        .iter()
+        .rev()
+        .find(|(first, _)| release(first) <= version)
+        .map_or(newest, |(_, t)| *t)
+}
+
+/// The VRAM `preset` needs with a CLI of `version`, in MiB: `saved_gb` from
+/// the preset_min_vram_gb setting when there is one, or else the table's; one
+/// the tables lack needs what the default preset does
+pub fn min_vram_mb(preset: &str, version: Option<&str>, saved_gb: Option<u32>) -> u64 {
+    if let Some(gb) = saved_gb {
+        return u64::from(gb) * 1024;
+    }
+    table_for(version, &TABLES)
+        .iter()
+        .find(|b| b.name == preset)
+        .map_or(gpu::MIN_VRAM_MB, |b| u64::from(b.min_vram_gb) * 1024)
+}
+
+/// The training parameters of the built-in `preset` with a CLI of `version`
+pub fn builtin_parameters(preset: &str, version: Option<&str>) -> Option<TrainingOptions> {
+    table_for(version, &TABLES)
+        .iter()
+        .find(|b| b.name == preset)
+        .map(|b| b.parameters.clone())
+}
+
+/// Describe `presets` for a CLI of `version`, given the seconds of footage
+/// in a video when they are known
+pub fn describe(
+    presets: &[String],
+    version: Option<&str>,
+    settings: &AppSettings,
+    history: &[JobRecord],
+    footage_secs: impl Fn(&str) -> Option<f64>,
+) -> Vec<PresetDescription> {
+    let table = table_for(version, &TABLES);
+    presets
+        .iter()
+        .map(|name| {
+            let saved = settings.preset_training.get(name);
+            let mut description = match table.iter().find(|b| b.name == name) {
+                Some(base) => from_builtin(name, base, saved),
+                None => synthesize(name, table, saved),
+            };
+            if let Some(gb) = settings.preset_min_vram_gb.get(name) {
+                description.min_vram_gb = *gb;
+            }
+            description.local = local_average(name, history, &footage_secs);
+            description
+        })
+        .collect()
+}
+
+fn from_builtin(name: &str, base: &Builtin, saved: Option<&TrainingOptions>) -> PresetDescription {
+    let saved = saved.cloned().unwrap_or_default();
+    let parameters = saved.or(&base.parameters);
+    PresetDescription {
+        name: name.to_string(),
+        builtin: true,
+        summary: Message::new(&format!("preset.{}", name)),
+        relative_duration: scaled_duration(base, &parameters),
+        quality: base.quality,
+        footage: base.footage,
+        min_vram_gb: base.min_vram_gb,
+        overridden: saved.fields_set(),
+        parameters,
+        local: None,
+    }
+}
+
+/// A preset the table lacks, measured against the built-in of its quality
+fn synthesize(name: &str, table: &[Builtin], saved: Option<&TrainingOptions>) -> PresetDescription {
+    let saved = saved.cloned().unwrap_or_default();
+    let quality = match saved.iterations {
+        Some(i) if i < 10_000 => QualityTier::Draft,
+        Some(i) if i < 25_000 => QualityTier::Standard,
+        Some(i) if i < 50_000 => QualityTier::High,
+        Some(_) => QualityTier::Maximum,
+        // Without saved options the CLI's own values are unknown
+        None => QualityTier::Standard,
+    };
+    let Some(base) = table.iter().find(|b| b.quality == quality) else {
+        return PresetDescription {
+            name: name.to_string(),
+            builtin: false,
+            summary: Message::new("preset.custom").with("preset", name),
+            relative_duration: 1.0,
+            quality,
+            footage: FootageRange {
+                min_secs: 0,
+                max_secs: None,
+            },
+            min_vram_gb: 0,
+            overridden: saved.fields_set(),
+            parameters: saved,
+            local: None,
+        };
+    };
+    PresetDescription {
+        builtin: false,
+        summary: Message::new("preset.custom").with("preset", name),
+        ..from_builtin(name, base, Some(&saved))
+    }
+}
+
+/// The base preset's duration, scaled by how much longer the parameters train
+fn scaled_duration(base: &Builtin, parameters: &TrainingOptions) -> f64 {
+    match (parameters.iterations, base.parameters.iterations) {
+        (Some(iterations), Some(base_iterations)) => {
+            base.relative_duration * iterations as f64 / base_iterations as f64
+        }
+        _ => base.relative_duration,
+    }
+}
+
+/// Minutes per minute of footage over the preset's completed jobs, counting
+/// only those whose every clip's length is known
+fn local_average(
+    name: &str,
+    history: &[JobRecord],
+    footage_secs: &impl Fn(&str) -> Option<f64>,
+) -> Option<LocalAverage> {
+    let mut jobs = 0;
+    let mut job_secs = 0.0;
+    let mut total_footage = 0.0;
+    for record in history {
+        if record.preset != name
+            || record.status != JobStatus::Completed
+            || record.preview
+            || record.finished_at < record.started_at
+        {
+            continue;
+        }
+        let footage: Option<f64> = record.videos.iter().map(|v| footage_secs(v)).sum();
+        let Some(footage) = footage.filter(|f| *f > 0.0) else {
+            continue;
+        };
+        jobs += 1;
+        job_secs += (record.finished_at - record.started_at) as f64;
+        total_footage += footage;
+    }
+    if jobs == 0 {
+        return None;
+    }
+    let ratio = job_secs / total_footage;
+    Some(LocalAverage {
+        jobs,
+        minutes_per_footage_minute: ratio,
+        message: Message::new("preset.local_average")
+            .with("preset", name)
+            .with("minutes", format!("{:.1}", ratio))
+            .with("jobs", jobs),
+    })
+}
+
+/// Describe the presets of the resolved gvcore-cli
+pub async fn descriptions(
+    paths: &impl PathProvider,
+    settings: &AppSettings,
+) -> Result<Vec<PresetDescription>, AppError> {
+    let caps = match crate::commands::cli_path(paths) {
+        Ok(cli_path) => capabilities::discover(&cli_path).await,
+        Err(_) => Default::default(),
+    };
+    let presets = capabilities::preset_list(&caps).presets;
+    let history = history::load(paths)?;
+    Ok(describe(
+        &presets,
+        caps.version.as_deref(),
+        settings,
+        &history,
+        |video| prefetch::cached_metadata(paths, video).map(|m| m.duration_secs),
+    ))
+}
+
+/// What each preset in the picker trades off
+#[tauri::command]
+pub async fn get_preset_descriptions(app: AppHandle) -> Result<Vec<PresetDescription>, AppError> {
+    descriptions(&app, &app.settings()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::tests::job;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn versions_pick_their_table() {
+        const NEWER: [Builtin; 1] = [builtin(
+            "fast",
+            0.3,
+            QualityTier::Draft,
+            (0, None),
+            4,
+            (5_000, 0, 200, 0.0004),
+        )];
+        let tables: [(&str, &[Builtin]); 2] = [("0.0", &PRESETS_V0), ("1.4", &NEWER)];
+        assert_eq!(table_for(Some("1.3.9"), &tables).len(), 4);
+        assert_eq!(table_for(Some("v1.10.0"), &tables).len(), 1);
+        assert_eq!(table_for(None, &tables).len(), 1);
+    }
+
+    #[test]
+    fn saved_options_and_local_jobs_shape_the_descriptions() {
+        let mut settings = AppSettings::default();
+        settings.preset_training.insert(
+            "balanced".to_string(),
+            TrainingOptions {
+                iterations: Some(30_000),
+                ..Default::default()
+            },
+        );
+        settings.preset_training.insert(
+            "studio".to_string(),
+            TrainingOptions {
+                iterations: Some(40_000),
+                sh_degree: Some(1),
+                ..Default::default()
+            },
+        );
+        settings.preset_min_vram_gb.insert("fast".to_string(), 6);
+        let mut done = job("one", JobStatus::Completed);
+        done.preset = "fast".to_string();
+        done.videos = names(&["/clips/a.mp4", "/clips/b.mp4"]);
+        done.finished_at = 600;
+        let mut unknown = done.clone();
+        unknown.videos = names(&["/clips/unprobed.mp4"]);
+        let mut failed = done.clone();
+        failed.status = JobStatus::Failed;
+        let footage = |video: &str| (video != "/clips/unprobed.mp4").then_some(150.0);
+
+        let presets = names(&["fast", "balanced", "studio"]);
+        let described = describe(
+            &presets,
+            Some("0.9.0"),
+            &settings,
+            &[done, unknown, failed],
+            footage,
+        );
+
+        let [fast, balanced, studio] = &described[..] else {
+            panic!("{:?}", described);
+        };
+        assert!(fast.builtin && fast.overridden.is_empty());
+        assert_eq!(fast.min_vram_gb, 6);
+        assert_eq!(min_vram_mb("fast", Some("0.9.0"), Some(6)), 6 * 1024);
+        assert_eq!(min_vram_mb("studio", Some("0.9.0"), None), gpu::MIN_VRAM_MB);
+        let local = fast.local.as_ref().unwrap();
+        assert_eq!((local.jobs, local.minutes_per_footage_minute), (1, 2.0));
+        assert_eq!(local.message.params["minutes"], "2.0");
+
+        assert_eq!(balanced.relative_duration, 2.0);
+        assert_eq!(balanced.overridden, ["iterations"]);
+        assert_eq!(balanced.parameters.sh_degree, Some(2));
+        assert!(balanced.local.is_none());
+
+        assert!(!studio.builtin);
+        assert_eq!(studio.summary.key, "preset.custom");
+        assert_eq!(studio.quality, QualityTier::High);
+        assert_eq!(studio.min_vram_gb, 8);
+        assert_eq!(studio.parameters.sh_degree, Some(1));
+        assert_eq!(studio.overridden, ["iterations", "sh_degree"]);
+    }
+}
@@ -0,0 +1,235 @@
+//! Preview Runs
+//!
+//! A preview is a quick low-resolution pass to check a capture before the
+//! hour-long full run. Its clips are replaced by 960p proxies made with ffmpeg,
+//! it runs with the fastest preset and, where the CLI can limit them, fewer
+//! frames, and it writes into a `<name>-preview` directory inside the
+//! production so it never overwrites a full-quality result. Proxies are kept
+//! under the scratch directory, keyed by the clip's content hash and the proxy
+//! settings, so a second preview of the same clips starts at once.
+
+use crate::capabilities::{self, CliCapabilities};
+use crate::commands::ProcessArgs;
+use crate::disk;
+use crate::error::AppError;
+use crate::extraction;
+use crate::hashing::{self, Reuse};
+use crate::messages::{Failure, Message};
+use crate::runner::{EventSink, ProcessProgress};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// Height of the proxies, which are never scaled up
+pub const PROXY_HEIGHT: u32 = 960;
+
+/// Frames a preview asks the CLI to reconstruct from, where it can limit them
+pub const PREVIEW_MAX_FRAMES: u32 = 150;
+
+/// Directory under the scratch directory that holds the proxies
+const DIR_NAME: &str = "gameview-proxies";
+
+const SUFFIX: &str = "-preview";
+
+/// The preset previews run with, which the CLI lists first when it has no "fast"
+const FASTEST_PRESET: &str = "fast";
+
+/// Where a preview of a production in `output_dir` writes
+pub fn output_dir(output_dir: &str) -> String {
+    let dir = Path::new(output_dir);
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "production".to_string());
+    dir.join(format!("{}{}", name, SUFFIX))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// The production a preview written to `preview_dir` was for
+pub fn full_output_dir(preview_dir: &str) -> String {
+    let dir = Path::new(preview_dir);
+    let is_preview = dir
+        .file_name()
+        .is_some_and(|n| n.to_string_lossy().ends_with(SUFFIX));
+    match dir.parent() {
+        Some(parent) if is_preview => parent.to_string_lossy().to_string(),
+        _ => preview_dir.to_string(),
+    }
+}
+
+/// The fastest of the CLI's presets
+pub fn preset(caps: &CliCapabilities) -> String {
+    let presets = capabilities::preset_list(caps).presets;
+    if presets.iter().any(|p| p == FASTEST_PRESET) {
+        return FASTEST_PRESET.to_string();
+    }
+    presets
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| FASTEST_PRESET.to_string())
+}
+
+/// The arguments a preview actually runs with: proxies instead of the clips,
+/// already tone-mapped, and the fastest preset
+pub async fn proxied_args(
+    args: &ProcessArgs,
+    caps: &CliCapabilities,
+    events: &mut dyn EventSink,
+    cancel: &AtomicBool,
+) -> Result<ProcessArgs, Failure> {
+    let root = disk::scratch_dir(args.scratch_dir.as_deref()).join(DIR_NAME);
+    let mut proxied = args.clone();
+    proxied.preset = preset(caps);
+    proxied.videos = vec![];
+    proxied.clips = vec![];
+    for video in &args.videos {
+        let mut options = args.clip_options(video);
+        let proxy = proxy(&root, video, options.tone_map, events, cancel).await?;
+        options.path = proxy.to_string_lossy().to_string();
+        options.tone_map = false;
+        proxied.videos.push(options.path.clone());
+        proxied.clips.push(options);
+    }
+    Ok(proxied)
+}
+
+/// The proxy of `video`, made now unless an earlier preview left one
+async fn proxy(
+    root: &Path,
+    video: &str,
+    tone_map: bool,
+    events: &mut dyn EventSink,
+    cancel: &AtomicBool,
+) -> Result<PathBuf, Failure> {
+    let path = proxy_path(root, video, tone_map, cancel)
+        .await
+        .map_err(|e| match e {
+            AppError::Cancelled => Message::new("job.cancelled").into(),
+            e => e.failure(),
+        })?;
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    events.progress(&ProcessProgress {
+        stage: "extracting_frames".to_string(),
+        progress: 0.0,
+        message: Some(Message::new("progress.preview_proxy").with("video", video)),
+        raw: None,
+        current_item: None,
+        items: None,
+        reported_item: None,
+        synthetic: false,
+    });
+    let dir = path.parent().unwrap_or(root);
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    // Made under another name so an interrupted proxy is never taken for a finished one
+    let partial = dir.join(format!("partial-{}", file_name(&path)));
+    if let Err(e) = extraction::make_proxy(video, &partial, PROXY_HEIGHT, tone_map).await {
+        std::fs::remove_file(&partial).ok();
+        return Err(e.into());
+    }
+    std::fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Keeps the clip's file stem, which masks are matched by
+async fn proxy_path(
+    root: &Path,
+    video: &str,
+    tone_map: bool,
+    cancel: &AtomicBool,
+) -> Result<PathBuf, AppError> {
+    let content = hashing::sha256(Path::new(video), Reuse::Cached, cancel, &mut |_, _| {}).await?;
+    let settings = format!("{}|{}|{}", content, PROXY_HEIGHT, tone_map);
+    let key: String = Sha256::digest(settings.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let stem = Path::new(video)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "clip".to_string());
+    Ok(root.join(key).join(format!("{}.mp4", stem)))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::ClipOptions;
+    use crate::platform::testing::{RecordingEvents, TempPaths};
+
+    #[test]
+    fn previews_write_beside_the_full_result() {
+        let preview = output_dir("/productions/pier");
+        assert_eq!(preview, "/productions/pier/pier-preview");
+        assert_eq!(full_output_dir(&preview), "/productions/pier");
+        assert_eq!(full_output_dir("/productions/pier"), "/productions/pier");
+    }
+
+    #[test]
+    fn previews_use_the_fastest_preset() {
+        assert_eq!(preset(&CliCapabilities::default()), "fast");
+        let caps = CliCapabilities {
+            presets: vec!["draft".to_string(), "final".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(preset(&caps), "draft");
+    }
+
+    #[tokio::test]
+    async fn proxies_are_reused_and_stand_in_for_the_clips() {
+        let paths = TempPaths::new();
+        let video = paths.root().join("cam1.mov");
+        std::fs::write(&video, b"clip").unwrap();
+        let video = video.to_string_lossy().to_string();
+        let scratch = paths.root().join("scratch");
+        let root = scratch.join(DIR_NAME);
+        let cancel = AtomicBool::new(false);
+
+        // A proxy an earlier preview made; ffmpeg is never needed here
+        let existing = proxy_path(&root, &video, true, &cancel).await.unwrap();
+        assert!(existing.ends_with("cam1.mp4"));
+        std::fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        std::fs::write(&existing, b"proxy").unwrap();
+        assert_ne!(
+            proxy_path(&root, &video, false, &cancel).await.unwrap(),
+            existing
+        );
+
+        let mut args: ProcessArgs = serde_json::from_value(serde_json::json!({
+            "videos": [video],
+            "output_dir": "/productions/pier/pier-preview",
+            "preset": "maximum",
+            "preview_mode": true,
+        }))
+        .unwrap();
+        args.clips = vec![ClipOptions {
+            path: video.clone(),
+            tone_map: true,
+            sync_offset_ms: 250,
+            ..Default::default()
+        }];
+        args.scratch_dir = Some(scratch.to_string_lossy().to_string());
+        let mut events = RecordingEvents::default();
+        let proxied = proxied_args(&args, &CliCapabilities::default(), &mut events, &cancel)
+            .await
+            .unwrap();
+
+        let existing = existing.to_string_lossy().to_string();
+        assert_eq!(proxied.videos, std::slice::from_ref(&existing));
+        let options = proxied.clip_options(&existing);
+        assert!(!options.tone_map);
+        assert_eq!(options.sync_offset_ms, 250);
+        assert_eq!(proxied.preset, "fast");
+        assert!(events.progress.is_empty());
+    }
+}
@@ -0,0 +1,739 @@
+//! Production Management
+//!
+//! Operations on whole production directories that keep recents, history and
+//! sidecars in sync with what is on disk.
+
+use crate::archive::ARCHIVE_NAME;
+use crate::artifacts;
+use crate::error::AppError;
+use crate::fsutil::rebase;
+use crate::hashing::{self, Reuse};
+use crate::job_log;
+use crate::messages::Message;
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::scheduler::{self, Pool};
+use crate::settings::{Persist, RecentProduction, SettingsStore};
+use crate::undo::{self, Restore};
+use crate::{history, jobs, sidecar};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use tauri::{AppHandle, Emitter, State};
+
+/// Directories and files the CLI leaves behind that are only needed to re-run stages
+pub const INTERMEDIATE_ENTRIES: [&str; 5] = ["frames", "colmap", "sparse", "dense", "database.db"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveProgress {
+    pub copied_bytes: u64,
+    pub total_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedProduction {
+    /// Why part of the old directory is still there, when it could not all be
+    /// deleted once its copy was verified
+    pub leftover: Option<Message>,
+}
+
+/// Move or rename a production directory and update everything that points into it
+#[tauri::command]
+pub async fn move_production(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    old_path: String,
+    new_path: String,
+) -> Result<MovedProduction, AppError> {
+    policy.check_existing(&old_path)?;
+    policy.check_target(&new_path)?;
+    let emitter = app.clone();
+    let leftover = relocate(
+        &app,
+        &app,
+        Path::new(&old_path),
+        Path::new(&new_path),
+        move |progress| {
+            emitter.emit("production-move-progress", progress).ok();
+        },
+    )
+    .await?;
+    if let Some(leftover) = &leftover {
+        job_log::app_line(&app, &leftover.to_string());
+    }
+    Ok(MovedProduction { leftover })
+}
+
+// Move `old_dir` to `new_dir` and rewrite the recents, history and sidecar
+// that point into it, putting them back if the move fails
+async fn relocate(
+    paths: &impl PathProvider,
+    store: &impl SettingsStore,
+    old_dir: &Path,
+    new_dir: &Path,
+    on_progress: impl FnMut(&MoveProgress) + Send + 'static,
+) -> Result<Option<Message>, AppError> {
+    if !old_dir.is_dir() {
+        return Err(AppError::NotFound(old_dir.display().to_string()));
+    }
+    if new_dir.exists() {
+        return Err(AppError::InvalidInput(format!(
+            "Destination already exists: {}",
+            new_dir.display()
+        )));
+    }
+    if new_dir.starts_with(old_dir) {
+        return Err(AppError::InvalidInput(
+            "Cannot move a production into itself".to_string(),
+        ));
+    }
+    if jobs::is_targeting(old_dir) {
+        return Err(AppError::ProductionBusy(old_dir.display().to_string()));
+    }
+
+    // Snapshot the sidecar so it can be restored if the move fails
+    let old_sidecar = sidecar::read(old_dir)?;
+
+    let rewrite = || -> Result<(), String> {
+        rebase_recents(store, old_dir, new_dir)?;
+        rebase_history(paths, old_dir, new_dir)?;
+        if let Some(mut meta) = old_sidecar.clone() {
+            meta.production_dir = new_dir.to_string_lossy().to_string();
+            if let Some(path) = rebase(&meta.artifact_path, old_dir, new_dir) {
+                meta.artifact_path = path;
+            }
+            artifacts::rebase(&mut meta.artifacts, old_dir, new_dir);
+            sidecar::write(old_dir, &meta)?;
+        }
+        Ok(())
+    };
+
+    let result = match rewrite() {
+        Ok(()) => move_dir(old_dir, new_dir, on_progress).await,
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| {
+        rebase_recents(store, new_dir, old_dir).ok();
+        rebase_history(paths, new_dir, old_dir).ok();
+        if let Some(meta) = &old_sidecar {
+            sidecar::write(old_dir, meta).ok();
+        }
+        e.into()
+    })
+}
+
+// Point recents under `from` at `to`; settings may change concurrently, so rebase in place
+fn rebase_recents(store: &impl SettingsStore, from: &Path, to: &Path) -> Result<(), String> {
+    store.update_settings(Persist::Now, |s| {
+        for recent in &mut s.recent_productions {
+            if let Some(path) = rebase(&recent.path, from, to) {
+                recent.path = path;
+            }
+        }
+        Ok(())
+    })
+}
+
+// Point history records under `from` at `to`. Only those records are
+// amended, so records other jobs add while a copy runs are kept.
+fn rebase_history(paths: &impl PathProvider, from: &Path, to: &Path) -> Result<(), String> {
+    let under = |path: &str| Path::new(path).starts_with(from);
+    let mut moved = vec![];
+    for mut record in history::load(paths)? {
+        let points_into = under(&record.output_dir)
+            || record.artifact_path.as_deref().is_some_and(under)
+            || record.artifacts.iter().any(|a| under(&a.path));
+        if !points_into {
+            continue;
+        }
+        if let Some(path) = rebase(&record.output_dir, from, to) {
+            record.output_dir = path;
+        }
+        if let Some(path) = record
+            .artifact_path
+            .as_deref()
+            .and_then(|p| rebase(p, from, to))
+        {
+            record.artifact_path = Some(path);
+        }
+        artifacts::rebase(&mut record.artifacts, from, to);
+        moved.push(record);
+    }
+    history::amend(paths, &moved)
+}
+
+// Rename in place when possible, otherwise copy, verify and delete (e.g. across
+// volumes). Once the copy is verified it is the one whole production, so the
+// move is done; what cannot be deleted of the original is only reported.
+async fn move_dir(
+    from: &Path,
+    to: &Path,
+    mut on_progress: impl FnMut(&MoveProgress) + Send + 'static,
+) -> Result<Option<Message>, String> {
+    let (source, target) = (from.to_path_buf(), to.to_path_buf());
+    let copied = scheduler::run_blocking(Pool::Io, move || -> std::io::Result<_> {
+        if std::fs::rename(&source, &target).is_ok() {
+            return Ok(None);
+        }
+        let total_bytes = dir_size(&source)?;
+        let mut progress = MoveProgress {
+            copied_bytes: 0,
+            total_bytes,
+        };
+        let mut files = vec![];
+        copy_dir(
+            &source,
+            &target,
+            &mut files,
+            &mut progress,
+            &mut on_progress,
+        )?;
+        Ok(Some(files))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let files = match copied {
+        Ok(Some(files)) => files,
+        Ok(None) => return Ok(None),
+        Err(e) => {
+            std::fs::remove_dir_all(to).ok();
+            return Err(format!("Failed to copy production: {}", e));
+        }
+    };
+    if let Err(e) = verify_copies(&files).await {
+        std::fs::remove_dir_all(to).ok();
+        return Err(e);
+    }
+
+    let from = from.to_path_buf();
+    scheduler::run_blocking(Pool::Io, move || {
+        std::fs::remove_dir_all(&from).err().map(|e| {
+            Message::new("production.leftover")
+                .with("path", from.display())
+                .with("detail", e)
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// Check every copy against its original, byte for byte by way of its hash,
+// before anything of the original is deleted
+async fn verify_copies(files: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let cancel = AtomicBool::new(false);
+    for (original, copy) in files {
+        let mismatch = || format!("Copy of {} does not match the original", original.display());
+        let original_bytes = std::fs::metadata(original)
+            .map_err(|e| e.to_string())?
+            .len();
+        let copy_bytes = std::fs::metadata(copy).map_err(|e| e.to_string())?.len();
+        if original_bytes != copy_bytes {
+            return Err(mismatch());
+        }
+        let expected = hashing::sha256(original, Reuse::Cached, &cancel, &mut |_, _| {})
+            .await
+            .map_err(|e| e.to_string())?;
+        let actual = hashing::sha256(copy, Reuse::Fresh, &cancel, &mut |_, _| {})
+            .await
+            .map_err(|e| e.to_string())?;
+        if expected != actual {
+            return Err(mismatch());
+        }
+    }
+    Ok(())
+}
+
+// Copy `from` into `to`, listing each file copied with its copy
+fn copy_dir(
+    from: &Path,
+    to: &Path,
+    files: &mut Vec<(PathBuf, PathBuf)>,
+    progress: &mut MoveProgress,
+    on_progress: &mut impl FnMut(&MoveProgress),
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target, files, progress, on_progress)?;
+        } else {
+            progress.copied_bytes += std::fs::copy(entry.path(), &target)?;
+            files.push((entry.path(), target));
+            on_progress(progress);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteScope {
+    Intermediates,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Intermediate,
+    Artifact,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteEntry {
+    pub path: String,
+    pub kind: EntryKind,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeletePreview {
+    pub entries: Vec<DeleteEntry>,
+    pub total_bytes: u64,
+}
+
+/// List what delete_production would remove for the given scope
+#[tauri::command]
+pub async fn preview_delete_production(
+    policy: State<'_, PathPolicy>,
+    path: String,
+    scope: DeleteScope,
+) -> Result<DeletePreview, AppError> {
+    policy.check_existing(&path)?;
+    delete_preview(Path::new(&path), scope)
+}
+
+/// Move a production (or just its intermediates) to the OS trash
+#[tauri::command]
+pub async fn delete_production(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+    scope: DeleteScope,
+) -> Result<DeletePreview, AppError> {
+    policy.check_existing(&path)?;
+    move_to_trash(&app, Path::new(&path), scope)
+}
+
+/// Move a production (or just its intermediates) to the OS trash, unlink it
+/// from the recents and history and record how to undo it
+pub fn move_to_trash(
+    app: &AppHandle,
+    dir: &Path,
+    scope: DeleteScope,
+) -> Result<DeletePreview, AppError> {
+    if jobs::is_targeting(dir) {
+        return Err(AppError::ProductionBusy(dir.to_string_lossy().to_string()));
+    }
+
+    let preview = delete_preview(dir, scope)?;
+    // Canonical, as the trash records them
+    let trashed = match scope {
+        DeleteScope::All => vec![dir.canonicalize()?],
+        DeleteScope::Intermediates => preview
+            .entries
+            .iter()
+            .map(|e| Path::new(&e.path).canonicalize())
+            .collect::<Result<Vec<PathBuf>, _>>()?,
+    };
+    trash::delete_all(&trashed)
+        .map_err(|e| AppError::Io(format!("Failed to move to trash: {}", e)))?;
+
+    let (recents, artifacts) = match scope {
+        DeleteScope::All => unlink(app, app, dir)?,
+        DeleteScope::Intermediates => (vec![], vec![]),
+    };
+
+    let trash_ids = undo::trashed::find(&trashed);
+    if let Some((description, restore)) =
+        undo_entry(dir, scope, &trashed, trash_ids, recents, artifacts)
+    {
+        undo::record(app, description, restore);
+    }
+
+    Ok(preview)
+}
+
+// Remove the recents under `dir` and clear the artifact of history records
+// under it, returning what was removed along with the job it belonged to
+fn unlink(
+    paths: &impl PathProvider,
+    store: &impl SettingsStore,
+    dir: &Path,
+) -> Result<(Vec<RecentProduction>, Vec<(String, String)>), AppError> {
+    let recents = store.update_settings(Persist::Now, |s| {
+        let (removed, kept) = std::mem::take(&mut s.recent_productions)
+            .into_iter()
+            .partition(|recent| Path::new(&recent.path).starts_with(dir));
+        s.recent_productions = kept;
+        Ok::<Vec<RecentProduction>, AppError>(removed)
+    })?;
+
+    let mut artifacts = vec![];
+    let mut changed = vec![];
+    for mut record in history::load(paths)? {
+        if record
+            .artifact_path
+            .as_deref()
+            .is_some_and(|p| Path::new(p).starts_with(dir))
+        {
+            let path = record.artifact_path.take().unwrap_or_default();
+            artifacts.push((record.job_id.clone(), path));
+            changed.push(record);
+        }
+    }
+    history::amend(paths, &changed)?;
+    Ok((recents, artifacts))
+}
+
+// How to undo trashing `trashed`; nothing to offer when the trash cannot give
+// all of it back
+fn undo_entry(
+    dir: &Path,
+    scope: DeleteScope,
+    trashed: &[PathBuf],
+    trash_ids: Vec<OsString>,
+    recents: Vec<RecentProduction>,
+    artifacts: Vec<(String, String)>,
+) -> Option<(Message, Restore)> {
+    if trash_ids.len() != trashed.len() {
+        return None;
+    }
+    let key = match scope {
+        DeleteScope::All => "undo.delete_production",
+        DeleteScope::Intermediates => "undo.delete_intermediates",
+    };
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    let restore = Restore::Trashed {
+        trash_ids,
+        recents,
+        artifacts,
+        links: vec![],
+    };
+    Some((Message::new(key).with("name", name), restore))
+}
+
+fn delete_preview(dir: &Path, scope: DeleteScope) -> Result<DeletePreview, AppError> {
+    if !dir.is_dir() {
+        return Err(AppError::NotFound(dir.to_string_lossy().to_string()));
+    }
+
+    let mut entries = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let kind = if INTERMEDIATE_ENTRIES.contains(&name.as_str()) || name == ARCHIVE_NAME {
+            EntryKind::Intermediate
+        } else {
+            EntryKind::Artifact
+        };
+        if scope == DeleteScope::Intermediates && kind != EntryKind::Intermediate {
+            continue;
+        }
+
+        let bytes = if entry.file_type()?.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            entry.metadata()?.len()
+        };
+        entries.push(DeleteEntry {
+            path: entry.path().to_string_lossy().to_string(),
+            kind,
+            bytes,
+        });
+    }
+
+    let total_bytes = entries.iter().map(|e| e.bytes).sum();
+    Ok(DeletePreview {
+        entries,
+        total_bytes,
+    })
+}
+
+/// Total size in bytes of all files under a directory
+pub fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::tests::job;
+    use crate::history::JobStatus;
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::AppSettings;
+    use crate::sidecar::Sidecar;
+
+    // A production at `dir` with an artifact and a sidecar, known to the
+    // recents and history, alongside a record of another production
+    fn production(paths: &TempPaths, dir: &Path) -> MemorySettings {
+        std::fs::create_dir_all(dir.join("frames")).unwrap();
+        std::fs::write(dir.join("output.ply"), b"ply").unwrap();
+        std::fs::write(dir.join("frames").join("0001.png"), b"png").unwrap();
+        let artifact = dir.join("output.ply").to_string_lossy().to_string();
+        sidecar::write(
+            dir,
+            &Sidecar {
+                job_id: "job-1".to_string(),
+                production_dir: dir.to_string_lossy().to_string(),
+                artifact_path: artifact.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut moved = job("job-1", JobStatus::Completed);
+        moved.output_dir = dir.to_string_lossy().to_string();
+        moved.artifact_path = Some(artifact);
+        history::save(paths, &[moved, job("job-2", JobStatus::Completed)]).unwrap();
+
+        MemorySettings::with(AppSettings {
+            recent_productions: vec![RecentProduction {
+                id: "p1".to_string(),
+                name: "Harbour".to_string(),
+                path: dir.to_string_lossy().to_string(),
+                last_opened: String::new(),
+                tags: vec![],
+                notes: String::new(),
+                imported: false,
+                missing: false,
+                pinned: false,
+            }],
+            ..Default::default()
+        })
+    }
+
+    fn record(paths: &TempPaths, job_id: &str) -> history::JobRecord {
+        history::load(paths)
+            .unwrap()
+            .into_iter()
+            .find(|r| r.job_id == job_id)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_rename_takes_recents_history_and_sidecar_along() {
+        let paths = TempPaths::new();
+        let old_dir = paths.root().join("harbour");
+        let new_dir = paths.root().join("harbour-2024");
+        let store = production(&paths, &old_dir);
+
+        let leftover = relocate(&paths, &store, &old_dir, &new_dir, |_| {})
+            .await
+            .unwrap();
+        assert!(leftover.is_none());
+        assert!(!old_dir.exists());
+        assert!(new_dir.join("frames").join("0001.png").exists());
+
+        let new_path = new_dir.to_string_lossy().to_string();
+        assert_eq!(store.settings().recent_productions[0].path, new_path);
+        let moved = record(&paths, "job-1");
+        assert_eq!(moved.output_dir, new_path);
+        assert_eq!(
+            moved.artifact_path,
+            Some(new_dir.join("output.ply").to_string_lossy().to_string())
+        );
+        assert_eq!(record(&paths, "job-2").output_dir, "/productions/harbour");
+        let meta = sidecar::read(&new_dir).unwrap().unwrap();
+        assert_eq!(meta.production_dir, new_path);
+    }
+
+    #[tokio::test]
+    async fn a_copy_is_checked_file_by_file() {
+        let paths = TempPaths::new();
+        let from = paths.root().join("harbour");
+        let to = paths.root().join("elsewhere");
+        production(&paths, &from);
+
+        let mut files = vec![];
+        let mut progress = MoveProgress {
+            copied_bytes: 0,
+            total_bytes: dir_size(&from).unwrap(),
+        };
+        let mut reported = 0;
+        copy_dir(&from, &to, &mut files, &mut progress, &mut |_| {
+            reported += 1
+        })
+        .unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(reported, 3);
+        assert_eq!(progress.copied_bytes, progress.total_bytes);
+        verify_copies(&files).await.unwrap();
+
+        // Same size, different bytes
+        std::fs::write(to.join("output.ply"), b"PLY").unwrap();
+        assert!(verify_copies(&files).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_failed_move_puts_recents_history_and_sidecar_back() {
+        let paths = TempPaths::new();
+        let old_dir = paths.root().join("harbour");
+        let store = production(&paths, &old_dir);
+        // Neither renaming nor copying can make a directory under a file
+        std::fs::write(paths.root().join("blocker"), b"").unwrap();
+        let new_dir = paths.root().join("blocker").join("harbour");
+
+        let result = relocate(&paths, &store, &old_dir, &new_dir, |_| {}).await;
+        assert!(result.is_err());
+
+        let old_path = old_dir.to_string_lossy().to_string();
+        assert!(old_dir.join("output.ply").exists());
+        assert_eq!(store.settings().recent_productions[0].path, old_path);
+        let restored = record(&paths, "job-1");
+        assert_eq!(restored.output_dir, old_path);
+        assert_eq!(
+            restored.artifact_path,
+            Some(old_dir.join("output.ply").to_string_lossy().to_string())
+        );
+        assert_eq!(record(&paths, "job-2").output_dir, "/productions/harbour");
+        let meta = sidecar::read(&old_dir).unwrap().unwrap();
+        assert_eq!(meta.production_dir, old_path);
+    }
+
+    #[test]
+    fn deleting_intermediates_spares_the_artifacts() {
+        let paths = TempPaths::new();
+        let dir = paths.root().join("harbour");
+        production(&paths, &dir);
+        std::fs::write(dir.join("database.db"), b"sqlite").unwrap();
+        std::fs::write(dir.join(ARCHIVE_NAME), b"archive").unwrap();
+
+        let kinds = |scope| {
+            let preview = delete_preview(&dir, scope).unwrap();
+            let mut kinds: Vec<(String, EntryKind)> = preview
+                .entries
+                .iter()
+                .map(|e| {
+                    let name = Path::new(&e.path).file_name().unwrap();
+                    (name.to_string_lossy().to_string(), e.kind)
+                })
+                .collect();
+            kinds.sort_by(|a, b| a.0.cmp(&b.0));
+            (kinds, preview.total_bytes)
+        };
+        let mut expected = vec![
+            (sidecar::SIDECAR_NAME.to_string(), EntryKind::Artifact),
+            (ARCHIVE_NAME.to_string(), EntryKind::Intermediate),
+            ("database.db".to_string(), EntryKind::Intermediate),
+            ("frames".to_string(), EntryKind::Intermediate),
+            ("output.ply".to_string(), EntryKind::Artifact),
+        ];
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        let (all, all_bytes) = kinds(DeleteScope::All);
+        assert_eq!(all, expected);
+        assert_eq!(all_bytes, dir_size(&dir).unwrap());
+
+        let (intermediates, bytes) = kinds(DeleteScope::Intermediates);
+        assert!(intermediates
+            .iter()
+            .all(|(_, kind)| *kind == EntryKind::Intermediate));
+        assert_eq!(intermediates.len(), 3);
+        assert_eq!(bytes, 6 + 7 + 3);
+    }
+
+    #[test]
+    fn deleting_a_production_unlinks_its_recents_and_history() {
+        let paths = TempPaths::new();
+        let dir = paths.root().join("harbour");
+        let store = production(&paths, &dir);
+
+        let (recents, artifacts) = unlink(&paths, &store, &dir).unwrap();
+        assert_eq!(recents.len(), 1);
+        assert!(store.settings().recent_productions.is_empty());
+        let artifact = dir.join("output.ply").to_string_lossy().to_string();
+        assert_eq!(artifacts, [("job-1".to_string(), artifact)]);
+        let unlinked = record(&paths, "job-1");
+        assert_eq!(unlinked.artifact_path, None);
+        // The record itself stays, as does every other
+        assert_eq!(unlinked.output_dir, dir.to_string_lossy());
+        assert_eq!(history::load(&paths).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn undo_is_offered_only_when_everything_trashed_can_come_back() {
+        let dir = Path::new("/productions/harbour");
+        let trashed = [dir.join("frames"), dir.join("colmap")];
+        let ids = |n: usize| (0..n).map(|i| OsString::from(i.to_string())).collect();
+
+        let (description, restore) = undo_entry(
+            dir,
+            DeleteScope::Intermediates,
+            &trashed,
+            ids(2),
+            vec![],
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(description.key, "undo.delete_intermediates");
+        assert_eq!(
+            description.to_string(),
+            "Moved the intermediates of harbour to the trash"
+        );
+        assert!(matches!(restore, Restore::Trashed { trash_ids, .. } if trash_ids.len() == 2));
+
+        let artifacts = vec![(
+            "job-1".to_string(),
+            "/productions/harbour/output.ply".to_string(),
+        )];
+        let (description, restore) = undo_entry(
+            dir,
+            DeleteScope::All,
+            &trashed[..1],
+            ids(1),
+            vec![],
+            artifacts.clone(),
+        )
+        .unwrap();
+        assert_eq!(description.key, "undo.delete_production");
+        match restore {
+            Restore::Trashed {
+                artifacts: restored,
+                ..
+            } => assert_eq!(restored, artifacts),
+            _ => panic!("not a trash restore"),
+        }
+
+        // One of them is not in the trash
+        assert!(undo_entry(
+            dir,
+            DeleteScope::Intermediates,
+            &trashed,
+            ids(1),
+            vec![],
+            vec![]
+        )
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn refuses_to_move_into_itself_or_onto_an_existing_path() {
+        let paths = TempPaths::new();
+        let old_dir = paths.root().join("harbour");
+        let store = production(&paths, &old_dir);
+        let taken = paths.root().join("taken");
+        std::fs::create_dir_all(&taken).unwrap();
+
+        for new_dir in [old_dir.join("nested"), taken] {
+            let result = relocate(&paths, &store, &old_dir, &new_dir, |_| {}).await;
+            assert!(matches!(result, Err(AppError::InvalidInput(_))));
+        }
+        assert!(old_dir.join("output.ply").exists());
+        assert_eq!(
+            store.settings().recent_productions[0].path,
+            old_dir.to_string_lossy().to_string()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Capture Profiles
+//!
+//! Curated CLI flag sets for common kinds of footage. Drone passes and walkthroughs
+//! are shot as continuous sequences, turntables circle one object, and each wants
+//! different COLMAP matching. Users can replace a profile's flags in settings.
+
+use crate::capabilities::CliCapabilities;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureType {
+    #[default]
+    Generic,
+    Drone,
+    Turntable,
+    Walkthrough,
+}
+
+/// Flags that replace the built-in ones of a capture type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileOverride {
+    pub capture_type: CaptureType,
+    pub flags: Vec<String>,
+}
+
+/// The built-in flags of a capture type
+pub fn builtin_flags(capture_type: CaptureType) -> &'static [&'static str] {
+    match capture_type {
+        CaptureType::Generic => &[],
+        // Frames follow a flight path and share one camera
+        CaptureType::Drone => &["--matcher", "sequential", "--single-camera"],
+        // Every view sees the same object, so match all pairs
+        CaptureType::Turntable => &["--matcher", "exhaustive", "--single-camera"],
+        // Long sequences that revisit rooms need loop closure
+        CaptureType::Walkthrough => &["--matcher", "sequential", "--loop-detection"],
+    }
+}
+
+/// Flags for a capture type, taking a user override over the built-in set
+pub fn flags_for(capture_type: CaptureType, overrides: &[ProfileOverride]) -> Vec<String> {
+    match overrides.iter().find(|o| o.capture_type == capture_type) {
+        Some(custom) => custom.flags.clone(),
+        None => builtin_flags(capture_type)
+            .iter()
+            .map(|f| f.to_string())
+            .collect(),
+    }
+}
+
+/// Split flags into those the CLI supports and those it does not. A value after
+/// a flag travels with it, so an unsupported flag never leaves its value behind.
+pub fn supported_flags(flags: &[String], caps: &CliCapabilities) -> (Vec<String>, Vec<String>) {
+    let mut supported = vec![];
+    let mut unsupported = vec![];
+    let mut keep = true;
+    for arg in flags {
+        if arg.starts_with("--") {
+            keep = caps.supports(arg);
+            if !keep {
+                unsupported.push(arg.clone());
+            }
+        }
+        if keep {
+            supported.push(arg.clone());
+        }
+    }
+    (supported, unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn overrides_replace_builtin_flags() {
+        let overrides = vec![ProfileOverride {
+            capture_type: CaptureType::Drone,
+            flags: strings(&["--matcher", "vocab-tree"]),
+        }];
+        assert_eq!(
+            flags_for(CaptureType::Drone, &overrides),
+            strings(&["--matcher", "vocab-tree"])
+        );
+        assert_eq!(
+            flags_for(CaptureType::Turntable, &overrides),
+            strings(&["--matcher", "exhaustive", "--single-camera"])
+        );
+        assert!(flags_for(CaptureType::Generic, &[]).is_empty());
+    }
+
+    #[test]
+    fn unsupported_flags_drop_with_their_values() {
+        let caps = CliCapabilities {
+            flags: strings(&["--single-camera", "--loop-detection"]),
+            ..Default::default()
+        };
+        let flags = flags_for(CaptureType::Walkthrough, &[]);
+        let (supported, unsupported) = supported_flags(&flags, &caps);
+
+        assert_eq!(supported, strings(&["--loop-detection"]));
+        assert_eq!(unsupported, strings(&["--matcher"]));
+    }
+}
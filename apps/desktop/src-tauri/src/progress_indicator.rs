@@ -0,0 +1,218 @@
+//! Progress Indicator
+//!
+//! Mirrors the running job in the main window's title, e.g.
+//! "Game View — Training splats 62%", so progress stays visible while the
+//! window is in the background and is read out by screen readers that announce
+//! title changes. It needs no taskbar or tray support. Updates are throttled,
+//! and a failed job leaves a suffix on the title until the user acknowledges it.
+
+use crate::error::AppError;
+use crate::messages::Message;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, UserAttentionType};
+
+/// The title of the main window when nothing is running
+const BASE_TITLE: &str = "Game View";
+
+/// Progress within one stage is shown at most this often
+const MIN_INTERVAL: Duration = Duration::from_secs(2);
+
+static STATE: Mutex<Indicator> = Mutex::new(Indicator::new());
+
+#[derive(Debug)]
+struct Indicator {
+    last_shown: Option<(Instant, String)>,
+    /// Queue entries whose failure has not been acknowledged yet
+    failed: Vec<String>,
+}
+
+impl Indicator {
+    const fn new() -> Self {
+        Self {
+            last_shown: None,
+            failed: Vec::new(),
+        }
+    }
+
+    /// The title for a progress update, or None when it came too soon after
+    /// the last one of the same stage
+    fn progress(&mut self, now: Instant, stage: &str, percent: f64) -> Option<String> {
+        if let Some((shown_at, shown_stage)) = &self.last_shown {
+            if shown_stage == stage && now.duration_since(*shown_at) < MIN_INTERVAL {
+                return None;
+            }
+        }
+        self.last_shown = Some((now, stage.to_string()));
+        let status = Message::new("title.progress")
+            .with("stage", stage_label(stage))
+            .with("percent", format!("{:.0}", percent.clamp(0.0, 100.0)));
+        Some(self.title(Some(status.to_string())))
+    }
+
+    /// The title while the job waits for its output volume to come back
+    fn paused(&mut self, now: Instant) -> String {
+        self.last_shown = Some((now, String::new()));
+        self.title(Some(Message::new("title.volume_disconnected").to_string()))
+    }
+
+    /// The title once a job is over, recording the entries that failed
+    fn finish(&mut self, failed: &[String]) -> String {
+        self.last_shown = None;
+        for entry_id in failed {
+            if !self.failed.contains(entry_id) {
+                self.failed.push(entry_id.clone());
+            }
+        }
+        self.title(None)
+    }
+
+    /// The title without the entry's failure, or None while a job is running,
+    /// whose next update shows the change
+    fn acknowledge(&mut self, entry_id: &str) -> Option<String> {
+        self.failed.retain(|id| id != entry_id);
+        self.last_shown.is_none().then(|| self.title(None))
+    }
+
+    fn title(&self, status: Option<String>) -> String {
+        let failed = (!self.failed.is_empty())
+            .then(|| Message::new("title.failed").with("count", self.failed.len()));
+        match (status, failed) {
+            (Some(status), Some(failed)) => format!("{} — {} ({})", BASE_TITLE, status, failed),
+            (Some(status), None) => format!("{} — {}", BASE_TITLE, status),
+            (None, Some(failed)) => format!("{} — {}", BASE_TITLE, failed),
+            (None, None) => BASE_TITLE.to_string(),
+        }
+    }
+}
+
+/// "training_splats" becomes "Training splats"
+fn stage_label(stage: &str) -> String {
+    let words = stage.replace('_', " ");
+    let mut chars = words.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Show a progress update of the running job
+pub fn progress(app: &AppHandle, stage: &str, percent: f64) {
+    let title = STATE
+        .lock()
+        .unwrap()
+        .progress(Instant::now(), stage, percent);
+    if let Some(title) = title {
+        set_title(app, &title);
+    }
+}
+
+/// Show that the running job is paused for its output volume, asking for the
+/// user's attention as the job cannot finish without them
+pub fn paused(app: &AppHandle) {
+    let title = STATE.lock().unwrap().paused(Instant::now());
+    set_title(app, &title);
+    if let Some(window) = app.get_webview_window("main") {
+        window
+            .request_user_attention(Some(UserAttentionType::Critical))
+            .ok();
+    }
+}
+
+/// Restore the plain title after a job, keeping a suffix while any of the
+/// given queue entries' failures is unacknowledged
+pub fn finish(app: &AppHandle, failed: &[String]) {
+    let title = STATE.lock().unwrap().finish(failed);
+    set_title(app, &title);
+}
+
+/// Clear the failure suffix a job left on the window title
+#[tauri::command]
+pub async fn acknowledge_job_result(app: AppHandle, job_id: String) -> Result<(), AppError> {
+    let title = STATE.lock().unwrap().acknowledge(&job_id);
+    if let Some(title) = title {
+        set_title(&app, &title);
+    }
+    Ok(())
+}
+
+fn set_title(app: &AppHandle, title: &str) {
+    // Headless runs have no window to update
+    if let Some(window) = app.get_webview_window("main") {
+        window.set_title(title).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_is_throttled_within_a_stage() {
+        let mut indicator = Indicator::new();
+        let start = Instant::now();
+        assert_eq!(
+            indicator
+                .progress(start, "training_splats", 61.6)
+                .as_deref(),
+            Some("Game View — Training splats 62%")
+        );
+        assert_eq!(
+            indicator.progress(start + Duration::from_millis(500), "training_splats", 63.0),
+            None
+        );
+        // A new stage shows at once
+        assert_eq!(
+            indicator
+                .progress(start + Duration::from_millis(600), "exporting", 0.0)
+                .as_deref(),
+            Some("Game View — Exporting 0%")
+        );
+        assert!(indicator
+            .progress(start + MIN_INTERVAL * 2, "exporting", 50.0)
+            .is_some());
+        assert_eq!(indicator.finish(&[]), "Game View");
+    }
+
+    #[test]
+    fn a_paused_job_shows_until_progress_resumes() {
+        let mut indicator = Indicator::new();
+        let start = Instant::now();
+        indicator.progress(start, "training_splats", 40.0);
+        assert_eq!(
+            indicator.paused(start),
+            "Game View — Paused: the output drive disconnected"
+        );
+        assert_eq!(
+            indicator
+                .progress(start, "training_splats", 40.0)
+                .as_deref(),
+            Some("Game View — Training splats 40%")
+        );
+    }
+
+    #[test]
+    fn failures_stay_until_acknowledged() {
+        let mut indicator = Indicator::new();
+        let failed = ["entry-1".to_string(), "entry-2".to_string()];
+        assert_eq!(indicator.finish(&failed), "Game View — 2 failed");
+        assert_eq!(
+            indicator
+                .progress(Instant::now(), "extracting_frames", 10.0)
+                .as_deref(),
+            Some("Game View — Extracting frames 10% (2 failed)")
+        );
+        assert_eq!(indicator.finish(&failed[..1]), "Game View — 2 failed");
+        assert_eq!(
+            indicator.acknowledge("entry-1").as_deref(),
+            Some("Game View — 1 failed")
+        );
+        indicator.progress(Instant::now(), "exporting", 90.0);
+        assert_eq!(indicator.acknowledge("entry-2"), None);
+        assert_eq!(indicator.finish(&[]), "Game View");
+        assert_eq!(
+            indicator.acknowledge("entry-3").as_deref(),
+            Some("Game View")
+        );
+    }
+}
@@ -0,0 +1,392 @@
+//! Job Queue
+//!
+//! Jobs waiting for or running on the CLI, in the order they will run. A per-clip
+//! request adds one entry per clip, linked by a shared batch id; entries leave the
+//! queue when their request finishes and live on in the history. An entry
+//! cancelled before its turn leaves the queue at once and never runs. The last
+//! few finished entries are also kept in memory, so their outcome can still be
+//! looked up by entry id.
+//!
+//! Once persisted, the queue is mirrored to app_data/queue.json: updates within
+//! a job are debounced, while requests starting and finishing are written at
+//! once. Entries an earlier run left queued or running are reported as
+//! interrupted.
+
+use crate::clip_progress::ClipProgress;
+use crate::datafile::{self, Debounced};
+use crate::messages::{Failure, Message};
+use crate::platform::PathProvider;
+use crate::runner::CliWarning;
+use crate::written_by::{self, FileFormat};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, OnceLock};
+
+static QUEUE: Mutex<Vec<QueueEntry>> = Mutex::new(Vec::new());
+
+/// Entries that left the queue, oldest first
+static FINISHED: Mutex<Vec<QueueEntry>> = Mutex::new(Vec::new());
+
+/// How many finished entries `find` still knows about
+const FINISHED_LIMIT: usize = 100;
+
+/// Name of the queue's mirror in app_data
+pub const FILE_NAME: &str = "queue.json";
+
+/// The queue's mirror on disk, once `persist` was called
+static FILE: OnceLock<Arc<Debounced>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub entry_id: String,
+    pub batch_id: Option<String>,
+    pub videos: Vec<String>,
+    pub output_dir: String,
+    pub status: QueueStatus,
+    pub artifact_path: Option<String>,
+    pub error: Option<Failure>,
+    /// Warnings the CLI printed so far
+    #[serde(default)]
+    pub warnings: Vec<CliWarning>,
+    /// Which clips the job worked on, and for how long
+    #[serde(default)]
+    pub clips: Vec<ClipProgress>,
+    /// The request's fingerprint, shared by the entries of a batch
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+impl QueueEntry {
+    pub fn queued(
+        entry_id: String,
+        batch_id: Option<String>,
+        videos: &[String],
+        output_dir: &str,
+    ) -> Self {
+        Self {
+            entry_id,
+            batch_id,
+            videos: videos.to_vec(),
+            output_dir: output_dir.to_string(),
+            status: QueueStatus::Queued,
+            artifact_path: None,
+            error: None,
+            warnings: vec![],
+            clips: vec![],
+            fingerprint: None,
+        }
+    }
+}
+
+/// Removes the entries of a request from the queue when dropped
+pub struct QueueGuard {
+    entry_ids: Vec<String>,
+}
+
+impl Drop for QueueGuard {
+    fn drop(&mut self) {
+        let mut queue = QUEUE.lock().unwrap();
+        let mut finished = FINISHED.lock().unwrap();
+        queue.retain(|entry| {
+            if !self.entry_ids.contains(&entry.entry_id) {
+                return true;
+            }
+            finished.push(entry.clone());
+            false
+        });
+        trim(&mut finished);
+        mirror(&queue, true);
+    }
+}
+
+fn trim(finished: &mut Vec<QueueEntry>) {
+    let excess = finished.len().saturating_sub(FINISHED_LIMIT);
+    finished.drain(..excess);
+}
+
+/// Queue entries for as long as the returned guard lives
+pub fn enqueue(entries: Vec<QueueEntry>) -> QueueGuard {
+    insert(&mut QUEUE.lock().unwrap(), entries)
+}
+
+/// Queue entries like `enqueue`, unless an entry with one of their
+/// fingerprints is still queued or running; that entry's id is returned instead
+pub fn enqueue_unique(entries: Vec<QueueEntry>) -> Result<QueueGuard, String> {
+    let mut queue = QUEUE.lock().unwrap();
+    let pending = queue.iter().find(|e| {
+        matches!(e.status, QueueStatus::Queued | QueueStatus::Running)
+            && e.fingerprint.is_some()
+            && entries.iter().any(|new| new.fingerprint == e.fingerprint)
+    });
+    if let Some(existing) = pending {
+        return Err(existing.entry_id.clone());
+    }
+    Ok(insert(&mut queue, entries))
+}
+
+fn insert(queue: &mut Vec<QueueEntry>, entries: Vec<QueueEntry>) -> QueueGuard {
+    let entry_ids = entries.iter().map(|e| e.entry_id.clone()).collect();
+    queue.extend(entries);
+    mirror(queue, true);
+    QueueGuard { entry_ids }
+}
+
+pub fn update(entry_id: &str, change: impl FnOnce(&mut QueueEntry)) {
+    let mut queue = QUEUE.lock().unwrap();
+    if let Some(entry) = queue.iter_mut().find(|e| e.entry_id == entry_id) {
+        change(entry);
+        mirror(&queue, false);
+    }
+}
+
+/// Mark a queued entry as running; false when it is no longer waiting to run,
+/// e.g. because it was cancelled before its turn
+pub fn start(entry_id: &str) -> bool {
+    let mut queue = QUEUE.lock().unwrap();
+    let Some(entry) = queue
+        .iter_mut()
+        .find(|e| e.entry_id == entry_id && e.status == QueueStatus::Queued)
+    else {
+        return false;
+    };
+    entry.status = QueueStatus::Running;
+    mirror(&queue, true);
+    true
+}
+
+/// Take an entry that is still waiting for its turn off the queue, as
+/// cancelled; None when it is not waiting
+pub fn cancel_queued(entry_id: &str) -> Option<QueueEntry> {
+    let mut queue = QUEUE.lock().unwrap();
+    let i = queue
+        .iter()
+        .position(|e| e.entry_id == entry_id && e.status == QueueStatus::Queued)?;
+    let mut entry = queue.remove(i);
+    entry.status = QueueStatus::Cancelled;
+    let mut finished = FINISHED.lock().unwrap();
+    finished.push(entry.clone());
+    trim(&mut finished);
+    mirror(&queue, true);
+    Some(entry)
+}
+
+/// Mirror the queue to app_data/queue.json from now on, first taking over the
+/// entries an earlier run left there
+pub fn persist(paths: &impl PathProvider) -> Result<(), String> {
+    let path = paths.app_data_dir()?.join(FILE_NAME);
+    let left = datafile::read_json::<Saved>(&path, "the job queue")?;
+    take_over(left.map(Saved::into_entries).unwrap_or_default());
+    let file = Debounced::new(path);
+    mirror_to(&file, &QUEUE.lock().unwrap(), true);
+    FILE.set(file).ok();
+    Ok(())
+}
+
+/// Write a pending debounced update now
+pub fn flush() {
+    if let Some(file) = FILE.get() {
+        file.flush();
+    }
+}
+
+/// Keep entries of an earlier run where `find` sees them, those that never
+/// finished marked as interrupted
+fn take_over(left: Vec<QueueEntry>) {
+    let mut finished = FINISHED.lock().unwrap();
+    for mut entry in left {
+        if matches!(entry.status, QueueStatus::Queued | QueueStatus::Running) {
+            entry.status = QueueStatus::Failed;
+            entry.error = Some(Message::new("job.interrupted").into());
+        }
+        finished.push(entry);
+    }
+    trim(&mut finished);
+}
+
+// Called with the queue locked, so writes reach the file in order
+fn mirror(queue: &[QueueEntry], now: bool) {
+    if let Some(file) = FILE.get() {
+        mirror_to(file, queue, now);
+    }
+}
+
+fn mirror_to(file: &Arc<Debounced>, queue: &[QueueEntry], now: bool) {
+    let mirrored = written_by::stamp(FileFormat::Queue, &Entries { entries: queue });
+    if now {
+        file.write_now(&mirrored);
+    } else {
+        file.write(&mirrored);
+    }
+}
+
+#[derive(Serialize)]
+struct Entries<'a> {
+    entries: &'a [QueueEntry],
+}
+
+/// The queue as mirrored, or as the bare list of versions before the header
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Saved {
+    Entries { entries: Vec<QueueEntry> },
+    Legacy(Vec<QueueEntry>),
+}
+
+impl Saved {
+    fn into_entries(self) -> Vec<QueueEntry> {
+        match self {
+            Saved::Entries { entries } | Saved::Legacy(entries) => entries,
+        }
+    }
+}
+
+pub fn snapshot() -> Vec<QueueEntry> {
+    QUEUE.lock().unwrap().clone()
+}
+
+/// An entry still in the queue, or one that finished recently
+pub fn find(entry_id: &str) -> Option<QueueEntry> {
+    let queued = QUEUE
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|e| e.entry_id == entry_id)
+        .cloned();
+    queued.or_else(|| {
+        FINISHED
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|e| e.entry_id == entry_id)
+            .cloned()
+    })
+}
+
+/// Get queued and running jobs in the order they run
+#[tauri::command]
+pub async fn get_queue() -> Result<Vec<QueueEntry>, String> {
+    Ok(snapshot())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_id: &str) -> QueueEntry {
+        QueueEntry::queued(
+            entry_id.to_string(),
+            Some("queue-test-batch".to_string()),
+            &[],
+            "/queue-test",
+        )
+    }
+
+    fn status_of(entry_id: &str) -> Option<QueueStatus> {
+        snapshot()
+            .into_iter()
+            .find(|e| e.entry_id == entry_id)
+            .map(|e| e.status)
+    }
+
+    #[test]
+    fn guard_keeps_entries_until_dropped() {
+        let guard = enqueue(vec![entry("queue-test-1"), entry("queue-test-2")]);
+        update("queue-test-1", |e| e.status = QueueStatus::Running);
+
+        assert_eq!(status_of("queue-test-1"), Some(QueueStatus::Running));
+        assert_eq!(status_of("queue-test-2"), Some(QueueStatus::Queued));
+
+        update("queue-test-1", |e| e.status = QueueStatus::Completed);
+        drop(guard);
+        assert_eq!(status_of("queue-test-1"), None);
+        assert_eq!(status_of("queue-test-2"), None);
+        // Finished entries can still be looked up
+        assert_eq!(
+            find("queue-test-1").map(|e| e.status),
+            Some(QueueStatus::Completed)
+        );
+        assert!(find("queue-test-3").is_none());
+    }
+
+    #[test]
+    fn entries_cancelled_before_their_turn_never_start() {
+        let guard = enqueue(vec![entry("queue-test-turn-1"), entry("queue-test-turn-2")]);
+        assert!(start("queue-test-turn-1"));
+        assert!(cancel_queued("queue-test-turn-1").is_none());
+
+        let cancelled = cancel_queued("queue-test-turn-2").unwrap();
+        assert_eq!(cancelled.status, QueueStatus::Cancelled);
+        assert_eq!(status_of("queue-test-turn-2"), None);
+        assert!(!start("queue-test-turn-2"));
+        assert_eq!(
+            find("queue-test-turn-2").map(|e| e.status),
+            Some(QueueStatus::Cancelled)
+        );
+        drop(guard);
+    }
+
+    #[test]
+    fn identical_requests_are_queued_once_at_a_time() {
+        let fingerprinted = |entry_id: &str| QueueEntry {
+            fingerprint: Some("queue-test-fingerprint".to_string()),
+            ..entry(entry_id)
+        };
+        let first = enqueue_unique(vec![fingerprinted("queue-test-unique-1")]).unwrap();
+        update("queue-test-unique-1", |e| e.status = QueueStatus::Running);
+
+        let existing = enqueue_unique(vec![fingerprinted("queue-test-unique-2")]).err();
+        assert_eq!(existing.as_deref(), Some("queue-test-unique-1"));
+        assert_eq!(status_of("queue-test-unique-2"), None);
+        // Entries without a fingerprint never match
+        drop(enqueue_unique(vec![entry("queue-test-unique-3")]).unwrap());
+
+        update("queue-test-unique-1", |e| e.status = QueueStatus::Completed);
+        let again = enqueue_unique(vec![fingerprinted("queue-test-unique-4")]);
+        assert!(again.is_ok());
+        drop(first);
+    }
+
+    #[test]
+    fn queues_mirrored_by_earlier_versions_are_taken_over() {
+        let mirrored = serde_json::to_string(&written_by::stamp(
+            FileFormat::Queue,
+            &Entries {
+                entries: &[entry("queue-test-saved-1")],
+            },
+        ))
+        .unwrap();
+        let saved: Saved = serde_json::from_str(&mirrored).unwrap();
+        assert_eq!(saved.into_entries()[0].entry_id, "queue-test-saved-1");
+
+        let legacy = serde_json::to_string(&[entry("queue-test-saved-2")]).unwrap();
+        let saved: Saved = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(saved.into_entries()[0].entry_id, "queue-test-saved-2");
+    }
+
+    #[test]
+    fn entries_left_unfinished_are_reported_as_interrupted() {
+        let mut done = entry("queue-test-left-1");
+        done.status = QueueStatus::Completed;
+        let mut running = entry("queue-test-left-2");
+        running.status = QueueStatus::Running;
+        take_over(vec![done, running]);
+
+        assert_eq!(
+            find("queue-test-left-1").map(|e| e.status),
+            Some(QueueStatus::Completed)
+        );
+        let interrupted = find("queue-test-left-2").unwrap();
+        assert_eq!(interrupted.status, QueueStatus::Failed);
+        assert_eq!(interrupted.error.unwrap().message.key, "job.interrupted");
+    }
+}
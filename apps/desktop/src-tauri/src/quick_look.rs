@@ -0,0 +1,363 @@
+//! Quick Look
+//!
+//! Tells the user whether a production's artifact is intact and roughly what
+//! it holds the moment its library card is clicked, before gigabytes are
+//! loaded into the viewer. quick_look answers within QUICK_LOOK_BUDGET with
+//! whatever it has gathered by then: the sidecar, whether the artifact exists
+//! and has the size its job recorded, and the splat count, bounds and
+//! preview thumbnail cached for it. Parts it could not gather in time are
+//! listed as pending.
+//!
+//! Splat counts and bounds are cached per artifact, with the version of the
+//! artifact they were read from: the checksum its sidecar stored, or else its
+//! modification time and size. Cached values of an earlier version are
+//! returned flagged stale. Pending parts, and stale or uncached ones, are
+//! computed in the background, and the whole quick look is sent again as
+//! quick-look-updated once they are done, so the UI never waits on a cold
+//! cache.
+
+use crate::cache::{self, Cache, Category};
+use crate::error::AppError;
+use crate::path_policy::PathPolicy;
+use crate::scheduler::{self, Pool};
+use crate::sidecar::{self, Sidecar};
+use crate::splat_preview::{self, Bounds};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::oneshot;
+
+/// How long quick_look gathers before it answers
+const QUICK_LOOK_BUDGET: Duration = Duration::from_millis(500);
+
+/// Side of the thumbnail rendered for a quick look, in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// Parts of a quick look that may arrive later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Part {
+    Sidecar,
+    Artifact,
+    /// Splat count, bounds and thumbnail
+    Splats,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArtifactState {
+    pub path: String,
+    pub exists: bool,
+    pub bytes: Option<u64>,
+    /// Whether its size is the one its job recorded; None when none was recorded
+    pub intact: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuickLook {
+    pub production_path: String,
+    pub sidecar: Option<Sidecar>,
+    pub artifact: Option<ArtifactState>,
+    pub splat_count: Option<usize>,
+    pub bounds: Option<Bounds>,
+    /// The preview in the thumbnail cache
+    pub thumbnail: Option<String>,
+    /// The splat count, bounds and thumbnail are of an earlier version of the artifact
+    pub stale: bool,
+    /// What is still being gathered; it follows in quick-look-updated
+    pub pending: Vec<Part>,
+}
+
+impl QuickLook {
+    fn new(production_path: &str) -> QuickLook {
+        QuickLook {
+            production_path: production_path.to_string(),
+            sidecar: None,
+            artifact: None,
+            splat_count: None,
+            bounds: None,
+            thumbnail: None,
+            stale: false,
+            pending: vec![Part::Sidecar, Part::Artifact, Part::Splats],
+        }
+    }
+
+    fn done(&mut self, part: Part) {
+        self.pending.retain(|p| *p != part);
+    }
+
+    fn complete(&self) -> bool {
+        self.pending.is_empty() && !self.stale
+    }
+}
+
+/// What is cached of an artifact's splats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Survey {
+    version: String,
+    splat_count: usize,
+    bounds: Option<Bounds>,
+    /// Name of the preview in the thumbnail cache
+    thumbnail: Option<String>,
+}
+
+struct Shared {
+    look: QuickLook,
+    /// quick_look answered before everything was gathered and fresh
+    answered_early: bool,
+}
+
+/// What can be told about the production at `production_path` within
+/// QUICK_LOOK_BUDGET; anything pending follows in quick-look-updated
+#[tauri::command]
+pub async fn quick_look(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    production_path: String,
+) -> Result<QuickLook, AppError> {
+    policy.check_existing(&production_path)?;
+    let cache = cache::app_cache(&app)?;
+    let shared = Arc::new(Mutex::new(Shared {
+        look: QuickLook::new(&production_path),
+        answered_early: false,
+    }));
+    let (gathered, gathered_rx) = oneshot::channel();
+
+    let task_shared = shared.clone();
+    tauri::async_runtime::spawn(async move {
+        let dir = PathBuf::from(&production_path);
+        let (gather_cache, gather_shared) = (cache.clone(), task_shared.clone());
+        let unsurveyed = scheduler::run_blocking(Pool::Io, move || {
+            gather(&dir, &gather_cache, &gather_shared)
+        })
+        .await
+        .ok()
+        .flatten();
+        gathered.send(()).ok();
+        if let Some(artifact) = unsurveyed {
+            let surveyed =
+                scheduler::run_blocking(Pool::Background, move || survey(&cache, &artifact))
+                    .await
+                    .and_then(|r| r);
+            let mut shared = task_shared.lock().unwrap();
+            match surveyed {
+                Ok(survey) => {
+                    shared.look.splat_count = Some(survey.splat_count);
+                    shared.look.bounds = survey.bounds;
+                    shared.look.thumbnail = survey.thumbnail;
+                    shared.look.stale = false;
+                }
+                Err(e) => eprintln!("Quick look of {} failed: {}", production_path, e),
+            }
+            shared.look.done(Part::Splats);
+        }
+        let shared = task_shared.lock().unwrap();
+        if shared.answered_early {
+            app.emit("quick-look-updated", &shared.look).ok();
+        }
+    });
+
+    tokio::time::timeout(QUICK_LOOK_BUDGET, gathered_rx)
+        .await
+        .ok();
+    let mut shared = shared.lock().unwrap();
+    shared.answered_early = !shared.look.complete();
+    Ok(shared.look.clone())
+}
+
+/// Fill in what is on disk and in the cache, part by part; the artifact to
+/// survey when its splats are uncached or stale
+fn gather(dir: &Path, cache: &Cache, shared: &Mutex<Shared>) -> Option<PathBuf> {
+    let sidecar = sidecar::read(dir).ok().flatten();
+    {
+        let mut shared = shared.lock().unwrap();
+        shared.look.sidecar = sidecar.clone();
+        shared.look.done(Part::Sidecar);
+    }
+
+    let found = sidecar::find_artifact(dir).ok();
+    let state = artifact_state(dir, sidecar.as_ref(), found.as_deref());
+    let Some(artifact) = found else {
+        let mut shared = shared.lock().unwrap();
+        shared.look.artifact = Some(state);
+        shared.look.done(Part::Artifact);
+        shared.look.done(Part::Splats);
+        return None;
+    };
+    {
+        let mut shared = shared.lock().unwrap();
+        shared.look.artifact = Some(state);
+        shared.look.done(Part::Artifact);
+    }
+
+    let version = splat_preview::version(&artifact).ok();
+    let cached = cache
+        .read(Category::Metadata, &survey_name(&artifact))
+        .and_then(|json| serde_json::from_slice::<Survey>(&json).ok());
+    let mut shared = shared.lock().unwrap();
+    let Some(cached) = cached else {
+        return Some(artifact);
+    };
+    let thumbnail = cached
+        .thumbnail
+        .as_deref()
+        .and_then(|name| cache.get(Category::Thumbnails, name));
+    shared.look.splat_count = Some(cached.splat_count);
+    shared.look.bounds = cached.bounds;
+    shared.look.thumbnail = thumbnail.as_ref().map(|t| t.to_string_lossy().to_string());
+    shared.look.stale = version.as_ref() != Some(&cached.version);
+    if shared.look.stale || thumbnail.is_none() {
+        return Some(artifact);
+    }
+    shared.look.done(Part::Splats);
+    None
+}
+
+/// Whether the artifact is there with the size its job recorded
+fn artifact_state(dir: &Path, sidecar: Option<&Sidecar>, found: Option<&Path>) -> ArtifactState {
+    let Some(found) = found else {
+        let path = sidecar
+            .map(|s| PathBuf::from(&s.artifact_path))
+            .unwrap_or_else(|| dir.join(sidecar::DEFAULT_ARTIFACT));
+        return ArtifactState {
+            path: path.to_string_lossy().to_string(),
+            exists: false,
+            bytes: None,
+            intact: None,
+        };
+    };
+    let bytes = std::fs::metadata(found).ok().map(|m| m.len());
+    let recorded = sidecar.and_then(|s| {
+        s.artifacts
+            .iter()
+            .find(|a| Path::new(&a.path).canonicalize().ok().as_deref() == Some(found))
+            .filter(|a| a.bytes > 0)
+    });
+    ArtifactState {
+        path: found.to_string_lossy().to_string(),
+        exists: true,
+        bytes,
+        intact: recorded.map(|a| Some(a.bytes) == bytes),
+    }
+}
+
+/// Read the artifact's splats, render its thumbnail and cache both
+fn survey(cache: &Cache, artifact: &Path) -> Result<Survey, AppError> {
+    let version = splat_preview::version(artifact)?;
+    let preview = splat_preview::preview(
+        cache,
+        artifact,
+        THUMBNAIL_SIZE,
+        None,
+        &AtomicBool::new(false),
+    )?;
+    let survey = Survey {
+        version,
+        splat_count: preview.total_splats,
+        bounds: preview.bounds,
+        thumbnail: Path::new(&preview.path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string()),
+    };
+    if let Ok(json) = serde_json::to_vec(&survey) {
+        cache
+            .write(Category::Metadata, &survey_name(artifact), &json)
+            .ok();
+    }
+    Ok(Survey {
+        thumbnail: Some(preview.path),
+        ..survey
+    })
+}
+
+/// The cache entry of an artifact's survey
+fn survey_name(artifact: &Path) -> String {
+    let digest = Sha256::digest(artifact.to_string_lossy().as_bytes());
+    let name: String = digest
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!("quick-look-{}.json", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifacts::Artifact;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::TempPaths;
+
+    fn splat(x: f32) -> [f32; 14] {
+        [
+            x, 0.0, 0.0, -3.0, -3.0, -3.0, 1.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 0.0,
+        ]
+    }
+
+    fn gathered(dir: &Path, cache: &Cache) -> (QuickLook, Option<PathBuf>) {
+        let shared = Mutex::new(Shared {
+            look: QuickLook::new(&dir.to_string_lossy()),
+            answered_early: false,
+        });
+        let unsurveyed = gather(dir, cache, &shared);
+        (shared.into_inner().unwrap().look, unsurveyed)
+    }
+
+    #[test]
+    fn a_cold_cache_leaves_the_splats_pending_until_surveyed() {
+        let paths = TempPaths::new();
+        let cache = cache::open(&paths.root().join("cache"), 64 * 1024 * 1024);
+        let dir = paths.root().join("production");
+        std::fs::create_dir_all(&dir).unwrap();
+        let artifact = dir.join("output.ply");
+        std::fs::write(&artifact, sample_ply(&[splat(0.0), splat(2.0)])).unwrap();
+        let bytes = std::fs::metadata(&artifact).unwrap().len();
+        let recorded = Artifact {
+            bytes,
+            ..Artifact::named(artifact.to_string_lossy().to_string(), None)
+        };
+        sidecar::write(
+            &dir,
+            &Sidecar {
+                artifact_path: recorded.path.clone(),
+                artifacts: vec![recorded],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let (look, pending) = gathered(&dir, &cache);
+        assert!(look.sidecar.is_some());
+        let state = look.artifact.unwrap();
+        assert_eq!((state.exists, state.intact), (true, Some(true)));
+        assert_eq!(look.pending, [Part::Splats]);
+        let artifact = pending.unwrap();
+
+        survey(&cache, &artifact).unwrap();
+        let (look, pending) = gathered(&dir, &cache);
+        assert!(pending.is_none() && look.complete());
+        assert_eq!(look.splat_count, Some(2));
+        assert_eq!(look.bounds.unwrap().max[0], 2.0);
+        assert!(look.thumbnail.is_some());
+
+        // A new artifact keeps showing the old values until surveyed again
+        std::fs::write(&artifact, sample_ply(&[splat(0.0), splat(1.0), splat(3.0)])).unwrap();
+        let (look, pending) = gathered(&dir, &cache);
+        assert!(look.stale && pending.is_some());
+        assert_eq!(look.splat_count, Some(2));
+        assert_eq!(look.artifact.unwrap().intact, Some(false));
+    }
+
+    #[test]
+    fn a_missing_artifact_has_nothing_to_survey() {
+        let paths = TempPaths::new();
+        let cache = cache::open(&paths.root().join("cache"), 64 * 1024 * 1024);
+        let (look, pending) = gathered(paths.root(), &cache);
+        assert!(pending.is_none() && look.pending.is_empty());
+        let state = look.artifact.unwrap();
+        assert!(!state.exists && state.bytes.is_none());
+    }
+}
@@ -0,0 +1,410 @@
+//! Recent Productions
+//!
+//! Tagging, notes and search over the recent-productions list stored in settings.
+
+use crate::artifacts::Artifact;
+use crate::error::AppError;
+use crate::hashing::{self, Reuse};
+use crate::integrity::{self, VerifyResult, VerifyStatus};
+use crate::reconcile;
+use crate::settings::{Persist, RecentProduction, SettingsStore};
+use crate::sidecar;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenedProduction {
+    pub production: RecentProduction,
+    /// The primary artifact, which the viewer opens
+    pub artifact_path: Option<String>,
+    /// The production's other artifacts, such as a web-optimized SPZ
+    pub other_artifacts: Vec<Artifact>,
+    /// Present when verify_artifacts_on_open is set and an artifact was found
+    pub verification: Option<VerifyResult>,
+    pub warning: Option<String>,
+}
+
+/// Resolve a recent production's artifact, checking its checksum if the settings ask to
+#[tauri::command]
+pub async fn open_recent_production(
+    app: AppHandle,
+    id: String,
+) -> Result<OpenedProduction, AppError> {
+    let cancel = integrity::start_verification();
+    open_recent(&app, &id, cancel, &mut |path, hashed, total| {
+        integrity::emit_progress(&app, path, hashed, total)
+    })
+    .await
+}
+
+/// Replace the tags of a recent production
+#[tauri::command]
+pub async fn set_production_tags(
+    app: AppHandle,
+    id: String,
+    tags: Vec<String>,
+) -> Result<RecentProduction, AppError> {
+    set_tags(&app, &id, &tags)
+}
+
+/// Replace the notes of a recent production
+#[tauri::command]
+pub async fn set_production_notes(
+    app: AppHandle,
+    id: String,
+    notes: String,
+) -> Result<RecentProduction, AppError> {
+    set_notes(&app, &id, notes)
+}
+
+/// Pin or unpin a recent production; the retention policy leaves pinned ones alone
+#[tauri::command]
+pub async fn set_production_pinned(
+    app: AppHandle,
+    id: String,
+    pinned: bool,
+) -> Result<RecentProduction, AppError> {
+    update_recent(&app, &id, Persist::Now, |recent| recent.pinned = pinned)
+}
+
+/// Search recent productions by name, tags, notes and path
+#[tauri::command]
+pub async fn search_productions(
+    app: AppHandle,
+    query: String,
+) -> Result<Vec<RecentProduction>, AppError> {
+    reconcile::check(&app).await;
+    search(&app, &query)
+}
+
+/// List every tag used across recent productions, most used first
+#[tauri::command]
+pub async fn list_all_tags(app: AppHandle) -> Result<Vec<String>, AppError> {
+    all_tags(&app)
+}
+
+pub async fn open_recent(
+    store: &impl SettingsStore,
+    id: &str,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(&str, u64, u64) + Send),
+) -> Result<OpenedProduction, AppError> {
+    let app_settings = store.settings();
+    let production = app_settings
+        .recent_productions
+        .into_iter()
+        .find(|r| r.id == id)
+        .ok_or_else(|| AppError::NotFound(format!("recent production {}", id)))?;
+
+    // A production without an artifact still opens; the frontend shows it as empty
+    let artifact = sidecar::find_artifact(Path::new(&production.path)).ok();
+    let artifact_path = artifact.as_ref().map(|a| a.to_string_lossy().to_string());
+    let other_artifacts = artifact
+        .as_ref()
+        .map(|a| sidecar::other_artifacts(Path::new(&production.path), a))
+        .unwrap_or_default();
+
+    let (verification, warning) = match &artifact {
+        Some(artifact) if app_settings.verify_artifacts_on_open => {
+            verify_on_open(artifact, cancel, progress).await?
+        }
+        _ => (None, None),
+    };
+
+    Ok(OpenedProduction {
+        production,
+        artifact_path,
+        other_artifacts,
+        verification,
+        warning,
+    })
+}
+
+/// Check an artifact against its recorded checksum, with the warning a
+/// mismatch gives
+pub async fn verify_on_open(
+    artifact: &Path,
+    cancel: &AtomicBool,
+    progress: &mut (dyn FnMut(&str, u64, u64) + Send),
+) -> Result<(Option<VerifyResult>, Option<String>), AppError> {
+    let path = artifact.to_string_lossy().to_string();
+    let verification = integrity::verify(artifact, cancel, &mut |hashed, total| {
+        progress(&path, hashed, total)
+    })
+    .await?;
+    let warning = (verification.status == VerifyStatus::Mismatch).then(|| {
+        format!(
+            "{} does not match the checksum recorded when it was produced; it may be truncated or corrupted",
+            verification.artifact_path
+        )
+    });
+    Ok((Some(verification), warning))
+}
+
+pub fn set_tags(
+    store: &impl SettingsStore,
+    id: &str,
+    tags: &[String],
+) -> Result<RecentProduction, AppError> {
+    update_recent(store, id, Persist::Now, |recent| {
+        recent.tags = normalize_tags(tags)
+    })
+}
+
+pub fn set_notes(
+    store: &impl SettingsStore,
+    id: &str,
+    notes: String,
+) -> Result<RecentProduction, AppError> {
+    // Notes are saved as the user types
+    update_recent(store, id, Persist::Debounced, |recent| recent.notes = notes)
+}
+
+pub fn search(store: &impl SettingsStore, query: &str) -> Result<Vec<RecentProduction>, AppError> {
+    let recents = store.settings().recent_productions;
+    let query = query.trim().to_lowercase();
+
+    let mut scored: Vec<(u32, RecentProduction)> = recents
+        .into_iter()
+        .map(|recent| (relevance(&recent, &query), recent))
+        .filter(|(score, _)| query.is_empty() || *score > 0)
+        .collect();
+
+    // Most relevant first, then most recently opened
+    scored.sort_by(|(score_a, a), (score_b, b)| {
+        score_b
+            .cmp(score_a)
+            .then_with(|| b.last_opened.cmp(&a.last_opened))
+    });
+
+    Ok(scored.into_iter().map(|(_, recent)| recent).collect())
+}
+
+pub fn all_tags(store: &impl SettingsStore) -> Result<Vec<String>, AppError> {
+    let recents = store.settings().recent_productions;
+
+    let mut counts: Vec<(String, usize)> = vec![];
+    for tag in recents.iter().flat_map(|r| r.tags.iter()) {
+        match counts.iter_mut().find(|(t, _)| t == tag) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((tag.clone(), 1)),
+        }
+    }
+    counts.sort_by_key(|(tag, count)| (Reverse(*count), tag.clone()));
+
+    Ok(counts.into_iter().map(|(tag, _)| tag).collect())
+}
+
+pub fn update_recent(
+    store: &impl SettingsStore,
+    id: &str,
+    persist: Persist,
+    update: impl FnOnce(&mut RecentProduction),
+) -> Result<RecentProduction, AppError> {
+    store.update_settings(persist, |app_settings| {
+        let recent = app_settings
+            .recent_productions
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("recent production {}", id)))?;
+
+        update(recent);
+        Ok(recent.clone())
+    })
+}
+
+/// Trim, lowercase and dedupe tags, dropping empty ones
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = vec![];
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+// Weighted match score; 0 means no match
+fn relevance(recent: &RecentProduction, query: &str) -> u32 {
+    if query.is_empty() {
+        return 0;
+    }
+
+    let name = recent.name.to_lowercase();
+    let mut score = 0;
+    if name == query {
+        score += 8;
+    } else if name.starts_with(query) {
+        score += 6;
+    } else if name.contains(query) {
+        score += 4;
+    }
+    if recent.tags.iter().any(|t| t == query) {
+        score += 3;
+    } else if recent.tags.iter().any(|t| t.contains(query)) {
+        score += 2;
+    }
+    if recent.notes.to_lowercase().contains(query) {
+        score += 1;
+    }
+    if recent.path.to_lowercase().contains(query) {
+        score += 1;
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::AppSettings;
+    use crate::sidecar::{ProductionSource, Sidecar};
+
+    fn recent(
+        id: &str,
+        name: &str,
+        tags: &[&str],
+        notes: &str,
+        last_opened: &str,
+    ) -> RecentProduction {
+        RecentProduction {
+            id: id.to_string(),
+            name: name.to_string(),
+            path: format!("/productions/{}", id),
+            last_opened: last_opened.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            notes: notes.to_string(),
+            imported: false,
+            missing: false,
+            pinned: false,
+        }
+    }
+
+    fn store() -> MemorySettings {
+        MemorySettings::with(AppSettings {
+            recent_productions: vec![
+                recent("a", "Harbour", &["outdoor", "drone"], "", "2024-01-01"),
+                recent("b", "Drone test", &[], "", "2024-02-01"),
+                recent(
+                    "c",
+                    "Studio",
+                    &["indoor"],
+                    "shot with a drone",
+                    "2024-03-01",
+                ),
+                recent("d", "Warehouse", &["indoor"], "", "2024-04-01"),
+            ],
+            ..Default::default()
+        })
+    }
+
+    fn ids(recents: &[RecentProduction]) -> Vec<&str> {
+        recents.iter().map(|r| r.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn open_recent_warns_on_checksum_mismatch() {
+        let paths = TempPaths::new();
+        let dir = paths.root().join("production");
+        std::fs::create_dir_all(&dir).unwrap();
+        let artifact = dir.join("output.ply");
+        std::fs::write(&artifact, b"ply\nfull contents").unwrap();
+        let cancel = AtomicBool::new(false);
+        let sha256 = hashing::sha256(&artifact, Reuse::Cached, &cancel, &mut |_, _| {})
+            .await
+            .unwrap();
+        let sidecar = Sidecar {
+            job_id: "job-1".to_string(),
+            production_dir: dir.to_string_lossy().to_string(),
+            artifact_path: artifact.to_string_lossy().to_string(),
+            preset: "balanced".to_string(),
+            videos: vec![],
+            created_at: 0,
+            artifact_sha256: Some(sha256),
+            capture_type: Default::default(),
+            archive: None,
+            preview: false,
+            input_sha256: vec![],
+            command: None,
+            training: None,
+            derivatives: vec![],
+            source: ProductionSource::Job,
+            imported_from: None,
+            resolution_normalization: None,
+            camera_groups: vec![],
+            artifacts: vec![],
+        };
+        sidecar::write(&dir, &sidecar).unwrap();
+        std::fs::write(&artifact, b"ply\nfull").unwrap();
+
+        let mut production = recent("p", "Production", &[], "", "2024-01-01");
+        production.path = dir.to_string_lossy().to_string();
+        let mut store = MemorySettings::with(AppSettings {
+            recent_productions: vec![production],
+            ..Default::default()
+        });
+
+        let opened = open_recent(&store, "p", &cancel, &mut |_, _, _| {})
+            .await
+            .unwrap();
+        assert!(opened.artifact_path.is_some());
+        assert!(opened.verification.is_none());
+        assert!(opened.warning.is_none());
+
+        let mut app_settings = store.settings();
+        app_settings.verify_artifacts_on_open = true;
+        store = MemorySettings::with(app_settings);
+        let opened = open_recent(&store, "p", &cancel, &mut |_, _, _| {})
+            .await
+            .unwrap();
+        assert_eq!(
+            opened.verification.map(|v| v.status),
+            Some(VerifyStatus::Mismatch)
+        );
+        assert!(opened.warning.is_some());
+    }
+
+    #[test]
+    fn set_tags_normalizes_and_persists() {
+        let store = store();
+        let tags = vec![
+            " Outdoor ".to_string(),
+            "outdoor".to_string(),
+            "".to_string(),
+        ];
+        let updated = set_tags(&store, "a", &tags).unwrap();
+
+        assert_eq!(updated.tags, vec!["outdoor"]);
+        assert_eq!(store.settings().recent_productions[0].tags, vec!["outdoor"]);
+    }
+
+    #[test]
+    fn set_notes_rejects_unknown_production() {
+        let err = set_notes(&store(), "missing", "notes".to_string()).unwrap_err();
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[test]
+    fn search_ranks_name_over_tags_over_notes() {
+        let results = search(&store(), "Drone").unwrap();
+        assert_eq!(ids(&results), vec!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn empty_search_lists_everything_most_recent_first() {
+        let results = search(&store(), "  ").unwrap();
+        assert_eq!(ids(&results), vec!["d", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn all_tags_orders_by_use_then_name() {
+        assert_eq!(
+            all_tags(&store()).unwrap(),
+            vec!["indoor", "drone", "outdoor"]
+        );
+    }
+}
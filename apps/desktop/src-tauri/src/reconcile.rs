@@ -0,0 +1,399 @@
+//! Library Reconciliation
+//!
+//! Productions get deleted or moved in Explorer and Finder, leaving history
+//! records and recents that point at nothing. A reconciliation stats every
+//! artifact the history refers to and every recent production's directory and
+//! flags the ones that are gone, clearing the flag again when they come back.
+//! The full pass run by reconcile_library also looks for a moved artifact: a
+//! file of the same name a few levels around where it was, confirmed by the
+//! SHA-256 recorded when it was produced. A confirmed move rebases everything
+//! that pointed into the old production directory, as move_production would
+//! have. The history and recents views run the cheap pass, which only stats.
+//! Flagged records are kept until purge_missing_records removes them.
+
+use crate::artifacts;
+use crate::error::AppError;
+use crate::fsutil::rebase;
+use crate::hashing::{self, Reuse};
+use crate::history;
+use crate::platform::PathProvider;
+use crate::settings::{Persist, SettingsStore};
+use crate::sidecar;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use tauri::AppHandle;
+
+/// Directories above a missing artifact's production that are searched from
+const SEARCH_LEVELS: usize = 2;
+
+/// Directories below the search root that are looked into
+const SEARCH_DEPTH: usize = SEARCH_LEVELS + 2;
+
+/// Directory entries looked at per missing artifact
+const MAX_ENTRIES: usize = 5_000;
+
+/// Same-named files hashed per missing artifact
+const MAX_CANDIDATES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Moved {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReconcileSummary {
+    /// Artifacts and production directories looked at
+    pub checked: usize,
+    /// Newly found to be gone
+    pub missing: Vec<String>,
+    /// Production directories found elsewhere, whose records now point there
+    pub moved: Vec<Moved>,
+    /// Flagged as gone earlier and back now
+    pub restored: Vec<String>,
+}
+
+impl ReconcileSummary {
+    fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.moved.is_empty() && self.restored.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PurgeSummary {
+    pub records: usize,
+    pub recents: usize,
+}
+
+/// Flag what is gone from the history and recents; with `find_moves` also
+/// follow moved artifacts by their checksum
+pub async fn reconcile(
+    paths: &impl PathProvider,
+    store: &impl SettingsStore,
+    find_moves: bool,
+) -> Result<ReconcileSummary, AppError> {
+    let mut records = history::load(paths)?;
+    let mut summary = ReconcileSummary::default();
+
+    // Another production's identical artifact is not where this one went
+    let present: Vec<PathBuf> = records
+        .iter()
+        .filter_map(|r| r.artifact_path.as_deref().map(PathBuf::from))
+        .filter(|p| p.is_file())
+        .collect();
+    // Several records share an artifact when a production was run again
+    let mut found: HashMap<String, Option<PathBuf>> = HashMap::new();
+    let mut moves: Vec<(PathBuf, PathBuf)> = vec![];
+    for record in &records {
+        let (Some(artifact), Some(sha256)) = (&record.artifact_path, &record.artifact_sha256)
+        else {
+            continue;
+        };
+        if !find_moves || Path::new(artifact).is_file() {
+            continue;
+        }
+        if found.contains_key(artifact) {
+            continue;
+        }
+        let moved = find_move(Path::new(artifact), sha256, &present).await;
+        if let Some(to) = &moved {
+            let dir = Path::new(&record.output_dir);
+            if let Some(new_dir) = moved_dir(Path::new(artifact), to, dir) {
+                if !moves.iter().any(|(from, _)| from == dir) {
+                    moves.push((dir.to_path_buf(), new_dir));
+                }
+            }
+        }
+        found.insert(artifact.clone(), moved);
+    }
+
+    for (from, to) in &moves {
+        for record in &mut records {
+            if let Some(path) = rebase(&record.output_dir, from, to) {
+                record.output_dir = path;
+            }
+            if let Some(path) = record
+                .artifact_path
+                .as_deref()
+                .and_then(|p| rebase(p, from, to))
+            {
+                record.artifact_path = Some(path);
+            }
+            artifacts::rebase(&mut record.artifacts, from, to);
+        }
+        if let Ok(Some(mut meta)) = sidecar::read(to) {
+            meta.production_dir = to.to_string_lossy().to_string();
+            if let Some(path) = rebase(&meta.artifact_path, from, to) {
+                meta.artifact_path = path;
+            }
+            artifacts::rebase(&mut meta.artifacts, from, to);
+            sidecar::write(to, &meta)?;
+        }
+        summary.moved.push(Moved {
+            from: from.to_string_lossy().to_string(),
+            to: to.to_string_lossy().to_string(),
+        });
+    }
+
+    let mut changed = !moves.is_empty();
+    for record in &mut records {
+        let Some(artifact) = &record.artifact_path else {
+            continue;
+        };
+        summary.checked += 1;
+        let missing = !Path::new(artifact).is_file();
+        if missing != record.missing {
+            record.missing = missing;
+            changed = true;
+            let list = if missing {
+                &mut summary.missing
+            } else {
+                &mut summary.restored
+            };
+            if !list.contains(artifact) {
+                list.push(artifact.clone());
+            }
+        }
+    }
+    if changed {
+        history::save(paths, &records)?;
+    }
+
+    let recents = store.update_settings(Persist::Now, |s| {
+        let mut recents = ReconcileSummary::default();
+        for recent in &mut s.recent_productions {
+            for (from, to) in &moves {
+                if let Some(path) = rebase(&recent.path, from, to) {
+                    recent.path = path;
+                }
+            }
+            recents.checked += 1;
+            let missing = !Path::new(&recent.path).is_dir();
+            if missing != recent.missing {
+                recent.missing = missing;
+                if missing {
+                    recents.missing.push(recent.path.clone());
+                } else {
+                    recents.restored.push(recent.path.clone());
+                }
+            }
+        }
+        Ok::<_, String>(recents)
+    })?;
+    summary.checked += recents.checked;
+    for path in recents.missing {
+        if !summary.missing.contains(&path) {
+            summary.missing.push(path);
+        }
+    }
+    for path in recents.restored {
+        if !summary.restored.contains(&path) {
+            summary.restored.push(path);
+        }
+    }
+    Ok(summary)
+}
+
+/// Where the production directory went, given where its artifact went
+fn moved_dir(artifact: &Path, moved_to: &Path, output_dir: &Path) -> Option<PathBuf> {
+    let inside = artifact.strip_prefix(output_dir).ok()?;
+    let mut dir = moved_to;
+    for _ in inside.components() {
+        dir = dir.parent()?;
+    }
+    moved_to.ends_with(inside).then(|| dir.to_path_buf())
+}
+
+/// A file of the same name near `artifact` with its recorded checksum
+async fn find_move(artifact: &Path, sha256: &str, present: &[PathBuf]) -> Option<PathBuf> {
+    let name = artifact.file_name()?;
+    // The highest directory still there, at most SEARCH_LEVELS above the production
+    let root = artifact
+        .ancestors()
+        .skip(2)
+        .take(SEARCH_LEVELS)
+        .filter(|dir| dir.is_dir())
+        .last()?;
+
+    let mut candidates = vec![];
+    let mut entries = 0;
+    let mut pending = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(read) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read.flatten() {
+            entries += 1;
+            if entries > MAX_ENTRIES || candidates.len() >= MAX_CANDIDATES {
+                break;
+            }
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            match entry.file_type() {
+                Ok(t) if t.is_dir() && !hidden && depth < SEARCH_DEPTH => {
+                    pending.push((path, depth + 1))
+                }
+                Ok(t) if t.is_file() && entry.file_name() == name && !present.contains(&path) => {
+                    candidates.push(path)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let cancel = AtomicBool::new(false);
+    for candidate in candidates {
+        let hash = hashing::sha256(&candidate, Reuse::Cached, &cancel, &mut |_, _| {}).await;
+        if hash.is_ok_and(|h| h == sha256) {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+/// Remove the history records and recents flagged as gone
+pub fn purge_missing(
+    paths: &impl PathProvider,
+    store: &impl SettingsStore,
+) -> Result<PurgeSummary, AppError> {
+    let mut records = history::load(paths)?;
+    let before = records.len();
+    records.retain(|r| !r.missing);
+    let purged = before - records.len();
+    if purged > 0 {
+        history::save(paths, &records)?;
+    }
+    let recents = store.update_settings(Persist::Now, |s| {
+        let before = s.recent_productions.len();
+        s.recent_productions.retain(|r| !r.missing);
+        Ok::<_, String>(before - s.recent_productions.len())
+    })?;
+    Ok(PurgeSummary {
+        records: purged,
+        recents,
+    })
+}
+
+/// Flag deleted artifacts and productions and follow moved ones
+#[tauri::command]
+pub async fn reconcile_library(app: AppHandle) -> Result<ReconcileSummary, AppError> {
+    reconcile(&app, &app, true).await
+}
+
+/// The cheap pass for the history and recents views, which show the flags
+pub async fn check(app: &AppHandle) {
+    match reconcile(app, app, false).await {
+        Ok(summary) if !summary.is_empty() => eprintln!("Reconciled the library: {:?}", summary),
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to reconcile the library: {}", e),
+    }
+}
+
+/// Remove history records and recents whose artifacts or directories are gone
+#[tauri::command]
+pub async fn purge_missing_records(app: AppHandle) -> Result<PurgeSummary, AppError> {
+    purge_missing(&app, &app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::tests::job;
+    use crate::history::JobStatus;
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::{AppSettings, RecentProduction};
+    use sha2::{Digest, Sha256};
+
+    fn production(paths: &TempPaths, name: &str, contents: &[u8]) -> (PathBuf, String) {
+        let dir = paths.root().join("productions").join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("output.ply"), contents).unwrap();
+        let sha256 = Sha256::digest(contents)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        (dir, sha256)
+    }
+
+    fn recent(dir: &Path) -> RecentProduction {
+        RecentProduction {
+            id: dir.file_name().unwrap().to_string_lossy().to_string(),
+            name: String::new(),
+            path: dir.to_string_lossy().to_string(),
+            last_opened: String::new(),
+            tags: vec![],
+            notes: String::new(),
+            imported: false,
+            missing: false,
+            pinned: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn moves_are_followed_and_deletions_flagged_until_purged() {
+        let paths = TempPaths::new();
+        let (harbour, harbour_sha) = production(&paths, "harbour", b"harbour");
+        let (pier, pier_sha) = production(&paths, "pier", b"pier");
+        // Another production's artifact of the same name is not mistaken for it
+        production(&paths, "beach", b"beach");
+        let mut records = vec![];
+        for (id, dir, sha) in [("h", &harbour, harbour_sha), ("p", &pier, pier_sha)] {
+            records.push(history::JobRecord {
+                output_dir: dir.to_string_lossy().to_string(),
+                artifact_path: Some(dir.join("output.ply").to_string_lossy().to_string()),
+                artifact_sha256: Some(sha),
+                ..job(id, JobStatus::Completed)
+            });
+        }
+        history::save(&paths, &records).unwrap();
+        let store = MemorySettings::with(AppSettings {
+            recent_productions: vec![recent(&harbour), recent(&pier)],
+            ..Default::default()
+        });
+
+        let archive = paths.root().join("productions").join("2024");
+        std::fs::create_dir_all(&archive).unwrap();
+        let moved = archive.join("harbour-final");
+        std::fs::rename(&harbour, &moved).unwrap();
+        std::fs::remove_dir_all(&pier).unwrap();
+
+        let cheap = reconcile(&paths, &store, false).await.unwrap();
+        assert_eq!(cheap.checked, 4);
+        assert_eq!(cheap.missing.len(), 4);
+        assert!(cheap.moved.is_empty());
+
+        let full = reconcile(&paths, &store, true).await.unwrap();
+        let moved_path = moved.to_string_lossy().to_string();
+        assert_eq!(
+            full.moved,
+            [Moved {
+                from: harbour.to_string_lossy().to_string(),
+                to: moved_path.clone(),
+            }]
+        );
+        assert_eq!(full.restored.len(), 2);
+        let records = history::load(&paths).unwrap();
+        assert_eq!(records[0].output_dir, moved_path);
+        assert!(!records[0].missing);
+        assert!(records[1].missing);
+        let recents = store.settings().recent_productions;
+        assert_eq!(
+            (recents[0].path.as_str(), recents[0].missing),
+            (moved_path.as_str(), false)
+        );
+        assert!(recents[1].missing);
+
+        let purged = purge_missing(&paths, &store).unwrap();
+        assert_eq!(
+            purged,
+            PurgeSummary {
+                records: 1,
+                recents: 1
+            }
+        );
+        assert_eq!(history::load(&paths).unwrap().len(), 1);
+        assert_eq!(store.settings().recent_productions.len(), 1);
+    }
+}
@@ -0,0 +1,214 @@
+//! Registration Check
+//!
+//! A reconstruction where only a handful of frames registered trains for an
+//! hour into a useless splat. At the end of sparse reconstruction the CLI
+//! prints how many images registered, in total and per clip. When fewer than
+//! min_registered_percent of them did, the run is stopped before training
+//! and the job fails with aborted_low_registration, naming the clips none of
+//! whose images registered, unless the request carried
+//! continue_on_poor_registration. A paused run is only stopped once it is
+//! resumed, and a cancelled one stays cancelled.
+//!
+//! The numbers go into the job's history whatever the outcome, so footage
+//! quality can be compared with results.
+
+use crate::runner::{ItemRef, RegistrationSummary};
+use crate::volume_watch::RunState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Share of the images that must register unless a job says otherwise
+pub const DEFAULT_MIN_REGISTERED_PERCENT: u32 = 30;
+
+/// How often the registration is looked for while the CLI runs
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// The last registration reported by each tracked job
+static SEEN: Mutex<BTreeMap<String, Option<Registration>>> = Mutex::new(BTreeMap::new());
+
+/// How many of a job's images COLMAP registered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Registration {
+    pub registered: usize,
+    pub total: usize,
+    /// Clips none of whose images registered
+    #[serde(default)]
+    pub unregistered_clips: Vec<String>,
+}
+
+impl Registration {
+    /// The CLI's summary, with the clips it names matched to the job's `videos`
+    pub fn of(summary: &RegistrationSummary, videos: &[String]) -> Registration {
+        let unregistered_clips = summary
+            .clips
+            .iter()
+            .filter(|c| c.registered == 0)
+            .map(|c| clip_of(&c.item, videos))
+            .collect();
+        Registration {
+            registered: summary.registered,
+            total: summary.total,
+            unregistered_clips,
+        }
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.registered as f64 * 100.0 / self.total as f64
+    }
+
+    /// Too few images registered for a job holding out for `min_percent`
+    pub fn is_poor(&self, min_percent: u32) -> bool {
+        self.percent() < min_percent as f64
+    }
+}
+
+/// Keeps a job's registration for as long as it lives
+pub struct RegistrationGuard {
+    job_id: String,
+}
+
+impl RegistrationGuard {
+    /// The last registration the job reported
+    pub fn finish(self) -> Option<Registration> {
+        SEEN.lock().unwrap().remove(&self.job_id).flatten()
+    }
+}
+
+impl Drop for RegistrationGuard {
+    fn drop(&mut self) {
+        SEEN.lock().unwrap().remove(&self.job_id);
+    }
+}
+
+/// Start keeping the registration `job_id` reports
+pub fn track(job_id: &str) -> RegistrationGuard {
+    SEEN.lock().unwrap().insert(job_id.to_string(), None);
+    RegistrationGuard {
+        job_id: job_id.to_string(),
+    }
+}
+
+/// Keep the registration of a tracked job, replacing an earlier attempt's
+pub fn record(job_id: &str, registration: Registration) {
+    if let Some(seen) = SEEN.lock().unwrap().get_mut(job_id) {
+        *seen = Some(registration);
+    }
+}
+
+fn latest(job_id: &str) -> Option<Registration> {
+    SEEN.lock().unwrap().get(job_id).cloned().flatten()
+}
+
+/// Wait for `job_id` to report a registration below `min_percent` and return
+/// it; never returns for a good one, without a minimum or once the job is
+/// cancelled, so it is meant to be raced against the run
+pub async fn watch(
+    job_id: &str,
+    state: &RunState,
+    min_percent: Option<u32>,
+    cancel: &AtomicBool,
+    interval: Duration,
+) -> Registration {
+    let Some(min_percent) = min_percent else {
+        return std::future::pending().await;
+    };
+    loop {
+        tokio::time::sleep(interval).await;
+        if cancel.load(Ordering::SeqCst) {
+            return std::future::pending().await;
+        }
+        // Decided once the user resumes the run
+        if state.suspended.load(Ordering::SeqCst) {
+            continue;
+        }
+        match latest(job_id) {
+            Some(registration) if registration.is_poor(min_percent) => return registration,
+            Some(_) => return std::future::pending().await,
+            None => {}
+        }
+    }
+}
+
+// The job's clip an item of the summary refers to, or the CLI's own name for it
+fn clip_of(item: &ItemRef, videos: &[String]) -> String {
+    match item {
+        ItemRef::Index(i) => videos.get(*i).cloned().unwrap_or_else(|| i.to_string()),
+        ItemRef::Name(name) => {
+            let stem = Path::new(name).file_stem();
+            videos
+                .iter()
+                .find(|v| v == &name || Path::new(v).file_stem() == stem)
+                .cloned()
+                .unwrap_or_else(|| name.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ClipRegistration;
+
+    fn summary(registered: usize, total: usize) -> RegistrationSummary {
+        RegistrationSummary {
+            registered,
+            total,
+            clips: vec![
+                ClipRegistration {
+                    item: ItemRef::Index(0),
+                    registered,
+                    total: total / 2,
+                },
+                ClipRegistration {
+                    item: ItemRef::Name("frames/pier-b".to_string()),
+                    registered: 0,
+                    total: total / 2,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn few_registered_images_are_poor() {
+        let videos = [
+            "/clips/pier-a.mp4".to_string(),
+            "/clips/pier-b.mp4".to_string(),
+        ];
+        let registration = Registration::of(&summary(12, 400), &videos);
+        assert_eq!(registration.percent(), 3.0);
+        assert!(registration.is_poor(DEFAULT_MIN_REGISTERED_PERCENT));
+        assert_eq!(registration.unregistered_clips, ["/clips/pier-b.mp4"]);
+        assert!(!Registration::of(&summary(200, 400), &videos).is_poor(30));
+        assert!(!Registration::of(&summary(0, 400), &videos).is_poor(0));
+    }
+
+    #[tokio::test]
+    async fn a_poor_registration_stops_the_run_once_it_is_resumed() {
+        let job_id = "registration-job";
+        let guard = track(job_id);
+        let state = RunState::default();
+        state.suspended.store(true, Ordering::SeqCst);
+        let cancel = AtomicBool::new(false);
+        record(job_id, Registration::of(&summary(12, 400), &[]));
+
+        let interval = Duration::from_millis(5);
+        let watch = watch(job_id, &state, Some(30), &cancel, interval);
+        tokio::pin!(watch);
+        let paused = tokio::time::timeout(Duration::from_millis(50), &mut watch).await;
+        assert!(paused.is_err());
+
+        state.suspended.store(false, Ordering::SeqCst);
+        let poor = tokio::time::timeout(Duration::from_millis(200), watch)
+            .await
+            .unwrap();
+        assert_eq!(poor.registered, 12);
+        assert_eq!(guard.finish().map(|r| r.total), Some(400));
+    }
+}
@@ -0,0 +1,365 @@
+//! Intermediate Retention
+//!
+//! Every production keeps its frames and COLMAP data until someone deletes
+//! them, so disk usage only grows. With retention on, the intermediates of a
+//! recent production are kept while it was used within keep_days or is among
+//! the keep_last most recently used, whichever keeps more, and are archived or
+//! moved to the trash after that. Pinned productions, missing ones and any a
+//! queued or running job writes to are left alone. preview_retention lists
+//! what would go without touching anything.
+//!
+//! The policy is applied a minute after the app starts and then daily. Each
+//! production it acts on is logged to app.log and can be undone for the rest
+//! of the session, like the archive or delete it stands in for.
+
+use crate::archive;
+use crate::disk::format_bytes;
+use crate::error::AppError;
+use crate::job_log;
+use crate::jobs;
+use crate::messages::Message;
+use crate::naming;
+use crate::productions::{self, dir_size, DeleteScope, INTERMEDIATE_ENTRIES};
+use crate::queue::{self, QueueEntry, QueueStatus};
+use crate::settings::{RecentProduction, SettingsStore};
+use crate::sidecar;
+use crate::undo::{self, Restore};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::AppHandle;
+
+pub const DEFAULT_KEEP_DAYS: u32 = 30;
+pub const DEFAULT_KEEP_LAST: u32 = 10;
+
+/// How long after startup the policy is first applied, so it stays out of the way of opening the app
+const FIRST_RUN_DELAY: Duration = Duration::from_secs(60);
+
+const INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+// Held while the policy is applied, so the daily run and run_retention never overlap
+static APPLYING: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// What happens to intermediates the policy lets go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionAction {
+    /// Packed into the production's archive, as archive_production_intermediates does
+    #[default]
+    Archive,
+    /// Moved to the OS trash
+    Trash,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetentionSettings {
+    /// Off unless the user turns it on
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_keep_days")]
+    pub keep_days: u32,
+    #[serde(default = "default_keep_last")]
+    pub keep_last: u32,
+    #[serde(default)]
+    pub action: RetentionAction,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keep_days: DEFAULT_KEEP_DAYS,
+            keep_last: DEFAULT_KEEP_LAST,
+            action: RetentionAction::default(),
+        }
+    }
+}
+
+fn default_keep_days() -> u32 {
+    DEFAULT_KEEP_DAYS
+}
+
+fn default_keep_last() -> u32 {
+    DEFAULT_KEEP_LAST
+}
+
+/// A production whose intermediates the policy lets go
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionCandidate {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    /// When it was last opened, or its directory last changed if it never was
+    pub last_used: String,
+    /// Size of its intermediates
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionPreview {
+    pub action: RetentionAction,
+    /// Least recently used first
+    pub candidates: Vec<RetentionCandidate>,
+    pub total_bytes: u64,
+}
+
+impl RetentionPreview {
+    fn of(action: RetentionAction, candidates: Vec<RetentionCandidate>) -> Self {
+        let total_bytes = candidates.iter().map(|c| c.bytes).sum();
+        Self {
+            action,
+            candidates,
+            total_bytes,
+        }
+    }
+}
+
+/// What the retention policy in the settings would archive or trash now
+#[tauri::command]
+pub async fn preview_retention(app: AppHandle) -> Result<RetentionPreview, AppError> {
+    let policy = app.settings().retention;
+    let recents = app.settings().recent_productions;
+    let now = job_log::unix_timestamp();
+    let candidates = candidates(&recents, &policy, now, &queue::snapshot());
+    Ok(RetentionPreview::of(policy.action, candidates))
+}
+
+/// Apply the retention policy in the settings now, whether or not it is
+/// enabled; returns the productions it acted on
+#[tauri::command]
+pub async fn run_retention(app: AppHandle) -> Result<RetentionPreview, AppError> {
+    let policy = app.settings().retention;
+    Ok(RetentionPreview::of(policy.action, apply(&app).await))
+}
+
+/// Apply the policy a minute from now and daily after that, while it is enabled
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_RUN_DELAY).await;
+        loop {
+            if app.settings().retention.enabled {
+                apply(&app).await;
+            }
+            tokio::time::sleep(INTERVAL).await;
+        }
+    });
+}
+
+async fn apply(app: &AppHandle) -> Vec<RetentionCandidate> {
+    let _applying = APPLYING.lock().await;
+    let policy = app.settings().retention;
+    let recents = app.settings().recent_productions;
+    let now = job_log::unix_timestamp();
+    let mut applied = vec![];
+    for candidate in candidates(&recents, &policy, now, &queue::snapshot()) {
+        let dir = PathBuf::from(&candidate.path);
+        // A job may have been queued since the candidates were listed
+        if busy(&dir, &queue::snapshot()) {
+            continue;
+        }
+        let name = dir.file_name().unwrap_or_default().to_string_lossy();
+        let (done, result) = match policy.action {
+            RetentionAction::Archive => (
+                "Archived",
+                archive::archive_intermediates(app.clone(), candidate.path.clone())
+                    .await
+                    .map(|_| {
+                        let message =
+                            Message::new("undo.archive_intermediates").with("name", &name);
+                        undo::record(app, message, Restore::Archived { dir: dir.clone() });
+                    }),
+            ),
+            RetentionAction::Trash => (
+                "Moved to the trash",
+                productions::move_to_trash(app, &dir, DeleteScope::Intermediates).map(|_| ()),
+            ),
+        };
+        match result {
+            Ok(()) => {
+                job_log::app_line(
+                    app,
+                    &format!(
+                        "Retention: {} the intermediates of {} ({}, last used {})",
+                        done,
+                        candidate.path,
+                        format_bytes(candidate.bytes),
+                        candidate.last_used
+                    ),
+                );
+                applied.push(candidate);
+            }
+            Err(e) => job_log::app_line(
+                app,
+                &format!(
+                    "Retention: left the intermediates of {} as they were: {}",
+                    candidate.path, e
+                ),
+            ),
+        }
+    }
+    applied
+}
+
+/// The recent productions whose intermediates `policy` lets go at `now`, least
+/// recently used first
+fn candidates(
+    recents: &[RecentProduction],
+    policy: &RetentionSettings,
+    now: u64,
+    queued: &[QueueEntry],
+) -> Vec<RetentionCandidate> {
+    let cutoff = naming::iso8601(now.saturating_sub(policy.keep_days as u64 * DAY_SECS));
+    let mut used: Vec<(String, &RecentProduction)> = recents
+        .iter()
+        .filter(|r| !r.missing)
+        .map(|r| (last_used(r), r))
+        .collect();
+    used.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    let mut candidates = vec![];
+    for (last_used, recent) in used.into_iter().skip(policy.keep_last as usize) {
+        let dir = Path::new(&recent.path);
+        if recent.pinned || last_used >= cutoff || busy(dir, queued) {
+            continue;
+        }
+        // Archiving records the archive in the sidecar
+        if policy.action == RetentionAction::Archive
+            && !sidecar::read(dir).is_ok_and(|s| s.is_some())
+        {
+            continue;
+        }
+        let bytes = intermediate_bytes(dir);
+        if bytes > 0 {
+            candidates.push(RetentionCandidate {
+                id: recent.id.clone(),
+                name: recent.name.clone(),
+                path: recent.path.clone(),
+                last_used,
+                bytes,
+            });
+        }
+    }
+    candidates.reverse();
+    candidates
+}
+
+// When a production was last opened, or its directory last changed if it never was
+fn last_used(recent: &RecentProduction) -> String {
+    if !recent.last_opened.is_empty() {
+        return recent.last_opened.clone();
+    }
+    std::fs::metadata(&recent.path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since| naming::iso8601(since.as_secs()))
+        .unwrap_or_default()
+}
+
+// Whether a running job or a queued one writes into `dir` or a directory around it
+fn busy(dir: &Path, queued: &[QueueEntry]) -> bool {
+    jobs::is_targeting(dir)
+        || queued.iter().any(|entry| {
+            let output_dir = Path::new(&entry.output_dir);
+            matches!(entry.status, QueueStatus::Queued | QueueStatus::Running)
+                && (output_dir.starts_with(dir) || dir.starts_with(output_dir))
+        })
+}
+
+fn intermediate_bytes(dir: &Path) -> u64 {
+    INTERMEDIATE_ENTRIES
+        .iter()
+        .map(|name| dir.join(name))
+        .map(|path| {
+            if path.is_dir() {
+                dir_size(&path).unwrap_or(0)
+            } else {
+                std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0)
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    const NOW: u64 = 1_750_000_000;
+
+    fn production(paths: &TempPaths, name: &str, days_ago: u64) -> RecentProduction {
+        let dir = paths.root().join(name);
+        std::fs::create_dir_all(dir.join("frames")).unwrap();
+        std::fs::write(dir.join("frames").join("0001.jpg"), [0u8; 64]).unwrap();
+        sidecar::write(&dir, &Default::default()).unwrap();
+        RecentProduction {
+            id: name.to_string(),
+            name: name.to_string(),
+            path: dir.to_string_lossy().to_string(),
+            last_opened: naming::iso8601(NOW - days_ago * DAY_SECS),
+            tags: vec![],
+            notes: String::new(),
+            imported: false,
+            missing: false,
+            pinned: false,
+        }
+    }
+
+    #[test]
+    fn old_unpinned_idle_productions_are_candidates() {
+        let paths = TempPaths::new();
+        let pinned = RecentProduction {
+            pinned: true,
+            ..production(&paths, "pinned", 200)
+        };
+        let missing = RecentProduction {
+            missing: true,
+            ..production(&paths, "missing", 300)
+        };
+        let queued = production(&paths, "queued", 400);
+        let emptied = production(&paths, "emptied", 500);
+        std::fs::remove_dir_all(Path::new(&emptied.path).join("frames")).unwrap();
+        let recents = vec![
+            production(&paths, "today", 0),
+            production(&paths, "last-week", 7),
+            production(&paths, "autumn", 100),
+            production(&paths, "spring", 250),
+            pinned,
+            missing,
+            queued.clone(),
+            emptied,
+        ];
+        let queue = [QueueEntry::queued(
+            "entry-1".to_string(),
+            None,
+            &[],
+            &queued.path,
+        )];
+        let policy = RetentionSettings {
+            enabled: true,
+            keep_days: 30,
+            keep_last: 1,
+            action: RetentionAction::Archive,
+        };
+
+        let found = candidates(&recents, &policy, NOW, &queue);
+        let names: Vec<&str> = found.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["spring", "autumn"]);
+        assert_eq!(found[0].bytes, 64);
+
+        // Whichever keeps more wins
+        let keep_three = RetentionSettings {
+            keep_last: 3,
+            ..policy.clone()
+        };
+        let names: Vec<String> = candidates(&recents, &keep_three, NOW, &queue)
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(names, ["spring"]);
+    }
+}
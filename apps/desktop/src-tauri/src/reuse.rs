@@ -0,0 +1,257 @@
+//! Reconstruction Reuse
+//!
+//! Adding a couple of clips to a capture should not mean matching every frame
+//! again. A production's sidecar records the content hash of each clip it was
+//! made from; when a new run into the same directory has all of those clips
+//! among its inputs and the previous COLMAP database is still there, the CLI is
+//! handed the database with its incremental flag so only the new images are
+//! registered. Anything else is a full run.
+
+use crate::capabilities::{CliCapabilities, FLAG_INCREMENTAL};
+use crate::error::AppError;
+use crate::hashing::{self, Reuse};
+use crate::sidecar::Sidecar;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+/// Where the CLI leaves the COLMAP database inside a production
+pub const DATABASE_NAME: &str = "database.db";
+
+/// Whether a job may build on the previous run in its output directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReuseReconstruction {
+    /// Reuse when the previous run's clips are all among the inputs
+    #[default]
+    Auto,
+    /// Also reuse a database whose run recorded no clip hashes to check
+    Always,
+    /// Always match everything again
+    Never,
+}
+
+/// Why a job did or did not build on the previous run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReuseVerdict {
+    Reused,
+    Disabled,
+    /// No sidecar or no COLMAP database in the output directory
+    NoPreviousRun,
+    /// A clip of the previous run is missing or changed
+    InputsChanged,
+    /// The previous run or this one has no clip hashes to compare
+    InputsUnverified,
+    CliUnsupported,
+}
+
+/// Kept in the job's history record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReuseDecision {
+    pub verdict: ReuseVerdict,
+    /// The previous run's database, whether or not it was reused
+    pub database: Option<String>,
+    /// Inputs the previous run did not have
+    pub new_inputs: usize,
+}
+
+impl ReuseDecision {
+    pub fn reused(&self) -> bool {
+        self.verdict == ReuseVerdict::Reused
+    }
+
+    /// The job log line for the decision
+    pub fn describe(&self) -> String {
+        let database = self.database.as_deref().unwrap_or("none");
+        match self.verdict {
+            ReuseVerdict::Reused => format!(
+                "Reusing COLMAP database {}; registering {} new clips",
+                database, self.new_inputs
+            ),
+            ReuseVerdict::Disabled => "Reconstruction reuse off; matching every clip".to_string(),
+            ReuseVerdict::NoPreviousRun => {
+                "No previous COLMAP database to reuse; matching every clip".to_string()
+            }
+            ReuseVerdict::InputsChanged => format!(
+                "Clips of the previous run are missing or changed; not reusing {}",
+                database
+            ),
+            ReuseVerdict::InputsUnverified => format!(
+                "Clips of the previous run cannot be verified; not reusing {}",
+                database
+            ),
+            ReuseVerdict::CliUnsupported => format!(
+                "The installed gvcore-cli does not support {}; not reusing {}",
+                FLAG_INCREMENTAL, database
+            ),
+        }
+    }
+}
+
+/// Content hashes of the inputs, in order
+pub async fn input_hashes(videos: &[String], cancel: &AtomicBool) -> Result<Vec<String>, AppError> {
+    let mut hashes = Vec::with_capacity(videos.len());
+    for video in videos {
+        hashes
+            .push(hashing::sha256(Path::new(video), Reuse::Cached, cancel, &mut |_, _| {}).await?);
+    }
+    Ok(hashes)
+}
+
+/// Decide whether a run into `output_dir` builds on the run `previous`
+/// describes. `hashes` are the inputs' content hashes, None when they could
+/// not be read.
+pub fn decide(
+    mode: ReuseReconstruction,
+    output_dir: &Path,
+    previous: Option<&Sidecar>,
+    hashes: Option<&[String]>,
+    caps: &CliCapabilities,
+) -> ReuseDecision {
+    let database = output_dir.join(DATABASE_NAME);
+    let decision = |verdict, new_inputs| ReuseDecision {
+        verdict,
+        database: database
+            .is_file()
+            .then(|| database.to_string_lossy().to_string()),
+        new_inputs,
+    };
+    if mode == ReuseReconstruction::Never {
+        return decision(ReuseVerdict::Disabled, 0);
+    }
+    let Some(previous) = previous.filter(|_| database.is_file()) else {
+        return decision(ReuseVerdict::NoPreviousRun, 0);
+    };
+
+    let new_inputs = match hashes {
+        Some(hashes) if !previous.input_sha256.is_empty() => {
+            if !previous.input_sha256.iter().all(|h| hashes.contains(h)) {
+                return decision(ReuseVerdict::InputsChanged, 0);
+            }
+            hashes
+                .iter()
+                .filter(|h| !previous.input_sha256.contains(h))
+                .count()
+        }
+        _ if mode == ReuseReconstruction::Always => {
+            let known = previous.videos.len();
+            hashes.map_or(0, |h| h.len().saturating_sub(known))
+        }
+        _ => return decision(ReuseVerdict::InputsUnverified, 0),
+    };
+    if !caps.supports(FLAG_INCREMENTAL) {
+        return decision(ReuseVerdict::CliUnsupported, new_inputs);
+    }
+    decision(ReuseVerdict::Reused, new_inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use crate::sidecar::ProductionSource;
+    use std::path::PathBuf;
+
+    fn previous(paths: &TempPaths, input_sha256: &[&str]) -> (PathBuf, Sidecar) {
+        let dir = paths.root().join("production");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(DATABASE_NAME), b"sqlite").unwrap();
+        let sidecar = Sidecar {
+            job_id: "job-1".to_string(),
+            production_dir: dir.to_string_lossy().to_string(),
+            artifact_path: dir.join("output.ply").to_string_lossy().to_string(),
+            preset: "balanced".to_string(),
+            videos: input_sha256
+                .iter()
+                .map(|h| format!("/clips/{}.mp4", h))
+                .collect(),
+            created_at: 0,
+            artifact_sha256: None,
+            capture_type: Default::default(),
+            archive: None,
+            preview: false,
+            input_sha256: input_sha256.iter().map(|h| h.to_string()).collect(),
+            command: None,
+            training: None,
+            derivatives: vec![],
+            source: ProductionSource::Job,
+            imported_from: None,
+            resolution_normalization: None,
+            camera_groups: vec![],
+            artifacts: vec![],
+        };
+        (dir, sidecar)
+    }
+
+    fn incremental() -> CliCapabilities {
+        CliCapabilities {
+            flags: vec![FLAG_INCREMENTAL.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn hashes(hashes: &[&str]) -> Vec<String> {
+        hashes.iter().map(|h| h.to_string()).collect()
+    }
+
+    #[test]
+    fn a_superset_of_the_previous_clips_reuses_its_database() {
+        let paths = TempPaths::new();
+        let (dir, sidecar) = previous(&paths, &["a", "b"]);
+        let auto = ReuseReconstruction::Auto;
+        let superset = hashes(&["b", "c", "a", "d"]);
+
+        let reused = decide(auto, &dir, Some(&sidecar), Some(&superset), &incremental());
+        assert!(reused.reused());
+        assert_eq!(reused.new_inputs, 2);
+        let database = dir.join(DATABASE_NAME).to_string_lossy().to_string();
+        assert_eq!(reused.database.as_deref(), Some(database.as_str()));
+
+        let dropped = hashes(&["a", "c"]);
+        let changed = decide(auto, &dir, Some(&sidecar), Some(&dropped), &incremental());
+        assert_eq!(changed.verdict, ReuseVerdict::InputsChanged);
+        let old_cli = decide(
+            auto,
+            &dir,
+            Some(&sidecar),
+            Some(&superset),
+            &Default::default(),
+        );
+        assert_eq!(old_cli.verdict, ReuseVerdict::CliUnsupported);
+        let never = ReuseReconstruction::Never;
+        let disabled = decide(never, &dir, Some(&sidecar), Some(&superset), &incremental());
+        assert_eq!(disabled.verdict, ReuseVerdict::Disabled);
+
+        std::fs::remove_file(dir.join(DATABASE_NAME)).unwrap();
+        let archived = decide(auto, &dir, Some(&sidecar), Some(&superset), &incremental());
+        assert_eq!(archived.verdict, ReuseVerdict::NoPreviousRun);
+        assert_eq!(archived.database, None);
+    }
+
+    #[test]
+    fn only_always_reuses_runs_without_clip_hashes() {
+        let paths = TempPaths::new();
+        let (dir, mut sidecar) = previous(&paths, &["a"]);
+        sidecar.input_sha256.clear();
+        let inputs = hashes(&["a", "b"]);
+
+        let auto = decide(
+            ReuseReconstruction::Auto,
+            &dir,
+            Some(&sidecar),
+            Some(&inputs),
+            &incremental(),
+        );
+        assert_eq!(auto.verdict, ReuseVerdict::InputsUnverified);
+        let always = decide(
+            ReuseReconstruction::Always,
+            &dir,
+            Some(&sidecar),
+            Some(&inputs),
+            &incremental(),
+        );
+        assert!(always.reused());
+        assert_eq!(always.new_inputs, 1);
+    }
+}
@@ -0,0 +1,556 @@
+//! Process Runner
+//!
+//! Runs gvcore-cli (or the simulator), parses its progress protocol and reports
+//! events through an [`EventSink`], independent of the Tauri runtime.
+//!
+//! The CLI prints progress as `[stage] percent% - message` lines. Newer versions
+//! also print typed JSON lines such as
+//! `{"type":"warning","code":"deprecated-flag","message":"..."}`; every line is
+//! passed on as-is, and those of a known type are parsed as well. A progress
+//! line may name the clip it is about with an `item`, a file name or index,
+//! and a result line, `{"type":"result","path":"...","kind":"ply"}`, names an
+//! artifact; a CLI that writes several prints one for each. A registration
+//! line ends sparse reconstruction with how many images registered.
+
+use crate::artifacts::{Artifact, ArtifactKind};
+use crate::clip_progress::ClipProgress;
+use crate::messages::Message;
+use crate::shell_quote::{self, Shell};
+use crate::training_metrics::{self, MetricSample};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader, DuplexStream};
+use tokio::process::{Child, Command};
+use tokio::task::JoinHandle;
+
+/// How often cancellation is checked while the CLI is silent
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait for stderr to close after the process exits
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maximum amount of stderr kept for diagnostics
+const STDERR_TAIL_BYTES: usize = 16 * 1024;
+
+/// Longest stdout line kept; the rest of a longer line is dropped unread
+pub const MAX_LINE_BYTES: usize = 1024 * 1024;
+
+/// Appended to a line cut at MAX_LINE_BYTES
+pub const TRUNCATED_MARKER: &str = " [truncated]";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessProgress {
+    pub stage: String,
+    pub progress: f64,
+    /// Backend status text, for the frontend to translate
+    pub message: Option<Message>,
+    /// Text the CLI printed, shown as-is
+    pub raw: Option<String>,
+    /// The clip being worked on, during the stages that go clip by clip
+    #[serde(default)]
+    pub current_item: Option<String>,
+    /// Clips of the stage done so far, out of all of them
+    #[serde(default)]
+    pub items: Option<ItemCount>,
+    /// The clip the CLI said the line was about, before it is matched to one of the job's
+    #[serde(skip)]
+    pub reported_item: Option<ItemRef>,
+    /// Estimated by the backend from the growth of the artifact being
+    /// exported, as the CLI reports nothing while it writes it
+    #[serde(default)]
+    pub synthetic: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ItemCount {
+    pub done: usize,
+    pub total: usize,
+}
+
+/// How the CLI names the clip a progress line is about
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ItemRef {
+    /// Position among the job's inputs, from 0
+    Index(usize),
+    /// File name or path of the clip
+    Name(String),
+}
+
+/// Something the CLI wants the user to act on, such as a deprecated flag
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CliWarning {
+    /// Stable identifier, e.g. "deprecated-flag"
+    pub code: String,
+    /// The CLI's own words, shown as-is
+    #[serde(default)]
+    pub message: String,
+}
+
+/// How many images registered at the end of sparse reconstruction
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RegistrationSummary {
+    pub registered: usize,
+    pub total: usize,
+    /// The same by clip, when the CLI reports it
+    #[serde(default)]
+    pub clips: Vec<ClipRegistration>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ClipRegistration {
+    pub item: ItemRef,
+    pub registered: usize,
+    #[serde(default)]
+    pub total: usize,
+}
+
+/// A typed JSON line of the stdout protocol
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CliMessage {
+    Progress {
+        stage: String,
+        #[serde(alias = "percent")]
+        progress: f64,
+        #[serde(default)]
+        message: Option<String>,
+        #[serde(default)]
+        item: Option<ItemRef>,
+    },
+    Warning(CliWarning),
+    Info {
+        message: String,
+    },
+    Metric {
+        name: String,
+        value: f64,
+        #[serde(default, alias = "iteration")]
+        step: Option<u64>,
+    },
+    Registration(RegistrationSummary),
+    /// Where the CLI wrote an artifact, relative to --output or absolute
+    Result {
+        path: String,
+        /// Implied by the extension when not given
+        #[serde(default)]
+        kind: Option<ArtifactKind>,
+    },
+    /// A type newer than this version; passed on only as a plain line
+    #[serde(other)]
+    Unknown,
+}
+
+/// Receives output and progress from a running job
+pub trait EventSink: Send {
+    /// Every stdout line, in order
+    fn line(&mut self, line: &str);
+    /// Parsed progress lines
+    fn progress(&mut self, progress: &ProcessProgress);
+    /// Warning lines; only sinks that surface warnings need to handle them
+    fn warning(&mut self, _warning: &CliWarning) {}
+    /// The per-clip breakdown of the job, whenever a clip or stage changes
+    fn clips(&mut self, _clips: &[ClipProgress]) {}
+    /// The CLI process started; never called for the simulator
+    fn started(&mut self, _pid: Option<u32>) {}
+    /// Training metrics, from typed lines or brush's log lines
+    fn metric(&mut self, _sample: &MetricSample) {}
+    /// The registration summary at the end of sparse reconstruction
+    fn registration(&mut self, _summary: &RegistrationSummary) {}
+}
+
+/// Exactly what a CLI run starts, kept with the job so it can be reproduced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommandSpec {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Variables set on top of the app's own environment
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Working directory, when not the app's own
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+impl CommandSpec {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+            env: BTreeMap::new(),
+            cwd: None,
+        }
+    }
+
+    /// One line that runs the same thing in this platform's shell
+    pub fn shell_quoted(&self) -> String {
+        shell_quote::command_line(self, Shell::native())
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        // A group of its own, so COLMAP and brush under it are paused and
+        // cancelled with it
+        #[cfg(unix)]
+        command.process_group(0);
+        command
+    }
+}
+
+/// Kill what the CLI started along with it; the CLI itself is killed after
+#[cfg(unix)]
+fn kill_group(child: &Child) {
+    if let Some(pid) = child.id() {
+        // SAFETY: kill only sends a signal to the process group it names
+        unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_group(_child: &Child) {}
+
+/// What to run
+pub enum Source {
+    Cli(CommandSpec),
+    Simulated {
+        stdout: DuplexStream,
+        handle: JoinHandle<bool>,
+    },
+}
+
+/// Decides what actually runs when a job asks for the CLI
+pub trait ProcessSpawner: Send + Sync {
+    fn spawn(&self, spec: CommandSpec) -> Source;
+}
+
+/// Runs the real CLI process
+pub struct CliSpawner;
+
+impl ProcessSpawner for CliSpawner {
+    fn spawn(&self, spec: CommandSpec) -> Source {
+        Source::Cli(spec)
+    }
+}
+
+#[derive(Debug)]
+pub struct RunOutcome {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    /// Human-readable exit status
+    pub status: String,
+    /// Tail of everything the process wrote to stderr
+    pub stderr: String,
+    /// The artifacts named by result lines, in the order they were named
+    pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RunError {
+    /// The OS error, and its raw code for diagnosing it
+    Spawn {
+        error: String,
+        os_code: Option<i32>,
+    },
+    Cancelled,
+    Io(String),
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Spawn { error, .. } => write!(f, "Failed to spawn CLI: {}", error),
+            RunError::Cancelled => write!(f, "Processing cancelled"),
+            RunError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+enum Running {
+    Cli(Child),
+    Simulated(JoinHandle<bool>),
+}
+
+/// Run a job to completion, streaming progress to `sink` until it exits or `cancel` is set
+pub async fn run(
+    source: Source,
+    sink: &mut dyn EventSink,
+    cancel: &AtomicBool,
+) -> Result<RunOutcome, RunError> {
+    let stderr_tail = Arc::new(Mutex::new(String::new()));
+    let mut stderr_reader = None;
+
+    let (stdout, mut running): (Box<dyn AsyncRead + Unpin + Send>, Running) = match source {
+        Source::Cli(spec) => {
+            let mut child = spec
+                .command()
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .kill_on_drop(true)
+                .spawn()
+                .map_err(|e| RunError::Spawn {
+                    error: e.to_string(),
+                    os_code: e.raw_os_error(),
+                })?;
+            let stdout = child
+                .stdout
+                .take()
+                .ok_or_else(|| RunError::Io("Failed to capture stdout".to_string()))?;
+            if let Some(stderr) = child.stderr.take() {
+                stderr_reader = Some(tokio::spawn(collect_stderr(stderr, stderr_tail.clone())));
+            }
+            sink.started(child.id());
+            (Box::new(stdout), Running::Cli(child))
+        }
+        Source::Simulated { stdout, handle } => (Box::new(stdout), Running::Simulated(handle)),
+    };
+
+    let mut reader = CappedLines::new(stdout);
+    let mut artifacts: Vec<Artifact> = vec![];
+    let mut poll = tokio::time::interval(CANCEL_POLL_INTERVAL);
+
+    loop {
+        // Check for cancellation even while the CLI prints nothing
+        let next = tokio::select! {
+            line = reader.next_line() => Some(line),
+            _ = poll.tick() => None,
+        };
+
+        if cancel.load(Ordering::SeqCst) {
+            match &mut running {
+                Running::Cli(child) => {
+                    kill_group(child);
+                    child.kill().await.ok();
+                }
+                Running::Simulated(handle) => handle.abort(),
+            }
+            return Err(RunError::Cancelled);
+        }
+
+        let line = match next {
+            Some(Ok(Some(line))) => line,
+            Some(Ok(None)) => break,
+            Some(Err(e)) => return Err(RunError::Io(e.to_string())),
+            None => continue,
+        };
+        // A cut line is only ever output to show, never a message to parse
+        if line.truncated {
+            sink.line(&format!("{}{}", line.text, TRUNCATED_MARKER));
+            continue;
+        }
+        let line = line.text;
+
+        sink.line(&line);
+        match parse_message(&line) {
+            Some(CliMessage::Progress {
+                stage,
+                progress,
+                message,
+                item,
+            }) => sink.progress(&ProcessProgress {
+                stage: map_stage(&stage).to_string(),
+                progress,
+                message: None,
+                raw: message,
+                current_item: None,
+                items: None,
+                reported_item: item,
+                synthetic: false,
+            }),
+            Some(CliMessage::Warning(warning)) => sink.warning(&warning),
+            Some(CliMessage::Registration(summary)) => sink.registration(&summary),
+            Some(CliMessage::Metric { name, value, step }) => {
+                sink.metric(&MetricSample { name, step, value })
+            }
+            Some(CliMessage::Result { path, kind }) => {
+                let named = Artifact::named(path, kind);
+                // An artifact named twice keeps its place and the later kind
+                match artifacts.iter_mut().find(|a| a.path == named.path) {
+                    Some(earlier) => *earlier = named,
+                    None => artifacts.push(named),
+                }
+            }
+            Some(CliMessage::Info { .. } | CliMessage::Unknown) => {}
+            None => {
+                if let Some(progress) = parse_progress_line(&line) {
+                    sink.progress(&progress);
+                }
+                for sample in training_metrics::parse_log_line(&line) {
+                    sink.metric(&sample);
+                }
+            }
+        }
+    }
+
+    let (success, exit_code, status) = match running {
+        Running::Cli(mut child) => {
+            let status = child
+                .wait()
+                .await
+                .map_err(|e| RunError::Io(e.to_string()))?;
+            (status.success(), status.code(), status.to_string())
+        }
+        Running::Simulated(handle) => {
+            let success = handle.await.unwrap_or(false);
+            let status = if success {
+                "simulated success"
+            } else {
+                "simulated failure"
+            };
+            (
+                success,
+                Some(if success { 0 } else { 1 }),
+                status.to_string(),
+            )
+        }
+    };
+
+    // Let the stderr reader drain what the process wrote before exiting
+    if let Some(handle) = stderr_reader {
+        tokio::time::timeout(STDERR_DRAIN_TIMEOUT, handle)
+            .await
+            .ok();
+    }
+    let stderr = stderr_tail.lock().unwrap().clone();
+
+    Ok(RunOutcome {
+        success,
+        exit_code,
+        status,
+        stderr,
+        artifacts,
+    })
+}
+
+/// Parse progress output: [stage_name] percent% - message
+pub fn parse_progress_line(line: &str) -> Option<ProcessProgress> {
+    static PROGRESS_RE: OnceLock<Regex> = OnceLock::new();
+    let progress_re = PROGRESS_RE.get_or_init(|| Regex::new(r"\[(\w+)\] (\d+)% - (.+)").unwrap());
+    let captures = progress_re.captures(line)?;
+
+    let stage = captures.get(1).map(|m| m.as_str()).unwrap_or("unknown");
+    let percent: f64 = captures
+        .get(2)
+        .and_then(|m| m.as_str().parse().ok())
+        .unwrap_or(0.0);
+    let raw = captures.get(3).map(|m| m.as_str().to_string());
+
+    Some(ProcessProgress {
+        stage: map_stage(stage).to_string(),
+        progress: percent,
+        message: None,
+        raw,
+        current_item: None,
+        items: None,
+        reported_item: None,
+        synthetic: false,
+    })
+}
+
+/// Parse a typed JSON line; None for plain output
+pub fn parse_message(line: &str) -> Option<CliMessage> {
+    let line = line.trim();
+    if !line.starts_with('{') {
+        return None;
+    }
+    serde_json::from_str(line).ok()
+}
+
+/// Map CLI stage names to frontend stage names
+fn map_stage(stage: &str) -> &str {
+    match stage {
+        "frame_extraction" => "extracting_frames",
+        "colmap" => "detecting_cameras",
+        "brush" => "training_splats",
+        "metadata" => "exporting",
+        "completed" => "complete",
+        "failed" => "failed",
+        other => other,
+    }
+}
+
+struct Line {
+    text: String,
+    truncated: bool,
+}
+
+/// Reads lines like `BufReader::lines`, but keeps at most MAX_LINE_BYTES of
+/// each, so one runaway line cannot exhaust memory. Like `next_line` it is
+/// cancel safe: a partly read line is kept until the next call.
+struct CappedLines<R> {
+    reader: BufReader<R>,
+    line: Vec<u8>,
+    truncated: bool,
+}
+
+impl<R: AsyncRead + Unpin> CappedLines<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            line: vec![],
+            truncated: false,
+        }
+    }
+
+    async fn next_line(&mut self) -> std::io::Result<Option<Line>> {
+        loop {
+            let buf = self.reader.fill_buf().await?;
+            if buf.is_empty() {
+                // The last line may lack its newline
+                if self.line.is_empty() && !self.truncated {
+                    return Ok(None);
+                }
+                return Ok(Some(self.take()));
+            }
+            let newline = buf.iter().position(|b| *b == b'\n');
+            let chunk = &buf[..newline.unwrap_or(buf.len())];
+            let room = MAX_LINE_BYTES.saturating_sub(self.line.len());
+            if chunk.len() > room {
+                self.truncated = true;
+            }
+            self.line.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            let used = newline.map_or(buf.len(), |i| i + 1);
+            self.reader.consume(used);
+            if newline.is_some() {
+                return Ok(Some(self.take()));
+            }
+        }
+    }
+
+    fn take(&mut self) -> Line {
+        if self.line.last() == Some(&b'\r') {
+            self.line.pop();
+        }
+        let line = Line {
+            text: String::from_utf8_lossy(&self.line).into_owned(),
+            truncated: self.truncated,
+        };
+        self.line.clear();
+        self.truncated = false;
+        line
+    }
+}
+
+async fn collect_stderr(stderr: impl AsyncRead + Unpin, tail: Arc<Mutex<String>>) {
+    let mut lines = BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let mut tail = tail.lock().unwrap();
+        tail.push_str(&line);
+        tail.push('\n');
+        if tail.len() > STDERR_TAIL_BYTES {
+            let mut cut = tail.len() - STDERR_TAIL_BYTES;
+            while !tail.is_char_boundary(cut) {
+                cut += 1;
+            }
+            tail.drain(..cut);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Safe Mode
+//!
+//! A launch marker in app_data is written at the start of setup and removed
+//! once the main window has been up for HEALTHY_AFTER, or on a clean exit, so
+//! the marker still being there at startup means the previous launch died
+//! early. It counts how many launches in a row did. After FAILED_LAUNCHES of
+//! them the app starts in safe mode and leaves out what runs at startup from
+//! files a crash may have come from, such as a corrupt queue.json. The user
+//! turns pieces back on one at a time with exit_safe_mode, so the piece that
+//! crashes is the one being turned on.
+
+use crate::error::AppError;
+use crate::fsutil;
+use crate::instance;
+use crate::job_log::{self, app_line};
+use crate::queue;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+/// Present in app_data until a launch is found healthy
+pub const LAUNCH_FILE: &str = "launch.json";
+
+/// Launches in a row that died early before the next starts in safe mode
+pub const FAILED_LAUNCHES: u32 = 2;
+
+/// How long the main window has to be up for a launch to count as healthy
+const HEALTHY_AFTER: Duration = Duration::from_secs(30);
+
+static FAILED: AtomicU32 = AtomicU32::new(0);
+
+static SKIPPED: Mutex<Vec<Piece>> = Mutex::new(vec![]);
+
+/// What safe mode leaves out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Piece {
+    /// Taking over the jobs an earlier run left in queue.json, and keeping
+    /// the queue there
+    Queue,
+    /// Accepting requests from launches of the binary with a subcommand
+    RemoteRequests,
+    /// Sharing productions on the local network
+    ShareServer,
+}
+
+const PIECES: [Piece; 3] = [Piece::Queue, Piece::RemoteRequests, Piece::ShareServer];
+
+impl Piece {
+    fn name(&self) -> &'static str {
+        match self {
+            Piece::Queue => "the job queue",
+            Piece::RemoteRequests => "requests from the command line",
+            Piece::ShareServer => "sharing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Launch {
+    /// Launches in a row before this one that died early
+    failed_before: u32,
+    pid: u32,
+    started_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafeModeStatus {
+    /// Some piece is still left out
+    pub active: bool,
+    /// Launches in a row before this one that died early
+    pub failed_launches: u32,
+    /// Left out for now
+    pub skipped: Vec<Piece>,
+}
+
+/// Mark the launch as started, going into safe mode after FAILED_LAUNCHES
+/// that died early; called first thing in setup
+pub fn begin(app_data: &Path) -> SafeModeStatus {
+    let failed = mark_launch(app_data);
+    FAILED.store(failed, Ordering::SeqCst);
+    if failed >= FAILED_LAUNCHES {
+        *SKIPPED.lock().unwrap() = PIECES.to_vec();
+    }
+    status()
+}
+
+/// Whether `piece` is left out for now
+pub fn skipped(piece: Piece) -> bool {
+    SKIPPED.lock().unwrap().contains(&piece)
+}
+
+/// Whether safe mode still leaves anything out
+pub fn skipped_any() -> bool {
+    !SKIPPED.lock().unwrap().is_empty()
+}
+
+/// Fail with what to do when `piece` is left out
+pub fn check(piece: Piece) -> Result<(), AppError> {
+    if skipped(piece) {
+        return Err(AppError::InvalidInput(format!(
+            "The app started in safe mode; turn {} back on first",
+            piece.name()
+        )));
+    }
+    Ok(())
+}
+
+/// Count the launch healthy once the main window has been up for
+/// HEALTHY_AFTER
+pub fn watch(app: &AppHandle, app_data: PathBuf) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(HEALTHY_AFTER).await;
+        if app.get_webview_window("main").is_some() {
+            healthy(&app_data);
+        }
+    });
+}
+
+/// Remove the launch marker, so the next launch starts normally
+pub fn healthy(app_data: &Path) {
+    std::fs::remove_file(app_data.join(LAUNCH_FILE)).ok();
+}
+
+/// What safe mode left out, and why
+#[tauri::command]
+pub async fn get_safe_mode_status() -> Result<SafeModeStatus, AppError> {
+    Ok(status())
+}
+
+/// Turn `piece` back on, or every piece when it is None
+#[tauri::command]
+pub async fn exit_safe_mode(
+    app: AppHandle,
+    piece: Option<Piece>,
+) -> Result<SafeModeStatus, AppError> {
+    let pieces = match piece {
+        Some(piece) => vec![piece],
+        None => PIECES.to_vec(),
+    };
+    for piece in pieces {
+        if !skipped(piece) {
+            continue;
+        }
+        app_line(
+            &app,
+            &format!("Turning {} back on after safe mode", piece.name()),
+        );
+        match piece {
+            Piece::Queue => queue::persist(&app)?,
+            Piece::RemoteRequests => instance::listen(&app),
+            Piece::ShareServer => {}
+        }
+        SKIPPED.lock().unwrap().retain(|p| *p != piece);
+    }
+    Ok(status())
+}
+
+fn status() -> SafeModeStatus {
+    let skipped = SKIPPED.lock().unwrap().clone();
+    SafeModeStatus {
+        active: !skipped.is_empty(),
+        failed_launches: FAILED.load(Ordering::SeqCst),
+        skipped,
+    }
+}
+
+/// Write the launch marker, returning the launches in a row before this one
+/// that died early
+fn mark_launch(app_data: &Path) -> u32 {
+    let path = app_data.join(LAUNCH_FILE);
+    let failed = match std::fs::read(&path) {
+        Err(_) => 0,
+        Ok(content) => serde_json::from_slice::<Launch>(&content)
+            .map(|previous| previous.failed_before + 1)
+            // Cut short while being written, which is a launch that died too
+            .unwrap_or(1),
+    };
+    let launch = Launch {
+        failed_before: failed,
+        pid: std::process::id(),
+        started_at: job_log::unix_timestamp(),
+    };
+    if let Err(e) = fsutil::write_json_atomic(&path, &launch) {
+        eprintln!("Failed to write the launch marker: {}", e);
+    }
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn launches_that_die_during_setup_add_up() {
+        let paths = TempPaths::new();
+        let app_data = paths.root();
+
+        assert_eq!(mark_launch(app_data), 0);
+        // Each launch dies before its window was up for long enough
+        assert_eq!(mark_launch(app_data), 1);
+        assert_eq!(mark_launch(app_data), FAILED_LAUNCHES);
+
+        std::fs::write(app_data.join(LAUNCH_FILE), "{\"failed_").unwrap();
+        assert_eq!(mark_launch(app_data), 1);
+    }
+
+    #[test]
+    fn a_launch_that_was_healthy_before_it_died_starts_the_count_again() {
+        let paths = TempPaths::new();
+        let app_data = paths.root();
+
+        assert_eq!(mark_launch(app_data), 0);
+        assert_eq!(mark_launch(app_data), 1);
+        // The window was up long enough, and then the app crashed
+        healthy(app_data);
+        assert_eq!(mark_launch(app_data), 0);
+        assert_eq!(mark_launch(app_data), 1);
+    }
+}
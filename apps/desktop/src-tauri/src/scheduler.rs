@@ -0,0 +1,415 @@
+//! Work Scheduler
+//!
+//! The backend's own heavy work, such as hashing, PLY parsing and thumbnail
+//! prefetch, runs through named pools of limited size, so that on a machine
+//! with few cores it cannot take every core at once and make the UI stutter:
+//!
+//! - `io` for work that mostly waits on the disk, such as library scans
+//! - `cpu_heavy` for work that keeps a core busy, such as hashing and parsing
+//! - `background` for work nobody waits on, such as prefetching clips
+//!
+//! Pool sizes come from the workers setting, or from the number of cores when
+//! unset. While a processing job runs, cpu_heavy shrinks to one worker to leave
+//! the cores to the CLI, and grows back once the job is over. Work in the
+//! cpu_heavy and background pools runs on threads of its own at a lower
+//! priority, on Linux where a thread's priority can be set by itself.
+
+use crate::error::AppError;
+use crate::jobs;
+use crate::settings::AppSettings;
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, OnceLock};
+use tokio::sync::{oneshot, Notify};
+
+/// Upper bound on the size of any one pool
+pub const MAX_WORKERS: u32 = 16;
+
+/// Upper bound on the niceness setting
+pub const MAX_NICENESS: u8 = 19;
+
+static SCHEDULER: OnceLock<WorkScheduler> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Pool {
+    Io,
+    CpuHeavy,
+    Background,
+}
+
+impl Pool {
+    const ALL: [Pool; 3] = [Pool::Io, Pool::CpuHeavy, Pool::Background];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn thread_name(self) -> &'static str {
+        match self {
+            Pool::Io => "gv-io",
+            Pool::CpuHeavy => "gv-cpu-heavy",
+            Pool::Background => "gv-background",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WorkerSettings {
+    /// Workers of each pool; derived from the number of cores when unset
+    pub io: Option<u32>,
+    pub cpu_heavy: Option<u32>,
+    pub background: Option<u32>,
+    /// How much lower than the UI the cpu_heavy and background pools run
+    pub niceness: u8,
+}
+
+impl Default for WorkerSettings {
+    fn default() -> Self {
+        Self {
+            io: None,
+            cpu_heavy: None,
+            background: None,
+            niceness: 10,
+        }
+    }
+}
+
+impl WorkerSettings {
+    /// The size of each pool on a machine with `cores` cores
+    fn sizes(&self, cores: usize) -> [usize; 3] {
+        let derived = [
+            cores.clamp(2, 8),
+            (cores / 2).max(1),
+            if cores <= 4 { 1 } else { 2 },
+        ];
+        let configured = [self.io, self.cpu_heavy, self.background];
+        let mut sizes = derived;
+        for (size, configured) in sizes.iter_mut().zip(configured) {
+            if let Some(workers) = configured {
+                *size = workers.clamp(1, MAX_WORKERS) as usize;
+            }
+        }
+        sizes
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolStatus {
+    pub pool: Pool,
+    /// Workers the pool is configured with
+    pub size: usize,
+    /// Workers it may use right now
+    pub allowed: usize,
+    pub running: usize,
+    /// Work waiting for a worker
+    pub queued: usize,
+    /// Work finished since startup
+    pub completed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerStatus {
+    pub pools: Vec<PoolStatus>,
+    /// A processing job has shrunk the cpu_heavy pool
+    pub throttled: bool,
+    pub niceness: u8,
+}
+
+#[derive(Debug, Default)]
+struct GateState {
+    size: usize,
+    running: usize,
+    queued: usize,
+    completed: u64,
+}
+
+#[derive(Debug, Default)]
+struct Gate {
+    state: Mutex<GateState>,
+    /// Notified whenever a worker frees up or the limits change
+    changed: Notify,
+}
+
+/// Named pools of limited size that internal work waits its turn in
+pub struct WorkScheduler {
+    gates: [Gate; 3],
+    niceness: Mutex<u8>,
+    /// Whether a processing job is running
+    busy: Box<dyn Fn() -> bool + Send + Sync>,
+}
+
+/// A worker of a pool, given back when dropped
+pub struct Permit<'a> {
+    gate: &'a Gate,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.gate.state.lock().unwrap();
+        state.running -= 1;
+        state.completed += 1;
+        drop(state);
+        self.gate.changed.notify_waiters();
+    }
+}
+
+/// Counts a caller as queued until it gets a worker or gives up
+struct Queued<'a>(&'a Gate);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().queued -= 1;
+    }
+}
+
+impl WorkScheduler {
+    pub fn new(
+        settings: &WorkerSettings,
+        cores: usize,
+        busy: impl Fn() -> bool + Send + Sync + 'static,
+    ) -> Self {
+        let scheduler = Self {
+            gates: Default::default(),
+            niceness: Mutex::new(0),
+            busy: Box::new(busy),
+        };
+        scheduler.configure(settings, cores);
+        scheduler
+    }
+
+    /// Apply the workers setting; work already running is not interrupted
+    pub fn configure(&self, settings: &WorkerSettings, cores: usize) {
+        for (gate, size) in self.gates.iter().zip(settings.sizes(cores)) {
+            gate.state.lock().unwrap().size = size;
+        }
+        *self.niceness.lock().unwrap() = settings.niceness.min(MAX_NICENESS);
+        self.refresh();
+    }
+
+    /// Let waiting work check the limits again, e.g. once a job has finished
+    pub fn refresh(&self) {
+        for gate in &self.gates {
+            gate.changed.notify_waiters();
+        }
+    }
+
+    /// Wait for a worker of `pool`
+    pub async fn acquire(&self, pool: Pool) -> Permit<'_> {
+        let gate = &self.gates[pool.index()];
+        gate.state.lock().unwrap().queued += 1;
+        let _queued = Queued(gate);
+        loop {
+            let notified = gate.changed.notified();
+            tokio::pin!(notified);
+            // Registered before checking, so a worker freed meanwhile is not missed
+            notified.as_mut().enable();
+            {
+                let mut state = gate.state.lock().unwrap();
+                if state.running < self.allowed(pool, state.size) {
+                    state.running += 1;
+                    return Permit { gate };
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Run blocking `work` on a worker of `pool`
+    pub async fn run_blocking<T: Send + 'static>(
+        &self,
+        pool: Pool,
+        work: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<T, AppError> {
+        let _permit = self.acquire(pool).await;
+        let niceness = match pool {
+            Pool::Io => 0,
+            Pool::CpuHeavy | Pool::Background => *self.niceness.lock().unwrap(),
+        };
+        if niceness == 0 {
+            return tokio::task::spawn_blocking(work)
+                .await
+                .map_err(|e| AppError::Io(e.to_string()));
+        }
+        // A thread of its own, as a thread's priority cannot be raised back
+        let (done, result) = oneshot::channel();
+        std::thread::Builder::new()
+            .name(pool.thread_name().to_string())
+            .spawn(move || {
+                lower_priority(niceness);
+                done.send(work()).ok();
+            })?;
+        result
+            .await
+            .map_err(|_| AppError::Io(format!("A {} worker stopped", pool.thread_name())))
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        let pools = Pool::ALL
+            .iter()
+            .map(|&pool| {
+                let state = self.gates[pool.index()].state.lock().unwrap();
+                PoolStatus {
+                    pool,
+                    size: state.size,
+                    allowed: self.allowed(pool, state.size),
+                    running: state.running,
+                    queued: state.queued,
+                    completed: state.completed,
+                }
+            })
+            .collect();
+        SchedulerStatus {
+            pools,
+            throttled: (self.busy)(),
+            niceness: *self.niceness.lock().unwrap(),
+        }
+    }
+
+    fn allowed(&self, pool: Pool, size: usize) -> usize {
+        if pool == Pool::CpuHeavy && (self.busy)() {
+            1
+        } else {
+            size.max(1)
+        }
+    }
+}
+
+/// The scheduler of the app, throttled while a processing job runs
+pub fn global() -> &'static WorkScheduler {
+    SCHEDULER
+        .get_or_init(|| WorkScheduler::new(&WorkerSettings::default(), cores(), jobs::any_active))
+}
+
+/// Apply the workers setting, at startup and whenever the settings change
+pub fn configure(settings: &AppSettings) {
+    global().configure(&settings.workers, cores());
+}
+
+/// Run blocking `work` on a worker of `pool` of the app's scheduler
+pub async fn run_blocking<T: Send + 'static>(
+    pool: Pool,
+    work: impl FnOnce() -> T + Send + 'static,
+) -> Result<T, AppError> {
+    global().run_blocking(pool, work).await
+}
+
+/// How busy each pool is, for the diagnostics screen
+#[tauri::command]
+pub async fn get_scheduler_status() -> Result<SchedulerStatus, AppError> {
+    Ok(global().status())
+}
+
+fn cores() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(target_os = "linux")]
+fn lower_priority(niceness: u8) {
+    // SAFETY: with PRIO_PROCESS and 0, setpriority only affects the calling thread on Linux
+    unsafe {
+        libc::setpriority(libc::PRIO_PROCESS, 0, niceness as libc::c_int);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn lower_priority(_niceness: u8) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Counts how many fake workloads run at once
+    #[derive(Default)]
+    struct Meter {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl Meter {
+        fn work(&self) {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(80));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        fn take_peak(&self) -> usize {
+            self.peak.swap(0, Ordering::SeqCst)
+        }
+    }
+
+    async fn run_many(scheduler: &Arc<WorkScheduler>, meter: &Arc<Meter>, count: usize) {
+        let tasks: Vec<_> = (0..count)
+            .map(|_| {
+                let (scheduler, meter) = (scheduler.clone(), meter.clone());
+                tokio::spawn(async move {
+                    scheduler
+                        .run_blocking(Pool::CpuHeavy, move || meter.work())
+                        .await
+                        .unwrap()
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    #[test]
+    fn pool_sizes_follow_the_cores_unless_configured() {
+        let settings = WorkerSettings::default();
+        assert_eq!(settings.sizes(2), [2, 1, 1]);
+        assert_eq!(settings.sizes(16), [8, 8, 2]);
+        let settings = WorkerSettings {
+            cpu_heavy: Some(3),
+            background: Some(0),
+            ..WorkerSettings::default()
+        };
+        assert_eq!(settings.sizes(2), [2, 3, 1]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn cpu_heavy_work_shrinks_to_one_worker_while_a_job_runs() {
+        let job_running = Arc::new(AtomicBool::new(false));
+        let busy = job_running.clone();
+        let settings = WorkerSettings {
+            cpu_heavy: Some(3),
+            niceness: 0,
+            ..WorkerSettings::default()
+        };
+        let scheduler = Arc::new(WorkScheduler::new(&settings, 8, move || {
+            busy.load(Ordering::SeqCst)
+        }));
+        let meter = Arc::new(Meter::default());
+
+        run_many(&scheduler, &meter, 9).await;
+        assert_eq!(meter.take_peak(), 3);
+
+        job_running.store(true, Ordering::SeqCst);
+        let throttled = tokio::spawn({
+            let (scheduler, meter) = (scheduler.clone(), meter.clone());
+            async move { run_many(&scheduler, &meter, 6).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let status = scheduler.status();
+        let cpu_heavy = &status.pools[Pool::CpuHeavy.index()];
+        assert!(status.throttled);
+        assert_eq!((cpu_heavy.allowed, cpu_heavy.running), (1, 1));
+        assert!(cpu_heavy.queued >= 2);
+        assert_eq!(meter.take_peak(), 1);
+
+        // The work still queued when the job finishes spreads out again
+        job_running.store(false, Ordering::SeqCst);
+        scheduler.refresh();
+        throttled.await.unwrap();
+        assert_eq!(meter.take_peak(), 3);
+        assert_eq!(
+            scheduler.status().pools[Pool::CpuHeavy.index()].completed,
+            15
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! Secret Storage
+//!
+//! Keeps upload credentials and API tokens in the OS keyring (Windows Credential
+//! Manager, macOS Keychain, Secret Service). When no keyring is usable, as on
+//! headless Linux, secrets go to an encrypted file in app_data keyed by the machine
+//! identifier instead, and get_secret_backend reports the fallback. With no
+//! machine identifier or app data either, secrets are only kept in memory for
+//! the session, rather than the app failing to start. Secret values are never
+//! returned to the frontend and are redacted from job logs.
+
+use crate::error::AppError;
+use crate::fsutil;
+use crate::platform::PathProvider;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use tauri::State;
+
+/// Keyring service name; matches the bundle identifier
+const SERVICE: &str = "ai.gameview.desktop";
+
+/// Entry looked up at startup to find out whether the keyring answers
+const PROBE_KEY: &str = "keyring-probe";
+
+const FALLBACK_FILE: &str = "secrets.enc";
+const NONCE_LEN: usize = 24;
+
+/// Values shorter than this are not redacted; they would mangle ordinary log text
+const MIN_REDACTED_LEN: usize = 4;
+
+// Secret values seen this session; only these can end up in a log
+static REDACTIONS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    Keyring,
+    EncryptedFile,
+    /// Nothing is kept after quitting
+    Memory,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecretBackendInfo {
+    pub backend: SecretBackend,
+    /// Why the keyring could not be used, when falling back to the encrypted
+    /// file or memory
+    pub fallback_reason: Option<String>,
+}
+
+/// Secret store managed as Tauri state
+#[derive(Clone)]
+pub struct Secrets {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    store: Store,
+    fallback_reason: Option<String>,
+}
+
+enum Store {
+    Keyring,
+    File(FileStore),
+    Memory(Mutex<HashMap<String, String>>),
+}
+
+impl Secrets {
+    /// Use the OS keyring if it responds, otherwise the encrypted fallback file,
+    /// otherwise memory
+    pub fn open(paths: &impl PathProvider) -> Self {
+        // The keyring client may block on its own runtime, so keep it off the caller's
+        let probe = std::thread::spawn(|| match keyring_entry(PROBE_KEY)?.get_password() {
+            Ok(_) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e),
+        })
+        .join()
+        .unwrap_or_else(|_| Err(keyring::Error::Invalid("probe".into(), "panicked".into())));
+
+        let (store, fallback_reason) = match probe {
+            Ok(()) => (Store::Keyring, None),
+            Err(e) => {
+                let file = fallback_key().and_then(|key| {
+                    Ok(FileStore {
+                        path: paths.app_data_dir()?.join(FALLBACK_FILE),
+                        key,
+                        lock: Mutex::new(()),
+                    })
+                });
+                match file {
+                    Ok(file) => (Store::File(file), Some(e.to_string())),
+                    Err(file_error) => {
+                        let reason = format!("{}; {}", e, file_error);
+                        return Self::memory(reason);
+                    }
+                }
+            }
+        };
+
+        Self {
+            inner: Arc::new(Inner {
+                store,
+                fallback_reason,
+            }),
+        }
+    }
+
+    /// Keep secrets in memory only, for `reason`
+    pub fn memory(reason: String) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store: Store::Memory(Mutex::new(HashMap::new())),
+                fallback_reason: Some(reason),
+            }),
+        }
+    }
+
+    pub fn backend(&self) -> SecretBackendInfo {
+        SecretBackendInfo {
+            backend: match self.inner.store {
+                Store::Keyring => SecretBackend::Keyring,
+                Store::File(_) => SecretBackend::EncryptedFile,
+                Store::Memory(_) => SecretBackend::Memory,
+            },
+            fallback_reason: self.inner.fallback_reason.clone(),
+        }
+    }
+
+    /// Read a secret for use inside the backend
+    pub async fn get(&self, key: &str) -> Result<Option<String>, AppError> {
+        validate_key(key)?;
+        let key = key.to_string();
+        let value = self.blocking(move |store| store.get(&key)).await?;
+        if let Some(value) = &value {
+            register_redaction(value);
+        }
+        Ok(value)
+    }
+
+    pub async fn set(&self, key: &str, value: String) -> Result<(), AppError> {
+        validate_key(key)?;
+        if value.is_empty() {
+            return Err(AppError::InvalidInput("Secret value is empty".to_string()));
+        }
+        register_redaction(&value);
+        let key = key.to_string();
+        self.blocking(move |store| store.set(&key, &value)).await
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), AppError> {
+        validate_key(key)?;
+        let key = key.to_string();
+        self.blocking(move |store| store.delete(&key)).await
+    }
+
+    async fn blocking<T: Send + 'static>(
+        &self,
+        op: impl FnOnce(&Store) -> Result<T, AppError> + Send + 'static,
+    ) -> Result<T, AppError> {
+        let inner = self.inner.clone();
+        tokio::task::spawn_blocking(move || op(&inner.store))
+            .await
+            .map_err(|e| AppError::Io(e.to_string()))?
+    }
+}
+
+impl Store {
+    fn get(&self, key: &str) -> Result<Option<String>, AppError> {
+        match self {
+            Store::Keyring => match keyring_entry(key).and_then(|e| e.get_password()) {
+                Ok(value) => Ok(Some(value)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(keyring_error(e)),
+            },
+            Store::File(file) => Ok(file.load()?.remove(key)),
+            Store::Memory(secrets) => Ok(secrets.lock().unwrap().get(key).cloned()),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), AppError> {
+        match self {
+            Store::Keyring => keyring_entry(key)
+                .and_then(|e| e.set_password(value))
+                .map_err(keyring_error),
+            Store::File(file) => file.update(|secrets| {
+                secrets.insert(key.to_string(), value.to_string());
+            }),
+            Store::Memory(secrets) => {
+                secrets
+                    .lock()
+                    .unwrap()
+                    .insert(key.to_string(), value.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<(), AppError> {
+        match self {
+            Store::Keyring => match keyring_entry(key).and_then(|e| e.delete_credential()) {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(keyring_error(e)),
+            },
+            Store::File(file) => file.update(|secrets| {
+                secrets.remove(key);
+            }),
+            Store::Memory(secrets) => {
+                secrets.lock().unwrap().remove(key);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Store a secret; it can be checked for and deleted but never read back by the frontend
+#[tauri::command]
+pub async fn set_secret(
+    secrets: State<'_, Secrets>,
+    key: String,
+    value: String,
+) -> Result<(), AppError> {
+    secrets.set(&key, value).await
+}
+
+/// Whether a secret is stored under `key`
+#[tauri::command]
+pub async fn has_secret(secrets: State<'_, Secrets>, key: String) -> Result<bool, AppError> {
+    Ok(secrets.get(&key).await?.is_some())
+}
+
+/// Delete a secret; deleting a missing secret succeeds
+#[tauri::command]
+pub async fn delete_secret(secrets: State<'_, Secrets>, key: String) -> Result<(), AppError> {
+    secrets.delete(&key).await
+}
+
+/// Report where secrets are stored and why the keyring is not used, if it is not
+#[tauri::command]
+pub async fn get_secret_backend(
+    secrets: State<'_, Secrets>,
+) -> Result<SecretBackendInfo, AppError> {
+    Ok(secrets.backend())
+}
+
+/// Replace every secret value seen this session with a placeholder
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let redactions = REDACTIONS.read().unwrap();
+    let mut text = Cow::Borrowed(text);
+    for secret in redactions.iter() {
+        if text.contains(secret.as_str()) {
+            text = Cow::Owned(text.replace(secret.as_str(), "[redacted]"));
+        }
+    }
+    text
+}
+
+fn register_redaction(value: &str) {
+    if value.len() < MIN_REDACTED_LEN {
+        return;
+    }
+    let mut redactions = REDACTIONS.write().unwrap();
+    if !redactions.iter().any(|v| v == value) {
+        redactions.push(value.to_string());
+        // Longest first so a secret containing another is replaced whole
+        redactions.sort_by_key(|v| std::cmp::Reverse(v.len()));
+    }
+}
+
+fn validate_key(key: &str) -> Result<(), AppError> {
+    let valid_chars = key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ':'));
+    if key.is_empty() || key.len() > 128 || !valid_chars || key == PROBE_KEY {
+        return Err(AppError::InvalidInput(format!(
+            "Invalid secret key: {}",
+            key
+        )));
+    }
+    Ok(())
+}
+
+fn keyring_entry(key: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, key)
+}
+
+fn keyring_error(e: keyring::Error) -> AppError {
+    AppError::Io(format!("Keyring error: {}", e))
+}
+
+// Derived from the machine identifier: keeps the file unreadable elsewhere, but anyone
+// with access to this machine and account can derive it too
+fn fallback_key() -> Result<[u8; 32], String> {
+    let machine_id =
+        machine_uid::get().map_err(|e| format!("No keyring and no machine identifier: {}", e))?;
+    let mut hasher = Sha256::new();
+    hasher.update(b"gameview-secrets-v1:");
+    hasher.update(machine_id.trim().as_bytes());
+    Ok(hasher.finalize().into())
+}
+
+/// All secrets in one file: a random nonce followed by the encrypted JSON map
+struct FileStore {
+    path: PathBuf,
+    key: [u8; 32],
+    // Serializes read-modify-write of the file
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    fn load(&self) -> Result<HashMap<String, String>, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        self.read()
+    }
+
+    fn update(&self, change: impl FnOnce(&mut HashMap<String, String>)) -> Result<(), AppError> {
+        let _guard = self.lock.lock().unwrap();
+        let mut secrets = self.read()?;
+        change(&mut secrets);
+        self.write(&secrets)
+    }
+
+    fn read(&self) -> Result<HashMap<String, String>, AppError> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+        let data = std::fs::read(&self.path)?;
+        let undecryptable =
+            || AppError::Io("Secrets file cannot be decrypted on this machine".to_string());
+        if data.len() < NONCE_LEN {
+            return Err(undecryptable());
+        }
+
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher()
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| undecryptable())?;
+        serde_json::from_slice(&plaintext).map_err(|_| undecryptable())
+    }
+
+    fn write(&self, secrets: &HashMap<String, String>) -> Result<(), AppError> {
+        let plaintext = serde_json::to_vec(secrets).map_err(|e| AppError::Io(e.to_string()))?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, plaintext.as_slice())
+            .map_err(|_| AppError::Io("Failed to encrypt secrets".to_string()))?;
+
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&ciphertext);
+        fsutil::write_atomic(&self.path, &data)?;
+        restrict_permissions(&self.path);
+        Ok(())
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(&self.key.into())
+    }
+}
+
+#[cfg(unix)]
+pub fn restrict_permissions(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).ok();
+}
+
+#[cfg(not(unix))]
+pub fn restrict_permissions(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn file_secrets(paths: &TempPaths, key: [u8; 32]) -> Secrets {
+        Secrets {
+            inner: Arc::new(Inner {
+                store: Store::File(FileStore {
+                    path: paths.app_data_dir().unwrap().join(FALLBACK_FILE),
+                    key,
+                    lock: Mutex::new(()),
+                }),
+                fallback_reason: Some("no keyring in tests".to_string()),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn file_fallback_round_trips() {
+        let paths = TempPaths::new();
+        let secrets = file_secrets(&paths, [7; 32]);
+
+        assert_eq!(secrets.get("upload.token").await.unwrap(), None);
+        secrets
+            .set("upload.token", "tok-round-trip-123".to_string())
+            .await
+            .unwrap();
+        secrets
+            .set("publish:api-key", "key-456789".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            secrets.get("upload.token").await.unwrap().as_deref(),
+            Some("tok-round-trip-123")
+        );
+
+        secrets.delete("upload.token").await.unwrap();
+        secrets.delete("upload.token").await.unwrap();
+        assert_eq!(secrets.get("upload.token").await.unwrap(), None);
+        assert!(secrets.get("publish:api-key").await.unwrap().is_some());
+
+        let info = secrets.backend();
+        assert_eq!(info.backend, SecretBackend::EncryptedFile);
+        assert!(info.fallback_reason.is_some());
+    }
+
+    #[tokio::test]
+    async fn file_does_not_contain_plaintext() {
+        let paths = TempPaths::new();
+        let secrets = file_secrets(&paths, [7; 32]);
+        secrets
+            .set("upload.token", "tok-plaintext-check".to_string())
+            .await
+            .unwrap();
+
+        let data = std::fs::read(paths.app_data_dir().unwrap().join(FALLBACK_FILE)).unwrap();
+        let text = String::from_utf8_lossy(&data);
+        assert!(!text.contains("tok-plaintext-check"));
+        assert!(!text.contains("upload.token"));
+    }
+
+    #[tokio::test]
+    async fn file_from_another_machine_is_rejected() {
+        let paths = TempPaths::new();
+        file_secrets(&paths, [7; 32])
+            .set("upload.token", "tok-other-machine".to_string())
+            .await
+            .unwrap();
+
+        let err = file_secrets(&paths, [8; 32])
+            .get("upload.token")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("cannot be decrypted"));
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_keys_and_empty_values() {
+        let paths = TempPaths::new();
+        let secrets = file_secrets(&paths, [7; 32]);
+        for key in ["", "has space", "../escape", PROBE_KEY, &"k".repeat(129)] {
+            let err = secrets
+                .set(key, "value-1234".to_string())
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), "invalid_input", "{}", key);
+        }
+        let err = secrets
+            .set("upload.token", String::new())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), "invalid_input");
+    }
+
+    #[tokio::test]
+    async fn stored_values_are_redacted() {
+        let paths = TempPaths::new();
+        let secrets = file_secrets(&paths, [7; 32]);
+        secrets
+            .set("upload.token", "tok-redact-me-42".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            redact("Uploading with --token tok-redact-me-42 to host"),
+            "Uploading with --token [redacted] to host"
+        );
+        assert!(matches!(redact("nothing secret here"), Cow::Borrowed(_)));
+    }
+
+    #[tokio::test]
+    async fn memory_backend_keeps_secrets_for_the_session() {
+        let secrets = Secrets::memory("no keyring and no machine identifier".to_string());
+        secrets
+            .set("upload.token", "tok-in-memory-1".to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            secrets.get("upload.token").await.unwrap().as_deref(),
+            Some("tok-in-memory-1")
+        );
+        secrets.delete("upload.token").await.unwrap();
+        assert_eq!(secrets.get("upload.token").await.unwrap(), None);
+
+        let info = secrets.backend();
+        assert_eq!(info.backend, SecretBackend::Memory);
+        assert!(info.fallback_reason.unwrap().contains("machine identifier"));
+    }
+
+    #[test]
+    fn short_values_are_not_redacted() {
+        register_redaction("abc");
+        assert_eq!(redact("abc def"), "abc def");
+    }
+
+    #[test]
+    fn overlapping_secrets_are_redacted_whole() {
+        register_redaction("overlap-secret");
+        register_redaction("overlap-secret-longer");
+        assert_eq!(redact("x overlap-secret-longer y"), "x [redacted] y");
+    }
+}
@@ -1,18 +1,146 @@
 //! Settings Management
 //!
-//! Handles application settings persistence.
+//! Handles application settings persistence. Settings are loaded once at startup
+//! and every change goes through a single update path that persists under a lock.
 
+use crate::appearance::{self, AppearanceSettings};
+use crate::auto_retry::RetrySettings;
+use crate::cache;
+use crate::camera_intrinsics::DeviceIntrinsics;
+use crate::checkpoints::CheckpointSettings;
+use crate::datafile;
+use crate::fsutil;
+use crate::hooks::PostRunHook;
+use crate::network::NetworkSettings;
+use crate::notifications::NotificationSettings;
+use crate::output_location::UnsafeOutputPolicy;
+use crate::platform::PathProvider;
+use crate::profiles::ProfileOverride;
+use crate::registration;
+use crate::retention::RetentionSettings;
+use crate::scheduler::WorkerSettings;
+use crate::settings_profiles;
+use crate::training::TrainingOptions;
+use crate::viewers::ExternalViewer;
+use crate::written_by::{self, FileFormat};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Name of the settings file in each settings profile's directory
+pub const FILE_NAME: &str = "settings.json";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AppSettings {
-    pub theme: String,
+    /// Theme, accent color and motion; older versions saved only a theme name, as "theme"
+    #[serde(default, alias = "theme", deserialize_with = "appearance::from_saved")]
+    pub appearance: AppearanceSettings,
     pub default_output_dir: String,
     pub default_preset: String,
     pub colmap_path: Option<String>,
     pub brush_path: Option<String>,
     pub recent_productions: Vec<RecentProduction>,
+    #[serde(default)]
+    pub first_run_completed: bool,
+    /// Re-hash a production's artifact against its sidecar checksum when it is opened
+    #[serde(default)]
+    pub verify_artifacts_on_open: bool,
+    #[serde(default)]
+    pub external_viewers: Vec<ExternalViewer>,
+    /// Name finished artifacts after this template instead of keeping the CLI's name
+    #[serde(default)]
+    pub output_name_template: Option<String>,
+    /// Clips probed and thumbnailed at once in the background
+    #[serde(default = "default_prefetch_concurrency")]
+    pub prefetch_concurrency: u32,
+    /// Replacement flags for built-in capture profiles
+    #[serde(default)]
+    pub capture_profiles: Vec<ProfileOverride>,
+    #[serde(default)]
+    pub network: NetworkSettings,
+    /// Where jobs keep the frames the backend extracts; the system temp directory when unset
+    #[serde(default)]
+    pub scratch_dir: Option<String>,
+    /// How long a destructive operation can be undone
+    #[serde(default = "default_undo_window_secs")]
+    pub undo_window_secs: u64,
+    /// A user's own ffmpeg, used instead of the bundled one; ffprobe is expected beside it
+    #[serde(default)]
+    pub ffmpeg_path: Option<String>,
+    /// Whether an output directory overlapping an input or app_data is refused or only warned about
+    #[serde(default)]
+    pub unsafe_output_locations: UnsafeOutputPolicy,
+    /// Training options each preset starts from, by preset name
+    #[serde(default)]
+    pub preset_training: BTreeMap<String, TrainingOptions>,
+    /// VRAM each preset needs in GB, by preset name, in place of the built-in
+    /// table's; jobs on GPUs with less are refused unless overridden
+    #[serde(default)]
+    pub preset_min_vram_gb: BTreeMap<String, u32>,
+    /// Intrinsics of devices missing from the built-in table, or correcting it
+    #[serde(default)]
+    pub known_devices: Vec<DeviceIntrinsics>,
+    /// Intermediate splats exported while a job trains
+    #[serde(default)]
+    pub checkpoints: CheckpointSettings,
+    /// A gvcore-cli to run instead of the bundled one
+    #[serde(default)]
+    pub gvcore_cli_path: Option<String>,
+    /// Size app_data/cache is kept to; the least recently used thumbnails and metadata go first
+    #[serde(default = "default_cache_max_bytes")]
+    pub cache_max_bytes: u64,
+    /// Share of a job's images that must register for it to go on to training
+    #[serde(default = "default_min_registered_percent")]
+    pub min_registered_percent: u32,
+    /// Suspend a job at the start of training while other processes hold the VRAM its preset needs
+    #[serde(default)]
+    pub wait_for_vram: bool,
+    /// How long a job waits for VRAM before training anyway
+    #[serde(default = "default_vram_wait_secs")]
+    pub vram_wait_secs: u64,
+    /// Running a job again by itself when its CLI fails in a way that is transient
+    #[serde(default)]
+    pub auto_retry: RetrySettings,
+    /// Pool sizes and priority of the backend's own heavy work
+    #[serde(default)]
+    pub workers: WorkerSettings,
+    /// Programs run once a job is over, in order
+    #[serde(default)]
+    pub post_run_hooks: Vec<PostRunHook>,
+    /// Whether post_run_hooks run at all; off unless the user turns it on
+    #[serde(default)]
+    pub post_run_hooks_enabled: bool,
+    /// How long productions keep their intermediates before they are archived or trashed
+    #[serde(default)]
+    pub retention: RetentionSettings,
+    /// OS notifications of finished jobs, and whether they wait out do-not-disturb
+    #[serde(default)]
+    pub notifications: NotificationSettings,
+}
+
+fn default_prefetch_concurrency() -> u32 {
+    3
+}
+
+fn default_undo_window_secs() -> u64 {
+    10 * 60
+}
+
+fn default_cache_max_bytes() -> u64 {
+    cache::DEFAULT_MAX_BYTES
+}
+
+fn default_min_registered_percent() -> u32 {
+    registration::DEFAULT_MIN_REGISTERED_PERCENT
+}
+
+fn default_vram_wait_secs() -> u64 {
+    10 * 60
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,17 +150,437 @@ pub struct RecentProduction {
     pub name: String,
     pub path: String,
     pub last_opened: String,
+    /// Normalized (trimmed, lowercase, unique) tags; absent in entries saved by older versions
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub notes: String,
+    /// Found by a library scan rather than opened in the app
+    #[serde(default)]
+    pub imported: bool,
+    /// The directory was gone when the library was last reconciled
+    #[serde(default)]
+    pub missing: bool,
+    /// Kept out of the retention policy, so its intermediates stay as they are
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
-            theme: "system".to_string(),
+            appearance: AppearanceSettings::default(),
             default_output_dir: String::new(),
             default_preset: "balanced".to_string(),
             colmap_path: None,
             brush_path: None,
             recent_productions: vec![],
+            first_run_completed: false,
+            verify_artifacts_on_open: false,
+            external_viewers: vec![],
+            output_name_template: None,
+            prefetch_concurrency: default_prefetch_concurrency(),
+            capture_profiles: vec![],
+            network: NetworkSettings::default(),
+            scratch_dir: None,
+            undo_window_secs: default_undo_window_secs(),
+            ffmpeg_path: None,
+            unsafe_output_locations: UnsafeOutputPolicy::default(),
+            preset_training: BTreeMap::new(),
+            preset_min_vram_gb: BTreeMap::new(),
+            known_devices: vec![],
+            checkpoints: CheckpointSettings::default(),
+            gvcore_cli_path: None,
+            cache_max_bytes: default_cache_max_bytes(),
+            min_registered_percent: default_min_registered_percent(),
+            wait_for_vram: false,
+            vram_wait_secs: default_vram_wait_secs(),
+            auto_retry: RetrySettings::default(),
+            workers: WorkerSettings::default(),
+            post_run_hooks: vec![],
+            post_run_hooks_enabled: false,
+            retention: RetentionSettings::default(),
+            notifications: NotificationSettings::default(),
+        }
+    }
+}
+
+/// How soon a settings update reaches disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Persist {
+    /// Written before the update returns; used for explicit saves
+    Now,
+    /// Coalesced and written shortly afterwards; used for high-frequency updates
+    Debounced,
+}
+
+/// Delay before a debounced update is written
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Access to the application settings
+pub trait SettingsStore: Send + Sync {
+    /// Snapshot of the current settings
+    fn settings(&self) -> AppSettings;
+
+    /// Apply `update` and persist the result. Updates are serialized, and an update
+    /// that fails (or whose immediate write fails) leaves the settings untouched.
+    fn update_settings<T, E: From<String>>(
+        &self,
+        persist: Persist,
+        update: impl FnOnce(&mut AppSettings) -> Result<T, E>,
+    ) -> Result<T, E>;
+}
+
+impl<R: Runtime> SettingsStore for AppHandle<R> {
+    fn settings(&self) -> AppSettings {
+        self.state::<SettingsState>().settings()
+    }
+
+    fn update_settings<T, E: From<String>>(
+        &self,
+        persist: Persist,
+        update: impl FnOnce(&mut AppSettings) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.state::<SettingsState>()
+            .update_settings(persist, update)
+    }
+}
+
+/// Settings held in memory for the lifetime of the app, managed as Tauri state
+pub struct SettingsState {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Settings file of the active profile, None when nothing is persisted
+    path: RwLock<Option<PathBuf>>,
+    settings: RwLock<AppSettings>,
+    /// In-memory settings differ from what is on disk
+    dirty: AtomicBool,
+    flush_scheduled: AtomicBool,
+}
+
+impl SettingsState {
+    /// Load settings once at startup. An unreadable file is set aside and defaults are used,
+    /// so the next save does not overwrite it.
+    pub fn load(paths: &impl PathProvider) -> Result<Self, String> {
+        let path = settings_path(paths)?;
+        let settings = match read(&path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                datafile::quarantine(&path, "the settings", e);
+                AppSettings::default()
+            }
+        };
+
+        Ok(Self::with(Some(path), settings))
+    }
+
+    /// Defaults that are never written anywhere, for when there is no app data
+    /// to keep settings in
+    pub fn in_memory() -> Self {
+        Self::with(None, AppSettings::default())
+    }
+
+    fn with(path: Option<PathBuf>, settings: AppSettings) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                path: RwLock::new(path),
+                settings: RwLock::new(settings),
+                dirty: AtomicBool::new(false),
+                flush_scheduled: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Write any pending debounced update now
+    pub fn flush(&self) {
+        self.inner.flush();
+    }
+
+    /// Write out the current settings and use those in `path` from now on.
+    /// An unreadable file is set aside and defaults are used, as at startup.
+    pub fn switch_to(&self, path: PathBuf) -> Result<(), String> {
+        self.inner.flush();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let loaded = match read(&path) {
+            Ok(settings) => settings,
+            Err(e) => {
+                datafile::quarantine(&path, "the settings", e);
+                AppSettings::default()
+            }
+        };
+        let mut settings = self.inner.settings.write().unwrap();
+        *self.inner.path.write().unwrap() = Some(path);
+        *settings = loaded;
+        self.inner.dirty.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl Inner {
+    // Holds the write lock so a flush never interleaves with another write of the file
+    #[allow(clippy::readonly_write_lock)]
+    fn flush(&self) {
+        let settings = self.settings.write().unwrap();
+        if self.dirty.swap(false, Ordering::SeqCst) {
+            let Some(path) = &*self.path.read().unwrap() else {
+                return;
+            };
+            if let Err(e) = write(path, &settings) {
+                eprintln!("Failed to save settings: {}", e);
+                self.dirty.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+impl SettingsStore for SettingsState {
+    fn settings(&self) -> AppSettings {
+        self.inner.settings.read().unwrap().clone()
+    }
+
+    fn update_settings<T, E: From<String>>(
+        &self,
+        persist: Persist,
+        update: impl FnOnce(&mut AppSettings) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut settings = self.inner.settings.write().unwrap();
+        let mut updated = settings.clone();
+        let result = update(&mut updated)?;
+
+        match persist {
+            Persist::Now => {
+                if let Some(path) = &*self.inner.path.read().unwrap() {
+                    write(path, &updated)?;
+                }
+                *settings = updated;
+                self.inner.dirty.store(false, Ordering::SeqCst);
+            }
+            Persist::Debounced => {
+                *settings = updated;
+                self.inner.dirty.store(true, Ordering::SeqCst);
+                if !self.inner.flush_scheduled.swap(true, Ordering::SeqCst) {
+                    let inner = self.inner.clone();
+                    tauri::async_runtime::spawn(async move {
+                        tokio::time::sleep(DEBOUNCE).await;
+                        inner.flush_scheduled.store(false, Ordering::SeqCst);
+                        inner.flush();
+                    });
+                }
+            }
         }
+        Ok(result)
+    }
+}
+
+// The active profile's settings file, moving settings kept before profiles into the default one
+fn settings_path(paths: &impl PathProvider) -> Result<PathBuf, String> {
+    let app_data = paths.app_data_dir()?;
+    settings_profiles::migrate(&app_data)?;
+    let path = settings_profiles::active_settings_path(&app_data);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    Ok(path)
+}
+
+// Read settings from disk, falling back to defaults when none are saved
+fn read(path: &Path) -> Result<AppSettings, String> {
+    if path.exists() {
+        let content = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&content).map_err(|e| written_by::explain(&content, e))
+    } else {
+        Ok(AppSettings::default())
+    }
+}
+
+fn write(path: &Path, settings: &AppSettings) -> Result<(), String> {
+    fsutil::write_json_atomic(path, &written_by::stamp(FileFormat::Settings, settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::appearance::Theme;
+    use crate::platform::testing::TempPaths;
+
+    fn on_disk(paths: &TempPaths) -> AppSettings {
+        read(&settings_path(paths).unwrap()).unwrap()
+    }
+
+    fn set_theme(theme: Theme) -> impl FnOnce(&mut AppSettings) -> Result<(), String> {
+        move |s| {
+            s.appearance.theme = theme;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn load_falls_back_to_defaults() {
+        let paths = TempPaths::new();
+        let settings = SettingsState::load(&paths).unwrap().settings();
+        assert_eq!(settings.appearance.theme, Theme::System);
+        assert_eq!(settings.default_preset, "balanced");
+        assert!(settings.recent_productions.is_empty());
+    }
+
+    #[test]
+    fn in_memory_settings_update_without_writing() {
+        let state = SettingsState::in_memory();
+        state
+            .update_settings(Persist::Now, set_theme(Theme::Dark))
+            .unwrap();
+        state.flush();
+        assert_eq!(state.settings().appearance.theme, Theme::Dark);
+    }
+
+    #[test]
+    fn immediate_update_is_persisted_before_returning() {
+        let paths = TempPaths::new();
+        let state = SettingsState::load(&paths).unwrap();
+        state
+            .update_settings(Persist::Now, |s| {
+                s.appearance.theme = Theme::Dark;
+                s.recent_productions.push(RecentProduction {
+                    id: "p1".to_string(),
+                    name: "Stadium".to_string(),
+                    path: "/productions/stadium".to_string(),
+                    last_opened: "2024-05-01".to_string(),
+                    tags: vec!["sports".to_string()],
+                    notes: "north stand".to_string(),
+                    imported: false,
+                    missing: false,
+                    pinned: false,
+                });
+                Ok::<_, String>(())
+            })
+            .unwrap();
+
+        let saved = on_disk(&paths);
+        assert_eq!(saved.appearance.theme, Theme::Dark);
+        assert_eq!(saved.recent_productions[0].tags, vec!["sports"]);
+        assert_eq!(saved.recent_productions[0].notes, "north stand");
+        assert_eq!(
+            SettingsState::load(&paths)
+                .unwrap()
+                .settings()
+                .appearance
+                .theme,
+            Theme::Dark
+        );
+    }
+
+    #[test]
+    fn failed_update_leaves_settings_untouched() {
+        let paths = TempPaths::new();
+        let state = SettingsState::load(&paths).unwrap();
+        let result: Result<(), String> = state.update_settings(Persist::Now, |s| {
+            s.appearance.theme = Theme::Dark;
+            Err("rejected".to_string())
+        });
+
+        assert_eq!(result.unwrap_err(), "rejected");
+        assert_eq!(state.settings().appearance.theme, Theme::System);
+        assert!(!settings_path(&paths).unwrap().exists());
+    }
+
+    #[test]
+    fn concurrent_updates_are_not_lost() {
+        let paths = TempPaths::new();
+        let state = SettingsState::load(&paths).unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let state = &state;
+                scope.spawn(move || {
+                    state
+                        .update_settings(Persist::Now, |s| {
+                            s.recent_productions.push(RecentProduction {
+                                id: format!("p{}", i),
+                                name: format!("Production {}", i),
+                                path: format!("/productions/{}", i),
+                                last_opened: String::new(),
+                                tags: vec![],
+                                notes: String::new(),
+                                imported: false,
+                                missing: false,
+                                pinned: false,
+                            });
+                            Ok::<_, String>(())
+                        })
+                        .unwrap();
+                });
+            }
+        });
+
+        assert_eq!(state.settings().recent_productions.len(), 8);
+        assert_eq!(on_disk(&paths).recent_productions.len(), 8);
+    }
+
+    #[tokio::test]
+    async fn debounced_updates_are_coalesced() {
+        let paths = TempPaths::new();
+        let state = SettingsState::load(&paths).unwrap();
+        state
+            .update_settings(Persist::Debounced, set_theme(Theme::Dark))
+            .unwrap();
+        state
+            .update_settings(Persist::Debounced, set_theme(Theme::Light))
+            .unwrap();
+
+        assert_eq!(state.settings().appearance.theme, Theme::Light);
+        assert!(!settings_path(&paths).unwrap().exists());
+
+        tokio::time::sleep(DEBOUNCE * 2).await;
+        assert_eq!(on_disk(&paths).appearance.theme, Theme::Light);
+    }
+
+    #[test]
+    fn flush_writes_pending_debounced_update() {
+        let paths = TempPaths::new();
+        let state = SettingsState::load(&paths).unwrap();
+        state
+            .update_settings(Persist::Debounced, set_theme(Theme::Dark))
+            .unwrap();
+        state.flush();
+        assert_eq!(on_disk(&paths).appearance.theme, Theme::Dark);
+    }
+
+    #[test]
+    fn loads_settings_saved_by_older_versions() {
+        let paths = TempPaths::new();
+        std::fs::write(
+            settings_path(&paths).unwrap(),
+            r#"{"theme":"light","defaultOutputDir":"/out","defaultPreset":"fast","colmapPath":null,"brushPath":null,
+                "recentProductions":[{"id":"p1","name":"Old","path":"/old","lastOpened":"2023-01-01"}]}"#,
+        )
+        .unwrap();
+
+        let loaded = SettingsState::load(&paths).unwrap().settings();
+        assert_eq!(loaded.appearance.theme, Theme::Light);
+        assert!(!loaded.first_run_completed);
+        assert!(loaded.recent_productions[0].tags.is_empty());
+        assert!(loaded.recent_productions[0].notes.is_empty());
+    }
+
+    #[test]
+    fn unreadable_file_is_set_aside() {
+        let paths = TempPaths::new();
+        let path = settings_path(&paths).unwrap();
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let loaded = SettingsState::load(&paths).unwrap().settings();
+        assert_eq!(loaded.appearance.theme, Theme::System);
+        let set_aside = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .any(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                name.starts_with("settings.json.corrupt-")
+            });
+        assert!(set_aside);
+        assert!(!path.exists());
     }
 }
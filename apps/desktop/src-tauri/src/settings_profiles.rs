@@ -0,0 +1,296 @@
+//! Settings Profiles
+//!
+//! Artists sharing one OS login on a studio workstation each want their own
+//! tool paths, output conventions and recent productions. Settings are kept
+//! per named profile in app_data/profiles/<name>/settings.json, and
+//! profiles.json in app_data names the active one. Everything that reads or
+//! writes settings, recents included, goes through SettingsState, which holds
+//! the active profile's; switch_profile writes them out and loads another's.
+//! A switch is refused while a job runs, since the job started with the
+//! settings it would leave behind.
+//!
+//! The settings.json of versions before profiles is moved into the default
+//! profile the first time this version starts.
+
+use crate::error::AppError;
+use crate::fsutil;
+use crate::jobs;
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::settings::{self, AppSettings, SettingsState, SettingsStore};
+use crate::{appearance, cache, cli_location, ffmpeg, scheduler};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// The profile settings are kept in until another is created
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Directory of app_data holding a directory per profile
+const DIR_NAME: &str = "profiles";
+
+/// File in app_data naming the active profile
+pub const ACTIVE_FILE: &str = "profiles.json";
+
+/// Longest profile name
+const MAX_NAME_CHARS: usize = 64;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ActiveProfile {
+    active: Option<String>,
+}
+
+/// The profiles there are, sorted by name, and the one in use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<String>,
+}
+
+/// The settings file of profile `name`
+pub fn settings_path(app_data: &Path, name: &str) -> PathBuf {
+    app_data.join(DIR_NAME).join(name).join(settings::FILE_NAME)
+}
+
+/// The settings file of the active profile
+pub fn active_settings_path(app_data: &Path) -> PathBuf {
+    settings_path(app_data, &active(app_data))
+}
+
+/// The profile in use; the default one when none was chosen or the chosen
+/// one is gone
+pub fn active(app_data: &Path) -> String {
+    let chosen = std::fs::read_to_string(app_data.join(ACTIVE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str::<ActiveProfile>(&content).ok())
+        .and_then(|file| file.active);
+    match chosen {
+        Some(name) if app_data.join(DIR_NAME).join(&name).is_dir() => name,
+        _ => DEFAULT_PROFILE.to_string(),
+    }
+}
+
+/// Move settings.json from the top of app_data into the default profile,
+/// unless the profile already has settings of its own
+pub fn migrate(app_data: &Path) -> Result<(), String> {
+    let flat = app_data.join(settings::FILE_NAME);
+    let target = settings_path(app_data, DEFAULT_PROFILE);
+    if !flat.is_file() || target.exists() {
+        return Ok(());
+    }
+    if let Some(dir) = target.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::rename(&flat, &target).map_err(|e| e.to_string())
+}
+
+fn list(app_data: &Path) -> Result<ProfileList, AppError> {
+    let active = active(app_data);
+    let mut profiles = vec![];
+    if let Ok(entries) = std::fs::read_dir(app_data.join(DIR_NAME)) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                profiles.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+    }
+    if !profiles.contains(&active) {
+        profiles.push(active.clone());
+    }
+    profiles.sort();
+    Ok(ProfileList { active, profiles })
+}
+
+fn check_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    let allowed = |c: char| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.');
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME_CHARS
+        || name.starts_with('.')
+        || !name.chars().all(allowed)
+    {
+        return Err(AppError::InvalidInput(format!(
+            "\"{}\" is not a valid profile name; use up to {} letters, digits, spaces, dots, dashes and underscores",
+            name, MAX_NAME_CHARS
+        )));
+    }
+    Ok(name)
+}
+
+fn existing(app_data: &Path, name: &str) -> Result<PathBuf, AppError> {
+    let dir = app_data.join(DIR_NAME).join(check_name(name)?);
+    if dir.is_dir() {
+        Ok(dir)
+    } else {
+        Err(AppError::NotFound(format!("settings profile {}", name)))
+    }
+}
+
+/// Make profile `name`, starting from the settings of `copy_from` or from defaults
+pub fn create(app_data: &Path, name: &str, copy_from: Option<&str>) -> Result<(), AppError> {
+    let name = check_name(name)?;
+    let dir = app_data.join(DIR_NAME).join(name);
+    if dir.exists() {
+        return Err(AppError::InvalidInput(format!(
+            "A profile called {} already exists",
+            name
+        )));
+    }
+    let source = copy_from
+        .map(|from| existing(app_data, from).map(|dir| dir.join(settings::FILE_NAME)))
+        .transpose()?;
+    std::fs::create_dir_all(&dir)?;
+    // A profile whose settings were never saved starts from defaults either way
+    if let Some(source) = source.filter(|s| s.is_file()) {
+        if let Err(e) = std::fs::copy(&source, dir.join(settings::FILE_NAME)) {
+            std::fs::remove_dir_all(&dir).ok();
+            return Err(e.into());
+        }
+    }
+    Ok(())
+}
+
+/// Remove profile `name` and its settings; the active profile cannot be removed
+pub fn delete(app_data: &Path, name: &str) -> Result<(), AppError> {
+    let dir = existing(app_data, name)?;
+    if active(app_data) == name.trim() {
+        return Err(AppError::InvalidInput(
+            "The active profile cannot be deleted; switch to another one first".to_string(),
+        ));
+    }
+    std::fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+fn set_active(app_data: &Path, name: &str) -> Result<(), AppError> {
+    let file = ActiveProfile {
+        active: Some(name.to_string()),
+    };
+    fsutil::write_json_atomic(&app_data.join(ACTIVE_FILE), &file)?;
+    Ok(())
+}
+
+/// Apply settings that were just loaded to the parts of the app configured from them
+fn configure(app: &AppHandle, settings: &AppSettings) {
+    if let Some(policy) = app.try_state::<PathPolicy>() {
+        policy.allow_configured(settings);
+    }
+    ffmpeg::configure(app, settings);
+    cli_location::configure(settings);
+    cache::configure(settings);
+    scheduler::configure(settings);
+    appearance::apply(app, &settings.appearance);
+}
+
+/// The settings profiles and the active one
+#[tauri::command]
+pub async fn list_profiles(app: AppHandle) -> Result<ProfileList, AppError> {
+    list(&app.app_data_dir()?)
+}
+
+/// Make a profile, with a copy of another's settings or with defaults
+#[tauri::command]
+pub async fn create_profile(
+    app: AppHandle,
+    state: State<'_, SettingsState>,
+    name: String,
+    copy_from: Option<String>,
+) -> Result<ProfileList, AppError> {
+    let app_data = app.app_data_dir()?;
+    // The active profile's file may not have its latest changes yet
+    state.flush();
+    create(&app_data, &name, copy_from.as_deref())?;
+    list(&app_data)
+}
+
+/// Use profile `name` from now on; refused while a job runs
+#[tauri::command]
+pub async fn switch_profile(
+    app: AppHandle,
+    state: State<'_, SettingsState>,
+    name: String,
+) -> Result<ProfileList, AppError> {
+    let app_data = app.app_data_dir()?;
+    existing(&app_data, &name)?;
+    let name = name.trim();
+    if active(&app_data) == name {
+        return list(&app_data);
+    }
+    if jobs::any_active() {
+        return Err(AppError::JobRunning);
+    }
+    state.switch_to(settings_path(&app_data, name))?;
+    set_active(&app_data, name)?;
+    configure(&app, &state.settings());
+    let profiles = list(&app_data)?;
+    app.emit("settings-profile-changed", &profiles).ok();
+    Ok(profiles)
+}
+
+/// Remove a profile other than the active one
+#[tauri::command]
+pub async fn delete_profile(app: AppHandle, name: String) -> Result<ProfileList, AppError> {
+    let app_data = app.app_data_dir()?;
+    delete(&app_data, &name)?;
+    list(&app_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn flat_settings_move_into_the_default_profile() {
+        let paths = TempPaths::new();
+        let app_data = paths.app_data_dir().unwrap();
+        std::fs::write(app_data.join(settings::FILE_NAME), b"{\"theme\":\"dark\"}").unwrap();
+
+        migrate(&app_data).unwrap();
+        assert!(!app_data.join(settings::FILE_NAME).exists());
+        let moved = std::fs::read(active_settings_path(&app_data)).unwrap();
+        assert_eq!(moved, b"{\"theme\":\"dark\"}");
+
+        // Settings a later launch finds in the profile are not overwritten
+        std::fs::write(app_data.join(settings::FILE_NAME), b"{}").unwrap();
+        migrate(&app_data).unwrap();
+        let kept = std::fs::read(active_settings_path(&app_data)).unwrap();
+        assert_eq!(kept, b"{\"theme\":\"dark\"}");
+    }
+
+    #[test]
+    fn profiles_are_created_copied_and_deleted() {
+        let paths = TempPaths::new();
+        let app_data = paths.app_data_dir().unwrap();
+        let default = settings_path(&app_data, DEFAULT_PROFILE);
+        std::fs::create_dir_all(default.parent().unwrap()).unwrap();
+        std::fs::write(&default, b"{\"theme\":\"dark\"}").unwrap();
+
+        create(&app_data, "Mara", Some(DEFAULT_PROFILE)).unwrap();
+        create(&app_data, "Jun", None).unwrap();
+        assert!(create(&app_data, "Jun", None).is_err());
+        assert!(create(&app_data, "../escape", None).is_err());
+        assert!(create(&app_data, "Kit", Some("nobody")).is_err());
+        assert_eq!(
+            std::fs::read(settings_path(&app_data, "Mara")).unwrap(),
+            b"{\"theme\":\"dark\"}"
+        );
+        assert!(!settings_path(&app_data, "Jun").exists());
+
+        set_active(&app_data, "Mara").unwrap();
+        let profiles = list(&app_data).unwrap();
+        assert_eq!(profiles.active, "Mara");
+        assert_eq!(profiles.profiles, ["Jun", "Mara", DEFAULT_PROFILE]);
+
+        assert!(delete(&app_data, "Mara").is_err());
+        delete(&app_data, "Jun").unwrap();
+        assert!(matches!(
+            delete(&app_data, "Jun"),
+            Err(AppError::NotFound(_))
+        ));
+
+        // A chosen profile that is gone falls back to the default one
+        std::fs::remove_dir_all(app_data.join(DIR_NAME).join("Mara")).unwrap();
+        assert_eq!(active(&app_data), DEFAULT_PROFILE);
+    }
+}
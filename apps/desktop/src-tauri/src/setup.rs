@@ -0,0 +1,464 @@
+//! First-Run Setup
+//!
+//! Backend for the onboarding wizard: reports which prerequisites are met and
+//! performs each setup step. Progress is kept in app_data/setup.json so a
+//! half-finished setup resumes on the next launch.
+
+use crate::error::AppError;
+use crate::ffmpeg::{self, Tool};
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::platform_support::{self, PlatformSupport};
+use crate::settings::{AppSettings, Persist, SettingsStore};
+use crate::{cli_location, fsutil, gpu};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+use tokio::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupStep {
+    Cli,
+    Tools,
+    Ffmpeg,
+    OutputDir,
+    Gpu,
+    Finish,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StepStatus {
+    pub step: SetupStep,
+    pub satisfied: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetupStatus {
+    pub steps: Vec<StepStatus>,
+    pub completed_steps: Vec<SetupStep>,
+    pub first_run_completed: bool,
+    /// The host checked against the support matrix at startup
+    pub platform: Option<PlatformSupport>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SetupState {
+    completed_steps: Vec<SetupStep>,
+}
+
+fn state_path(paths: &impl PathProvider) -> Result<PathBuf, String> {
+    Ok(paths.app_data_dir()?.join("setup.json"))
+}
+
+fn load_state(paths: &impl PathProvider) -> Result<SetupState, String> {
+    let path = state_path(paths)?;
+    if !path.exists() {
+        return Ok(SetupState::default());
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| e.to_string())
+}
+
+/// Report which setup prerequisites are satisfied
+#[tauri::command]
+pub async fn get_setup_status(app: AppHandle) -> Result<SetupStatus, AppError> {
+    status(&app).await
+}
+
+/// Perform one setup step and return the updated status
+#[tauri::command]
+pub async fn complete_setup_step(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    step: SetupStep,
+    payload: Option<Value>,
+) -> Result<SetupStatus, AppError> {
+    let payload = payload.unwrap_or(Value::Null);
+
+    match step {
+        SetupStep::Cli => {
+            let check = check_cli(&app).await;
+            if !check.satisfied {
+                return Err(AppError::NotFound(check.detail));
+            }
+        }
+        SetupStep::Tools => {
+            let colmap_path = locate_tool(&policy, &payload, "colmap_path", "colmap")?;
+            let brush_path = locate_tool(&policy, &payload, "brush_path", "brush")?;
+            app.update_settings(Persist::Now, |s| {
+                s.colmap_path = Some(colmap_path);
+                s.brush_path = Some(brush_path);
+                Ok::<_, AppError>(())
+            })?;
+        }
+        SetupStep::Ffmpeg => {
+            if let Some(path) = payload["ffmpeg_path"].as_str() {
+                policy.check_existing(path)?;
+                if !Path::new(path).is_file() {
+                    return Err(AppError::NotFound(path.to_string()));
+                }
+                let settings = app.update_settings(Persist::Now, |s| {
+                    s.ffmpeg_path = Some(path.to_string());
+                    Ok::<_, AppError>(s.clone())
+                })?;
+                ffmpeg::configure(&app, &settings);
+            }
+            let check = check_ffmpeg().await;
+            if !check.satisfied {
+                return Err(AppError::NotFound(check.detail));
+            }
+        }
+        SetupStep::OutputDir => {
+            let path = payload["path"].as_str().ok_or_else(|| {
+                AppError::InvalidInput("An output directory is required".to_string())
+            })?;
+            policy.check_target(path)?;
+            std::fs::create_dir_all(path)?;
+            if !is_writable(Path::new(path)) {
+                return Err(AppError::InvalidInput(format!(
+                    "Output directory is not writable: {}",
+                    path
+                )));
+            }
+            app.update_settings(Persist::Now, |s| {
+                s.default_output_dir = path.to_string();
+                Ok::<_, AppError>(())
+            })?;
+        }
+        // An inadequate GPU is reported but the user may continue anyway
+        SetupStep::Gpu => {}
+        SetupStep::Finish => {
+            let current = status(&app).await?;
+            let blocking = finish_blockers(&current.steps);
+            if !blocking.is_empty() {
+                return Err(AppError::InvalidInput(blocking.join("; ")));
+            }
+            app.update_settings(Persist::Now, |s| {
+                s.first_run_completed = true;
+                Ok::<_, AppError>(())
+            })?;
+        }
+    }
+
+    mark_completed(&app, step)?;
+    status(&app).await
+}
+
+// Record `step` as done, so a later launch resumes after it
+fn mark_completed(paths: &impl PathProvider, step: SetupStep) -> Result<(), String> {
+    let mut state = load_state(paths)?;
+    if !state.completed_steps.contains(&step) {
+        state.completed_steps.push(step);
+        fsutil::write_json_atomic(&state_path(paths)?, &state)?;
+    }
+    Ok(())
+}
+
+// Why setup cannot finish yet; only the CLI and an output directory are required
+fn finish_blockers(steps: &[StepStatus]) -> Vec<String> {
+    steps
+        .iter()
+        .filter(|s| matches!(s.step, SetupStep::Cli | SetupStep::OutputDir))
+        .filter(|s| !s.satisfied)
+        .map(|s| s.detail.clone())
+        .collect()
+}
+
+async fn status(app: &AppHandle) -> Result<SetupStatus, AppError> {
+    let app_settings = app.settings();
+    let state = load_state(app)?;
+
+    let steps = vec![
+        check_cli(app).await,
+        check_tools(&app_settings),
+        check_ffmpeg().await,
+        check_output_dir(&app_settings.default_output_dir),
+        check_gpu().await,
+    ];
+
+    Ok(SetupStatus {
+        steps,
+        completed_steps: state.completed_steps,
+        first_run_completed: app_settings.first_run_completed,
+        platform: platform_support::of(app),
+    })
+}
+
+async fn check_cli(app: &AppHandle) -> StepStatus {
+    let resolution = cli_location::resolve(app);
+    let cli_path = match resolution.program() {
+        Ok(cli_path) => cli_path,
+        Err(detail) => {
+            return StepStatus {
+                step: SetupStep::Cli,
+                satisfied: false,
+                detail,
+            }
+        }
+    };
+    let output = Command::new(&cli_path).arg("--version").output().await;
+
+    let (satisfied, detail) = match output {
+        Ok(out) if out.status.success() => {
+            let version = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            if resolution.emulated() {
+                let detail = format!(
+                    "{}, running under emulation; a build for {} would be faster",
+                    version, resolution.host
+                );
+                (true, detail)
+            } else {
+                (true, version)
+            }
+        }
+        Ok(out) => (false, format!("gvcore-cli failed to run: {}", out.status)),
+        Err(_) => (false, format!("gvcore-cli not found at {}", cli_path)),
+    };
+    StepStatus {
+        step: SetupStep::Cli,
+        satisfied,
+        detail,
+    }
+}
+
+fn check_tools(app_settings: &AppSettings) -> StepStatus {
+    let colmap = configured_or_path(app_settings.colmap_path.as_deref(), "colmap");
+    let brush = configured_or_path(app_settings.brush_path.as_deref(), "brush");
+
+    let missing: Vec<&str> = [("COLMAP", &colmap), ("Brush", &brush)]
+        .iter()
+        .filter(|(_, found)| found.is_none())
+        .map(|(name, _)| *name)
+        .collect();
+
+    StepStatus {
+        step: SetupStep::Tools,
+        satisfied: missing.is_empty(),
+        detail: if missing.is_empty() {
+            "COLMAP and Brush found".to_string()
+        } else {
+            format!("Not found: {}", missing.join(", "))
+        },
+    }
+}
+
+/// Which ffmpeg and ffprobe are used and where they were found
+async fn check_ffmpeg() -> StepStatus {
+    let ffmpeg = ffmpeg::status(Tool::Ffmpeg).await;
+    let ffprobe = ffmpeg::status(Tool::Ffprobe).await;
+    StepStatus {
+        step: SetupStep::Ffmpeg,
+        satisfied: ffmpeg.supported && ffprobe.supported,
+        detail: format!(
+            "{}; {}",
+            ffmpeg::describe(&ffmpeg),
+            ffmpeg::describe(&ffprobe)
+        ),
+    }
+}
+
+fn check_output_dir(dir: &str) -> StepStatus {
+    let (satisfied, detail) = if dir.is_empty() {
+        (false, "No default output directory chosen".to_string())
+    } else if !is_writable(Path::new(dir)) {
+        (false, format!("Output directory is not writable: {}", dir))
+    } else {
+        (true, dir.to_string())
+    };
+    StepStatus {
+        step: SetupStep::OutputDir,
+        satisfied,
+        detail,
+    }
+}
+
+async fn check_gpu() -> StepStatus {
+    let (satisfied, detail) = match gpu::detect().await {
+        Some(gpu::GpuInfo {
+            name,
+            vram_mb: Some(vram),
+        }) if vram < gpu::MIN_VRAM_MB => (
+            false,
+            format!(
+                "{} has {} MB VRAM; at least {} MB is recommended",
+                name,
+                vram,
+                gpu::MIN_VRAM_MB
+            ),
+        ),
+        Some(gpu) => (true, gpu.name),
+        None => (false, "No supported GPU detected".to_string()),
+    };
+    StepStatus {
+        step: SetupStep::Gpu,
+        satisfied,
+        detail,
+    }
+}
+
+// Use the path from the payload if given, otherwise search PATH
+fn locate_tool(
+    policy: &PathPolicy,
+    payload: &Value,
+    key: &str,
+    binary: &str,
+) -> Result<String, AppError> {
+    if let Some(path) = payload[key].as_str() {
+        policy.check_existing(path)?;
+        return if Path::new(path).is_file() {
+            Ok(path.to_string())
+        } else {
+            Err(AppError::NotFound(path.to_string()))
+        };
+    }
+
+    find_in_path(binary)
+        .map(|p| p.to_string_lossy().to_string())
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "{} (automatic download is not available; select it manually)",
+                binary
+            ))
+        })
+}
+
+fn configured_or_path(configured: Option<&str>, binary: &str) -> Option<PathBuf> {
+    match configured {
+        Some(path) if Path::new(path).is_file() => Some(PathBuf::from(path)),
+        _ => find_in_path(binary),
+    }
+}
+
+/// Find an executable on the system PATH
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let name = if cfg!(target_os = "windows") {
+        format!("{}.exe", binary)
+    } else {
+        binary.to_string()
+    };
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(&name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Whether a file can be created and removed in `dir`
+pub fn is_writable(dir: &Path) -> bool {
+    let probe = dir.join(".gameview-write-probe");
+    let writable = std::fs::write(&probe, b"").is_ok();
+    std::fs::remove_file(&probe).ok();
+    writable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use serde_json::json;
+
+    #[test]
+    fn tools_are_taken_from_the_payload_or_found_on_the_path() {
+        let paths = TempPaths::new();
+        let colmap = paths.root().join("colmap");
+        std::fs::write(&colmap, b"").unwrap();
+        let colmap = colmap.to_string_lossy().to_string();
+        let policy = PathPolicy::default();
+        let payload = json!({ "colmap_path": colmap, "brush_path": paths.root() });
+
+        // Only paths the user picked may be used
+        assert!(matches!(
+            locate_tool(&policy, &payload, "colmap_path", "colmap"),
+            Err(AppError::PathNotAllowed(_))
+        ));
+        policy.allow(paths.root());
+        assert_eq!(
+            locate_tool(&policy, &payload, "colmap_path", "colmap").unwrap(),
+            colmap
+        );
+        assert!(matches!(
+            locate_tool(&policy, &payload, "brush_path", "brush"),
+            Err(AppError::NotFound(_))
+        ));
+        let missing = locate_tool(&policy, &Value::Null, "brush_path", "gameview-no-such-tool");
+        assert!(matches!(missing, Err(AppError::NotFound(_))));
+    }
+
+    #[test]
+    fn configured_tools_count_only_while_they_exist() {
+        let paths = TempPaths::new();
+        let colmap = paths.root().join("colmap");
+        std::fs::write(&colmap, b"").unwrap();
+        let settings = AppSettings {
+            colmap_path: Some(colmap.to_string_lossy().to_string()),
+            brush_path: Some(paths.root().join("brush").to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            configured_or_path(settings.colmap_path.as_deref(), "gameview-no-such-tool"),
+            Some(colmap)
+        );
+        assert_eq!(
+            configured_or_path(settings.brush_path.as_deref(), "gameview-no-such-tool"),
+            None
+        );
+        // Unless this machine has Brush on its PATH
+        if find_in_path("brush").is_none() {
+            let check = check_tools(&settings);
+            assert!(!check.satisfied);
+            assert_eq!(check.detail, "Not found: Brush");
+        }
+    }
+
+    #[test]
+    fn the_output_dir_must_be_chosen_and_writable() {
+        let paths = TempPaths::new();
+        assert!(!check_output_dir("").satisfied);
+        let dir = paths.root().to_string_lossy().to_string();
+        let check = check_output_dir(&dir);
+        assert!(check.satisfied);
+        assert_eq!(check.detail, dir);
+
+        // Nothing can be created under a regular file, even as root
+        let file = paths.root().join("file");
+        std::fs::write(&file, b"").unwrap();
+        let check = check_output_dir(&file.join("out").to_string_lossy());
+        assert!(!check.satisfied);
+        assert!(check.detail.starts_with("Output directory is not writable"));
+    }
+
+    #[test]
+    fn steps_are_recorded_once_and_finishing_needs_the_cli_and_an_output_dir() {
+        let paths = TempPaths::new();
+        assert!(load_state(&paths).unwrap().completed_steps.is_empty());
+        mark_completed(&paths, SetupStep::Cli).unwrap();
+        mark_completed(&paths, SetupStep::Tools).unwrap();
+        mark_completed(&paths, SetupStep::Cli).unwrap();
+        assert_eq!(
+            load_state(&paths).unwrap().completed_steps,
+            [SetupStep::Cli, SetupStep::Tools]
+        );
+
+        let step = |step, satisfied| StepStatus {
+            step,
+            satisfied,
+            detail: format!("{:?}", step),
+        };
+        let mut steps = vec![
+            step(SetupStep::Cli, false),
+            step(SetupStep::Tools, false),
+            step(SetupStep::OutputDir, false),
+            step(SetupStep::Gpu, false),
+        ];
+        assert_eq!(finish_blockers(&steps), ["Cli", "OutputDir"]);
+        steps[0].satisfied = true;
+        steps[2].satisfied = true;
+        // Tools and a GPU are only recommended
+        assert!(finish_blockers(&steps).is_empty());
+    }
+}
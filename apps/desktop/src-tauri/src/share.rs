@@ -0,0 +1,505 @@
+//! LAN Sharing
+//!
+//! Serves one finished production over HTTP so reviewers on the same network can
+//! open it in a browser without installing anything. The server only answers a
+//! viewer page and that production's artifact, as the PLY itself or converted
+//! to `.splat` or `.spz`, listens on the LAN interface rather than every
+//! address, and by default requires a token carried in the link.
+
+use crate::conversion;
+use crate::error::AppError;
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::safe_mode::{self, Piece};
+use crate::scheduler::{self, Pool};
+use crate::sidecar;
+use axum::body::{Body, Bytes};
+use axum::extract::{RawQuery, State as Extract};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, State};
+use tokio::net::TcpListener;
+use tokio::sync::OnceCell;
+use tokio::task::JoinHandle;
+use tokio_util::io::ReaderStream;
+
+const VIEWER_PAGE: &str = include_str!("share/viewer.html");
+
+const QR_FILE: &str = "share/share-qr.png";
+
+/// Pixels per QR module and width of the quiet zone in modules
+const QR_SCALE: usize = 8;
+const QR_BORDER: usize = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareInfo {
+    /// Link to open on the reviewer's device, including the access token
+    pub url: String,
+    pub production_path: String,
+    pub qr_code_path: String,
+    pub token_required: bool,
+}
+
+/// The share server, if one is running; managed as Tauri state
+#[derive(Default)]
+pub struct ShareState {
+    running: Mutex<Option<Running>>,
+}
+
+struct Running {
+    info: ShareInfo,
+    handle: JoinHandle<()>,
+}
+
+/// What the server may hand out: one artifact and nothing else
+struct Shared {
+    artifact: PathBuf,
+    token: Option<String>,
+    splat: OnceCell<Result<Bytes, String>>,
+    spz: OnceCell<Result<Bytes, String>>,
+}
+
+impl ShareState {
+    /// Serve `production_dir` on `listener`, replacing any server already running
+    pub fn start(
+        &self,
+        listener: TcpListener,
+        production_dir: &Path,
+        token: Option<String>,
+        qr_path: &Path,
+    ) -> Result<ShareInfo, AppError> {
+        let artifact = sidecar::find_artifact(production_dir)?;
+        let addr = listener.local_addr()?;
+        let url = share_url(addr, token.as_deref());
+        write_qr_code(&url, qr_path)?;
+
+        let info = ShareInfo {
+            url,
+            production_path: production_dir.to_string_lossy().to_string(),
+            qr_code_path: qr_path.to_string_lossy().to_string(),
+            token_required: token.is_some(),
+        };
+        let shared = Arc::new(Shared {
+            artifact,
+            token,
+            splat: OnceCell::new(),
+            spz: OnceCell::new(),
+        });
+        let handle = tokio::spawn(async move {
+            axum::serve(listener, router(shared)).await.ok();
+        });
+
+        let previous = self.running.lock().unwrap().replace(Running {
+            info: info.clone(),
+            handle,
+        });
+        if let Some(previous) = previous {
+            previous.handle.abort();
+        }
+        Ok(info)
+    }
+
+    /// Stop serving; dropping the task closes the listener and open connections
+    pub fn stop(&self) {
+        if let Some(running) = self.running.lock().unwrap().take() {
+            running.handle.abort();
+        }
+    }
+
+    pub fn status(&self) -> Option<ShareInfo> {
+        self.running
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|running| running.info.clone())
+    }
+}
+
+/// Serve a production to browsers on the local network and return its link and QR code
+#[tauri::command]
+pub async fn start_share_server(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    share: State<'_, ShareState>,
+    production_path: String,
+    port: Option<u16>,
+    require_token: Option<bool>,
+) -> Result<ShareInfo, AppError> {
+    policy.check_existing(&production_path)?;
+    safe_mode::check(Piece::ShareServer)?;
+    let ip = lan_address()
+        .ok_or_else(|| AppError::Io("No local network connection to share on".to_string()))?;
+    let listener = TcpListener::bind((ip, port.unwrap_or(0)))
+        .await
+        .map_err(|e| AppError::Io(format!("Cannot listen on {}: {}", ip, e)))?;
+
+    let token = require_token.unwrap_or(true).then(generate_token);
+    let qr_path = app.app_data_dir()?.join(QR_FILE);
+    share.start(listener, Path::new(&production_path), token, &qr_path)
+}
+
+#[tauri::command]
+pub async fn stop_share_server(share: State<'_, ShareState>) -> Result<(), AppError> {
+    share.stop();
+    Ok(())
+}
+
+/// The running share server, or None
+#[tauri::command]
+pub async fn get_share_status(share: State<'_, ShareState>) -> Result<Option<ShareInfo>, AppError> {
+    Ok(share.status())
+}
+
+fn router(shared: Arc<Shared>) -> Router {
+    Router::new()
+        .route("/", get(viewer))
+        .route("/scene.splat", get(scene_splat))
+        .route("/scene.spz", get(scene_spz))
+        .route("/scene.ply", get(scene_ply))
+        .layer(axum::middleware::map_response(private_headers))
+        .with_state(shared)
+}
+
+async fn viewer(Extract(shared): Extract<Arc<Shared>>, RawQuery(query): RawQuery) -> Response {
+    if let Err(status) = authorize(&shared, query.as_deref()) {
+        return status.into_response();
+    }
+    Html(VIEWER_PAGE).into_response()
+}
+
+async fn scene_splat(Extract(shared): Extract<Arc<Shared>>, RawQuery(query): RawQuery) -> Response {
+    if let Err(status) = authorize(&shared, query.as_deref()) {
+        return status.into_response();
+    }
+    converted(&shared.splat, &shared.artifact, conversion::ply_to_splat).await
+}
+
+async fn scene_spz(Extract(shared): Extract<Arc<Shared>>, RawQuery(query): RawQuery) -> Response {
+    if let Err(status) = authorize(&shared, query.as_deref()) {
+        return status.into_response();
+    }
+    converted(&shared.spz, &shared.artifact, conversion::ply_to_spz).await
+}
+
+/// The artifact converted once per server, on the first request for it
+async fn converted(
+    cell: &OnceCell<Result<Bytes, String>>,
+    artifact: &Path,
+    convert: fn(&[u8]) -> Result<Vec<u8>, String>,
+) -> Response {
+    let result = cell
+        .get_or_init(|| async {
+            let artifact = artifact.to_path_buf();
+            scheduler::run_blocking(Pool::CpuHeavy, move || {
+                let ply = std::fs::read(&artifact).map_err(|e| e.to_string())?;
+                convert(&ply).map(Bytes::from)
+            })
+            .await
+            .map_err(|e| e.to_string())?
+        })
+        .await;
+
+    match result {
+        // Bytes is reference counted, so every response shares the one conversion
+        Ok(bytes) => binary(Body::from(bytes.clone()), Some(bytes.len() as u64)),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.clone()).into_response(),
+    }
+}
+
+async fn scene_ply(Extract(shared): Extract<Arc<Shared>>, RawQuery(query): RawQuery) -> Response {
+    if let Err(status) = authorize(&shared, query.as_deref()) {
+        return status.into_response();
+    }
+    // Streamed, since artifacts can be far larger than is worth holding in memory
+    let file = match tokio::fs::File::open(&shared.artifact).await {
+        Ok(file) => file,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+    let len = file.metadata().await.ok().map(|metadata| metadata.len());
+    binary(Body::from_stream(ReaderStream::new(file)), len)
+}
+
+fn binary(body: Body, len: Option<u64>) -> Response {
+    let mut response = ([(header::CONTENT_TYPE, "application/octet-stream")], body).into_response();
+    if let Some(len) = len {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+    }
+    response
+}
+
+// Keep the token out of caches and Referer headers
+async fn private_headers(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static("no-referrer"),
+    );
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    response
+}
+
+fn authorize(shared: &Shared, query: Option<&str>) -> Result<(), StatusCode> {
+    let Some(expected) = &shared.token else {
+        return Ok(());
+    };
+    let given = query
+        .unwrap_or_default()
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("t="));
+    match given {
+        Some(given) if constant_time_eq(given.as_bytes(), expected.as_bytes()) => Ok(()),
+        _ => Err(StatusCode::UNAUTHORIZED),
+    }
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn share_url(addr: SocketAddr, token: Option<&str>) -> String {
+    match token {
+        Some(token) => format!("http://{}/?t={}", addr, token),
+        None => format!("http://{}/", addr),
+    }
+}
+
+/// Address of the interface that routes to the wider network
+fn lan_address() -> Option<IpAddr> {
+    // Connecting a UDP socket only picks a route; nothing is sent
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).ok()?;
+    socket.connect(("192.0.2.1", 9)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_loopback() && !ip.is_unspecified()).then_some(ip)
+}
+
+/// Render `url` as a black-on-white grayscale PNG
+fn write_qr_code(url: &str, path: &Path) -> Result<(), AppError> {
+    let code = qrcode::QrCode::new(url).map_err(|e| AppError::Io(e.to_string()))?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let size = (modules + 2 * QR_BORDER) * QR_SCALE;
+
+    let mut pixels = vec![255u8; size * size];
+    for (i, color) in colors.iter().enumerate() {
+        if *color == qrcode::Color::Dark {
+            let (x, y) = (i % modules + QR_BORDER, i / modules + QR_BORDER);
+            for row in y * QR_SCALE..(y + 1) * QR_SCALE {
+                pixels[row * size + x * QR_SCALE..row * size + (x + 1) * QR_SCALE].fill(0);
+            }
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .map_err(|e| AppError::Io(format!("Failed to write QR code: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::TempPaths;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    struct Fixture {
+        temp: TempPaths,
+        production: PathBuf,
+        share: ShareState,
+    }
+
+    fn fixture() -> Fixture {
+        let temp = TempPaths::new();
+        let production = temp.root().join("production");
+        std::fs::create_dir_all(&production).unwrap();
+        std::fs::write(production.join("output.ply"), sample_ply(&[[0.5; 14]])).unwrap();
+        std::fs::write(production.join("settings.json"), b"{}").unwrap();
+        Fixture {
+            temp,
+            production,
+            share: ShareState::default(),
+        }
+    }
+
+    impl Fixture {
+        async fn start(&self, token: Option<&str>) -> SocketAddr {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            self.share
+                .start(
+                    listener,
+                    &self.production,
+                    token.map(str::to_string),
+                    &self.temp.root().join(QR_FILE),
+                )
+                .unwrap();
+            addr
+        }
+    }
+
+    /// Status code and body of a GET request
+    async fn get(addr: SocketAddr, target: &str) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            target, addr
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).await.unwrap();
+
+        let status = String::from_utf8_lossy(&response[9..12]).parse().unwrap();
+        let body_start = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        (status, response[body_start..].to_vec())
+    }
+
+    #[tokio::test]
+    async fn serves_viewer_and_converted_scene_with_token() {
+        let f = fixture();
+        let addr = f.start(Some("secret-token")).await;
+
+        let (status, body) = get(addr, "/?t=secret-token").await;
+        assert_eq!(status, 200);
+        assert!(String::from_utf8_lossy(&body).contains("scene.splat"));
+
+        let (status, body) = get(addr, "/scene.splat?t=secret-token").await;
+        assert_eq!(status, 200);
+        assert_eq!(body.len(), conversion::SPLAT_RECORD_LEN);
+
+        let (status, body) = get(addr, "/scene.spz?t=secret-token").await;
+        assert_eq!(status, 200);
+        assert_eq!(conversion::spz_to_vertices(&body).unwrap().len(), 1);
+
+        let (status, body) = get(addr, "/scene.ply?t=secret-token").await;
+        assert_eq!(status, 200);
+        assert_eq!(body, sample_ply(&[[0.5; 14]]));
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_wrong_token() {
+        let f = fixture();
+        let addr = f.start(Some("secret-token")).await;
+        for target in [
+            "/",
+            "/scene.splat",
+            "/scene.spz?t=",
+            "/scene.ply?t=wrong",
+            "/?t=secret-tokenx",
+        ] {
+            assert_eq!(get(addr, target).await.0, 401, "{}", target);
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_nothing_but_the_artifact() {
+        let f = fixture();
+        let addr = f.start(None).await;
+        assert_eq!(get(addr, "/scene.splat").await.0, 200);
+        for target in [
+            "/settings.json",
+            "/output.ply",
+            "/../production/settings.json",
+            "/scene.ply/../settings.json",
+        ] {
+            assert_eq!(get(addr, target).await.0, 404, "{}", target);
+        }
+    }
+
+    #[tokio::test]
+    async fn stop_closes_the_listener() {
+        let f = fixture();
+        let addr = f.start(None).await;
+        assert!(f.share.status().is_some());
+
+        f.share.stop();
+        assert!(f.share.status().is_none());
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn writes_qr_code_with_tokenized_link() {
+        let f = fixture();
+        let addr = f.start(Some("abc123")).await;
+        let info = f.share.status().unwrap();
+        assert_eq!(info.url, format!("http://{}/?t=abc123", addr));
+        assert!(info.token_required);
+
+        let png = std::fs::read(&info.qr_code_path).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn artifact_must_stay_inside_the_production() {
+        let f = fixture();
+        let outside = f.temp.root().join("other.ply");
+        std::fs::write(&outside, b"ply").unwrap();
+        let sidecar = sidecar::Sidecar {
+            job_id: "job-1".to_string(),
+            production_dir: f.production.to_string_lossy().to_string(),
+            artifact_path: outside.to_string_lossy().to_string(),
+            preset: "balanced".to_string(),
+            videos: vec![],
+            created_at: 0,
+            artifact_sha256: None,
+            capture_type: Default::default(),
+            archive: None,
+            preview: false,
+            input_sha256: vec![],
+            command: None,
+            training: None,
+            derivatives: vec![],
+            source: sidecar::ProductionSource::Job,
+            imported_from: None,
+            resolution_normalization: None,
+            camera_groups: vec![],
+            artifacts: vec![],
+        };
+        sidecar::write(&f.production, &sidecar).unwrap();
+        assert_eq!(
+            sidecar::find_artifact(&f.production).unwrap_err().code(),
+            "not_found"
+        );
+
+        std::fs::remove_file(sidecar::sidecar_path(&f.production)).unwrap();
+        assert_eq!(
+            sidecar::find_artifact(&f.production).unwrap(),
+            f.production.canonicalize().unwrap().join("output.ply")
+        );
+    }
+
+    #[test]
+    fn generated_tokens_are_unique() {
+        let token = generate_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, generate_token());
+    }
+}
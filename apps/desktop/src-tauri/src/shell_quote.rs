@@ -0,0 +1,159 @@
+//! Shell Quoting
+//!
+//! Turns a [`CommandSpec`] into one line a user can paste into a terminal to
+//! run exactly what a job ran. Joining arguments with spaces breaks on paths
+//! with spaces, quotes or shell characters, so each argument is quoted for the
+//! shell of the platform: POSIX sh on macOS and Linux, cmd.exe on Windows,
+//! where the program splits its own command line by the CommandLineToArgvW
+//! rules. A `%` is left as-is on Windows, as cmd.exe has no way to escape it
+//! inside quotes; it only expands there when it names a set variable.
+
+use crate::runner::CommandSpec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Posix,
+    Cmd,
+}
+
+impl Shell {
+    pub fn native() -> Self {
+        if cfg!(windows) {
+            Shell::Cmd
+        } else {
+            Shell::Posix
+        }
+    }
+
+    pub fn quote(self, arg: &str) -> String {
+        match self {
+            Shell::Posix => posix(arg),
+            Shell::Cmd => windows(arg),
+        }
+    }
+}
+
+/// The command as one line for `shell`, with its working directory and
+/// environment changes in front
+pub fn command_line(spec: &CommandSpec, shell: Shell) -> String {
+    let mut parts = vec![];
+    if let Some(cwd) = &spec.cwd {
+        parts.push(match shell {
+            Shell::Posix => format!("cd {} &&", posix(cwd)),
+            Shell::Cmd => format!("cd /d {} &&", windows(cwd)),
+        });
+    }
+    for (name, value) in &spec.env {
+        parts.push(match shell {
+            Shell::Posix => format!("{}={}", name, posix(value)),
+            Shell::Cmd => format!("set \"{}={}\" &&", name, value),
+        });
+    }
+    parts.push(shell.quote(&spec.program));
+    parts.extend(spec.args.iter().map(|arg| shell.quote(arg)));
+    parts.join(" ")
+}
+
+/// Bare when every character is one sh never treats specially, otherwise in
+/// single quotes, inside which only the quote itself needs escaping
+fn posix(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+/// Double quotes around anything with whitespace, quotes or cmd.exe
+/// operators. Backslashes are literal except before a quote, so those
+/// directly before an escaped quote or the closing quote are doubled.
+fn windows(arg: &str) -> String {
+    let special = |c: char| c.is_whitespace() || "\"&|<>^()".contains(c);
+    if !arg.is_empty() && !arg.chars().any(special) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posix_quoting_survives_spaces_quotes_and_unicode() {
+        assert_eq!(posix("/clips/cam1.mp4"), "/clips/cam1.mp4");
+        assert_eq!(posix("--min-sharpness"), "--min-sharpness");
+        assert_eq!(
+            posix("/Volumes/Shoot Day/cam 1.mp4"),
+            "'/Volumes/Shoot Day/cam 1.mp4'"
+        );
+        assert_eq!(posix("Director's cut"), r"'Director'\''s cut'");
+        assert_eq!(posix(r#"say "hi""#), r#"'say "hi"'"#);
+        assert_eq!(posix("$HOME;rm -rf *"), "'$HOME;rm -rf *'");
+        assert_eq!(posix("Kamera_Überblick.mp4"), "'Kamera_Überblick.mp4'");
+        assert_eq!(posix(""), "''");
+    }
+
+    #[test]
+    fn windows_quoting_follows_the_argv_rules() {
+        assert_eq!(windows(r"C:\clips\cam1.mp4"), r"C:\clips\cam1.mp4");
+        assert_eq!(
+            windows(r"C:\Shoot Day\cam 1.mp4"),
+            r#""C:\Shoot Day\cam 1.mp4""#
+        );
+        // Backslashes are only doubled before a quote
+        assert_eq!(windows(r"C:\Shoot Day\"), r#""C:\Shoot Day\\""#);
+        assert_eq!(windows(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(windows(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(windows("R&D"), r#""R&D""#);
+        assert_eq!(windows("Überblick"), "Überblick");
+        assert_eq!(windows(""), r#""""#);
+    }
+
+    #[test]
+    fn command_lines_set_the_directory_and_environment_first() {
+        let mut spec = CommandSpec::new(
+            "/Applications/Game View.app/gvcore-cli",
+            vec![
+                "run".to_string(),
+                "--output".to_string(),
+                "/out/pier 2".to_string(),
+            ],
+        );
+        spec.cwd = Some("/tmp/work dir".to_string());
+        spec.env
+            .insert("RUST_LOG".to_string(), "info debug".to_string());
+        assert_eq!(
+            command_line(&spec, Shell::Posix),
+            "cd '/tmp/work dir' && RUST_LOG='info debug' '/Applications/Game View.app/gvcore-cli' run --output '/out/pier 2'"
+        );
+
+        let spec = CommandSpec::new(
+            r"C:\Program Files\Game View\gvcore-cli.exe",
+            vec!["--input".to_string(), r"D:\Shoot\cam 1.mp4".to_string()],
+        );
+        assert_eq!(
+            command_line(&spec, Shell::Cmd),
+            r#""C:\Program Files\Game View\gvcore-cli.exe" --input "D:\Shoot\cam 1.mp4""#
+        );
+    }
+}
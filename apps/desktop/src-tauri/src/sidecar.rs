@@ -0,0 +1,176 @@
+//! Production Sidecars
+//!
+//! Each production directory carries a production.gvmeta JSON file describing
+//! how its artifact was made.
+
+use crate::archive::IntermediatesArchive;
+use crate::artifacts::{self, Artifact};
+use crate::camera_groups::CameraGroup;
+use crate::error::AppError;
+use crate::fsutil;
+use crate::media::ResolutionNormalization;
+use crate::profiles::CaptureType;
+use crate::runner::CommandSpec;
+use crate::training::TrainingOptions;
+use crate::written_by::{self, FileFormat};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const SIDECAR_NAME: &str = "production.gvmeta";
+
+/// Artifact the CLI writes when a production has no sidecar
+pub const DEFAULT_ARTIFACT: &str = "output.ply";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sidecar {
+    pub job_id: String,
+    pub production_dir: String,
+    pub artifact_path: String,
+    pub preset: String,
+    pub videos: Vec<String>,
+    pub created_at: u64,
+    /// Hex SHA-256 of the artifact when the job finished; absent in older sidecars
+    #[serde(default)]
+    pub artifact_sha256: Option<String>,
+    #[serde(default)]
+    pub capture_type: CaptureType,
+    /// Intermediates compressed by archive_production_intermediates
+    #[serde(default)]
+    pub archive: Option<IntermediatesArchive>,
+    /// Made by a quick preview run on proxies of the clips
+    #[serde(default)]
+    pub preview: bool,
+    /// Hex SHA-256 of each clip, by position; empty when they were not hashed
+    #[serde(default)]
+    pub input_sha256: Vec<String>,
+    /// What was run to make the artifact; absent in older sidecars
+    #[serde(default)]
+    pub command: Option<CommandSpec>,
+    /// The training options handed to the CLI; absent when it trained with the preset's
+    #[serde(default)]
+    pub training: Option<TrainingOptions>,
+    /// Files in the directory made from an artifact rather than by a job
+    #[serde(default)]
+    pub derivatives: Vec<Derivative>,
+    #[serde(default)]
+    pub source: ProductionSource,
+    /// The file an imported artifact was read from; the artifact itself when
+    /// it was imported by reference
+    #[serde(default)]
+    pub imported_from: Option<String>,
+    /// How clips of different resolutions were brought to one; absent when they were not
+    #[serde(default)]
+    pub resolution_normalization: Option<ResolutionNormalization>,
+    /// The clips of each camera group the job ran with; empty when none were grouped
+    #[serde(default)]
+    pub camera_groups: Vec<CameraGroup>,
+    /// Every artifact the job made, the primary, artifact_path, first; empty
+    /// in older sidecars and for imported artifacts
+    #[serde(default)]
+    pub artifacts: Vec<Artifact>,
+}
+
+/// How a production's artifact came to be
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductionSource {
+    /// Trained by a job
+    #[default]
+    Job,
+    /// Made with another tool and imported by import_artifact
+    Imported,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivativeKind {
+    /// Fewer splats, for sharing
+    Downsampled,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Derivative {
+    pub path: String,
+    pub kind: DerivativeKind,
+    pub sha256: String,
+    pub source_path: String,
+    /// Hex SHA-256 of the artifact it was made from, as its sidecar records it
+    pub source_sha256: String,
+    pub created_at: u64,
+}
+
+pub fn sidecar_path(production_dir: &Path) -> PathBuf {
+    production_dir.join(SIDECAR_NAME)
+}
+
+/// Read the sidecar of a production, if it has one
+pub fn read(production_dir: &Path) -> Result<Option<Sidecar>, String> {
+    let path = sidecar_path(production_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| written_by::explain(&content, e))
+}
+
+pub fn write(production_dir: &Path, sidecar: &Sidecar) -> Result<(), String> {
+    let stamped = written_by::stamp(FileFormat::Sidecar, sidecar);
+    fsutil::write_json_atomic(&sidecar_path(production_dir), &stamped)
+}
+
+/// The artifact of a production, which must live inside the production directory
+pub fn find_artifact(production_dir: &Path) -> Result<PathBuf, AppError> {
+    let dir = production_dir.canonicalize()?;
+    let sidecar = read(&dir)?;
+    // Only an artifact imported by reference may live elsewhere
+    let referenced = sidecar
+        .as_ref()
+        .filter(|s| s.source == ProductionSource::Imported)
+        .filter(|s| s.imported_from.as_deref() == Some(s.artifact_path.as_str()))
+        .and_then(|s| Path::new(&s.artifact_path).canonicalize().ok());
+    // The primary of the listed artifacts still there, or what older sidecars recorded
+    let recorded = sidecar.map(|s| {
+        let present: Vec<Artifact> = s
+            .artifacts
+            .into_iter()
+            .filter(|a| Path::new(&a.path).exists())
+            .collect();
+        artifacts::primary(&present)
+            .map(|a| PathBuf::from(&a.path))
+            .unwrap_or_else(|| PathBuf::from(s.artifact_path))
+    });
+    let artifact = recorded
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| dir.join(DEFAULT_ARTIFACT));
+
+    let not_found = || AppError::NotFound(format!("Splat artifact in {}", dir.display()));
+    let artifact = artifact.canonicalize().map_err(|_| not_found())?;
+    let is_ply = artifact
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ply"));
+    let inside = artifact.starts_with(&dir) || referenced.as_ref() == Some(&artifact);
+    if !inside || !artifact.is_file() || !is_ply {
+        return Err(not_found());
+    }
+    Ok(artifact)
+}
+
+/// The artifacts a production lists besides `primary` that are still there
+pub fn other_artifacts(production_dir: &Path, primary: &Path) -> Vec<Artifact> {
+    let Ok(Some(sidecar)) = read(production_dir) else {
+        return vec![];
+    };
+    let primary = primary.canonicalize().ok();
+    sidecar
+        .artifacts
+        .into_iter()
+        .filter(|a| {
+            let path = Path::new(&a.path).canonicalize().ok();
+            path.is_some() && path != primary
+        })
+        .collect()
+}
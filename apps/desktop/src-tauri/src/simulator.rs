@@ -0,0 +1,192 @@
+//! Simulated CLI
+//!
+//! Generates a realistic gvcore-cli output stream without running the CLI, so the
+//! progress UI can be exercised on machines without COLMAP. The stream is fed
+//! through the same line handling as real CLI output.
+
+use crate::commands::ProcessArgs;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, DuplexStream};
+use tokio::task::JoinHandle;
+
+/// Default length of a simulated run
+const DEFAULT_DURATION_SECS: f64 = 10.0;
+
+// (CLI stage, start %, end %, message) in the order the real CLI reports them
+const STAGES: [(&str, u32, u32, &str); 4] = [
+    ("frame_extraction", 0, 20, "Extracting frames..."),
+    ("colmap", 20, 40, "Running Structure from Motion..."),
+    ("brush", 40, 95, "Training Gaussian splats..."),
+    ("metadata", 95, 100, "Generating metadata..."),
+];
+
+const STEPS_PER_STAGE: u32 = 5;
+
+/// Whether a job should run against the simulator instead of the CLI.
+/// Neither the per-job flag nor GV_SIMULATE=1 does anything in a release build
+/// unless it was built with the "simulate" feature for E2E tests, so a shipped
+/// app always runs the real CLI.
+pub fn enabled(args: &ProcessArgs) -> bool {
+    cfg!(any(debug_assertions, feature = "simulate"))
+        && (args.simulate || std::env::var("GV_SIMULATE").is_ok_and(|v| v == "1"))
+}
+
+/// Start writing simulated CLI output; the task resolves to whether the run succeeded.
+/// Dropping the reader stops the simulation at the next line.
+pub fn spawn(args: &ProcessArgs) -> (DuplexStream, JoinHandle<bool>) {
+    let (reader, writer) = tokio::io::duplex(64 * 1024);
+    let duration = args
+        .simulate_duration_secs
+        .unwrap_or(DEFAULT_DURATION_SECS)
+        .max(0.0);
+    let fail_at_stage = args.simulate_fail_at_stage.clone();
+    let output_dir = args.output_dir.clone();
+    let clip_count = args.videos.len().max(1);
+
+    let handle = tokio::spawn(async move {
+        run(writer, duration, fail_at_stage, &output_dir, clip_count)
+            .await
+            .unwrap_or(false)
+    });
+    (reader, handle)
+}
+
+async fn run(
+    mut writer: DuplexStream,
+    duration: f64,
+    fail_at_stage: Option<String>,
+    output_dir: &str,
+    clip_count: usize,
+) -> std::io::Result<bool> {
+    let step_delay =
+        Duration::from_secs_f64(duration / (STAGES.len() as u32 * STEPS_PER_STAGE) as f64);
+
+    for (stage, start, end, message) in STAGES {
+        for step in 0..STEPS_PER_STAGE {
+            let percent = start + (end - start) * step / STEPS_PER_STAGE;
+
+            if step == STEPS_PER_STAGE / 2 && fail_at_stage.as_deref() == Some(stage) {
+                let line = format!("[failed] {}% - Simulated failure in {}\n", percent, stage);
+                writer.write_all(line.as_bytes()).await?;
+                return Ok(false);
+            }
+
+            let line = format!("[{}] {}% - {}\n", stage, percent, message);
+            writer.write_all(line.as_bytes()).await?;
+            if stage == "frame_extraction" {
+                let clip = (step as usize * clip_count / STEPS_PER_STAGE as usize) + 1;
+                let line = format!("Decoding clip {}/{}\n", clip, clip_count);
+                writer.write_all(line.as_bytes()).await?;
+            }
+            tokio::time::sleep(step_delay).await;
+        }
+    }
+
+    let line = format!(
+        "[completed] 100% - Processing complete ({}/{})\n",
+        clip_count, clip_count
+    );
+    writer.write_all(line.as_bytes()).await?;
+
+    std::fs::create_dir_all(output_dir)?;
+    std::fs::write(
+        Path::new(output_dir).join("output.ply"),
+        b"simulated ply data",
+    )?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+    use serde_json::json;
+    use tokio::io::AsyncReadExt;
+
+    fn simulated(paths: &TempPaths, fail_at_stage: Option<&str>) -> ProcessArgs {
+        serde_json::from_value(json!({
+            "videos": ["/clips/a.mp4", "/clips/b.mp4"],
+            "output_dir": paths.root().join("production"),
+            "preset": "fast",
+            "simulate": true,
+            "simulate_duration_secs": 0.0,
+            "simulate_fail_at_stage": fail_at_stage,
+        }))
+        .unwrap()
+    }
+
+    async fn output(args: &ProcessArgs) -> (Vec<String>, bool) {
+        let (mut reader, handle) = spawn(args);
+        let mut text = String::new();
+        reader.read_to_string(&mut text).await.unwrap();
+        (
+            text.lines().map(String::from).collect(),
+            handle.await.unwrap(),
+        )
+    }
+
+    #[test]
+    fn only_debug_and_simulate_builds_honour_the_flag() {
+        let paths = TempPaths::new();
+        let mut args = simulated(&paths, None);
+        assert_eq!(
+            enabled(&args),
+            cfg!(any(debug_assertions, feature = "simulate"))
+        );
+        args.simulate = false;
+        if std::env::var_os("GV_SIMULATE").is_none() {
+            assert!(!enabled(&args));
+        }
+    }
+
+    #[tokio::test]
+    async fn a_run_reports_every_stage_in_order_then_writes_an_artifact() {
+        let paths = TempPaths::new();
+        let args = simulated(&paths, None);
+        let (lines, succeeded) = output(&args).await;
+        assert!(succeeded);
+
+        let stages: Vec<&str> = lines
+            .iter()
+            .filter_map(|line| line.strip_prefix('[')?.split_once(']'))
+            .map(|(stage, _)| stage)
+            .collect();
+        let mut order = stages.clone();
+        order.dedup();
+        assert_eq!(
+            order,
+            [
+                "frame_extraction",
+                "colmap",
+                "brush",
+                "metadata",
+                "completed"
+            ]
+        );
+        assert_eq!(stages.len(), STAGES.len() * STEPS_PER_STAGE as usize + 1);
+        assert_eq!(lines[0], "[frame_extraction] 0% - Extracting frames...");
+        assert_eq!(lines[1], "Decoding clip 1/2");
+        assert!(lines.contains(&"Decoding clip 2/2".to_string()));
+        assert_eq!(
+            lines.last().unwrap(),
+            "[completed] 100% - Processing complete (2/2)"
+        );
+        let artifact = Path::new(&args.output_dir).join("output.ply");
+        assert_eq!(std::fs::read(artifact).unwrap(), b"simulated ply data");
+    }
+
+    #[tokio::test]
+    async fn a_failing_run_stops_midway_through_its_stage() {
+        let paths = TempPaths::new();
+        let args = simulated(&paths, Some("colmap"));
+        let (lines, succeeded) = output(&args).await;
+        assert!(!succeeded);
+        assert_eq!(
+            lines.last().unwrap(),
+            "[failed] 28% - Simulated failure in colmap"
+        );
+        assert!(!lines.iter().any(|line| line.starts_with("[brush]")));
+        assert!(!Path::new(&args.output_dir).join("output.ply").exists());
+    }
+}
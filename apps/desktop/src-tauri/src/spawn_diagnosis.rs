@@ -0,0 +1,368 @@
+//! Spawn Diagnosis
+//!
+//! "Failed to spawn CLI: Os { code: 193 }" tells a user nothing. When the CLI
+//! cannot be started, the executable is inspected for the usual reasons on
+//! each platform: a missing executable bit, the macOS quarantine attribute, a
+//! build for another processor, a missing Visual C++ runtime and SELinux
+//! denials. The cause comes back as spawn_diagnosis with a repair id where
+//! repair_cli_issue can fix it safely.
+
+use crate::commands;
+use crate::error::AppError;
+use crate::messages::{Failure, Message};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::AppHandle;
+
+const KEY_PREFIX: &str = "job.spawn_";
+
+/// Extended attribute macOS puts on downloaded files until they are approved
+#[cfg(target_os = "macos")]
+const QUARANTINE_ATTR: &str = "com.apple.quarantine";
+
+/// ERROR_BAD_EXE_FORMAT
+const WINDOWS_BAD_EXE_FORMAT: i32 = 193;
+/// ERROR_MOD_NOT_FOUND, a DLL the executable links did not load
+const WINDOWS_MOD_NOT_FOUND: i32 = 126;
+/// STATUS_DLL_NOT_FOUND, the exit code of a process whose DLLs are missing
+const WINDOWS_STATUS_DLL_NOT_FOUND: i32 = 0xC000_0135_u32 as i32;
+/// STATUS_INVALID_IMAGE_FORMAT, the exit code of a process built for another processor
+const WINDOWS_STATUS_INVALID_IMAGE_FORMAT: i32 = 0xC000_007B_u32 as i32;
+
+/// Why the CLI could not be started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnCause {
+    NotFound,
+    NotExecutable,
+    /// macOS Gatekeeper holds the downloaded executable in quarantine
+    Quarantined,
+    WrongArchitecture,
+    /// The Microsoft Visual C++ runtime DLLs are not installed
+    MissingRuntime,
+    SelinuxDenied,
+}
+
+/// The fixes repair_cli_issue applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpawnRepair {
+    /// chmod +x
+    MakeExecutable,
+    /// Clear com.apple.quarantine
+    RemoveQuarantine,
+}
+
+impl SpawnCause {
+    fn name(self) -> &'static str {
+        match self {
+            SpawnCause::NotFound => "not_found",
+            SpawnCause::NotExecutable => "not_executable",
+            SpawnCause::Quarantined => "quarantined",
+            SpawnCause::WrongArchitecture => "wrong_architecture",
+            SpawnCause::MissingRuntime => "missing_runtime",
+            SpawnCause::SelinuxDenied => "selinux_denied",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+    }
+
+    /// The fix that is safe to apply without asking more of the user
+    pub fn repair(self) -> Option<SpawnRepair> {
+        match self {
+            SpawnCause::NotExecutable => Some(SpawnRepair::MakeExecutable),
+            SpawnCause::Quarantined => Some(SpawnRepair::RemoveQuarantine),
+            _ => None,
+        }
+    }
+}
+
+impl SpawnRepair {
+    fn name(self) -> &'static str {
+        match self {
+            SpawnRepair::MakeExecutable => "make_executable",
+            SpawnRepair::RemoveQuarantine => "remove_quarantine",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpawnDiagnosis {
+    pub cause: SpawnCause,
+    pub repair: Option<SpawnRepair>,
+    /// The executable that was inspected, or the bare name that was not found
+    pub path: String,
+    /// The OS error the spawn failed with
+    pub detail: Option<String>,
+}
+
+impl SpawnDiagnosis {
+    fn new(cause: SpawnCause, path: &Path) -> Self {
+        Self {
+            cause,
+            repair: cause.repair(),
+            path: path.to_string_lossy().to_string(),
+            detail: None,
+        }
+    }
+
+    pub fn message(&self) -> Message {
+        let message = Message::new(&format!("{}{}", KEY_PREFIX, self.cause.name()))
+            .with("path", &self.path)
+            .with("cause", self.cause.name());
+        match self.repair {
+            Some(repair) => message.with("repair", repair.name()),
+            None => message,
+        }
+    }
+
+    /// A job failure carrying the diagnosis, for from_failure to read back
+    pub fn failure(&self) -> Failure {
+        let failure = self.message().into_failure();
+        match &self.detail {
+            Some(detail) => failure.raw(detail.clone()),
+            None => failure,
+        }
+    }
+
+    pub fn from_failure(failure: &Failure) -> Option<Self> {
+        let params = &failure.message.params;
+        let cause = SpawnCause::from_name(params.get("cause")?)?;
+        if failure.message.key != format!("{}{}", KEY_PREFIX, cause.name()) {
+            return None;
+        }
+        Some(Self {
+            cause,
+            repair: cause.repair(),
+            path: params.get("path").cloned().unwrap_or_default(),
+            detail: failure.raw.clone(),
+        })
+    }
+}
+
+/// Attempt the fix for `cause` on the CLI, logging what was done
+#[tauri::command]
+pub async fn repair_cli_issue(app: AppHandle, cause: SpawnCause) -> Result<(), AppError> {
+    let program = commands::cli_path(&app)?;
+    let path = resolve(&program).ok_or_else(|| AppError::NotFound(program.clone()))?;
+    repair(&path, cause)
+}
+
+/// Why `program` failed to start with `os_code`, when the reason is recognisable
+pub fn diagnose(program: &str, os_code: Option<i32>) -> Option<SpawnDiagnosis> {
+    let Some(path) = resolve(program).filter(|p| p.is_file()) else {
+        return Some(SpawnDiagnosis::new(
+            SpawnCause::NotFound,
+            Path::new(program),
+        ));
+    };
+    inspect(&path, os_code).map(|cause| SpawnDiagnosis::new(cause, &path))
+}
+
+/// Why `program` exited with `exit_code` before it could run, on Windows where
+/// the loader reports missing DLLs and foreign executables that way
+pub fn diagnose_exit(program: &str, exit_code: Option<i32>) -> Option<SpawnDiagnosis> {
+    if !cfg!(windows) {
+        return None;
+    }
+    let cause = match exit_code? {
+        WINDOWS_STATUS_DLL_NOT_FOUND => SpawnCause::MissingRuntime,
+        WINDOWS_STATUS_INVALID_IMAGE_FORMAT => SpawnCause::WrongArchitecture,
+        _ => return None,
+    };
+    let path = resolve(program).unwrap_or_else(|| PathBuf::from(program));
+    Some(SpawnDiagnosis::new(cause, &path))
+}
+
+fn inspect(path: &Path, os_code: Option<i32>) -> Option<SpawnCause> {
+    if cfg!(windows) {
+        return match os_code? {
+            WINDOWS_BAD_EXE_FORMAT => Some(SpawnCause::WrongArchitecture),
+            WINDOWS_MOD_NOT_FOUND => Some(SpawnCause::MissingRuntime),
+            _ => None,
+        };
+    }
+    inspect_unix(path, os_code)
+}
+
+#[cfg(unix)]
+fn inspect_unix(path: &Path, os_code: Option<i32>) -> Option<SpawnCause> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path).ok()?.permissions().mode();
+    if mode & 0o111 == 0 {
+        return Some(SpawnCause::NotExecutable);
+    }
+    #[cfg(target_os = "macos")]
+    if has_quarantine(path) {
+        return Some(SpawnCause::Quarantined);
+    }
+    // EBADARCH on macOS; elsewhere the code is unused
+    let bad_arch = cfg!(target_os = "macos") && os_code == Some(86);
+    if os_code == Some(libc::ENOEXEC) || bad_arch {
+        return Some(SpawnCause::WrongArchitecture);
+    }
+    if os_code == Some(libc::EACCES) && selinux_enforcing() {
+        return Some(SpawnCause::SelinuxDenied);
+    }
+    None
+}
+
+#[cfg(not(unix))]
+fn inspect_unix(_path: &Path, _os_code: Option<i32>) -> Option<SpawnCause> {
+    None
+}
+
+#[cfg(unix)]
+fn selinux_enforcing() -> bool {
+    std::fs::read_to_string("/sys/fs/selinux/enforce").is_ok_and(|s| s.trim() == "1")
+}
+
+#[cfg(target_os = "macos")]
+fn has_quarantine(path: &Path) -> bool {
+    std::process::Command::new("xattr")
+        .arg("-p")
+        .arg(QUARANTINE_ATTR)
+        .arg(path)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// The executable `program` names, looked up on PATH when it is a bare name
+fn resolve(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return Some(path.to_path_buf());
+    }
+    let names: Vec<String> = if cfg!(windows) && path.extension().is_none() {
+        vec![format!("{}.exe", program)]
+    } else {
+        vec![program.to_string()]
+    };
+    let search = std::env::var_os("PATH")?;
+    std::env::split_paths(&search)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+fn repair(path: &Path, cause: SpawnCause) -> Result<(), AppError> {
+    let Some(repair) = cause.repair() else {
+        return Err(AppError::InvalidInput(format!(
+            "{} has no automated repair",
+            cause.name()
+        )));
+    };
+    eprintln!(
+        "Repairing gvcore-cli at {}: {}",
+        path.display(),
+        repair.name()
+    );
+    let result = match repair {
+        SpawnRepair::MakeExecutable => make_executable(path),
+        SpawnRepair::RemoveQuarantine => remove_quarantine(path),
+    };
+    match &result {
+        Ok(()) => eprintln!("Repaired gvcore-cli at {}", path.display()),
+        Err(e) => eprintln!("Failed to repair gvcore-cli at {}: {}", path.display(), e),
+    }
+    result
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), AppError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut permissions = std::fs::metadata(path)?.permissions();
+    // Executable by whoever may read it, as chmod +x does
+    let readable = permissions.mode() & 0o444;
+    permissions.set_mode(permissions.mode() | (readable >> 2));
+    std::fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), AppError> {
+    Err(AppError::InvalidInput(
+        "Executable bits exist only on Unix".to_string(),
+    ))
+}
+
+#[cfg(target_os = "macos")]
+fn remove_quarantine(path: &Path) -> Result<(), AppError> {
+    let output = std::process::Command::new("xattr")
+        .arg("-d")
+        .arg(QUARANTINE_ATTR)
+        .arg(path)
+        .output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(AppError::Io(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ))
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn remove_quarantine(_path: &Path) -> Result<(), AppError> {
+    Err(AppError::InvalidInput(
+        "Quarantine exists only on macOS".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn diagnoses_survive_the_trip_through_a_job_failure() {
+        let paths = TempPaths::new();
+        let missing = paths.root().join("resources/gvcore-cli");
+        let diagnosis = diagnose(&missing.to_string_lossy(), Some(2)).unwrap();
+        assert_eq!(diagnosis.cause, SpawnCause::NotFound);
+        assert_eq!(diagnosis.repair, None);
+
+        let failure = SpawnDiagnosis {
+            detail: Some("No such file or directory (os error 2)".to_string()),
+            ..diagnosis.clone()
+        }
+        .failure();
+        assert_eq!(failure.message.key, "job.spawn_not_found");
+        let error = AppError::from(failure);
+        assert_eq!(error.code(), "spawn_diagnosis");
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["cause"], "not_found");
+        assert!(json["repair"].is_null());
+
+        // Other failures that happen to have a cause parameter are left alone
+        let other = Message::new("job.failed").with("cause", "not_found");
+        assert_eq!(SpawnDiagnosis::from_failure(&other.into()), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_missing_executable_bit_is_found_and_repaired() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let paths = TempPaths::new();
+        let cli = paths.root().join("gvcore-cli");
+        std::fs::write(&cli, b"#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&cli, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let program = cli.to_string_lossy().to_string();
+
+        let diagnosis = diagnose(&program, Some(libc::EACCES)).unwrap();
+        assert_eq!(diagnosis.cause, SpawnCause::NotExecutable);
+        assert_eq!(diagnosis.repair, Some(SpawnRepair::MakeExecutable));
+        assert_eq!(diagnosis.message().params["repair"], "make_executable");
+
+        repair(&cli, diagnosis.cause).unwrap();
+        let mode = std::fs::metadata(&cli).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        let after = diagnose(&program, Some(libc::ENOEXEC)).unwrap();
+        assert_eq!(after.cause, SpawnCause::WrongArchitecture);
+        assert!(repair(&cli, after.cause).is_err());
+    }
+}
@@ -0,0 +1,543 @@
+//! Splat Preview
+//!
+//! A still image of a splat for production cards, rendered on the CPU without
+//! opening a viewer. Up to MAX_SCANNED splats are read, evenly spaced through
+//! the file, and the MAX_SAMPLES that contribute most, as downsampling scores
+//! them, are drawn back to front as soft round sprites. Reading seeks past the
+//! splats it skips, so time and memory stay bounded however large the
+//! artifact is. Without an explicit camera the view is framed on the bounding
+//! box of the bulk of the splats, ignoring stray ones far out.
+//!
+//! Previews go to the thumbnail cache, named by the artifact's checksum, or by
+//! its modification time where no checksum was stored, so a new version of an
+//! artifact gets a new preview and an unchanged one is rendered once.
+
+use crate::cache::{self, Cache, Category};
+use crate::conversion::{self, MAX_HEADER_LEN, SH_C0};
+use crate::downsample;
+use crate::error::AppError;
+use crate::path_policy::PathPolicy;
+use crate::scheduler::{self, Pool};
+use crate::sidecar;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, State};
+
+/// Splats read from the file at most
+const MAX_SCANNED: usize = 400_000;
+
+/// Splats drawn at most
+const MAX_SAMPLES: usize = 100_000;
+
+/// Sides of the square image accepted, in pixels
+const MIN_SIZE: u32 = 32;
+const MAX_SIZE: u32 = 1024;
+
+/// Largest sprite radius in pixels, so a splat close to the camera cannot
+/// cost a full-image fill
+const MAX_RADIUS: f32 = 6.0;
+
+const DEFAULT_FOV_DEGREES: f32 = 50.0;
+
+/// Share of splats on each side of every axis left out of the framing
+const FRAMING_OUTLIERS: f32 = 0.02;
+
+/// The cancel flag is looked at after this many splats
+const CANCEL_EVERY: usize = 4096;
+
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+/// Where the preview is seen from, in the artifact's own coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PreviewCamera {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    /// Negative y when absent, as COLMAP orients reconstructions
+    pub up: Option<[f32; 3]>,
+    /// Vertical field of view, DEFAULT_FOV_DEGREES when absent
+    pub fov_degrees: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SplatPreview {
+    /// The PNG in the thumbnail cache
+    pub path: String,
+    pub artifact_path: String,
+    pub size: u32,
+    /// The camera used; pass it back to frame later versions the same way
+    pub camera: PreviewCamera,
+    pub rendered_splats: usize,
+    pub total_splats: usize,
+    /// Of the splats drawn; absent for previews cached by older versions
+    pub bounds: Option<Bounds>,
+    /// Taken from the cache rather than rendered now
+    pub cached: bool,
+}
+
+/// An axis-aligned box, in the artifact's own coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+/// What a cached preview was rendered with, kept next to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Rendered {
+    camera: PreviewCamera,
+    rendered_splats: usize,
+    total_splats: usize,
+    #[serde(default)]
+    bounds: Option<Bounds>,
+}
+
+/// One splat as it is drawn
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    position: [f32; 3],
+    color: [f32; 3],
+    alpha: f32,
+    /// Of its largest axis, in scene units
+    radius: f32,
+    score: f32,
+}
+
+/// Render a `size` pixel square preview of the splat at `path`, a PLY or a
+/// production directory, seen from `camera` or from a view framed on it
+#[tauri::command]
+pub async fn render_splat_preview(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+    size: u32,
+    camera: Option<PreviewCamera>,
+) -> Result<SplatPreview, AppError> {
+    policy.check_existing(&path)?;
+    CANCEL.store(false, Ordering::SeqCst);
+    let cache = cache::app_cache(&app)?;
+    scheduler::run_blocking(Pool::Background, move || {
+        preview(&cache, Path::new(&path), size, camera, &CANCEL)
+    })
+    .await?
+}
+
+/// Cancel the previews being rendered
+#[tauri::command]
+pub async fn cancel_splat_preview() -> Result<(), AppError> {
+    CANCEL.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Render or take from the cache the preview of `path`, a PLY or a production directory
+pub fn preview(
+    cache: &Cache,
+    path: &Path,
+    size: u32,
+    camera: Option<PreviewCamera>,
+    cancel: &AtomicBool,
+) -> Result<SplatPreview, AppError> {
+    let artifact = if path.is_dir() {
+        sidecar::find_artifact(path)?
+    } else {
+        path.to_path_buf()
+    };
+    let size = size.clamp(MIN_SIZE, MAX_SIZE);
+    let key = format!(
+        "{}\0{}\0{}",
+        version(&artifact)?,
+        size,
+        serde_json::to_string(&camera).unwrap_or_default()
+    );
+    let name: String = Sha256::digest(key.as_bytes())
+        .iter()
+        .take(16)
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let (image_name, rendered_name) = (
+        format!("splat-{}.png", name),
+        format!("splat-{}.json", name),
+    );
+    let artifact_path = artifact.to_string_lossy().to_string();
+
+    let cached = cache
+        .get(Category::Thumbnails, &image_name)
+        .and_then(|image| {
+            let rendered = cache.read(Category::Metadata, &rendered_name)?;
+            Some((image, serde_json::from_slice::<Rendered>(&rendered).ok()?))
+        });
+    if let Some((image, rendered)) = cached {
+        return Ok(SplatPreview {
+            path: image.to_string_lossy().to_string(),
+            artifact_path,
+            size,
+            camera: rendered.camera,
+            rendered_splats: rendered.rendered_splats,
+            total_splats: rendered.total_splats,
+            bounds: rendered.bounds,
+            cached: true,
+        });
+    }
+
+    let (samples, total_splats) = read_samples(&artifact, cancel)?;
+    let camera = camera.unwrap_or_else(|| frame(&samples));
+    let pixels = render(&samples, &camera, size, cancel)?;
+    let staging = cache.staging(Category::Thumbnails, &image_name)?;
+    let image = write_png(&staging, size, &pixels)
+        .map_err(AppError::from)
+        .and_then(|()| Ok(cache.insert(Category::Thumbnails, &image_name, &staging)?));
+    let image = match image {
+        Ok(image) => image,
+        Err(e) => {
+            std::fs::remove_file(&staging).ok();
+            return Err(e);
+        }
+    };
+    let rendered = Rendered {
+        camera,
+        rendered_splats: samples.len(),
+        total_splats,
+        bounds: bounds(&samples),
+    };
+    if let Ok(json) = serde_json::to_vec(&rendered) {
+        cache.write(Category::Metadata, &rendered_name, &json).ok();
+    }
+    Ok(SplatPreview {
+        path: image.to_string_lossy().to_string(),
+        artifact_path,
+        size,
+        camera,
+        rendered_splats: samples.len(),
+        total_splats,
+        bounds: rendered.bounds,
+        cached: false,
+    })
+}
+
+/// RGBA pixels of a `size` pixel square image of the splat at `artifact`, seen
+/// from `camera` or from a view framed on it, and the camera used
+pub fn render_pixels(
+    artifact: &Path,
+    size: u32,
+    camera: Option<PreviewCamera>,
+    cancel: &AtomicBool,
+) -> Result<(Vec<u8>, PreviewCamera), AppError> {
+    let (samples, _) = read_samples(artifact, cancel)?;
+    let camera = camera.unwrap_or_else(|| frame(&samples));
+    Ok((render(&samples, &camera, size, cancel)?, camera))
+}
+
+/// What tells versions of an artifact apart: the checksum its sidecar stored,
+/// or its modification time, along with its size
+pub fn version(artifact: &Path) -> Result<String, AppError> {
+    let metadata = std::fs::metadata(artifact)?;
+    let stored = artifact
+        .parent()
+        .and_then(|dir| sidecar::read(dir).ok().flatten())
+        .filter(|s| Path::new(&s.artifact_path).file_name() == artifact.file_name())
+        .and_then(|s| s.artifact_sha256);
+    let version = match stored {
+        Some(sha256) => sha256.to_lowercase(),
+        None => {
+            let modified = metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default();
+            format!("{}\0{}", artifact.display(), modified)
+        }
+    };
+    Ok(format!("{}\0{}", version, metadata.len()))
+}
+
+/// The splats worth drawing, and how many the file holds
+fn read_samples(artifact: &Path, cancel: &AtomicBool) -> Result<(Vec<Sample>, usize), AppError> {
+    let mut file = File::open(artifact)?;
+    let total_bytes = file.metadata()?.len();
+    let mut header = vec![];
+    (&mut file).take(MAX_HEADER_LEN).read_to_end(&mut header)?;
+    let layout = conversion::parse_header(&header).map_err(AppError::InvalidInput)?;
+    let count = layout.vertex_count;
+    if layout.body_offset as u64 + count as u64 * layout.stride as u64 > total_bytes {
+        return Err(AppError::InvalidInput("PLY file is truncated".to_string()));
+    }
+
+    let step = ((count + MAX_SCANNED - 1) / MAX_SCANNED).max(1);
+    let skip = ((step - 1) * layout.stride) as i64;
+    file.seek(SeekFrom::Start(layout.body_offset as u64))?;
+    let mut reader = BufReader::new(file);
+    let mut vertex = vec![0u8; layout.stride];
+    let mut samples = Vec::with_capacity((count + step - 1) / step);
+    for i in (0..count).step_by(step) {
+        if i % (CANCEL_EVERY * step) == 0 && cancel.load(Ordering::SeqCst) {
+            return Err(AppError::Cancelled);
+        }
+        reader.read_exact(&mut vertex)?;
+        if i + step < count {
+            reader.seek_relative(skip)?;
+        }
+        let v = layout.values(&vertex);
+        let sample = Sample {
+            position: [v[0], v[1], v[2]],
+            color: [v[11], v[12], v[13]].map(|dc| (0.5 + SH_C0 * dc).clamp(0.0, 1.0)),
+            alpha: 1.0 / (1.0 + (-v[10]).exp()),
+            radius: v[3].max(v[4]).max(v[5]).exp(),
+            score: downsample::score(&v),
+        };
+        if sample.position.iter().all(|c| c.is_finite()) && sample.radius.is_finite() {
+            samples.push(sample);
+        }
+    }
+
+    if samples.len() > MAX_SAMPLES {
+        // Highest score first, earlier splats first among equals
+        samples.select_nth_unstable_by(MAX_SAMPLES - 1, |a, b| b.score.total_cmp(&a.score));
+        samples.truncate(MAX_SAMPLES);
+    }
+    Ok((samples, count))
+}
+
+/// The box around every splat drawn
+fn bounds(samples: &[Sample]) -> Option<Bounds> {
+    let first = samples.first()?.position;
+    Some(samples.iter().fold(
+        Bounds {
+            min: first,
+            max: first,
+        },
+        |b, s| Bounds {
+            min: [0, 1, 2].map(|axis| b.min[axis].min(s.position[axis])),
+            max: [0, 1, 2].map(|axis| b.max[axis].max(s.position[axis])),
+        },
+    ))
+}
+
+/// A camera above and in front of the bulk of the splats, far enough back to
+/// see all of it
+fn frame(samples: &[Sample]) -> PreviewCamera {
+    let mut low = [0.0; 3];
+    let mut high = [0.0; 3];
+    if !samples.is_empty() {
+        for axis in 0..3 {
+            let mut values: Vec<f32> = samples.iter().map(|s| s.position[axis]).collect();
+            values.sort_by(|a, b| a.total_cmp(b));
+            let outliers = (values.len() as f32 * FRAMING_OUTLIERS) as usize;
+            low[axis] = values[outliers];
+            high[axis] = values[values.len() - 1 - outliers];
+        }
+    }
+    let center = [0, 1, 2].map(|axis| (low[axis] + high[axis]) / 2.0);
+    let radius = length(sub(high, low)).max(1e-3) / 2.0;
+    let distance = radius / (DEFAULT_FOV_DEGREES.to_radians() / 2.0).tan() * 1.1;
+    let direction = normalize([0.0, -0.35, -1.0]);
+    PreviewCamera {
+        position: [0, 1, 2].map(|axis| center[axis] + direction[axis] * distance),
+        target: center,
+        up: None,
+        fov_degrees: None,
+    }
+}
+
+/// RGBA pixels, row by row, unpremultiplied
+fn render(
+    samples: &[Sample],
+    camera: &PreviewCamera,
+    size: u32,
+    cancel: &AtomicBool,
+) -> Result<Vec<u8>, AppError> {
+    let forward = normalize(sub(camera.target, camera.position));
+    let mut right = normalize(cross(forward, camera.up.unwrap_or([0.0, -1.0, 0.0])));
+    if !right.iter().all(|c| c.is_finite()) {
+        // Looking straight along the up direction
+        right = normalize(cross(forward, [0.0, 0.0, 1.0]));
+    }
+    let up = cross(right, forward);
+    let fov = camera
+        .fov_degrees
+        .unwrap_or(DEFAULT_FOV_DEGREES)
+        .clamp(1.0, 170.0);
+    let half = size as f32 / 2.0;
+    let focal = half / (fov.to_radians() / 2.0).tan();
+
+    // Screen position, depth and radius in pixels of each splat in front of the camera
+    let mut projected: Vec<(f32, f32, f32, f32, &Sample)> = samples
+        .iter()
+        .filter_map(|sample| {
+            let d = sub(sample.position, camera.position);
+            let depth = dot(d, forward);
+            if depth <= 1e-4 {
+                return None;
+            }
+            let x = dot(d, right) * focal / depth + half;
+            let y = -dot(d, up) * focal / depth + half;
+            let radius = (sample.radius * focal / depth).clamp(0.5, MAX_RADIUS);
+            Some((x, y, depth, radius, sample))
+        })
+        .collect();
+    projected.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let side = size as usize;
+    // Premultiplied, so sprites blend over one another and over nothing alike
+    let mut buffer = vec![[0.0f32; 4]; side * side];
+    for (i, (x, y, _, radius, sample)) in projected.into_iter().enumerate() {
+        if i % CANCEL_EVERY == 0 && cancel.load(Ordering::SeqCst) {
+            return Err(AppError::Cancelled);
+        }
+        let sigma = radius / 2.0;
+        let (left, right) = ((x - radius).floor().max(0.0), (x + radius).ceil());
+        let (top, bottom) = ((y - radius).floor().max(0.0), (y + radius).ceil());
+        if right < 0.0 || bottom < 0.0 || left >= size as f32 || top >= size as f32 {
+            continue;
+        }
+        for py in top as usize..(bottom as usize).min(side) {
+            for px in left as usize..(right as usize).min(side) {
+                let (dx, dy) = (px as f32 + 0.5 - x, py as f32 + 0.5 - y);
+                let distance = dx * dx + dy * dy;
+                if distance > radius * radius {
+                    continue;
+                }
+                let a = sample.alpha * (-0.5 * distance / (sigma * sigma)).exp();
+                let pixel = &mut buffer[py * side + px];
+                for (channel, color) in pixel.iter_mut().zip(sample.color) {
+                    *channel = color * a + *channel * (1.0 - a);
+                }
+                pixel[3] = a + pixel[3] * (1.0 - a);
+            }
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(side * side * 4);
+    for [r, g, b, a] in buffer {
+        let unpremultiply = |c: f32| {
+            let c = if a > 0.0 { c / a } else { 0.0 };
+            (c.clamp(0.0, 1.0) * 255.0).round() as u8
+        };
+        pixels.extend_from_slice(&[
+            unpremultiply(r),
+            unpremultiply(g),
+            unpremultiply(b),
+            (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+        ]);
+    }
+    Ok(pixels)
+}
+
+pub fn write_png(path: &Path, size: u32, pixels: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), size, size);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(pixels))
+        .map_err(|e| format!("Failed to write the preview: {}", e))
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = length(a);
+    a.map(|c| c / length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::TempPaths;
+
+    /// A red splat at `position`; the DC term gives full red once offset by 0.5
+    fn red(position: [f32; 3]) -> [f32; 14] {
+        let [x, y, z] = position;
+        let dc = 0.5 / SH_C0;
+        [
+            x, y, z, -3.0, -3.0, -3.0, 1.0, 0.0, 0.0, 0.0, 4.0, dc, -dc, -dc,
+        ]
+    }
+
+    #[test]
+    fn a_framed_preview_shows_the_splats_and_is_cached_per_version() {
+        let paths = TempPaths::new();
+        let cache = cache::open(&paths.root().join("cache"), 64 * 1024 * 1024);
+        let artifact = paths.root().join("splat.ply");
+        let mut vertices: Vec<[f32; 14]> = (0..400)
+            .map(|i| red([(i % 20) as f32 * 0.05, (i / 20) as f32 * 0.05, 0.0]))
+            .collect();
+        // A stray splat far out is left out of the framing
+        vertices.push(red([500.0, 500.0, 500.0]));
+        std::fs::write(&artifact, sample_ply(&vertices)).unwrap();
+        let cancel = AtomicBool::new(false);
+
+        let preview = preview(&cache, &artifact, 64, None, &cancel).unwrap();
+        assert!(!preview.cached);
+        assert_eq!(preview.total_splats, 401);
+        assert_eq!(preview.bounds.unwrap().max, [500.0, 500.0, 500.0]);
+        let target = preview.camera.target;
+        assert!(
+            length(sub(target, [0.475, 0.475, 0.0])) < 0.1,
+            "{:?}",
+            target
+        );
+
+        let (samples, _) = read_samples(&artifact, &cancel).unwrap();
+        let pixels = render(&samples, &preview.camera, 64, &cancel).unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..(y * 64 + x) * 4 + 4];
+        let center = pixel(32, 32);
+        assert!(
+            center[3] > 200 && center[0] > 200 && center[1] < 50,
+            "{:?}",
+            center
+        );
+        assert_eq!(pixel(0, 0)[3], 0);
+
+        let decoder = png::Decoder::new(File::open(&preview.path).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!((info.width, info.height), (64, 64));
+
+        let again = super::preview(&cache, &artifact, 64, None, &cancel).unwrap();
+        assert!(again.cached);
+        assert_eq!(again.path, preview.path);
+        // A new version of the artifact gets a preview of its own
+        sidecar::write(
+            paths.root(),
+            &sidecar::Sidecar {
+                artifact_path: artifact.to_string_lossy().to_string(),
+                artifact_sha256: Some("abc".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let changed = super::preview(&cache, &artifact, 64, None, &cancel).unwrap();
+        assert!(!changed.cached);
+        assert_ne!(changed.path, preview.path);
+
+        cancel.store(true, Ordering::SeqCst);
+        let camera = Some(preview.camera);
+        assert!(matches!(
+            super::preview(&cache, &artifact, 128, camera, &cancel),
+            Err(AppError::Cancelled)
+        ));
+    }
+}
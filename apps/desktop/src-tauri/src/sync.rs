@@ -0,0 +1,287 @@
+//! Audio Sync
+//!
+//! Estimates how far apart the cameras of a multi-camera capture were started by
+//! cross-correlating the onsets of their audio tracks. Offsets are relative to a
+//! reference clip: a positive offset means the clip started earlier, so that much
+//! of its start is skipped to line it up.
+
+use crate::error::AppError;
+use crate::extraction;
+use crate::path_policy::PathPolicy;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::State;
+
+/// Audio is decoded at this rate; onsets need no more
+const SAMPLE_RATE: u32 = 8000;
+
+/// Envelope bins per second, which is also the resolution of the offsets
+const ENVELOPE_RATE: usize = 100;
+
+/// Only the start of each clip is compared
+const ANALYSIS_SECS: f64 = 120.0;
+
+/// Largest start difference looked for
+const MAX_OFFSET_SECS: usize = 10;
+
+static CANCEL: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    /// The clip the others are aligned to
+    Reference,
+    Synced,
+    /// No audio stream, or one that is silent
+    NoAudio,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipSync {
+    pub path: String,
+    pub status: SyncStatus,
+    pub offset_ms: Option<i64>,
+    /// Normalized correlation at the chosen offset, 0-1
+    pub confidence: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncReport {
+    pub reference: Option<String>,
+    pub clips: Vec<ClipSync>,
+}
+
+/// Estimate start offsets between clips from their audio
+#[tauri::command]
+pub async fn analyze_sync(
+    policy: State<'_, PathPolicy>,
+    videos: Vec<String>,
+) -> Result<SyncReport, AppError> {
+    for video in &videos {
+        policy.check_existing(video)?;
+    }
+    CANCEL.store(false, Ordering::SeqCst);
+    analyze(&videos, &CANCEL).await
+}
+
+/// Cancel a running sync analysis
+#[tauri::command]
+pub async fn cancel_sync_analysis() -> Result<(), AppError> {
+    CANCEL.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+pub async fn analyze(videos: &[String], cancel: &AtomicBool) -> Result<SyncReport, AppError> {
+    if videos.len() < 2 {
+        return Err(AppError::InvalidInput(
+            "Sync analysis needs at least two clips".to_string(),
+        ));
+    }
+
+    let mut envelopes = vec![];
+    for video in videos {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(AppError::Cancelled);
+        }
+        let samples = extraction::extract_mono_audio(video, SAMPLE_RATE, ANALYSIS_SECS).await?;
+        envelopes.push(samples.and_then(|s| onset_envelope(&s)));
+    }
+
+    if cancel.load(Ordering::SeqCst) {
+        return Err(AppError::Cancelled);
+    }
+    report(videos, &envelopes, cancel)
+}
+
+fn report(
+    videos: &[String],
+    envelopes: &[Option<Vec<f64>>],
+    cancel: &AtomicBool,
+) -> Result<SyncReport, AppError> {
+    let reference = envelopes.iter().position(Option::is_some);
+    let mut clips = vec![];
+    for (index, (video, envelope)) in videos.iter().zip(envelopes).enumerate() {
+        if cancel.load(Ordering::SeqCst) {
+            return Err(AppError::Cancelled);
+        }
+        let mut clip = ClipSync {
+            path: video.clone(),
+            status: SyncStatus::NoAudio,
+            offset_ms: None,
+            confidence: None,
+        };
+        match (envelope, reference) {
+            (Some(_), Some(r)) if r == index => {
+                clip.status = SyncStatus::Reference;
+                clip.offset_ms = Some(0);
+                clip.confidence = Some(1.0);
+            }
+            (Some(envelope), Some(r)) => {
+                let reference = envelopes[r].as_deref().unwrap_or_default();
+                let (lag, correlation) =
+                    best_lag(reference, envelope, MAX_OFFSET_SECS * ENVELOPE_RATE);
+                clip.status = SyncStatus::Synced;
+                clip.offset_ms = Some(lag * 1000 / ENVELOPE_RATE as i64);
+                clip.confidence = Some(correlation.clamp(0.0, 1.0));
+            }
+            _ => {}
+        }
+        clips.push(clip);
+    }
+
+    Ok(SyncReport {
+        reference: reference.map(|r| videos[r].clone()),
+        clips,
+    })
+}
+
+/// Rises in loudness, normalized to zero mean and unit variance. None for silence.
+fn onset_envelope(samples: &[f32]) -> Option<Vec<f64>> {
+    let bin = SAMPLE_RATE as usize / ENVELOPE_RATE;
+    let loudness: Vec<f64> = samples
+        .chunks(bin)
+        .map(|chunk| {
+            let power: f64 = chunk.iter().map(|&s| f64::from(s) * f64::from(s)).sum();
+            (power / chunk.len() as f64).sqrt()
+        })
+        .collect();
+    // Onsets match across microphones far better than absolute levels do
+    let onsets: Vec<f64> = loudness
+        .windows(2)
+        .map(|pair| (pair[1] - pair[0]).max(0.0))
+        .collect();
+    normalize(onsets)
+}
+
+fn normalize(mut values: Vec<f64>) -> Option<Vec<f64>> {
+    if values.is_empty() {
+        return None;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    if variance < 1e-12 {
+        return None;
+    }
+    let deviation = variance.sqrt();
+    for value in &mut values {
+        *value = (*value - mean) / deviation;
+    }
+    Some(values)
+}
+
+/// The lag of `other` against `reference` with the highest normalized correlation:
+/// what happens at bin i of the reference happens at bin i + lag of the other clip
+fn best_lag(reference: &[f64], other: &[f64], max_lag: usize) -> (i64, f64) {
+    // Lags that would compare less than a quarter of the shorter signal are too noisy
+    let min_overlap = reference.len().min(other.len()) / 4;
+    let max_lag = max_lag as i64;
+    let mut best = (0, f64::NEG_INFINITY);
+    for lag in -max_lag..=max_lag {
+        let start = (-lag).max(0) as usize;
+        let end = (reference.len() as i64).min(other.len() as i64 - lag);
+        if end <= start as i64 || ((end as usize) - start) < min_overlap.max(1) {
+            continue;
+        }
+        let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+        for i in start..end as usize {
+            let a = reference[i];
+            let b = other[(i as i64 + lag) as usize];
+            dot += a * b;
+            norm_a += a * a;
+            norm_b += b * b;
+        }
+        let correlation = if norm_a > 0.0 && norm_b > 0.0 {
+            dot / (norm_a * norm_b).sqrt()
+        } else {
+            0.0
+        };
+        if correlation > best.1 {
+            best = (lag, correlation);
+        }
+    }
+    (best.0, best.1.max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Quiet noise with a clap every so often, like a set with people talking
+    fn recording(secs: usize, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ((state >> 33) as f32 / u32::MAX as f32) - 0.25
+        };
+        let mut samples: Vec<f32> = (0..secs * SAMPLE_RATE as usize)
+            .map(|_| next() * 0.05)
+            .collect();
+        let mut at = 0;
+        while at < samples.len() {
+            // Irregular spacing so no two lags look alike
+            at += SAMPLE_RATE as usize / 2 + (next().abs() * SAMPLE_RATE as f32) as usize;
+            for sample in samples.iter_mut().skip(at).take(400) {
+                *sample += next() * 2.0;
+            }
+        }
+        samples
+    }
+
+    /// The same scene heard by a camera started `delay_ms` earlier, with its own noise
+    fn earlier_camera(scene: &[f32], delay_ms: usize, seed: u64) -> Vec<f32> {
+        let noise = recording(1, seed);
+        let mut samples: Vec<f32> = vec![0.0; delay_ms * SAMPLE_RATE as usize / 1000];
+        samples.extend(scene);
+        for (sample, n) in samples.iter_mut().zip(noise.iter().cycle()) {
+            *sample += n * 0.1;
+        }
+        samples
+    }
+
+    fn clips(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("/clips/cam{}.mp4", i + 1)).collect()
+    }
+
+    #[test]
+    fn finds_start_offsets_against_the_reference() {
+        let scene = recording(30, 7);
+        let envelopes = vec![
+            onset_envelope(&scene),
+            onset_envelope(&earlier_camera(&scene, 350, 11)),
+            onset_envelope(&scene[SAMPLE_RATE as usize * 2..]),
+        ];
+        let report = report(&clips(3), &envelopes, &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(report.reference.as_deref(), Some("/clips/cam1.mp4"));
+        assert_eq!(report.clips[0].status, SyncStatus::Reference);
+        assert_eq!(report.clips[1].offset_ms, Some(350));
+        assert_eq!(report.clips[2].offset_ms, Some(-2000));
+        assert!(report.clips[1].confidence.unwrap() > 0.5);
+    }
+
+    #[test]
+    fn clips_without_audio_get_no_offset() {
+        let scene = recording(10, 3);
+        let envelopes = vec![
+            None,
+            onset_envelope(&vec![0.0; 80_000]),
+            onset_envelope(&scene),
+        ];
+        assert!(envelopes[1].is_none());
+
+        let report = report(&clips(3), &envelopes, &AtomicBool::new(false)).unwrap();
+        assert_eq!(report.reference.as_deref(), Some("/clips/cam3.mp4"));
+        assert_eq!(report.clips[0].status, SyncStatus::NoAudio);
+        assert_eq!(report.clips[0].offset_ms, None);
+        assert_eq!(report.clips[1].status, SyncStatus::NoAudio);
+    }
+
+    #[test]
+    fn cancelled_analysis_stops() {
+        let envelopes = vec![onset_envelope(&recording(5, 1)), None];
+        let err = report(&clips(2), &envelopes, &AtomicBool::new(true)).unwrap_err();
+        assert_eq!(err.code(), "cancelled");
+    }
+}
@@ -0,0 +1,238 @@
+//! Training Options
+//!
+//! The splat's quality is set mostly by how long brush trains and how
+//! eagerly it densifies. These knobs are structured options rather than raw
+//! CLI flags: each is range-checked when the job is started, filled in from
+//! the defaults saved for the job's preset, and passed only when the
+//! installed gvcore-cli reports its flag. An option the CLI does not know is
+//! left out with a warning, so the job trains with the CLI's own value instead
+//! of failing.
+
+use crate::capabilities::{
+    CliCapabilities, FLAG_DENSIFY_GRAD_THRESHOLD, FLAG_DENSIFY_INTERVAL, FLAG_ITERATIONS,
+    FLAG_OPACITY_RESET_INTERVAL, FLAG_SH_DEGREE,
+};
+use crate::messages::Message;
+use crate::runner::CliWarning;
+use serde::{Deserialize, Serialize};
+
+/// Code of the warning for an option the CLI does not support
+pub const WARNING_UNSUPPORTED: &str = "unsupported-training-option";
+
+const MAX_ITERATIONS: u32 = 1_000_000;
+const MAX_SH_DEGREE: u32 = 3;
+
+/// Brush hyperparameters; an unset option keeps the CLI's preset value
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingOptions {
+    /// Training steps
+    pub iterations: Option<u32>,
+    /// Degree of the spherical harmonics that model view-dependent colour
+    pub sh_degree: Option<u32>,
+    /// Steps between densification passes
+    pub densify_interval: Option<u32>,
+    /// Positional gradient above which a splat is split or cloned
+    pub densify_grad_threshold: Option<f64>,
+    /// Steps between opacity resets
+    pub opacity_reset_interval: Option<u32>,
+}
+
+impl TrainingOptions {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// These options, with any left unset taken from `defaults`
+    pub fn or(&self, defaults: &TrainingOptions) -> TrainingOptions {
+        TrainingOptions {
+            iterations: self.iterations.or(defaults.iterations),
+            sh_degree: self.sh_degree.or(defaults.sh_degree),
+            densify_interval: self.densify_interval.or(defaults.densify_interval),
+            densify_grad_threshold: self
+                .densify_grad_threshold
+                .or(defaults.densify_grad_threshold),
+            opacity_reset_interval: self
+                .opacity_reset_interval
+                .or(defaults.opacity_reset_interval),
+        }
+    }
+
+    /// The field names of the set options
+    pub fn fields_set(&self) -> Vec<String> {
+        self.flags()
+            .into_iter()
+            .map(|(field, _, _)| field.to_string())
+            .collect()
+    }
+
+    /// Each set option as its field name, CLI flag and value
+    fn flags(&self) -> Vec<(&'static str, &'static str, String)> {
+        let mut flags = vec![];
+        let mut push = |field, flag, value: Option<String>| {
+            if let Some(value) = value {
+                flags.push((field, flag, value));
+            }
+        };
+        push(
+            "iterations",
+            FLAG_ITERATIONS,
+            self.iterations.map(|v| v.to_string()),
+        );
+        push(
+            "sh_degree",
+            FLAG_SH_DEGREE,
+            self.sh_degree.map(|v| v.to_string()),
+        );
+        push(
+            "densify_interval",
+            FLAG_DENSIFY_INTERVAL,
+            self.densify_interval.map(|v| v.to_string()),
+        );
+        push(
+            "densify_grad_threshold",
+            FLAG_DENSIFY_GRAD_THRESHOLD,
+            self.densify_grad_threshold.map(|v| v.to_string()),
+        );
+        push(
+            "opacity_reset_interval",
+            FLAG_OPACITY_RESET_INTERVAL,
+            self.opacity_reset_interval.map(|v| v.to_string()),
+        );
+        flags
+    }
+
+    /// Problems with the options' values, by field name
+    pub fn check(&self) -> Vec<(&'static str, Message)> {
+        let out_of_range = |min: &str, max: &str| {
+            Message::new("args.out_of_range")
+                .with("min", min)
+                .with("max", max)
+        };
+        let mut problems = vec![];
+        let mut count = |field, value: Option<u32>, min: u32, max: u32| {
+            if value.is_some_and(|v| v < min || v > max) {
+                problems.push((field, out_of_range(&min.to_string(), &max.to_string())));
+            }
+        };
+        count("iterations", self.iterations, 1, MAX_ITERATIONS);
+        count("sh_degree", self.sh_degree, 0, MAX_SH_DEGREE);
+        count("densify_interval", self.densify_interval, 1, MAX_ITERATIONS);
+        count(
+            "opacity_reset_interval",
+            self.opacity_reset_interval,
+            1,
+            MAX_ITERATIONS,
+        );
+        // NaN fails both comparisons, so the check is written to reject it
+        if self
+            .densify_grad_threshold
+            .is_some_and(|t| !(t > 0.0 && t <= 1.0))
+        {
+            problems.push(("densify_grad_threshold", out_of_range("0", "1")));
+        }
+        problems
+    }
+}
+
+/// The options the CLI will be handed, as it supports them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Applied {
+    pub options: TrainingOptions,
+    pub args: Vec<String>,
+    /// One per option left out because the CLI lacks its flag
+    pub warnings: Vec<CliWarning>,
+}
+
+/// Keep the options `caps` has a flag for
+pub fn apply(options: &TrainingOptions, caps: &CliCapabilities) -> Applied {
+    let supported = |flag| caps.supports(flag);
+    let mut applied = Applied {
+        options: TrainingOptions {
+            iterations: options.iterations.filter(|_| supported(FLAG_ITERATIONS)),
+            sh_degree: options.sh_degree.filter(|_| supported(FLAG_SH_DEGREE)),
+            densify_interval: options
+                .densify_interval
+                .filter(|_| supported(FLAG_DENSIFY_INTERVAL)),
+            densify_grad_threshold: options
+                .densify_grad_threshold
+                .filter(|_| supported(FLAG_DENSIFY_GRAD_THRESHOLD)),
+            opacity_reset_interval: options
+                .opacity_reset_interval
+                .filter(|_| supported(FLAG_OPACITY_RESET_INTERVAL)),
+        },
+        ..Default::default()
+    };
+    for (field, flag, value) in options.flags() {
+        if supported(flag) {
+            applied.args.push(flag.to_string());
+            applied.args.push(value);
+        } else {
+            applied.warnings.push(CliWarning {
+                code: WARNING_UNSUPPORTED.to_string(),
+                message: format!(
+                    "The installed gvcore-cli does not support {}; {} keeps the preset's value",
+                    flag, field
+                ),
+            });
+        }
+    }
+    applied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_options_are_left_out_with_a_warning() {
+        let options = TrainingOptions {
+            iterations: Some(30_000),
+            sh_degree: Some(2),
+            densify_grad_threshold: Some(0.0002),
+            ..Default::default()
+        };
+        let caps = CliCapabilities {
+            flags: vec![FLAG_ITERATIONS.to_string(), FLAG_SH_DEGREE.to_string()],
+            ..Default::default()
+        };
+        let applied = apply(&options, &caps);
+        assert_eq!(applied.args, ["--iterations", "30000", "--sh-degree", "2"]);
+        assert_eq!(applied.options.densify_grad_threshold, None);
+        assert_eq!(applied.options.iterations, Some(30_000));
+        assert_eq!(applied.warnings.len(), 1);
+        assert_eq!(applied.warnings[0].code, WARNING_UNSUPPORTED);
+        assert!(applied.warnings[0]
+            .message
+            .contains(FLAG_DENSIFY_GRAD_THRESHOLD));
+    }
+
+    #[test]
+    fn preset_defaults_fill_unset_options_and_ranges_are_checked() {
+        let defaults = TrainingOptions {
+            iterations: Some(7_000),
+            sh_degree: Some(3),
+            ..Default::default()
+        };
+        let options = TrainingOptions {
+            iterations: Some(50_000),
+            ..Default::default()
+        };
+        let merged = options.or(&defaults);
+        assert_eq!(merged.iterations, Some(50_000));
+        assert_eq!(merged.sh_degree, Some(3));
+        assert!(merged.check().is_empty());
+
+        let bad = TrainingOptions {
+            iterations: Some(0),
+            sh_degree: Some(4),
+            densify_grad_threshold: Some(f64::NAN),
+            ..Default::default()
+        };
+        let fields: Vec<&str> = bad.check().into_iter().map(|(f, _)| f).collect();
+        assert_eq!(
+            fields,
+            ["iterations", "sh_degree", "densify_grad_threshold"]
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Training Metrics
+//!
+//! Collects the loss, PSNR and splat count brush reports while it trains, for
+//! the chart on the job detail page. Metrics arrive as typed `metric` lines,
+//! or are picked out of brush's plain log lines when the CLI prints no typed
+//! ones. A metric with a name the app does not know is kept as a series of its
+//! own, so a new brush metric shows up without an app update. Each series is
+//! bounded: at MAX_POINTS every other point is dropped and from then on only
+//! every second sample is kept, doubling again each time it fills, so a long
+//! run keeps an even spread over all its iterations. Updates for the frontend
+//! are batched to one training-metrics event per EMIT_INTERVAL, and the series
+//! are kept with the job's history record.
+
+use crate::error::AppError;
+use crate::history;
+use crate::platform::PathProvider;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::AppHandle;
+
+/// Points a series holds at most
+pub const MAX_POINTS: usize = 2000;
+
+/// Shortest time between two training-metrics events of a job
+const EMIT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Metrics recognised in plain log lines, by the names brush has used for them
+const KNOWN: [(&str, &str); 10] = [
+    ("loss", "loss"),
+    ("train_loss", "loss"),
+    ("psnr", "psnr"),
+    ("ssim", "ssim"),
+    ("splats", "splat_count"),
+    ("num_splats", "splat_count"),
+    ("splat_count", "splat_count"),
+    ("gaussians", "splat_count"),
+    ("num_gaussians", "splat_count"),
+    ("lr", "learning_rate"),
+];
+
+// Jobs still training, by job id
+static LIVE: Mutex<BTreeMap<String, Recorder>> = Mutex::new(BTreeMap::new());
+
+/// One reading of one metric
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricSample {
+    pub name: String,
+    /// The training iteration; the last one seen when the CLI gave none
+    pub step: Option<u64>,
+    pub value: f64,
+}
+
+/// Sent as training-metrics: the points of a job added since the last event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricsUpdate {
+    pub job_id: String,
+    pub points: Vec<MetricSample>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricPoint {
+    pub step: u64,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricSeries {
+    pub name: String,
+    pub points: Vec<MetricPoint>,
+    /// Every how many samples one is kept
+    #[serde(skip)]
+    stride: usize,
+    /// Samples received, kept or not
+    #[serde(skip)]
+    seen: usize,
+}
+
+impl MetricSeries {
+    /// Whether the sample was kept
+    fn push(&mut self, point: MetricPoint) -> bool {
+        let stride = self.stride.max(1);
+        let keep = self.seen % stride == 0;
+        self.seen += 1;
+        if !keep {
+            return false;
+        }
+        self.points.push(point);
+        if self.points.len() >= MAX_POINTS {
+            let mut i = 0;
+            self.points.retain(|_| {
+                i += 1;
+                i % 2 == 1
+            });
+            self.stride = stride * 2;
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrainingMetrics {
+    pub series: Vec<MetricSeries>,
+    #[serde(skip)]
+    last_step: Option<u64>,
+}
+
+impl TrainingMetrics {
+    pub fn is_empty(&self) -> bool {
+        self.series.is_empty()
+    }
+
+    /// Add a sample, returning it with its step filled in when it was kept
+    pub fn record(&mut self, sample: &MetricSample) -> Option<MetricSample> {
+        let name = normalize(&sample.name);
+        let index = match self.series.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.series.push(MetricSeries {
+                    name: name.clone(),
+                    ..Default::default()
+                });
+                self.series.len() - 1
+            }
+        };
+        let series = &mut self.series[index];
+        let step = sample.step.or(self.last_step).unwrap_or(series.seen as u64);
+        if sample.step.is_some() {
+            self.last_step = sample.step;
+        }
+        let point = MetricPoint {
+            step,
+            value: sample.value,
+        };
+        series.push(point).then_some(MetricSample {
+            name,
+            step: Some(step),
+            value: sample.value,
+        })
+    }
+}
+
+/// A job's metrics while it runs, and the points not yet sent
+#[derive(Default)]
+struct Recorder {
+    metrics: TrainingMetrics,
+    unsent: Vec<MetricSample>,
+    last_sent: Option<Instant>,
+}
+
+/// Keeps a job's metrics available to get_training_metrics while it runs
+pub struct MetricsGuard {
+    job_id: String,
+}
+
+impl MetricsGuard {
+    /// The job's series, and the update with points not yet sent
+    pub fn finish(self) -> (TrainingMetrics, Option<MetricsUpdate>) {
+        let recorder = LIVE
+            .lock()
+            .unwrap()
+            .remove(&self.job_id)
+            .unwrap_or_default();
+        let update = (!recorder.unsent.is_empty()).then(|| MetricsUpdate {
+            job_id: self.job_id.clone(),
+            points: recorder.unsent,
+        });
+        (recorder.metrics, update)
+    }
+}
+
+impl Drop for MetricsGuard {
+    fn drop(&mut self) {
+        LIVE.lock().unwrap().remove(&self.job_id);
+    }
+}
+
+/// Start collecting the metrics of a job, for as long as the guard lives
+pub fn track(job_id: &str) -> MetricsGuard {
+    LIVE.lock()
+        .unwrap()
+        .insert(job_id.to_string(), Recorder::default());
+    MetricsGuard {
+        job_id: job_id.to_string(),
+    }
+}
+
+/// Add a sample to a tracked job, returning the update to send when one is due
+pub fn record(job_id: &str, sample: &MetricSample) -> Option<MetricsUpdate> {
+    let mut live = LIVE.lock().unwrap();
+    let recorder = live.get_mut(job_id)?;
+    recorder.unsent.extend(recorder.metrics.record(sample));
+    let due = recorder
+        .last_sent
+        .map_or(true, |sent| sent.elapsed() >= EMIT_INTERVAL);
+    if !due || recorder.unsent.is_empty() {
+        return None;
+    }
+    recorder.last_sent = Some(Instant::now());
+    Some(MetricsUpdate {
+        job_id: job_id.to_string(),
+        points: std::mem::take(&mut recorder.unsent),
+    })
+}
+
+/// The last value of a tracked job's `series`, such as splat_count
+pub fn latest(job_id: &str, series: &str) -> Option<f64> {
+    let live = LIVE.lock().unwrap();
+    let metrics = &live.get(job_id)?.metrics;
+    let series = metrics.series.iter().find(|s| s.name == series)?;
+    series.points.last().map(|p| p.value)
+}
+
+/// The metrics in a plain brush log line such as
+/// `step 1500/30000 loss=0.0421 psnr=24.7 splats=182340`
+pub fn parse_log_line(line: &str) -> Vec<MetricSample> {
+    static STEP_RE: OnceLock<Regex> = OnceLock::new();
+    static VALUE_RE: OnceLock<Regex> = OnceLock::new();
+    let step_re = STEP_RE
+        .get_or_init(|| Regex::new(r"(?i)\b(?:iter(?:ation)?|step)\s*[:=#]?\s*(\d+)").unwrap());
+    let value_re = VALUE_RE.get_or_init(|| {
+        Regex::new(r"(?i)\b([a-z][a-z0-9_]*)\s*[:=]\s*([-+]?(?:\d+\.?\d*|\.\d+)(?:e[-+]?\d+)?)")
+            .unwrap()
+    });
+
+    let step = step_re
+        .captures(line)
+        .and_then(|c| c.get(1)?.as_str().parse().ok());
+    value_re
+        .captures_iter(line)
+        .filter_map(|c| {
+            let name = c.get(1)?.as_str().to_lowercase();
+            KNOWN.iter().find(|(known, _)| *known == name)?;
+            Some(MetricSample {
+                name,
+                step,
+                value: c.get(2)?.as_str().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// The series name of a metric; names the app does not know keep their own
+fn normalize(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match KNOWN.iter().find(|(known, _)| *known == name) {
+        Some((_, series)) => series.to_string(),
+        None => name,
+    }
+}
+
+/// The loss, PSNR and other series of a job, while it runs or from its history
+#[tauri::command]
+pub async fn get_training_metrics(
+    app: AppHandle,
+    job_id: String,
+) -> Result<TrainingMetrics, AppError> {
+    training_metrics(&app, &job_id)
+}
+
+fn training_metrics(paths: &impl PathProvider, job_id: &str) -> Result<TrainingMetrics, AppError> {
+    if let Some(recorder) = LIVE.lock().unwrap().get(job_id) {
+        return Ok(recorder.metrics.clone());
+    }
+    history::load(paths)?
+        .into_iter()
+        .find(|r| r.job_id == job_id)
+        .map(|r| r.metrics)
+        .ok_or_else(|| AppError::NotFound(job_id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str, step: Option<u64>, value: f64) -> MetricSample {
+        MetricSample {
+            name: name.to_string(),
+            step,
+            value,
+        }
+    }
+
+    #[test]
+    fn brush_log_lines_yield_known_metrics() {
+        let samples = parse_log_line("step 1500/30000 loss=0.0421 psnr: 24.7 splats=182340");
+        assert_eq!(
+            samples,
+            [
+                sample("loss", Some(1500), 0.0421),
+                sample("psnr", Some(1500), 24.7),
+                sample("splats", Some(1500), 182340.0),
+            ]
+        );
+        assert!(parse_log_line("[colmap] 40% - Matching features").is_empty());
+        assert!(parse_log_line("Using device=0 threads=8").is_empty());
+    }
+
+    #[test]
+    fn unknown_metrics_get_their_own_series_and_steps_carry_over() {
+        let mut metrics = TrainingMetrics::default();
+        metrics.record(&sample("num_gaussians", Some(100), 5000.0));
+        let kept = metrics.record(&sample("opacity_mean", None, 0.4)).unwrap();
+        assert_eq!(kept.step, Some(100));
+
+        let names: Vec<&str> = metrics.series.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["splat_count", "opacity_mean"]);
+    }
+
+    #[test]
+    fn series_are_downsampled_evenly_beyond_the_limit() {
+        let mut metrics = TrainingMetrics::default();
+        for step in 0..(MAX_POINTS as u64 * 5) {
+            metrics.record(&sample("loss", Some(step), 1.0));
+        }
+        let points = &metrics.series[0].points;
+        assert!(points.len() < MAX_POINTS);
+        assert!(points.len() > MAX_POINTS / 4);
+        assert_eq!(points[0].step, 0);
+        let gap = points[1].step - points[0].step;
+        assert!(points.windows(2).all(|w| w[1].step - w[0].step == gap));
+        assert!(points.last().unwrap().step > MAX_POINTS as u64 * 4);
+    }
+
+    #[test]
+    fn updates_are_batched_until_the_job_finishes() {
+        let guard = track("job-metrics-test");
+        let first = record("job-metrics-test", &sample("loss", Some(1), 0.9)).unwrap();
+        assert_eq!(first.points.len(), 1);
+        assert_eq!(
+            record("job-metrics-test", &sample("loss", Some(2), 0.8)),
+            None
+        );
+        assert_eq!(
+            record("job-metrics-test", &sample("psnr", Some(2), 21.0)),
+            None
+        );
+        assert_eq!(record("job-unknown", &sample("loss", Some(2), 0.8)), None);
+
+        let (metrics, update) = guard.finish();
+        assert_eq!(metrics.series.len(), 2);
+        assert_eq!(update.unwrap().points.len(), 2);
+        assert!(!LIVE.lock().unwrap().contains_key("job-metrics-test"));
+    }
+}
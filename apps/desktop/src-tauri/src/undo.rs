@@ -0,0 +1,437 @@
+//! Undo Journal
+//!
+//! Destructive commands record how to reverse themselves, so the frontend can
+//! offer an "Undo" toast. The journal only lasts for the session. Entries expire
+//! after the configured window, or as soon as what they would restore from is
+//! gone, such as a production that was emptied from the trash.
+
+use crate::archive;
+use crate::error::AppError;
+use crate::history;
+use crate::job_log;
+use crate::messages::Message;
+use crate::platform::PathProvider;
+use crate::settings::{Persist, RecentProduction, SettingsStore};
+use crate::viewers::ExternalViewer;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+/// Oldest entries are dropped beyond this many
+const MAX_ENTRIES: usize = 20;
+
+static JOURNAL: Mutex<Vec<UndoEntry>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UndoEntry {
+    pub id: String,
+    /// What the operation did, e.g. "Moved harbour to the trash"
+    pub description: Message,
+    pub created_at: u64,
+    pub expires_at: u64,
+    #[serde(skip)]
+    restore: Restore,
+}
+
+/// How to reverse an operation
+#[derive(Debug, Clone)]
+pub enum Restore {
+    /// Put trashed files back, along with what delete_production unlinked from them
+    Trashed {
+        trash_ids: Vec<OsString>,
+        recents: Vec<RecentProduction>,
+        /// Job id and artifact path of history records that lost their artifact
+        artifacts: Vec<(String, String)>,
+        /// Links made where trashed files were, removed before they are put back
+        links: Vec<PathBuf>,
+    },
+    /// Extract intermediates the retention policy archived back into their production
+    Archived { dir: PathBuf },
+    /// Re-add a removed external viewer where it was in the list
+    Viewer {
+        viewer: ExternalViewer,
+        index: usize,
+    },
+}
+
+/// Record an operation that undo_last_operation can reverse
+pub fn record(store: &impl SettingsStore, description: Message, restore: Restore) {
+    let now = job_log::unix_timestamp();
+    let entry = UndoEntry {
+        id: job_log::new_id("undo"),
+        description,
+        created_at: now,
+        expires_at: now + store.settings().undo_window_secs,
+        restore,
+    };
+    push(&mut JOURNAL.lock().unwrap(), entry);
+}
+
+/// Operations that can still be undone, newest first
+#[tauri::command]
+pub async fn get_undo_stack() -> Result<Vec<UndoEntry>, AppError> {
+    let mut journal = JOURNAL.lock().unwrap();
+    prune(&mut journal, job_log::unix_timestamp(), available);
+    Ok(journal.iter().rev().cloned().collect())
+}
+
+/// Reverse the newest operation that can still be undone, returning it
+#[tauri::command]
+pub async fn undo_last_operation(app: AppHandle) -> Result<UndoEntry, AppError> {
+    let entry = {
+        let mut journal = JOURNAL.lock().unwrap();
+        prune(&mut journal, job_log::unix_timestamp(), available);
+        journal.pop()
+    };
+    let entry = entry.ok_or_else(|| AppError::NotFound("An operation to undo".to_string()))?;
+    // Taken out first so a second undo cannot apply it too, and put back when
+    // it fails so it can be retried
+    if let Err(e) = apply(&app, &app, &entry.restore) {
+        reinstate(&mut JOURNAL.lock().unwrap(), entry);
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+fn push(journal: &mut Vec<UndoEntry>, entry: UndoEntry) {
+    journal.push(entry);
+    if journal.len() > MAX_ENTRIES {
+        journal.remove(0);
+    }
+}
+
+/// Put back an entry whose undo failed, behind anything recorded since
+fn reinstate(journal: &mut Vec<UndoEntry>, entry: UndoEntry) {
+    let index = journal
+        .iter()
+        .position(|e| e.created_at > entry.created_at)
+        .unwrap_or(journal.len());
+    journal.insert(index, entry);
+    if journal.len() > MAX_ENTRIES {
+        journal.remove(0);
+    }
+}
+
+fn prune(journal: &mut Vec<UndoEntry>, now: u64, available: impl Fn(&Restore) -> bool) {
+    journal.retain(|entry| entry.expires_at > now && available(&entry.restore));
+}
+
+fn available(restore: &Restore) -> bool {
+    match restore {
+        Restore::Trashed { trash_ids, .. } => trashed::contains(trash_ids),
+        Restore::Archived { dir } => dir.join(archive::ARCHIVE_NAME).is_file(),
+        Restore::Viewer { .. } => true,
+    }
+}
+
+fn apply(
+    store: &impl SettingsStore,
+    paths: &impl PathProvider,
+    restore: &Restore,
+) -> Result<(), AppError> {
+    match restore {
+        Restore::Trashed {
+            trash_ids,
+            recents,
+            artifacts,
+            links,
+        } => {
+            for link in links {
+                if link.symlink_metadata().is_ok() {
+                    std::fs::remove_file(link)?;
+                }
+            }
+            trashed::restore(trash_ids)?;
+            store.update_settings(Persist::Now, |s| {
+                for recent in recents {
+                    if !s.recent_productions.iter().any(|r| r.id == recent.id) {
+                        s.recent_productions.push(recent.clone());
+                    }
+                }
+                Ok::<_, AppError>(())
+            })?;
+            if !artifacts.is_empty() {
+                let mut changed = vec![];
+                for mut record in history::load(paths)? {
+                    let artifact = artifacts
+                        .iter()
+                        .find(|(job_id, _)| *job_id == record.job_id);
+                    if let (None, Some((_, path))) = (&record.artifact_path, artifact) {
+                        record.artifact_path = Some(path.clone());
+                        changed.push(record);
+                    }
+                }
+                history::amend(paths, &changed)?;
+            }
+            Ok(())
+        }
+        Restore::Archived { dir } => archive::restore_now(dir),
+        Restore::Viewer { viewer, index } => store.update_settings(Persist::Now, |s| {
+            if !s.external_viewers.iter().any(|v| v.id == viewer.id) {
+                let index = (*index).min(s.external_viewers.len());
+                s.external_viewers.insert(index, viewer.clone());
+            }
+            Ok(())
+        }),
+    }
+}
+
+/// Items in the OS trash. Only Windows and the freedesktop trash can be listed
+/// and restored from; elsewhere nothing that was trashed can be undone.
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+pub mod trashed {
+    use crate::error::AppError;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+    use trash::os_limited;
+
+    /// The newest trash item of each path that was just moved to the trash
+    pub fn find(paths: &[PathBuf]) -> Vec<OsString> {
+        let Ok(items) = os_limited::list() else {
+            return vec![];
+        };
+        paths
+            .iter()
+            .filter_map(|path| {
+                items
+                    .iter()
+                    .filter(|item| item.original_path() == *path)
+                    .max_by_key(|item| item.time_deleted)
+                    .map(|item| item.id.clone())
+            })
+            .collect()
+    }
+
+    pub fn contains(ids: &[OsString]) -> bool {
+        if ids.is_empty() {
+            return true;
+        }
+        os_limited::list()
+            .map(|items| ids.iter().all(|id| items.iter().any(|item| item.id == *id)))
+            .unwrap_or(false)
+    }
+
+    pub fn restore(ids: &[OsString]) -> Result<(), AppError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let items = os_limited::list().map_err(|e| AppError::Io(e.to_string()))?;
+        let items: Vec<_> = items
+            .into_iter()
+            .filter(|item| ids.contains(&item.id))
+            .collect();
+        if items.len() != ids.len() {
+            return Err(AppError::NotFound("The trashed files".to_string()));
+        }
+        os_limited::restore_all(items)
+            .map_err(|e| AppError::Io(format!("Failed to restore from the trash: {}", e)))
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+pub mod trashed {
+    use crate::error::AppError;
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    pub fn find(_paths: &[PathBuf]) -> Vec<OsString> {
+        vec![]
+    }
+
+    pub fn contains(ids: &[OsString]) -> bool {
+        ids.is_empty()
+    }
+
+    pub fn restore(ids: &[OsString]) -> Result<(), AppError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        Err(AppError::NotFound("The trashed files".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{JobRecord, JobStatus};
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::AppSettings;
+
+    fn viewer(id: &str) -> ExternalViewer {
+        ExternalViewer {
+            id: id.to_string(),
+            name: id.to_string(),
+            path: format!("/apps/{}", id),
+            args_template: String::new(),
+        }
+    }
+
+    fn entry(expires_at: u64, restore: Restore) -> UndoEntry {
+        UndoEntry {
+            id: job_log::new_id("undo"),
+            description: Message::new("undo.remove_external_viewer"),
+            created_at: expires_at,
+            expires_at,
+            restore,
+        }
+    }
+
+    #[test]
+    fn expired_and_unavailable_entries_are_dropped() {
+        let mut journal = vec![];
+        for i in 0..MAX_ENTRIES + 2 {
+            let restore = Restore::Viewer {
+                viewer: viewer(&format!("v{}", i)),
+                index: 0,
+            };
+            push(&mut journal, entry(100 + i as u64, restore));
+        }
+        assert_eq!(journal.len(), MAX_ENTRIES);
+
+        prune(&mut journal, 110, |_| true);
+        assert_eq!(journal.len(), MAX_ENTRIES - 9);
+        prune(&mut journal, 110, |restore| match restore {
+            Restore::Viewer { viewer, .. } => viewer.id != "v21",
+            Restore::Trashed { .. } | Restore::Archived { .. } => true,
+        });
+        let Restore::Viewer { viewer, .. } = &journal.last().unwrap().restore else {
+            panic!("expected a viewer entry");
+        };
+        assert_eq!(viewer.id, "v20");
+    }
+
+    #[test]
+    fn a_failed_undo_is_put_back_behind_newer_entries() {
+        let viewer_entry = |id: &str, created_at| {
+            entry(
+                created_at,
+                Restore::Viewer {
+                    viewer: viewer(id),
+                    index: 0,
+                },
+            )
+        };
+        let mut journal = vec![viewer_entry("a", 1), viewer_entry("b", 2)];
+        let failed = journal.pop().unwrap();
+        push(&mut journal, viewer_entry("c", 3));
+        reinstate(&mut journal, failed);
+
+        let ids: Vec<&str> = journal
+            .iter()
+            .map(|e| match &e.restore {
+                Restore::Viewer { viewer, .. } => viewer.id.as_str(),
+                _ => panic!("expected a viewer entry"),
+            })
+            .collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn undo_puts_a_removed_viewer_back_in_place() {
+        let paths = TempPaths::new();
+        let store = MemorySettings::with(AppSettings {
+            external_viewers: vec![viewer("a"), viewer("c")],
+            ..Default::default()
+        });
+        let restore = Restore::Viewer {
+            viewer: viewer("b"),
+            index: 1,
+        };
+        apply(&store, &paths, &restore).unwrap();
+        apply(&store, &paths, &restore).unwrap();
+
+        let ids: Vec<String> = store
+            .settings()
+            .external_viewers
+            .into_iter()
+            .map(|v| v.id)
+            .collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn undo_relinks_recents_and_history() {
+        let paths = TempPaths::new();
+        let store = MemorySettings::with(AppSettings::default());
+        let record = JobRecord {
+            job_id: "job-1".to_string(),
+            batch_id: None,
+            status: JobStatus::Completed,
+            preset: "balanced".to_string(),
+            videos: vec![],
+            output_dir: "/productions/harbour".to_string(),
+            artifact_path: None,
+            error: None,
+            started_at: 0,
+            finished_at: 0,
+            overlap: None,
+            artifact_sha256: None,
+            capture_type: Default::default(),
+            partial: false,
+            warnings: vec![],
+            clips: vec![],
+            stages: Default::default(),
+            input_secs: None,
+            preview: false,
+            args: None,
+            reuse: None,
+            command: None,
+            metrics: Default::default(),
+            frames: None,
+            missing: false,
+            label: None,
+            tags: vec![],
+            attempts: vec![],
+            artifacts: vec![],
+            vram_override: None,
+            registration: None,
+            verification: None,
+        };
+        history::save(&paths, &[record]).unwrap();
+        let recent = RecentProduction {
+            id: "p1".to_string(),
+            name: "Harbour".to_string(),
+            path: "/productions/harbour".to_string(),
+            last_opened: String::new(),
+            tags: vec![],
+            notes: String::new(),
+            imported: false,
+            missing: false,
+            pinned: false,
+        };
+
+        let restore = Restore::Trashed {
+            trash_ids: vec![],
+            recents: vec![recent],
+            artifacts: vec![(
+                "job-1".to_string(),
+                "/productions/harbour/output.ply".to_string(),
+            )],
+            links: vec![],
+        };
+        apply(&store, &paths, &restore).unwrap();
+        assert_eq!(store.settings().recent_productions[0].id, "p1");
+        assert_eq!(
+            history::load(&paths).unwrap()[0].artifact_path.as_deref(),
+            Some("/productions/harbour/output.ply")
+        );
+    }
+}
@@ -0,0 +1,612 @@
+//! Command Input Validation
+//!
+//! Complex command inputs arrive as plain JSON and are checked field by field
+//! before they become typed arguments. Every problem is collected, so instead of
+//! serde's first error about an unnamed position, the frontend gets one keyed
+//! message per offending field (`AppError::InvalidArguments`) to highlight in
+//! the form.
+
+use crate::appearance::{self, AppearanceSettings, Theme};
+use crate::auto_retry;
+use crate::cache;
+use crate::camera_groups;
+use crate::camera_intrinsics;
+use crate::commands::{BatchMode, ProcessArgs};
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::scheduler;
+use crate::settings::AppSettings;
+use crate::vram_policy;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fmt;
+use std::path::Path;
+
+/// A problem with one field of a command input; the message names the field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// The field's name, with an index for list items, e.g. "videos[2]"
+    pub field: String,
+    #[serde(flatten)]
+    pub message: Message,
+    /// The values the field may take, when it is one of a fixed set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The fields of a JSON object, taken one at a time
+struct Fields {
+    object: Map<String, Value>,
+    errors: Vec<FieldError>,
+}
+
+impl Fields {
+    fn new(raw: Value) -> Result<Self, AppError> {
+        match raw {
+            Value::Object(object) => Ok(Self {
+                object,
+                errors: vec![],
+            }),
+            _ => Err(AppError::InvalidArguments(vec![FieldError {
+                field: String::new(),
+                message: Message::new("args.not_an_object"),
+                options: vec![],
+            }])),
+        }
+    }
+
+    fn error(&mut self, field: &str, message: Message) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            message: message.with("field", field),
+            options: vec![],
+        });
+    }
+
+    /// A field that must be present and not null
+    fn required<T: DeserializeOwned + Default>(&mut self, name: &str) -> T {
+        match self.object.remove(name) {
+            None | Some(Value::Null) => {
+                self.error(name, Message::new("args.required"));
+                T::default()
+            }
+            Some(value) => self.parse(name, value).unwrap_or_default(),
+        }
+    }
+
+    /// A field that falls back to `default` when absent or null
+    fn optional<T: DeserializeOwned>(&mut self, name: &str, default: T) -> T {
+        match self.object.remove(name) {
+            None | Some(Value::Null) => default,
+            Some(value) => self.parse(name, value).unwrap_or(default),
+        }
+    }
+
+    fn parse<T: DeserializeOwned>(&mut self, name: &str, value: Value) -> Option<T> {
+        match serde_json::from_value(value) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                let message = Message::new("args.wrong_type").with("detail", e);
+                self.error(name, message);
+                None
+            }
+        }
+    }
+
+    fn not_empty(&mut self, name: &str, value: &str) {
+        if value.trim().is_empty() && !self.has_error(name) {
+            self.error(name, Message::new("args.empty"));
+        }
+    }
+
+    fn absolute(&mut self, name: &str, path: &str) {
+        if !path.trim().is_empty() && !Path::new(path).is_absolute() {
+            self.error(name, Message::new("args.not_absolute").with("path", path));
+        }
+    }
+
+    fn preset(&mut self, name: &str, preset: &str, known: &[String]) {
+        if !preset.is_empty() && !known.iter().any(|p| p == preset) {
+            let message = Message::new("args.unknown_preset").with("preset", preset);
+            self.error(name, message);
+            // So the form can offer a picker instead
+            self.errors.last_mut().unwrap().options = known.to_vec();
+        }
+    }
+
+    fn has_error(&self, name: &str) -> bool {
+        self.errors.iter().any(|e| e.field == name)
+    }
+
+    fn finish<T>(self, value: T) -> Result<T, AppError> {
+        if self.errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(AppError::InvalidArguments(self.errors))
+        }
+    }
+}
+
+/// Check the arguments of process_videos
+pub fn process_args(raw: Value, presets: &[String]) -> Result<ProcessArgs, AppError> {
+    let mut fields = Fields::new(raw)?;
+    let args = ProcessArgs {
+        production_id: fields.optional("production_id", None),
+        videos: fields.required("videos"),
+        output_dir: fields.required("output_dir"),
+        preset: fields.required("preset"),
+        colmap_path: fields.optional("colmap_path", None),
+        brush_path: fields.optional("brush_path", None),
+        clips: fields.optional("clips", vec![]),
+        allow_mixed_projection: fields.optional("allow_mixed_projection", false),
+        masks: fields.optional("masks", None),
+        auto_mask: fields.optional("auto_mask", false),
+        mode: fields.optional("mode", BatchMode::default()),
+        stop_on_error: fields.optional("stop_on_error", false),
+        filter_frames: fields.optional("filter_frames", Default::default()),
+        capture_type: fields.optional("capture_type", Default::default()),
+        profile_overrides: vec![],
+        scratch_dir: None,
+        output_name_template: fields.optional("output_name_template", None),
+        batch_id: None,
+        simulate: fields.optional("simulate", false),
+        simulate_fail_at_stage: fields.optional("simulate_fail_at_stage", None),
+        simulate_duration_secs: fields.optional("simulate_duration_secs", None),
+        force_reextract: fields.optional("force_reextract", false),
+        normalize_resolution: fields.optional("normalize_resolution", false),
+        preview_mode: fields.optional("preview_mode", false),
+        reuse_reconstruction: fields.optional("reuse_reconstruction", Default::default()),
+        training: fields.optional("training", None),
+        label: fields.optional("label", None),
+        tags: fields.optional("tags", vec![]),
+        checkpoints: None,
+        vram_wait_secs: None,
+        override_vram_policy: fields.optional("override", false),
+        min_vram_gb: None,
+        vram_override: None,
+        vram_warning: None,
+        retry: None,
+        post_run_hooks: vec![],
+        known_devices: vec![],
+        continue_on_poor_registration: fields.optional("continue_on_poor_registration", false),
+        min_registered_percent: None,
+        allow_duplicate: fields.optional("allow_duplicate", false),
+        fingerprint: None,
+    };
+
+    if args.videos.is_empty() && !fields.has_error("videos") {
+        fields.error("videos", Message::new("args.empty"));
+    }
+    for (i, video) in args.videos.iter().enumerate() {
+        fields.absolute(&format!("videos[{}]", i), video);
+    }
+    for (i, clip) in args.clips.iter().enumerate() {
+        let problem = clip
+            .camera_group
+            .as_deref()
+            .and_then(camera_groups::check_name);
+        if let Some(message) = problem {
+            fields.error(&format!("clips[{}].camera_group", i), message);
+        }
+    }
+    fields.not_empty("output_dir", &args.output_dir);
+    fields.absolute("output_dir", &args.output_dir);
+    fields.preset("preset", &args.preset, presets);
+    if let Some(training) = &args.training {
+        for (field, message) in training.check() {
+            fields.error(&format!("training.{}", field), message);
+        }
+    }
+    for (name, path) in [
+        ("colmap_path", &args.colmap_path),
+        ("brush_path", &args.brush_path),
+        ("masks", &args.masks),
+    ] {
+        if let Some(path) = path {
+            fields.absolute(name, path);
+        }
+    }
+    fields.finish(args)
+}
+
+/// Settings the backend keeps up to date itself; a copy sent back by the
+/// webview may be stale, so it is ignored
+const BACKEND_MANAGED: [&str; 1] = ["recentProductions"];
+
+/// Settings naming programs the app runs, with the command that checks them
+/// before they are set
+const SET_BY_COMMAND: [(&str, &str); 7] = [
+    ("colmapPath", "set_tool_path"),
+    ("brushPath", "set_tool_path"),
+    ("ffmpegPath", "set_tool_path"),
+    ("gvcoreCliPath", "set_tool_path"),
+    ("externalViewers", "add_external_viewer"),
+    ("postRunHooks", "set_post_run_hooks"),
+    ("postRunHooksEnabled", "set_post_run_hooks"),
+];
+
+/// Check the settings passed to save_settings and merge them over `current`:
+/// settings left out keep their value. Settings use their stored, camelCase
+/// field names. Those the backend manages are ignored, and those naming
+/// programs may be sent only as they are.
+pub fn settings(
+    raw: Value,
+    presets: &[String],
+    current: &AppSettings,
+) -> Result<AppSettings, AppError> {
+    let mut fields = Fields::new(raw)?;
+    for name in BACKEND_MANAGED {
+        fields.object.remove(name);
+    }
+    let stored = serde_json::to_value(current).unwrap_or_default();
+    for (name, command) in SET_BY_COMMAND {
+        let Some(sent) = fields.object.remove(name) else {
+            continue;
+        };
+        if sent != stored[name] {
+            let message = Message::new("args.set_by_command").with("command", command);
+            fields.error(name, message);
+        }
+    }
+    let current = current.clone();
+    // Sent by frontends from before appearance settings
+    let theme: Option<Theme> = fields.optional("theme", None);
+    let mut settings = AppSettings {
+        appearance: fields.optional(
+            "appearance",
+            AppearanceSettings {
+                theme: theme.unwrap_or(current.appearance.theme),
+                ..current.appearance
+            },
+        ),
+        default_output_dir: fields.optional("defaultOutputDir", current.default_output_dir),
+        default_preset: fields.optional("defaultPreset", current.default_preset),
+        colmap_path: current.colmap_path,
+        brush_path: current.brush_path,
+        recent_productions: current.recent_productions,
+        first_run_completed: fields.optional("firstRunCompleted", current.first_run_completed),
+        verify_artifacts_on_open: fields
+            .optional("verifyArtifactsOnOpen", current.verify_artifacts_on_open),
+        external_viewers: current.external_viewers,
+        output_name_template: fields.optional("outputNameTemplate", current.output_name_template),
+        prefetch_concurrency: fields.optional("prefetchConcurrency", current.prefetch_concurrency),
+        capture_profiles: fields.optional("captureProfiles", current.capture_profiles),
+        network: fields.optional("network", current.network),
+        scratch_dir: fields.optional("scratchDir", current.scratch_dir),
+        undo_window_secs: fields.optional("undoWindowSecs", current.undo_window_secs),
+        ffmpeg_path: current.ffmpeg_path,
+        unsafe_output_locations: fields
+            .optional("unsafeOutputLocations", current.unsafe_output_locations),
+        preset_training: fields.optional("presetTraining", current.preset_training),
+        preset_min_vram_gb: fields.optional("presetMinVramGb", current.preset_min_vram_gb),
+        known_devices: fields.optional("knownDevices", current.known_devices),
+        checkpoints: fields.optional("checkpoints", current.checkpoints),
+        gvcore_cli_path: current.gvcore_cli_path,
+        cache_max_bytes: fields.optional("cacheMaxBytes", current.cache_max_bytes),
+        min_registered_percent: fields
+            .optional("minRegisteredPercent", current.min_registered_percent),
+        wait_for_vram: fields.optional("waitForVram", current.wait_for_vram),
+        vram_wait_secs: fields.optional("vramWaitSecs", current.vram_wait_secs),
+        auto_retry: fields.optional("autoRetry", current.auto_retry),
+        workers: fields.optional("workers", current.workers),
+        post_run_hooks: current.post_run_hooks,
+        post_run_hooks_enabled: current.post_run_hooks_enabled,
+        retention: fields.optional("retention", current.retention),
+        notifications: fields.optional("notifications", current.notifications),
+    };
+
+    fields.absolute("defaultOutputDir", &settings.default_output_dir);
+    fields.preset("defaultPreset", &settings.default_preset, presets);
+    if settings.checkpoints.interval_minutes == 0 {
+        let message = Message::new("args.out_of_range")
+            .with("min", 1)
+            .with("max", u32::MAX);
+        fields.error("checkpoints.intervalMinutes", message);
+    }
+    if settings.cache_max_bytes < cache::MIN_MAX_BYTES {
+        let message = Message::new("args.out_of_range")
+            .with("min", cache::MIN_MAX_BYTES)
+            .with("max", u64::MAX);
+        fields.error("cacheMaxBytes", message);
+    }
+    if settings.auto_retry.max_retries > auto_retry::MAX_RETRIES {
+        let message = Message::new("args.out_of_range")
+            .with("min", 0)
+            .with("max", auto_retry::MAX_RETRIES);
+        fields.error("autoRetry.maxRetries", message);
+    }
+    if settings.min_registered_percent > 100 {
+        let message = Message::new("args.out_of_range")
+            .with("min", 0)
+            .with("max", 100);
+        fields.error("minRegisteredPercent", message);
+    }
+    if settings.retention.keep_days == 0 {
+        let message = Message::new("args.out_of_range")
+            .with("min", 1)
+            .with("max", u32::MAX);
+        fields.error("retention.keepDays", message);
+    }
+    if settings.notifications.expiry_hours == 0 {
+        let message = Message::new("args.out_of_range")
+            .with("min", 1)
+            .with("max", u32::MAX);
+        fields.error("notifications.expiryHours", message);
+    }
+    if settings.vram_wait_secs == 0 {
+        let message = Message::new("args.out_of_range")
+            .with("min", 1)
+            .with("max", u64::MAX);
+        fields.error("vramWaitSecs", message);
+    }
+    for (name, workers) in [
+        ("workers.io", settings.workers.io),
+        ("workers.cpuHeavy", settings.workers.cpu_heavy),
+        ("workers.background", settings.workers.background),
+    ] {
+        if workers.is_some_and(|w| !(1..=scheduler::MAX_WORKERS).contains(&w)) {
+            let message = Message::new("args.out_of_range")
+                .with("min", 1)
+                .with("max", scheduler::MAX_WORKERS);
+            fields.error(name, message);
+        }
+    }
+    if settings.workers.niceness > scheduler::MAX_NICENESS {
+        let message = Message::new("args.out_of_range")
+            .with("min", 0)
+            .with("max", scheduler::MAX_NICENESS);
+        fields.error("workers.niceness", message);
+    }
+    for (preset, training) in &settings.preset_training {
+        for (field, message) in training.check() {
+            fields.error(&format!("presetTraining.{}.{}", preset, field), message);
+        }
+    }
+    for (preset, gb) in &settings.preset_min_vram_gb {
+        if !(1..=vram_policy::MAX_MIN_VRAM_GB).contains(gb) {
+            let message = Message::new("args.out_of_range")
+                .with("min", 1)
+                .with("max", vram_policy::MAX_MIN_VRAM_GB);
+            fields.error(&format!("presetMinVramGb.{}", preset), message);
+        }
+    }
+    for (field, message) in appearance::check(&settings.appearance, "appearance") {
+        fields.error(&field, message);
+    }
+    settings.appearance = settings.appearance.normalized();
+    for (field, message) in camera_intrinsics::check(&settings.known_devices, "knownDevices") {
+        fields.error(&field, message);
+    }
+    if let Some(path) = &settings.scratch_dir {
+        fields.absolute("scratchDir", path);
+    }
+    fields.finish(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn presets() -> Vec<String> {
+        vec!["fast".to_string(), "balanced".to_string()]
+    }
+
+    fn root() -> String {
+        std::env::temp_dir().to_string_lossy().to_string()
+    }
+
+    fn errors(result: Result<impl fmt::Debug, AppError>) -> Vec<(String, String)> {
+        match result.unwrap_err() {
+            AppError::InvalidArguments(errors) => errors
+                .into_iter()
+                .map(|e| (e.field, e.message.key))
+                .collect(),
+            other => panic!("expected invalid arguments, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn process_args_round_trip() {
+        let video = format!("{}/clip.mp4", root());
+        let raw = json!({
+            "videos": [video],
+            "output_dir": root(),
+            "preset": "fast",
+            "mode": "per-clip",
+            "clips": [{ "path": video, "tone_map": true }],
+        });
+        let args = process_args(raw, &presets()).unwrap();
+        assert_eq!(args.mode, BatchMode::PerClip);
+        assert!(args.clips[0].tone_map);
+
+        let serialized = serde_json::to_value(&args).unwrap();
+        let again = process_args(serialized.clone(), &presets()).unwrap();
+        assert_eq!(serde_json::to_value(&again).unwrap(), serialized);
+    }
+
+    #[test]
+    fn reports_every_bad_field() {
+        let raw = json!({
+            "videos": "foo",
+            "output_dir": "relative/out",
+            "preset": null,
+            "masks": 3,
+            "mode": "sideways",
+        });
+        assert_eq!(
+            errors(process_args(raw, &presets())),
+            [
+                ("videos".to_string(), "args.wrong_type".to_string()),
+                ("preset".to_string(), "args.required".to_string()),
+                ("masks".to_string(), "args.wrong_type".to_string()),
+                ("mode".to_string(), "args.wrong_type".to_string()),
+                ("output_dir".to_string(), "args.not_absolute".to_string()),
+            ]
+        );
+
+        let raw = json!({
+            "videos": [],
+            "output_dir": " ",
+            "preset": "ultra",
+        });
+        assert_eq!(
+            errors(process_args(raw, &presets())),
+            [
+                ("videos".to_string(), "args.empty".to_string()),
+                ("output_dir".to_string(), "args.empty".to_string()),
+                ("preset".to_string(), "args.unknown_preset".to_string()),
+            ]
+        );
+        let raw = json!({ "videos": [], "output_dir": root(), "preset": "ultra" });
+        let json = serde_json::to_value(process_args(raw, &presets()).unwrap_err()).unwrap();
+        assert_eq!(json["fields"][1]["options"], json!(["fast", "balanced"]));
+        assert!(json["fields"][0].get("options").is_none());
+        assert_eq!(
+            errors(process_args(json!(["clip.mp4"]), &presets())),
+            [(String::new(), "args.not_an_object".to_string())]
+        );
+    }
+
+    #[test]
+    fn field_errors_serialize_with_the_error() {
+        let raw = json!({ "videos": ["clip.mp4"], "output_dir": root(), "preset": "fast" });
+        let error = process_args(raw, &presets()).unwrap_err();
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["code"], "invalid_arguments");
+        assert_eq!(json["fields"][0]["field"], "videos[0]");
+        assert_eq!(json["fields"][0]["key"], "args.not_absolute");
+        assert_eq!(json["fields"][0]["params"]["path"], "clip.mp4");
+        assert_eq!(
+            error.to_string(),
+            "Invalid arguments: videos[0] must be an absolute path"
+        );
+    }
+
+    #[test]
+    fn settings_round_trip_and_fill_in_defaults() {
+        let current = AppSettings::default();
+        let saved = serde_json::to_value(&current).unwrap();
+        let loaded = settings(saved.clone(), &presets(), &current).unwrap();
+        assert_eq!(serde_json::to_value(&loaded).unwrap(), saved);
+
+        // Saved by a version without the newer settings
+        let older = json!({
+            "theme": "dark",
+            "defaultOutputDir": "",
+            "defaultPreset": "fast",
+            "recentProductions": [],
+        });
+        let loaded = settings(older, &presets(), &current).unwrap();
+        assert_eq!(loaded.appearance.theme, Theme::Dark);
+        assert_eq!(loaded.prefetch_concurrency, 3);
+
+        let raw = json!({
+            "theme": "dark",
+            "defaultPreset": "ultra",
+            "recentProductions": [],
+            "prefetchConcurrency": "four",
+        });
+        assert_eq!(
+            errors(settings(raw, &presets(), &current)),
+            [
+                (
+                    "prefetchConcurrency".to_string(),
+                    "args.wrong_type".to_string()
+                ),
+                (
+                    "defaultPreset".to_string(),
+                    "args.unknown_preset".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn settings_merge_over_the_current_ones() {
+        let current = AppSettings {
+            default_output_dir: root(),
+            recent_productions: vec![crate::settings::RecentProduction {
+                id: "p1".to_string(),
+                name: "Harbour".to_string(),
+                path: root(),
+                last_opened: String::new(),
+                tags: vec![],
+                notes: String::new(),
+                imported: false,
+                missing: false,
+                pinned: true,
+            }],
+            ffmpeg_path: Some("/opt/ffmpeg/bin/ffmpeg".to_string()),
+            ..Default::default()
+        };
+
+        // A stale copy neither drops recents nor reverts what it leaves out
+        let raw = json!({ "defaultPreset": "fast", "recentProductions": [] });
+        let merged = settings(raw, &presets(), &current).unwrap();
+        assert_eq!(merged.default_preset, "fast");
+        assert_eq!(merged.default_output_dir, root());
+        assert!(merged.recent_productions[0].pinned);
+
+        // Programs are set by their own commands; sent unchanged, they pass
+        let unchanged = json!({ "ffmpegPath": "/opt/ffmpeg/bin/ffmpeg", "postRunHooks": [] });
+        assert!(settings(unchanged, &presets(), &current).is_ok());
+        let raw = json!({
+            "ffmpegPath": "/tmp/evil",
+            "externalViewers": [{ "id": "v", "name": "v", "path": "/tmp/evil", "argsTemplate": "" }],
+            "postRunHooksEnabled": true,
+        });
+        assert_eq!(
+            errors(settings(raw, &presets(), &current)),
+            [
+                ("ffmpegPath".to_string(), "args.set_by_command".to_string()),
+                (
+                    "externalViewers".to_string(),
+                    "args.set_by_command".to_string()
+                ),
+                (
+                    "postRunHooksEnabled".to_string(),
+                    "args.set_by_command".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn appearance_is_checked_and_normalized() {
+        let with = |extra: Value| {
+            let mut raw = json!({
+                "defaultOutputDir": "",
+                "defaultPreset": "fast",
+                "recentProductions": [],
+            });
+            raw.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            settings(raw, &presets(), &AppSettings::default())
+        };
+
+        assert_eq!(
+            errors(with(json!({ "theme": "drak" }))),
+            [("theme".to_string(), "args.wrong_type".to_string())]
+        );
+        let appearance = json!({ "appearance": { "theme": "dark", "accentColor": "#3B82F6" } });
+        let loaded = with(appearance).unwrap();
+        assert_eq!(loaded.appearance.theme, Theme::Dark);
+        assert_eq!(loaded.appearance.accent_color.as_deref(), Some("#3b82f6"));
+        assert_eq!(
+            errors(with(json!({ "appearance": { "accentColor": "blue" } }))),
+            [(
+                "appearance.accentColor".to_string(),
+                "args.invalid".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+//! External Viewers
+//!
+//! Third-party programs the user configured to open artifacts with. Viewers are
+//! spawned directly, never through a shell: the argument template is split on
+//! whitespace and `{file}` is replaced by the artifact path.
+
+use crate::error::AppError;
+use crate::job_log::{self, app_line};
+use crate::messages::Message;
+use crate::path_policy::PathPolicy;
+use crate::platform::PathProvider;
+use crate::settings::{Persist, SettingsStore};
+use crate::undo::{self, Restore};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use tauri::{AppHandle, State};
+
+/// Placeholder replaced by the artifact path
+const FILE_PLACEHOLDER: &str = "{file}";
+
+/// Characters a shell would interpret; templates are not run through one, so they
+/// would reach the viewer literally and almost certainly not do what was intended
+pub const SHELL_METACHARACTERS: &[char] = &[
+    '|', '&', ';', '<', '>', '(', ')', '$', '`', '"', '\'', '*', '?', '!', '\n', '\r',
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExternalViewer {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    /// Whitespace-separated arguments; an empty template passes just the file
+    pub args_template: String,
+}
+
+/// List configured external viewers, in the order they were added
+#[tauri::command]
+pub async fn list_external_viewers(app: AppHandle) -> Result<Vec<ExternalViewer>, AppError> {
+    Ok(app.settings().external_viewers)
+}
+
+/// Add an external viewer
+#[tauri::command]
+pub async fn add_external_viewer(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    name: String,
+    path: String,
+    args_template: String,
+) -> Result<ExternalViewer, AppError> {
+    policy.check_existing(&path)?;
+    add(&app, name, path, args_template)
+}
+
+/// Replace the name, program and arguments of an external viewer
+#[tauri::command]
+pub async fn update_external_viewer(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    id: String,
+    name: String,
+    path: String,
+    args_template: String,
+) -> Result<ExternalViewer, AppError> {
+    policy.check_existing(&path)?;
+    let viewer = ExternalViewer {
+        id,
+        name,
+        path,
+        args_template,
+    };
+    update(&app, viewer)
+}
+
+/// Remove an external viewer
+#[tauri::command]
+pub async fn remove_external_viewer(app: AppHandle, id: String) -> Result<(), AppError> {
+    let (index, viewer) = remove(&app, &id)?;
+    let description = Message::new("undo.remove_external_viewer").with("name", &viewer.name);
+    undo::record(&app, description, Restore::Viewer { viewer, index });
+    Ok(())
+}
+
+/// Open an artifact in an external viewer, without waiting for it to exit
+#[tauri::command]
+pub async fn open_with_external_viewer(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    viewer_id: String,
+    artifact_path: String,
+) -> Result<(), AppError> {
+    policy.check_existing(&artifact_path)?;
+    open_with(&app, &app, &viewer_id, &artifact_path)
+}
+
+pub fn add(
+    store: &impl SettingsStore,
+    name: String,
+    path: String,
+    args_template: String,
+) -> Result<ExternalViewer, AppError> {
+    let viewer = ExternalViewer {
+        id: job_log::new_id("viewer"),
+        name,
+        path,
+        args_template,
+    };
+    validate(&viewer)?;
+    store.update_settings(Persist::Now, |app_settings| {
+        app_settings.external_viewers.push(viewer.clone());
+        Ok(viewer)
+    })
+}
+
+pub fn update(
+    store: &impl SettingsStore,
+    viewer: ExternalViewer,
+) -> Result<ExternalViewer, AppError> {
+    validate(&viewer)?;
+    store.update_settings(Persist::Now, |app_settings| {
+        let existing = app_settings
+            .external_viewers
+            .iter_mut()
+            .find(|v| v.id == viewer.id)
+            .ok_or_else(|| AppError::NotFound(format!("external viewer {}", viewer.id)))?;
+        *existing = viewer.clone();
+        Ok(viewer)
+    })
+}
+
+/// Remove a viewer, returning it and where it was in the list
+pub fn remove(store: &impl SettingsStore, id: &str) -> Result<(usize, ExternalViewer), AppError> {
+    store.update_settings(Persist::Now, |app_settings| {
+        let index = app_settings
+            .external_viewers
+            .iter()
+            .position(|v| v.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("external viewer {}", id)))?;
+        Ok((index, app_settings.external_viewers.remove(index)))
+    })
+}
+
+pub fn open_with(
+    store: &impl SettingsStore,
+    paths: &impl PathProvider,
+    viewer_id: &str,
+    artifact_path: &str,
+) -> Result<(), AppError> {
+    let viewer = store
+        .settings()
+        .external_viewers
+        .into_iter()
+        .find(|v| v.id == viewer_id)
+        .ok_or_else(|| AppError::NotFound(format!("external viewer {}", viewer_id)))?;
+    // The program may have been moved or uninstalled since it was configured
+    check_executable(Path::new(&viewer.path))?;
+
+    let args = build_args(&viewer.args_template, artifact_path)?;
+    let mut child = Command::new(&viewer.path)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| {
+            app_line(
+                paths,
+                &format!("Failed to launch {} ({}): {}", viewer.name, viewer.path, e),
+            );
+            AppError::Io(format!("Failed to launch {}: {}", viewer.name, e))
+        })?;
+    app_line(
+        paths,
+        &format!(
+            "Launched {} ({}) with {:?}, pid {}",
+            viewer.name,
+            viewer.path,
+            args,
+            child.id()
+        ),
+    );
+
+    // Reap the viewer when it exits so it does not linger as a zombie
+    std::thread::spawn(move || child.wait().ok());
+    Ok(())
+}
+
+/// Check a viewer before it is saved
+pub fn validate(viewer: &ExternalViewer) -> Result<(), AppError> {
+    if viewer.name.trim().is_empty() {
+        return Err(AppError::InvalidInput(
+            "External viewer needs a name".to_string(),
+        ));
+    }
+    check_executable(Path::new(&viewer.path))?;
+    build_args(&viewer.args_template, "").map(|_| ())
+}
+
+/// Split a template into arguments and substitute the artifact path
+pub fn build_args(template: &str, file: &str) -> Result<Vec<String>, AppError> {
+    if let Some(c) = template.chars().find(|c| SHELL_METACHARACTERS.contains(c)) {
+        return Err(AppError::InvalidInput(format!(
+            "Viewer arguments may not contain {:?}; they are passed to the program as-is, without a shell",
+            c
+        )));
+    }
+    if template.trim().is_empty() {
+        return Ok(vec![file.to_string()]);
+    }
+    if !template.contains(FILE_PLACEHOLDER) {
+        return Err(AppError::InvalidInput(format!(
+            "Viewer arguments must include {}",
+            FILE_PLACEHOLDER
+        )));
+    }
+    Ok(template
+        .split_whitespace()
+        .map(|arg| arg.replace(FILE_PLACEHOLDER, file))
+        .collect())
+}
+
+/// Check that `path` is a program the app can run without a shell
+pub fn check_executable(path: &Path) -> Result<(), AppError> {
+    let metadata =
+        std::fs::metadata(path).map_err(|_| AppError::NotFound(path.display().to_string()))?;
+    if metadata.is_file() && is_executable(path, &metadata) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidInput(format!(
+            "{} is not an executable program",
+            path.display()
+        )))
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(_path: &Path, metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+// Only programs Windows runs without cmd.exe; batch files would reintroduce a shell
+#[cfg(windows)]
+fn is_executable(path: &Path, _metadata: &std::fs::Metadata) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exe") || ext.eq_ignore_ascii_case("com"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::{MemorySettings, TempPaths};
+    use crate::settings::AppSettings;
+    use std::path::PathBuf;
+
+    #[cfg(unix)]
+    fn program(paths: &TempPaths, name: &str, executable: bool) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = paths.root().join(name);
+        std::fs::write(&path, b"#!/bin/sh\n").unwrap();
+        let mode = if executable { 0o755 } else { 0o644 };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn build_args_substitutes_file() {
+        assert_eq!(
+            build_args("--open {file} --fullscreen", "/p/output.ply").unwrap(),
+            vec!["--open", "/p/output.ply", "--fullscreen"]
+        );
+        assert_eq!(
+            build_args("--scene={file}", "/p/a b.ply").unwrap(),
+            vec!["--scene=/p/a b.ply"]
+        );
+        assert_eq!(
+            build_args(" ", "/p/output.ply").unwrap(),
+            vec!["/p/output.ply"]
+        );
+    }
+
+    #[test]
+    fn build_args_refuses_shell_syntax() {
+        for template in [
+            "{file} && rm -rf ~",
+            "{file} | tee log",
+            "\"{file}\"",
+            "$(id) {file}",
+        ] {
+            let err = build_args(template, "/p/output.ply").unwrap_err();
+            assert_eq!(err.code(), "invalid_input", "{}", template);
+        }
+        let err = build_args("--fullscreen", "/p/output.ply").unwrap_err();
+        assert_eq!(err.code(), "invalid_input");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_update_remove_viewers() {
+        let paths = TempPaths::new();
+        let store = MemorySettings::with(AppSettings::default());
+        let viewer_path = program(&paths, "viewer", true)
+            .to_string_lossy()
+            .to_string();
+
+        let viewer = add(
+            &store,
+            "SuperSplat".to_string(),
+            viewer_path.clone(),
+            String::new(),
+        )
+        .unwrap();
+        assert_eq!(store.settings().external_viewers, vec![viewer.clone()]);
+
+        let renamed = ExternalViewer {
+            name: "Viewer".to_string(),
+            args_template: "--file {file}".to_string(),
+            ..viewer.clone()
+        };
+        update(&store, renamed.clone()).unwrap();
+        assert_eq!(store.settings().external_viewers, vec![renamed]);
+
+        remove(&store, &viewer.id).unwrap();
+        assert!(store.settings().external_viewers.is_empty());
+        assert_eq!(remove(&store, &viewer.id).unwrap_err().code(), "not_found");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_refuses_non_executable_programs() {
+        let paths = TempPaths::new();
+        let store = MemorySettings::with(AppSettings::default());
+        let not_executable = program(&paths, "notes.txt", false);
+
+        let err = add(
+            &store,
+            "Notes".to_string(),
+            not_executable.to_string_lossy().to_string(),
+            String::new(),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), "invalid_input");
+
+        let missing = paths.root().join("missing").to_string_lossy().to_string();
+        let err = add(&store, "Missing".to_string(), missing, String::new()).unwrap_err();
+        assert_eq!(err.code(), "not_found");
+        assert!(store.settings().external_viewers.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn open_with_logs_launch() {
+        let paths = TempPaths::new();
+        let store = MemorySettings::with(AppSettings::default());
+        let viewer = add(
+            &store,
+            "True".to_string(),
+            "/bin/true".to_string(),
+            String::new(),
+        )
+        .unwrap();
+
+        open_with(&store, &paths, &viewer.id, "/p/output.ply").unwrap();
+        let log =
+            std::fs::read_to_string(paths.app_data_dir().unwrap().join("logs/app.log")).unwrap();
+        assert!(log.contains("Launched True (/bin/true) with [\"/p/output.ply\"]"));
+
+        let err = open_with(&store, &paths, "viewer-0", "/p/output.ply").unwrap_err();
+        assert_eq!(err.code(), "not_found");
+    }
+}
@@ -0,0 +1,277 @@
+//! Output Volume Watch
+//!
+//! External drives sleep or get unplugged mid-run, and a CLI writing to a
+//! drive that is gone fails at a random point with a half-written database.
+//! While a job runs, its output volume is probed every few seconds: the mount
+//! point must still be there and a small probe file beside the output must
+//! still be writable. When a probe fails the CLI is suspended at once, with
+//! the COLMAP and brush processes it started, and volume-disconnected is
+//! sent. If the volume comes back within the grace period, volume-reconnected
+//! offers resume_processing; otherwise the job fails with output_volume_lost.
+//! Nothing is probed between jobs, so an idle drive is free to sleep. Windows
+//! offers no supported way to suspend another process, so there the CLI keeps
+//! running while the volume is away.
+
+use crate::disk;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Written next to the output on every probe and removed when the job ends
+const PROBE_NAME: &str = ".gameview-volume-probe";
+
+/// How often resume_processing and cancellation are checked while paused
+const RESUME_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// Set by resume_processing, cleared whenever a volume disconnects
+static RESUME: AtomicBool = AtomicBool::new(false);
+
+/// Continue a job paused because its output volume disconnected, once the
+/// volume is back
+#[tauri::command]
+pub async fn resume_processing() -> Result<(), AppError> {
+    RESUME.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+pub fn resume_flag() -> &'static AtomicBool {
+    &RESUME
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+    pub probe_interval: Duration,
+    /// How long a disconnected volume has to come back
+    pub grace_period: Duration,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            probe_interval: Duration::from_secs(5),
+            grace_period: Duration::from_secs(120),
+        }
+    }
+}
+
+/// What the watch knows about the run, kept current by the job's sink
+#[derive(Debug, Default)]
+pub struct RunState {
+    /// The CLI process; None for the simulator, which is never suspended
+    pub pid: Mutex<Option<u32>>,
+    /// The last stage and percentage reported
+    pub progress: Mutex<Option<(String, f64)>>,
+    /// Size of the artifact being exported, while the CLI reports no progress
+    pub exported_bytes: Mutex<Option<u64>>,
+    /// The CLI is stopped by suspend until continue_run
+    pub suspended: AtomicBool,
+}
+
+/// Sent as volume-disconnected and volume-reconnected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolumeChange {
+    pub output_dir: String,
+    pub mount: String,
+    pub connected: bool,
+    /// The CLI was suspended, and waits for resume_processing
+    pub paused: bool,
+}
+
+/// How far the job got before its volume went away for good
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolumeLost {
+    pub stage: Option<String>,
+    pub progress: f64,
+}
+
+/// Watch the volume of `output_dir` until it has been gone for the grace
+/// period. Never returns while the volume stays, so it is meant to be raced
+/// against the run.
+pub async fn watch(
+    output_dir: &Path,
+    state: &RunState,
+    resume: &AtomicBool,
+    cancel: &AtomicBool,
+    timing: Timing,
+    notify: &mut (dyn FnMut(VolumeChange) + Send),
+) -> VolumeLost {
+    let Some(probe) = Probe::new(output_dir) else {
+        return std::future::pending().await;
+    };
+    // A directory that cannot be written to from the start is not a disconnect
+    if !probe.check() {
+        return std::future::pending().await;
+    }
+    let change = |connected, paused| VolumeChange {
+        output_dir: output_dir.to_string_lossy().to_string(),
+        mount: probe.mount.to_string_lossy().to_string(),
+        connected,
+        paused,
+    };
+
+    loop {
+        tokio::time::sleep(timing.probe_interval).await;
+        if probe.check() {
+            continue;
+        }
+
+        let paused = suspend(state);
+        resume.store(false, Ordering::SeqCst);
+        notify(change(false, paused));
+
+        let deadline = Instant::now() + timing.grace_period;
+        loop {
+            tokio::time::sleep(timing.probe_interval).await;
+            if probe.check() {
+                break;
+            }
+            if Instant::now() >= deadline {
+                let progress = state.progress.lock().unwrap().clone();
+                return VolumeLost {
+                    progress: progress.as_ref().map_or(0.0, |(_, p)| *p),
+                    stage: progress.map(|(stage, _)| stage),
+                };
+            }
+        }
+        notify(change(true, paused));
+
+        // Cancelling kills the suspended CLI, which ends the race
+        while paused && !resume.load(Ordering::SeqCst) && !cancel.load(Ordering::SeqCst) {
+            tokio::time::sleep(RESUME_POLL_INTERVAL).await;
+        }
+        if paused {
+            continue_run(state);
+        }
+    }
+}
+
+struct Probe {
+    mount: PathBuf,
+    /// The output directory, or the nearest ancestor there was when the job started
+    dir: PathBuf,
+}
+
+impl Probe {
+    fn new(output_dir: &Path) -> Option<Self> {
+        let dir = output_dir.ancestors().find(|p| p.is_dir())?.to_path_buf();
+        let mount = disk::volume_of(&dir).ok()?.mount;
+        Some(Self {
+            mount: PathBuf::from(mount),
+            dir,
+        })
+    }
+
+    fn check(&self) -> bool {
+        self.mount.is_dir()
+            && self.dir.is_dir()
+            && std::fs::write(self.dir.join(PROBE_NAME), b"").is_ok()
+    }
+}
+
+impl Drop for Probe {
+    fn drop(&mut self) {
+        std::fs::remove_file(self.dir.join(PROBE_NAME)).ok();
+    }
+}
+
+/// Stop the CLI where it is; false when it cannot be stopped
+pub fn suspend(state: &RunState) -> bool {
+    let suspended = match *state.pid.lock().unwrap() {
+        Some(pid) => signal(pid, true),
+        None => false,
+    };
+    if suspended {
+        state.suspended.store(true, Ordering::SeqCst);
+    }
+    suspended
+}
+
+pub fn continue_run(state: &RunState) {
+    if let Some(pid) = *state.pid.lock().unwrap() {
+        signal(pid, false);
+    }
+    state.suspended.store(false, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, stop: bool) -> bool {
+    let signal = if stop { libc::SIGSTOP } else { libc::SIGCONT };
+    // The CLI leads a process group of its own, so the tools it started
+    // stop writing too
+    // SAFETY: kill only sends a signal to the process group it names
+    unsafe { libc::kill(-(pid as libc::pid_t), signal) == 0 }
+}
+
+#[cfg(not(unix))]
+fn signal(_pid: u32, _stop: bool) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    fn timing() -> Timing {
+        Timing {
+            probe_interval: Duration::from_millis(10),
+            grace_period: Duration::from_millis(200),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_volume_that_stays_away_loses_the_job() {
+        let paths = TempPaths::new();
+        let output = paths.root().join("Drive/pier");
+        std::fs::create_dir_all(&output).unwrap();
+        let state = RunState::default();
+        *state.progress.lock().unwrap() = Some(("detecting_cameras".to_string(), 41.0));
+        let flag = AtomicBool::new(false);
+        let mut changes = vec![];
+        let mut notify = |change: VolumeChange| changes.push(change);
+
+        let unplug = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::remove_dir_all(paths.root().join("Drive")).unwrap();
+        };
+        let watch = watch(&output, &state, &flag, &flag, timing(), &mut notify);
+        let (lost, ()) = tokio::join!(watch, unplug);
+
+        assert_eq!(lost.stage.as_deref(), Some("detecting_cameras"));
+        assert_eq!(lost.progress, 41.0);
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].connected && !changes[0].paused);
+    }
+
+    #[tokio::test]
+    async fn a_volume_back_within_the_grace_period_is_watched_again() {
+        let paths = TempPaths::new();
+        let output = paths.root().join("Drive/pier");
+        std::fs::create_dir_all(&output).unwrap();
+        let state = RunState::default();
+        let flag = AtomicBool::new(false);
+        let mut changes = vec![];
+        let mut notify = |change: VolumeChange| changes.push(change);
+
+        let replug = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::remove_dir_all(&output).unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::create_dir_all(&output).unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+        let watch = watch(&output, &state, &flag, &flag, timing(), &mut notify);
+        tokio::select! {
+            _ = watch => panic!("the volume came back"),
+            () = replug => {}
+        }
+
+        let connected: Vec<bool> = changes.iter().map(|c| c.connected).collect();
+        assert_eq!(connected, [false, true]);
+        // The watch is gone with the job, and so is its probe file
+        assert!(!output.join(PROBE_NAME).exists());
+    }
+}
@@ -0,0 +1,140 @@
+//! VRAM Policy
+//!
+//! A preset that needs more VRAM than the GPU has fails, often an hour into
+//! training. Such a job is now refused before it is queued: each preset has a
+//! minimum, from the built-in table or from preset_min_vram_gb for presets of
+//! a team's own, and a GPU detected with less VRAM fails the request with
+//! InsufficientVram. A request carrying override runs anyway, and who
+//! overrode the policy, when, and with how much VRAM is written to its job log
+//! and history record for later audit.
+//!
+//! When no VRAM can be detected at all, as without nvidia-smi or on a
+//! unified-memory GPU, the job runs with a warning instead.
+
+use crate::gpu::GpuInfo;
+use crate::gpu_contention::format_mb;
+use crate::job_log::unix_timestamp;
+use crate::messages::Message;
+use serde::{Deserialize, Serialize};
+
+/// Largest minimum preset_min_vram_gb may set
+pub const MAX_MIN_VRAM_GB: u32 = 256;
+
+/// A job run despite its GPU having too little VRAM for its preset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VramOverride {
+    /// The account the app ran as
+    pub user: String,
+    pub at: u64,
+    pub gpu: String,
+    pub detected_mb: u64,
+    pub required_mb: u64,
+    pub preset: String,
+}
+
+impl VramOverride {
+    /// The line kept in the job log
+    pub fn describe(&self) -> String {
+        format!(
+            "VRAM policy overridden by {} at {}: {} has {} MiB, the {} preset needs {} MiB",
+            self.user, self.at, self.gpu, self.detected_mb, self.preset, self.required_mb
+        )
+    }
+}
+
+/// How a job's preset measures up to the GPU
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Enough,
+    /// Too little VRAM, but the request overrides the policy
+    Overridden(VramOverride),
+    /// No VRAM was detected; holds the warning to show
+    Undetected(Message),
+}
+
+/// Check `gpu` against the `required_mb` of `preset`; the message of the
+/// refusal when it has too little VRAM and the policy is not overridden
+pub fn check(
+    gpu: Option<&GpuInfo>,
+    preset: &str,
+    required_mb: u64,
+    override_policy: bool,
+) -> Result<Verdict, Message> {
+    let Some((gpu, detected_mb)) = gpu.and_then(|g| Some((g, g.vram_mb?))) else {
+        return Ok(Verdict::Undetected(
+            Message::new("vram.undetected")
+                .with("preset", preset)
+                .with("required", format_mb(required_mb)),
+        ));
+    };
+    if detected_mb >= required_mb {
+        return Ok(Verdict::Enough);
+    }
+    if !override_policy {
+        return Err(Message::new("error.insufficient_vram")
+            .with("gpu", &gpu.name)
+            .with("detected", format_mb(detected_mb))
+            .with("preset", preset)
+            .with("required", format_mb(required_mb)));
+    }
+    Ok(Verdict::Overridden(VramOverride {
+        user: current_user(),
+        at: unix_timestamp(),
+        gpu: gpu.name.clone(),
+        detected_mb,
+        required_mb,
+        preset: preset.to_string(),
+    }))
+}
+
+fn current_user() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|u| !u.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gpu(vram_mb: Option<u64>) -> GpuInfo {
+        GpuInfo {
+            name: "RTX 3060 Laptop".to_string(),
+            vram_mb,
+        }
+    }
+
+    #[test]
+    fn too_little_vram_is_refused_unless_overridden() {
+        let laptop = gpu(Some(6 * 1024));
+        assert_eq!(
+            check(Some(&laptop), "balanced", 6 * 1024, false),
+            Ok(Verdict::Enough)
+        );
+
+        let refused = check(Some(&laptop), "maximum", 12 * 1024, false).unwrap_err();
+        assert_eq!(refused.key, "error.insufficient_vram");
+        assert_eq!(refused.params["detected"], "6.0 GB");
+        assert_eq!(refused.params["required"], "12.0 GB");
+
+        let Ok(Verdict::Overridden(overridden)) = check(Some(&laptop), "maximum", 12 * 1024, true)
+        else {
+            panic!("expected the override to be recorded");
+        };
+        assert_eq!(overridden.detected_mb, 6 * 1024);
+        assert_eq!(overridden.required_mb, 12 * 1024);
+        assert!(!overridden.user.is_empty());
+    }
+
+    #[test]
+    fn undetected_vram_only_warns() {
+        for gpu in [None, Some(gpu(None))] {
+            let Ok(Verdict::Undetected(warning)) = check(gpu.as_ref(), "maximum", 12 * 1024, false)
+            else {
+                panic!("expected a warning");
+            };
+            assert_eq!(warning.key, "vram.undetected");
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Web Export
+//!
+//! The share server converts an artifact for the browser on the fly, but
+//! hosting it anywhere else needs the converted file on disk. Exporting reads
+//! the whole PLY, so a large one takes a while: it runs as a pending task,
+//! writing to a temp file that is renamed over the output once complete.
+
+use crate::conversion;
+use crate::error::AppError;
+use crate::job_log;
+use crate::path_policy::PathPolicy;
+use crate::pending_tasks::{self, PendingTask, TaskWork};
+use crate::scheduler::{self, Pool};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+use tauri::{AppHandle, State};
+
+/// Formats web viewers load
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebFormat {
+    Splat,
+    Spz,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebExport {
+    pub output_path: String,
+    pub format: WebFormat,
+    pub bytes: u64,
+    pub source_sha256: String,
+}
+
+/// Convert a splat PLY to a format web viewers load, written to `output`
+#[tauri::command]
+pub async fn export_web_artifact(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    input: String,
+    output: String,
+    format: WebFormat,
+) -> Result<WebExport, AppError> {
+    policy.check_existing(&input)?;
+    policy.check_target(&output)?;
+    run(app, input, output, format, None).await
+}
+
+/// Export as a pending task; `resumed` is the paused task it runs again
+pub async fn run(
+    app: AppHandle,
+    input: String,
+    output: String,
+    format: WebFormat,
+    resumed: Option<PendingTask>,
+) -> Result<WebExport, AppError> {
+    let task = match resumed {
+        Some(task) => task,
+        None => PendingTask::start(TaskWork::Conversion {
+            input: input.clone(),
+            output: output.clone(),
+            format,
+        })?,
+    };
+    if let Err(e) = pending_tasks::begin(&app, &task) {
+        job_log::app_line(&app, &format!("Failed to record a pending task: {}", e));
+    }
+
+    let (paths, task_id) = (app.clone(), task.task_id.clone());
+    let result = scheduler::run_blocking(Pool::CpuHeavy, move || {
+        export(
+            Path::new(&input),
+            Path::new(&output),
+            format,
+            &mut |sha256| pending_tasks::source_hashed(&paths, &task_id, sha256),
+        )
+    })
+    .await
+    .and_then(|result| result);
+    if let Err(e) = pending_tasks::finish(&app, &task.task_id) {
+        job_log::app_line(&app, &format!("Failed to clear a pending task: {}", e));
+    }
+    result
+}
+
+/// Convert `input` into `output` by way of its temp file. `source_hashed`
+/// hears the source's SHA-256 before anything is written, and stops the
+/// export by failing.
+pub fn export(
+    input: &Path,
+    output: &Path,
+    format: WebFormat,
+    source_hashed: &mut dyn FnMut(&str) -> Result<(), AppError>,
+) -> Result<WebExport, AppError> {
+    let ply = std::fs::read(input)?;
+    let source_sha256: String = Sha256::digest(&ply)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    source_hashed(&source_sha256)?;
+    let converted = match format {
+        WebFormat::Splat => conversion::ply_to_splat(&ply),
+        WebFormat::Spz => conversion::ply_to_spz(&ply),
+    }
+    .map_err(AppError::InvalidInput)?;
+    drop(ply);
+
+    let temp = pending_tasks::temp_path(output);
+    let written = std::fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(&converted)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp, output));
+    if let Err(e) = written {
+        std::fs::remove_file(&temp).ok();
+        return Err(e.into());
+    }
+    Ok(WebExport {
+        output_path: output.to_string_lossy().to_string(),
+        format,
+        bytes: converted.len() as u64,
+        source_sha256,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::tests::sample_ply;
+    use crate::platform::testing::TempPaths;
+
+    fn splat() -> [f32; 14] {
+        [
+            0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
+        ]
+    }
+
+    #[test]
+    fn exports_through_a_temp_file() {
+        let paths = TempPaths::new();
+        let input = paths.root().join("output.ply");
+        std::fs::write(&input, sample_ply(&[splat(), splat()])).unwrap();
+        let output = paths.root().join("web.splat");
+
+        let mut heard = None;
+        let export = export(&input, &output, WebFormat::Splat, &mut |sha256| {
+            heard = Some(sha256.to_string());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(export.bytes, 2 * conversion::SPLAT_RECORD_LEN as u64);
+        assert_eq!(std::fs::read(&output).unwrap().len() as u64, export.bytes);
+        assert_eq!(heard, Some(export.source_sha256));
+        assert!(!pending_tasks::temp_path(&output).exists());
+
+        let output = paths.root().join("web.spz");
+        export(&input, &output, WebFormat::Spz, &mut |_| Ok(())).unwrap();
+        // Gzip, as SPZ files are
+        assert_eq!(std::fs::read(&output).unwrap()[..2], [0x1f, 0x8b]);
+    }
+
+    #[test]
+    fn a_refused_source_writes_nothing() {
+        let paths = TempPaths::new();
+        let input = paths.root().join("output.ply");
+        std::fs::write(&input, sample_ply(&[splat()])).unwrap();
+        let output = paths.root().join("web.splat");
+
+        let refused = export(&input, &output, WebFormat::Splat, &mut |_| {
+            Err(AppError::InvalidInput("changed".to_string()))
+        });
+        assert!(refused.is_err());
+        assert!(!output.exists());
+        assert!(!pending_tasks::temp_path(&output).exists());
+    }
+}
@@ -0,0 +1,122 @@
+//! Window Event Routing
+//!
+//! Job events used to be broadcast to every window, so a viewer window showing
+//! an old production re-rendered on every progress tick of an unrelated job.
+//! Windows now say which jobs and productions they care about with
+//! subscribe_events, and each job event goes only to the windows whose filter
+//! matches the job it is about. The main window gets every event until it
+//! subscribes itself. A window that never subscribed still gets the lifecycle
+//! events, job-started and job-finished, which keep badge counts right. A
+//! window's subscription is forgotten when it is destroyed.
+
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// The window that gets every event unless it subscribes
+pub const MAIN_WINDOW: &str = "main";
+
+// Filters of the windows that subscribed, by window label
+static FILTERS: Mutex<BTreeMap<String, EventFilter>> = Mutex::new(BTreeMap::new());
+
+/// Which job events a window gets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EventFilter {
+    /// Every event, whichever job it is about
+    pub all: bool,
+    pub job_ids: Vec<String>,
+    /// Events of the jobs making these recent productions
+    pub production_ids: Vec<String>,
+}
+
+impl EventFilter {
+    fn firehose() -> EventFilter {
+        EventFilter {
+            all: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether an event about `job_id` of `production_id` passes; an event
+    /// about no known job passes only a filter taking all of them
+    pub fn matches(&self, job_id: Option<&str>, production_id: Option<&str>) -> bool {
+        self.all
+            || job_id.is_some_and(|id| self.job_ids.iter().any(|j| j == id))
+            || production_id.is_some_and(|id| self.production_ids.iter().any(|p| p == id))
+    }
+}
+
+/// The filter `label` gets events by; None for a window that only gets lifecycle events
+fn filter_of(label: &str) -> Option<EventFilter> {
+    let filters = FILTERS.lock().unwrap();
+    match filters.get(label) {
+        Some(filter) => Some(filter.clone()),
+        None => (label == MAIN_WINDOW).then(EventFilter::firehose),
+    }
+}
+
+/// Send the job event `name` to each window whose filter lets it through
+pub fn emit<S: Serialize + Clone>(
+    app: &AppHandle,
+    job_id: Option<&str>,
+    production_id: Option<&str>,
+    name: &str,
+    payload: S,
+) {
+    for label in app.webview_windows().keys() {
+        if filter_of(label).is_some_and(|f| f.matches(job_id, production_id)) {
+            app.emit_to(label.as_str(), name, payload.clone()).ok();
+        }
+    }
+}
+
+/// Drop the subscription of a destroyed window
+pub fn forget(label: &str) {
+    FILTERS.lock().unwrap().remove(label);
+}
+
+/// Receive only the job events `filter` lets through in the window `window_label`,
+/// replacing what it subscribed to before
+#[tauri::command]
+pub async fn subscribe_events(window_label: String, filter: EventFilter) -> Result<(), AppError> {
+    FILTERS.lock().unwrap().insert(window_label, filter);
+    Ok(())
+}
+
+/// Go back to the events the window got before it subscribed
+#[tauri::command]
+pub async fn unsubscribe_events(window_label: String) -> Result<(), AppError> {
+    forget(&window_label);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_get_what_they_subscribed_to() {
+        let viewer = "window-events-viewer";
+        assert_eq!(filter_of(viewer), None);
+        assert_eq!(filter_of(MAIN_WINDOW), Some(EventFilter::firehose()));
+
+        FILTERS.lock().unwrap().insert(
+            viewer.to_string(),
+            EventFilter {
+                production_ids: vec!["harbour".to_string()],
+                ..Default::default()
+            },
+        );
+        let filter = filter_of(viewer).unwrap();
+        assert!(filter.matches(Some("job-1"), Some("harbour")));
+        assert!(!filter.matches(Some("job-2"), Some("studio")));
+        assert!(!filter.matches(None, None));
+        assert!(EventFilter::firehose().matches(None, None));
+
+        forget(viewer);
+        assert_eq!(filter_of(viewer), None);
+    }
+}
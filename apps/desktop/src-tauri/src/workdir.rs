@@ -0,0 +1,161 @@
+//! Job Working Directories
+//!
+//! Jobs writing to sibling productions under one root used to collide in the
+//! CLI's default temp locations, and a failed job left its half-written files
+//! among the production's results. Every job now gets a directory of its own,
+//! <output_dir>/.gv/job-<id>: the CLI writes its results to output/ and its
+//! temporary files to tmp/, which it is pointed at through TMPDIR, TEMP and
+//! TMP and, when it supports it, --work-dir. Checkpoints and partially
+//! exported artifacts therefore stay there too. Only when the job succeeds are
+//! the results moved into the production, on the same volume so each is a
+//! rename; whatever ended the job, the directory is deleted afterwards. Ones
+//! left by a crash are deleted when the next job writes to the production.
+
+use crate::jobs;
+use crate::runner::CommandSpec;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Directory inside the production that holds the jobs' working directories
+pub const DIR_NAME: &str = ".gv";
+
+const JOB_PREFIX: &str = "job-";
+
+/// Deleted with everything in it when dropped
+pub struct WorkDir {
+    dir: PathBuf,
+}
+
+impl WorkDir {
+    /// Create the working directory of `job_id` in `output_dir`
+    pub fn create(output_dir: &Path, job_id: &str) -> io::Result<WorkDir> {
+        remove_stale(output_dir);
+        let work = WorkDir {
+            dir: dir(output_dir, job_id),
+        };
+        std::fs::create_dir_all(work.output())?;
+        std::fs::create_dir_all(work.temp())?;
+        Ok(work)
+    }
+
+    /// Where the CLI writes its results
+    pub fn output(&self) -> PathBuf {
+        self.dir.join("output")
+    }
+
+    /// Where the CLI keeps its temporary files
+    pub fn temp(&self) -> PathBuf {
+        self.dir.join("tmp")
+    }
+
+    /// Point the temporary files of `spec` here
+    pub fn apply(&self, spec: &mut CommandSpec) {
+        let temp = self.temp().to_string_lossy().to_string();
+        for var in ["TMPDIR", "TEMP", "TMP"] {
+            spec.env.insert(var.to_string(), temp.clone());
+        }
+    }
+
+    /// Move the results into `output_dir`, replacing earlier ones of the same
+    /// name, except those in `skip`; returns the names moved
+    pub fn publish(&self, output_dir: &Path, skip: &[&str]) -> io::Result<Vec<String>> {
+        let mut names: Vec<String> = std::fs::read_dir(self.output())?
+            .map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+            .collect::<io::Result<_>>()?;
+        names.retain(|name| !skip.contains(&name.as_str()));
+        names.sort();
+        for name in &names {
+            self.publish_one(name, output_dir)?;
+        }
+        Ok(names)
+    }
+
+    /// Move one result into `output_dir`, returning where it now is
+    pub fn publish_one(&self, name: &str, output_dir: &Path) -> io::Result<PathBuf> {
+        let target = output_dir.join(name);
+        // rename replaces a file but not a directory
+        if target.is_dir() {
+            std::fs::remove_dir_all(&target)?;
+        }
+        std::fs::rename(self.output().join(name), &target)?;
+        Ok(target)
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+        // Only succeeds once no other job's directory is left
+        if let Some(parent) = self.dir.parent() {
+            std::fs::remove_dir(parent).ok();
+        }
+    }
+}
+
+/// The working directory of `job_id`, whether or not it exists
+pub fn dir(output_dir: &Path, job_id: &str) -> PathBuf {
+    output_dir
+        .join(DIR_NAME)
+        .join(format!("{}{}", JOB_PREFIX, job_id))
+}
+
+/// Where a job's CLI writes its results while it runs
+pub fn output_of(output_dir: &Path, job_id: &str) -> PathBuf {
+    dir(output_dir, job_id).join("output")
+}
+
+/// Delete the working directories of jobs that are no longer running
+fn remove_stale(output_dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(output_dir.join(DIR_NAME)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(job_id) = name.strip_prefix(JOB_PREFIX) else {
+            continue;
+        };
+        if jobs::output_dir(job_id).is_none() {
+            std::fs::remove_dir_all(entry.path()).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[test]
+    fn results_replace_earlier_ones_and_the_directory_goes_away() {
+        let paths = TempPaths::new();
+        let output_dir = paths.root().join("production");
+        std::fs::create_dir_all(output_dir.join("colmap")).unwrap();
+        std::fs::write(output_dir.join("colmap").join("old.bin"), b"old").unwrap();
+        // Left by a job that crashed, and one still running
+        std::fs::create_dir_all(dir(&output_dir, "crashed")).unwrap();
+        let _running = jobs::register("workdir-running", &output_dir.to_string_lossy(), None);
+        std::fs::create_dir_all(dir(&output_dir, "workdir-running")).unwrap();
+
+        let work = WorkDir::create(&output_dir, "workdir-job").unwrap();
+        assert!(!dir(&output_dir, "crashed").exists());
+        assert!(dir(&output_dir, "workdir-running").exists());
+        std::fs::create_dir_all(work.output().join("colmap")).unwrap();
+        std::fs::write(work.output().join("colmap").join("new.bin"), b"new").unwrap();
+        std::fs::write(work.output().join("output.ply"), b"ply").unwrap();
+        std::fs::create_dir_all(work.output().join("checkpoints")).unwrap();
+
+        let moved = work.publish(&output_dir, &["checkpoints"]).unwrap();
+        assert_eq!(moved, ["colmap", "output.ply"]);
+        assert!(output_dir.join("colmap").join("new.bin").is_file());
+        assert!(!output_dir.join("colmap").join("old.bin").exists());
+        assert!(!output_dir.join("checkpoints").exists());
+
+        let mut spec = CommandSpec::new("gvcore-cli", vec![]);
+        work.apply(&mut spec);
+        assert_eq!(spec.env["TMPDIR"], work.temp().to_string_lossy());
+
+        drop(work);
+        assert!(!dir(&output_dir, "workdir-job").exists());
+        assert!(output_dir.join(DIR_NAME).exists());
+    }
+}
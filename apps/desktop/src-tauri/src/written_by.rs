@@ -0,0 +1,335 @@
+//! File Versions
+//!
+//! A sidecar, history or project written by a newer version used to fail in
+//! an older one with a bare serde error. Every file format the app owns now
+//! carries a written_by header naming the app version that wrote it and the
+//! schema version of its format, set on every write. A file that does not
+//! parse reports the version that wrote it, and get_file_compatibility lets
+//! the frontend explain a newer file before opening it. Files of versions
+//! from before the header read as they always did.
+
+use crate::error::AppError;
+use crate::messages::Message;
+use crate::path_policy::{resolve_app_data, PathPolicy};
+use crate::platform::PathProvider;
+use crate::{history, queue, settings, sidecar};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, State};
+
+/// Version of the app writing files now
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    Settings,
+    History,
+    Queue,
+    Sidecar,
+    /// Written by the frontend, which sets the header itself
+    Project,
+}
+
+impl FileFormat {
+    /// Schema version of the format as this version writes it; raised when a
+    /// change would trip up an older version reading it
+    pub fn schema_version(self) -> u32 {
+        match self {
+            FileFormat::Settings => 1,
+            FileFormat::History => 1,
+            FileFormat::Queue => 1,
+            FileFormat::Sidecar => 1,
+            // PROJECT_SCHEMA_VERSION in the frontend
+            FileFormat::Project => 1,
+        }
+    }
+
+    /// The format of a file, by its name
+    pub fn of(path: &Path) -> Option<FileFormat> {
+        let name = path.file_name()?.to_string_lossy();
+        match name.as_ref() {
+            settings::FILE_NAME => Some(FileFormat::Settings),
+            history::HISTORY_FILE => Some(FileFormat::History),
+            queue::FILE_NAME => Some(FileFormat::Queue),
+            sidecar::SIDECAR_NAME => Some(FileFormat::Sidecar),
+            _ if path.extension().is_some_and(|e| e == "gvproj") => Some(FileFormat::Project),
+            _ => None,
+        }
+    }
+}
+
+/// The header naming what wrote a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WrittenBy {
+    pub app_version: String,
+    pub schema_version: u32,
+    /// Version of the frontend, in files it writes itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frontend_version: Option<String>,
+}
+
+impl WrittenBy {
+    /// The header this version writes into a file of `format`
+    pub fn current(format: FileFormat) -> WrittenBy {
+        WrittenBy {
+            app_version: APP_VERSION.to_string(),
+            schema_version: format.schema_version(),
+            frontend_version: None,
+        }
+    }
+}
+
+/// A file's content with its header ahead of the content's own fields
+#[derive(Serialize)]
+pub struct Stamped<'a, T: ?Sized> {
+    written_by: WrittenBy,
+    #[serde(flatten)]
+    content: &'a T,
+}
+
+/// `content`, which serializes as an object, with the header of `format`
+pub fn stamp<T: ?Sized>(format: FileFormat, content: &T) -> Stamped<'_, T> {
+    Stamped {
+        written_by: WrittenBy::current(format),
+        content,
+    }
+}
+
+#[derive(Deserialize)]
+struct Header {
+    written_by: WrittenBy,
+}
+
+/// The header of a JSON file, or of the first line of a JSON lines file; None
+/// when it has none
+pub fn header(content: &str) -> Option<WrittenBy> {
+    let first_line = content.lines().next().unwrap_or_default();
+    [content, first_line]
+        .into_iter()
+        .find_map(|json| serde_json::from_str::<Header>(json).ok())
+        .map(|header| header.written_by)
+}
+
+/// `error` from reading `content`, with the version that wrote it when that
+/// was another
+pub fn explain(content: &str, error: impl fmt::Display) -> String {
+    match header(content) {
+        Some(written_by) if written_by.app_version != APP_VERSION => {
+            let newer = compare_versions(&written_by.app_version, APP_VERSION) == Ordering::Greater;
+            format!(
+                "{} (written by Game View {}{}, this is {})",
+                error,
+                written_by.app_version,
+                if newer { ", a newer version" } else { "" },
+                APP_VERSION
+            )
+        }
+        _ => error.to_string(),
+    }
+}
+
+/// Order of two versions like "1.4.2", compared part by part as numbers
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .split(['.', '-', '+'])
+            .take(3)
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    parts(a).cmp(&parts(b))
+}
+
+/// How the version that wrote a file relates to this one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionRelation {
+    Same,
+    Older,
+    Newer,
+    /// Written before files named their version
+    Unrecorded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileCompatibility {
+    pub path: String,
+    pub format: FileFormat,
+    /// The file's header; None for files of versions from before it
+    pub written_by: Option<WrittenBy>,
+    /// The header this version would write
+    pub current: WrittenBy,
+    pub relation: VersionRelation,
+    /// Its schema is one this version reads
+    pub readable: bool,
+    /// What to tell the user, for files of a newer version
+    pub message: Option<Message>,
+}
+
+/// The version a file was written by, and whether this version can read it
+#[tauri::command]
+pub async fn get_file_compatibility(
+    app: AppHandle,
+    policy: State<'_, PathPolicy>,
+    path: String,
+) -> Result<FileCompatibility, AppError> {
+    let format = FileFormat::of(Path::new(&path))
+        .ok_or_else(|| AppError::InvalidInput(format!("{} is not a Game View file", path)))?;
+    let path = allowed(&app, &policy, &path)?;
+    let content = std::fs::read_to_string(&path)?;
+    Ok(compatibility(&path, format, &content))
+}
+
+/// A file under app_data must resolve inside it; anything else is the user's
+/// and must be somewhere the path policy allows
+fn allowed(
+    paths: &impl PathProvider,
+    policy: &PathPolicy,
+    path: &str,
+) -> Result<PathBuf, AppError> {
+    let app_data = paths.app_data_dir()?;
+    match Path::new(path).strip_prefix(&app_data) {
+        Ok(relative) => resolve_app_data(&app_data, &relative.to_string_lossy()),
+        Err(_) => {
+            policy.check_existing(path)?;
+            Ok(PathBuf::from(path))
+        }
+    }
+}
+
+fn compatibility(path: &Path, format: FileFormat, content: &str) -> FileCompatibility {
+    let written_by = header(content);
+    let current = WrittenBy::current(format);
+    let (relation, readable) = match &written_by {
+        None => (VersionRelation::Unrecorded, true),
+        Some(w) => {
+            let relation = match compare_versions(&w.app_version, &current.app_version) {
+                Ordering::Less => VersionRelation::Older,
+                Ordering::Equal => VersionRelation::Same,
+                Ordering::Greater => VersionRelation::Newer,
+            };
+            (relation, w.schema_version <= current.schema_version)
+        }
+    };
+    let message = written_by
+        .as_ref()
+        .filter(|_| relation == VersionRelation::Newer || !readable)
+        .map(|w| {
+            let key = if readable {
+                "compat.newer_version"
+            } else {
+                "compat.newer_schema"
+            };
+            Message::new(key)
+                .with(
+                    "file",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                )
+                .with("version", &w.app_version)
+                .with("current", &current.app_version)
+        });
+    FileCompatibility {
+        path: path.to_string_lossy().to_string(),
+        format,
+        written_by,
+        current,
+        relation,
+        readable,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::testing::TempPaths;
+
+    #[derive(Serialize, Deserialize)]
+    struct Content {
+        name: String,
+    }
+
+    #[test]
+    fn stamped_files_name_their_version_and_legacy_ones_still_read() {
+        let content = Content {
+            name: "pier".to_string(),
+        };
+        let written = serde_json::to_string(&stamp(FileFormat::Sidecar, &content)).unwrap();
+        assert_eq!(
+            header(&written),
+            Some(WrittenBy::current(FileFormat::Sidecar))
+        );
+        // Readers ignore the header as they ignore any field they do not know
+        let read: Content = serde_json::from_str(&written).unwrap();
+        assert_eq!(read.name, "pier");
+
+        let legacy = r#"{"name":"pier"}"#;
+        assert_eq!(header(legacy), None);
+        assert_eq!(explain(legacy, "bad"), "bad");
+        let compat = compatibility(Path::new("production.gvmeta"), FileFormat::Sidecar, legacy);
+        assert_eq!(compat.relation, VersionRelation::Unrecorded);
+        assert!(compat.readable && compat.message.is_none());
+
+        let lines = concat!(
+            r#"{"written_by":{"app_version":"1.0.0","schema_version":1}}"#,
+            "\n",
+            r#"{"jobId":"job-1"}"#,
+            "\n"
+        );
+        assert_eq!(header(lines).unwrap().app_version, "1.0.0");
+    }
+
+    #[test]
+    fn files_of_newer_versions_are_explained() {
+        let newer = r#"{"written_by":{"app_version":"99.1.0","schema_version":7},"name":3}"#;
+        let compat = compatibility(Path::new("pier.gvproj"), FileFormat::Project, newer);
+        assert_eq!(compat.relation, VersionRelation::Newer);
+        assert!(!compat.readable);
+        let message = compat.message.unwrap();
+        assert_eq!(message.key, "compat.newer_schema");
+        assert_eq!(message.params["version"], "99.1.0");
+
+        let error = serde_json::from_str::<Content>(newer).unwrap_err();
+        let explained = explain(newer, error);
+        assert!(explained.contains("written by Game View 99.1.0, a newer version"));
+
+        assert_eq!(compare_versions("1.10.0", "1.9.3"), Ordering::Greater);
+        assert_eq!(compare_versions("2.0.0-beta.1", "2.0.0"), Ordering::Equal);
+        assert_eq!(
+            FileFormat::of(Path::new("/data/profiles/default/settings.json")),
+            Some(FileFormat::Settings)
+        );
+        assert_eq!(FileFormat::of(Path::new("/data/notes.txt")), None);
+    }
+
+    #[test]
+    fn only_app_data_and_allowed_paths_are_read() {
+        let paths = TempPaths::new();
+        let policy = PathPolicy::default();
+        let app_data = paths.app_data_dir().unwrap();
+        let history = app_data.join(history::HISTORY_FILE);
+        assert_eq!(
+            allowed(&paths, &policy, &history.to_string_lossy()).unwrap(),
+            history
+        );
+        let escaping = app_data.join("..").join(history::HISTORY_FILE);
+        assert!(matches!(
+            allowed(&paths, &policy, &escaping.to_string_lossy()),
+            Err(AppError::PathNotAllowed(_))
+        ));
+
+        let production = paths.root().join("production");
+        std::fs::create_dir_all(&production).unwrap();
+        let sidecar = production.join(sidecar::SIDECAR_NAME);
+        std::fs::write(&sidecar, "{}").unwrap();
+        assert!(matches!(
+            allowed(&paths, &policy, &sidecar.to_string_lossy()),
+            Err(AppError::PathNotAllowed(_))
+        ));
+        policy.allow(&production);
+        assert!(allowed(&paths, &policy, &sidecar.to_string_lossy()).is_ok());
+    }
+}
@@ -0,0 +1,71 @@
+//! Mock gvcore-cli
+//!
+//! Replays a script of stdout/stderr output for the process runner integration
+//! tests. The script is passed with `--script <path>` (or MOCK_CLI_SCRIPT) and
+//! contains one directive per line:
+//!
+//!   out <text>        print a stdout line
+//!   out-partial <text> print to stdout without a trailing newline
+//!   out-repeat <n> <text> print one stdout line of <text> repeated n times,
+//!                     written in pieces so the mock itself stays small
+//!   err <text>        print a stderr line
+//!   sleep <ms>        pause
+//!   ignore-sigterm    keep running when sent SIGTERM
+//!   exit <code>       exit immediately with the given code
+
+use std::io::Write;
+use std::time::Duration;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let script_path = args
+        .iter()
+        .position(|a| a == "--script")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| std::env::var("MOCK_CLI_SCRIPT").ok())
+        .expect("mock-cli needs --script <path> or MOCK_CLI_SCRIPT");
+    let script = std::fs::read_to_string(&script_path).expect("failed to read script");
+
+    let mut stdout = std::io::stdout();
+    let mut stderr = std::io::stderr();
+
+    for line in script.lines() {
+        let (directive, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match directive {
+            "out" => {
+                writeln!(stdout, "{}", rest).ok();
+                stdout.flush().ok();
+            }
+            "out-partial" => {
+                write!(stdout, "{}", rest).ok();
+                stdout.flush().ok();
+            }
+            "out-repeat" => {
+                let (count, text) = rest.split_once(' ').unwrap_or((rest, ""));
+                for _ in 0..count.parse::<usize>().unwrap_or(0) {
+                    stdout.write_all(text.as_bytes()).ok();
+                }
+                writeln!(stdout).ok();
+                stdout.flush().ok();
+            }
+            "err" => {
+                writeln!(stderr, "{}", rest).ok();
+            }
+            "sleep" => std::thread::sleep(Duration::from_millis(rest.parse().unwrap_or(0))),
+            "ignore-sigterm" => ignore_sigterm(),
+            "exit" => std::process::exit(rest.parse().unwrap_or(1)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(unix)]
+fn ignore_sigterm() {
+    // SAFETY: installing SIG_IGN for SIGTERM has no preconditions
+    unsafe {
+        libc::signal(libc::SIGTERM, libc::SIG_IGN);
+    }
+}
+
+#[cfg(not(unix))]
+fn ignore_sigterm() {}
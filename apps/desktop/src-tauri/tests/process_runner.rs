@@ -0,0 +1,252 @@
+//! Integration tests for the process runner against the mock CLI example binary.
+
+use gameview_desktop_lib::job_events::{self, BusSink, JobEvent};
+use gameview_desktop_lib::runner::{
+    self, CliWarning, CommandSpec, EventSink, ProcessProgress, RunError, Source,
+};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct RecordingSink {
+    lines: Vec<String>,
+    progress: Vec<ProcessProgress>,
+    warnings: Vec<CliWarning>,
+}
+
+impl EventSink for RecordingSink {
+    fn line(&mut self, line: &str) {
+        self.lines.push(line.to_string());
+    }
+
+    fn progress(&mut self, progress: &ProcessProgress) {
+        self.progress.push(progress.clone());
+    }
+
+    fn warning(&mut self, warning: &CliWarning) {
+        self.warnings.push(warning.clone());
+    }
+}
+
+// Examples are built next to the test binaries' deps directory
+fn mock_cli() -> String {
+    let mut dir = std::env::current_exe().unwrap();
+    dir.pop();
+    if dir.ends_with("deps") {
+        dir.pop();
+    }
+    dir.join("examples")
+        .join(format!("mock-cli{}", std::env::consts::EXE_SUFFIX))
+        .to_string_lossy()
+        .to_string()
+}
+
+fn fixture(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+        .to_string_lossy()
+        .to_string()
+}
+
+fn source(script: &str) -> Source {
+    Source::Cli(CommandSpec::new(
+        mock_cli(),
+        vec!["--script".to_string(), fixture(script)],
+    ))
+}
+
+#[tokio::test]
+async fn parses_and_maps_progress_stages() {
+    let mut sink = RecordingSink::default();
+    let cancel = AtomicBool::new(false);
+
+    let outcome = runner::run(source("progress.txt"), &mut sink, &cancel)
+        .await
+        .unwrap();
+
+    assert!(outcome.success);
+    assert_eq!(outcome.exit_code, Some(0));
+    assert_eq!(sink.lines.len(), 6);
+    let stages: Vec<&str> = sink.progress.iter().map(|p| p.stage.as_str()).collect();
+    assert_eq!(
+        stages,
+        [
+            "extracting_frames",
+            "detecting_cameras",
+            "training_splats",
+            "exporting",
+            "complete"
+        ]
+    );
+    assert_eq!(sink.progress[3].progress, 95.0);
+}
+
+#[tokio::test]
+async fn progress_reaches_bus_subscribers_as_processing_progress_payloads() {
+    let mut events = job_events::subscribe();
+    let cancel = AtomicBool::new(false);
+
+    runner::run(source("progress.txt"), &mut BusSink, &cancel)
+        .await
+        .unwrap();
+
+    let mut payloads = vec![];
+    while let Some(routed) = events.try_recv() {
+        if let JobEvent::Progress(progress) = routed.event {
+            payloads.push(serde_json::to_value(job_events::frontend_progress(&progress)).unwrap());
+        }
+    }
+    assert_eq!(payloads.len(), 5);
+    assert_eq!(
+        payloads[1],
+        json!({
+            "stage": "detecting_cameras",
+            "progress": 20.0,
+            "message": null,
+            "raw": "Running Structure from Motion...",
+            "current_item": null,
+            "items": null,
+        })
+    );
+}
+
+#[tokio::test]
+async fn typed_lines_are_parsed_and_unknown_types_passed_through() {
+    let mut sink = RecordingSink::default();
+    let cancel = AtomicBool::new(false);
+
+    let outcome = runner::run(source("typed_messages.txt"), &mut sink, &cancel)
+        .await
+        .unwrap();
+
+    assert_eq!(sink.lines.len(), 8);
+    // Named but not yet measured
+    assert_eq!(
+        json!(outcome.artifacts),
+        json!([
+            {
+                "kind": "ply",
+                "path": "export/20260301-1200/splat.ply",
+                "bytes": 0,
+                "sha256": null,
+            },
+            {
+                "kind": "spz",
+                "path": "export/20260301-1200/splat.web",
+                "bytes": 0,
+                "sha256": null,
+            },
+        ])
+    );
+    assert_eq!(
+        sink.warnings,
+        [CliWarning {
+            code: "deprecated-flag".to_string(),
+            message: "--preset fast is deprecated, use --preset draft".to_string(),
+        }]
+    );
+    // The unknown type is not mistaken for progress despite its text
+    let stages: Vec<&str> = sink.progress.iter().map(|p| p.stage.as_str()).collect();
+    assert_eq!(stages, ["detecting_cameras", "complete"]);
+    assert_eq!(
+        sink.progress[0].raw.as_deref(),
+        Some("Running Structure from Motion...")
+    );
+}
+
+#[tokio::test]
+async fn oversized_lines_are_cut_and_the_job_carries_on() {
+    let mut sink = RecordingSink::default();
+    let cancel = AtomicBool::new(false);
+
+    // The mock prints one 14 MB line between two progress lines
+    let outcome = runner::run(source("oversized_line.txt"), &mut sink, &cancel)
+        .await
+        .unwrap();
+
+    assert!(outcome.success);
+    assert_eq!(sink.lines.len(), 4);
+    let cut = &sink.lines[1];
+    assert_eq!(
+        cut.len(),
+        runner::MAX_LINE_BYTES + runner::TRUNCATED_MARKER.len()
+    );
+    assert!(cut.ends_with(runner::TRUNCATED_MARKER));
+    // A cut line is not parsed, though it starts like a typed message
+    assert!(sink.warnings.is_empty());
+    let stages: Vec<&str> = sink.progress.iter().map(|p| p.stage.as_str()).collect();
+    assert_eq!(stages, ["detecting_cameras", "training_splats", "complete"]);
+}
+
+#[tokio::test]
+async fn captures_stderr_and_maps_exit_code() {
+    let mut sink = RecordingSink::default();
+    let cancel = AtomicBool::new(false);
+
+    let outcome = runner::run(source("stderr_failure.txt"), &mut sink, &cancel)
+        .await
+        .unwrap();
+
+    assert!(!outcome.success);
+    assert_eq!(outcome.exit_code, Some(3));
+    assert_eq!(outcome.stderr, "COLMAP: no images registered\naborting\n");
+}
+
+#[tokio::test]
+async fn delivers_final_line_without_newline() {
+    let mut sink = RecordingSink::default();
+    let cancel = AtomicBool::new(false);
+
+    runner::run(source("partial_line.txt"), &mut sink, &cancel)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        sink.lines.last().map(String::as_str),
+        Some("[brush] 40% - Training without newline")
+    );
+    assert_eq!(sink.progress.len(), 2);
+}
+
+#[tokio::test]
+async fn cancels_silent_process_ignoring_sigterm_promptly() {
+    let mut sink = RecordingSink::default();
+    let cancel = Arc::new(AtomicBool::new(false));
+
+    let trigger = cancel.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        trigger.store(true, Ordering::SeqCst);
+    });
+
+    let started = Instant::now();
+    let result = runner::run(source("hang_ignoring_sigterm.txt"), &mut sink, &cancel).await;
+
+    assert_eq!(result.unwrap_err(), RunError::Cancelled);
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn reports_spawn_failure() {
+    let mut sink = RecordingSink::default();
+    let cancel = AtomicBool::new(false);
+
+    let result = runner::run(
+        Source::Cli(CommandSpec::new("/nonexistent/gvcore-cli", vec![])),
+        &mut sink,
+        &cancel,
+    )
+    .await;
+
+    assert!(matches!(
+        result,
+        Err(RunError::Spawn {
+            os_code: Some(_),
+            ..
+        })
+    ));
+}
@@ -4,44 +4,79 @@
  */
 
 import { invoke } from '@tauri-apps/api/core';
+import { getVersion } from '@tauri-apps/api/app';
 import { open, save } from '@tauri-apps/plugin-dialog';
 import { readTextFile, writeTextFile } from '@tauri-apps/plugin-fs';
 import { listen, UnlistenFn } from '@tauri-apps/api/event';
 import { useCallback, useEffect, useState } from 'react';
-import type { ProcessingProgress, AppSettings, QualityPreset, GVProject } from '@gameview/types';
+import type {
+  ProcessingProgress,
+  AppSettings,
+  QualityPreset,
+  GVProject,
+  RecentProduction,
+  ExternalViewer,
+  CaptureType,
+  TrainingOptions,
+  DeviceIntrinsics,
+  Theme,
+  RetentionAction,
+  WrittenBy,
+  PostRunHook,
+} from '@gameview/types';
+
+/** The frontend's package version, set by Vite */
+declare const __FRONTEND_VERSION__: string;
 
 // ===== File Dialogs =====
 
+// Media and output locations are picked through the backend so it can allow
+// later commands to use them; paths from elsewhere are rejected.
+
 /**
  * Open file dialog to pick video files
  */
 export async function pickVideos(): Promise<string[]> {
-  const selected = await open({
-    multiple: true,
-    filters: [
-      {
-        name: 'Video Files',
-        extensions: ['mp4', 'mov', 'avi', 'mkv', 'webm', 'mts', 'm2ts'],
-      },
-    ],
-    title: 'Select Video Files',
-  });
+  return invoke<string[]>('pick_videos');
+}
 
-  if (!selected) return [];
-  return Array.isArray(selected) ? selected : [selected];
+/**
+ * Open directory dialog to pick output folder. With the clips it is for, a
+ * folder holding them, inside them or in the app's data is refused with
+ * unsafe_output_location, or only warned about per unsafeOutputLocations.
+ * A folder that cannot be written to is refused with output_not_writable,
+ * whose `fallback` names the default output directory when that is writable.
+ */
+export async function pickOutputDirectory(videos?: string[]): Promise<string | null> {
+  return invoke<string | null>('pick_output_directory', { videos });
 }
 
 /**
- * Open directory dialog to pick output folder
+ * Open file dialog to pick a program, for setToolPath or a post-run hook
  */
-export async function pickOutputDirectory(): Promise<string | null> {
-  const selected = await open({
-    directory: true,
-    multiple: false,
-    title: 'Select Output Directory',
-  });
+export async function pickToolExecutable(): Promise<string | null> {
+  return invoke<string | null>('pick_tool_executable');
+}
 
-  return selected as string | null;
+/**
+ * Output directories let through despite holding or sitting inside an input
+ * or the app's data, when unsafeOutputLocations is 'warn'
+ */
+export async function onOutputLocationWarning(
+  handler: (warning: BackendMessage) => void
+): Promise<UnlistenFn> {
+  return listen<BackendMessage>('output-location-warning', (event) => handler(event.payload));
+}
+
+/**
+ * Message of a backend error, which is either a string or { code, message }
+ */
+export function errorMessage(err: unknown): string {
+  if (err instanceof Error) return err.message;
+  if (typeof err === 'object' && err !== null && 'message' in err) {
+    return String((err as { message: unknown }).message);
+  }
+  return String(err);
 }
 
 // ===== Settings =====
@@ -75,15 +110,17 @@ export function useSettings(): UseSettingsResult {
     }
   }, []);
 
+  // Only the changed settings are sent; the backend merges them over its own
+  // and returns the result. Tool paths, external viewers and post-run hooks
+  // have commands of their own.
   const updateSettings = useCallback(async (updates: Partial<AppSettings>) => {
     if (!settings) return;
 
-    const newSettings = { ...settings, ...updates };
     try {
-      await invoke('save_settings', { settings: newSettings });
-      setSettings(newSettings);
+      const saved = await invoke<AppSettings>('save_settings', { settings: updates });
+      setSettings(saved);
     } catch (err) {
-      setError(err instanceof Error ? err.message : String(err));
+      setError(errorMessage(err));
       throw err;
     }
   }, [settings]);
@@ -92,6 +129,15 @@ export function useSettings(): UseSettingsResult {
     fetchSettings();
   }, [fetchSettings]);
 
+  useEffect(() => {
+    const unlisten = onSettingsProfileChanged(() => {
+      fetchSettings();
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [fetchSettings]);
+
   return {
     settings,
     loading,
@@ -101,6 +147,148 @@ export function useSettings(): UseSettingsResult {
   };
 }
 
+export type Tool = 'colmap' | 'brush' | 'ffmpeg' | 'gvcore_cli';
+
+/**
+ * Run a program picked with pickToolExecutable as `tool`, or the bundled one
+ * again with null; returns the settings as saved
+ */
+export async function setToolPath(tool: Tool, path: string | null): Promise<AppSettings> {
+  return invoke<AppSettings>('set_tool_path', { tool, path });
+}
+
+// ===== Appearance =====
+
+/** The theme windows show, with system resolved against the OS */
+export interface EffectiveTheme {
+  theme: 'light' | 'dark';
+  setting: Theme;
+  /** Null until a window has reported the OS's theme */
+  os_theme: 'light' | 'dark' | null;
+}
+
+export async function getEffectiveTheme(): Promise<EffectiveTheme> {
+  return invoke<EffectiveTheme>('get_effective_theme');
+}
+
+/** Sent when the OS switches theme while the theme setting is system */
+export async function onOsThemeChanged(
+  handler: (theme: EffectiveTheme) => void
+): Promise<UnlistenFn> {
+  return listen<EffectiveTheme>('os-theme-changed', (event) => handler(event.payload));
+}
+
+// ===== Settings Profiles =====
+
+/** The settings profiles, sorted by name, and the one in use */
+export interface ProfileList {
+  active: string;
+  profiles: string[];
+}
+
+export async function listProfiles(): Promise<ProfileList> {
+  return invoke<ProfileList>('list_profiles');
+}
+
+/**
+ * Make a profile starting from a copy of copyFrom's settings, or from
+ * defaults without one
+ */
+export async function createProfile(name: string, copyFrom?: string): Promise<ProfileList> {
+  return invoke<ProfileList>('create_profile', { name, copyFrom });
+}
+
+/**
+ * Use another profile's settings and recents from now on. Fails with the
+ * job_running error code while a job runs.
+ */
+export async function switchProfile(name: string): Promise<ProfileList> {
+  return invoke<ProfileList>('switch_profile', { name });
+}
+
+/** Remove a profile; the active one cannot be removed */
+export async function deleteProfile(name: string): Promise<ProfileList> {
+  return invoke<ProfileList>('delete_profile', { name });
+}
+
+/** Sent after a switch, once the new profile's settings are in use */
+export async function onSettingsProfileChanged(
+  handler: (profiles: ProfileList) => void
+): Promise<UnlistenFn> {
+  return listen<ProfileList>('settings-profile-changed', (event) => handler(event.payload));
+}
+
+// ===== Backend Messages =====
+
+/** Backend text as a catalog key and the values for its {placeholders} */
+export interface BackendMessage {
+  key: string;
+  params: Record<string, string>;
+}
+
+/** A failure with free-form detail (such as CLI stderr) that is never translated */
+export interface BackendFailure extends BackendMessage {
+  raw: string | null;
+}
+
+/** A command input field that failed validation, e.g. "videos[2]" */
+export interface FieldError extends BackendMessage {
+  field: string;
+  /** The values the field may take, e.g. the known presets */
+  options?: string[];
+}
+
+/** What a failed command rejects with */
+export interface CommandError extends BackendFailure {
+  code: string;
+  message: string;
+  /** Present with code invalid_arguments, to highlight the offending form fields */
+  fields?: FieldError[];
+  /** Present with code already_queued: the queue entry of the identical job */
+  entry_id?: string;
+  already_queued?: true;
+  /** Present with code target_missing: where the target may be now, or null */
+  suggestion?: string | null;
+  /** Present with code ambiguous_artifact: the equally new artifacts a job wrote, to choose from */
+  candidates?: string[];
+  /** Present with code not_ready: the inputs that are empty or still being copied */
+  not_ready?: NotReadyInput[];
+}
+
+/** An input a job cannot read yet; copier is the program holding it open, when known */
+export interface NotReadyInput {
+  path: string;
+  reason: 'empty' | 'locked' | 'growing';
+  copier: string | null;
+  message: BackendMessage;
+}
+
+export interface MessageCatalog {
+  locale: string;
+  messages: Record<string, string>;
+}
+
+/**
+ * The backend's built-in English catalog, to fall back to for keys a translation lacks
+ */
+export async function getMessageCatalog(locale: string): Promise<MessageCatalog> {
+  return invoke<MessageCatalog>('get_message_catalog', { locale });
+}
+
+let fallbackCatalog: Promise<MessageCatalog> | undefined;
+
+/**
+ * Text of a backend message from a catalog, with raw detail appended as-is
+ */
+export function formatMessage(
+  message: BackendMessage & { raw?: string | null },
+  catalog: MessageCatalog | undefined,
+): string {
+  const template = catalog?.messages[message.key] ?? message.key;
+  const text = template.replace(/\{(\w+)\}/g, (match, name: string) => message.params[name] ?? match);
+  return message.raw ? `${text}: ${message.raw}` : text;
+}
+
 // ===== Processing =====
 
 interface ProcessArgs {
@@ -109,13 +297,613 @@ interface ProcessArgs {
   preset: QualityPreset;
   colmapPath?: string;
   brushPath?: string;
+  /** 'per-clip' reconstructs each clip into its own subdirectory of outputDir */
+  mode?: 'combined' | 'per-clip';
+  stopOnError?: boolean;
+  /** Drop blurred or badly exposed frames; brightness is mean luminance 0-255 */
+  filterFrames?: {
+    enabled: boolean;
+    blur_threshold?: number;
+    min_brightness?: number;
+    max_brightness?: number;
+  };
+  /** Kind of footage; selects COLMAP matching flags the installed CLI supports */
+  captureType?: CaptureType;
+  /** Overrides the outputNameTemplate setting, e.g. '{production}_{date}_{preset}_v{version}' */
+  outputNameTemplate?: string;
+  /** Extract frames again instead of reusing the ones cached for these clips */
+  forceReextract?: boolean;
+  /**
+   * Scale clips down to the smallest of their resolutions when they differ,
+   * leaving the clips already that size as they are
+   */
+  normalizeResolution?: boolean;
+  /**
+   * A quick pass on 960p proxies with the fastest preset, written to a
+   * '<name>-preview' directory inside outputDir
+   */
+  previewMode?: boolean;
+  /**
+   * Register only new clips into the COLMAP database a previous run left in
+   * outputDir. 'auto' (the default) requires every clip of that run among
+   * videos, unchanged; 'always' also trusts runs that recorded no clip hashes.
+   */
+  reuseReconstruction?: 'auto' | 'always' | 'never';
+  /** Overrides the settings' presetTraining for this job's preset, option by option */
+  training?: TrainingOptions;
+  /** Shown in the history and searched by getJobHistory */
+  label?: string;
+  /** Trimmed and lowercased */
+  tags?: string[];
+  /**
+   * Queue the job even when an identical one is queued or running; otherwise
+   * the request fails with code already_queued and that job's entry_id
+   */
+  allowDuplicate?: boolean;
+  /**
+   * Run even when the GPU has less VRAM than the preset needs, which otherwise
+   * fails with insufficient_vram; who overrode it is kept in the history
+   */
+  override?: boolean;
+  /**
+   * Train even when fewer than minRegisteredPercent of the images register,
+   * which otherwise fails with aborted_low_registration before training
+   */
+  continueOnPoorRegistration?: boolean;
+}
+
+export interface VolumeVerdict {
+  mount: string;
+  roles: ('inputs' | 'scratch' | 'output')[];
+  required_bytes: number;
+  available_bytes: number | null;
+  fits: boolean;
+}
+
+export interface DiskPreflight {
+  input_bytes: number;
+  volumes: VolumeVerdict[];
+}
+
+/**
+ * Projected space a job needs on each drive it touches. process_videos runs the
+ * same check and fails with not_enough_space when a drive cannot hold its share.
+ */
+export async function preflightDiskSpace(args: ProcessArgs): Promise<DiskPreflight> {
+  return invoke<DiskPreflight>('preflight_disk_space', { args });
+}
+
+/**
+ * Clear the failure note a job left on the window title, once the user has
+ * seen the result. Takes the job's queue entry id.
+ */
+export async function acknowledgeJobResult(jobId: string): Promise<void> {
+  return invoke('acknowledge_job_result', { jobId });
+}
+
+/**
+ * The arguments a finished job ran with, to queue it again. A preview comes
+ * back as the full-quality run of the same production.
+ */
+export async function cloneJobArgs(jobId: string): Promise<ProcessArgs> {
+  return invoke<ProcessArgs>('clone_job_args', { jobId });
+}
+
+export type JobStatus = 'completed' | 'failed' | 'cancelled';
+
+/** A finished job as the history keeps it; only the fields the UI reads */
+export interface JobRecord {
+  jobId: string;
+  batchId: string | null;
+  status: JobStatus;
+  preset: string;
+  videos: string[];
+  outputDir: string;
+  artifactPath: string | null;
+  error: string | null;
+  /** Seconds since the Unix epoch */
+  startedAt: number;
+  finishedAt: number;
+  /** Seconds spent in each stage, e.g. training_splats */
+  stages: Record<string, number>;
+  /** Total length of the clips in seconds, when every one's was known */
+  inputSecs: number | null;
+  label: string | null;
+  tags: string[];
+  /** Every run of the CLI when the job was retried; absent when it ran once */
+  attempts?: JobAttempt[];
+  /** Every artifact the job made, the primary (artifactPath) first; empty in older records */
+  artifacts?: JobArtifact[];
+  /** Present when the job ran despite the GPU having too little VRAM for its preset */
+  vramOverride?: VramOverride;
+  /** How many images COLMAP registered, whether or not the job went on to train */
+  registration?: Registration;
+  /** What the last history sweep found on disk; absent until one has looked */
+  verification?: HistoryVerification;
+}
+
+export interface HistoryVerification {
+  /** Seconds since the Unix epoch */
+  verified_at: number;
+  /** Empty when the disk matched the record */
+  discrepancies: HistoryDiscrepancy[];
+  /** SHA-256 of samples of each artifact too large to hash whole, by path */
+  samples?: Record<string, string>;
+}
+
+export type HistoryDiscrepancy =
+  | { kind: 'artifact_missing'; path: string }
+  | { kind: 'size_changed'; path: string; expected: number; actual: number }
+  | { kind: 'hash_mismatch'; path: string }
+  /** Samples of an artifact too large to hash whole differ from the last sweep's */
+  | { kind: 'samples_changed'; path: string }
+  | { kind: 'artifact_unreadable'; path: string; error: string }
+  | { kind: 'log_missing' }
+  | { kind: 'sidecar_missing'; dir: string }
+  | { kind: 'sidecar_unreadable'; dir: string; error: string }
+  /** The sidecar describes another job, such as a later run into the same production */
+  | { kind: 'sidecar_replaced'; dir: string; job_id: string }
+  | { kind: 'sidecar_mismatch'; dir: string };
+
+export interface HistorySweepSummary {
+  checked: number;
+  /** Stopped before every record was checked */
+  cancelled: boolean;
+  /** By job id, for the records where the disk did not match */
+  discrepancies: Record<string, HistoryDiscrepancy[]>;
+}
+
+export interface Registration {
+  registered: number;
+  total: number;
+  /** Clips none of whose images registered */
+  unregistered_clips: string[];
+}
+
+/** Who ran a job past the VRAM policy; sizes in MiB */
+export interface VramOverride {
+  user: string;
+  /** Seconds since the Unix epoch */
+  at: number;
+  gpu: string;
+  detected_mb: number;
+  required_mb: number;
+  preset: string;
+}
+
+/**
+ * An artifact of a job. The primary, which the viewer opens, is the first
+ * PLY, or without one the first SPZ, or without either the first named.
+ */
+export interface JobArtifact {
+  kind: 'ply' | 'spz' | 'other';
+  path: string;
+  bytes: number;
+  sha256: string | null;
+}
+
+/** What process_videos made */
+export interface JobOutput {
+  /** The primary artifact, or in per-clip mode the directory holding the productions */
+  artifact_path: string;
+  /** Primary first; empty in per-clip mode */
+  artifacts: JobArtifact[];
+}
+
+export interface JobAttempt {
+  /** Counted from 1 */
+  attempt: number;
+  started_at: number;
+  finished_at: number;
+  /** Null for the attempt that succeeded */
+  error: string | null;
+  exit_code: number | null;
+  resumed_from_stage: string | null;
+}
+
+/** Every part given must match */
+export interface HistoryFilter {
+  /** Words each found in the label, production or preset, in any case */
+  query?: string;
+  /** All of these */
+  tags?: string[];
+  /** Any of these */
+  statuses?: JobStatus[];
+  /** Started at or after, in seconds since the Unix epoch */
+  since?: number;
+  /** Started before */
+  until?: number;
+}
+
+export interface HistoryPage {
+  /** Newest first */
+  records: JobRecord[];
+  /** Matching records across all pages */
+  total: number;
+}
+
+/** Finished jobs matching `filter`, newest first */
+export async function getJobHistory(
+  filter?: HistoryFilter,
+  offset?: number,
+  limit?: number
+): Promise<HistoryPage> {
+  return invoke<HistoryPage>('get_job_history', { filter, offset, limit });
+}
+
+/**
+ * Check every history record's artifacts, log and sidecar against the disk,
+ * noting what no longer matches on the record. Opening the history view also
+ * starts a sweep once the last is over a day old; onHistoryVerified reports both.
+ */
+export async function verifyHistory(): Promise<HistorySweepSummary> {
+  return invoke<HistorySweepSummary>('verify_history');
+}
+
+/** Stop a running history sweep, keeping what it found so far */
+export async function cancelHistoryVerification(): Promise<void> {
+  return invoke('cancel_history_verification');
+}
+
+export async function onHistoryVerified(
+  handler: (summary: HistorySweepSummary) => void
+): Promise<UnlistenFn> {
+  return listen<HistorySweepSummary>('history-verified', (event) => handler(event.payload));
+}
+
+/**
+ * Write the jobs matching `filter` to a CSV file at `path`, one row per job
+ * with its clips, stage durations and artifact. Returns the rows written.
+ */
+export async function exportHistoryCsv(path: string, filter?: HistoryFilter): Promise<number> {
+  return invoke<number>('export_history_csv', { path, filter });
+}
+
+/** Replace a finished job's label and tags; an empty label clears it */
+export async function setJobMetadata(
+  jobId: string,
+  label: string | null,
+  tags: string[]
+): Promise<JobRecord> {
+  return invoke<JobRecord>('set_job_metadata', { jobId, label, tags });
+}
+
+/** Exactly what a job started; env holds only what differs from the app's environment */
+export interface CommandSpec {
+  program: string;
+  args: string[];
+  env: Record<string, string>;
+  cwd: string | null;
+}
+
+/**
+ * What a job ran, as recorded in its history. With shellQuoted, one line
+ * quoted for this platform's shell (sh, or cmd.exe on Windows) to paste into
+ * a terminal.
+ */
+export async function getJobCommand(jobId: string, shellQuoted: true): Promise<string>;
+export async function getJobCommand(jobId: string, shellQuoted: false): Promise<CommandSpec>;
+export async function getJobCommand(
+  jobId: string,
+  shellQuoted: boolean,
+): Promise<CommandSpec | string> {
+  return invoke<CommandSpec | string>('get_job_command', { jobId, shellQuoted });
+}
+
+export interface MetricPoint {
+  step: number;
+  value: number;
+}
+
+/**
+ * Series such as 'loss', 'psnr' and 'splat_count', plus any metric the CLI
+ * reports under a name of its own. Each holds at most 2000 evenly spread points.
+ */
+export interface TrainingMetrics {
+  series: { name: string; points: MetricPoint[] }[];
+}
+
+/** Points added to a job's series since the last training-metrics event */
+export interface MetricsUpdate {
+  job_id: string;
+  points: { name: string; step: number; value: number }[];
+}
+
+export interface Checkpoint {
+  job_id: string;
+  path: string;
+  /** From the file name, when it gives one */
+  iteration: number | null;
+  splat_count: number;
+}
+
+/** Checkpoints of a running or finished job, oldest first */
+export async function getJobCheckpoints(jobId: string): Promise<Checkpoint[]> {
+  return invoke<Checkpoint[]>('get_job_checkpoints', { jobId });
+}
+
+/** A checkpoint of the running job is complete and can be opened in the viewer */
+export async function onCheckpointAvailable(
+  handler: (checkpoint: Checkpoint) => void
+): Promise<UnlistenFn> {
+  return listen<Checkpoint>('checkpoint-available', (event) => handler(event.payload));
+}
+
+export interface VramConsumer {
+  pid: number;
+  name: string;
+  /** Null where the driver does not report it, as on Windows */
+  vram_mb: number | null;
+}
+
+/** VRAM of an NVIDIA GPU when the running job started training */
+export interface VramCheck {
+  job_id: string;
+  required_mb: number;
+  sample: {
+    gpu: string;
+    total_mb: number;
+    free_mb: number;
+    /** Held by the job's own CLI, which it can use */
+    job_mb: number;
+    /** Other processes using the GPU, largest first */
+    consumers: VramConsumer[];
+    taken_at: number;
+  };
+  contended: boolean;
+  /** Null when the job was not suspended to wait for VRAM */
+  wait: 'waiting' | 'freed' | 'timed_out' | null;
+}
+
+/**
+ * Too little VRAM was free when the running job started training; sent again
+ * when a job suspended with the waitForVram setting continues
+ */
+export async function onGpuContention(
+  handler: (check: VramCheck) => void
+): Promise<UnlistenFn> {
+  return listen<VramCheck>('gpu-contention', (event) => handler(event.payload));
+}
+
+/** A post-run hook of a finished job started or ended */
+export interface HookRun {
+  job_id: string;
+  name: string;
+  state: 'running' | 'succeeded' | 'failed' | 'timed-out' | 'cancelled';
+}
+
+export async function onPostRunHook(handler: (run: HookRun) => void): Promise<UnlistenFn> {
+  return listen<HookRun>('post-run-hook', (event) => handler(event.payload));
+}
+
+/**
+ * Replace the post-run hooks and turn them on or off; each program must have
+ * been picked with pickToolExecutable
+ */
+export async function setPostRunHooks(hooks: PostRunHook[], enabled: boolean): Promise<void> {
+  return invoke('set_post_run_hooks', { hooks, enabled });
+}
+
+/** Stop a running post-run hook; the job gets a warning, and later hooks still run */
+export async function cancelPostRunHook(jobId: string, name: string): Promise<void> {
+  return invoke('cancel_post_run_hook', { jobId, name });
+}
+
+/** What cancelling a running job costs against letting it finish */
+export interface CancelImpact {
+  job_id: string;
+  /** Null before the job reported any progress */
+  stage: string | null;
+  progress: number;
+  /** Null without earlier completed jobs of the preset to go by */
+  eta_to_finish_secs: number | null;
+  /** Time spent on work a new run would have to do again */
+  work_lost_if_cancelled_secs: number;
+  /** Where a new run would start, when not at the beginning */
+  resumable_from_stage: string | null;
+}
+
+/** For the cancel confirmation; the running job when jobId is omitted */
+export async function getCancelImpact(jobId?: string): Promise<CancelImpact> {
+  return invoke<CancelImpact>('get_cancel_impact', { jobId: jobId ?? null });
+}
+
+export interface ProcessingStall {
+  job_id: string;
+  stage: string;
+  progress: number;
+  stalled_secs: number;
+  impact: CancelImpact;
+}
+
+/** The running job's progress has stood still for 15 minutes */
+export async function onProcessingStalled(
+  handler: (stall: ProcessingStall) => void
+): Promise<UnlistenFn> {
+  return listen<ProcessingStall>('processing-stalled', (event) => handler(event.payload));
+}
+
+export interface AutoRetrying {
+  job_id: string;
+  /** The attempt about to start, counted from 1 */
+  attempt: number;
+  max_attempts: number;
+  exit_code: number;
+  backoff_secs: number;
+  /** Where the attempt starts, e.g. detecting_cameras when the frames were kept */
+  resumes_from_stage: string | null;
+}
+
+/** A job failed with a retryable exit code and runs again after the backoff */
+export async function onJobAutoRetrying(
+  handler: (retrying: AutoRetrying) => void
+): Promise<UnlistenFn> {
+  return listen<AutoRetrying>('job-auto-retrying', (event) => handler(event.payload));
+}
+
+/** The training metrics of a running or finished job */
+export async function getTrainingMetrics(jobId: string): Promise<TrainingMetrics> {
+  return invoke<TrainingMetrics>('get_training_metrics', { jobId });
+}
+
+/** New training metrics of the running job, at most twice a second */
+export async function onTrainingMetrics(
+  handler: (update: MetricsUpdate) => void
+): Promise<UnlistenFn> {
+  return listen<MetricsUpdate>('training-metrics', (event) => handler(event.payload));
+}
+
+export interface FramesCacheUsage {
+  dir: string;
+  sets: number;
+  bytes: number;
+  max_bytes: number;
+}
+
+/**
+ * Frames extracted for earlier runs, kept under the scratch directory and
+ * reused when the same clips are extracted the same way again
+ */
+export async function getFramesCacheUsage(): Promise<FramesCacheUsage> {
+  return invoke<FramesCacheUsage>('get_frames_cache_usage');
+}
+
+/**
+ * Delete the cached frames no running job uses; returns the bytes freed
+ */
+export async function clearFramesCache(): Promise<number> {
+  return invoke<number>('clear_frames_cache');
+}
+
+export type CacheCategory = 'thumbnails' | 'metadata' | 'frames';
+
+export interface CacheCategoryStats {
+  category: CacheCategory;
+  dir: string;
+  entries: number;
+  bytes: number;
+  /** Limit of the cache holding the category; thumbnails and metadata share one */
+  max_bytes: number;
+}
+
+/** Size of each cache category; the least recently used entries are evicted past the limit */
+export async function getCacheStats(): Promise<CacheCategoryStats[]> {
+  return invoke<CacheCategoryStats[]>('get_cache_stats');
+}
+
+/** Delete a category's entries no running job uses; returns the bytes freed */
+export async function clearCache(category: CacheCategory): Promise<number> {
+  return invoke<number>('clear_cache', { category });
+}
+
+export async function onFramesCacheHit(
+  handler: (hit: { video: string; frames_dir: string }) => void
+): Promise<UnlistenFn> {
+  return listen<{ video: string; frames_dir: string }>('frames-cache-hit', (event) =>
+    handler(event.payload)
+  );
+}
+
+/** The running job's output drive went away or came back */
+export interface VolumeChange {
+  output_dir: string;
+  mount: string;
+  connected: boolean;
+  /** The CLI is suspended until resumeProcessing; never on Windows */
+  paused: boolean;
+}
+
+/**
+ * The output drive of the running job disconnected. The job fails with
+ * output_volume_lost unless the drive is back within two minutes.
+ */
+export async function onVolumeDisconnected(
+  handler: (change: VolumeChange) => void
+): Promise<UnlistenFn> {
+  return listen<VolumeChange>('volume-disconnected', (event) => handler(event.payload));
+}
+
+/** The output drive is back; a paused job waits for resumeProcessing */
+export async function onVolumeReconnected(
+  handler: (change: VolumeChange) => void
+): Promise<UnlistenFn> {
+  return listen<VolumeChange>('volume-reconnected', (event) => handler(event.payload));
+}
+
+export async function resumeProcessing(): Promise<void> {
+  return invoke('resume_processing');
+}
+
+/** A warning the CLI printed, e.g. code 'deprecated-flag' */
+export interface CliWarning {
+  code: string;
+  message: string;
+}
+
+/**
+ * Warnings of the running job, sent as the CLI prints them. They are also kept
+ * on the queue entry and in the job's history record.
+ */
+export async function onProcessingWarning(
+  handler: (warning: CliWarning) => void
+): Promise<UnlistenFn> {
+  return listen<CliWarning>('processing-warning', (event) => handler(event.payload));
+}
+
+export interface QueueEntry {
+  entry_id: string;
+  batch_id: string | null;
+  videos: string[];
+  output_dir: string;
+  status: 'queued' | 'running' | 'completed' | 'failed' | 'cancelled';
+  artifact_path: string | null;
+  error: BackendFailure | null;
+  warnings: CliWarning[];
+  clips: ClipProgress[];
+}
+
+/** Time a job spent on one clip, also kept in its history record */
+export interface ClipProgress {
+  video: string;
+  /** Seconds per stage, e.g. { extracting_frames: 12.5 } */
+  seconds: Record<string, number>;
+  finished: string[];
+  /** Attributed from the clip order because the CLI did not name the clip */
+  estimated: boolean;
+}
+
+/**
+ * Frames expected from the clips' durations and those extracted, kept in the
+ * history record; a job whose counts are far apart also gets a
+ * 'frame-count-mismatch' warning
+ */
+export interface FrameCounts {
+  expected: number;
+  actual: number | null;
+}
+
+/**
+ * Get queued and running jobs; clips of one batch share a batch_id
+ */
+export async function getQueue(): Promise<QueueEntry[]> {
+  return invoke<QueueEntry[]>('get_queue');
+}
+
+/** processing-progress payload: backend status as a message, CLI text as raw */
+interface BackendProgress {
+  stage: ProcessingProgress['stage'];
+  progress: number;
+  message: BackendMessage | null;
+  raw: string | null;
+  current_item: string | null;
+  items: { done: number; total: number } | null;
 }
 
 interface UseProcessingResult {
   isProcessing: boolean;
   progress: ProcessingProgress;
   error: string | null;
-  startProcessing: (args: ProcessArgs) => Promise<string>;
+  startProcessing: (args: ProcessArgs) => Promise<JobOutput>;
   cancelProcessing: () => Promise<void>;
 }
 
@@ -135,14 +923,25 @@ export function useProcessing(): UseProcessingResult {
     let unlisten: UnlistenFn | undefined;
 
     const setupListener = async () => {
-      unlisten = await listen<ProcessingProgress>('processing-progress', (event) => {
-        setProgress(event.payload);
+      if (!fallbackCatalog) fallbackCatalog = getMessageCatalog('en');
+      const catalog = await fallbackCatalog.catch(() => undefined);
+      unlisten = await listen<BackendProgress>('processing-progress', (event) => {
+        const { stage, progress, message, raw, current_item, items } = event.payload;
+        const text = message ? formatMessage(message, catalog) : raw ?? undefined;
+        setProgress({
+          stage,
+          progress,
+          message: text,
+          currentItem: current_item ?? undefined,
+          currentStep: items ? items.done + 1 : undefined,
+          totalSteps: items?.total,
+        });
 
-        if (event.payload.stage === 'complete') {
+        if (stage === 'complete') {
           setIsProcessing(false);
-        } else if (event.payload.stage === 'failed') {
+        } else if (stage === 'failed') {
           setIsProcessing(false);
-          setError(event.payload.message || 'Processing failed');
+          setError(text || 'Processing failed');
         }
       });
     };
@@ -154,19 +953,19 @@ export function useProcessing(): UseProcessingResult {
     };
   }, []);
 
-  const startProcessing = useCallback(async (args: ProcessArgs): Promise<string> => {
+  const startProcessing = useCallback(async (args: ProcessArgs): Promise<JobOutput> => {
     setIsProcessing(true);
     setError(null);
     setProgress({ stage: 'extracting_frames', progress: 0 });
 
     try {
-      const result = await invoke<string>('process_videos', { args });
+      const result = await invoke<JobOutput>('process_videos', { args });
       return result;
     } catch (err) {
-      const errorMessage = err instanceof Error ? err.message : String(err);
-      setError(errorMessage);
+      const message = errorMessage(err);
+      setError(message);
       setIsProcessing(false);
-      setProgress({ stage: 'failed', progress: 0, message: errorMessage });
+      setProgress({ stage: 'failed', progress: 0, message });
       throw err;
     }
   }, []);
@@ -190,7 +989,53 @@ export function useProcessing(): UseProcessingResult {
   };
 }
 
-// ===== CLI Path =====
+// ===== Overlap Analysis =====
+
+export interface OverlapReport {
+  clips: string[];
+  matrix: number[][];
+  verdict: 'good' | 'marginal' | 'likely_to_fail';
+  weakest_pairs: { a: string; b: string; overlap: number }[];
+}
+
+/**
+ * Estimate how well clips overlap before running COLMAP on them
+ */
+export async function analyzeOverlap(videos: string[]): Promise<OverlapReport> {
+  return invoke<OverlapReport>('analyze_overlap', { videos });
+}
+
+export async function cancelOverlapAnalysis(): Promise<void> {
+  return invoke('cancel_overlap_analysis');
+}
+
+// ===== Audio Sync =====
+
+export interface ClipSync {
+  path: string;
+  status: 'reference' | 'synced' | 'no_audio';
+  /** Positive when the clip started earlier than the reference; pass as the clip's sync_offset_ms */
+  offset_ms: number | null;
+  confidence: number | null;
+}
+
+export interface SyncReport {
+  reference: string | null;
+  clips: ClipSync[];
+}
+
+/**
+ * Estimate start offsets between multi-camera clips from their audio
+ */
+export async function analyzeSync(videos: string[]): Promise<SyncReport> {
+  return invoke<SyncReport>('analyze_sync', { videos });
+}
+
+export async function cancelSyncAnalysis(): Promise<void> {
+  return invoke('cancel_sync_analysis');
+}
+
+// ===== CLI Path =====
 
 /**
  * Get the path to the bundled gvcore-cli executable
@@ -199,6 +1044,1175 @@ export async function getCliPath(): Promise<string> {
   return invoke<string>('get_cli_path');
 }
 
+export interface CliCandidate {
+  source: 'configured' | 'update' | 'native' | 'emulated' | 'legacy' | 'path';
+  path: string;
+  exists: boolean;
+}
+
+export interface CliResolution {
+  /** Target triple of the machine, e.g. aarch64-apple-darwin */
+  host: string;
+  /** Every place looked at, in order */
+  candidates: CliCandidate[];
+  chosen: CliCandidate | null;
+  /** Bundled builds that cannot run on this machine */
+  incompatible: string[];
+}
+
+/** Where each gvcore-cli is looked for and which one jobs run */
+export async function explainCliResolution(): Promise<CliResolution> {
+  return invoke<CliResolution>('explain_cli_resolution');
+}
+
+// ===== Performance Environment =====
+
+export interface PerformanceFinding {
+  code:
+    | 'defender_scanning'
+    | 'defender_exclusions_unknown'
+    | 'third_party_antivirus'
+    | 'usb_drive'
+    | 'slow_random_writes'
+    | 'security_unknown';
+  severity: 'info' | 'warning';
+  message: BackendMessage;
+  /** The directory the finding is about, when it is about one */
+  path: string | null;
+  docs_url: string | null;
+}
+
+export interface PerformanceEnvironment {
+  dirs: string[];
+  /** Warnings first */
+  findings: PerformanceFinding[];
+}
+
+export interface PoolStatus {
+  pool: 'io' | 'cpu_heavy' | 'background';
+  size: number;
+  /** Workers it may use right now; cpu_heavy gets one while a job runs */
+  allowed: number;
+  running: number;
+  queued: number;
+  completed: number;
+}
+
+export interface SchedulerStatus {
+  pools: PoolStatus[];
+  /** A processing job has shrunk the cpu_heavy pool */
+  throttled: boolean;
+  niceness: number;
+}
+
+/** How busy the backend's worker pools are, for the diagnostics screen */
+export async function getSchedulerStatus(): Promise<SchedulerStatus> {
+  return invoke<SchedulerStatus>('get_scheduler_status');
+}
+
+/**
+ * Look for antivirus scanning and slow drives that would slow jobs writing
+ * to the scratch directory and the output directory (the default one when
+ * omitted). Advice only; nothing is changed.
+ */
+export async function analyzePerformanceEnvironment(
+  outputDir?: string
+): Promise<PerformanceEnvironment> {
+  return invoke<PerformanceEnvironment>('analyze_performance_environment', { outputDir });
+}
+
+export interface PresetList {
+  presets: string[];
+  /** The CLI could not report its presets, so these are the built-in ones */
+  degraded: boolean;
+}
+
+/**
+ * The presets the installed gvcore-cli supports. process_videos and
+ * save_settings reject any other preset.
+ */
+export async function listPresets(): Promise<PresetList> {
+  return invoke<PresetList>('list_presets');
+}
+
+export interface KnownDevice extends DeviceIntrinsics {
+  /** From the built-in table rather than the knownDevices setting */
+  builtin: boolean;
+}
+
+/**
+ * Devices whose clips get intrinsics hints, the user's own first
+ */
+export async function listKnownDevices(): Promise<KnownDevice[]> {
+  return invoke<KnownDevice[]>('list_known_devices');
+}
+
+/**
+ * Clips shot with one physical camera. validate_videos suggests these as
+ * suggested_groups; a clip joins one through its options' camera_group
+ * (at most 64 characters), and the sidecar records the groups a job ran with.
+ */
+export interface CameraGroup {
+  name: string;
+  clips: string[];
+}
+
+export type QualityTier = 'draft' | 'standard' | 'high' | 'maximum';
+
+export interface PresetDescription {
+  name: string;
+  /** Described from the built-in table rather than from saved training options */
+  builtin: boolean;
+  summary: BackendMessage;
+  /** Expected duration, balanced being 1 */
+  relative_duration: number;
+  quality: QualityTier;
+  footage: { min_secs: number; max_secs: number | null };
+  min_vram_gb: number;
+  parameters: TrainingOptions;
+  /** Parameters taken from the training options saved for the preset */
+  overridden: string[];
+  /** How long the preset's finished jobs took on this machine */
+  local: {
+    jobs: number;
+    minutes_per_footage_minute: number;
+    message: BackendMessage;
+  } | null;
+}
+
+/** What each preset in the picker trades off, for the installed gvcore-cli */
+export async function getPresetDescriptions(): Promise<PresetDescription[]> {
+  return invoke<PresetDescription[]>('get_preset_descriptions');
+}
+
+/** Why gvcore-cli could not be started, from a spawn_diagnosis error's cause */
+export type SpawnCause =
+  | 'not_found'
+  | 'not_executable'
+  | 'quarantined'
+  | 'wrong_architecture'
+  | 'missing_runtime'
+  | 'selinux_denied';
+
+/**
+ * Apply the fix a spawn_diagnosis error offered in its repair field:
+ * 'make_executable' for not_executable, 'remove_quarantine' for quarantined.
+ * Other causes have no automated repair and fail with invalid_input.
+ */
+export async function repairCliIssue(cause: SpawnCause): Promise<void> {
+  return invoke('repair_cli_issue', { cause });
+}
+
+// ===== Secrets =====
+
+export interface SecretBackendInfo {
+  backend: 'keyring' | 'encrypted_file' | 'memory';
+  fallback_reason: string | null;
+}
+
+/**
+ * Store a credential in the OS keyring; it can never be read back from the frontend
+ */
+export async function setSecret(key: string, value: string): Promise<void> {
+  return invoke('set_secret', { key, value });
+}
+
+/**
+ * Check whether a credential is stored
+ */
+export async function hasSecret(key: string): Promise<boolean> {
+  return invoke<boolean>('has_secret', { key });
+}
+
+/**
+ * Delete a stored credential
+ */
+export async function deleteSecret(key: string): Promise<void> {
+  return invoke('delete_secret', { key });
+}
+
+/**
+ * Report whether secrets are in the OS keyring, the encrypted fallback file, or
+ * only in memory for the session
+ */
+export async function getSecretBackend(): Promise<SecretBackendInfo> {
+  return invoke<SecretBackendInfo>('get_secret_backend');
+}
+
+// ===== LAN Sharing =====
+
+export interface ShareInfo {
+  url: string;
+  production_path: string;
+  qr_code_path: string;
+  token_required: boolean;
+}
+
+/**
+ * Serve a finished production to browsers on the local network
+ */
+export async function startShareServer(
+  productionPath: string,
+  port?: number,
+  requireToken = true
+): Promise<ShareInfo> {
+  return invoke<ShareInfo>('start_share_server', { productionPath, port, requireToken });
+}
+
+export async function stopShareServer(): Promise<void> {
+  return invoke('stop_share_server');
+}
+
+export async function getShareStatus(): Promise<ShareInfo | null> {
+  return invoke<ShareInfo | null>('get_share_status');
+}
+
+// ===== Artifact Integrity =====
+
+export interface VerifyResult {
+  status: 'ok' | 'mismatch' | 'missing_sidecar';
+  artifact_path: string;
+  expected: string | null;
+  actual: string | null;
+}
+
+export interface HashProgress {
+  path: string;
+  hashed_bytes: number;
+  total_bytes: number;
+}
+
+/**
+ * Re-hash an artifact against the checksum recorded when it was produced;
+ * progress arrives as 'hash-progress' events
+ */
+export async function verifyArtifact(path: string): Promise<VerifyResult> {
+  return invoke<VerifyResult>('verify_artifact', { path });
+}
+
+export async function cancelVerification(): Promise<void> {
+  return invoke('cancel_verification');
+}
+
+export interface FileHash {
+  path: string;
+  sha256: string;
+  bytes: number;
+  /** Taken from the cache rather than read now */
+  cached: boolean;
+}
+
+/**
+ * SHA-256 of a file, from the cache while the file is unchanged; runs as a
+ * 'hashing' operation, so cancelOperation stops it and a later call resumes
+ */
+export async function getFileHash(path: string): Promise<FileHash> {
+  return invoke<FileHash>('get_file_hash', { path });
+}
+
+export interface OpenedProduction {
+  production: RecentProduction;
+  /** The primary artifact, which the viewer opens */
+  artifact_path: string | null;
+  /** The production's other artifacts, such as a web-optimized SPZ */
+  other_artifacts: JobArtifact[];
+  verification: VerifyResult | null;
+  warning: string | null;
+}
+
+/**
+ * Resolve a recent production's artifact, verifying it when verifyArtifactsOnOpen is set
+ */
+export async function openRecentProduction(id: string): Promise<OpenedProduction> {
+  return invoke<OpenedProduction>('open_recent_production', { id });
+}
+
+/** What openProduction did, for the UI to route on */
+export type OpenedTarget =
+  | {
+      action: 'viewer';
+      /** Null for a production that has no artifact yet */
+      artifact_path: string | null;
+      /** The production's other artifacts, such as a web-optimized SPZ */
+      other_artifacts: JobArtifact[];
+      production: RecentProduction | null;
+      verification: VerifyResult | null;
+      warning: string | null;
+    }
+  | {
+      action: 'project';
+      path: string;
+      project: GVProject;
+      production: RecentProduction | null;
+    }
+  | { action: 'revealed'; path: string };
+
+/**
+ * Open a recent production by id, or a production directory, splat PLY or
+ * .gvproj by path. A target that is gone rejects with code target_missing
+ * and a suggestion when one was found.
+ */
+export async function openProduction(idOrPath: string): Promise<OpenedTarget> {
+  return invoke<OpenedTarget>('open_production', { idOrPath });
+}
+
+// ===== Camera Bookmarks =====
+
+/**
+ * The viewer camera, versioned by the viewer. The backend stores it as given
+ * and only requires a version of at least 1.
+ */
+export interface CameraState {
+  version: 1;
+  position: [number, number, number];
+  target: [number, number, number];
+  up: [number, number, number];
+  /** Vertical field of view in degrees */
+  fov: number;
+}
+
+export interface CameraBookmark {
+  /** Unique within the production */
+  name: string;
+  cameraState: CameraState;
+  savedAt: number;
+}
+
+/** Save the camera as `name` in a production, replacing a bookmark of that name */
+export async function saveCameraBookmark(
+  production: string,
+  name: string,
+  cameraState: CameraState
+): Promise<CameraBookmark> {
+  return invoke<CameraBookmark>('save_camera_bookmark', { production, name, cameraState });
+}
+
+/** The camera bookmarks of a production, oldest first */
+export async function listCameraBookmarks(production: string): Promise<CameraBookmark[]> {
+  return invoke<CameraBookmark[]>('list_camera_bookmarks', { production });
+}
+
+export async function deleteCameraBookmark(production: string, name: string): Promise<void> {
+  return invoke('delete_camera_bookmark', { production, name });
+}
+
+// ===== Intermediate Archives =====
+
+export interface IntermediatesArchive {
+  entries: string[];
+  originalBytes: number;
+  archiveBytes: number;
+  archivedAt: number;
+}
+
+export interface ArchiveProgress {
+  path: string;
+  stage: 'compressing' | 'verifying' | 'restoring';
+  done_bytes: number;
+  total_bytes: number;
+}
+
+/**
+ * Compress a production's frames and COLMAP data into one verified archive and
+ * delete the originals. Processing into the production then fails with the
+ * needs_restore error code until restoreProductionIntermediates is called.
+ */
+export async function archiveProductionIntermediates(path: string): Promise<IntermediatesArchive> {
+  return invoke<IntermediatesArchive>('archive_production_intermediates', { path });
+}
+
+export async function restoreProductionIntermediates(path: string): Promise<void> {
+  return invoke('restore_production_intermediates', { path });
+}
+
+export async function cancelArchiveOperation(): Promise<void> {
+  return invoke('cancel_archive_operation');
+}
+
+export async function onArchiveProgress(
+  handler: (progress: ArchiveProgress) => void
+): Promise<UnlistenFn> {
+  return listen<ArchiveProgress>('archive-progress', (event) => handler(event.payload));
+}
+
+// ===== Retention =====
+
+/** A production whose intermediates the retention policy lets go */
+export interface RetentionCandidate {
+  id: string;
+  name: string;
+  path: string;
+  /** When it was last opened, or its directory last changed if it never was */
+  last_used: string;
+  /** Size of its intermediates in bytes */
+  bytes: number;
+}
+
+export interface RetentionPreview {
+  action: RetentionAction;
+  /** Least recently used first */
+  candidates: RetentionCandidate[];
+  total_bytes: number;
+}
+
+/** What the retention policy in the settings would archive or trash now, touching nothing */
+export async function previewRetention(): Promise<RetentionPreview> {
+  return invoke<RetentionPreview>('preview_retention');
+}
+
+/**
+ * Apply the retention policy now, whether or not it is enabled; returns the
+ * productions it acted on, each undoable like an archive or delete
+ */
+export async function runRetention(): Promise<RetentionPreview> {
+  return invoke<RetentionPreview>('run_retention');
+}
+
+/** Pinned productions keep their intermediates whatever the retention policy says */
+export async function setProductionPinned(id: string, pinned: boolean): Promise<void> {
+  return invoke('set_production_pinned', { id, pinned });
+}
+
+// ===== Backend Operations =====
+
+export type OperationKind =
+  | 'hashing'
+  | 'conversion'
+  | 'archive'
+  | 'library_scan'
+  | 'history_sweep'
+  | 'comparison';
+
+/** A hash, downsample, archive or library scan running in the backend */
+export interface OperationInfo {
+  id: string;
+  kind: OperationKind;
+  /** What it works on, usually a path */
+  subject: string;
+  done: number;
+  /** Null while the amount of work is not known */
+  total: number | null;
+  /** Seconds since the Unix epoch */
+  started_at: number;
+  cancelled: boolean;
+}
+
+export interface OperationFinished {
+  id: string;
+  kind: OperationKind;
+}
+
+export async function listOperations(): Promise<OperationInfo[]> {
+  return invoke<OperationInfo[]>('list_operations');
+}
+
+/** The operation stops at its next block of work and fails as cancelled */
+export async function cancelOperation(id: string): Promise<void> {
+  return invoke('cancel_operation', { id });
+}
+
+export async function onOperationProgress(
+  handler: (operation: OperationInfo) => void
+): Promise<UnlistenFn> {
+  return listen<OperationInfo>('operation-progress', (event) => handler(event.payload));
+}
+
+/** Sent when an operation ends, whether it succeeded, failed or was cancelled */
+export async function onOperationFinished(
+  handler: (finished: OperationFinished) => void
+): Promise<UnlistenFn> {
+  return listen<OperationFinished>('operation-finished', (event) => handler(event.payload));
+}
+
+// ===== Downsampling =====
+
+/** How small a downsampled copy must be */
+export type DownsampleTarget = { max_splats: number } | { max_bytes: number };
+
+export interface DownsampleResult {
+  output_path: string;
+  splat_count: number;
+  source_splat_count: number;
+  bytes: number;
+  /** Share of the source's visibility left out, from 0 to 1 */
+  estimated_quality_loss: number;
+  sha256: string;
+  source_sha256: string;
+}
+
+export interface DownsampleProgress {
+  output_path: string;
+  stage: 'scoring' | 'writing';
+  done_bytes: number;
+  total_bytes: number;
+}
+
+/**
+ * Write a copy of a splat PLY keeping its most visible splats, for sharing.
+ * The copy is recorded in its directory's sidecar as derived from the source.
+ * Progress arrives through onDownsampleProgress.
+ */
+export async function downsampleSplats(
+  input: string,
+  output: string,
+  target: DownsampleTarget
+): Promise<DownsampleResult> {
+  return invoke<DownsampleResult>('downsample_splats', { input, output, target });
+}
+
+export async function cancelDownsample(): Promise<void> {
+  return invoke('cancel_downsample');
+}
+
+export async function onDownsampleProgress(
+  handler: (progress: DownsampleProgress) => void
+): Promise<UnlistenFn> {
+  return listen<DownsampleProgress>('downsample-progress', (event) => handler(event.payload));
+}
+
+// ===== Productions Library =====
+
+export interface LibraryEntry {
+  kind: 'production' | 'project' | 'splat';
+  name: string;
+  /** The production directory, or the project file */
+  path: string;
+  artifact_path: string | null;
+  artifact_size: number | null;
+  splat_count: number | null;
+  /** Seconds since the Unix epoch */
+  created_at: number | null;
+}
+
+export interface LibraryScan {
+  root: string;
+  entries: LibraryEntry[];
+  directories_read: number;
+  directories_cached: number;
+  /** The depth or entry limit cut the scan short */
+  truncated: boolean;
+  /** Entries newly added to the recent productions */
+  imported: number;
+}
+
+export interface LibraryScanProgress {
+  root: string;
+  directories: number;
+  found: number;
+}
+
+/**
+ * Find productions under a folder; with import, new ones are added to the recent
+ * productions. Progress arrives through onLibraryScanProgress.
+ */
+export async function scanProductions(rootDir: string, importRecents = false): Promise<LibraryScan> {
+  return invoke<LibraryScan>('scan_productions', { rootDir, import: importRecents });
+}
+
+export async function cancelLibraryScan(): Promise<void> {
+  return invoke('cancel_library_scan');
+}
+
+export async function onLibraryScanProgress(
+  handler: (progress: LibraryScanProgress) => void
+): Promise<UnlistenFn> {
+  return listen<LibraryScanProgress>('library-scan-progress', (event) => handler(event.payload));
+}
+
+export interface DuplicateCopy {
+  artifact_path: string;
+  production_path: string;
+  in_recents: boolean;
+  in_history: boolean;
+}
+
+export interface DuplicateGroup {
+  sha256: string;
+  /** Of each copy */
+  size: number;
+  copies: DuplicateCopy[];
+}
+
+export interface ResolvedDuplicates {
+  resolved: string[];
+  /** Given back once the trash is emptied */
+  reclaimable_bytes: number;
+}
+
+/**
+ * Artifacts stored more than once under `roots`, most wasted space first.
+ * Scanning reports through onLibraryScanProgress, hashing through hash-progress.
+ */
+export async function findDuplicateArtifacts(roots: string[]): Promise<DuplicateGroup[]> {
+  return invoke<DuplicateGroup[]>('find_duplicate_artifacts', { roots });
+}
+
+export async function cancelDuplicateScan(): Promise<void> {
+  return invoke('cancel_duplicate_scan');
+}
+
+/**
+ * Keep one copy and trash the others, or replace them with links to it. Both
+ * can be undone; fails when a copy changed since it was found.
+ */
+export async function resolveDuplicates(
+  group: DuplicateGroup,
+  keepPath: string,
+  action: 'trash' | 'link'
+): Promise<ResolvedDuplicates> {
+  const copies = group.copies.map((copy) => copy.artifact_path);
+  return invoke<ResolvedDuplicates>('resolve_duplicates', { copies, keepPath, action });
+}
+
+export interface PreviewCamera {
+  position: [number, number, number];
+  target: [number, number, number];
+  /** Negative y when absent, as COLMAP orients reconstructions */
+  up?: [number, number, number] | null;
+  fov_degrees?: number | null;
+}
+
+export interface SplatPreview {
+  /** The PNG in the thumbnail cache */
+  path: string;
+  artifact_path: string;
+  size: number;
+  /** The camera used; pass it back to frame later versions the same way */
+  camera: PreviewCamera;
+  rendered_splats: number;
+  total_splats: number;
+  /** Of the splats drawn; null for previews cached by older versions */
+  bounds: SplatBounds | null;
+  cached: boolean;
+}
+
+export interface SplatBounds {
+  min: [number, number, number];
+  max: [number, number, number];
+}
+
+/**
+ * A square preview image of the splat at `path`, a PLY or a production
+ * directory, for production cards. Framed on the splat unless a camera is given;
+ * rendered again only once the artifact changes.
+ */
+export async function renderSplatPreview(
+  path: string,
+  size: number,
+  camera?: PreviewCamera
+): Promise<SplatPreview> {
+  return invoke<SplatPreview>('render_splat_preview', { path, size, camera: camera ?? null });
+}
+
+export async function cancelSplatPreview(): Promise<void> {
+  return invoke('cancel_splat_preview');
+}
+
+export interface ComparisonRender {
+  job_id_a: string;
+  job_id_b: string;
+  /** PNGs in the thumbnail cache */
+  image_a: string;
+  image_b: string;
+  /** Black where the renders agree, through red and yellow to white where they differ most */
+  heatmap: string;
+  size: number;
+  camera: PreviewCamera;
+  /** The bookmark the camera came from; null when framed on the older artifact */
+  bookmark: string | null;
+  difference: {
+    /** Average over the pixels either render drew, from 0 to 1 */
+    mean: number;
+    changed_share: number;
+  };
+  cached: boolean;
+}
+
+/**
+ * Render the artifacts of two jobs side by side from the same camera, with a
+ * heatmap of where they differ. The camera is the named bookmark of either
+ * production, or framed on the older artifact; repeated comparisons come from
+ * the cache.
+ */
+export async function renderComparison(
+  jobIdA: string,
+  jobIdB: string,
+  bookmark?: string
+): Promise<ComparisonRender> {
+  return invoke<ComparisonRender>('render_comparison', {
+    jobIdA,
+    jobIdB,
+    bookmark: bookmark ?? null,
+  });
+}
+
+export async function cancelComparison(): Promise<void> {
+  return invoke('cancel_comparison');
+}
+
+export interface QuickLook {
+  production_path: string;
+  /** The production.gvmeta contents */
+  sidecar: Record<string, unknown> | null;
+  artifact: {
+    path: string;
+    exists: boolean;
+    bytes: number | null;
+    /** Whether its size is the one its job recorded; null when none was recorded */
+    intact: boolean | null;
+  } | null;
+  splat_count: number | null;
+  bounds: SplatBounds | null;
+  /** The preview in the thumbnail cache */
+  thumbnail: string | null;
+  /** The splat count, bounds and thumbnail are of an earlier version of the artifact */
+  stale: boolean;
+  /** Parts still being gathered; the full quick look follows in onQuickLookUpdated */
+  pending: ('sidecar' | 'artifact' | 'splats')[];
+}
+
+/**
+ * Whether a production's artifact is intact and roughly what it holds, answered
+ * within half a second from what is cached, before opening the viewer
+ */
+export async function quickLook(productionPath: string): Promise<QuickLook> {
+  return invoke<QuickLook>('quick_look', { productionPath });
+}
+
+export async function onQuickLookUpdated(
+  handler: (look: QuickLook) => void
+): Promise<UnlistenFn> {
+  return listen<QuickLook>('quick-look-updated', (event) => handler(event.payload));
+}
+
+export interface ImportedProduction {
+  production: RecentProduction;
+  artifact_path: string;
+  format: 'ply' | 'splat' | 'spz';
+  splat_count: number;
+  /** False when a PLY was imported by reference and stays where it was */
+  copied: boolean;
+}
+
+/**
+ * Import a splat made with another tool as a production in the default output
+ * directory. Only a PLY can be imported by reference; other formats are converted.
+ */
+export async function importArtifact(
+  path: string,
+  name?: string,
+  copyIntoLibrary = true
+): Promise<ImportedProduction> {
+  return invoke<ImportedProduction>('import_artifact', { path, name, copyIntoLibrary });
+}
+
+export interface ReconcileSummary {
+  /** Artifacts and production directories looked at */
+  checked: number;
+  /** Newly found to be gone */
+  missing: string[];
+  /** Production directories found elsewhere, whose records now point there */
+  moved: { from: string; to: string }[];
+  /** Flagged as gone earlier and back now */
+  restored: string[];
+}
+
+/**
+ * Flag history records and recent productions whose files were deleted
+ * outside the app, and follow moved ones by their checksum
+ */
+export async function reconcileLibrary(): Promise<ReconcileSummary> {
+  return invoke<ReconcileSummary>('reconcile_library');
+}
+
+/** Remove the history records and recent productions flagged as missing */
+export async function purgeMissingRecords(): Promise<{ records: number; recents: number }> {
+  return invoke('purge_missing_records');
+}
+
+// ===== Pending Tasks =====
+
+/** Formats web viewers load */
+export type WebFormat = 'splat' | 'spz';
+
+export interface WebExport {
+  output_path: string;
+  format: WebFormat;
+  bytes: number;
+  source_sha256: string;
+}
+
+/**
+ * Convert a splat PLY to a format web viewers load. It runs as a pending task,
+ * so an export the app does not live to finish can be resumed or discarded.
+ */
+export async function exportWebArtifact(
+  input: string,
+  output: string,
+  format: WebFormat
+): Promise<WebExport> {
+  return invoke<WebExport>('export_web_artifact', { input, output, format });
+}
+
+/** A web export or downsample that is running, or that an earlier run left unfinished */
+export interface PendingTask {
+  task_id: string;
+  work:
+    | { kind: 'conversion'; input: string; output: string; format: WebFormat }
+    | { kind: 'downsample'; input: string; output: string; target: DownsampleTarget };
+  /** Where the output is written until it is complete */
+  temp_path: string;
+  source: { bytes: number; modified: number };
+  /** Known once the task has read its source */
+  source_sha256: string | null;
+  started_at: number;
+  state: 'running' | 'paused';
+}
+
+export type TaskResult =
+  | ({ kind: 'conversion' } & WebExport)
+  | ({ kind: 'downsample' } & DownsampleResult);
+
+export async function getPendingTasks(): Promise<PendingTask[]> {
+  return invoke<PendingTask[]>('get_pending_tasks');
+}
+
+/** Run a paused task again from the start; refused if its source changed since */
+export async function resumePendingTask(taskId: string): Promise<TaskResult> {
+  return invoke<TaskResult>('resume_pending_task', { taskId });
+}
+
+/** Forget a paused task and remove the temp file it left */
+export async function discardPendingTask(taskId: string): Promise<void> {
+  return invoke('discard_pending_task', { taskId });
+}
+
+// ===== External Viewers =====
+
+/**
+ * List configured external viewers, for the "Open with" menu
+ */
+export async function listExternalViewers(): Promise<ExternalViewer[]> {
+  return invoke<ExternalViewer[]>('list_external_viewers');
+}
+
+/**
+ * Add an external viewer; argsTemplate is split on spaces and {file} is replaced
+ * by the artifact path. Shell syntax such as quotes or pipes is refused.
+ */
+export async function addExternalViewer(
+  name: string,
+  path: string,
+  argsTemplate = ''
+): Promise<ExternalViewer> {
+  return invoke<ExternalViewer>('add_external_viewer', { name, path, argsTemplate });
+}
+
+export async function updateExternalViewer(viewer: ExternalViewer): Promise<ExternalViewer> {
+  return invoke<ExternalViewer>('update_external_viewer', { ...viewer });
+}
+
+export async function removeExternalViewer(id: string): Promise<void> {
+  return invoke('remove_external_viewer', { id });
+}
+
+export async function openWithExternalViewer(viewerId: string, artifactPath: string): Promise<void> {
+  return invoke('open_with_external_viewer', { viewerId, artifactPath });
+}
+
+// ===== Undo =====
+
+export interface UndoEntry {
+  id: string;
+  description: BackendMessage;
+  created_at: number;
+  expires_at: number;
+}
+
+/**
+ * Operations of this session that can still be undone, newest first
+ */
+export async function getUndoStack(): Promise<UndoEntry[]> {
+  return invoke<UndoEntry[]>('get_undo_stack');
+}
+
+/**
+ * Reverse the newest undoable operation, e.g. from an "Undo" toast
+ */
+export async function undoLastOperation(): Promise<UndoEntry> {
+  return invoke<UndoEntry>('undo_last_operation');
+}
+
+// ===== Data Files =====
+
+/** A settings, history or queue file that no longer parsed and was set aside */
+export interface DataFileRecovery {
+  file: string;
+  moved_to: string | null;
+  /** What could not be read back, e.g. "2 job history records" */
+  lost: string;
+  error: string;
+}
+
+/**
+ * Data files found unreadable this session, including before the UI listened
+ */
+export async function getDataFileRecoveries(): Promise<DataFileRecovery[]> {
+  return invoke<DataFileRecovery[]>('get_data_file_recoveries');
+}
+
+export async function onDataFileRecovered(
+  handler: (recovery: DataFileRecovery) => void
+): Promise<UnlistenFn> {
+  return listen<DataFileRecovery>('data-file-recovered', (event) => handler(event.payload));
+}
+
+// ===== Backend Health =====
+
+export interface HealthProblem {
+  /** e.g. "app_data_unwritable", which relocateAppData repairs */
+  code: string;
+  /** The UI cannot work until it is dealt with */
+  blocking: boolean;
+  message: BackendMessage;
+}
+
+/** A graphics API the trainer can run on */
+export type GpuApi = 'vulkan' | 'metal' | 'dx12';
+
+/** The host checked against the support matrix at startup */
+export interface PlatformSupport {
+  platform: {
+    os: string;
+    /** null when it could not be read */
+    os_version: string | null;
+    arch: string;
+    gpu_apis: GpuApi[];
+  };
+  /** No finding stands in the way of processing */
+  supported: boolean;
+  /** e.g. code "os_too_old"; each is also a health problem with code unsupported_platform */
+  findings: { code: string; message: BackendMessage }[];
+}
+
+/** Where a candidate app data directory came from, in the order they are tried */
+export type DataDirSource = 'relocated' | 'standard' | 'environment' | 'portable' | 'inspection';
+
+/** The app was launched without being installed, e.g. from its DMG or a read-only AppImage */
+export interface NotInstalled {
+  reason: 'translocated' | 'read_only_volume';
+  /** The app bundle or AppImage that was launched */
+  location: string;
+}
+
+export interface InspectionStatus {
+  active: boolean;
+  not_installed: NotInstalled | null;
+  /** Features off for this launch */
+  disabled: ('queue_persistence' | 'history')[];
+}
+
+export interface BackendHealth {
+  app_version: string;
+  settings: { loaded: boolean; recovered: boolean };
+  /** null when no location for app data could be written to */
+  app_data: {
+    path: string;
+    overridden: boolean;
+    source: DataDirSource;
+    /** false in inspection mode, where app data is an overlay lost on quitting */
+    persistent: boolean;
+    /** null when the check timed out */
+    writable: boolean | null;
+    available_bytes: number | null;
+  } | null;
+  /** Locations passed over for app data at startup; path is empty when it could not be determined */
+  rejected_app_data: { source: DataDirSource; path: string; reason: string }[];
+  /** null when the check timed out */
+  cli: { resolved: boolean; path: string | null; error: string | null } | null;
+  platform: PlatformSupport | null;
+  recovered: DataFileRecovery[];
+  previous_session_crashed: boolean;
+  /** Started in safe mode, and some of what it left out is still off */
+  safe_mode: boolean;
+  /** Launched without being installed, and what that turns off */
+  inspection: InspectionStatus;
+  timed_out: string[];
+  /** Blocking problems first */
+  problems: HealthProblem[];
+  ok: boolean;
+}
+
+export interface RelocatedAppData {
+  path: string;
+  copied: number;
+  failed: number;
+}
+
+/**
+ * Check the backend before showing the UI; route to a recovery screen unless ok
+ */
+export async function getBackendHealth(frontendVersion?: string): Promise<BackendHealth> {
+  return invoke<BackendHealth>('get_backend_health', { frontendVersion });
+}
+
+/**
+ * Move app data to `path`, a folder picked with a native dialog such as
+ * pickOutputDirectory, or a suggested directory, and restart the app
+ */
+export async function relocateAppData(path?: string): Promise<RelocatedAppData> {
+  return invoke<RelocatedAppData>('relocate_app_data', { path });
+}
+
+/** What safe mode leaves out after launches in a row that failed to start */
+export type SafeModePiece = 'queue' | 'remote_requests' | 'share_server';
+
+export interface SafeModeStatus {
+  /** Some piece is still off */
+  active: boolean;
+  failed_launches: number;
+  skipped: SafeModePiece[];
+}
+
+export async function getSafeModeStatus(): Promise<SafeModeStatus> {
+  return invoke<SafeModeStatus>('get_safe_mode_status');
+}
+
+/** Turn one piece back on, or all of them when none is given */
+export async function exitSafeMode(piece?: SafeModePiece): Promise<SafeModeStatus> {
+  return invoke<SafeModeStatus>('exit_safe_mode', { piece: piece ?? null });
+}
+
+// ===== Actions =====
+
+export interface BackendAction {
+  id: string;
+  title: string;
+  category: string;
+  needs_production: boolean;
+  /** False when app state rules it out, e.g. Cancel Job with no job running */
+  enabled: boolean;
+}
+
+/**
+ * Actions for the command palette, built from the commands the backend registers
+ */
+export async function getAvailableActions(): Promise<BackendAction[]> {
+  return invoke<BackendAction[]>('get_available_actions');
+}
+
+/**
+ * Run an action with the arguments its command takes; errors are always { code, message }
+ */
+export async function dispatchAction<T = unknown>(id: string, payload?: Record<string, unknown>): Promise<T> {
+  return invoke<T>('dispatch_action', { id, payload });
+}
+
+// ===== Window Events =====
+
+/** Which job events a window gets; the main window gets all of them until it subscribes */
+export interface EventFilter {
+  all?: boolean;
+  job_ids?: string[];
+  /** Events of the jobs making these recent productions */
+  production_ids?: string[];
+}
+
+/**
+ * Receive only the job events the filter lets through in this window,
+ * replacing what it subscribed to before
+ */
+export async function subscribeEvents(windowLabel: string, filter: EventFilter): Promise<void> {
+  return invoke('subscribe_events', { windowLabel, filter });
+}
+
+export async function unsubscribeEvents(windowLabel: string): Promise<void> {
+  return invoke('unsubscribe_events', { windowLabel });
+}
+
+export interface JobStarted {
+  job_id: string;
+  production_id?: string;
+}
+
+/** Sent to every window, whatever it subscribed to */
+export async function onJobStarted(
+  handler: (started: JobStarted) => void
+): Promise<UnlistenFn> {
+  return listen<JobStarted>('job-started', (event) => handler(event.payload));
+}
+
+export interface JobFinished {
+  job_id?: string;
+  failed: boolean;
+  /** The user cancelled it */
+  cancelled: boolean;
+}
+
+/** Sent to every window, whatever it subscribed to */
+export async function onJobFinished(
+  handler: (finished: JobFinished) => void
+): Promise<UnlistenFn> {
+  return listen<JobFinished>('job-finished', (event) => handler(event.payload));
+}
+
+// ===== Notifications =====
+
+/** A job notification held while do-not-disturb was on */
+export interface PendingNotification {
+  id: string;
+  kind: 'completed' | 'failed';
+  message: BackendMessage;
+  job_id: string | null;
+  /** Seconds since the Unix epoch */
+  held_at: number;
+  /** When it is dropped unless shown before */
+  expires_at: number;
+}
+
+/** Notifications held while do-not-disturb was on, for an in-app inbox */
+export async function getPendingNotifications(): Promise<PendingNotification[]> {
+  return invoke<PendingNotification[]>('get_pending_notifications');
+}
+
+/**
+ * The held notifications, handed over instead of shown by the OS when a
+ * window gains focus before do-not-disturb lifts
+ */
+export async function onNotificationsFlushed(
+  handler: (notifications: PendingNotification[]) => void
+): Promise<UnlistenFn> {
+  return listen<PendingNotification[]>('notifications-flushed', (event) =>
+    handler(event.payload)
+  );
+}
+
+/**
+ * Sent once per launch, when the page has loaded, if the app runs without
+ * being installed; the cue to walk the user through installing it
+ */
+export async function onAppNotInstalled(
+  handler: (notInstalled: NotInstalled) => void
+): Promise<UnlistenFn> {
+  return listen<NotInstalled>('app-not-installed', (event) => handler(event.payload));
+}
+
+// ===== Network =====
+
+export interface ConnectionTest {
+  url: string;
+  status: number;
+  latency_ms: number;
+  proxied: boolean;
+}
+
+/**
+ * Request the configured probe URL through the network settings
+ */
+export async function testNetworkConnection(): Promise<ConnectionTest> {
+  return invoke<ConnectionTest>('test_network_connection');
+}
+
 // ===== Video Metadata =====
 
 export interface VideoMetadata {
@@ -227,8 +2241,65 @@ export async function getVideoMetadata(path: string): Promise<VideoMetadata> {
   };
 }
 
+// ===== Media Prefetch =====
+
+export interface PrefetchResult {
+  path: string;
+  /** ffprobe metadata as returned by the backend (duration_secs, frame_rate, ...) */
+  metadata: Record<string, unknown> | null;
+  thumbnail_path: string | null;
+  error: string | null;
+}
+
+/**
+ * Probe and thumbnail dropped clips in the background. Cached results are returned
+ * at once; the others arrive through onMediaPrefetched.
+ */
+export async function queueMediaPrefetch(paths: string[]): Promise<PrefetchResult[]> {
+  return invoke<PrefetchResult[]>('queue_media_prefetch', { paths });
+}
+
+export async function cancelMediaPrefetch(): Promise<void> {
+  return invoke('cancel_media_prefetch');
+}
+
+export async function onMediaPrefetched(
+  handler: (result: PrefetchResult) => void
+): Promise<UnlistenFn> {
+  return listen<PrefetchResult>('media-prefetched', (event) => handler(event.payload));
+}
+
 // ===== Project Files =====
 
+/** FileFormat::Project's schema version in the backend; raised with it */
+export const PROJECT_SCHEMA_VERSION = 1;
+
+export type FileFormat = 'settings' | 'history' | 'queue' | 'sidecar' | 'project';
+
+export type VersionRelation = 'same' | 'older' | 'newer' | 'unrecorded';
+
+export interface FileCompatibility {
+  path: string;
+  format: FileFormat;
+  /** Null for files of versions from before the header */
+  written_by: WrittenBy | null;
+  /** The header this version would write */
+  current: WrittenBy;
+  relation: VersionRelation;
+  /** Its schema is one this version reads */
+  readable: boolean;
+  /** What to tell the user, for files of a newer version */
+  message: BackendMessage | null;
+}
+
+/**
+ * Which version wrote a settings file, history, queue, sidecar or project, so a
+ * file of a newer version can be explained before it fails to open
+ */
+export async function getFileCompatibility(path: string): Promise<FileCompatibility> {
+  return invoke<FileCompatibility>('get_file_compatibility', { path });
+}
+
 /**
  * Open a project file dialog
  */
@@ -269,6 +2340,13 @@ export async function pickProjectSaveLocation(defaultName?: string): Promise<str
  * Read a project file
  */
 export async function readProjectFile(path: string): Promise<GVProject> {
+  const compatibility = await getFileCompatibility(path);
+  if (!compatibility.readable) {
+    throw new Error(
+      `This project was created by a newer version of Game View (${compatibility.written_by?.app_version}); update Game View to open it`
+    );
+  }
+
   const content = await readTextFile(path);
   const project = JSON.parse(content) as GVProject;
 
@@ -287,7 +2365,12 @@ export async function readProjectFile(path: string): Promise<GVProject> {
  * Write a project file
  */
 export async function writeProjectFile(path: string, project: GVProject): Promise<void> {
-  const content = JSON.stringify(project, null, 2);
+  const written_by: WrittenBy = {
+    app_version: await getVersion(),
+    schema_version: PROJECT_SCHEMA_VERSION,
+    frontend_version: __FRONTEND_VERSION__,
+  };
+  const content = JSON.stringify({ ...project, written_by }, null, 2);
   await writeTextFile(path, content);
 }
 
@@ -49,7 +49,7 @@ interface AppState {
 }
 
 const defaultSettings: AppSettings = {
-  theme: 'dark',
+  appearance: { theme: 'dark' },
   defaultOutputDir: '',
   defaultPreset: 'balanced',
   recentProductions: [],
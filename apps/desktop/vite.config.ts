@@ -5,6 +5,10 @@ import path from 'path';
 // https://vitejs.dev/config/
 export default defineConfig({
   plugins: [react()],
+  define: {
+    // Stamped into the files the frontend writes, next to the app's version
+    __FRONTEND_VERSION__: JSON.stringify(process.env.npm_package_version ?? ''),
+  },
   resolve: {
     alias: {
       '@': path.resolve(__dirname, './src'),
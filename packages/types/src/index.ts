@@ -22,6 +22,10 @@ export interface ProcessingProgress {
   message?: string;
   currentStep?: number;
   totalSteps?: number;
+  /** Clip being extracted or matched, when the stage goes clip by clip */
+  currentItem?: string;
+  /** Estimated from the growth of the artifact while the CLI exports silently; stays below 100 */
+  synthetic?: boolean;
 }
 
 export interface ProcessingOptions {
@@ -159,9 +163,18 @@ export type ProductionStatus = 'draft' | 'processing' | 'completed' | 'failed';
 
 // ===== Project File Types (.gvproj) =====
 
+/** What wrote a file the app owns; absent in files of versions from before it */
+export interface WrittenBy {
+  app_version: string;
+  schema_version: number;
+  /** Set in files the frontend writes itself, such as projects */
+  frontend_version?: string;
+}
+
 export interface GVProject {
   version: '1.0';
   type: 'gameview-project';
+  written_by?: WrittenBy;
   metadata: {
     name: string;
     description?: string;
@@ -209,21 +222,205 @@ export interface GVVSFile {
 
 // ===== Settings Types =====
 
+export type Theme = 'light' | 'dark' | 'system';
+
+export interface AppearanceSettings {
+  theme: Theme;
+  /** "#rrggbb"; the app's own accent when unset */
+  accentColor?: string | null;
+  /** Fewer animations and transitions */
+  reduceMotion?: boolean;
+}
+
 export interface AppSettings {
-  theme: 'light' | 'dark' | 'system';
+  /** Settings saved by older versions load with their theme here */
+  appearance: AppearanceSettings;
   defaultOutputDir: string;
   defaultPreset: QualityPreset;
   colmapPath?: string;
   brushPath?: string;
   recentProductions: RecentProduction[];
+  /** Check artifact checksums when a recent production is opened */
+  verifyArtifactsOnOpen?: boolean;
+  externalViewers?: ExternalViewer[];
+  /** Artifact name from {production}, {preset}, {date}, {time} and {version} (UTC date and time) */
+  outputNameTemplate?: string;
+  /** Clips probed and thumbnailed at once in the background (1-8, default 3) */
+  prefetchConcurrency?: number;
+  /** Flags replacing a built-in capture profile's */
+  captureProfiles?: { captureType: CaptureType; flags: string[] }[];
+  network?: NetworkSettings;
+  /** Where jobs keep extracted frames; the system temp directory when unset */
+  scratchDir?: string;
+  /** Seconds a destructive operation stays undoable (default 600) */
+  undoWindowSecs?: number;
+  /** A user's own ffmpeg build, preferred over the bundled one; ffprobe must be beside it */
+  ffmpegPath?: string;
+  /**
+   * Output directories holding or inside an input or the app's data: 'reject'
+   * (default) fails with unsafe_output_location, 'warn' only warns
+   */
+  unsafeOutputLocations?: 'reject' | 'warn';
+  /** Training options each preset starts from, by preset name */
+  presetTraining?: Record<string, TrainingOptions>;
+  /**
+   * GB of VRAM each preset needs, by preset name, in place of the built-in
+   * minimum; jobs on GPUs with less fail with insufficient_vram unless overridden
+   */
+  presetMinVramGb?: Record<string, number>;
+  /**
+   * Share of a job's images (0-100, default 30) that must register for it to
+   * train; fewer fail it with aborted_low_registration
+   */
+  minRegisteredPercent?: number;
+  /** Intrinsics of devices missing from the built-in table, or correcting it */
+  knownDevices?: DeviceIntrinsics[];
+  /** Intermediate splats exported while a job trains, where the CLI supports it */
+  checkpoints?: {
+    enabled: boolean;
+    intervalMinutes: number;
+    /** Older checkpoints beyond this many are deleted as new ones arrive */
+    keep: number;
+    deleteOnSuccess: boolean;
+  };
+  /** gvcore-cli to run instead of the bundled one for this machine */
+  gvcoreCliPath?: string;
+  /** Bytes app_data/cache is kept to (default 1 GiB, at least 64 MiB) */
+  cacheMaxBytes?: number;
+  /** Suspend a job at the start of training while other programs hold the VRAM its preset needs */
+  waitForVram?: boolean;
+  /** Seconds a job waits for VRAM before training anyway (default 600) */
+  vramWaitSecs?: number;
+  /** Running a job again by itself when its CLI exits with a transient failure */
+  autoRetry?: {
+    /** Runs after the first; 0 turns retrying off (default 2, at most 10) */
+    maxRetries?: number;
+    /** Wait before the first retry, doubled for each later one (default 30) */
+    backoffSecs?: number;
+    /** Exit codes of the CLI that mean a transient failure (default none) */
+    retryableCodes?: number[];
+  };
+  /** Pool sizes of the backend's own heavy work; derived from the cores when unset */
+  workers?: {
+    io?: number;
+    cpuHeavy?: number;
+    background?: number;
+    /** How much lower the cpuHeavy and background pools run than the UI (0-19, default 10) */
+    niceness: number;
+  };
+  /** Programs run, in order, once a job is over */
+  postRunHooks?: PostRunHook[];
+  /** Whether postRunHooks run at all (default off) */
+  postRunHooksEnabled?: boolean;
+  retention?: RetentionSettings;
+  notifications?: NotificationSettings;
+}
+
+/** OS notifications of finished and failed jobs */
+export interface NotificationSettings {
+  /** Default on */
+  enabled: boolean;
+  /** Hold notifications while do-not-disturb is on, for the in-app inbox (default on) */
+  respectDoNotDisturb: boolean;
+  /** Held notifications older than this are dropped; at least 1 (default 24) */
+  expiryHours: number;
+}
+
+export type RetentionAction = 'archive' | 'trash';
+
+/**
+ * Intermediates of recent productions not used within keepDays and not among
+ * the keepLast most recently used are archived or trashed, once a day
+ */
+export interface RetentionSettings {
+  /** Off by default */
+  enabled: boolean;
+  /** At least 1 (default 30) */
+  keepDays: number;
+  /** Default 10 */
+  keepLast: number;
+  action: RetentionAction;
+}
+
+/**
+ * A device whose clips give COLMAP prior intrinsics, matched by the clip's
+ * make, model and lens tags
+ */
+export interface DeviceIntrinsics {
+  make: string;
+  model: string;
+  /** Part of the clip's lens name, e.g. "2.22mm"; any lens of the model when absent */
+  lens?: string;
+  /** Focal length in video as its 35mm equivalent (4-1200) */
+  focalLength35mm: number;
+  /** Principal point as fractions (0-1) of width and height; the centre when absent */
+  principalPoint?: [number, number];
+}
+
+/**
+ * A program run without a shell once a job ends. The arguments may use
+ * {artifact}, {output_dir}, {job_id} and {status}.
+ */
+export interface PostRunHook {
+  /** Unique among the hooks */
+  name: string;
+  /** Absolute path of the program */
+  program: string;
+  argsTemplate: string;
+  on: 'success' | 'failure' | 'always';
+  /** Seconds before the hook is stopped (1-86400, default 600) */
+  timeoutSecs: number;
+}
+
+/**
+ * Brush hyperparameters; unset ones keep the preset's value. Options the
+ * installed CLI has no flag for are left out with an
+ * 'unsupported-training-option' warning.
+ */
+export interface TrainingOptions {
+  /** Training steps (1-1000000) */
+  iterations?: number;
+  /** Spherical harmonics degree (0-3) */
+  sh_degree?: number;
+  /** Steps between densification passes (1-1000000) */
+  densify_interval?: number;
+  /** Gradient above which a splat is densified (above 0, at most 1) */
+  densify_grad_threshold?: number;
+  /** Steps between opacity resets (1-1000000) */
+  opacity_reset_interval?: number;
+}
+
+export interface NetworkSettings {
+  /** http:// or https:// proxy; the password is the 'network.proxy_password' secret */
+  proxyUrl?: string;
+  proxyUsername?: string;
+  noProxy: string[];
+  /** Network commands fail at once with the offline_mode error code */
+  offlineMode: boolean;
+  probeUrl: string;
+}
+
+export type CaptureType = 'generic' | 'drone' | 'turntable' | 'walkthrough';
+
+export interface ExternalViewer {
+  id: string;
+  name: string;
+  path: string;
+  argsTemplate: string;
 }
 
 export interface RecentProduction {
   id: string;
   name: string;
   path: string;
-  lastOpened: string; // ISO date string
+  lastOpened: string; // ISO date string; empty for imported productions not opened yet
   thumbnail?: string;
+  /** Found by a library scan rather than opened in the app */
+  imported?: boolean;
+  /** The directory was gone when the library was last reconciled */
+  missing?: boolean;
+  /** Kept out of the retention policy */
+  pinned?: boolean;
 }
 
 // ===== API Types =====